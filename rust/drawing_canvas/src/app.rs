@@ -4,9 +4,15 @@
 //! It's designed to be independent of the windowing system, making it easier
 //! to port to different platforms (native, web, Flutter).
 
-use crate::brush::BrushState;
-use crate::input::{InputQueue, PointerEvent};
-use crate::renderer::Renderer;
+use crate::brush::{BrushBlendMode, BrushDab, BrushState, OpacityMode, Symmetry, SymmetryMode, Tool};
+use crate::config::AppConfig;
+use crate::fill::Fill;
+use crate::history::{HistoryStroke, StrokeHistory};
+use crate::input::{BarrelButtonAction, InputPredictor, InputQueue, PointerEvent, StrokePoint, StrokeRecorder};
+use crate::recording::{InputRecorder, InputRecording};
+use crate::renderer::{CursorRing, Renderer};
+use crate::session::PoseSession;
+use std::collections::VecDeque;
 
 /// Main application state
 pub struct App {
@@ -14,8 +20,54 @@ pub struct App {
     clear_color: [f64; 4],
     /// Input event queue
     input_queue: InputQueue,
+    /// Extrapolates the stroke ahead of the pen to hide latency (off unless set)
+    input_predictor: InputPredictor,
     /// Brush state
     brush_state: BrushState,
+    /// Rotation snap increment in degrees (0.0 = no snapping)
+    rotation_snap_degrees: f32,
+    /// What the stylus barrel button does
+    barrel_button_action: BarrelButtonAction,
+    /// Mirror or radial copies drawn with every dab
+    symmetry: Symmetry,
+    /// Whether strokes paint or smudge
+    tool: Tool,
+    /// Whether the eraser is temporarily active (e.g. barrel button held)
+    momentary_erase: bool,
+    /// Whether pointer input picks colors instead of drawing
+    eyedropper_active: bool,
+    /// Whether the pointer is pressed with the eyedropper (a pick drag is in progress)
+    eyedropper_pressed: bool,
+    /// Canvas position the eyedropper was last dragged to, waiting to be sampled
+    eyedropper_request: Option<[f32; 2]>,
+    /// Whether rendering is paused (e.g. canvas offscreen or tab hidden)
+    rendering_paused: bool,
+    /// Whether new strokes are dropped while paused (otherwise queued until resume)
+    drop_input_while_paused: bool,
+    /// Raw point stream of recent strokes (for gesture analysis)
+    stroke_recorder: StrokeRecorder,
+    /// Whether the dabs of the last frame are kept for inspection (debugging and tests)
+    retain_frame_dabs: bool,
+    /// Dabs rendered by the last frame that processed input (empty unless retained)
+    last_frame_dabs: Vec<BrushDab>,
+    /// Synthetic input waiting to be released into the input queue as it comes due
+    synthetic_events: VecDeque<PointerEvent>,
+    /// Session recording of every queued pointer event (for lesson replays)
+    input_recorder: InputRecorder,
+    /// Completed strokes that can be undone and redone
+    history: StrokeHistory,
+    /// Stroke being drawn, recorded for the undo history when it ends
+    history_stroke: Option<HistoryStroke>,
+    /// Whether an undo was requested (by the barrel button) for the next frame
+    undo_requested: bool,
+    /// Timed gesture-drawing session in progress, if any
+    session: Option<PoseSession>,
+    /// Whether a brush outline follows the pointer while hovering
+    cursor_preview: bool,
+    /// Canvas position of the hovering pointer (None while it is off the canvas surface)
+    hover_position: Option<[f32; 2]>,
+    /// Whether a line from the pen to the stabilized ink point is shown while drawing
+    stabilizer_preview: bool,
 }
 
 impl App {
@@ -24,7 +76,30 @@ impl App {
         Self {
             clear_color: [0.0, 0.0, 0.0, 0.0],
             input_queue: InputQueue::new(),
+            input_predictor: InputPredictor::new(),
             brush_state: BrushState::new(),
+            rotation_snap_degrees: 0.0,
+            barrel_button_action: BarrelButtonAction::default(),
+            symmetry: Symmetry::NONE,
+            tool: Tool::Brush,
+            momentary_erase: false,
+            eyedropper_active: false,
+            eyedropper_pressed: false,
+            eyedropper_request: None,
+            rendering_paused: false,
+            drop_input_while_paused: false,
+            stroke_recorder: StrokeRecorder::new(),
+            retain_frame_dabs: false,
+            last_frame_dabs: Vec::new(),
+            synthetic_events: VecDeque::new(),
+            input_recorder: InputRecorder::new(),
+            history: StrokeHistory::new(),
+            history_stroke: None,
+            undo_requested: false,
+            session: None,
+            cursor_preview: false,
+            hover_position: None,
+            stabilizer_preview: false,
         }
     }
 
@@ -33,7 +108,30 @@ impl App {
         Self {
             clear_color: [0.0, 0.0, 0.0, 0.0],
             input_queue: InputQueue::new(),
+            input_predictor: InputPredictor::new(),
             brush_state: BrushState::with_params(params),
+            rotation_snap_degrees: 0.0,
+            barrel_button_action: BarrelButtonAction::default(),
+            symmetry: Symmetry::NONE,
+            tool: Tool::Brush,
+            momentary_erase: false,
+            eyedropper_active: false,
+            eyedropper_pressed: false,
+            eyedropper_request: None,
+            rendering_paused: false,
+            drop_input_while_paused: false,
+            stroke_recorder: StrokeRecorder::new(),
+            retain_frame_dabs: false,
+            last_frame_dabs: Vec::new(),
+            synthetic_events: VecDeque::new(),
+            input_recorder: InputRecorder::new(),
+            history: StrokeHistory::new(),
+            history_stroke: None,
+            undo_requested: false,
+            session: None,
+            cursor_preview: false,
+            hover_position: None,
+            stabilizer_preview: false,
         }
     }
 
@@ -43,14 +141,26 @@ impl App {
     }
 
     /// Render the application (called each frame)
+    ///
+    /// All of the frame's brush work (dabs, stroke layer changes, the prediction) is batched
+    /// by the renderer and submitted together with the blit.
     pub fn render(&mut self, renderer: &mut Renderer) {
-        // Process input events and generate brush dabs
-        let dabs = self.process_input_events();
-        
-        // Render dabs to canvas if any
-        if !dabs.is_empty() {
-            renderer.render_dabs(&dabs);
+        // No GPU work at all while paused; queued input is processed on resume
+        if self.rendering_paused {
+            return;
         }
+
+        // Move the session on first, so a pose's auto-clear lands before this frame's input
+        self.advance_session(renderer);
+
+        // Process input events and render brush dabs
+        self.release_synthetic_events(crate::time::now_ms());
+        self.process_input_events(renderer);
+        if std::mem::take(&mut self.undo_requested) {
+            self.undo(renderer);
+        }
+        renderer.set_cursor_ring(self.cursor_ring());
+        renderer.set_stabilizer_tether(self.stabilizer_tether());
         
         // Copy canvas to surface
         renderer.render();
@@ -59,11 +169,202 @@ impl App {
     /// Clear the canvas
     pub fn clear_canvas(&mut self, renderer: &mut Renderer) {
         renderer.clear_canvas(&self.clear_color);
+        // Retained strokes and undo history describe the cleared pixels, so they go with them
+        self.stroke_recorder.clear_retained();
+        self.discard_history();
+    }
+
+    /// Bucket fill the region around a canvas position with the brush color
+    ///
+    /// The region is every pixel of the active layer 4-connected to the one at (`x`, `y`)
+    /// whose channels are all within `tolerance` (0.0-1.0) of it. It is filled with the
+    /// brush color, blended over the existing paint in the blend color space, so a
+    /// translucent color tints the region. Fills are undone and redone like strokes.
+    ///
+    /// The layer is read back to find the region, so this waits on the GPU (see
+    /// `crate::fill` for why the fill runs on the CPU).
+    ///
+    /// # Returns
+    /// Whether anything was filled (false outside the canvas); an error if a stroke is in
+    /// progress or the readback fails
+    pub async fn fill_at(&mut self, x: f32, y: f32, tolerance: f32, renderer: &mut Renderer) -> Result<bool, String> {
+        if self.history_stroke.is_some() {
+            return Err("Can't fill while a stroke is in progress".to_string());
+        }
+        renderer.commit_stroke_layer();
+        let (pixels, size) = renderer.read_active_layer_rgba8().await?;
+        self.fill_from_pixels(x, y, tolerance, &pixels, size, renderer)
+    }
+
+    /// Bucket fill using a readback of the active layer from `Renderer::read_active_layer_rgba8`
+    ///
+    /// The second half of `fill_at`, for callers that can't hold the app across the readback.
+    /// The readback must be of the current canvas, with nothing drawn since.
+    pub fn fill_from_pixels(
+        &mut self,
+        x: f32,
+        y: f32,
+        tolerance: f32,
+        pixels: &[u8],
+        size: [u32; 2],
+        renderer: &mut Renderer,
+    ) -> Result<bool, String> {
+        if self.history_stroke.is_some() {
+            return Err("Can't fill while a stroke is in progress".to_string());
+        }
+        if !(x >= 0.0 && y >= 0.0 && x < size[0] as f32 && y < size[1] as f32) {
+            return Ok(false);
+        }
+        let tolerance = if tolerance.is_finite() { tolerance.clamp(0.0, 1.0) } else { 0.0 };
+        let spans = crate::fill::flood_fill(pixels, size, [x as u32, y as u32], tolerance);
+        if spans.is_empty() {
+            return Ok(false);
+        }
+
+        let fill = Fill { spans, color: self.brush_state.params.color };
+        if self.history.limit() > 0 {
+            self.ensure_undo_base(renderer);
+        }
+        renderer.fill(&fill);
+        log::info!("Filled {} pixels at ({}, {})", fill.pixel_count(), x, y);
+        if self.history.limit() > 0 {
+            let evicted = self.history.push(HistoryStroke {
+                layer: renderer.active_layer(),
+                dabs: Vec::new(),
+                erase: false,
+                blend_mode: BrushBlendMode::Normal,
+                max_opacity: 1.0,
+                stroke_opacity: 1.0,
+                per_stroke: false,
+                smudge: None,
+                smudge_length: 0.0,
+                fill: Some(fill),
+            });
+            Self::bake_into_undo_base(renderer, &evicted);
+        }
+        Ok(true)
+    }
+
+    /// Undo the last completed stroke, on whichever layer it was drawn
+    ///
+    /// That layer is restored to its undo base and its remaining strokes are drawn again.
+    /// Returns false if there is nothing to undo or a stroke is in progress.
+    pub fn undo(&mut self, renderer: &mut Renderer) -> bool {
+        if !self.history_ready(renderer) {
+            return false;
+        }
+        let Some(layer) = self.history.undo().map(|stroke| stroke.layer) else {
+            return false;
+        };
+        self.recomposite_history(layer, renderer);
+        log::debug!("Undid stroke on layer {}", layer);
+        true
+    }
+
+    /// Redo the last undone stroke, on whichever layer it was drawn
+    /// Returns false if there is nothing to redo or a stroke is in progress.
+    pub fn redo(&mut self, renderer: &mut Renderer) -> bool {
+        if !self.history_ready(renderer) {
+            return false;
+        }
+        let Some(layer) = self.history.redo().map(|stroke| stroke.layer) else {
+            return false;
+        };
+        self.recomposite_history(layer, renderer);
+        log::debug!("Redid stroke on layer {}", layer);
+        true
+    }
+
+    /// Check whether there is a stroke to undo
+    pub fn can_undo(&self) -> bool {
+        self.history.can_undo()
+    }
+
+    /// Check whether there is a stroke to redo
+    pub fn can_redo(&self) -> bool {
+        self.history.can_redo()
+    }
+
+    /// Set how many strokes can be undone (default 50, 0 = undo off)
+    ///
+    /// Strokes that no longer fit are baked into their layer's undo base and can't be
+    /// undone. Each kept stroke costs its dabs in memory, and the renderer holds one
+    /// canvas-sized snapshot for each layer drawn on while undo is on.
+    pub fn set_undo_limit(&mut self, limit: usize, renderer: &mut Renderer) {
+        let evicted = self.history.set_limit(limit);
+        if limit == 0 {
+            self.history.clear();
+            renderer.discard_undo_bases();
+        } else {
+            Self::bake_into_undo_base(renderer, &evicted);
+        }
+    }
+
+    /// Get how many strokes can be undone (0 = undo off)
+    pub fn undo_limit(&self) -> usize {
+        self.history.limit()
+    }
+
+    /// Check whether nothing has been drawn since the canvas was last cleared
+    pub fn is_canvas_empty(&self, renderer: &Renderer) -> bool {
+        renderer.is_canvas_empty()
+    }
+
+    /// Add a transparent layer on top and return its index (see `Renderer::add_layer`)
+    pub fn add_layer(&mut self, renderer: &mut Renderer) -> usize {
+        renderer.add_layer()
+    }
+
+    /// Remove a layer (see `Renderer::remove_layer`)
+    /// Strokes drawn on the layer leave the undo history; those on other layers stay.
+    pub fn remove_layer(&mut self, index: usize, renderer: &mut Renderer) -> Result<(), String> {
+        renderer.remove_layer(index)?;
+        self.history.remove_layer(index);
+        let Some(stroke) = &mut self.history_stroke else {
+            return Ok(());
+        };
+        if stroke.layer == index {
+            // The stroke in progress went with its layer and carries on in the new active one
+            self.history_stroke = None;
+            self.begin_history_stroke(renderer);
+        } else if stroke.layer > index {
+            stroke.layer -= 1;
+        }
+        Ok(())
+    }
+
+    /// Make a layer the one strokes are drawn into
+    /// The undo history is kept; undo still steps back through strokes on every layer.
+    pub fn set_active_layer(&mut self, index: usize, renderer: &mut Renderer) -> Result<(), String> {
+        if index == renderer.active_layer() {
+            return Ok(());
+        }
+        renderer.set_active_layer(index)?;
+        // A stroke in progress carries on in the new layer, so it is recorded as two
+        if self.history_stroke.is_some() {
+            self.end_history_stroke(renderer);
+            self.begin_history_stroke(renderer);
+        }
+        Ok(())
+    }
+
+    /// Show or hide a layer
+    pub fn set_layer_visible(&mut self, index: usize, visible: bool, renderer: &mut Renderer) -> Result<(), String> {
+        renderer.set_layer_visible(index, visible)
     }
 
-    /// Set the clear color
+    /// Set the opacity a layer is composited at (0-1)
+    pub fn set_layer_opacity(&mut self, index: usize, opacity: f32, renderer: &mut Renderer) -> Result<(), String> {
+        renderer.set_layer_opacity(index, opacity)
+    }
+
+    /// Set the color the canvas is cleared to (sRGB RGBA with straight alpha, clamped to 0.0-1.0)
+    ///
+    /// Takes effect at the next `clear_canvas`. The clear color is part of the canvas, so
+    /// it is exported; clear to alpha 0 and use `Renderer::set_paper_color` for a paper
+    /// look on screen that exports transparent.
     pub fn set_clear_color(&mut self, r: f64, g: f64, b: f64, a: f64) {
-        self.clear_color = [r, g, b, a];
+        self.clear_color = [r, g, b, a].map(|c| c.clamp(0.0, 1.0));
     }
 
     /// Get the current clear color
@@ -71,16 +372,428 @@ impl App {
         self.clear_color
     }
 
+    /// Set what the stylus barrel button does
+    pub fn set_barrel_button_action(&mut self, action: BarrelButtonAction) {
+        self.barrel_button_action = action;
+        if action != BarrelButtonAction::Erase {
+            self.momentary_erase = false;
+        }
+    }
+
+    /// Get what the stylus barrel button does
+    pub fn barrel_button_action(&self) -> BarrelButtonAction {
+        self.barrel_button_action
+    }
+
+    /// Draw mirrored or radially repeated copies of every dab
+    ///
+    /// `axis_or_center` is in canvas pixels: the point the mirror line passes through
+    /// (Vertical uses its x, Horizontal its y) or the radial center. Input reaches the brush
+    /// in canvas space, so the axis stays fixed to the drawing as the view pans, zooms, and
+    /// rotates. Copies are drawn in the same batch as the original dabs and undo with them.
+    pub fn set_symmetry(&mut self, mode: SymmetryMode, axis_or_center: [f32; 2]) -> Result<(), String> {
+        let symmetry = Symmetry { mode, center: axis_or_center };
+        symmetry.validate()?;
+        self.symmetry = symmetry;
+        log::info!("Symmetry set to {:?} at {:?}", mode, axis_or_center);
+        Ok(())
+    }
+
+    /// Get the current symmetry
+    pub fn symmetry(&self) -> Symmetry {
+        self.symmetry
+    }
+
+    /// Choose what strokes do: paint with the brush, or smudge existing paint
+    ///
+    /// Takes effect from the next batch of dabs; a stroke in progress switches mid-way.
+    pub fn set_tool(&mut self, tool: Tool) -> Result<(), String> {
+        tool.validate()?;
+        self.tool = tool;
+        log::info!("Tool set to {:?}", tool);
+        Ok(())
+    }
+
+    /// Get the current tool
+    pub fn tool(&self) -> Tool {
+        self.tool
+    }
+
+    /// Handle the stylus barrel button being pressed or released
+    pub fn handle_barrel_button(&mut self, pressed: bool) {
+        match self.barrel_button_action {
+            BarrelButtonAction::Erase => {
+                self.momentary_erase = pressed;
+                log::debug!("Momentary erase {}", if pressed { "on" } else { "off" });
+            }
+            BarrelButtonAction::None => {}
+            BarrelButtonAction::Undo => {
+                if pressed {
+                    // Undo needs the renderer; it runs at the end of the next frame's input
+                    self.undo_requested = true;
+                }
+            }
+        }
+    }
+
+    /// Check whether the eraser is temporarily active
+    pub fn is_momentary_erase(&self) -> bool {
+        self.momentary_erase
+    }
+
+    /// Check whether dabs erase, either from the brush's eraser mode or the momentary eraser
+    pub fn is_erasing(&self) -> bool {
+        self.momentary_erase || self.brush_state.params.eraser
+    }
+
     /// Queue an input event for processing
     pub fn queue_input_event(&mut self, event: PointerEvent) {
+        // Up events always pass so a stroke started before pausing still ends
+        if self.rendering_paused
+            && self.drop_input_while_paused
+            && event.event_type != crate::input::PointerEventType::Up
+        {
+            return;
+        }
+        if self.eyedropper_active && self.eyedropper_event(&event) {
+            return;
+        }
+        self.input_recorder.record(&event);
         self.input_queue.push_event(event);
     }
 
+    /// Switch pointer input between drawing and picking colors with the eyedropper
+    ///
+    /// While the eyedropper is active, pressing or dragging the pointer draws nothing and
+    /// queues its position to be sampled (see `take_eyedropper_request`). A stroke already
+    /// in progress still ends when the pointer lifts.
+    pub fn set_eyedropper_active(&mut self, active: bool) {
+        self.eyedropper_active = active;
+        self.eyedropper_pressed = false;
+        self.eyedropper_request = None;
+        log::info!("Eyedropper {}", if active { "active" } else { "inactive" });
+    }
+
+    /// Check whether pointer input picks colors instead of drawing
+    pub fn is_eyedropper_active(&self) -> bool {
+        self.eyedropper_active
+    }
+
+    /// Take the canvas position the eyedropper is waiting to sample, if any
+    ///
+    /// Only the latest position of a drag is kept. The platform layer samples it with
+    /// `pick_color` after each frame, since sampling waits on the GPU.
+    pub fn take_eyedropper_request(&mut self) -> Option<[f32; 2]> {
+        self.eyedropper_request.take()
+    }
+
+    /// Sample the canvas color at a canvas position and make it the brush color
+    ///
+    /// The color is read from the active layer (see `Renderer::sample_pixel`). The brush
+    /// takes its RGB and keeps its own alpha; picking a fully transparent pixel leaves the
+    /// brush color unchanged.
+    ///
+    /// # Returns
+    /// The sampled color as straight sRGB RGBA (0.0-1.0)
+    pub async fn pick_color(&mut self, x: f32, y: f32, renderer: &mut Renderer) -> Result<[f32; 4], String> {
+        renderer.commit_stroke_layer();
+        let color = renderer.sample_pixel(x, y).await?;
+        self.apply_picked_color(color);
+        Ok(color)
+    }
+
+    /// Make a color sampled with `Renderer::sample_pixel` the brush color (see `pick_color`)
+    pub fn apply_picked_color(&mut self, color: [f32; 4]) {
+        if color[3] <= 0.0 {
+            return;
+        }
+        let brush_color = &mut self.brush_state.params.color;
+        brush_color[..3].copy_from_slice(&color[..3]);
+        log::info!("Picked brush color: {:?}", brush_color);
+    }
+
+    /// Route a pointer event to the eyedropper; returns whether it was consumed
+    fn eyedropper_event(&mut self, event: &PointerEvent) -> bool {
+        match event.event_type {
+            crate::input::PointerEventType::Down => self.eyedropper_pressed = true,
+            crate::input::PointerEventType::Move if self.eyedropper_pressed => {}
+            // The rest of a stroke started before the eyedropper was picked up: the moves are
+            // dropped and the Up ends the stroke
+            crate::input::PointerEventType::Move => return true,
+            crate::input::PointerEventType::Up if !self.eyedropper_pressed => return false,
+            crate::input::PointerEventType::Up => self.eyedropper_pressed = false,
+        }
+        self.eyedropper_request = Some(event.position);
+        true
+    }
+
+    /// Draw a stroke along a path as if the pointer moved along it
+    ///
+    /// For assistive input and scripted demos. Timed pointer events are generated along
+    /// `path` (canvas pixels) at `speed` (canvas pixels per second) with constant `pressure`,
+    /// and played back in real time through the normal input queue and dab generation.
+    /// Strokes injected while one is still playing start after it finishes.
+    pub fn inject_synthetic_stroke(&mut self, path: &[[f32; 2]], pressure: f32, speed: f32) -> Result<(), String> {
+        let now = crate::time::now_ms();
+        let start = self.synthetic_events.back().map_or(now, |last| last.timestamp.max(now));
+        let events = crate::input::synthetic_stroke_events(path, pressure, speed, start)?;
+        log::info!("Injected synthetic stroke: {} points, {} events", path.len(), events.len());
+        self.synthetic_events.extend(events);
+        Ok(())
+    }
+
+    /// Start a timed gesture-drawing session, replacing any session in progress
+    ///
+    /// The poses run back to back from now. Each time one ends, the next starts (clearing
+    /// the canvas first if `auto_clear` is set) and `onPoseAdvance(index)` is called; after
+    /// the last, it is called with the pose count and the session ends.
+    ///
+    /// # Arguments
+    /// * `durations_secs` - Duration of each pose in seconds (at least one, all positive)
+    /// * `auto_clear` - Whether the canvas is cleared when each following pose starts
+    pub fn start_session(&mut self, durations_secs: &[f32], auto_clear: bool) -> Result<(), String> {
+        let session = PoseSession::new(durations_secs, auto_clear, crate::time::now_ms())?;
+        log::info!("Started a session of {} poses", session.pose_count());
+        self.session = Some(session);
+        Ok(())
+    }
+
+    /// Stop the session in progress without finishing it (no callback is made)
+    pub fn stop_session(&mut self) {
+        if self.session.take().is_some() {
+            log::info!("Stopped the session");
+        }
+    }
+
+    /// Get the session in progress, if any
+    pub fn session(&self) -> Option<&PoseSession> {
+        self.session.as_ref()
+    }
+
+    /// Time in milliseconds until the session needs the next frame, or None without one
+    /// The event loop waits for input, so the caller should schedule a redraw this far out.
+    pub fn next_session_tick_ms(&self) -> Option<f64> {
+        self.session.as_ref()?.next_tick_ms(crate::time::now_ms())
+    }
+
+    /// Start every pose whose time has come, clearing the canvas for it if configured
+    fn advance_session(&mut self, renderer: &mut Renderer) {
+        let Some(session) = &mut self.session else {
+            return;
+        };
+        let entered = session.advance(crate::time::now_ms());
+        let pose_count = session.pose_count();
+        let auto_clear = session.auto_clear();
+        if session.is_finished() {
+            self.session = None;
+        }
+
+        for &pose in &entered {
+            crate::session::notify_pose_advance(pose);
+        }
+        // Skipped poses would each be cleared in turn; once is enough
+        if auto_clear && entered.iter().any(|&pose| pose < pose_count) {
+            self.clear_canvas(renderer);
+        }
+    }
+
+    /// Check whether a synthetic stroke or recording is still playing back
+    /// The caller should keep requesting redraws until it finishes.
+    pub fn is_playing_synthetic_input(&self) -> bool {
+        !self.synthetic_events.is_empty()
+    }
+
+    /// Start recording every queued pointer event, discarding any recording in progress
+    ///
+    /// Everything that reaches the input queue is recorded, including played-back and
+    /// synthetic input. Strokes already in progress are skipped.
+    pub fn start_recording(&mut self) {
+        self.input_recorder.start();
+        log::info!("Input recording started");
+    }
+
+    /// Check whether input is being recorded
+    pub fn is_recording(&self) -> bool {
+        self.input_recorder.is_recording()
+    }
+
+    /// Stop recording and return the session as versioned JSON (see `InputRecording`)
+    pub fn stop_recording(&mut self) -> String {
+        let recording = self.input_recorder.stop();
+        log::info!("Input recording stopped: {} events, {:.0} ms", recording.events.len(), recording.duration_ms());
+        recording.to_json()
+    }
+
+    /// Play a recording back through the normal input pipeline
+    ///
+    /// Events are released at their recorded times scaled by `speed` (2.0 = twice as fast),
+    /// just like a synthetic stroke, and queue up behind any playback still running. Pressure,
+    /// tilt, and the rest are replayed as recorded; the current brush settings apply.
+    pub fn play_recording(&mut self, json: &str, speed: f64) -> Result<(), String> {
+        let recording = InputRecording::from_json(json)?;
+        let now = crate::time::now_ms();
+        let start = self.synthetic_events.back().map_or(now, |last| last.timestamp.max(now));
+        let events = recording.playback_events(speed, start)?;
+        log::info!("Playing recording: {} events at {}x", events.len(), speed);
+        self.synthetic_events.extend(events);
+        Ok(())
+    }
+
+    /// Move synthetic events whose time has come into the input queue
+    fn release_synthetic_events(&mut self, now_ms: f64) {
+        let due = self.synthetic_events.iter().take_while(|event| event.timestamp <= now_ms).count();
+        let events: Vec<PointerEvent> = self.synthetic_events.drain(..due).collect();
+        for event in events {
+            self.queue_input_event(event);
+        }
+    }
+
+    /// Get the raw points (with pressure, tilt, etc.) of the last completed stroke
+    pub fn last_stroke_points(&self) -> &[StrokePoint] {
+        self.stroke_recorder.last_stroke()
+    }
+
+    /// Set how many completed strokes are retained for vector export/replay (0 = off)
+    ///
+    /// When the cap is exceeded the oldest strokes are dropped from the list. Strokes are
+    /// rasterized into the canvas as they are drawn, so dropped strokes stay baked in
+    /// exactly as they look; only their vector data is freed.
+    pub fn set_max_retained_strokes(&mut self, max: usize) {
+        self.stroke_recorder.set_max_retained_strokes(max);
+    }
+
+    /// Retained completed strokes (raw input points), oldest first
+    pub fn retained_strokes(&self) -> impl ExactSizeIterator<Item = &[StrokePoint]> {
+        self.stroke_recorder.retained_strokes()
+    }
+
+    /// Pause or resume rendering
+    ///
+    /// While paused, `render` does nothing. The caller should stop requesting redraws
+    /// and request one on resume, which redraws the whole canvas.
+    pub fn set_rendering_paused(&mut self, paused: bool) {
+        self.rendering_paused = paused;
+        log::info!("Rendering {}", if paused { "paused" } else { "resumed" });
+    }
+
+    /// Check whether rendering is paused
+    pub fn is_rendering_paused(&self) -> bool {
+        self.rendering_paused
+    }
+
+    /// Set whether new strokes are dropped while paused (otherwise queued until resume)
+    pub fn set_drop_input_while_paused(&mut self, drop: bool) {
+        self.drop_input_while_paused = drop;
+    }
+
+    /// Set the rate pointer moves are resampled to before dab generation (0 = off)
+    pub fn set_input_resample_rate(&mut self, hz: f32) {
+        self.input_queue.set_resample_rate(hz);
+    }
+
+    /// Get the input resample rate in events per second (0 = off)
+    pub fn input_resample_rate(&self) -> f32 {
+        self.input_queue.resample_rate()
+    }
+
+    /// Set input prediction: each frame the stroke is extended `ms_ahead` milliseconds past
+    /// the newest sample, shown on display only and replaced once the real samples arrive
+    ///
+    /// The lookahead is clamped to `InputPredictor::MAX_LOOKAHEAD_MS`.
+    pub fn set_prediction(&mut self, enabled: bool, ms_ahead: f32) {
+        self.input_predictor.set_lookahead_ms(if enabled { ms_ahead } else { 0.0 });
+    }
+
+    /// Get how far ahead strokes are predicted in milliseconds (0 = prediction off)
+    pub fn prediction_ms(&self) -> f32 {
+        self.input_predictor.lookahead_ms()
+    }
+
+    /// Show or hide the brush outline that follows the pointer while hovering
+    ///
+    /// The outline shows the brush size and hardness at the pointer so the scale can be
+    /// judged before drawing. It is drawn on display only and hidden during strokes.
+    pub fn set_cursor_preview(&mut self, enabled: bool) {
+        self.cursor_preview = enabled;
+    }
+
+    /// Check whether the hover brush outline is enabled
+    pub fn cursor_preview(&self) -> bool {
+        self.cursor_preview
+    }
+
+    /// Track the hovering pointer's canvas position (None once it leaves)
+    ///
+    /// Returns whether the brush outline moved, so the caller knows to redraw.
+    pub fn set_hover_position(&mut self, position: Option<[f32; 2]>) -> bool {
+        let before = self.cursor_ring();
+        self.hover_position = position;
+        self.cursor_ring() != before
+    }
+
+    /// Brush outline to show this frame: only while hovering with the brush tool
+    fn cursor_ring(&self) -> Option<CursorRing> {
+        if !self.cursor_preview || self.eyedropper_active || self.input_queue.is_drawing() {
+            return None;
+        }
+        let params = &self.brush_state.params;
+        self.hover_position.map(|center| CursorRing {
+            center,
+            radius: params.size * 0.5,
+            hardness: params.hardness,
+        })
+    }
+
+    /// Show or hide the line from the pen to the point the ink follows while stabilizing
+    ///
+    /// With strong stabilization the ink trails the pen; the line shows how far, so the
+    /// smoothing doesn't feel like lag. Display only, like the cursor preview.
+    pub fn set_stabilizer_preview(&mut self, enabled: bool) {
+        self.stabilizer_preview = enabled;
+    }
+
+    /// Check whether the stabilizer line is enabled
+    pub fn stabilizer_preview(&self) -> bool {
+        self.stabilizer_preview
+    }
+
+    /// Stabilizer line to show this frame: only once the ink trails the pen by a pixel
+    fn stabilizer_tether(&self) -> Option<[[f32; 2]; 2]> {
+        if !self.stabilizer_preview {
+            return None;
+        }
+        self.brush_state
+            .stabilizer_tether()
+            .filter(|[pen, ink]| (pen[0] - ink[0]).hypot(pen[1] - ink[1]) >= 1.0)
+    }
+
+    /// Set whether the dabs rendered each frame are kept for inspection
+    ///
+    /// Off by default so normal drawing doesn't pay for the copy. Turning it off frees
+    /// the retained dabs.
+    pub fn set_retain_frame_dabs(&mut self, retain: bool) {
+        self.retain_frame_dabs = retain;
+        if !retain {
+            self.last_frame_dabs = Vec::new();
+        }
+    }
+
+    /// Dabs rendered by the last frame that processed input, in render order
+    /// Always empty unless enabled with `set_retain_frame_dabs`.
+    pub fn last_frame_dabs(&self) -> &[BrushDab] {
+        &self.last_frame_dabs
+    }
+
     /// Check if there are pending input events
     pub fn has_pending_input(&self) -> bool {
         self.input_queue.has_events()
     }
 
+    /// Check whether a held airbrush is depositing, so frames must keep coming without input
+    pub fn is_airbrushing(&self) -> bool {
+        self.brush_state.is_airbrushing()
+    }
+
     /// Get mutable reference to brush state (for parameter adjustment)
     pub fn brush_state_mut(&mut self) -> &mut BrushState {
         &mut self.brush_state
@@ -91,46 +804,590 @@ impl App {
         &self.brush_state
     }
 
+    /// Replace all brush parameters at once
+    ///
+    /// Safe to call mid-stroke: the stroke carries on with the new parameters from the
+    /// next input sample, without a seam or a new stroke. Dab shape, color, and spacing
+    /// change right away, and eraser, blend mode, and max opacity from the next batch of
+    /// dabs rendered. The opacity mode decides how a stroke is composited when it starts,
+    /// so a change to it takes effect with the next stroke.
+    pub fn set_brush_params(&mut self, params: crate::brush::BrushParams) {
+        self.brush_state.params = params;
+    }
+
+    /// Apply a built-in brush preset by name
+    ///
+    /// See [`crate::brush::BrushParams::apply_preset`] for what is kept.
+    /// Returns false if no preset with that name exists.
+    pub fn apply_brush_preset(&mut self, name: &str) -> bool {
+        let mut params = self.brush_state.params;
+        if !params.apply_preset(name) {
+            log::warn!("Unknown brush preset: {}", name);
+            return false;
+        }
+        self.set_brush_params(params);
+
+        log::info!("Applied brush preset: {}", name);
+        true
+    }
+
+    /// Snapshot every editor setting (brush, blending, view, display)
+    pub fn config(&self, renderer: &Renderer) -> AppConfig {
+        AppConfig {
+            version: crate::config::CONFIG_VERSION,
+            brush: self.brush_state.params,
+            blend_color_space: renderer.blend_color_space(),
+            srgb_canvas: renderer.srgb_canvas(),
+            canvas_resolution: renderer.canvas_resolution(),
+            canvas_bleed: renderer.canvas_bleed(),
+            view_transform: renderer.view_transform(),
+            clear_color: self.clear_color,
+            rotation_snap_degrees: self.rotation_snap_degrees,
+            barrel_button_action: self.barrel_button_action,
+            symmetry: self.symmetry,
+            drop_input_while_paused: self.drop_input_while_paused,
+            input_resample_rate: self.input_queue.resample_rate(),
+            input_prediction_ms: self.input_predictor.lookahead_ms(),
+            max_retained_strokes: self.stroke_recorder.max_retained_strokes(),
+            undo_limit: self.history.limit(),
+            surface_background: renderer.surface_background(),
+            paper_color: renderer.paper_color(),
+            bloom_intensity: renderer.bloom_intensity(),
+            dithering: renderer.dithering(),
+            wrap_mode: renderer.sampler_address_mode(),
+            antialiasing: renderer.antialiasing(),
+            export_includes_background: renderer.export_includes_background(),
+            export_includes_bleed: renderer.export_includes_bleed(),
+            export_hdr_mode: renderer.export_hdr_mode(),
+            custom_brush_shader: renderer.custom_brush_shader().map(str::to_string),
+        }
+    }
+
+    /// Restore every editor setting from a snapshot
+    ///
+    /// The config is validated (and its custom brush shader compiled) before anything is
+    /// applied, so an invalid config leaves the current settings untouched. Canvas pixels
+    /// are not affected, unless the config changes the canvas format (see
+    /// `set_srgb_canvas`) or bleed margin, which clear the canvas.
+    pub fn apply_config(&mut self, config: &AppConfig, renderer: &mut Renderer) -> Result<(), String> {
+        config.validate()?;
+        if renderer.custom_brush_shader() != config.custom_brush_shader.as_deref() {
+            match &config.custom_brush_shader {
+                Some(wgsl) => renderer.set_custom_brush_shader(wgsl)?,
+                None => renderer.clear_custom_brush_shader(),
+            }
+        }
+
+        self.set_brush_params(config.brush);
+        self.clear_color = config.clear_color;
+        self.set_rotation_snap(config.rotation_snap_degrees);
+        self.set_barrel_button_action(config.barrel_button_action);
+        self.symmetry = config.symmetry;
+        self.drop_input_while_paused = config.drop_input_while_paused;
+        self.input_queue.set_resample_rate(config.input_resample_rate);
+        self.input_predictor.set_lookahead_ms(config.input_prediction_ms);
+        self.stroke_recorder.set_max_retained_strokes(config.max_retained_strokes);
+        self.set_undo_limit(config.undo_limit, renderer);
+
+        self.set_srgb_canvas(config.srgb_canvas, renderer);
+        let [width, height] = config.canvas_resolution.unwrap_or([0, 0]);
+        renderer.set_canvas_resolution(width, height);
+        renderer.set_canvas_bleed(config.canvas_bleed);
+        self.set_blend_color_space(config.blend_color_space, renderer);
+        renderer.set_view_transform(config.view_transform);
+        renderer.set_surface_background(config.surface_background);
+        renderer.set_paper_color(config.paper_color);
+        renderer.set_bloom_intensity(config.bloom_intensity);
+        renderer.set_dithering(config.dithering);
+        renderer.set_sampler_address_mode(config.wrap_mode);
+        // Falls back to analytic where MSAA is unsupported, like a direct call would
+        renderer.set_antialiasing(config.antialiasing);
+        renderer.set_export_includes_background(config.export_includes_background);
+        renderer.set_export_includes_bleed(config.export_includes_bleed);
+        renderer.set_export_hdr_mode(config.export_hdr_mode);
+
+        log::info!("Applied app config");
+        Ok(())
+    }
+
+    /// Export every editor setting as JSON (see `AppConfig`)
+    pub fn export_config(&self, renderer: &Renderer) -> String {
+        self.config(renderer).to_json()
+    }
+
+    /// Restore every editor setting from JSON produced by `export_config`
+    /// Returns an error (and changes nothing) if the JSON is malformed or out of range.
+    pub fn import_config(&mut self, json: &str, renderer: &mut Renderer) -> Result<(), String> {
+        let config = AppConfig::from_json(json)?;
+        self.apply_config(&config, renderer)
+    }
+
+    /// Export the current brush as JSON, for saving as a user preset
+    pub fn export_brush(&self) -> String {
+        self.brush_state.params.to_json()
+    }
+
+    /// Replace the brush with one from JSON produced by `export_brush`
+    /// Out-of-range values are repaired rather than rejected; returns an error (and
+    /// changes nothing) only if the JSON is malformed.
+    pub fn import_brush(&mut self, json: &str) -> Result<(), String> {
+        let params = crate::brush::BrushParams::from_json(json)?;
+        self.set_brush_params(params);
+        log::info!("Imported brush preset");
+        Ok(())
+    }
+
+    /// Set the canvas view rotation in degrees, snapping to the rotation snap increment
+    /// when within a few degrees of it. Returns the applied rotation in degrees.
+    pub fn set_canvas_rotation(&mut self, degrees: f32, renderer: &mut Renderer) -> f32 {
+        let snapped = crate::view::snap_rotation_degrees(
+            degrees,
+            self.rotation_snap_degrees,
+            crate::view::DEFAULT_ROTATION_SNAP_THRESHOLD_DEGREES,
+        );
+        let mut view = renderer.view_transform();
+        view.rotation = snapped.to_radians();
+        renderer.set_view_transform(view);
+        snapped
+    }
+
+    /// Get the canvas view rotation in degrees
+    pub fn canvas_rotation(&self, renderer: &Renderer) -> f32 {
+        renderer.view_transform().rotation.to_degrees()
+    }
+
+    /// Set the canvas view zoom and pan, keeping the rotation
+    ///
+    /// # Arguments
+    /// * `scale` - Surface pixels per canvas pixel (clamped to the supported zoom range)
+    /// * `translation` - Offset of the canvas center from the surface center, in surface pixels
+    ///
+    /// # Returns
+    /// The zoom actually applied
+    pub fn set_view_pan_zoom(&mut self, scale: f32, translation: [f32; 2], renderer: &mut Renderer) -> f32 {
+        let mut view = renderer.view_transform();
+        view.scale = scale;
+        view.translation = translation;
+        renderer.set_view_transform(view);
+        renderer.view_transform().scale
+    }
+
+    /// Pan and zoom the view to follow a two-finger pinch (see `ViewTransform::pinched`)
+    ///
+    /// # Arguments
+    /// * `from` - Both touch positions before the move, in surface pixels
+    /// * `to` - The same touches after the move, in the same order
+    pub fn pinch_view(&mut self, from: [[f32; 2]; 2], to: [[f32; 2]; 2], renderer: &mut Renderer) {
+        let view = renderer.view_transform().pinched(from, to, renderer.surface_size());
+        renderer.set_view_transform(view);
+    }
+
+    /// Set the rotation snap increment in degrees (0.0 disables snapping)
+    pub fn set_rotation_snap(&mut self, degrees: f32) {
+        self.rotation_snap_degrees = degrees.max(0.0);
+    }
+
     /// Set the blend mode
     pub fn set_blend_color_space(&mut self, color_space: crate::renderer::BlendColorSpace, renderer: &mut Renderer) {
+        // An 8-bit sRGB canvas is recreated in the new blend space's format
+        let recreates_canvas = renderer.srgb_canvas() && renderer.blend_color_space() != color_space;
         renderer.set_blend_color_space(color_space);
+        if recreates_canvas {
+            self.clear_canvas(renderer);
+        }
         log::info!("App blend color space changed to: {:?}", color_space);
     }
 
+    /// Set the blend mode, converting the canvas so existing art keeps its colors
+    /// (see `Renderer::set_blend_color_space_preserving`). The canvas is never cleared.
+    pub fn set_blend_color_space_preserving(&mut self, color_space: crate::renderer::BlendColorSpace, renderer: &mut Renderer) {
+        renderer.set_blend_color_space_preserving(color_space);
+        log::info!("App blend color space changed to: {:?} (canvas converted)", color_space);
+    }
+
+    /// Set whether the canvas is stored as 8-bit sRGB (see `Renderer::set_srgb_canvas`)
+    /// Changing it recreates the canvas, which is then cleared to the clear color.
+    pub fn set_srgb_canvas(&mut self, enabled: bool, renderer: &mut Renderer) {
+        if renderer.srgb_canvas() == enabled {
+            return;
+        }
+        renderer.set_srgb_canvas(enabled);
+        self.clear_canvas(renderer);
+    }
+
     /// Get the current blend mode from the renderer
     pub fn blend_color_space(&self, renderer: &Renderer) -> crate::renderer::BlendColorSpace {
         renderer.blend_color_space()
     }
 
+    /// Set the brush grain texture (8-bit grayscale) multiplied into every dab
+    pub fn set_brush_grain(&mut self, renderer: &mut Renderer, data: &[u8], width: u32, height: u32, scale: f32) -> Result<(), String> {
+        renderer.set_brush_grain(data, width, height, scale)
+    }
+
+    /// Set the brush stamp texture (8-bit grayscale tip shape mask)
+    pub fn set_brush_texture(&mut self, renderer: &mut Renderer, data: &[u8], width: u32, height: u32) -> Result<(), String> {
+        renderer.set_brush_texture(data, width, height)
+    }
+
+    /// Set the brush stamp texture from an encoded PNG
+    pub fn set_brush_texture_png(&mut self, renderer: &mut Renderer, png_data: &[u8]) -> Result<(), String> {
+        renderer.set_brush_texture_png(png_data)
+    }
+
+    /// Remove the brush stamp texture
+    pub fn clear_brush_texture(&mut self, renderer: &mut Renderer) {
+        renderer.clear_brush_texture();
+    }
+
+    /// Remove the brush grain texture
+    pub fn clear_brush_grain(&mut self, renderer: &mut Renderer) {
+        renderer.clear_brush_grain();
+    }
+
+    /// Get the estimated GPU memory used by the canvas and related textures (bytes)
+    pub fn estimated_vram_bytes(&self, renderer: &Renderer) -> u64 {
+        renderer.estimated_vram_bytes()
+    }
+
+    /// Get the smoothed frames-per-second from the renderer
+    pub fn fps(&self, renderer: &Renderer) -> f32 {
+        renderer.fps()
+    }
+
     /// Process input events and generate brush dabs
-    fn process_input_events(&mut self) -> Vec<crate::brush::BrushDab> {
-        let mut all_dabs = Vec::new();
+    /// Process input events, rendering dabs to the canvas as strokes progress
+    ///
+    /// Dabs are batched, but flushed at stroke boundaries so each stroke's dabs land in
+    /// the right place when PerStroke opacity routes them through the stroke layer.
+    fn process_input_events(&mut self, renderer: &mut Renderer) {
+        let mut pending_dabs = Vec::new();
+        let mut dab_count = 0;
 
-        for event in self.input_queue.drain_events() {
-            self.brush_state.update_brush_src(event.source);
+        // Zoom-aware spacing follows how large canvas pixels currently appear on screen
+        self.brush_state.set_view_zoom(renderer.view_transform().max_scale());
+
+        // Collect first: rendering mid-loop needs `self` while the queue would still be borrowed
+        let events: Vec<PointerEvent> = self.input_queue.drain_events().collect();
+        if self.retain_frame_dabs && !events.is_empty() {
+            self.last_frame_dabs.clear();
+        }
+        for event in events {
+            self.stroke_recorder.record(&event);
+            self.input_predictor.record(&event);
+            // A source change mid-stroke ends it, releasing the dabs held for its end
+            let mut released = self.brush_state.update_brush_src(event.source);
+            self.finish_dabs(&mut released);
+            self.record_history_dabs(&released);
+            pending_dabs.extend(released);
             match event.event_type {
                 crate::input::PointerEventType::Down => {
                     // Start new stroke
+                    dab_count += pending_dabs.len();
+                    self.render_dabs(renderer, &mut pending_dabs);
+                    self.begin_history_stroke(renderer);
+                    self.begin_stroke_layer(renderer);
                     self.brush_state.begin_stroke();
-                    let dabs = self.brush_state.calculate_dabs(event.position, event.pressure, event.event_type);
-                    all_dabs.extend(dabs);
+                    self.brush_state.update_angles(event.azimuth, event.twist);
+                    self.brush_state.update_timestamp(event.timestamp);
+                    let mut dabs = self.brush_state.calculate_dabs(event.position, event.pressure, event.event_type);
+                    self.finish_dabs(&mut dabs);
+                    self.record_history_dabs(&dabs);
+                    pending_dabs.extend(dabs);
                 }
                 crate::input::PointerEventType::Move => {
                     // Continue stroke
-                    let dabs = self.brush_state.calculate_dabs(event.position, event.pressure, event.event_type);
-                    all_dabs.extend(dabs);
+                    self.brush_state.update_angles(event.azimuth, event.twist);
+                    self.brush_state.update_timestamp(event.timestamp);
+                    let mut dabs = self.brush_state.calculate_dabs(event.position, event.pressure, event.event_type);
+                    self.finish_dabs(&mut dabs);
+                    self.record_history_dabs(&dabs);
+                    pending_dabs.extend(dabs);
+                    if self.brush_state.take_segment_break() {
+                        dab_count += pending_dabs.len();
+                        self.render_dabs(renderer, &mut pending_dabs);
+                        self.split_stroke();
+                    }
                 }
                 crate::input::PointerEventType::Up => {
                     // End stroke
-                    let dabs = self.brush_state.calculate_dabs(event.position, event.pressure, event.event_type);
-                    all_dabs.extend(dabs);
+                    self.brush_state.update_angles(event.azimuth, event.twist);
+                    self.brush_state.update_timestamp(event.timestamp);
+                    let mut dabs = self.brush_state.calculate_dabs(event.position, event.pressure, event.event_type);
+                    self.finish_dabs(&mut dabs);
+                    self.record_history_dabs(&dabs);
+                    pending_dabs.extend(dabs);
                     self.brush_state.end_stroke();
+                    dab_count += pending_dabs.len();
+                    self.render_dabs(renderer, &mut pending_dabs);
+                    self.end_stroke_layer(renderer);
+                    self.end_history_stroke(renderer);
                 }
             }
         }
 
-        log::debug!("Processed input events, generated {} dabs", all_dabs.len());
-        all_dabs
+        // A held airbrush keeps depositing between input events
+        let mut dabs = self.brush_state.airbrush_dabs(crate::time::now_ms());
+        self.finish_dabs(&mut dabs);
+        self.record_history_dabs(&dabs);
+        pending_dabs.extend(dabs);
+
+        dab_count += pending_dabs.len();
+        self.render_dabs(renderer, &mut pending_dabs);
+        self.render_prediction(renderer);
+        log::debug!("Processed input events, generated {} dabs", dab_count);
+    }
+
+    /// Apply the current brush settings to the renderer before it draws dabs
+    fn apply_dab_settings(&self, renderer: &mut Renderer) {
+        renderer.set_max_opacity(self.brush_state.params.max_opacity);
+        renderer.set_erase_mode(self.is_erasing());
+        renderer.set_brush_blend_mode(self.brush_state.params.blend_mode);
+        renderer.set_smudge_strength(self.tool.smudge_strength());
+        renderer.set_smudge_length(self.brush_state.params.smudge_length);
+    }
+
+    /// Render and clear a batch of dabs with the current brush settings
+    fn render_dabs(&mut self, renderer: &mut Renderer, dabs: &mut Vec<BrushDab>) {
+        if dabs.is_empty() {
+            return;
+        }
+        if self.retain_frame_dabs {
+            self.last_frame_dabs.extend_from_slice(dabs);
+        }
+        self.apply_dab_settings(renderer);
+        renderer.render_dabs(dabs);
+        dabs.clear();
+    }
+
+    /// Show the predicted continuation of the stroke in progress, or hide the last one
+    ///
+    /// The predicted sample runs through a copy of the brush state, so the stroke itself
+    /// (and its history) never sees it. Only Normal painting is predicted: other blend
+    /// modes, erasing, and smudging depend on the canvas under the dabs.
+    fn render_prediction(&mut self, renderer: &mut Renderer) {
+        let predicted = self.input_predictor.predict();
+        let mut dabs = Vec::new();
+        if let Some(event) = predicted.filter(|_| self.brush_state.params.blend_mode == BrushBlendMode::Normal) {
+            let mut brush_state = self.brush_state.clone();
+            brush_state.update_angles(event.azimuth, event.twist);
+            brush_state.update_timestamp(event.timestamp);
+            dabs = brush_state.calculate_dabs(event.position, event.pressure, event.event_type);
+            self.finish_dabs(&mut dabs);
+        }
+        if dabs.is_empty() && !renderer.has_prediction() {
+            return;
+        }
+        self.apply_dab_settings(renderer);
+        renderer.render_prediction(&dabs);
+    }
+
+    /// Mirror new dabs per the symmetry settings, and draw them at full flow in Uniform
+    /// opacity mode (the stroke layer applies the flow to the whole stroke)
+    fn finish_dabs(&self, dabs: &mut Vec<BrushDab>) {
+        self.symmetry.apply(dabs);
+        if self.brush_state.params.opacity_mode == OpacityMode::Uniform && self.stroke_uses_layer() {
+            for dab in dabs.iter_mut() {
+                dab.opacity = 1.0;
+            }
+        }
+    }
+
+    /// Check whether the next stroke goes through the stroke layer (PerStroke or Uniform
+    /// opacity, or a blend mode that blends whole strokes)
+    fn stroke_uses_layer(&self) -> bool {
+        let params = &self.brush_state.params;
+        if self.is_erasing() || self.tool != Tool::Brush {
+            return false;
+        }
+        match params.blend_mode {
+            BrushBlendMode::Normal => params.opacity_mode != OpacityMode::Accumulate,
+            BrushBlendMode::AddHdr => false,
+            mode => mode.blends_per_stroke(),
+        }
+    }
+
+    /// Set up the renderer's stroke layer for a new stroke according to the opacity mode
+    fn begin_stroke_layer(&self, renderer: &mut Renderer) {
+        let params = &self.brush_state.params;
+        if !self.stroke_uses_layer() {
+            // Accumulate mode (with Normal blending), erasing, additive, and smudge dabs
+            // draw straight into the canvas
+            renderer.commit_stroke_layer();
+            return;
+        }
+
+        // Keep sharing coverage with the previous stroke only if nothing that would
+        // change how it composites has changed since
+        let can_continue = !params.restart_resets_coverage
+            && renderer.is_stroke_layer_active()
+            && renderer.stroke_layer_opacity() == params.stroke_opacity()
+            && renderer.stroke_layer_blend_mode() == params.blend_mode;
+        if !can_continue {
+            renderer.begin_stroke_layer(params.stroke_opacity(), params.blend_mode);
+        }
+    }
+
+    /// Close the stroke in progress as a logical segment and carry on drawing in a new one
+    ///
+    /// Called when a stroke reaches the max stroke length. The recorded points are handed
+    /// off as a completed stroke so retained data stays bounded. The stroke layer is left
+    /// open: it is a fixed-size texture that doesn't grow with the stroke, and compositing
+    /// it mid-stroke would let the next segment's dabs pile up over the seam in PerStroke
+    /// mode, visibly darkening it.
+    fn split_stroke(&mut self) {
+        self.stroke_recorder.split_stroke();
+        log::debug!("Stroke reached max length, continuing in a new segment");
+    }
+
+    /// Finish the stroke layer at the end of a stroke
+    fn end_stroke_layer(&self, renderer: &mut Renderer) {
+        // Shared coverage stays open for the next stroke; it is committed when a stroke
+        // starts fresh, the mode changes, or the canvas is read back
+        if self.brush_state.params.restart_resets_coverage {
+            renderer.commit_stroke_layer();
+        }
+    }
+
+    /// Forget the undo history and any stroke being recorded for it
+    fn discard_history(&mut self) {
+        self.history.clear();
+        self.history_stroke = None;
+    }
+
+    /// Check whether the undo history can be used, dropping it if its canvas is gone
+    fn history_ready(&mut self, renderer: &Renderer) -> bool {
+        if self.history_stroke.is_some() {
+            log::debug!("Ignoring undo/redo while a stroke is in progress");
+            return false;
+        }
+        // Undo bases go away when the canvas is cleared (including by a bleed change)
+        let before = self.history.strokes().len();
+        self.history.retain(|stroke| renderer.has_undo_base(stroke.layer));
+        if self.history.strokes().len() < before {
+            log::debug!("Canvas was reset, dropping undo history of the cleared layers");
+        }
+        true
+    }
+
+    /// Start recording a stroke for the undo history (nothing is recorded with undo off)
+    ///
+    /// The settings that decide how the stroke composites are captured here, as the stroke
+    /// layer is set up. Toggling the eraser mid-stroke is replayed as the stroke started.
+    fn begin_history_stroke(&mut self, renderer: &mut Renderer) {
+        if self.history.limit() == 0 {
+            return;
+        }
+        self.ensure_undo_base(renderer);
+        let params = &self.brush_state.params;
+        let per_stroke = self.stroke_uses_layer();
+        self.history_stroke = Some(HistoryStroke {
+            layer: renderer.active_layer(),
+            dabs: Vec::new(),
+            erase: self.is_erasing(),
+            blend_mode: params.blend_mode,
+            max_opacity: params.max_opacity,
+            stroke_opacity: params.stroke_opacity(),
+            per_stroke,
+            smudge: self.tool.smudge_strength(),
+            smudge_length: params.smudge_length,
+            fill: None,
+        });
+    }
+
+    /// Snapshot the active layer as its undo base if it has nothing to undo
+    ///
+    /// With nothing to undo on it the layer is the new undo base (it may hold strokes drawn
+    /// with undo off, or its history may have been dropped with its canvas).
+    fn ensure_undo_base(&mut self, renderer: &mut Renderer) {
+        let layer = renderer.active_layer();
+        if !renderer.has_undo_base(layer) {
+            self.history.retain(|stroke| stroke.layer != layer);
+        } else if self.history.strokes_on(layer).next().is_some() {
+            return;
+        }
+        renderer.snapshot_undo_base();
+    }
+
+    /// Add dabs to the stroke being recorded for the undo history
+    fn record_history_dabs(&mut self, dabs: &[BrushDab]) {
+        if let Some(stroke) = &mut self.history_stroke {
+            stroke.dabs.extend_from_slice(dabs);
+        }
+    }
+
+    /// Push the finished stroke onto the undo history
+    fn end_history_stroke(&mut self, renderer: &mut Renderer) {
+        let Some(stroke) = self.history_stroke.take() else {
+            return;
+        };
+        if stroke.dabs.is_empty() {
+            return;
+        }
+        let evicted = self.history.push(stroke);
+        Self::bake_into_undo_base(renderer, &evicted);
+    }
+
+    /// Draw strokes that dropped out of the undo history into their layers' undo bases
+    fn bake_into_undo_base(renderer: &mut Renderer, strokes: &[HistoryStroke]) {
+        let mut layers: Vec<usize> = strokes.iter().map(|stroke| stroke.layer).collect();
+        layers.sort_unstable();
+        layers.dedup();
+        for layer in layers {
+            let layer_strokes = strokes.iter().filter(|stroke| stroke.layer == layer);
+            let drawn = renderer.draw_into_layer(layer, |renderer| {
+                renderer.draw_into_undo_base(|renderer| Self::replay_strokes(renderer, layer_strokes));
+            });
+            if let Err(e) = drawn {
+                log::warn!("Couldn't bake strokes into the undo base: {}", e);
+            }
+        }
+        if !strokes.is_empty() {
+            log::debug!("Baked {} strokes into the undo base", strokes.len());
+        }
+    }
+
+    /// Rebuild a layer from its undo base and its strokes still in the history
+    fn recomposite_history(&self, layer: usize, renderer: &mut Renderer) {
+        let history = &self.history;
+        let drawn = renderer.draw_into_layer(layer, |renderer| {
+            if !renderer.restore_undo_base() {
+                log::warn!("No undo base to restore, layer {} left unchanged", layer);
+                return;
+            }
+            Self::replay_strokes(renderer, history.strokes_on(layer));
+        });
+        if let Err(e) = drawn {
+            log::warn!("Couldn't rebuild layer {}: {}", layer, e);
+        }
+    }
+
+    /// Draw recorded strokes in order, each with the settings it was drawn with
+    ///
+    /// Consecutive strokes with the same settings are replayed together. Strokes that
+    /// shared stroke layer coverage (with `restart_resets_coverage` off) are replayed with
+    /// their own coverage each.
+    fn replay_strokes<'a>(renderer: &mut Renderer, strokes: impl Iterator<Item = &'a HistoryStroke>) {
+        let strokes: Vec<&HistoryStroke> = strokes.collect();
+        for group in strokes.chunk_by(|a, b| a.same_render_settings(b)) {
+            let first = group[0];
+            if let Some(fill) = &first.fill {
+                renderer.fill(fill);
+                continue;
+            }
+            renderer.set_max_opacity(first.max_opacity);
+            renderer.set_erase_mode(first.erase);
+            renderer.set_brush_blend_mode(first.blend_mode);
+            renderer.set_smudge_strength(first.smudge);
+            renderer.set_smudge_length(first.smudge_length);
+            if first.per_stroke {
+                renderer.begin_stroke_layer(first.stroke_opacity, first.blend_mode);
+            } else {
+                renderer.commit_stroke_layer();
+            }
+            let dabs: Vec<&[BrushDab]> = group.iter().map(|stroke| stroke.dabs.as_slice()).collect();
+            renderer.replay_dabs(&dabs);
+        }
     }
 }
 
@@ -139,3 +1396,114 @@ impl Default for App {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::{PointerEventSource, PointerEventType};
+
+    fn pointer(event_type: PointerEventType, position: [f32; 2]) -> PointerEvent {
+        PointerEvent {
+            position,
+            pressure: 1.0,
+            tilt: None,
+            azimuth: None,
+            twist: None,
+            timestamp: 0.0,
+            event_type,
+            source: PointerEventSource::TabletTool,
+        }
+    }
+
+    #[test]
+    fn test_eyedropper_takes_presses_instead_of_drawing() {
+        let mut app = App::new();
+        app.set_eyedropper_active(true);
+        app.queue_input_event(pointer(PointerEventType::Down, [1.0, 2.0]));
+        app.queue_input_event(pointer(PointerEventType::Move, [3.0, 4.0]));
+
+        // Only the latest position of the drag is sampled, and nothing reaches the brush
+        assert_eq!(app.take_eyedropper_request(), Some([3.0, 4.0]));
+        assert_eq!(app.take_eyedropper_request(), None);
+        assert!(!app.has_pending_input());
+
+        app.queue_input_event(pointer(PointerEventType::Up, [5.0, 6.0]));
+        assert_eq!(app.take_eyedropper_request(), Some([5.0, 6.0]));
+        assert!(!app.has_pending_input());
+    }
+
+    #[test]
+    fn test_eyedropper_lets_an_earlier_stroke_end() {
+        let mut app = App::new();
+        app.queue_input_event(pointer(PointerEventType::Down, [1.0, 2.0]));
+        app.set_eyedropper_active(true);
+
+        app.queue_input_event(pointer(PointerEventType::Move, [3.0, 4.0]));
+        app.queue_input_event(pointer(PointerEventType::Up, [3.0, 4.0]));
+        assert_eq!(app.take_eyedropper_request(), None);
+        assert!(app.has_pending_input());
+    }
+
+    #[test]
+    fn test_picked_color_keeps_the_brush_alpha() {
+        let mut app = App::new();
+        app.brush_state_mut().params.color = [0.0, 0.0, 0.0, 0.5];
+
+        app.apply_picked_color([0.2, 0.4, 0.6, 1.0]);
+        assert_eq!(app.brush_state().params.color, [0.2, 0.4, 0.6, 0.5]);
+
+        // Transparent pixels have no color to pick
+        app.apply_picked_color([1.0, 1.0, 1.0, 0.0]);
+        assert_eq!(app.brush_state().params.color, [0.2, 0.4, 0.6, 0.5]);
+    }
+
+    #[test]
+    fn test_barrel_button_erases_only_while_held() {
+        let mut app = App::new();
+        assert_eq!(app.barrel_button_action(), BarrelButtonAction::Erase);
+        app.handle_barrel_button(true);
+        assert!(app.is_momentary_erase() && app.is_erasing());
+        app.handle_barrel_button(false);
+        assert!(!app.is_erasing());
+
+        // Switching the button to another action releases a held eraser
+        app.handle_barrel_button(true);
+        app.set_barrel_button_action(BarrelButtonAction::None);
+        assert!(!app.is_erasing());
+        app.handle_barrel_button(true);
+        assert!(!app.is_erasing());
+    }
+
+    #[test]
+    fn test_invalid_tool_and_symmetry_are_rejected_unchanged() {
+        let mut app = App::new();
+        assert!(app.set_tool(Tool::Smudge(0.5)).is_ok());
+        assert!(app.set_tool(Tool::Smudge(1.5)).is_err());
+        assert_eq!(app.tool(), Tool::Smudge(0.5));
+
+        assert!(app.set_symmetry(SymmetryMode::Vertical, [64.0, 0.0]).is_ok());
+        assert!(app.set_symmetry(SymmetryMode::Radial(SymmetryMode::MAX_RADIAL + 1), [0.0, 0.0]).is_err());
+        assert!(app.set_symmetry(SymmetryMode::Horizontal, [f32::NAN, 0.0]).is_err());
+        assert_eq!(app.symmetry(), Symmetry { mode: SymmetryMode::Vertical, center: [64.0, 0.0] });
+    }
+
+    #[test]
+    fn test_paused_input_is_dropped_but_strokes_still_end() {
+        let mut app = App::new();
+        app.set_rendering_paused(true);
+        app.set_drop_input_while_paused(true);
+
+        app.queue_input_event(pointer(PointerEventType::Down, [1.0, 2.0]));
+        app.queue_input_event(pointer(PointerEventType::Move, [3.0, 4.0]));
+        assert!(!app.has_pending_input());
+        app.queue_input_event(pointer(PointerEventType::Up, [3.0, 4.0]));
+        assert!(app.has_pending_input());
+    }
+
+    #[test]
+    fn test_undo_is_empty_until_something_is_drawn() {
+        let app = App::new();
+        assert_eq!(app.undo_limit(), crate::history::DEFAULT_UNDO_LIMIT);
+        assert!(!app.can_undo() && !app.can_redo());
+    }
+}
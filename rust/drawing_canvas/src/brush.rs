@@ -4,9 +4,50 @@
 //! brush dabs from input events.
 
 use crate::input::PointerEventSource;
+use std::collections::VecDeque;
+
+/// Number of recent input points the line assist fits its line through
+const LINE_ASSIST_WINDOW: usize = 32;
+/// Fewest points needed before the line assist fits a line
+const LINE_ASSIST_MIN_POINTS: usize = 6;
+/// Number of most recent steps averaged to measure pen speed
+const LINE_ASSIST_SPEED_STEPS: usize = 8;
+/// Average step (canvas pixels per input sample) at and above which the pen counts as fast
+const LINE_ASSIST_SLOW_STEP_PX: f32 = 3.0;
+/// Number of consecutive points averaged before measuring curvature, to cancel hand jitter
+const LINE_ASSIST_DENOISE_POINTS: usize = 4;
+/// RMS distance (canvas pixels) of the denoised input from the fitted line at which the
+/// input counts as a curve. Jitter averages out; a curve over the window's span does not.
+const LINE_ASSIST_CURVE_TOLERANCE_PX: f32 = 0.5;
+/// Fraction of the way the assist weight moves toward its target per input sample
+const LINE_ASSIST_RAMP: f32 = 0.25;
+/// Distance (canvas pixels) from the stroke start at which the angle constraint locks
+/// onto an axis. Closer in, the axis follows the pen so the first wobble doesn't pick it.
+const ANGLE_CONSTRAINT_LOCK_PX: f32 = 6.0;
+/// Spacing (fraction of diameter) at and above which the brush counts as a stamp brush,
+/// so `DownDabMode::Auto` places the first dab on Down
+const STAMP_SPACING_THRESHOLD: f32 = 0.5;
+/// Starting state of the random rotation generator (any nonzero value), fixed so the same
+/// input always produces the same dabs
+const RANDOM_ROTATION_SEED: u32 = 0x9E37_79B9;
+/// Mixed with each stroke's starting position to seed its color jitter generator
+const COLOR_JITTER_SEED: u32 = 0x85EB_CA6B;
+/// Most dabs the airbrush deposits in one update, so a long stall (e.g. a paused tab)
+/// doesn't dump a blot when frames resume
+const MAX_AIRBRUSH_DABS_PER_UPDATE: usize = 32;
+/// Highest airbrush deposit rate in dabs per second
+pub const MAX_AIRBRUSH_RATE: f32 = 1000.0;
+/// Length in canvas pixels of the straight pieces a spline segment is sampled into
+const SPLINE_SAMPLE_PX: f32 = 2.0;
+/// Most straight pieces a single spline segment is sampled into
+const SPLINE_MAX_SAMPLES: usize = 64;
+/// Fraction of the way the stabilized position moves toward the pen per input sample at
+/// full stabilization (higher values follow the pen more closely)
+const STABILIZATION_MIN_FOLLOW: f32 = 0.1;
 
 /// Parameters that define brush behavior
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 pub struct BrushParams {
     /// Brush size in pixels (diameter)
     pub size: f32,
@@ -14,9 +55,12 @@ pub struct BrushParams {
     pub flow: f32,
     /// Brush edge hardness (0.0=soft, 1.0=hard)
     pub hardness: f32,
-    /// Spacing between dabs as a fraction of brush diameter (0.0-1.0)
-    /// e.g., 0.05 = 5% of diameter, 0.25 = 25% of diameter
+    /// Spacing between dabs, interpreted according to `spacing_mode`
+    /// As a fraction of brush diameter (0.0-1.0), e.g. 0.05 = 5% of diameter, 0.25 = 25%;
+    /// in absolute mode, a distance in canvas pixels (> 0.0)
     pub spacing: f32,
+    /// Whether `spacing` scales with the dab size or is a fixed pixel distance
+    pub spacing_mode: SpacingMode,
     /// Brush color in sRGB RGBA (0.0-1.0)
     /// Will be converted to linear at render time if needed
     pub color: [f32; 4],
@@ -42,11 +86,280 @@ pub struct BrushParams {
     /// Flow pressure curve gamma
     /// <1.0 = aggressive early opacity, =1.0 = linear, >1.0 = delayed opacity
     pub flow_gamma: f32,
+    /// Minimum hardness as a fraction of full hardness at zero pressure (0.0-1.0)
+    /// Only applies when Hardness or All pressure mapping is enabled
+    pub min_hardness_percent: f32,
+    /// Hardness scaling factor at full pressure (clamped at 1.0 in dab creation)
+    pub max_hardness_percent: f32,
+    /// Hardness pressure curve gamma
+    /// <1.0 = hardens early, =1.0 = linear, >1.0 = stays soft until pressed hard
+    pub hardness_gamma: f32,
+    /// Shape of the pressure response for size, flow, and hardness
+    /// Gamma uses the per-property gammas above; Curve replaces them with one shared curve
+    pub pressure_response: PressureResponse,
+    /// How pen speed affects the brush (on top of pressure, so it also works for a mouse)
+    pub velocity_mapping: VelocityMapping,
+    /// Pen speed in canvas pixels per second at which the velocity effect is strongest
+    pub velocity_max_speed: f32,
+    /// Size/flow as a fraction of full at or above the max speed (0.0-1.0)
+    /// e.g., 0.3 = fast strokes thin out to 30%
+    pub min_velocity_percent: f32,
+    /// Size/flow as a fraction of full when the pen is still (clamped at 1.0 in dab creation)
+    pub max_velocity_percent: f32,
+    /// Velocity curve gamma
+    /// <1.0 = thins out at low speeds already, =1.0 = linear, >1.0 = only fast strokes thin out
+    pub velocity_gamma: f32,
+    /// Smoothing applied to pen speed before the velocity curve (0.0-1.0)
+    /// Evens out single-sample spikes from uneven input timing.
+    /// 0.0 = raw speed, higher = smoother but slower to respond (exponential moving average)
+    pub velocity_smoothing: f32,
     /// Input filter mode - which input sources to accept
     pub input_filter_mode: InputFilterMode,
+    /// How the pressure of the deferred first dab is chosen
+    pub first_dab_pressure_mode: FirstDabPressureMode,
+    /// Whether the first dab is placed on Down or deferred until the pen moves
+    pub down_dab_mode: DownDabMode,
+    /// Hard ceiling on accumulated opacity across all passes (0.0-1.0)
+    /// Repeated strokes build up to this value and no further (like a marker that can
+    /// only get so dark), and never lighten paint that is already more opaque. 1.0 = no
+    /// cap. Distinct from flow, which is per dab. In PerStroke and Uniform opacity modes
+    /// each stroke is also composited at this opacity.
+    pub max_opacity: f32,
+    /// How dab coverage combines into the canvas
+    pub opacity_mode: OpacityMode,
+    /// Smoothing applied to pressure before the pressure curves (0.0-1.0)
+    /// Independent of position: evens out noisy pressure without rounding off the line.
+    /// 0.0 = raw pressure, higher = smoother but laggier (exponential moving average)
+    pub pressure_smoothing: f32,
+    /// Smoothing applied to azimuth/twist before they drive dab rotation (0.0-1.0)
+    /// 0.0 = raw angles, higher = smoother but laggier (exponential moving average)
+    pub angle_smoothing: f32,
+    /// In PerStroke and Uniform modes, whether each new stroke starts with fresh coverage
+    /// When false, strokes keep sharing one coverage layer until the setting or
+    /// stroke opacity changes, so restarting a stroke over itself doesn't darken it.
+    pub restart_resets_coverage: bool,
+    /// How dab color combines with the canvas
+    pub blend_mode: BrushBlendMode,
+    /// Whether dabs erase (remove canvas coverage) instead of painting
+    /// Color, blend mode and opacity mode are ignored; flow, hardness, and the tip still
+    /// shape how much each dab removes.
+    pub eraser: bool,
+    /// Shape of each dab
+    pub tip_shape: TipShape,
+    /// Whether each dab's tip (and stamp texture) is turned by a random angle
+    /// Hides the repeating pattern a textured stamp leaves along a stroke. Replaces the
+    /// stylus angle, which would otherwise orient the tip.
+    pub random_rotation: bool,
+    /// How far each dab's hue may stray from the brush color (0.0-1.0)
+    /// 1.0 = anywhere on the color wheel; 0.0 = off. Together with the saturation and value
+    /// jitter this gives the dab-to-dab variation of pencils and pastels.
+    pub hue_jitter: f32,
+    /// How far each dab's HSV saturation may stray from the brush color (0.0-1.0, 0.0 = off)
+    pub saturation_jitter: f32,
+    /// How far each dab's HSV value may stray from the brush color (0.0-1.0, 0.0 = off)
+    pub value_jitter: f32,
+    /// Whether spacing shrinks with the view zoom so dabs stay dense on screen
+    /// Only applies when zoomed in; the stroke is still stored at canvas resolution.
+    pub zoom_aware_spacing: bool,
+    /// Minimum diameter in pixels of the dab placed by a tap (a stroke with no movement)
+    /// A tap always leaves a mark of at least this size; 0.0 = taps follow normal dynamics
+    pub min_tap_dab_size: f32,
+    /// Strength of the "straighten when slow" line assist (0.0-1.0)
+    /// When the pen slows down over a run of nearly collinear input, new points are pulled
+    /// toward the best-fit line through it. Clearly curved input is left alone. 0.0 = off
+    pub line_assist: f32,
+    /// Strength of position stabilization (0.0-1.0)
+    /// The ink follows the pen through an exponential moving average of input positions,
+    /// evening out jitter (especially on touch) at the cost of lag. The lag is flushed
+    /// when the pen lifts, so strokes still end where the pen did. 0.0 = off
+    pub stabilization: f32,
+    /// Stroke length in pixels after which the stroke is split into a new logical segment
+    /// Bounds per-stroke bookkeeping on very long strokes; drawing continues seamlessly
+    /// across the split. 0.0 = unlimited
+    pub max_stroke_length: f32,
+    /// Shape of the stroke's start and end
+    pub cap_style: CapStyle,
+    /// Length in pixels over which the stroke's size ramps up from zero at its start
+    /// 0.0 = off. Works with any cap style; like caps, it delays the visible tip of the
+    /// stroke while drawing (see `taper_out`).
+    pub taper_in: f32,
+    /// Length in pixels over which the stroke's size ramps back down to zero at its end
+    /// 0.0 = off. The end isn't known until the pen lifts, so the last this many pixels
+    /// of the stroke only appear then.
+    pub taper_out: f32,
+    /// Angle step in degrees the stroke direction is locked to (0.0 = unconstrained)
+    /// e.g. 45.0 keeps each stroke on the nearest of 0/45/90/... degrees from where it
+    /// started. Pressure and spacing still follow the pen; only the axis is fixed.
+    pub angle_constraint: f32,
+    /// Whether paint keeps depositing while the pen is held still, like an airbrush
+    /// Without it dabs are only placed as the pen moves (see `airbrush_rate`).
+    pub airbrush: bool,
+    /// Airbrush deposit rate in dabs per second at full pressure (scaled down by pressure)
+    pub airbrush_rate: f32,
+    /// How much of the color the smudge tool carries survives from one dab to the next (0.0-1.0)
+    /// The rest is picked up fresh from the canvas under each dab. 1.0 = one color is dragged
+    /// along the whole stroke, 0.0 = color barely moves. Only applies with the smudge tool.
+    pub smudge_length: f32,
 }
 
 impl BrushParams {
+    /// Default brush parameters (usable in const contexts, e.g. presets)
+    pub const DEFAULT: Self = Self {
+        size: 30.0,
+        flow: 1.0,
+        hardness: 1.0,
+        spacing: 0.15,
+        spacing_mode: SpacingMode::DiameterFraction,
+        color: [163.0 / 255.0, 2.0 / 255.0, 222.0 / 255.0, 1.0],
+        pressure_mapping: PressureMapping::Flow,
+        min_size_percent: 1.0,
+        max_size_percent: 3.2,
+        min_flow_percent: 0.0,
+        max_flow_percent: 3.0,
+        size_gamma: 1.0,
+        flow_gamma: 1.0,
+        min_hardness_percent: 0.0,
+        max_hardness_percent: 1.0,
+        hardness_gamma: 1.0,
+        pressure_response: PressureResponse::Gamma,
+        velocity_mapping: VelocityMapping::None,
+        velocity_max_speed: 3000.0,
+        min_velocity_percent: 0.3,
+        max_velocity_percent: 1.0,
+        velocity_gamma: 1.0,
+        velocity_smoothing: 0.7,
+        input_filter_mode: InputFilterMode::PenAndTouch,
+        first_dab_pressure_mode: FirstDabPressureMode::FirstMove,
+        down_dab_mode: DownDabMode::Auto,
+        max_opacity: 1.0,
+        opacity_mode: OpacityMode::Accumulate,
+        pressure_smoothing: 0.0,
+        angle_smoothing: 0.5,
+        restart_resets_coverage: true,
+        blend_mode: BrushBlendMode::Normal,
+        eraser: false,
+        tip_shape: TipShape::Round,
+        random_rotation: false,
+        hue_jitter: 0.0,
+        saturation_jitter: 0.0,
+        value_jitter: 0.0,
+        zoom_aware_spacing: false,
+        min_tap_dab_size: 0.0,
+        line_assist: 0.0,
+        stabilization: 0.0,
+        max_stroke_length: 0.0,
+        cap_style: CapStyle::Round,
+        taper_in: 0.0,
+        taper_out: 0.0,
+        angle_constraint: 0.0,
+        airbrush: false,
+        airbrush_rate: 30.0,
+        smudge_length: 0.9,
+    };
+
+    /// Pencil: small, slightly soft graphite line
+    /// Pressure drives both size and flow, with a delayed flow curve so light strokes stay faint
+    pub const PENCIL: Self = Self {
+        size: 6.0,
+        flow: 0.6,
+        hardness: 0.8,
+        spacing: 0.1,
+        color: [0.2, 0.2, 0.22, 1.0],
+        pressure_mapping: PressureMapping::Both,
+        min_size_percent: 0.4,
+        max_size_percent: 1.0,
+        min_flow_percent: 0.1,
+        max_flow_percent: 1.0,
+        flow_gamma: 1.3,
+        ..Self::DEFAULT
+    };
+
+    /// Ink: hard-edged, fully opaque line whose width follows pressure
+    pub const INK: Self = Self {
+        size: 8.0,
+        flow: 1.0,
+        hardness: 1.0,
+        spacing: 0.05,
+        color: [0.0, 0.0, 0.0, 1.0],
+        pressure_mapping: PressureMapping::Size,
+        min_size_percent: 0.2,
+        max_size_percent: 1.0,
+        size_gamma: 0.8,
+        ..Self::DEFAULT
+    };
+
+    /// Marker: broad, constant-width stroke that builds up to a capped opacity
+    pub const MARKER: Self = Self {
+        size: 24.0,
+        flow: 0.8,
+        hardness: 0.9,
+        spacing: 0.1,
+        color: [0.1, 0.1, 0.1, 1.0],
+        pressure_mapping: PressureMapping::None,
+        max_opacity: 0.8,
+        ..Self::DEFAULT
+    };
+
+    /// Airbrush: large, fully soft spray with very low flow that builds up with pressure
+    pub const AIRBRUSH: Self = Self {
+        size: 80.0,
+        flow: 0.05,
+        hardness: 0.0,
+        spacing: 0.05,
+        color: [0.1, 0.1, 0.1, 1.0],
+        pressure_mapping: PressureMapping::Flow,
+        min_flow_percent: 0.0,
+        max_flow_percent: 1.0,
+        flow_gamma: 1.5,
+        ..Self::DEFAULT
+    };
+
+    /// Eraser: large, mostly hard brush that removes paint down to transparent
+    pub const ERASER: Self = Self {
+        size: 40.0,
+        flow: 1.0,
+        hardness: 0.9,
+        spacing: 0.1,
+        pressure_mapping: PressureMapping::None,
+        eraser: true,
+        ..Self::DEFAULT
+    };
+
+    /// Names of the built-in presets, in menu order
+    const PRESET_NAMES: [&'static str; 5] = ["Pencil", "Ink", "Marker", "Airbrush", "Eraser"];
+
+    /// List the names of the built-in brush presets
+    pub fn list_presets() -> Vec<&'static str> {
+        Self::PRESET_NAMES.to_vec()
+    }
+
+    /// Look up a built-in brush preset by name (case-insensitive)
+    pub fn preset(name: &str) -> Option<BrushParams> {
+        match name.to_ascii_lowercase().as_str() {
+            "pencil" => Some(Self::PENCIL),
+            "ink" => Some(Self::INK),
+            "marker" => Some(Self::MARKER),
+            "airbrush" => Some(Self::AIRBRUSH),
+            "eraser" => Some(Self::ERASER),
+            _ => None,
+        }
+    }
+
+    /// Switch to a built-in brush preset by name
+    ///
+    /// The current color and input filter mode are kept (they're user choices, not part
+    /// of the brush feel).
+    /// Returns false, leaving the parameters unchanged, if no preset with that name exists.
+    pub fn apply_preset(&mut self, name: &str) -> bool {
+        let Some(mut preset) = Self::preset(name) else {
+            return false;
+        };
+        preset.input_filter_mode = self.input_filter_mode;
+        preset.color = self.color;
+        *self = preset;
+        true
+    }
+
     /// Create new brush parameters with specified values
     pub fn new(size: f32, flow: f32, hardness: f32, spacing: f32, color: [f32; 4]) -> Self {
         Self {
@@ -59,26 +372,30 @@ impl BrushParams {
         }
     }
 
-    /// Apply gamma curve and map pressure to a range [min, max]
+    /// Apply the response curve and map pressure to a range [min, max]
     /// 
     /// # Arguments
     /// * `pressure` - Raw pressure value (0.0-1.0)
     /// * `gamma` - Gamma curve exponent (<1.0 = aggressive early response, =1.0 = linear, >1.0 = delayed response)
+    /// * `curve` - Control point curve used instead of the gamma when set
     /// * `min` - Minimum output value at zero pressure
     /// * `max` - Maximum output value at full pressure
     /// 
     /// # Returns
     /// Mapped value in the range [min, max]
-    fn apply_pressure_curve(pressure: f32, gamma: f32, min: f32, max: f32) -> f32 {
+    fn apply_pressure_curve(pressure: f32, gamma: f32, curve: Option<&PressureCurve>, min: f32, max: f32) -> f32 {
         let pressure_clamped = pressure.clamp(0.0, 1.0);
-        let curved = pressure_clamped.powf(gamma);
+        let curved = match curve {
+            Some(curve) => curve.evaluate(pressure_clamped),
+            None => pressure_clamped.powf(gamma),
+        };
         min + curved * (max - min)
     }
 
     /// Validate that parameters are in acceptable ranges
     pub fn validate(&self) -> Result<(), String> {
-        if self.size <= 0.0 {
-            return Err("Brush size must be positive".to_string());
+        if !self.size.is_finite() || self.size <= 0.0 {
+            return Err("Brush size must be a positive number".to_string());
         }
         if !(0.0..=1.0).contains(&self.flow) {
             return Err("Flow must be between 0.0 and 1.0".to_string());
@@ -86,30 +403,179 @@ impl BrushParams {
         if !(0.0..=1.0).contains(&self.hardness) {
             return Err("Hardness must be between 0.0 and 1.0".to_string());
         }
-        if !(0.0..=1.0).contains(&self.spacing) {
-            return Err("Spacing must be between 0.0 and 1.0".to_string());
+        match self.spacing_mode {
+            SpacingMode::DiameterFraction if !(0.0..=1.0).contains(&self.spacing) => {
+                return Err("Spacing must be between 0.0 and 1.0".to_string());
+            }
+            SpacingMode::AbsolutePixels if !(self.spacing.is_finite() && self.spacing > 0.0) => {
+                return Err("Absolute spacing must be a positive number of pixels".to_string());
+            }
+            _ => {}
+        }
+        if !(0.0..=1.0).contains(&self.max_opacity) {
+            return Err("Max opacity must be between 0.0 and 1.0".to_string());
+        }
+        if !(0.0..1.0).contains(&self.pressure_smoothing) {
+            return Err("Pressure smoothing must be at least 0.0 and less than 1.0".to_string());
+        }
+        if !(0.0..1.0).contains(&self.velocity_smoothing) {
+            return Err("Velocity smoothing must be at least 0.0 and less than 1.0".to_string());
+        }
+        if !self.velocity_max_speed.is_finite() || self.velocity_max_speed <= 0.0 {
+            return Err("Velocity max speed must be positive".to_string());
+        }
+        if !(0.0..1.0).contains(&self.angle_smoothing) {
+            return Err("Angle smoothing must be at least 0.0 and less than 1.0".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.line_assist) {
+            return Err("Line assist strength must be between 0.0 and 1.0".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.stabilization) {
+            return Err("Stabilization must be between 0.0 and 1.0".to_string());
+        }
+        if !self.max_stroke_length.is_finite() || self.max_stroke_length < 0.0 {
+            return Err("Max stroke length must not be negative".to_string());
+        }
+        if !(self.taper_in >= 0.0 && self.taper_out >= 0.0 && (self.taper_in + self.taper_out).is_finite()) {
+            return Err("Taper lengths must not be negative".to_string());
+        }
+        if !(0.0..=180.0).contains(&self.angle_constraint) {
+            return Err("Angle constraint must be between 0 and 180 degrees".to_string());
+        }
+        if ![self.hue_jitter, self.saturation_jitter, self.value_jitter].iter().all(|jitter| (0.0..=1.0).contains(jitter)) {
+            return Err("Color jitter must be between 0.0 and 1.0".to_string());
+        }
+        if !self.min_tap_dab_size.is_finite() || self.min_tap_dab_size < 0.0 {
+            return Err("Minimum tap dab size must not be negative".to_string());
+        }
+        if !(self.airbrush_rate > 0.0 && self.airbrush_rate <= MAX_AIRBRUSH_RATE) {
+            return Err(format!("Airbrush rate must be above 0 and at most {} dabs per second", MAX_AIRBRUSH_RATE));
+        }
+        if !(0.0..=1.0).contains(&self.smudge_length) {
+            return Err("Smudge length must be between 0.0 and 1.0".to_string());
+        }
+        if let PressureResponse::Curve(curve) = &self.pressure_response {
+            curve.validate()?;
+        }
+        if let TipShape::Polygon(sides) = self.tip_shape {
+            if !(TipShape::MIN_SIDES..=TipShape::MAX_SIDES).contains(&sides) {
+                return Err(format!(
+                    "Polygon tip must have between {} and {} sides",
+                    TipShape::MIN_SIDES,
+                    TipShape::MAX_SIDES
+                ));
+            }
         }
         Ok(())
     }
+
+    /// Bring out-of-range values back into range, keeping everything that is already valid
+    ///
+    /// For parameters from outside the app (saved presets, hand-edited JSON). Values are
+    /// clamped to the nearest valid value; non-finite values, invalid pressure curves, and
+    /// unsupported polygon tips fall back to the defaults. The result always passes `validate`.
+    pub fn repaired(self) -> Self {
+        /// Largest smoothing factor; 1.0 would freeze the smoothed value
+        const MAX_SMOOTHING: f32 = 0.99;
+        let default = Self::DEFAULT;
+        let fix = |value: f32, default: f32, min: f32, max: f32| {
+            if value.is_finite() {
+                value.clamp(min, max)
+            } else {
+                default
+            }
+        };
+        let unit = |value: f32, default: f32| fix(value, default, 0.0, 1.0);
+        let non_negative = |value: f32, default: f32| fix(value, default, 0.0, f32::MAX);
+
+        let spacing = match self.spacing_mode {
+            SpacingMode::DiameterFraction => unit(self.spacing, default.spacing),
+            SpacingMode::AbsolutePixels => fix(self.spacing, 1.0, f32::MIN_POSITIVE, f32::MAX),
+        };
+        let pressure_response = match self.pressure_response {
+            PressureResponse::Curve(curve) if curve.validate().is_err() => PressureResponse::Gamma,
+            response => response,
+        };
+        let tip_shape = match self.tip_shape {
+            TipShape::Polygon(sides) => TipShape::Polygon(sides.clamp(TipShape::MIN_SIDES, TipShape::MAX_SIDES)),
+            shape => shape,
+        };
+
+        Self {
+            size: fix(self.size, default.size, f32::MIN_POSITIVE, f32::MAX),
+            flow: unit(self.flow, default.flow),
+            hardness: unit(self.hardness, default.hardness),
+            spacing,
+            color: [0, 1, 2, 3].map(|i| unit(self.color[i], default.color[i])),
+            min_size_percent: non_negative(self.min_size_percent, default.min_size_percent),
+            max_size_percent: non_negative(self.max_size_percent, default.max_size_percent),
+            min_flow_percent: non_negative(self.min_flow_percent, default.min_flow_percent),
+            max_flow_percent: non_negative(self.max_flow_percent, default.max_flow_percent),
+            size_gamma: fix(self.size_gamma, default.size_gamma, f32::MIN_POSITIVE, f32::MAX),
+            flow_gamma: fix(self.flow_gamma, default.flow_gamma, f32::MIN_POSITIVE, f32::MAX),
+            min_hardness_percent: non_negative(self.min_hardness_percent, default.min_hardness_percent),
+            max_hardness_percent: non_negative(self.max_hardness_percent, default.max_hardness_percent),
+            hardness_gamma: fix(self.hardness_gamma, default.hardness_gamma, f32::MIN_POSITIVE, f32::MAX),
+            pressure_response,
+            velocity_max_speed: fix(self.velocity_max_speed, default.velocity_max_speed, 1.0, f32::MAX),
+            min_velocity_percent: non_negative(self.min_velocity_percent, default.min_velocity_percent),
+            max_velocity_percent: non_negative(self.max_velocity_percent, default.max_velocity_percent),
+            velocity_gamma: fix(self.velocity_gamma, default.velocity_gamma, f32::MIN_POSITIVE, f32::MAX),
+            velocity_smoothing: fix(self.velocity_smoothing, default.velocity_smoothing, 0.0, MAX_SMOOTHING),
+            max_opacity: unit(self.max_opacity, default.max_opacity),
+            pressure_smoothing: fix(self.pressure_smoothing, default.pressure_smoothing, 0.0, MAX_SMOOTHING),
+            angle_smoothing: fix(self.angle_smoothing, default.angle_smoothing, 0.0, MAX_SMOOTHING),
+            tip_shape,
+            hue_jitter: unit(self.hue_jitter, default.hue_jitter),
+            saturation_jitter: unit(self.saturation_jitter, default.saturation_jitter),
+            value_jitter: unit(self.value_jitter, default.value_jitter),
+            min_tap_dab_size: non_negative(self.min_tap_dab_size, default.min_tap_dab_size),
+            line_assist: unit(self.line_assist, default.line_assist),
+            stabilization: unit(self.stabilization, default.stabilization),
+            max_stroke_length: non_negative(self.max_stroke_length, default.max_stroke_length),
+            taper_in: fix(self.taper_in, default.taper_in, 0.0, f32::MAX / 2.0),
+            taper_out: fix(self.taper_out, default.taper_out, 0.0, f32::MAX / 2.0),
+            angle_constraint: fix(self.angle_constraint, default.angle_constraint, 0.0, 180.0),
+            airbrush_rate: fix(self.airbrush_rate, default.airbrush_rate, f32::MIN_POSITIVE, MAX_AIRBRUSH_RATE),
+            smudge_length: unit(self.smudge_length, default.smudge_length),
+            ..self
+        }
+    }
+
+    /// Opacity each stroke is composited at when it goes through the stroke layer
+    ///
+    /// In Uniform opacity mode dabs are drawn at full flow and the flow is applied to the
+    /// whole stroke instead, so it is the max opacity scaled by the flow.
+    pub fn stroke_opacity(&self) -> f32 {
+        match self.opacity_mode {
+            OpacityMode::Uniform => self.max_opacity * self.flow.clamp(0.0, 1.0),
+            OpacityMode::Accumulate | OpacityMode::PerStroke => self.max_opacity,
+        }
+    }
+
+    /// Serialize to JSON, for saving as a user preset
+    pub fn to_json(&self) -> String {
+        // Plain data with string keys: serialization cannot fail
+        serde_json::to_string(self).expect("Brush params are always serializable")
+    }
+
+    /// Parse brush parameters from JSON produced by `to_json`
+    ///
+    /// Missing fields take their defaults and out-of-range values are repaired (see
+    /// `repaired`); only malformed JSON is an error.
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let params: Self = serde_json::from_str(json).map_err(|e| format!("Invalid brush JSON: {}", e))?;
+        let repaired = params.repaired();
+        if let Err(e) = params.validate() {
+            log::warn!("Repaired imported brush settings: {}", e);
+        }
+        Ok(repaired)
+    }
 }
 
 impl Default for BrushParams {
     fn default() -> Self {
-        Self {
-            size: 30.0,
-            flow: 1.0,
-            hardness: 1.0,
-            spacing: 0.15,
-            color: [163.0 / 255.0, 2.0 / 255.0, 222.0 / 255.0, 1.0],
-            pressure_mapping: PressureMapping::Flow,
-            min_size_percent: 1.0,
-            max_size_percent: 3.2,
-            min_flow_percent: 0.0,
-            max_flow_percent: 3.0,
-            size_gamma: 1.0,
-            flow_gamma: 1.0,
-            input_filter_mode: InputFilterMode::default(),
-        }
+        Self::DEFAULT
     }
 }
 
@@ -126,10 +592,224 @@ pub struct BrushDab {
     pub color: [f32; 4],
     /// Hardness (0.0-1.0)
     pub hardness: f32,
+    /// Rotation in radians from the smoothed stylus azimuth (or twist if no azimuth)
+    /// Round dabs are rotationally symmetric, so this only affects polygon and textured tips
+    pub angle: f32,
+    /// Shape of the dab
+    pub tip_shape: TipShape,
+    /// Half-planes the dab is trimmed against, for flat and square stroke caps
+    pub clips: [Option<DabClip>; 2],
+    /// Whether this is a square cap piece: a square aligned with `angle` instead of the tip
+    pub square_cap: bool,
+    /// Offset in pixels from the previous dab of the stroke to this one (zero for the first)
+    /// The smudge tool pulls each dab's color from this far back along the stroke.
+    pub smudge_offset: [f32; 2],
+}
+
+/// Half-plane a dab is trimmed against
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DabClip {
+    /// Unit normal pointing toward the trimmed side, in canvas space
+    pub normal: [f32; 2],
+    /// Distance from the dab center to the clip line along the normal, in pixels
+    pub offset: f32,
+}
+
+impl BrushDab {
+    /// Linear map from the dab's local frame (the -1 to 1 unit quad) to canvas pixels,
+    /// as columns `[x_axis, y_axis]`
+    ///
+    /// The shader places and shapes every dab through this one matrix, so rotation and
+    /// any non-uniform scale apply to all tip shapes, stamps, and masks alike.
+    pub fn transform(&self) -> [[f32; 2]; 2] {
+        let radius = self.size * 0.5;
+        let (sin, cos) = self.angle.sin_cos();
+        [[cos * radius, sin * radius], [-sin * radius, cos * radius]]
+    }
+}
+
+impl DabClip {
+    /// The clip half-plane in a dab's local frame: unit normal and offset in local units
+    ///
+    /// `transform` is the dab's local → canvas map (see `BrushDab::transform`).
+    pub fn in_dab_frame(&self, transform: [[f32; 2]; 2]) -> ([f32; 2], f32) {
+        // dot(n, M * p) = dot(Mᵀ * n, p): pull the canvas normal back through the map
+        let [x_axis, y_axis] = transform;
+        let normal = [
+            x_axis[0] * self.normal[0] + x_axis[1] * self.normal[1],
+            y_axis[0] * self.normal[0] + y_axis[1] * self.normal[1],
+        ];
+        let length = (normal[0] * normal[0] + normal[1] * normal[1]).sqrt().max(f32::EPSILON);
+        ([normal[0] / length, normal[1] / length], self.offset / length)
+    }
+}
+
+/// How each dab is repeated for symmetric drawing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum SymmetryMode {
+    /// Dabs are drawn once
+    None,
+    /// Mirrored across a vertical line (left/right symmetry)
+    Vertical,
+    /// Mirrored across a horizontal line (top/bottom symmetry)
+    Horizontal,
+    /// Repeated the given number of times (the original included), rotated evenly
+    /// about a center point
+    Radial(u32),
+}
+
+impl SymmetryMode {
+    /// Fewest copies radial symmetry can have
+    pub const MIN_RADIAL: u32 = 2;
+    /// Most copies radial symmetry can have
+    pub const MAX_RADIAL: u32 = 64;
+}
+
+impl Default for SymmetryMode {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+/// Symmetry guide: the mode and the point its axis or center sits at
+///
+/// Positions are in canvas space, like dabs, so the guide stays attached to the drawing
+/// whatever the view's pan, zoom, and rotation.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Symmetry {
+    /// How dabs are repeated
+    pub mode: SymmetryMode,
+    /// Point the mirror line passes through (Vertical uses x, Horizontal uses y), or the
+    /// radial center, in canvas pixels
+    pub center: [f32; 2],
+}
+
+impl Symmetry {
+    /// No symmetry
+    pub const NONE: Self = Self { mode: SymmetryMode::None, center: [0.0, 0.0] };
+
+    /// Validate the radial copy count and the center
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.center.iter().all(|c| c.is_finite()) {
+            return Err("Symmetry center must be finite".to_string());
+        }
+        if let SymmetryMode::Radial(copies) = self.mode {
+            if !(SymmetryMode::MIN_RADIAL..=SymmetryMode::MAX_RADIAL).contains(&copies) {
+                return Err(format!(
+                    "Radial symmetry must have between {} and {} copies",
+                    SymmetryMode::MIN_RADIAL,
+                    SymmetryMode::MAX_RADIAL
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Append the symmetric copies of `dabs` after the originals
+    ///
+    /// Copies keep each dab's size, opacity, and color, with the position, rotation, cap
+    /// trims, and smudge offset mirrored or rotated. Mirrored copies are drawn with the mirrored rotation,
+    /// which matches a true reflection for round and polygon tips; stamp textures are
+    /// rotated, not flipped.
+    pub fn apply(&self, dabs: &mut Vec<BrushDab>) {
+        let count = dabs.len();
+        let [cx, cy] = self.center;
+        match self.mode {
+            SymmetryMode::None => {}
+            // Reflecting a rotation θ is rotation -θ followed by a local x flip, which
+            // round and polygon tips are symmetric under
+            SymmetryMode::Vertical => {
+                for i in 0..count {
+                    let dab = dabs[i];
+                    dabs.push(BrushDab {
+                        position: [2.0 * cx - dab.position[0], dab.position[1]],
+                        angle: -dab.angle,
+                        clips: dab.clips.map(|clip| clip.map(|c| DabClip { normal: [-c.normal[0], c.normal[1]], ..c })),
+                        smudge_offset: [-dab.smudge_offset[0], dab.smudge_offset[1]],
+                        ..dab
+                    });
+                }
+            }
+            // A horizontal mirror is a vertical one turned half a turn: rotation π - θ
+            SymmetryMode::Horizontal => {
+                for i in 0..count {
+                    let dab = dabs[i];
+                    dabs.push(BrushDab {
+                        position: [dab.position[0], 2.0 * cy - dab.position[1]],
+                        angle: std::f32::consts::PI - dab.angle,
+                        clips: dab.clips.map(|clip| clip.map(|c| DabClip { normal: [c.normal[0], -c.normal[1]], ..c })),
+                        smudge_offset: [dab.smudge_offset[0], -dab.smudge_offset[1]],
+                        ..dab
+                    });
+                }
+            }
+            SymmetryMode::Radial(copies) => {
+                for k in 1..copies {
+                    let turn = std::f32::consts::TAU * k as f32 / copies as f32;
+                    let (sin, cos) = turn.sin_cos();
+                    let rotate = |v: [f32; 2]| [v[0] * cos - v[1] * sin, v[0] * sin + v[1] * cos];
+                    for i in 0..count {
+                        let dab = dabs[i];
+                        let offset = rotate([dab.position[0] - cx, dab.position[1] - cy]);
+                        dabs.push(BrushDab {
+                            position: [cx + offset[0], cy + offset[1]],
+                            angle: dab.angle + turn,
+                            clips: dab.clips.map(|clip| clip.map(|c| DabClip { normal: rotate(c.normal), ..c })),
+                            smudge_offset: rotate(dab.smudge_offset),
+                            ..dab
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Default for Symmetry {
+    fn default() -> Self {
+        Self::NONE
+    }
+}
+
+/// What strokes do to the canvas
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Tool {
+    /// Dabs paint with the brush color (or erase, in eraser mode)
+    Brush,
+    /// Dabs drag existing paint along the stroke, each laying down the given fraction
+    /// (0.0-1.0) of the color it carries (see `BrushParams::smudge_length`)
+    /// The brush size, hardness, and flow still shape each dab; its color is unused.
+    Smudge(f32),
+}
+
+impl Tool {
+    /// Validate the smudge strength
+    pub fn validate(&self) -> Result<(), String> {
+        if let Tool::Smudge(strength) = self {
+            if !(0.0..=1.0).contains(strength) {
+                return Err("Smudge strength must be between 0.0 and 1.0".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// Smudge strength, or None for the brush
+    pub fn smudge_strength(&self) -> Option<f32> {
+        match self {
+            Tool::Brush => None,
+            Tool::Smudge(strength) => Some(*strength),
+        }
+    }
+}
+
+impl Default for Tool {
+    fn default() -> Self {
+        Self::Brush
+    }
 }
 
 /// Controls how input pressure affects brush parameters
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub enum PressureMapping {
     /// Pressure controls opacity/flow
     Flow,
@@ -137,6 +817,10 @@ pub enum PressureMapping {
     Size,
     /// Pressure controls both size and flow
     Both,
+    /// Pressure controls edge hardness (harder when pressed, like a felt tip)
+    Hardness,
+    /// Pressure controls size, flow, and hardness
+    All,
     /// No pressure sensitivity
     None,
 }
@@ -147,8 +831,270 @@ impl Default for PressureMapping {
     }
 }
 
+/// Controls how pen speed affects brush parameters
+/// Faster movement scales the mapped properties down, like a real brush thinning out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum VelocityMapping {
+    /// Speed controls opacity/flow
+    Flow,
+    /// Speed controls size
+    Size,
+    /// Speed controls both size and flow
+    Both,
+    /// No speed sensitivity
+    None,
+}
+
+impl Default for VelocityMapping {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+/// Pressure response curve through normalized (input, output) control points
+///
+/// Points are joined with monotone cubic interpolation (Fritsch-Carlson), so the curve is
+/// smooth but never overshoots between points: a flat run stays flat and a rising run never
+/// dips. Pressure below the first point or above the last holds that point's output.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct PressureCurve {
+    points: [[f32; 2]; PressureCurve::MAX_POINTS],
+    len: usize,
+}
+
+impl PressureCurve {
+    /// Most control points a curve can hold
+    pub const MAX_POINTS: usize = 8;
+
+    /// Create a curve from 2 to 8 (input, output) points, both 0.0-1.0, in ascending input order
+    pub fn new(points: &[[f32; 2]]) -> Result<Self, String> {
+        if !(2..=Self::MAX_POINTS).contains(&points.len()) {
+            return Err(format!(
+                "Pressure curve needs between 2 and {} points, got {}",
+                Self::MAX_POINTS,
+                points.len()
+            ));
+        }
+        let mut curve = Self { points: [[0.0; 2]; Self::MAX_POINTS], len: points.len() };
+        curve.points[..points.len()].copy_from_slice(points);
+        curve.validate()?;
+        Ok(curve)
+    }
+
+    /// Create a curve from flat input/output pairs: `[in0, out0, in1, out1, ...]`
+    pub fn from_flat(values: &[f32]) -> Result<Self, String> {
+        let pairs = values.chunks_exact(2);
+        if !pairs.remainder().is_empty() {
+            return Err("Pressure curve values must come in (input, output) pairs".to_string());
+        }
+        let points: Vec<[f32; 2]> = pairs.map(|pair| [pair[0], pair[1]]).collect();
+        Self::new(&points)
+    }
+
+    /// Control points in ascending input order
+    pub fn points(&self) -> &[[f32; 2]] {
+        &self.points[..self.len.min(Self::MAX_POINTS)]
+    }
+
+    /// Check the point count, ranges, and ordering (curves can also come from saved configs)
+    pub fn validate(&self) -> Result<(), String> {
+        if !(2..=Self::MAX_POINTS).contains(&self.len) {
+            return Err(format!("Pressure curve needs between 2 and {} points", Self::MAX_POINTS));
+        }
+        let points = self.points();
+        if points.iter().flatten().any(|value| !(0.0..=1.0).contains(value)) {
+            return Err("Pressure curve points must be between 0.0 and 1.0".to_string());
+        }
+        if points.windows(2).any(|pair| pair[1][0] <= pair[0][0]) {
+            return Err("Pressure curve inputs must be in strictly ascending order".to_string());
+        }
+        Ok(())
+    }
+
+    /// Output of the curve at a pressure (0.0-1.0)
+    pub fn evaluate(&self, pressure: f32) -> f32 {
+        let points = self.points();
+        let (first, last) = (points[0], points[points.len() - 1]);
+        if pressure <= first[0] {
+            return first[1];
+        }
+        if pressure >= last[0] {
+            return last[1];
+        }
+
+        // Secant slopes of each segment
+        let mut secants = [0.0; Self::MAX_POINTS];
+        for (i, pair) in points.windows(2).enumerate() {
+            secants[i] = (pair[1][1] - pair[0][1]) / (pair[1][0] - pair[0][0]);
+        }
+        let segments = points.len() - 1;
+
+        // Tangents: averaged secants, flattened at local extrema
+        let mut tangents = [0.0; Self::MAX_POINTS];
+        tangents[0] = secants[0];
+        tangents[segments] = secants[segments - 1];
+        for i in 1..segments {
+            tangents[i] = if secants[i - 1] * secants[i] <= 0.0 {
+                0.0
+            } else {
+                (secants[i - 1] + secants[i]) * 0.5
+            };
+        }
+        // Limit tangents so each segment stays monotone
+        for i in 0..segments {
+            if secants[i] == 0.0 {
+                tangents[i] = 0.0;
+                tangents[i + 1] = 0.0;
+                continue;
+            }
+            let a = tangents[i] / secants[i];
+            let b = tangents[i + 1] / secants[i];
+            let length_sq = a * a + b * b;
+            if length_sq > 9.0 {
+                let scale = 3.0 / length_sq.sqrt();
+                tangents[i] = scale * a * secants[i];
+                tangents[i + 1] = scale * b * secants[i];
+            }
+        }
+
+        // Cubic Hermite on the segment containing the pressure
+        let i = points.windows(2).position(|pair| pressure < pair[1][0]).unwrap_or(segments - 1);
+        let (p0, p1) = (points[i], points[i + 1]);
+        let h = p1[0] - p0[0];
+        let t = (pressure - p0[0]) / h;
+        let t2 = t * t;
+        let t3 = t2 * t;
+        let value = (2.0 * t3 - 3.0 * t2 + 1.0) * p0[1]
+            + (t3 - 2.0 * t2 + t) * h * tangents[i]
+            + (-2.0 * t3 + 3.0 * t2) * p1[1]
+            + (t3 - t2) * h * tangents[i + 1];
+        value.clamp(0.0, 1.0)
+    }
+}
+
+/// How pressure is shaped before it maps to size, flow, and hardness
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum PressureResponse {
+    /// Each property's own gamma exponent (`size_gamma`, `flow_gamma`, `hardness_gamma`)
+    Gamma,
+    /// One control point curve shared by every property (the gammas are ignored)
+    Curve(PressureCurve),
+}
+
+impl PressureResponse {
+    /// The control point curve, if one replaces the gammas
+    pub fn curve(&self) -> Option<&PressureCurve> {
+        match self {
+            Self::Gamma => None,
+            Self::Curve(curve) => Some(curve),
+        }
+    }
+}
+
+impl Default for PressureResponse {
+    fn default() -> Self {
+        Self::Gamma
+    }
+}
+
+/// Controls how dab coverage combines into the canvas
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum OpacityMode {
+    /// Dabs blend straight into the canvas, capped by the max opacity
+    Accumulate,
+    /// Dabs build up coverage in a scratch layer that is composited into the canvas
+    /// once at max opacity, so overlaps within a stroke never exceed it
+    PerStroke,
+    /// Dabs are drawn into the scratch layer at full flow and the stroke is composited
+    /// once at flow × max opacity, so a low-flow stroke has one even opacity however its
+    /// dabs overlap (like Procreate's brush opacity)
+    Uniform,
+}
+
+impl Default for OpacityMode {
+    fn default() -> Self {
+        Self::Accumulate
+    }
+}
+
+/// Shape of a brush dab
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum TipShape {
+    /// Circular dab
+    Round,
+    /// Regular polygon with the given number of sides (e.g. 3 = triangle, 4 = square)
+    /// inscribed in the dab's diameter, with a flat edge at the bottom before rotation
+    Polygon(u32),
+}
+
+impl TipShape {
+    /// Fewest sides a polygon tip can have
+    pub const MIN_SIDES: u32 = 3;
+    /// Most sides a polygon tip can have (beyond this it is indistinguishable from round)
+    pub const MAX_SIDES: u32 = 32;
+}
+
+impl Default for TipShape {
+    fn default() -> Self {
+        Self::Round
+    }
+}
+
+/// Shape of a stroke's start and end
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum CapStyle {
+    /// Ends are the round edge of the first/last dab
+    Round,
+    /// Ends are cut square and extended by half the brush size past the endpoint
+    Square,
+    /// Ends are cut square exactly at the endpoint
+    Flat,
+}
+
+impl Default for CapStyle {
+    fn default() -> Self {
+        Self::Round
+    }
+}
+
+/// Controls how dab color combines with the canvas
+///
+/// Multiply, Screen, Darken, and Lighten build each stroke up normally on the stroke
+/// layer, then blend the whole stroke with the canvas beneath in linear color (whatever
+/// the blend color space), so they always composite per stroke.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum BrushBlendMode {
+    /// Premultiplied alpha "over" (regular paint)
+    Normal,
+    /// Additive: dab color is added to the canvas and may exceed 1.0 in the float
+    /// canvas, for neon/glow effects (pair with bloom to make bright areas bleed)
+    AddHdr,
+    /// Canvas color times stroke color: darkens, like layering transparent inks
+    Multiply,
+    /// Inverse of multiplying the inverses: lightens, like overlapping projected light
+    Screen,
+    /// Darker of the canvas and stroke color, per channel
+    Darken,
+    /// Lighter of the canvas and stroke color, per channel
+    Lighten,
+}
+
+impl BrushBlendMode {
+    /// Check whether strokes are blended with the canvas as a whole (through the stroke
+    /// layer) rather than dab by dab
+    pub fn blends_per_stroke(self) -> bool {
+        matches!(self, Self::Multiply | Self::Screen | Self::Darken | Self::Lighten)
+    }
+}
+
+impl Default for BrushBlendMode {
+    fn default() -> Self {
+        Self::Normal
+    }
+}
+
 /// Controls which input sources are accepted for drawing
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub enum InputFilterMode {
     /// Only accept pen/stylus input (TabletTool)
     PenOnly,
@@ -162,7 +1108,66 @@ impl Default for InputFilterMode {
     }
 }
 
+/// Controls how the pressure for the first dab of a stroke is chosen
+///
+/// The first dab is deferred past the Down event because many styluses report
+/// unreliable pressure on contact. Some still report zero on the first move,
+/// which makes the stroke start invisibly; these modes work around that.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum FirstDabPressureMode {
+    /// Place the first dab on the first move, using that move's pressure
+    FirstMove,
+    /// Wait for this many move samples and use the maximum pressure seen
+    /// (the dab is still placed at the stroke's start position)
+    MaxOfFirstSamples(u32),
+    /// Wait until pressure reaches this threshold, then start the stroke there
+    Threshold(f32),
+}
+
+impl Default for FirstDabPressureMode {
+    fn default() -> Self {
+        Self::FirstMove
+    }
+}
+
+/// Controls whether the first dab of a stroke waits for the pen to move
+///
+/// Deferring gives the first dab a more reliable pressure, but a pure tap never moves, so
+/// stamp and stippling brushes want each tap to leave a mark right away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum DownDabMode {
+    /// Instant for stamp-like brushes (spacing of half the diameter or more), deferred otherwise
+    Auto,
+    /// Always place the first dab on Down, using the Down pressure
+    Instant,
+    /// Always defer the first dab until the pen moves (see `FirstDabPressureMode`)
+    Deferred,
+}
+
+impl Default for DownDabMode {
+    fn default() -> Self {
+        Self::Auto
+    }
+}
+
+/// How brush spacing is measured
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum SpacingMode {
+    /// Spacing is a fraction of the dab diameter, so gaps grow and shrink with the brush
+    DiameterFraction,
+    /// Spacing is a fixed distance in canvas pixels, independent of brush size
+    /// Keeps very large brushes dense without retuning spacing per size.
+    AbsolutePixels,
+}
+
+impl Default for SpacingMode {
+    fn default() -> Self {
+        Self::DiameterFraction
+    }
+}
+
 /// Brush state that tracks the current stroke
+#[derive(Clone)]
 pub struct BrushState {
     /// Current brush parameters
     pub params: BrushParams,
@@ -172,23 +1177,69 @@ pub struct BrushState {
     last_dab_pressure: f32,
     /// Whether the last dab was the first in the stroke
     has_moved: bool,
+    /// Number of move samples seen while the first dab is deferred
+    first_dab_samples: u32,
+    /// Maximum pressure seen while the first dab is deferred
+    first_dab_max_pressure: f32,
     /// Whether the brush is currently down (in a stroke)
     brush_down: bool,
     /// Source of the brush input (Mouse, Touch, TabletTool, Unknown)
     brush_src: PointerEventSource,
+    /// Smoothed stylus azimuth in radians, if the device reports it
+    smoothed_azimuth: Option<f32>,
+    /// Smoothed stylus twist in degrees, if the device reports it
+    smoothed_twist: Option<f32>,
+    /// Smoothed pressure for the current stroke (None until the first move)
+    smoothed_pressure: Option<f32>,
+    /// Stabilized pen position for the current stroke (None until the stroke starts)
+    stabilized_position: Option<[f32; 2]>,
+    /// Pen position the stabilized position is following (None until the stroke starts)
+    pen_position: Option<[f32; 2]>,
+    /// Timestamp in milliseconds of the input event being processed (None if never given)
+    input_time: Option<f64>,
+    /// Position and timestamp the next speed measurement is taken from
+    speed_sample: Option<([f32; 2], f64)>,
+    /// Smoothed pen speed in canvas pixels per second for the current stroke
+    smoothed_speed: f32,
+    /// Screen pixels per canvas pixel, for zoom-aware spacing
+    view_zoom: f32,
+    /// Recent input points (position, pressure) of the current stroke, for the spline
+    spline_points: VecDeque<([f32; 2], f32)>,
+    /// Recent raw input positions of the current stroke, for the line assist fit
+    line_assist_points: VecDeque<[f32; 2]>,
+    /// Current line assist pull (0.0-1.0), eased toward its target so it engages gradually
+    line_assist_weight: f32,
+    /// Distance covered by dabs in the current stroke segment, for the max stroke length split
+    segment_length: f32,
+    /// Stroke caps: dabs held back until they are known not to need the end cap, with
+    /// their distance along the stroke
+    cap_held_dabs: Vec<(BrushDab, f32)>,
+    /// Stroke caps: distance along the stroke of the newest dab
+    cap_path_length: f32,
+    /// Stroke caps: position of the newest dab
+    cap_last_position: Option<[f32; 2]>,
+    /// Stroke caps: position of the first dab
+    cap_start: Option<[f32; 2]>,
+    /// Stroke caps: outward normal at the start, once the stroke has moved far enough to tell
+    cap_start_normal: Option<[f32; 2]>,
+    /// Angle constraint: stroke start the constrained axis passes through
+    constraint_anchor: Option<[f32; 2]>,
+    /// Angle constraint: unit direction of the axis, once the pen is far enough to lock it
+    constraint_axis: Option<[f32; 2]>,
+    /// Random rotation: generator state (xorshift32, never zero)
+    rotation_rng: u32,
+    /// Color jitter: generator state (xorshift32), 0 until the stroke's first dab seeds it
+    jitter_rng: u32,
+    /// Smudge: position of the previous dab of the stroke
+    smudge_anchor: Option<[f32; 2]>,
+    /// Airbrush: time in milliseconds the held pen last deposited (or moved the stroke on)
+    airbrush_time: Option<f64>,
 }
 
 impl BrushState {
     /// Create a new brush state with default parameters
     pub fn new() -> Self {
-        Self {
-            params: BrushParams::default(),
-            last_dab_position: None,
-            last_dab_pressure: 1.0,
-            has_moved: false,
-            brush_down: false,
-            brush_src: PointerEventSource::Unknown,
-        }
+        Self::with_params(BrushParams::default())
     }
 
     /// Create a new brush state with specified parameters
@@ -198,18 +1249,85 @@ impl BrushState {
             last_dab_position: None,
             last_dab_pressure: 1.0,
             has_moved: false,
+            first_dab_samples: 0,
+            first_dab_max_pressure: 0.0,
             brush_down: false,
             brush_src: PointerEventSource::Unknown,
+            smoothed_azimuth: None,
+            smoothed_twist: None,
+            smoothed_pressure: None,
+            stabilized_position: None,
+            pen_position: None,
+            input_time: None,
+            speed_sample: None,
+            smoothed_speed: 0.0,
+            view_zoom: 1.0,
+            spline_points: VecDeque::with_capacity(4),
+            line_assist_points: VecDeque::with_capacity(LINE_ASSIST_WINDOW),
+            line_assist_weight: 0.0,
+            segment_length: 0.0,
+            cap_held_dabs: Vec::new(),
+            cap_path_length: 0.0,
+            cap_last_position: None,
+            cap_start: None,
+            cap_start_normal: None,
+            constraint_anchor: None,
+            constraint_axis: None,
+            rotation_rng: RANDOM_ROTATION_SEED,
+            jitter_rng: 0,
+            smudge_anchor: None,
+            airbrush_time: None,
+        }
+    }
+
+    /// Set the current view zoom (screen pixels per canvas pixel) for zoom-aware spacing
+    pub fn set_view_zoom(&mut self, zoom: f32) {
+        self.view_zoom = if zoom.is_finite() && zoom > 0.0 { zoom } else { 1.0 };
+    }
+
+    /// Whether the first dab of a stroke is placed on Down rather than deferred
+    fn places_dab_on_down(&self) -> bool {
+        match self.params.down_dab_mode {
+            DownDabMode::Auto => {
+                let spacing = match self.params.spacing_mode {
+                    SpacingMode::DiameterFraction => self.params.spacing,
+                    SpacingMode::AbsolutePixels => self.params.spacing / self.params.size,
+                };
+                spacing >= STAMP_SPACING_THRESHOLD
+            }
+            DownDabMode::Instant => true,
+            DownDabMode::Deferred => false,
         }
     }
 
+    /// Spacing in canvas pixels between dabs of the given size
+    ///
+    /// In absolute mode the size is ignored. With zoom-aware spacing, spacing shrinks by the
+    /// zoom so gaps stay the same size on screen. Either way it never drops below half a
+    /// canvas pixel, which caps how finely a segment is subdivided (and avoids infinite loops).
+    fn spacing_px(&self, size: f32) -> f32 {
+        let min_spacing_px = 0.5;
+        let zoom = if self.params.zoom_aware_spacing { self.view_zoom.max(1.0) } else { 1.0 };
+        let spacing = match self.params.spacing_mode {
+            SpacingMode::DiameterFraction => self.params.spacing * size,
+            SpacingMode::AbsolutePixels => self.params.spacing,
+        };
+        (spacing / zoom).max(min_spacing_px)
+    }
+
     /// Update the source of the brush input, potentially ending the stroke if source changes
-    pub fn update_brush_src(&mut self, source: PointerEventSource) {
+    ///
+    /// Returns the dabs still held back for the stroke's caps and tapers when it ends this
+    /// way, released with the end cap applied (empty otherwise).
+    pub fn update_brush_src(&mut self, source: PointerEventSource) -> Vec<BrushDab> {
+        let mut released = Vec::new();
         if self.brush_src != source && self.brush_down {
             // If source changed during stroke, end the stroke
+            released = self.apply_caps(Vec::new(), true);
             self.end_stroke();
         }
         self.brush_src = source;
+        released
     }
 
     /// Reset brush state to initial conditions
@@ -217,8 +1335,24 @@ impl BrushState {
         self.last_dab_position = None;
         self.last_dab_pressure = 0.0;
         self.has_moved = false;
+        self.first_dab_samples = 0;
+        self.first_dab_max_pressure = 0.0;
         self.brush_down = false;
         self.brush_src = PointerEventSource::Unknown;
+        self.smoothed_azimuth = None;
+        self.smoothed_twist = None;
+        self.stabilized_position = None;
+        self.pen_position = None;
+        self.speed_sample = None;
+        self.smoothed_speed = 0.0;
+        self.spline_points.clear();
+        self.line_assist_points.clear();
+        self.line_assist_weight = 0.0;
+        self.segment_length = 0.0;
+        self.constraint_anchor = None;
+        self.constraint_axis = None;
+        self.airbrush_time = None;
+        self.reset_caps();
     }
 
     /// Begin a new stroke (call when starting a new stroke)
@@ -226,146 +1360,1954 @@ impl BrushState {
         self.last_dab_position = None;
         self.last_dab_pressure = 0.0;
         self.has_moved = false;
+        self.first_dab_samples = 0;
+        self.first_dab_max_pressure = 0.0;
+        self.smoothed_azimuth = None;
+        self.smoothed_twist = None;
+        self.smoothed_pressure = None;
+        self.stabilized_position = None;
+        self.pen_position = None;
+        self.speed_sample = None;
+        self.smoothed_speed = 0.0;
+        self.spline_points.clear();
+        self.line_assist_points.clear();
+        self.line_assist_weight = 0.0;
+        self.segment_length = 0.0;
+        self.constraint_anchor = None;
+        self.constraint_axis = None;
+        self.jitter_rng = 0;
+        self.smudge_anchor = None;
+        self.airbrush_time = None;
+        self.reset_caps();
         self.brush_down = true;
     }
 
-    /// End the current stroke (call when finishing a stroke)
-    pub fn end_stroke(&mut self) {
-        self.reset_brush();
+    /// Forget the stroke cap state (held dabs are dropped)
+    fn reset_caps(&mut self) {
+        self.cap_held_dabs.clear();
+        self.cap_path_length = 0.0;
+        self.cap_last_position = None;
+        self.cap_start = None;
+        self.cap_start_normal = None;
     }
 
-    /// Calculate dabs for a segment from previous position to current position
-    /// Returns a vector of dabs to render
-    pub fn calculate_dabs(&mut self, position: [f32; 2], pressure: f32, event_type: crate::input::PointerEventType) -> Vec<BrushDab> {
-        let mut dabs = Vec::new();
-        // Only draw if brush is down
-        if !self.brush_down {
+    /// Trim the stroke's ends for flat and square caps, and taper them
+    ///
+    /// The end of a stroke isn't known until the pen lifts, so dabs are held back until
+    /// the stroke has moved more than a dab radius past them (no later end can reach them).
+    /// This delays the visible tip of the stroke by that much while drawing. Dabs within a
+    /// diameter of the start are trimmed by the start cap as they are released; on release
+    /// at the end, the remaining dabs are trimmed by the end cap. Square caps add a square
+    /// piece past each trim line.
+    ///
+    /// Tapers hold dabs back the same way, for the taper-out length, and nothing is
+    /// released until the stroke is longer than both tapers together (see `taper_scale`).
+    fn apply_caps(&mut self, dabs: Vec<BrushDab>, is_end: bool) -> Vec<BrushDab> {
+        let capped = self.params.cap_style != CapStyle::Round;
+        let tapered = self.params.taper_in > 0.0 || self.params.taper_out > 0.0;
+        if !capped && !tapered {
             return dabs;
         }
 
-        // Filter input based on input filter mode
-        if self.params.input_filter_mode == InputFilterMode::PenOnly {
-            // In PenOnly mode, only accept non-touch input
-            if self.brush_src == PointerEventSource::Touch {
-                log::debug!("Rejecting input from {:?} in PenOnly mode", self.brush_src);
-                return dabs;
+        for dab in dabs {
+            match self.cap_last_position {
+                Some(last) => self.cap_path_length += distance(last, dab.position),
+                None => self.cap_start = Some(dab.position),
+            }
+            self.cap_last_position = Some(dab.position);
+            // Measure the start direction a dab radius in, so jitter at the start doesn't skew it
+            if let (Some(start), None) = (self.cap_start, self.cap_start_normal) {
+                if distance(start, dab.position) >= dab.size * 0.5 {
+                    self.cap_start_normal = normalize([start[0] - dab.position[0], start[1] - dab.position[1]]);
+                }
             }
+            self.cap_held_dabs.push((dab, self.cap_path_length));
         }
 
-        // Defer adding the first dab until we have movement to get accurate pressure
-        let prev_pos = match self.last_dab_position {
-            Some(pos) => pos,
-            None => {
-                let dab = self.create_dab(position, pressure);
-                self.last_dab_position = Some(dab.position);
-                self.last_dab_pressure = pressure;
-                return dabs;
+        if is_end && self.cap_start_normal.is_none() {
+            // Short stroke: take the direction from whatever it covered
+            if let (Some(start), Some((last, _))) = (self.cap_start, self.cap_held_dabs.last()) {
+                self.cap_start_normal = normalize([start[0] - last.position[0], start[1] - last.position[1]]);
             }
-        };
-        let is_first_movement = !self.has_moved && matches!(event_type, crate::input::PointerEventType::Move);
-        if is_first_movement {
-            // Now that we have movement, add the first dab with current pressure (first useable pressure measurement)
-            let first_dab = self.create_dab(prev_pos, pressure);
-            dabs.push(first_dab);
         }
-        self.has_moved = self.has_moved || matches!(event_type, crate::input::PointerEventType::Move);
-
-        let prev_pressure = self.last_dab_pressure;
+        // Start direction still unknown (or a tap): nothing can be released or capped yet
+        let start_normal = self.cap_start_normal.filter(|_| capped);
+        if capped && start_normal.is_none() && !is_end {
+            return Vec::new();
+        }
+        let start = self.cap_start.unwrap_or_default();
 
-        // Calculate distance from last DAB position to current DAB position
-        let dx = position[0] - prev_pos[0];
-        let dy = position[1] - prev_pos[1];
-        let segment_distance = (dx * dx + dy * dy).sqrt();
+        let newest = self.cap_path_length;
+        let release_count = if is_end {
+            self.cap_held_dabs.len()
+        } else if newest < self.params.taper_in + self.params.taper_out {
+            // Still short enough that the tapers may have to shrink to fit
+            0
+        } else {
+            self.cap_held_dabs
+                .iter()
+                .take_while(|(dab, path)| {
+                    (!capped || newest - path > dab.size * 0.5) && newest - path >= self.params.taper_out
+                })
+                .count()
+        };
 
-        // Calculate actual spacing in pixels as a percentage of brush diameter
-        // Clamp spacing px to half a pixel minimum to avoid infinite loops, and still allow for sub-pixel spacing
-        let spacing_ratio = self.params.spacing;
-        let min_spacing_px = 0.5;
-        let mut spacing_px = (spacing_ratio * self.calculate_size_at_pressure(prev_pressure)).max(min_spacing_px);
-
-        let mut remaining_distance = segment_distance;
-        while remaining_distance >= spacing_px {
-            // Calculate how far along the CURRENT SEGMENT this dab should be
-            // accumulated_distance is measured from the LAST DAB we placed (which might be in a previous segment)
-            // We need to figure out where along [prev_pos -> position] to place this dab
-            
-            let distance_into_segment = segment_distance - remaining_distance + spacing_px;
-            let t = (distance_into_segment / segment_distance).clamp(0.0, 1.0);
-
-            // Interpolate position
-            let dab_pos = [
-                prev_pos[0] + dx * t,
-                prev_pos[1] + dy * t,
-            ];
+        let total_length = is_end.then_some(newest);
+        let end_cap = if is_end && start_normal.is_some() { self.end_cap() } else { None };
+        let mut released = Vec::with_capacity(release_count + 2);
+        for (index, (mut dab, path)) in self.cap_held_dabs.drain(..release_count).enumerate() {
+            dab.size *= taper_scale(self.params.taper_in, self.params.taper_out, path, total_length);
+            let Some(start_normal) = start_normal else {
+                released.push(dab);
+                continue;
+            };
+            if path <= dab.size {
+                clip_dab(&mut dab, start, start_normal);
+            }
+            if let Some((end, end_normal)) = end_cap {
+                clip_dab(&mut dab, end, end_normal);
+            }
+
+            // The first dab of the stroke carries the start piece, and the last one the end piece
+            let is_first = index == 0 && dab.position == start && path == 0.0;
+            if self.params.cap_style == CapStyle::Square && is_first {
+                released.push(square_cap_piece(&dab, start, start_normal));
+            }
+            released.push(dab);
+        }
+        if let (Some((end, end_normal)), Some(last)) = (end_cap, released.last().copied()) {
+            if self.params.cap_style == CapStyle::Square {
+                released.push(square_cap_piece(&last, end, end_normal));
+            }
+        }
+        released
+    }
+
+    /// End point and outward normal for the end cap, from the dabs still held
+    fn end_cap(&self) -> Option<([f32; 2], [f32; 2])> {
+        let (last, last_path) = self.cap_held_dabs.last()?;
+        // Like the start, measure the direction over about a dab radius
+        let (from, _) = self
+            .cap_held_dabs
+            .iter()
+            .find(|(dab, path)| last_path - path <= dab.size * 0.5)
+            .filter(|(dab, _)| dab.position != last.position)
+            .or_else(|| self.cap_held_dabs.first())?;
+        let normal = normalize([last.position[0] - from.position[0], last.position[1] - from.position[1]])
+            .or_else(|| self.cap_start_normal.map(|n| [-n[0], -n[1]]))?;
+        Some((last.position, normal))
+    }
+
+    /// Feed the latest stylus azimuth (radians) and twist (degrees) into the angle smoothing
+    /// Call before `calculate_dabs` for each input event
+    pub fn update_angles(&mut self, azimuth: Option<f32>, twist: Option<f32>) {
+        let factor = self.params.angle_smoothing;
+        self.smoothed_azimuth = match (self.smoothed_azimuth, azimuth) {
+            (Some(prev), Some(new)) => Some(smooth_angle(prev, new, factor, std::f32::consts::TAU)),
+            (_, new) => new,
+        };
+        self.smoothed_twist = match (self.smoothed_twist, twist) {
+            (Some(prev), Some(new)) => Some(smooth_angle(prev, new, factor, 360.0)),
+            (_, new) => new,
+        };
+    }
+
+    /// Feed the timestamp (milliseconds) of the latest input event into the speed tracking
+    /// Call before `calculate_dabs` for each input event. Without timestamps the pen
+    /// counts as still, so velocity dynamics have no effect.
+    pub fn update_timestamp(&mut self, timestamp_ms: f64) {
+        self.input_time = Some(timestamp_ms);
+    }
+
+    /// Measure the pen speed since the last sample and fold it into the speed EMA
+    ///
+    /// Each stroke starts from rest. Samples sharing a timestamp (coalesced events) are
+    /// measured together with the next sample that has time between them.
+    fn update_speed(&mut self, position: [f32; 2], event_type: crate::input::PointerEventType) {
+        let Some(time) = self.input_time else {
+            return;
+        };
+        if event_type == crate::input::PointerEventType::Down {
+            self.speed_sample = Some((position, time));
+            self.smoothed_speed = 0.0;
+            return;
+        }
+        if let Some((prev_position, prev_time)) = self.speed_sample {
+            let elapsed_ms = time - prev_time;
+            if elapsed_ms <= 0.0 {
+                return;
+            }
+            let speed = distance(prev_position, position) / (elapsed_ms as f32 / 1000.0);
+            let factor = self.params.velocity_smoothing.clamp(0.0, 1.0);
+            self.smoothed_speed += (speed - self.smoothed_speed) * (1.0 - factor);
+        }
+        self.speed_sample = Some((position, time));
+    }
+
+    /// Get the smoothed pen speed in canvas pixels per second (0.0 without timestamps)
+    pub fn speed(&self) -> f32 {
+        self.smoothed_speed
+    }
 
-            // Interpolate pressure
-            let dab_pressure = prev_pressure + (pressure - prev_pressure) * t;
+    /// Scale the velocity mapping applies at the current speed
+    fn velocity_scale(&self) -> f32 {
+        let normalized_speed = self.smoothed_speed / self.params.velocity_max_speed.max(f32::EPSILON);
+        // The curve runs from the still value down to the fast value as speed rises
+        BrushParams::apply_pressure_curve(
+            normalized_speed,
+            self.params.velocity_gamma,
+            None,
+            self.params.max_velocity_percent,
+            self.params.min_velocity_percent,
+        )
+        .clamp(0.0, 1.0)
+    }
+
+    /// Apply the pressure EMA to a raw pressure sample
+    ///
+    /// Down pressure is unreliable on many devices (the first dab is deferred for the same
+    /// reason), so it passes through without seeding the average.
+    fn smooth_pressure(&mut self, pressure: f32, event_type: crate::input::PointerEventType) -> f32 {
+        if event_type == crate::input::PointerEventType::Down {
+            return pressure;
+        }
+        let factor = self.params.pressure_smoothing.clamp(0.0, 1.0);
+        let smoothed = match self.smoothed_pressure {
+            Some(prev) => prev + (pressure - prev) * (1.0 - factor),
+            None => pressure,
+        };
+        self.smoothed_pressure = Some(smoothed);
+        smoothed
+    }
+
+    /// Apply position stabilization (exponential moving average of input positions)
+    ///
+    /// The stroke starts where the pen lands, and on Up the stabilized position jumps to
+    /// the pen so the lag is drawn out as the stroke's final segment instead of lost.
+    fn stabilize_position(&mut self, position: [f32; 2], event_type: crate::input::PointerEventType) -> [f32; 2] {
+        let stabilized = match (event_type, self.stabilized_position) {
+            (crate::input::PointerEventType::Move, Some(prev)) => {
+                let stabilization = self.params.stabilization.clamp(0.0, 1.0);
+                let follow = 1.0 - stabilization * (1.0 - STABILIZATION_MIN_FOLLOW);
+                [
+                    prev[0] + (position[0] - prev[0]) * follow,
+                    prev[1] + (position[1] - prev[1]) * follow,
+                ]
+            }
+            _ => position,
+        };
+        self.stabilized_position = Some(stabilized);
+        self.pen_position = Some(position);
+        stabilized
+    }
+
+    /// Pen position and the stabilized position trailing it, as `[pen, stabilized]`
+    ///
+    /// For showing the stabilizer's lag while drawing. None outside strokes, and while
+    /// stabilization is off.
+    pub fn stabilizer_tether(&self) -> Option<[[f32; 2]; 2]> {
+        if !self.brush_down || self.params.stabilization <= 0.0 {
+            return None;
+        }
+        Some([self.pen_position?, self.stabilized_position?])
+    }
+
+    /// Pull an input position toward the line through recent input when the pen is slow
+    ///
+    /// The target pull is the strength scaled by how slow the pen is and how straight the
+    /// recent input is, so fast strokes and curves pass through untouched. The fit uses raw
+    /// positions (not assisted ones) so the assist never feeds back on itself.
+    fn apply_line_assist(&mut self, position: [f32; 2]) -> [f32; 2] {
+        if self.line_assist_points.len() == LINE_ASSIST_WINDOW {
+            self.line_assist_points.pop_front();
+        }
+        self.line_assist_points.push_back(position);
 
-            // Create and add dab
-            let dab = self.create_dab(dab_pos, dab_pressure);
-            dabs.push(dab);
+        let strength = self.params.line_assist.clamp(0.0, 1.0);
+        let fit = if strength > 0.0 && self.line_assist_points.len() >= LINE_ASSIST_MIN_POINTS {
+            fit_line(self.line_assist_points.iter().copied())
+        } else {
+            None
+        };
 
-            self.last_dab_position = Some(dab.position);
-            self.last_dab_pressure = dab_pressure;
-            remaining_distance -= spacing_px;
-            spacing_px = (spacing_ratio * dab.size).max(min_spacing_px);
+        let target = match &fit {
+            Some(fit) => {
+                let steps = self.line_assist_points.len() - 1;
+                let speed_steps = steps.min(LINE_ASSIST_SPEED_STEPS);
+                let recent = self.line_assist_points.iter().skip(steps - speed_steps);
+                let travelled: f32 = recent
+                    .clone()
+                    .zip(recent.skip(1))
+                    .map(|(a, b)| ((b[0] - a[0]).powi(2) + (b[1] - a[1]).powi(2)).sqrt())
+                    .sum();
+                let average_step = travelled / speed_steps as f32;
+                let slowness = (1.0 - average_step / LINE_ASSIST_SLOW_STEP_PX).clamp(0.0, 1.0);
+                let curvature = self.line_assist_curvature(fit);
+                let straightness = (1.0 - curvature / LINE_ASSIST_CURVE_TOLERANCE_PX).clamp(0.0, 1.0);
+                strength * slowness * straightness
+            }
+            None => 0.0,
+        };
+        self.line_assist_weight += (target - self.line_assist_weight) * LINE_ASSIST_RAMP;
+
+        match fit {
+            Some(fit) if self.line_assist_weight > f32::EPSILON => {
+                let on_line = fit.project(position);
+                [
+                    position[0] + (on_line[0] - position[0]) * self.line_assist_weight,
+                    position[1] + (on_line[1] - position[1]) * self.line_assist_weight,
+                ]
+            }
+            _ => position,
         }
+    }
+
+    /// Project an input position onto the constrained axis through the stroke start
+    ///
+    /// The axis is the direction from the start to the pen, snapped to the nearest multiple
+    /// of the constraint angle. It follows the pen until it is `ANGLE_CONSTRAINT_LOCK_PX`
+    /// from the start, then stays locked for the rest of the stroke (backtracking along the
+    /// axis is allowed).
+    fn apply_angle_constraint(&mut self, stroke_start: [f32; 2], position: [f32; 2]) -> [f32; 2] {
+        let step = self.params.angle_constraint;
+        if step <= 0.0 {
+            return position;
+        }
+        let anchor = *self.constraint_anchor.get_or_insert(stroke_start);
+        let offset = [position[0] - anchor[0], position[1] - anchor[1]];
+
+        let axis = match self.constraint_axis {
+            Some(axis) => axis,
+            None => {
+                if offset[0] == 0.0 && offset[1] == 0.0 {
+                    return anchor;
+                }
+                let step = step.to_radians();
+                let angle = (offset[1].atan2(offset[0]) / step).round() * step;
+                let axis = [angle.cos(), angle.sin()];
+                if distance(anchor, position) >= ANGLE_CONSTRAINT_LOCK_PX {
+                    self.constraint_axis = Some(axis);
+                }
+                axis
+            }
+        };
 
+        let along = offset[0] * axis[0] + offset[1] * axis[1];
+        [anchor[0] + axis[0] * along, anchor[1] + axis[1] * along]
+    }
+
+    /// RMS distance of the jitter-averaged recent input from a line fitted through it
+    fn line_assist_curvature(&self, fit: &LineFit) -> f32 {
+        let points: Vec<[f32; 2]> = self.line_assist_points.iter().copied().collect();
+        let (sum, count) = points
+            .windows(LINE_ASSIST_DENOISE_POINTS)
+            .map(|window| {
+                let n = window.len() as f32;
+                let x = window.iter().map(|p| p[0]).sum::<f32>() / n;
+                let y = window.iter().map(|p| p[1]).sum::<f32>() / n;
+                fit.distance([x, y]).powi(2)
+            })
+            .fold((0.0f32, 0usize), |(sum, count), d2| (sum + d2, count + 1));
+        if count == 0 {
+            0.0
+        } else {
+            (sum / count as f32).sqrt()
+        }
+    }
+
+    /// Smoothed stylus azimuth in radians, if available
+    pub fn smoothed_azimuth(&self) -> Option<f32> {
+        self.smoothed_azimuth
+    }
+
+    /// Smoothed stylus twist in degrees, if available
+    pub fn smoothed_twist(&self) -> Option<f32> {
+        self.smoothed_twist
+    }
+
+    /// Check whether the stroke segment has reached the max stroke length, starting a
+    /// new segment if so
+    ///
+    /// Only the length count restarts: dab spacing and smoothing carry on from the previous
+    /// segment, so the split leaves no gap or doubled dab. Call after `calculate_dabs`.
+    pub fn take_segment_break(&mut self) -> bool {
+        let max_length = self.params.max_stroke_length;
+        if max_length > 0.0 && self.segment_length >= max_length {
+            self.segment_length = 0.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// End the current stroke (call when finishing a stroke)
+    pub fn end_stroke(&mut self) {
+        self.reset_brush();
+    }
+
+    /// Calculate dabs for a segment from previous position to current position
+    /// Returns a vector of dabs to render
+    pub fn calculate_dabs(&mut self, position: [f32; 2], pressure: f32, event_type: crate::input::PointerEventType) -> Vec<BrushDab> {
+        let last_position = self.last_dab_position;
+        let dabs = self.calculate_path_dabs(position, pressure, event_type);
+        // The airbrush only deposits while the stroke stays put
+        if event_type == crate::input::PointerEventType::Down || self.last_dab_position != last_position {
+            self.airbrush_time = self.input_time;
+        }
         dabs
     }
 
-    /// Calculate the brush size at a given pressure value
+    /// Check whether the airbrush is depositing: it is on and the brush is down
+    pub fn is_airbrushing(&self) -> bool {
+        self.params.airbrush && self.brush_down
+    }
+
+    /// Calculate the dabs the airbrush has deposited at the current stroke position since
+    /// the stroke last moved or deposited, up to `now_ms` (on the input timestamp clock)
+    ///
+    /// Call every frame while `is_airbrushing`. Dabs come at `airbrush_rate` dabs per
+    /// second scaled by the current pressure, so holding still builds paint up over time;
+    /// while the pen moves, the usual distance-spaced dabs are placed instead. Returns
+    /// nothing once the stroke has ended.
+    pub fn airbrush_dabs(&mut self, now_ms: f64) -> Vec<BrushDab> {
+        if !self.is_airbrushing()
+            || (self.params.input_filter_mode == InputFilterMode::PenOnly && self.brush_src == PointerEventSource::Touch)
+        {
+            return Vec::new();
+        }
+        let Some(position) = self.last_dab_position else {
+            return Vec::new();
+        };
+        let pressure = self.last_dab_pressure.clamp(0.0, 1.0);
+        let last = *self.airbrush_time.get_or_insert(now_ms);
+        let dabs_per_ms = (self.params.airbrush_rate * pressure) as f64 / 1000.0;
+        if dabs_per_ms <= 0.0 {
+            // Nothing builds up without pressure
+            self.airbrush_time = Some(now_ms);
+            return Vec::new();
+        }
+
+        let due = ((now_ms - last) * dabs_per_ms).floor().max(0.0) as usize;
+        if due == 0 {
+            return Vec::new();
+        }
+        let count = due.min(MAX_AIRBRUSH_DABS_PER_UPDATE);
+        // Keep the remainder toward the next dab, unless capped (the backlog is dropped)
+        self.airbrush_time = Some(if count < due { now_ms } else { last + count as f64 / dabs_per_ms });
+        let dabs = (0..count).map(|_| self.create_dab(position, pressure)).collect();
+        self.apply_caps(dabs, false)
+    }
+
+    /// Place the dabs for an input event along the path from the last dab
+    fn calculate_path_dabs(&mut self, position: [f32; 2], pressure: f32, event_type: crate::input::PointerEventType) -> Vec<BrushDab> {
+        let mut dabs = Vec::new();
+        // Only draw if brush is down
+        if !self.brush_down {
+            return dabs;
+        }
+
+        // Filter input based on input filter mode
+        if self.params.input_filter_mode == InputFilterMode::PenOnly {
+            // In PenOnly mode, only accept non-touch input
+            if self.brush_src == PointerEventSource::Touch {
+                log::debug!("Rejecting input from {:?} in PenOnly mode", self.brush_src);
+                return dabs;
+            }
+        }
+
+        let pressure = self.smooth_pressure(pressure, event_type);
+        let position = self.stabilize_position(position, event_type);
+        self.update_speed(position, event_type);
+
+        // Defer adding the first dab until we have movement to get accurate pressure,
+        // unless it should land on Down (stamp brushes)
+        let mut prev_pos = match self.last_dab_position {
+            Some(pos) => pos,
+            None if event_type == crate::input::PointerEventType::Down && self.places_dab_on_down() => {
+                let dab = self.create_dab(position, pressure);
+                self.last_dab_position = Some(dab.position);
+                self.last_dab_pressure = pressure;
+                self.has_moved = true;
+                return self.apply_caps(vec![dab], false);
+            }
+            None => {
+                let dab = self.create_dab(position, pressure);
+                self.last_dab_position = Some(dab.position);
+                self.last_dab_pressure = pressure;
+                return dabs;
+            }
+        };
+        let is_move = matches!(event_type, crate::input::PointerEventType::Move);
+        if !self.has_moved && is_move {
+            self.first_dab_samples += 1;
+            self.first_dab_max_pressure = self.first_dab_max_pressure.max(pressure);
+
+            match self.params.first_dab_pressure_mode {
+                FirstDabPressureMode::FirstMove => {
+                    // Now that we have movement, add the first dab with current pressure (first useable pressure measurement)
+                    let first_dab = self.create_dab(prev_pos, pressure);
+                    dabs.push(first_dab);
+                }
+                FirstDabPressureMode::MaxOfFirstSamples(sample_count) => {
+                    if self.first_dab_samples < sample_count.max(1) {
+                        // Keep deferring; interpolation starts from the stroke start once placed
+                        return dabs;
+                    }
+                    let first_pressure = self.first_dab_max_pressure;
+                    let first_dab = self.create_dab(prev_pos, first_pressure);
+                    dabs.push(first_dab);
+                    self.last_dab_pressure = first_pressure;
+                }
+                FirstDabPressureMode::Threshold(threshold) => {
+                    if pressure < threshold {
+                        // Not pressing hard enough yet: slide the stroke start along with the pen
+                        self.last_dab_position = Some(position);
+                        self.last_dab_pressure = pressure;
+                        return dabs;
+                    }
+                    let first_dab = self.create_dab(position, pressure);
+                    dabs.push(first_dab);
+                    self.last_dab_position = Some(position);
+                    self.last_dab_pressure = pressure;
+                    prev_pos = position;
+                }
+            }
+            self.has_moved = true;
+        } else if !self.has_moved && matches!(event_type, crate::input::PointerEventType::Up) {
+            if let FirstDabPressureMode::MaxOfFirstSamples(_) = self.params.first_dab_pressure_mode {
+                // Stroke ended before enough samples arrived: place the first dab with what we have
+                if self.first_dab_samples > 0 {
+                    let first_pressure = self.first_dab_max_pressure.max(pressure);
+                    let first_dab = self.create_dab(prev_pos, first_pressure);
+                    dabs.push(first_dab);
+                    self.last_dab_pressure = first_pressure;
+                    self.has_moved = true;
+                }
+            }
+
+            // A tap: make sure it leaves a visible dot whatever the pressure dynamics did
+            if self.params.min_tap_dab_size > 0.0 {
+                if dabs.is_empty() {
+                    let tap_pressure = self.first_dab_max_pressure.max(self.last_dab_pressure).max(pressure);
+                    dabs.push(self.create_dab(prev_pos, tap_pressure));
+                    self.last_dab_pressure = tap_pressure;
+                    self.has_moved = true;
+                }
+                for dab in &mut dabs {
+                    dab.size = dab.size.max(self.params.min_tap_dab_size);
+                }
+            }
+        }
+
+        // Straighten slow, nearly straight input (the first dab stays where the pen landed),
+        // then lock it to the constrained axis if one is set
+        let position = if self.has_moved {
+            let position = self.apply_line_assist(position);
+            self.apply_angle_constraint(prev_pos, position)
+        } else {
+            position
+        };
+
+        // Place dabs along the path from the last dab: a spline through recent input once
+        // the stroke is under way, a straight segment before that
+        let is_end = matches!(event_type, crate::input::PointerEventType::Up);
+        if self.has_moved {
+            for (path, end_pressure) in self.spline_segments(prev_pos, position, pressure, is_end) {
+                self.place_dabs_along(prev_pos, &path, end_pressure, &mut dabs);
+                prev_pos = self.last_dab_position.unwrap_or(prev_pos);
+            }
+        } else {
+            self.place_dabs_along(prev_pos, &[position], pressure, &mut dabs);
+        }
+
+        self.apply_caps(dabs, is_end)
+    }
+
+    /// Add an input point to the spline and return the path segments that are now ready to
+    /// draw, each as points after the last dab with the pressure at its end
+    ///
+    /// Interior segments are centripetal Catmull-Rom curves, so each is drawn once the input
+    /// point after it arrives (one sample of latency). The first segment of a stroke is
+    /// straight, since there is no point before it to shape it, and on Up the last segment
+    /// is drawn with a mirrored point standing in for the one after it. Until the stroke has
+    /// three points, the straight first segment is redrawn from the last dab each time.
+    fn spline_segments(
+        &mut self,
+        stroke_start: [f32; 2],
+        position: [f32; 2],
+        pressure: f32,
+        is_end: bool,
+    ) -> Vec<(Vec<[f32; 2]>, f32)> {
+        if self.spline_points.is_empty() {
+            self.spline_points.push_back((stroke_start, self.last_dab_pressure));
+        }
+        // A repeated position adds no shape (and would divide by zero in the knot spacing)
+        match self.spline_points.back_mut() {
+            Some(last) if last.0 == position => last.1 = pressure,
+            _ => self.spline_points.push_back((position, pressure)),
+        }
+
+        let mut segments = Vec::new();
+        let points: Vec<_> = self.spline_points.iter().copied().collect();
+        match points.len() {
+            2 => segments.push((vec![points[1].0], points[1].1)),
+            4 => {
+                segments.push(catmull_rom_segment(points[0].0, points[1], points[2], points[3].0));
+                self.spline_points.pop_front();
+            }
+            _ => {}
+        }
+
+        if is_end {
+            // Draw the pending segment with a mirrored point past the end of the stroke
+            let points: Vec<_> = self.spline_points.iter().copied().collect();
+            if let [.., before, from, to] = points[..] {
+                let after = [2.0 * to.0[0] - from.0[0], 2.0 * to.0[1] - from.0[1]];
+                segments.push(catmull_rom_segment(before.0, from, to, after));
+            }
+        }
+        segments
+    }
+
+    /// Place dabs at the brush spacing along a path starting at the last dab
+    ///
+    /// `path` holds the points after `start`, joined by straight lines. Pressure runs from
+    /// the last dab's pressure to `end_pressure` over the path's length.
+    fn place_dabs_along(&mut self, start: [f32; 2], path: &[[f32; 2]], end_pressure: f32, dabs: &mut Vec<BrushDab>) {
+        let start_pressure = self.last_dab_pressure;
+        let total_length: f32 = std::iter::once(start)
+            .chain(path.iter().copied())
+            .zip(path.iter().copied())
+            .map(|(a, b)| distance(a, b))
+            .sum();
+        if total_length <= 0.0 {
+            return;
+        }
+
+        // Spacing is measured from the last dab, and adapts to each dab's size
+        let mut spacing_px = self.spacing_px(self.calculate_size_at_pressure(start_pressure));
+        let mut next_dab_distance = spacing_px;
+        let mut piece_start_distance = 0.0;
+        for (a, b) in std::iter::once(start).chain(path.iter().copied()).zip(path.iter().copied()) {
+            let piece_length = distance(a, b);
+            while piece_length > 0.0 && next_dab_distance <= piece_start_distance + piece_length {
+                let t = (next_dab_distance - piece_start_distance) / piece_length;
+                let dab_pos = [a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t];
+                let path_t = (next_dab_distance / total_length).clamp(0.0, 1.0);
+                let dab_pressure = start_pressure + (end_pressure - start_pressure) * path_t;
+
+                let dab = self.create_dab(dab_pos, dab_pressure);
+                dabs.push(dab);
+
+                self.last_dab_position = Some(dab.position);
+                self.last_dab_pressure = dab_pressure;
+                self.segment_length += spacing_px;
+                spacing_px = self.spacing_px(dab.size);
+                next_dab_distance += spacing_px;
+            }
+            piece_start_distance += piece_length;
+        }
+    }
+
+    /// Calculate the brush size at a given pressure value (and the current speed)
     fn calculate_size_at_pressure(&self, pressure: f32) -> f32 {
-        match self.params.pressure_mapping {
-            PressureMapping::Size | PressureMapping::Both => {
+        let velocity_scale = match self.params.velocity_mapping {
+            VelocityMapping::Size | VelocityMapping::Both => self.velocity_scale(),
+            VelocityMapping::Flow | VelocityMapping::None => 1.0,
+        };
+        let size = match self.params.pressure_mapping {
+            PressureMapping::Size | PressureMapping::Both | PressureMapping::All => {
                 let size_scale = BrushParams::apply_pressure_curve(
                     pressure,
                     self.params.size_gamma,
+                    self.params.pressure_response.curve(),
                     self.params.min_size_percent,
                     self.params.max_size_percent,
                 ).clamp(0.0, 1.0);
                 self.params.size * size_scale
             }
-            PressureMapping::Flow | PressureMapping::None => {
+            PressureMapping::Flow | PressureMapping::Hardness | PressureMapping::None => {
                 self.params.size
             }
-        }
+        };
+        size * velocity_scale
     }
 
     fn calculate_flow_at_pressure(&self, pressure: f32) -> f32 {
-        match self.params.pressure_mapping {
-            PressureMapping::Flow | PressureMapping::Both => {
+        let velocity_scale = match self.params.velocity_mapping {
+            VelocityMapping::Flow | VelocityMapping::Both => self.velocity_scale(),
+            VelocityMapping::Size | VelocityMapping::None => 1.0,
+        };
+        let flow = match self.params.pressure_mapping {
+            PressureMapping::Flow | PressureMapping::Both | PressureMapping::All => {
                 let flow_scale = BrushParams::apply_pressure_curve(
                     pressure,
                     self.params.flow_gamma,
+                    self.params.pressure_response.curve(),
                     self.params.min_flow_percent,
                     self.params.max_flow_percent,
                 ).clamp(0.0, 1.0);
                 self.params.flow * flow_scale
             }
-            PressureMapping::Size | PressureMapping::None => {
+            PressureMapping::Size | PressureMapping::Hardness | PressureMapping::None => {
                 self.params.flow
             }
+        };
+        flow * velocity_scale
+    }
+
+    fn calculate_hardness_at_pressure(&self, pressure: f32) -> f32 {
+        match self.params.pressure_mapping {
+            PressureMapping::Hardness | PressureMapping::All => {
+                let hardness_scale = BrushParams::apply_pressure_curve(
+                    pressure,
+                    self.params.hardness_gamma,
+                    self.params.pressure_response.curve(),
+                    self.params.min_hardness_percent,
+                    self.params.max_hardness_percent,
+                ).clamp(0.0, 1.0);
+                self.params.hardness * hardness_scale
+            }
+            PressureMapping::Flow | PressureMapping::Size | PressureMapping::Both | PressureMapping::None => {
+                self.params.hardness
+            }
         }
     }
 
+    /// The brush color with this dab's color jitter applied
+    ///
+    /// The generator is seeded from where the stroke's first dab lands rather than carried
+    /// over from earlier strokes, so a stroke replayed from a recording gets the same jitter
+    /// whatever was drawn before it, while different strokes still differ.
+    fn jittered_color(&mut self, position: [f32; 2]) -> [f32; 4] {
+        let params = &self.params;
+        if params.hue_jitter == 0.0 && params.saturation_jitter == 0.0 && params.value_jitter == 0.0 {
+            return params.color;
+        }
+        if self.jitter_rng == 0 {
+            let seed = COLOR_JITTER_SEED ^ position[0].to_bits().wrapping_mul(0x9E37_79B1) ^ position[1].to_bits().rotate_left(16);
+            self.jitter_rng = if seed == 0 { COLOR_JITTER_SEED } else { seed };
+        }
+
+        // Symmetric offsets in -jitter/2..jitter/2 for hue (in turns), -jitter..jitter otherwise
+        let mut offset = |jitter: f32, range: f32| (next_random(&mut self.jitter_rng) * 2.0 - 1.0) * jitter * range;
+        let [r, g, b, a] = self.params.color;
+        let [hue, saturation, value] = crate::color::rgb_to_hsv([r, g, b]);
+        let hue = hue + offset(self.params.hue_jitter, 0.5);
+        let saturation = (saturation + offset(self.params.saturation_jitter, 1.0)).clamp(0.0, 1.0);
+        let value = (value + offset(self.params.value_jitter, 1.0)).clamp(0.0, 1.0);
+        let [r, g, b] = crate::color::hsv_to_rgb([hue, saturation, value]);
+        [r, g, b, a]
+    }
+
     /// Create a single dab with pressure applied
-    fn create_dab(&self, position: [f32; 2], pressure: f32) -> BrushDab {
+    fn create_dab(&mut self, position: [f32; 2], pressure: f32) -> BrushDab {
         let size = self.calculate_size_at_pressure(pressure);
         let opacity = self.calculate_flow_at_pressure(pressure);
+        let hardness = self.calculate_hardness_at_pressure(pressure);
+
+        let angle = if self.params.random_rotation {
+            next_random(&mut self.rotation_rng) * std::f32::consts::TAU
+        } else {
+            self.smoothed_azimuth
+                .or(self.smoothed_twist.map(f32::to_radians))
+                .unwrap_or(0.0)
+        };
+
+        let color = self.jittered_color(position);
+        let anchor = self.smudge_anchor.replace(position).unwrap_or(position);
 
         BrushDab {
             position,
             size,
             opacity,
-            color: self.params.color,
-            hardness: self.params.hardness,
+            color,
+            hardness,
+            angle,
+            tip_shape: self.params.tip_shape,
+            clips: [None; 2],
+            square_cap: false,
+            smudge_offset: [position[0] - anchor[0], position[1] - anchor[1]],
         }
     }
 }
 
+/// Advance a xorshift32 generator and return a value in [0, 1)
+fn next_random(state: &mut u32) -> f32 {
+    let mut x = *state;
+    x ^= x << 13;
+    x ^= x >> 17;
+    x ^= x << 5;
+    *state = x;
+    // The top 24 bits fit an f32 mantissa exactly
+    (x >> 8) as f32 / (1u32 << 24) as f32
+}
+
+/// Sample the centripetal Catmull-Rom curve from `from` to `to` into short straight pieces
+///
+/// `before` and `after` are the input points on either side and only shape the curve.
+/// Returns the sample points after `from` (ending at `to`) with `to`'s pressure.
+fn catmull_rom_segment(
+    before: [f32; 2],
+    (from, _): ([f32; 2], f32),
+    (to, to_pressure): ([f32; 2], f32),
+    after: [f32; 2],
+) -> (Vec<[f32; 2]>, f32) {
+    // Centripetal knot spacing (square root of the distances) keeps unevenly spaced input
+    // from overshooting or looping
+    let knot = |a: [f32; 2], b: [f32; 2]| distance(a, b).sqrt().max(f32::EPSILON);
+    let (d0, d1, d2) = (knot(before, from), knot(from, to), knot(to, after));
+
+    // Hermite tangents at both ends, scaled to the segment's parameter range
+    let tangent = |p0: [f32; 2], p1: [f32; 2], p2: [f32; 2], d01: f32, d12: f32| -> [f32; 2] {
+        [0, 1].map(|i| {
+            d1 * ((p1[i] - p0[i]) / d01 - (p2[i] - p0[i]) / (d01 + d12) + (p2[i] - p1[i]) / d12)
+        })
+    };
+    let from_tangent = tangent(before, from, to, d0, d1);
+    let to_tangent = tangent(from, to, after, d1, d2);
+
+    let sample_count = (distance(from, to) / SPLINE_SAMPLE_PX).ceil().clamp(1.0, SPLINE_MAX_SAMPLES as f32) as usize;
+    let points = (1..=sample_count)
+        .map(|i| {
+            let t = i as f32 / sample_count as f32;
+            let (t2, t3) = (t * t, t * t * t);
+            let h00 = 2.0 * t3 - 3.0 * t2 + 1.0;
+            let h10 = t3 - 2.0 * t2 + t;
+            let h01 = -2.0 * t3 + 3.0 * t2;
+            let h11 = t3 - t2;
+            [0, 1].map(|i| h00 * from[i] + h10 * from_tangent[i] + h01 * to[i] + h11 * to_tangent[i])
+        })
+        .collect();
+    (points, to_pressure)
+}
+
+/// Distance between two points
+fn distance(a: [f32; 2], b: [f32; 2]) -> f32 {
+    ((b[0] - a[0]).powi(2) + (b[1] - a[1]).powi(2)).sqrt()
+}
+
+/// Unit vector in the direction of `v`, or None if it has no length
+fn normalize(v: [f32; 2]) -> Option<[f32; 2]> {
+    let length = (v[0] * v[0] + v[1] * v[1]).sqrt();
+    (length > f32::EPSILON).then(|| [v[0] / length, v[1] / length])
+}
+
+/// Trim a dab at the line through `point` with outward `normal`, if the dab reaches past it
+fn clip_dab(dab: &mut BrushDab, point: [f32; 2], normal: [f32; 2]) {
+    let offset = (point[0] - dab.position[0]) * normal[0] + (point[1] - dab.position[1]) * normal[1];
+    if offset >= dab.size * 0.5 {
+        return;
+    }
+    let clip = DabClip { normal, offset };
+    if let Some(slot) = dab.clips.iter_mut().find(|slot| slot.is_none()) {
+        *slot = Some(clip);
+    }
+}
+
+/// Size scale (0.0-1.0) of a dab `path` pixels along a stroke with tapered ends
+///
+/// Size ramps up linearly over the first `taper_in` pixels and back down over the last
+/// `taper_out`. The end is only known once the stroke is over (`total_length`); before
+/// that only the taper in applies. A stroke shorter than both tapers together shrinks
+/// them in proportion, so it still tapers in and out and peaks where they meet.
+fn taper_scale(taper_in: f32, taper_out: f32, path: f32, total_length: Option<f32>) -> f32 {
+    let (mut taper_in, mut taper_out) = (taper_in, taper_out);
+    let mut remaining = f32::INFINITY;
+    if let Some(total) = total_length {
+        let span = taper_in + taper_out;
+        if span > total {
+            taper_in *= total / span;
+            taper_out *= total / span;
+        }
+        remaining = total - path;
+    }
+    let mut scale: f32 = 1.0;
+    if taper_in > 0.0 {
+        scale = scale.min(path / taper_in);
+    }
+    if taper_out > 0.0 {
+        scale = scale.min(remaining / taper_out);
+    }
+    scale.clamp(0.0, 1.0)
+}
+
+/// Square piece filling the half-square past a cap line, sized like `dab`
+fn square_cap_piece(dab: &BrushDab, point: [f32; 2], outward: [f32; 2]) -> BrushDab {
+    BrushDab {
+        position: point,
+        angle: outward[1].atan2(outward[0]),
+        clips: [Some(DabClip { normal: [-outward[0], -outward[1]], offset: 0.0 }), None],
+        square_cap: true,
+        ..*dab
+    }
+}
+
+/// Exponential moving average step for a periodic angle
+///
+/// Moves `prev` toward `new` along the shortest way around the circle, so a reading
+/// crossing the wrap point (e.g. 359 -> 0 degrees) nudges the angle forward by one
+/// degree instead of swinging it back across the whole range.
+/// The result is normalized to `[0, period)`.
+pub fn smooth_angle(prev: f32, new: f32, factor: f32, period: f32) -> f32 {
+    let half = period * 0.5;
+    let delta = (new - prev + half).rem_euclid(period) - half;
+    let smoothed = prev + delta * (1.0 - factor.clamp(0.0, 1.0));
+    smoothed.rem_euclid(period)
+}
+
+/// Line fitted through a set of points by orthogonal least squares
+#[derive(Debug, Clone, Copy)]
+pub struct LineFit {
+    /// Centroid of the points (a point on the line)
+    pub center: [f32; 2],
+    /// Unit direction of the line
+    pub direction: [f32; 2],
+    /// Root-mean-square perpendicular distance of the points from the line
+    pub rms_distance: f32,
+}
+
+impl LineFit {
+    /// Perpendicular distance of `point` from the line
+    pub fn distance(&self, point: [f32; 2]) -> f32 {
+        ((point[0] - self.center[0]) * self.direction[1] - (point[1] - self.center[1]) * self.direction[0]).abs()
+    }
+
+    /// Closest point on the line to `point`
+    pub fn project(&self, point: [f32; 2]) -> [f32; 2] {
+        let along = (point[0] - self.center[0]) * self.direction[0]
+            + (point[1] - self.center[1]) * self.direction[1];
+        [
+            self.center[0] + self.direction[0] * along,
+            self.center[1] + self.direction[1] * along,
+        ]
+    }
+}
+
+/// Fit a line through points, minimizing perpendicular (not vertical) distance
+///
+/// The direction is the principal axis of the points' covariance, so vertical lines fit
+/// as well as horizontal ones. Returns None for fewer than two points or when the points
+/// all coincide (no direction to fit).
+pub fn fit_line(points: impl Iterator<Item = [f32; 2]> + Clone) -> Option<LineFit> {
+    let (count, sum_x, sum_y) = points
+        .clone()
+        .fold((0usize, 0.0f32, 0.0f32), |(n, sx, sy), p| (n + 1, sx + p[0], sy + p[1]));
+    if count < 2 {
+        return None;
+    }
+    let center = [sum_x / count as f32, sum_y / count as f32];
+
+    let (xx, xy, yy) = points.fold((0.0f32, 0.0f32, 0.0f32), |(xx, xy, yy), p| {
+        let dx = p[0] - center[0];
+        let dy = p[1] - center[1];
+        (xx + dx * dx, xy + dx * dy, yy + dy * dy)
+    });
+    if xx + yy <= f32::EPSILON {
+        return None;
+    }
+
+    let angle = 0.5 * (2.0 * xy).atan2(xx - yy);
+    let direction = [angle.cos(), angle.sin()];
+    // Variance across the line: total variance minus the variance along it
+    let along = xx * direction[0] * direction[0] + 2.0 * xy * direction[0] * direction[1] + yy * direction[1] * direction[1];
+    let across = (xx + yy - along).max(0.0);
+
+    Some(LineFit {
+        center,
+        direction,
+        rms_distance: (across / count as f32).sqrt(),
+    })
+}
+
 impl Default for BrushState {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::PointerEventType;
+
+    /// Feed a stroke that starts with zero pressure (as some styluses report on the
+    /// first move) and return all generated dabs
+    fn zero_start_stroke(mode: FirstDabPressureMode) -> Vec<BrushDab> {
+        let mut state = BrushState::with_params(BrushParams {
+            pressure_mapping: PressureMapping::Flow,
+            first_dab_pressure_mode: mode,
+            ..BrushParams::default()
+        });
+        state.begin_stroke();
+
+        let mut dabs = Vec::new();
+        dabs.extend(state.calculate_dabs([0.0, 0.0], 0.0, PointerEventType::Down));
+        dabs.extend(state.calculate_dabs([1.0, 0.0], 0.0, PointerEventType::Move));
+        dabs.extend(state.calculate_dabs([2.0, 0.0], 0.5, PointerEventType::Move));
+        dabs.extend(state.calculate_dabs([3.0, 0.0], 0.8, PointerEventType::Move));
+        dabs.extend(state.calculate_dabs([20.0, 0.0], 0.8, PointerEventType::Move));
+        dabs
+    }
+
+    #[test]
+    fn test_smooth_angle_wraps_the_short_way() {
+        // 350 -> 10 degrees is a 20 degree step forward, not 340 back
+        let smoothed = smooth_angle(350.0, 10.0, 0.5, 360.0);
+        assert!((smoothed - 0.0).abs() < 1e-4 || (smoothed - 360.0).abs() < 1e-4);
+        let smoothed = smooth_angle(10.0, 350.0, 0.5, 360.0);
+        assert!((smoothed - 0.0).abs() < 1e-4 || (smoothed - 360.0).abs() < 1e-4);
+        // No smoothing passes the new angle through (normalized)
+        assert!((smooth_angle(359.0, 1.0, 0.0, 360.0) - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_random_rotation_varies_per_dab_and_repeats() {
+        let angles = || {
+            let mut state = BrushState::with_params(BrushParams { random_rotation: true, ..BrushParams::DEFAULT });
+            state.begin_stroke();
+            state.update_angles(Some(1.0), None);
+            let mut dabs = state.calculate_dabs([0.0, 0.0], 1.0, PointerEventType::Down);
+            dabs.extend(state.calculate_dabs([100.0, 0.0], 1.0, PointerEventType::Up));
+            dabs.iter().map(|dab| dab.angle).collect::<Vec<_>>()
+        };
+
+        let first = angles();
+        assert!(first.len() > 10);
+        assert!(first.iter().all(|angle| (0.0..std::f32::consts::TAU).contains(angle)));
+        // Spread over the circle instead of following the stylus azimuth
+        let spread = first.iter().fold(0.0_f32, |max, angle| max.max(*angle)) - first.iter().fold(f32::MAX, |min, angle| min.min(*angle));
+        assert!(spread > std::f32::consts::PI);
+        assert_eq!(first, angles(), "the same input should give the same dabs");
+    }
+
+    #[test]
+    fn test_color_jitter_varies_per_dab_and_replays() {
+        let stroke = |state: &mut BrushState, start: f32| {
+            state.begin_stroke();
+            let mut dabs = state.calculate_dabs([start, 0.0], 1.0, PointerEventType::Down);
+            dabs.extend(state.calculate_dabs([start + 100.0, 0.0], 1.0, PointerEventType::Up));
+            dabs.iter().map(|dab| dab.color).collect::<Vec<_>>()
+        };
+
+        // Off by default: every dab gets the brush color exactly
+        let mut plain = BrushState::with_params(BrushParams::DEFAULT);
+        assert!(stroke(&mut plain, 0.0).iter().all(|color| *color == BrushParams::DEFAULT.color));
+
+        let params = BrushParams { hue_jitter: 0.1, saturation_jitter: 0.2, value_jitter: 0.2, ..BrushParams::DEFAULT };
+        let mut state = BrushState::with_params(params);
+        let first = stroke(&mut state, 0.0);
+        assert!(first.windows(2).any(|pair| pair[0] != pair[1]));
+        let base = crate::color::rgb_to_hsv([params.color[0], params.color[1], params.color[2]]);
+        for color in &first {
+            let [hue, saturation, value] = crate::color::rgb_to_hsv([color[0], color[1], color[2]]);
+            let hue_offset = (hue - base[0] + 0.5).rem_euclid(1.0) - 0.5;
+            assert!(hue_offset.abs() <= 0.05 + 1e-4);
+            assert!((saturation - base[1]).abs() <= 0.2 + 1e-4);
+            assert!((value - base[2]).abs() <= 0.2 + 1e-4);
+            assert_eq!(color[3], params.color[3]);
+        }
+
+        // Other strokes differ, but replaying the same input reproduces the jitter
+        assert_ne!(stroke(&mut state, 50.0), first);
+        assert_eq!(stroke(&mut state, 0.0), first);
+
+        let invalid = BrushParams { value_jitter: 1.5, ..BrushParams::DEFAULT };
+        assert!(invalid.validate().is_err());
+    }
+
+    #[test]
+    fn test_smudge_offsets_point_back_to_the_previous_dab() {
+        let mut state = BrushState::with_params(BrushParams::DEFAULT);
+        let stroke = |state: &mut BrushState| {
+            state.begin_stroke();
+            let mut dabs = state.calculate_dabs([10.0, 10.0], 1.0, PointerEventType::Down);
+            dabs.extend(state.calculate_dabs([40.0, 10.0], 1.0, PointerEventType::Move));
+            dabs.extend(state.calculate_dabs([40.0, 40.0], 1.0, PointerEventType::Up));
+            dabs
+        };
+
+        let dabs = stroke(&mut state);
+        assert!(dabs.len() > 3);
+        assert_eq!(dabs[0].smudge_offset, [0.0, 0.0]);
+        for pair in dabs.windows(2) {
+            let step = [pair[1].position[0] - pair[0].position[0], pair[1].position[1] - pair[0].position[1]];
+            assert_eq!(pair[1].smudge_offset, step);
+        }
+        // A new stroke doesn't pull from where the last one ended
+        assert_eq!(stroke(&mut state)[0].smudge_offset, [0.0, 0.0]);
+
+        assert!(Tool::Smudge(0.5).validate().is_ok());
+        assert!(Tool::Smudge(1.5).validate().is_err());
+        assert_eq!(Tool::default().smudge_strength(), None);
+    }
+
+    #[test]
+    fn test_update_angles_smooths_azimuth() {
+        let mut state = BrushState::with_params(BrushParams {
+            angle_smoothing: 0.5,
+            ..BrushParams::default()
+        });
+        state.begin_stroke();
+        state.update_angles(Some(1.0), None);
+        assert_eq!(state.smoothed_azimuth(), Some(1.0));
+        state.update_angles(Some(2.0), None);
+        assert!((state.smoothed_azimuth().unwrap() - 1.5).abs() < 1e-5);
+        assert_eq!(state.smoothed_twist(), None);
+    }
+
+    #[test]
+    fn test_first_move_mode_zero_pressure_start_is_invisible() {
+        let dabs = zero_start_stroke(FirstDabPressureMode::FirstMove);
+        assert_eq!(dabs[0].position, [0.0, 0.0]);
+        assert_eq!(dabs[0].opacity, 0.0);
+    }
+
+    #[test]
+    fn test_max_of_first_samples_makes_start_visible() {
+        let dabs = zero_start_stroke(FirstDabPressureMode::MaxOfFirstSamples(3));
+        assert_eq!(dabs[0].position, [0.0, 0.0]);
+        assert!(dabs[0].opacity > 0.0);
+    }
+
+    #[test]
+    fn test_threshold_mode_starts_where_pressure_is_reached() {
+        let dabs = zero_start_stroke(FirstDabPressureMode::Threshold(0.4));
+        assert_eq!(dabs[0].position, [2.0, 0.0]);
+        assert!(dabs.iter().all(|dab| dab.opacity > 0.0));
+    }
+
+    #[test]
+    fn test_down_dab_is_instant_for_stamp_brushes() {
+        let tap = |spacing, down_dab_mode| {
+            let mut state = BrushState::with_params(BrushParams {
+                pressure_mapping: PressureMapping::Flow,
+                max_flow_percent: 1.0,
+                spacing,
+                down_dab_mode,
+                ..BrushParams::DEFAULT
+            });
+            state.begin_stroke();
+            let down = state.calculate_dabs([5.0, 5.0], 0.6, PointerEventType::Down);
+            let up = state.calculate_dabs([5.0, 5.0], 0.0, PointerEventType::Up);
+            (down, up)
+        };
+
+        // Auto: wide spacing stamps on Down with the Down pressure, and the tap adds nothing more
+        let (down, up) = tap(0.8, DownDabMode::Auto);
+        assert_eq!(down.len(), 1);
+        assert_eq!(down[0].position, [5.0, 5.0]);
+        assert!((down[0].opacity - 0.6).abs() < 1e-5);
+        assert!(up.is_empty());
+
+        // Auto: dense spacing keeps deferring, so a pure tap leaves nothing
+        let (down, up) = tap(0.15, DownDabMode::Auto);
+        assert!(down.is_empty() && up.is_empty());
+
+        // Explicit overrides win over the spacing
+        assert_eq!(tap(0.15, DownDabMode::Instant).0.len(), 1);
+        assert!(tap(0.8, DownDabMode::Deferred).0.is_empty());
+    }
+
+    #[test]
+    fn test_polygon_tip_side_count_is_validated() {
+        let params = |tip_shape| BrushParams { tip_shape, ..BrushParams::DEFAULT };
+        assert!(params(TipShape::Round).validate().is_ok());
+        assert!(params(TipShape::Polygon(3)).validate().is_ok());
+        assert!(params(TipShape::Polygon(2)).validate().is_err());
+        assert!(params(TipShape::Polygon(TipShape::MAX_SIDES + 1)).validate().is_err());
+    }
+
+    #[test]
+    fn test_zoom_aware_spacing_shrinks_when_zoomed_in() {
+        let mut state = BrushState::with_params(BrushParams {
+            zoom_aware_spacing: true,
+            ..BrushParams::DEFAULT
+        });
+        let size = 30.0; // 0.15 spacing = 4.5px at 1:1
+        assert!((state.spacing_px(size) - 4.5).abs() < 1e-4);
+        state.set_view_zoom(3.0);
+        assert!((state.spacing_px(size) - 1.5).abs() < 1e-4);
+        // Zooming out never spreads dabs further apart than the canvas spacing
+        state.set_view_zoom(0.25);
+        assert!((state.spacing_px(size) - 4.5).abs() < 1e-4);
+        // Subdivision is capped at half a canvas pixel
+        state.set_view_zoom(100.0);
+        assert_eq!(state.spacing_px(size), 0.5);
+        // Disabled: zoom is ignored
+        state.params.zoom_aware_spacing = false;
+        assert!((state.spacing_px(size) - 4.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_spacing_mode_sets_dab_count_on_a_segment() {
+        let dab_count = |size, spacing, spacing_mode| {
+            let mut state = BrushState::with_params(BrushParams { size, spacing, spacing_mode, ..BrushParams::DEFAULT });
+            state.begin_stroke();
+            let mut dabs = state.calculate_dabs([0.0, 0.0], 1.0, PointerEventType::Down);
+            dabs.extend(state.calculate_dabs([100.0, 0.0], 1.0, PointerEventType::Up));
+            dabs.len()
+        };
+
+        // Diameter fraction over 100px: 0.1 of a 20px brush = 2px apart, of a 200px brush = 20px
+        assert_eq!(dab_count(20.0, 0.1, SpacingMode::DiameterFraction), 50);
+        assert_eq!(dab_count(200.0, 0.1, SpacingMode::DiameterFraction), 5);
+        // Absolute pixels: 5px apart whatever the size
+        assert_eq!(dab_count(20.0, 5.0, SpacingMode::AbsolutePixels), 20);
+        assert_eq!(dab_count(200.0, 5.0, SpacingMode::AbsolutePixels), 20);
+
+        let params = |spacing, spacing_mode| BrushParams { spacing, spacing_mode, ..BrushParams::DEFAULT };
+        assert_eq!(BrushParams::DEFAULT.spacing_mode, SpacingMode::DiameterFraction);
+        assert!(params(5.0, SpacingMode::AbsolutePixels).validate().is_ok());
+        assert!(params(5.0, SpacingMode::DiameterFraction).validate().is_err());
+        assert!(params(0.0, SpacingMode::AbsolutePixels).validate().is_err());
+        assert!(params(-1.0, SpacingMode::AbsolutePixels).validate().is_err());
+        assert!(params(f32::NAN, SpacingMode::AbsolutePixels).validate().is_err());
+    }
+
+    #[test]
+    fn test_tap_places_dab_of_min_size() {
+        let tap = |min_tap_dab_size| {
+            let mut state = BrushState::with_params(BrushParams {
+                pressure_mapping: PressureMapping::Size,
+                min_tap_dab_size,
+                ..BrushParams::DEFAULT
+            });
+            state.begin_stroke();
+            let mut dabs = state.calculate_dabs([5.0, 5.0], 0.05, PointerEventType::Down);
+            dabs.extend(state.calculate_dabs([5.0, 5.0], 0.0, PointerEventType::Up));
+            dabs
+        };
+
+        // Default: a tap follows normal dynamics and places nothing
+        assert!(tap(0.0).is_empty());
+
+        let dabs = tap(12.0);
+        assert_eq!(dabs.len(), 1);
+        assert_eq!(dabs[0].position, [5.0, 5.0]);
+        assert!(dabs[0].size >= 12.0);
+    }
+
+    #[test]
+    fn test_pressure_smoothing_reduces_noise() {
+        // Alternating noisy pressure around 0.5 along a straight line
+        let stroke = |pressure_smoothing| {
+            let mut state = BrushState::with_params(BrushParams {
+                pressure_mapping: PressureMapping::Flow,
+                pressure_smoothing,
+                ..BrushParams::DEFAULT
+            });
+            state.begin_stroke();
+            let mut dabs = state.calculate_dabs([0.0, 0.0], 0.5, PointerEventType::Down);
+            for i in 1..40 {
+                let pressure = if i % 2 == 0 { 0.7 } else { 0.3 };
+                dabs.extend(state.calculate_dabs([i as f32 * 5.0, 0.0], pressure, PointerEventType::Move));
+            }
+            dabs
+        };
+        let variance = |dabs: &[BrushDab]| {
+            let tail = &dabs[dabs.len() / 2..];
+            let mean = tail.iter().map(|d| d.opacity).sum::<f32>() / tail.len() as f32;
+            tail.iter().map(|d| (d.opacity - mean).powi(2)).sum::<f32>() / tail.len() as f32
+        };
+
+        let raw = stroke(0.0);
+        let smoothed = stroke(0.8);
+        assert!(variance(&smoothed) < variance(&raw) * 0.25);
+        // Positions are unaffected by pressure smoothing
+        assert_eq!(raw.len(), smoothed.len());
+        assert_eq!(raw.last().unwrap().position, smoothed.last().unwrap().position);
+    }
+
+    #[test]
+    fn test_stabilization_reduces_zig_zag_variation() {
+        // A noisy zig-zag along a horizontal line, as from a shaky finger
+        let stroke = |stabilization| {
+            let mut state = BrushState::with_params(BrushParams {
+                size: 4.0,
+                stabilization,
+                ..BrushParams::DEFAULT
+            });
+            state.begin_stroke();
+            let mut dabs = state.calculate_dabs([0.0, 0.0], 1.0, PointerEventType::Down);
+            for i in 1..80 {
+                let y = if i % 2 == 0 { 3.0 } else { -3.0 };
+                dabs.extend(state.calculate_dabs([i as f32 * 2.0, y], 1.0, PointerEventType::Move));
+            }
+            dabs.extend(state.calculate_dabs([160.0, 0.0], 1.0, PointerEventType::Up));
+            dabs
+        };
+        // Total variation of the cross-stroke coordinate
+        let variation = |dabs: &[BrushDab]| dabs.windows(2).map(|w| (w[1].position[1] - w[0].position[1]).abs()).sum::<f32>();
+
+        let raw = stroke(0.0);
+        let stabilized = stroke(0.8);
+        assert!(
+            variation(&stabilized) < variation(&raw) * 0.5,
+            "stabilized {} vs raw {}",
+            variation(&stabilized),
+            variation(&raw)
+        );
+
+        // The lag is flushed on Up: the stroke still reaches the pen
+        let spacing = BrushParams { size: 4.0, ..BrushParams::DEFAULT }.spacing * 4.0;
+        let end = stabilized.last().unwrap().position;
+        assert!((end[0] - 160.0).abs() <= spacing + 1e-3 && end[1].abs() <= spacing + 1e-3, "stroke ended at {:?}", end);
+    }
+
+    #[test]
+    fn test_stabilizer_tether_trails_the_pen() {
+        let mut state = BrushState::with_params(BrushParams {
+            stabilization: 0.8,
+            ..BrushParams::DEFAULT
+        });
+        assert_eq!(state.stabilizer_tether(), None);
+        state.begin_stroke();
+        state.calculate_dabs([0.0, 0.0], 1.0, PointerEventType::Down);
+        state.calculate_dabs([50.0, 0.0], 1.0, PointerEventType::Move);
+
+        // The stabilized end lags between the start and the pen
+        let [pen, ink] = state.stabilizer_tether().unwrap();
+        assert_eq!(pen, [50.0, 0.0]);
+        assert!(ink[0] > 0.0 && ink[0] < 50.0);
+
+        state.calculate_dabs([60.0, 0.0], 1.0, PointerEventType::Up);
+        state.end_stroke();
+        assert_eq!(state.stabilizer_tether(), None);
+
+        // Nothing to show without stabilization
+        state.params.stabilization = 0.0;
+        state.begin_stroke();
+        state.calculate_dabs([0.0, 0.0], 1.0, PointerEventType::Down);
+        assert_eq!(state.stabilizer_tether(), None);
+    }
+
+    #[test]
+    fn test_fast_strokes_thin_out_without_pressure() {
+        // Mouse-like input: no pressure dynamics, only speed
+        let average_size = |step_px: f32| {
+            let mut state = BrushState::with_params(BrushParams {
+                pressure_mapping: PressureMapping::None,
+                velocity_mapping: VelocityMapping::Size,
+                ..BrushParams::DEFAULT
+            });
+            state.begin_stroke();
+            state.update_timestamp(0.0);
+            let mut dabs = state.calculate_dabs([0.0, 0.0], 0.5, PointerEventType::Down);
+            for i in 1..40 {
+                // One sample every 8ms; a coalesced duplicate must not read as infinite speed
+                state.update_timestamp(i as f64 * 8.0);
+                dabs.extend(state.calculate_dabs([i as f32 * step_px, 0.0], 0.5, PointerEventType::Move));
+                dabs.extend(state.calculate_dabs([i as f32 * step_px, 0.0], 0.5, PointerEventType::Move));
+            }
+            let sizes: Vec<f32> = dabs.iter().skip(dabs.len() / 2).map(|dab| dab.size).collect();
+            sizes.iter().sum::<f32>() / sizes.len() as f32
+        };
+
+        let slow = average_size(1.0); // 125 px/s
+        let fast = average_size(40.0); // 5000 px/s, past the max speed
+        let params = BrushParams::DEFAULT;
+        assert!(slow > params.size * 0.9, "slow stroke size {}", slow);
+        assert!((fast - params.size * params.min_velocity_percent).abs() < 1.0, "fast stroke size {}", fast);
+    }
+
+    #[test]
+    fn test_pen_pressure_varies_dab_size() {
+        let mut state = BrushState::with_params(BrushParams {
+            size: 20.0,
+            pressure_mapping: PressureMapping::Size,
+            min_size_percent: 0.2,
+            ..BrushParams::DEFAULT
+        });
+        state.begin_stroke();
+
+        // Tablet force readings rising to full and falling again, converted as the desktop
+        // build converts them
+        let mut sizes_by_sample = Vec::new();
+        for i in 0..=40 {
+            let force = 1.0 - (i as f64 / 20.0 - 1.0).abs();
+            let pressure = crate::input::pressure_from_force(Some(force));
+            let event_type = if i == 0 { PointerEventType::Down } else { PointerEventType::Move };
+            state.update_timestamp(i as f64 * 8.0);
+            let dabs = state.calculate_dabs([i as f32 * 4.0, 0.0], pressure, event_type);
+            sizes_by_sample.push(dabs.iter().map(|dab| dab.size).collect::<Vec<_>>());
+        }
+
+        let sizes: Vec<f32> = sizes_by_sample.iter().flatten().copied().collect();
+        assert!(sizes.len() > 10);
+        let smallest = sizes.iter().copied().fold(f32::MAX, f32::min);
+        let largest = sizes.iter().copied().fold(0.0, f32::max);
+        assert!(largest > 18.0, "Full pressure should reach the full size, got {}", largest);
+        assert!(smallest < largest * 0.5, "Light pressure should thin the stroke ({} vs {})", smallest, largest);
+
+        // Sizes follow the pressure: thin where the pen lands and lifts, full in between
+        assert!(sizes[0] < largest * 0.5 && sizes[sizes.len() - 1] < largest * 0.5, "{:?}", sizes);
+        assert!(sizes_by_sample[20].iter().all(|&size| size == largest));
+    }
+
+    #[test]
+    fn test_spline_dabs_follow_a_quarter_circle() {
+        // Coarse input around a quarter circle: straight segments would cut corners by
+        // r * (1 - cos(7.5deg)) = 0.86px between samples
+        let radius = 100.0_f32;
+        let point = |degrees: f32| [radius * degrees.to_radians().cos(), radius * degrees.to_radians().sin()];
+        let mut state = BrushState::with_params(BrushParams {
+            size: 4.0,
+            spacing: 0.1,
+            pressure_mapping: PressureMapping::None,
+            ..BrushParams::DEFAULT
+        });
+        state.begin_stroke();
+        let mut dabs = state.calculate_dabs(point(0.0), 1.0, PointerEventType::Down);
+        for step in 1..6 {
+            dabs.extend(state.calculate_dabs(point(step as f32 * 15.0), 1.0, PointerEventType::Move));
+        }
+        dabs.extend(state.calculate_dabs(point(90.0), 1.0, PointerEventType::Up));
+
+        let error = |dab: &BrushDab| (distance([0.0, 0.0], dab.position) - radius).abs();
+        let angle = |dab: &BrushDab| dab.position[1].atan2(dab.position[0]).to_degrees();
+        // Interior segments are true Catmull-Rom curves and hug the circle
+        let interior: Vec<_> = dabs.iter().filter(|dab| (15.0..=75.0).contains(&angle(dab))).collect();
+        assert!(interior.len() > 100);
+        let worst = interior.iter().map(|dab| error(dab)).fold(0.0, f32::max);
+        assert!(worst < 0.05, "interior dabs are up to {}px off the circle", worst);
+        // The straight first segment and mirrored last segment still stay within the chord sag
+        assert!(dabs.iter().all(|dab| error(dab) < 0.9));
+        let end = dabs.last().unwrap().position;
+        // (spacing is never below half a pixel)
+        assert!(distance(end, point(90.0)) <= 0.5 + 1e-3, "stroke ended at {:?}", end);
+    }
+
+    #[test]
+    fn test_fit_line_vertical_and_residual() {
+        let fit = fit_line([[5.0, 0.0], [5.0, 10.0], [5.0, 20.0]].into_iter()).unwrap();
+        assert!(fit.direction[0].abs() < 1e-5);
+        assert!(fit.rms_distance < 1e-4);
+        let projected = fit.project([8.0, 7.0]);
+        assert!((projected[0] - 5.0).abs() < 1e-4 && (projected[1] - 7.0).abs() < 1e-4);
+
+        // Points alternating one pixel either side of y = 0
+        let zigzag = (0..10).map(|i| [i as f32, if i % 2 == 0 { 1.0 } else { -1.0 }]);
+        let fit = fit_line(zigzag).unwrap();
+        assert!((fit.rms_distance - 1.0).abs() < 0.05);
+        assert!(fit_line([[1.0, 1.0], [1.0, 1.0]].into_iter()).is_none());
+    }
+
+    /// Feed a slow stroke through `path` and return the largest distance of any dab from y = 0
+    fn line_assist_max_deviation(line_assist: f32, path: impl Fn(usize) -> [f32; 2]) -> f32 {
+        let mut state = BrushState::with_params(BrushParams {
+            line_assist,
+            ..BrushParams::DEFAULT
+        });
+        state.begin_stroke();
+        state.calculate_dabs(path(0), 1.0, PointerEventType::Down);
+        let mut dabs = Vec::new();
+        for i in 1..80 {
+            dabs.extend(state.calculate_dabs(path(i), 1.0, PointerEventType::Move));
+        }
+        // Skip the start, before the assist has seen enough input to engage
+        dabs[dabs.len() / 2..]
+            .iter()
+            .map(|dab| dab.position[1].abs())
+            .fold(0.0, f32::max)
+    }
+
+    #[test]
+    fn test_line_assist_straightens_slow_jitter() {
+        let jitter = |i: usize| [i as f32 * 0.5, if i % 2 == 0 { 0.4 } else { -0.4 }];
+        let raw = line_assist_max_deviation(0.0, jitter);
+        let assisted = line_assist_max_deviation(1.0, jitter);
+        assert!(assisted < raw * 0.6, "assisted {} vs raw {}", assisted, raw);
+    }
+
+    #[test]
+    fn test_line_assist_leaves_curves_alone() {
+        // A slow arc of radius 40 (one pixel per sample), measured as drift from the raw arc
+        let arc = |i: usize| {
+            let angle = i as f32 / 40.0;
+            [40.0 * angle.sin(), 40.0 * (1.0 - angle.cos())]
+        };
+        let raw = line_assist_max_deviation(0.0, arc);
+        let assisted = line_assist_max_deviation(1.0, arc);
+        assert!((assisted - raw).abs() < 1.0, "assisted {} vs raw {}", assisted, raw);
+    }
+
+    #[test]
+    fn test_max_stroke_length_splits_without_gaps() {
+        let stroke = |max_stroke_length| {
+            let mut state = BrushState::with_params(BrushParams {
+                max_stroke_length,
+                ..BrushParams::DEFAULT
+            });
+            state.begin_stroke();
+            state.calculate_dabs([0.0, 0.0], 1.0, PointerEventType::Down);
+            let mut dabs = Vec::new();
+            let mut breaks = 0;
+            for i in 1..=110 {
+                dabs.extend(state.calculate_dabs([i as f32 * 3.0, 0.0], 1.0, PointerEventType::Move));
+                if state.take_segment_break() {
+                    breaks += 1;
+                }
+            }
+            (dabs, breaks)
+        };
+
+        let (unsplit, no_breaks) = stroke(0.0);
+        let (split, breaks) = stroke(100.0);
+        assert_eq!(no_breaks, 0);
+        assert_eq!(breaks, 3);
+        // Splitting doesn't change where dabs land
+        assert_eq!(unsplit.len(), split.len());
+        for (a, b) in unsplit.iter().zip(split.iter()) {
+            assert_eq!(a.position, b.position);
+        }
+    }
+
+    #[test]
+    fn test_angle_constraint_locks_to_nearest_axis() {
+        let mut state = BrushState::with_params(BrushParams {
+            angle_constraint: 45.0,
+            ..BrushParams::DEFAULT
+        });
+        state.begin_stroke();
+        state.calculate_dabs([10.0, 10.0], 1.0, PointerEventType::Down);
+        // Heading at roughly 40 degrees with a wobble, then drifting off toward 60 degrees
+        let mut dabs = Vec::new();
+        for i in 1..=40 {
+            let t = i as f32 * 2.0;
+            let wobble = if i % 2 == 0 { 0.5 } else { -0.5 };
+            let drift = if i > 20 { (i - 20) as f32 } else { 0.0 };
+            let position = [10.0 + t * 0.77, 10.0 + t * 0.64 + wobble + drift];
+            dabs.extend(state.calculate_dabs(position, 1.0, PointerEventType::Move));
+        }
+
+        assert!(dabs.len() > 10);
+        for dab in &dabs {
+            let [x, y] = [dab.position[0] - 10.0, dab.position[1] - 10.0];
+            assert!((x - y).abs() < 1e-3, "dab {:?} is off the 45 degree axis", dab.position);
+        }
+
+        // The next stroke picks its own axis
+        state.end_stroke();
+        state.begin_stroke();
+        state.calculate_dabs([0.0, 0.0], 1.0, PointerEventType::Down);
+        for i in 1..=10 {
+            for dab in state.calculate_dabs([i as f32 * 3.0, 1.0], 1.0, PointerEventType::Move) {
+                assert!(dab.position[1].abs() < 1e-3);
+            }
+        }
+    }
+
+    /// Draw a straight stroke along +x with the given cap style and return all dabs
+    fn capped_stroke(cap_style: CapStyle) -> Vec<BrushDab> {
+        let mut state = BrushState::with_params(BrushParams {
+            cap_style,
+            pressure_mapping: PressureMapping::None,
+            ..BrushParams::DEFAULT
+        });
+        state.begin_stroke();
+        let mut dabs = state.calculate_dabs([0.0, 0.0], 1.0, PointerEventType::Down);
+        for i in 1..=20 {
+            dabs.extend(state.calculate_dabs([i as f32 * 5.0, 0.0], 1.0, PointerEventType::Move));
+        }
+        dabs.extend(state.calculate_dabs([100.0, 0.0], 1.0, PointerEventType::Up));
+        dabs
+    }
+
+    #[test]
+    fn test_flat_caps_trim_both_ends() {
+        let round = capped_stroke(CapStyle::Round);
+        let flat = capped_stroke(CapStyle::Flat);
+        // Caps only trim dabs, they never move, add, or drop them
+        assert_eq!(round.len(), flat.len());
+        assert!(round.iter().all(|dab| dab.clips == [None, None]));
+
+        // The first dab is cut through its center, facing back along the stroke
+        let first = flat.first().unwrap();
+        let clip = first.clips[0].unwrap();
+        assert!((clip.normal[0] + 1.0).abs() < 1e-4 && clip.normal[1].abs() < 1e-4);
+        assert!(clip.offset.abs() < 1e-4);
+
+        // The last dab is cut through its center, facing forward
+        let last = flat.last().unwrap();
+        let clip = last.clips.iter().flatten().last().unwrap();
+        assert!((clip.normal[0] - 1.0).abs() < 1e-4);
+        assert!(clip.offset.abs() < 1e-4);
+
+        // Nothing reaches behind the start or past the end
+        let end = last.position[0];
+        for dab in &flat {
+            let radius = dab.size * 0.5;
+            if dab.position[0] - radius < 0.0 {
+                assert!(dab.clips.iter().flatten().any(|c| c.normal[0] < 0.0));
+            }
+            if dab.position[0] + radius > end {
+                assert!(dab.clips.iter().flatten().any(|c| c.normal[0] > 0.0));
+            }
+        }
+    }
+
+    #[test]
+    fn test_source_change_releases_held_cap_dabs() {
+        // A stroke cut short by switching to the mouse, before the pen lifts
+        let interrupted_stroke = |cap_style| {
+            let mut state = BrushState::with_params(BrushParams {
+                cap_style,
+                pressure_mapping: PressureMapping::None,
+                ..BrushParams::DEFAULT
+            });
+            assert!(state.update_brush_src(PointerEventSource::TabletTool).is_empty());
+            state.begin_stroke();
+            let mut dabs = state.calculate_dabs([0.0, 0.0], 1.0, PointerEventType::Down);
+            for i in 1..=20 {
+                dabs.extend(state.calculate_dabs([i as f32 * 5.0, 0.0], 1.0, PointerEventType::Move));
+            }
+            let released = state.update_brush_src(PointerEventSource::Mouse);
+            (dabs, released)
+        };
+
+        // Round caps hold nothing back; flat caps release the rest, end-capped
+        let (round, round_released) = interrupted_stroke(CapStyle::Round);
+        assert!(round_released.is_empty());
+        let (flat, flat_released) = interrupted_stroke(CapStyle::Flat);
+        assert!(!flat_released.is_empty());
+        assert_eq!(flat.len() + flat_released.len(), round.len());
+        let clip = flat_released.last().unwrap().clips.iter().flatten().last().copied().unwrap();
+        assert!((clip.normal[0] - 1.0).abs() < 1e-4);
+    }
+
+    fn tapered_stroke(taper_in: f32, taper_out: f32, length: f32) -> Vec<BrushDab> {
+        let mut state = BrushState::with_params(BrushParams {
+            taper_in,
+            taper_out,
+            pressure_mapping: PressureMapping::None,
+            ..BrushParams::DEFAULT
+        });
+        state.begin_stroke();
+        let mut dabs = state.calculate_dabs([0.0, 0.0], 1.0, PointerEventType::Down);
+        let steps = (length / 5.0) as usize;
+        for i in 1..steps {
+            dabs.extend(state.calculate_dabs([i as f32 * 5.0, 0.0], 1.0, PointerEventType::Move));
+        }
+        dabs.extend(state.calculate_dabs([length, 0.0], 1.0, PointerEventType::Up));
+        dabs
+    }
+
+    #[test]
+    fn test_taper_shrinks_both_ends() {
+        let full = BrushParams::DEFAULT.size;
+        let dabs = tapered_stroke(40.0, 60.0, 300.0);
+        assert!(dabs.len() > 20);
+
+        let first = dabs.first().unwrap();
+        let last = dabs.last().unwrap();
+        let middle = dabs.iter().find(|dab| dab.position[0] >= 150.0).unwrap();
+        assert!((middle.size - full).abs() < 1e-3);
+        assert!(first.size < middle.size * 0.25);
+        assert!(last.size < middle.size * 0.25);
+
+        // Sizes ramp with distance from each end, over each taper's own length
+        for dab in &dabs {
+            let x = dab.position[0];
+            let expected = (x / 40.0).min((last.position[0] - x) / 60.0).clamp(0.0, 1.0) * full;
+            assert!((dab.size - expected).abs() < 0.5, "dab at {} is {}, expected {}", x, dab.size, expected);
+        }
+
+        // Without tapers every dab is full size
+        assert!(tapered_stroke(0.0, 0.0, 300.0).iter().all(|dab| (dab.size - full).abs() < 1e-3));
+    }
+
+    #[test]
+    fn test_short_stroke_tapers_symmetrically() {
+        // 50 pixels long, with 100 pixels of taper each way: both shrink to 25 pixels
+        let dabs = tapered_stroke(100.0, 100.0, 50.0);
+        let end = dabs.last().unwrap().position[0];
+        let peak = dabs.iter().map(|dab| dab.size).fold(0.0, f32::max);
+        assert!(dabs.first().unwrap().size < peak && dabs.last().unwrap().size < peak);
+        for dab in &dabs {
+            let mirrored = dabs
+                .iter()
+                .min_by(|a, b| {
+                    let da = (a.position[0] - (end - dab.position[0])).abs();
+                    let db = (b.position[0] - (end - dab.position[0])).abs();
+                    da.total_cmp(&db)
+                })
+                .unwrap();
+            assert!((dab.size - mirrored.size).abs() < 4.0);
+        }
+    }
+
+    #[test]
+    fn test_square_caps_add_pieces_past_the_ends() {
+        let flat = capped_stroke(CapStyle::Flat);
+        let square = capped_stroke(CapStyle::Square);
+        assert_eq!(square.len(), flat.len() + 2);
+
+        let pieces: Vec<&BrushDab> = square.iter().filter(|dab| dab.square_cap).collect();
+        assert_eq!(pieces.len(), 2);
+        assert_eq!(pieces[0].position, [0.0, 0.0]);
+        assert_eq!(pieces[1].position, flat.last().unwrap().position);
+        // Each piece keeps only the half beyond its cap line
+        assert!(pieces[0].clips[0].unwrap().normal[0] > 0.99);
+        assert!(pieces[1].clips[0].unwrap().normal[0] < -0.99);
+    }
+
+    #[test]
+    fn test_dab_transform_maps_local_frame_to_canvas() {
+        let dab = BrushDab {
+            position: [50.0, 50.0],
+            size: 20.0,
+            opacity: 1.0,
+            color: [0.0, 0.0, 0.0, 1.0],
+            hardness: 1.0,
+            angle: std::f32::consts::FRAC_PI_2,
+            tip_shape: TipShape::Round,
+            clips: [None, None],
+            square_cap: false,
+            smudge_offset: [0.0; 2],
+        };
+
+        // A quarter turn: local +x points down the canvas, scaled to the radius
+        let [x_axis, y_axis] = dab.transform();
+        assert!(x_axis[0].abs() < 1e-4 && (x_axis[1] - 10.0).abs() < 1e-4);
+        assert!((y_axis[0] + 10.0).abs() < 1e-4 && y_axis[1].abs() < 1e-4);
+
+        // A clip 5px below the center is half a radius along local +x
+        let clip = DabClip { normal: [0.0, 1.0], offset: 5.0 };
+        let (normal, offset) = clip.in_dab_frame(dab.transform());
+        assert!((normal[0] - 1.0).abs() < 1e-4 && normal[1].abs() < 1e-4);
+        assert!((offset - 0.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_symmetry_mirrors_dab_positions() {
+        let dab = BrushDab {
+            position: [30.0, 40.0],
+            size: 12.0,
+            opacity: 0.7,
+            color: [1.0, 0.0, 0.0, 1.0],
+            hardness: 0.5,
+            angle: 0.3,
+            tip_shape: TipShape::Polygon(3),
+            clips: [Some(DabClip { normal: [0.6, 0.8], offset: 2.0 }), None],
+            square_cap: false,
+            smudge_offset: [3.0, 4.0],
+        };
+        let mirrored = |mode| {
+            let mut dabs = vec![dab];
+            Symmetry { mode, center: [50.0, 50.0] }.apply(&mut dabs);
+            dabs
+        };
+
+        let vertical = mirrored(SymmetryMode::Vertical);
+        assert_eq!(vertical.len(), 2);
+        assert_eq!(vertical[1].position, [70.0, 40.0]);
+        assert_eq!(vertical[1].angle, -0.3);
+        assert_eq!(vertical[1].clips[0].unwrap().normal, [-0.6, 0.8]);
+        assert_eq!(vertical[1].smudge_offset, [-3.0, 4.0]);
+        assert_eq!((vertical[1].size, vertical[1].opacity), (dab.size, dab.opacity));
+
+        let horizontal = mirrored(SymmetryMode::Horizontal);
+        assert_eq!(horizontal[1].position, [30.0, 60.0]);
+        assert_eq!(horizontal[1].smudge_offset, [3.0, -4.0]);
+
+        // Quarter turns about the center
+        let radial = mirrored(SymmetryMode::Radial(4));
+        let expected = [[30.0, 40.0], [60.0, 30.0], [70.0, 60.0], [40.0, 70.0]];
+        assert_eq!(radial.len(), 4);
+        for (dab, expected) in radial.iter().zip(expected) {
+            assert!((dab.position[0] - expected[0]).abs() < 1e-3 && (dab.position[1] - expected[1]).abs() < 1e-3);
+        }
+        assert!((radial[1].angle - (0.3 + std::f32::consts::FRAC_PI_2)).abs() < 1e-5);
+        assert!((radial[1].smudge_offset[0] + 4.0).abs() < 1e-5 && (radial[1].smudge_offset[1] - 3.0).abs() < 1e-5);
+
+        assert_eq!(mirrored(SymmetryMode::None).len(), 1);
+        assert!(Symmetry { mode: SymmetryMode::Radial(1), center: [0.0, 0.0] }.validate().is_err());
+    }
+
+    #[test]
+    fn test_pressure_curve_follows_control_points() {
+        // Two points give a straight line
+        let linear = PressureCurve::new(&[[0.0, 0.0], [1.0, 1.0]]).unwrap();
+        for pressure in [0.0, 0.1, 0.5, 0.9, 1.0] {
+            assert!((linear.evaluate(pressure) - pressure).abs() < 1e-6);
+        }
+
+        // An S-curve passes through its points, never falls, and is symmetric about the middle
+        let s_curve = PressureCurve::from_flat(&[0.0, 0.0, 0.25, 0.05, 0.5, 0.5, 0.75, 0.95, 1.0, 1.0]).unwrap();
+        for &[input, output] in s_curve.points() {
+            assert!((s_curve.evaluate(input) - output).abs() < 1e-6);
+        }
+        let samples: Vec<f32> = (0..=100).map(|i| s_curve.evaluate(i as f32 / 100.0)).collect();
+        assert!(samples.windows(2).all(|pair| pair[1] >= pair[0]));
+        assert!(s_curve.evaluate(0.1) < 0.1 && s_curve.evaluate(0.9) > 0.9);
+        assert!((s_curve.evaluate(0.3) + s_curve.evaluate(0.7) - 1.0).abs() < 1e-5);
+
+        // A plateau stays flat instead of overshooting, and the ends hold outside the points
+        let plateau = PressureCurve::new(&[[0.2, 0.1], [0.4, 0.5], [0.6, 0.5], [1.0, 1.0]]).unwrap();
+        assert_eq!(plateau.evaluate(0.5), 0.5);
+        assert_eq!(plateau.evaluate(0.0), 0.1);
+        assert!((0.1..=0.5).contains(&plateau.evaluate(0.3)));
+
+        // The curve replaces the size gamma when set
+        let mut params = BrushParams { pressure_mapping: PressureMapping::Size, size_gamma: 3.0, ..BrushParams::DEFAULT };
+        params.pressure_response = PressureResponse::Curve(linear);
+        let state = BrushState::with_params(params);
+        let scale = params.min_size_percent + 0.2 * (params.max_size_percent - params.min_size_percent);
+        assert!((state.calculate_size_at_pressure(0.2) - params.size * scale.min(1.0)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_pressure_curve_validation() {
+        assert!(PressureCurve::new(&[[0.0, 0.0]]).is_err());
+        assert!(PressureCurve::new(&[[0.0, 0.0]; PressureCurve::MAX_POINTS + 1]).is_err());
+        assert!(PressureCurve::new(&[[0.5, 0.0], [0.2, 1.0]]).is_err());
+        assert!(PressureCurve::new(&[[0.5, 0.0], [0.5, 1.0]]).is_err());
+        assert!(PressureCurve::new(&[[0.0, 0.0], [1.0, 1.5]]).is_err());
+        assert!(PressureCurve::from_flat(&[0.0, 0.0, 1.0]).is_err());
+        assert!(PressureCurve::from_flat(&[0.0, 0.0, 1.0, 1.0]).is_ok());
+    }
+
+    #[test]
+    fn test_apply_preset_keeps_color_and_input_filter() {
+        let mut params = BrushParams {
+            color: [0.2, 0.4, 0.6, 1.0],
+            input_filter_mode: InputFilterMode::PenOnly,
+            ..BrushParams::PENCIL
+        };
+        assert!(params.apply_preset("ink"));
+        assert_eq!(params.size, BrushParams::INK.size);
+        assert_eq!(params.color, [0.2, 0.4, 0.6, 1.0]);
+        assert_eq!(params.input_filter_mode, InputFilterMode::PenOnly);
+
+        // Unknown names leave the brush alone
+        assert!(!params.apply_preset("crayon"));
+        assert_eq!(params.size, BrushParams::INK.size);
+    }
+
+    #[test]
+    fn test_brush_json_round_trip() {
+        let params = BrushParams {
+            tip_shape: TipShape::Polygon(5),
+            pressure_response: PressureResponse::Curve(PressureCurve::new(&[[0.0, 0.2], [1.0, 0.9]]).unwrap()),
+            blend_mode: BrushBlendMode::Multiply,
+            ..BrushParams::INK
+        };
+        let restored = BrushParams::from_json(&params.to_json()).unwrap();
+        assert_eq!(restored.to_json(), params.to_json());
+
+        // Missing fields take their defaults; malformed JSON is an error
+        assert_eq!(BrushParams::from_json(r#"{"size": 12.0}"#).unwrap().flow, BrushParams::DEFAULT.flow);
+        assert!(BrushParams::from_json("{ size: 12").is_err());
+    }
+
+    #[test]
+    fn test_uniform_opacity_moves_flow_to_the_stroke() {
+        let params = BrushParams { flow: 0.5, max_opacity: 0.8, ..BrushParams::DEFAULT };
+        assert_eq!(params.stroke_opacity(), 0.8);
+        let per_stroke = BrushParams { opacity_mode: OpacityMode::PerStroke, ..params };
+        assert_eq!(per_stroke.stroke_opacity(), 0.8);
+        let uniform = BrushParams { opacity_mode: OpacityMode::Uniform, ..params };
+        assert!((uniform.stroke_opacity() - 0.4).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_airbrush_deposits_while_held_still() {
+        let mut state = BrushState::with_params(BrushParams {
+            airbrush: true,
+            airbrush_rate: 20.0,
+            ..BrushParams::DEFAULT
+        });
+        state.begin_stroke();
+        state.update_timestamp(1000.0);
+        state.calculate_dabs([50.0, 50.0], 1.0, PointerEventType::Down);
+
+        // 20 dabs per second at full pressure: one every 50 ms, all where the pen rests
+        assert!(state.airbrush_dabs(1040.0).is_empty());
+        let dabs = state.airbrush_dabs(1260.0);
+        assert_eq!(dabs.len(), 5);
+        assert!(dabs.iter().all(|dab| dab.position == [50.0, 50.0]));
+        // The leftover 10 ms carries over to the next dab
+        assert_eq!(state.airbrush_dabs(1300.0).len(), 1);
+
+        // A long stall deposits a bounded amount
+        assert_eq!(state.airbrush_dabs(60_000.0).len(), MAX_AIRBRUSH_DABS_PER_UPDATE);
+
+        // Lifting the pen stops deposition at once
+        state.end_stroke();
+        assert!(!state.is_airbrushing());
+        assert!(state.airbrush_dabs(70_000.0).is_empty());
+    }
+
+    #[test]
+    fn test_airbrush_rate_scales_with_pressure() {
+        let held = |pressure: f32| {
+            let mut state = BrushState::with_params(BrushParams {
+                airbrush: true,
+                airbrush_rate: 20.0,
+                pressure_mapping: PressureMapping::None,
+                ..BrushParams::DEFAULT
+            });
+            state.begin_stroke();
+            state.update_timestamp(0.0);
+            state.calculate_dabs([10.0, 10.0], pressure, PointerEventType::Down);
+            state.airbrush_dabs(1000.0).len()
+        };
+        assert_eq!(held(1.0), 20);
+        assert_eq!(held(0.5), 10);
+        assert_eq!(held(0.0), 0);
+
+        // Off: holding still places nothing
+        let mut state = BrushState::new();
+        state.begin_stroke();
+        state.update_timestamp(0.0);
+        state.calculate_dabs([10.0, 10.0], 1.0, PointerEventType::Down);
+        assert!(state.airbrush_dabs(1000.0).is_empty());
+    }
+
+    #[test]
+    fn test_repaired_brush_passes_validation() {
+        let broken = BrushParams {
+            size: -4.0,
+            flow: 1.5,
+            hardness: f32::NAN,
+            spacing: 2.0,
+            pressure_smoothing: 1.0,
+            angle_constraint: 400.0,
+            tip_shape: TipShape::Polygon(100),
+            smudge_length: 1.5,
+            ..BrushParams::DEFAULT
+        };
+        assert!(broken.validate().is_err());
+        assert!(BrushParams { smudge_length: -0.1, ..BrushParams::DEFAULT }.validate().is_err());
+
+        let repaired = broken.repaired();
+        assert!(repaired.validate().is_ok());
+        assert_eq!(repaired.flow, 1.0);
+        assert_eq!(repaired.smudge_length, 1.0);
+        assert_eq!(repaired.hardness, BrushParams::DEFAULT.hardness);
+        assert_eq!(repaired.tip_shape, TipShape::Polygon(TipShape::MAX_SIDES));
+
+        // Valid parameters come back unchanged
+        assert_eq!(BrushParams::PENCIL.repaired().to_json(), BrushParams::PENCIL.to_json());
+    }
+
+    #[test]
+    fn test_non_finite_values_fail_validation() {
+        for value in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+            let cases = [
+                BrushParams { size: value, ..BrushParams::DEFAULT },
+                BrushParams { spacing: value, ..BrushParams::DEFAULT },
+                BrushParams { flow: value, ..BrushParams::DEFAULT },
+                BrushParams { max_opacity: value, ..BrushParams::DEFAULT },
+                BrushParams { velocity_max_speed: value, ..BrushParams::DEFAULT },
+                BrushParams { max_stroke_length: value, ..BrushParams::DEFAULT },
+                BrushParams { min_tap_dab_size: value, ..BrushParams::DEFAULT },
+            ];
+            for params in cases {
+                assert!(params.validate().is_err(), "{:?} passed validation", params);
+                assert!(params.repaired().validate().is_ok());
+            }
+        }
+    }
+}
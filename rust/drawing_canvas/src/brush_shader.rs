@@ -0,0 +1,237 @@
+//! Custom Brush Shaders
+//!
+//! Builds the brush shader source, optionally with a user-supplied WGSL tip mask
+//! spliced into the template in `shaders/brush.wgsl`. The mask is a single function
+//!
+//! ```wgsl
+//! fn brush_mask(uv: vec2<f32>) -> f32
+//! ```
+//!
+//! returning coverage (0-1) at `uv`, a point in the dab's local frame with the tip
+//! spanning -1 to 1 on each axis. It multiplies the procedural round/polygon falloff
+//! the same way a stamp texture does.
+//!
+//! Snippets are sandboxed to plain functions and constants: they can't declare
+//! bindings, entry points, or pipeline overrides, so they only ever see `uv`.
+
+use wgpu::naga;
+
+/// Built-in brush shader (the template the custom mask is spliced into)
+const BRUSH_SHADER_TEMPLATE: &str = include_str!("shaders/brush.wgsl");
+
+/// Markers around the default `brush_mask` in the template
+const MASK_BEGIN_MARKER: &str = "// BEGIN BRUSH MASK";
+const MASK_END_MARKER: &str = "// END BRUSH MASK";
+
+/// The template's interpolated local coordinates, rewritten for sample-rate shading
+const UV_OUTPUT: &str = "@location(0) uv: vec2<f32>,";
+const UV_OUTPUT_PER_SAMPLE: &str = "@location(0) @interpolate(perspective, sample) uv: vec2<f32>,";
+
+/// Name of the function a custom mask must define
+pub const BRUSH_MASK_FUNCTION: &str = "brush_mask";
+
+/// Longest accepted custom mask snippet, in bytes
+pub const MAX_BRUSH_MASK_SOURCE_LEN: usize = 64 * 1024;
+
+/// Build the brush shader source, with `mask` in place of the default mask if given
+///
+/// The snippet is checked on its own first (so errors point at the user's code), then
+/// the assembled shader is validated. Errors are human-readable compiler messages.
+pub fn build_brush_shader(mask: Option<&str>) -> Result<String, String> {
+    let Some(mask) = mask else {
+        return Ok(BRUSH_SHADER_TEMPLATE.to_string());
+    };
+
+    validate_mask_snippet(mask)?;
+
+    let begin = BRUSH_SHADER_TEMPLATE
+        .find(MASK_BEGIN_MARKER)
+        .expect("brush.wgsl is missing the brush mask begin marker");
+    let end = BRUSH_SHADER_TEMPLATE
+        .find(MASK_END_MARKER)
+        .expect("brush.wgsl is missing the brush mask end marker")
+        + MASK_END_MARKER.len();
+    let source = format!(
+        "{}{}\n{}\n{}{}",
+        &BRUSH_SHADER_TEMPLATE[..begin],
+        MASK_BEGIN_MARKER,
+        mask.trim(),
+        MASK_END_MARKER,
+        &BRUSH_SHADER_TEMPLATE[end..],
+    );
+
+    // Catches clashes with the template's own names and anything only wrong in context
+    let module = naga::front::wgsl::parse_str(&source)
+        .map_err(|e| format!("Brush shader failed to compile:\n{}", e.emit_to_string(&source)))?;
+    validate_module(&module, &source)?;
+
+    Ok(source)
+}
+
+/// Make a built brush shader run its fragment stage once per sample
+///
+/// The tip shape is computed in the fragment shader, not by the quad's geometry, so plain
+/// MSAA would only smooth the quad's edges. Interpolating `uv` at each sample position
+/// evaluates the shape per sample and lets the resolve average it. Needs multisampled
+/// shading support (`wgpu::DownlevelFlags::MULTISAMPLED_SHADING`, absent on WebGL2).
+pub fn with_sample_shading(source: &str) -> String {
+    assert!(source.contains(UV_OUTPUT), "brush.wgsl is missing the uv vertex output");
+    source.replacen(UV_OUTPUT, UV_OUTPUT_PER_SAMPLE, 1)
+}
+
+/// Check that a snippet is a self-contained mask function and nothing else
+fn validate_mask_snippet(mask: &str) -> Result<(), String> {
+    if mask.len() > MAX_BRUSH_MASK_SOURCE_LEN {
+        return Err(format!(
+            "Brush mask is {} bytes, longer than the {} byte limit",
+            mask.len(),
+            MAX_BRUSH_MASK_SOURCE_LEN
+        ));
+    }
+    // The markers delimit the slot, so a snippet containing them would break splicing
+    if mask.contains(MASK_BEGIN_MARKER) || mask.contains(MASK_END_MARKER) {
+        return Err("Brush mask must not contain the template markers".to_string());
+    }
+
+    let module = naga::front::wgsl::parse_str(mask)
+        .map_err(|e| format!("Brush mask failed to compile:\n{}", e.emit_to_string(mask)))?;
+
+    if !module.global_variables.is_empty() {
+        return Err("Brush mask must not declare global variables or bindings".to_string());
+    }
+    if !module.entry_points.is_empty() {
+        return Err("Brush mask must not declare entry points".to_string());
+    }
+    if !module.overrides.is_empty() {
+        return Err("Brush mask must not declare pipeline overrides".to_string());
+    }
+
+    let mask_fn = module
+        .functions
+        .iter()
+        .map(|(_, function)| function)
+        .find(|function| function.name.as_deref() == Some(BRUSH_MASK_FUNCTION))
+        .ok_or_else(|| format!("Brush mask must define `fn {}(uv: vec2<f32>) -> f32`", BRUSH_MASK_FUNCTION))?;
+
+    let is_vec2_f32 = |ty: naga::Handle<naga::Type>| {
+        matches!(
+            module.types[ty].inner,
+            naga::TypeInner::Vector { size: naga::VectorSize::Bi, scalar: naga::Scalar::F32 }
+        )
+    };
+    let is_f32 = |ty: naga::Handle<naga::Type>| {
+        matches!(module.types[ty].inner, naga::TypeInner::Scalar(naga::Scalar::F32))
+    };
+    let signature_ok = mask_fn.arguments.len() == 1
+        && is_vec2_f32(mask_fn.arguments[0].ty)
+        && mask_fn.result.as_ref().is_some_and(|result| is_f32(result.ty));
+    if !signature_ok {
+        return Err(format!(
+            "`{}` must have the signature `fn {}(uv: vec2<f32>) -> f32`",
+            BRUSH_MASK_FUNCTION, BRUSH_MASK_FUNCTION
+        ));
+    }
+
+    validate_module(&module, mask)
+}
+
+/// Run naga's validator over a parsed module
+fn validate_module(module: &naga::Module, source: &str) -> Result<(), String> {
+    naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::empty())
+        .validate(module)
+        .map(|_| ())
+        .map_err(|e| format!("Brush shader failed validation:\n{}", e.emit_to_string(source)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_shader_is_template() {
+        let source = build_brush_shader(None).unwrap();
+        assert_eq!(source, BRUSH_SHADER_TEMPLATE);
+        let module = naga::front::wgsl::parse_str(&source).unwrap();
+        validate_module(&module, &source).unwrap();
+    }
+
+    #[test]
+    fn test_sample_shading_variant_validates() {
+        let source = with_sample_shading(&build_brush_shader(None).unwrap());
+        assert!(source.contains(UV_OUTPUT_PER_SAMPLE));
+        let module = naga::front::wgsl::parse_str(&source).unwrap();
+        naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::MULTISAMPLED_SHADING)
+            .validate(&module)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_custom_mask_is_spliced_in() {
+        let mask = "fn brush_mask(uv: vec2<f32>) -> f32 {\n    return step(0.5, fract(uv.x * 4.0));\n}";
+        let source = build_brush_shader(Some(mask)).unwrap();
+        assert!(source.contains("fract(uv.x * 4.0)"));
+        assert!(!source.contains("return 1.0;\n}\n// END BRUSH MASK"));
+        assert!(source.contains("fn fs_main"));
+    }
+
+    #[test]
+    fn test_custom_mask_may_use_helpers_and_constants() {
+        let mask = "
+            const RINGS: f32 = 5.0;
+            fn ring(d: f32) -> f32 { return 0.5 + 0.5 * cos(d * RINGS * 6.2831853); }
+            fn brush_mask(uv: vec2<f32>) -> f32 { return ring(length(uv)); }
+        ";
+        assert!(build_brush_shader(Some(mask)).is_ok());
+    }
+
+    #[test]
+    fn test_rejects_syntax_errors() {
+        let err = build_brush_shader(Some("fn brush_mask(uv: vec2<f32>) -> f32 { return uv.x }")).unwrap_err();
+        assert!(err.contains("failed to compile"), "{}", err);
+    }
+
+    #[test]
+    fn test_rejects_missing_or_wrong_signature() {
+        assert!(build_brush_shader(Some("fn other(uv: vec2<f32>) -> f32 { return 1.0; }")).is_err());
+        assert!(build_brush_shader(Some("fn brush_mask(uv: vec2<f32>) -> vec4<f32> { return vec4<f32>(1.0); }")).is_err());
+        assert!(build_brush_shader(Some("fn brush_mask(x: f32, y: f32) -> f32 { return x * y; }")).is_err());
+    }
+
+    #[test]
+    fn test_rejects_bindings_and_entry_points() {
+        let binding = "
+            @group(2) @binding(0) var<uniform> secret: vec4<f32>;
+            fn brush_mask(uv: vec2<f32>) -> f32 { return secret.x; }
+        ";
+        assert!(build_brush_shader(Some(binding)).unwrap_err().contains("global"));
+
+        let private = "
+            var<private> state: f32;
+            fn brush_mask(uv: vec2<f32>) -> f32 { return state; }
+        ";
+        assert!(build_brush_shader(Some(private)).is_err());
+
+        let entry = "
+            fn brush_mask(uv: vec2<f32>) -> f32 { return 1.0; }
+            @fragment fn fs_custom() -> @location(0) vec4<f32> { return vec4<f32>(1.0); }
+        ";
+        assert!(build_brush_shader(Some(entry)).unwrap_err().contains("entry points"));
+    }
+
+    #[test]
+    fn test_rejects_names_that_clash_with_the_template() {
+        let mask = "
+            fn polygon_distance(p: vec2<f32>, sides: f32) -> f32 { return 0.0; }
+            fn brush_mask(uv: vec2<f32>) -> f32 { return 1.0; }
+        ";
+        assert!(build_brush_shader(Some(mask)).is_err());
+    }
+
+    #[test]
+    fn test_rejects_oversized_and_marker_snippets() {
+        let huge = format!("fn brush_mask(uv: vec2<f32>) -> f32 {{ return 1.0; }}\n{}", " ".repeat(MAX_BRUSH_MASK_SOURCE_LEN));
+        assert!(build_brush_shader(Some(&huge)).is_err());
+        let marker = format!("{}\nfn brush_mask(uv: vec2<f32>) -> f32 {{ return 1.0; }}", MASK_END_MARKER);
+        assert!(build_brush_shader(Some(&marker)).is_err());
+    }
+}
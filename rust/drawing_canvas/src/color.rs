@@ -1,6 +1,7 @@
 //! Color Space Conversion Utilities
 //!
-//! This module provides utilities for converting between sRGB and linear RGB color spaces.
+//! This module provides utilities for converting between sRGB, linear RGB, and OKLab
+//! color spaces.
 //! 
 //! Color workflow:
 //! - Flutter colors (e.g., kPaperColor, kBrushDarkDefault) are in sRGB space
@@ -20,6 +21,19 @@ pub fn srgb_to_linear(srgb: f32) -> f32 {
     }
 }
 
+/// Convert a single linear color component to sRGB space
+///
+/// Inverse of `srgb_to_linear`. Values above 1.0 follow the same curve, so HDR values
+/// survive a round trip.
+#[inline]
+pub fn linear_to_srgb(linear: f32) -> f32 {
+    if linear <= 0.0031308 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
 /// Convert sRGB color (0.0-1.0) to linear RGB
 /// 
 /// # Arguments
@@ -37,6 +51,23 @@ pub fn srgb_to_linear_rgba(srgb: [f32; 4]) -> [f32; 4] {
     ]
 }
 
+/// Convert linear RGB color (0.0-1.0) to sRGB
+///
+/// # Arguments
+/// * `linear` - Color in linear space [r, g, b, a]
+///
+/// # Returns
+/// Color in sRGB space [r, g, b, a] where RGB are gamma-encoded and alpha is unchanged
+#[inline]
+pub fn linear_to_srgb_rgba(linear: [f32; 4]) -> [f32; 4] {
+    [
+        linear_to_srgb(linear[0]),
+        linear_to_srgb(linear[1]),
+        linear_to_srgb(linear[2]),
+        linear[3], // Alpha is not gamma-encoded
+    ]
+}
+
 /// Convert sRGB color (0.0-1.0) to linear RGB for use with f64
 /// 
 /// # Arguments
@@ -44,7 +75,6 @@ pub fn srgb_to_linear_rgba(srgb: [f32; 4]) -> [f32; 4] {
 /// 
 /// # Returns
 /// Color in linear space [r, g, b, a] where all components are linear
-#[allow(dead_code)]
 #[inline]
 pub fn srgb_to_linear_rgba_f64(srgb: &[f64; 4]) -> [f64; 4] {
     [
@@ -63,8 +93,6 @@ pub fn srgb_to_linear_rgba_f64(srgb: &[f64; 4]) -> [f64; 4] {
 /// 
 /// # Returns
 /// Color in linear space [r, g, b, a] where all values are 0.0-1.0
-/// #[allow(dead_code)]
-#[allow(dead_code)]
 #[inline]
 pub fn srgb_u8_to_linear_f32(r: u8, g: u8, b: u8, a: f32) -> [f32; 4] {
     srgb_to_linear_rgba([
@@ -75,6 +103,142 @@ pub fn srgb_u8_to_linear_f32(r: u8, g: u8, b: u8, a: f32) -> [f32; 4] {
     ])
 }
 
+/// Convert a premultiplied canvas color to straight (unpremultiplied) sRGB
+///
+/// # Arguments
+/// * `premultiplied` - Color as the canvas stores it [r*a, g*a, b*a, a]
+/// * `linear` - Whether the color is in linear space (otherwise it is already sRGB-encoded)
+///
+/// # Returns
+/// Straight sRGB color clamped to 0.0-1.0, or transparent black if alpha is zero
+pub fn premultiplied_to_straight_srgb(premultiplied: [f32; 4], linear: bool) -> [f32; 4] {
+    let alpha = premultiplied[3].clamp(0.0, 1.0);
+    if alpha <= 0.0 {
+        return [0.0; 4];
+    }
+    let straight = [premultiplied[0] / alpha, premultiplied[1] / alpha, premultiplied[2] / alpha, alpha];
+    let srgb = if linear { linear_to_srgb_rgba(straight) } else { straight };
+    [srgb[0].clamp(0.0, 1.0), srgb[1].clamp(0.0, 1.0), srgb[2].clamp(0.0, 1.0), alpha]
+}
+
+/// Convert RGB (0.0-1.0) to HSV
+///
+/// Works on whatever encoding it is given; the brush uses it on sRGB so hue and value
+/// steps look even.
+///
+/// # Returns
+/// [hue, saturation, value], hue in turns (0.0-1.0); gray has hue and saturation 0.0
+pub fn rgb_to_hsv(rgb: [f32; 3]) -> [f32; 3] {
+    let [r, g, b] = rgb;
+    let max = r.max(g).max(b);
+    let delta = max - r.min(g).min(b);
+    if delta <= 0.0 {
+        return [0.0, 0.0, max];
+    }
+    let sector = if max == r {
+        (g - b) / delta
+    } else if max == g {
+        (b - r) / delta + 2.0
+    } else {
+        (r - g) / delta + 4.0
+    };
+    [(sector / 6.0).rem_euclid(1.0), delta / max, max]
+}
+
+/// Convert HSV to RGB (0.0-1.0)
+///
+/// Inverse of `rgb_to_hsv`. Hue wraps, so any value in turns is accepted.
+pub fn hsv_to_rgb(hsv: [f32; 3]) -> [f32; 3] {
+    let [hue, saturation, value] = hsv;
+    let channel = |n: f32| {
+        let k = (n + hue.rem_euclid(1.0) * 6.0) % 6.0;
+        value - value * saturation * k.min(4.0 - k).clamp(0.0, 1.0)
+    };
+    [channel(5.0), channel(3.0), channel(1.0)]
+}
+
+/// Convert linear RGB to OKLab
+///
+/// OKLab is perceptually uniform: interpolating in it keeps lightness and saturation even,
+/// where linear RGB dips toward gray between saturated colors.
+/// Formula from: https://bottosson.github.io/posts/oklab/
+#[inline]
+pub fn linear_to_oklab(rgb: [f32; 3]) -> [f32; 3] {
+    let [r, g, b] = rgb;
+    let l = (0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b).cbrt();
+    let m = (0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b).cbrt();
+    let s = (0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b).cbrt();
+    [
+        0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s,
+        1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s,
+        0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s,
+    ]
+}
+
+/// Convert OKLab to linear RGB
+///
+/// Inverse of `linear_to_oklab`. Colors outside the sRGB gamut come back with components
+/// outside 0.0-1.0; clamp before display if needed.
+#[inline]
+pub fn oklab_to_linear(lab: [f32; 3]) -> [f32; 3] {
+    let [lightness, a, b] = lab;
+    let l = (lightness + 0.3963377774 * a + 0.2158037573 * b).powi(3);
+    let m = (lightness - 0.1055613458 * a - 0.0638541728 * b).powi(3);
+    let s = (lightness - 0.0894841775 * a - 1.2914855480 * b).powi(3);
+    [
+        4.0767416621 * l - 3.3077115913 * m + 0.2309699292 * s,
+        -1.2684380046 * l + 2.6097574011 * m - 0.3413193965 * s,
+        -0.0041960863 * l - 0.7034186147 * m + 1.7076147010 * s,
+    ]
+}
+
+/// 4x4 Bayer matrix, matching `bayer4` in the brush and blit shaders
+const BAYER_4X4: [[u8; 4]; 4] = [
+    [0, 8, 2, 10],
+    [12, 4, 14, 6],
+    [3, 11, 1, 9],
+    [15, 7, 13, 5],
+];
+
+/// Ordered dither threshold (0.0-1.0, exclusive) for a pixel
+///
+/// Repeats every 4 pixels on both axes. Thresholds are spread evenly, so a value that
+/// falls a fraction `f` of the way between two 8-bit levels rounds up on `f` of the pixels.
+#[inline]
+pub fn ordered_dither_threshold(x: u32, y: u32) -> f32 {
+    (BAYER_4X4[(y % 4) as usize][(x % 4) as usize] as f32 + 0.5) / 16.0
+}
+
+/// Quantize a 0.0-1.0 value to 8 bits, rounding up when its remainder exceeds `1 - threshold`
+///
+/// A threshold of 0.0 truncates; pass `ordered_dither_threshold` to dither.
+#[inline]
+pub fn quantize_unorm8(value: f32, threshold: f32) -> u8 {
+    (value * 255.0 + threshold).clamp(0.0, 255.0) as u8
+}
+
+/// Quantize a 0.0-1.0 value to 16 bits (rounded to the nearest level)
+#[inline]
+pub fn quantize_unorm16(value: f32) -> u16 {
+    (value * 65535.0).round().clamp(0.0, 65535.0) as u16
+}
+
+/// Value above which `tone_map_hdr` starts compressing
+const TONE_MAP_KNEE: f32 = 0.8;
+
+/// Bring an HDR value (above 1.0, e.g. from additive dabs) into 0.0-1.0 with a soft shoulder
+///
+/// Values up to the knee pass through unchanged; above it they roll off exponentially
+/// toward 1.0, matching in value and slope at the knee, so bright areas keep their
+/// gradations instead of clipping flat. Negative values clamp to 0.0.
+pub fn tone_map_hdr(value: f32) -> f32 {
+    if value <= TONE_MAP_KNEE {
+        return value.max(0.0);
+    }
+    let range = 1.0 - TONE_MAP_KNEE;
+    TONE_MAP_KNEE + range * (1.0 - (-(value - TONE_MAP_KNEE) / range).exp())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -117,4 +281,154 @@ mod tests {
         assert!(linear[1] >= 0.0 && linear[1] <= 1.0);
         assert!(linear[2] >= 0.0 && linear[2] <= 1.0);
     }
+
+    #[test]
+    fn test_hsv_round_trip() {
+        assert_eq!(rgb_to_hsv([1.0, 0.0, 0.0]), [0.0, 1.0, 1.0]);
+        assert_eq!(rgb_to_hsv([0.5, 0.5, 0.5]), [0.0, 0.0, 0.5]);
+        let [hue, saturation, value] = rgb_to_hsv([0.0, 0.5, 1.0]);
+        assert!((hue - 7.0 / 12.0).abs() < 1e-6 && saturation == 1.0 && value == 1.0);
+
+        for rgb in [[0.64, 0.01, 0.87], [0.2, 0.9, 0.3], [0.95, 0.95, 0.9], [0.0, 0.0, 0.0]] {
+            let back = hsv_to_rgb(rgb_to_hsv(rgb));
+            assert!(rgb.iter().zip(back).all(|(a, b)| (a - b).abs() < 1e-5), "{:?} -> {:?}", rgb, back);
+        }
+        // Hue wraps around
+        let red = hsv_to_rgb([1.0, 1.0, 1.0]);
+        assert!((red[0] - 1.0).abs() < 1e-6 && red[1].abs() < 1e-6 && red[2].abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_linear_to_srgb_round_trip() {
+        // Every 8-bit sRGB level survives a round trip through linear
+        for level in 0..=255u8 {
+            let srgb = level as f32 / 255.0;
+            let round_trip = linear_to_srgb(srgb_to_linear(srgb));
+            assert!((round_trip - srgb).abs() < 1e-5, "level {} came back as {}", level, round_trip * 255.0);
+        }
+
+        // And the other way, including the linear segment near black
+        for linear in [0.0, 0.001, 0.0031308, 0.01, 0.214, 0.5, 1.0] {
+            assert!((srgb_to_linear(linear_to_srgb(linear)) - linear).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_linear_to_srgb_rgba_keeps_alpha() {
+        let srgb = [0.25, 0.5, 0.75, 0.3];
+        let round_trip = linear_to_srgb_rgba(srgb_to_linear_rgba(srgb));
+        for (a, b) in round_trip.iter().zip(srgb.iter()) {
+            assert!((a - b).abs() < 1e-5);
+        }
+        assert_eq!(round_trip[3], 0.3);
+    }
+
+    #[test]
+    fn test_oklab_round_trip_and_white_point() {
+        let white = linear_to_oklab([1.0, 1.0, 1.0]);
+        assert!((white[0] - 1.0).abs() < 1e-3);
+        assert!(white[1].abs() < 1e-3 && white[2].abs() < 1e-3);
+
+        for rgb in [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.2, 0.5, 0.9], [0.04, 0.3, 0.01]] {
+            let round_trip = oklab_to_linear(linear_to_oklab(rgb));
+            for (a, b) in round_trip.iter().zip(rgb.iter()) {
+                assert!((a - b).abs() < 1e-4, "{:?} came back as {:?}", rgb, round_trip);
+            }
+        }
+    }
+
+    #[test]
+    fn test_dithering_breaks_up_banding_on_soft_gradient() {
+        // A soft ramp spanning only a few 8-bit levels, like the falloff of a large soft dab
+        let (width, height) = (256u32, 16u32);
+        let ramp = |x: u32| 0.40 + 0.02 * x as f32 / (width - 1) as f32;
+        let quantize = |dither: bool| -> Vec<u8> {
+            (0..height)
+                .flat_map(|y| (0..width).map(move |x| (x, y)))
+                .map(|(x, y)| quantize_unorm8(ramp(x), if dither { ordered_dither_threshold(x, y) } else { 0.0 }))
+                .collect()
+        };
+
+        // Seen from a normal viewing distance, each 4x4 block blends into its average;
+        // banding is how far those averages stray from the true ramp
+        let banding_error = |pixels: &[u8]| -> f32 {
+            let mut total = 0.0;
+            let mut blocks = 0;
+            for by in (0..height).step_by(4) {
+                for bx in (0..width).step_by(4) {
+                    let mut sum = 0.0;
+                    for y in by..by + 4 {
+                        for x in bx..bx + 4 {
+                            sum += pixels[(y * width + x) as usize] as f32 / 255.0;
+                        }
+                    }
+                    let expected = (bx..bx + 4).map(ramp).sum::<f32>() / 4.0;
+                    total += (sum / 16.0 - expected).abs();
+                    blocks += 1;
+                }
+            }
+            total / blocks as f32
+        };
+        // Longest run of identical pixels along a row: the width of a visible band
+        let widest_band = |pixels: &[u8]| -> usize {
+            let row = &pixels[..width as usize];
+            row.chunk_by(|a, b| a == b).map(<[u8]>::len).max().unwrap_or(0)
+        };
+
+        let banded = quantize(false);
+        let dithered = quantize(true);
+        assert!(
+            banding_error(&dithered) < banding_error(&banded) * 0.25,
+            "dithered error {} vs banded {}",
+            banding_error(&dithered),
+            banding_error(&banded)
+        );
+        assert!(
+            widest_band(&dithered) * 2 < widest_band(&banded),
+            "dithered band {}px vs banded {}px",
+            widest_band(&dithered),
+            widest_band(&banded)
+        );
+    }
+
+    #[test]
+    fn test_premultiplied_to_straight_srgb() {
+        // Half-transparent sRGB mid gray, stored premultiplied in linear space
+        let linear = srgb_to_linear(0.5);
+        let straight = premultiplied_to_straight_srgb([linear * 0.5, 0.0, linear * 0.5, 0.5], true);
+        assert!((straight[0] - 0.5).abs() < 1e-4 && straight[1] == 0.0 && (straight[2] - 0.5).abs() < 1e-4);
+        assert_eq!(straight[3], 0.5);
+
+        assert_eq!(premultiplied_to_straight_srgb([0.25, 0.1, 0.0, 0.5], false), [0.5, 0.2, 0.0, 0.5]);
+        assert_eq!(premultiplied_to_straight_srgb([0.0; 4], true), [0.0; 4]);
+    }
+
+    #[test]
+    fn test_tone_map_keeps_range_and_order() {
+        // Up to the knee nothing changes
+        for value in [0.0, 0.25, 0.5, TONE_MAP_KNEE] {
+            assert_eq!(tone_map_hdr(value), value);
+        }
+        // Above it, values stay distinct and below 1.0 instead of clipping together
+        let mapped: Vec<f32> = [0.9, 1.0, 1.5, 3.0].iter().map(|v| tone_map_hdr(*v)).collect();
+        assert!(mapped.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(mapped.iter().all(|v| *v < 1.0));
+        assert_eq!(quantize_unorm16(tone_map_hdr(1000.0)), 65535);
+        assert_eq!(tone_map_hdr(-0.5), 0.0);
+    }
+
+    #[test]
+    fn test_ordered_dither_thresholds_cover_levels_evenly() {
+        let mut thresholds: Vec<f32> = (0..4).flat_map(|y| (0..4).map(move |x| ordered_dither_threshold(x, y))).collect();
+        thresholds.sort_by(f32::total_cmp);
+        for (i, t) in thresholds.iter().enumerate() {
+            assert!((t - (i as f32 + 0.5) / 16.0).abs() < 1e-6);
+        }
+        // Exact levels are unaffected by the dither
+        for level in [0u8, 1, 128, 254, 255] {
+            for (x, y) in [(0, 0), (3, 1), (2, 2)] {
+                assert_eq!(quantize_unorm8(level as f32 / 255.0, ordered_dither_threshold(x, y)), level);
+            }
+        }
+    }
 }
@@ -0,0 +1,245 @@
+//! App Configuration Snapshots
+//!
+//! This module defines the serializable snapshot of every editor setting (brush,
+//! blending, view, and display options), so a session's configuration can be saved
+//! and restored as one JSON blob. Canvas pixels and brush textures are not included,
+//! but the canvas size (resolution and bleed) is.
+
+use serde::{Deserialize, Serialize};
+
+use crate::brush::{BrushParams, Symmetry};
+use crate::export::HdrExportMode;
+use crate::input::BarrelButtonAction;
+use crate::renderer::{AaMode, BlendColorSpace, WrapMode};
+use crate::view::ViewTransform;
+
+/// Version written into exported configs; imports from newer versions are rejected
+pub const CONFIG_VERSION: u32 = 1;
+
+/// Snapshot of all editor settings
+///
+/// Fields missing from an imported config keep their defaults, so configs exported by
+/// older versions still load.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppConfig {
+    /// Format version of the snapshot
+    pub version: u32,
+    /// Brush parameters
+    pub brush: BrushParams,
+    /// Color space dabs are blended in
+    pub blend_color_space: BlendColorSpace,
+    /// Whether the canvas is stored as 8-bit sRGB instead of 16-bit float
+    pub srgb_canvas: bool,
+    /// Fixed visible canvas size in pixels, or None to follow the surface size
+    pub canvas_resolution: Option<[u32; 2]>,
+    /// Extra canvas allocated past each visible edge, in pixels
+    pub canvas_bleed: u32,
+    /// Canvas → surface display transform
+    pub view_transform: ViewTransform,
+    /// Canvas clear color (RGBA, 0.0-1.0)
+    pub clear_color: [f64; 4],
+    /// Rotation snap increment in degrees (0.0 = no snapping)
+    pub rotation_snap_degrees: f32,
+    /// What the stylus barrel button does
+    pub barrel_button_action: BarrelButtonAction,
+    /// Mirror or radial copies drawn with every dab
+    pub symmetry: Symmetry,
+    /// Whether new strokes are dropped while rendering is paused
+    pub drop_input_while_paused: bool,
+    /// Rate pointer moves are resampled to, in events per second (0.0 = off)
+    pub input_resample_rate: f32,
+    /// How far ahead strokes are predicted on display, in milliseconds (0.0 = off)
+    pub input_prediction_ms: f32,
+    /// Most completed strokes retained for vector export/replay (0 = retention off)
+    pub max_retained_strokes: usize,
+    /// Most strokes that can be undone (0 = undo off)
+    pub undo_limit: usize,
+    /// sRGB color behind/around the canvas on the surface
+    pub surface_background: [f32; 3],
+    /// sRGB paper shown beneath the canvas content on screen (alpha 0.0 = none)
+    pub paper_color: [f32; 4],
+    /// Glow applied around HDR pixels when displayed (0.0 = off)
+    pub bloom_intensity: f32,
+    /// Whether 8-bit output is written with ordered dithering
+    pub dithering: bool,
+    /// How the canvas is sampled past its edges when displayed
+    pub wrap_mode: WrapMode,
+    /// How brush dab edges are antialiased
+    pub antialiasing: AaMode,
+    /// Whether readback/export composites the background gradient beneath
+    pub export_includes_background: bool,
+    /// Whether readback/export includes the bleed margin
+    pub export_includes_bleed: bool,
+    /// How 16-bit exports bring values above 1.0 into range
+    pub export_hdr_mode: HdrExportMode,
+    /// User WGSL brush tip mask, if one is set
+    pub custom_brush_shader: Option<String>,
+}
+
+impl AppConfig {
+    /// Serialize to pretty-printed JSON
+    pub fn to_json(&self) -> String {
+        // Plain data with string keys: serialization cannot fail
+        serde_json::to_string_pretty(self).expect("App config is always serializable")
+    }
+
+    /// Parse and validate a config from JSON
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let config: Self = serde_json::from_str(json).map_err(|e| format!("Invalid config JSON: {}", e))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Validate that every setting is in its acceptable range
+    pub fn validate(&self) -> Result<(), String> {
+        if self.version > CONFIG_VERSION {
+            return Err(format!(
+                "Config version {} is newer than the supported version {}",
+                self.version, CONFIG_VERSION
+            ));
+        }
+        self.brush.validate().map_err(|e| format!("Invalid brush config: {}", e))?;
+        if self.canvas_resolution.is_some_and(|[width, height]| width == 0 || height == 0) {
+            return Err("Canvas resolution must be positive".to_string());
+        }
+        if !self.view_transform.rotation.is_finite() {
+            return Err("View rotation must be finite".to_string());
+        }
+        if !(self.view_transform.pixel_aspect.is_finite() && self.view_transform.pixel_aspect > 0.0) {
+            return Err("View pixel aspect must be positive".to_string());
+        }
+        if !self.clear_color.iter().all(|c| (0.0..=1.0).contains(c)) {
+            return Err("Clear color components must be between 0.0 and 1.0".to_string());
+        }
+        if !self.surface_background.iter().all(|c| (0.0..=1.0).contains(c)) {
+            return Err("Surface background components must be between 0.0 and 1.0".to_string());
+        }
+        if !self.paper_color.iter().all(|c| (0.0..=1.0).contains(c)) {
+            return Err("Paper color components must be between 0.0 and 1.0".to_string());
+        }
+        self.symmetry.validate()?;
+        if !(self.rotation_snap_degrees.is_finite() && self.rotation_snap_degrees >= 0.0) {
+            return Err("Rotation snap must not be negative".to_string());
+        }
+        if !(self.input_resample_rate.is_finite() && self.input_resample_rate >= 0.0) {
+            return Err("Input resample rate must not be negative".to_string());
+        }
+        if !(0.0..=crate::input::InputPredictor::MAX_LOOKAHEAD_MS).contains(&self.input_prediction_ms) {
+            return Err(format!(
+                "Input prediction must be between 0 and {} ms",
+                crate::input::InputPredictor::MAX_LOOKAHEAD_MS
+            ));
+        }
+        if !(self.bloom_intensity.is_finite() && self.bloom_intensity >= 0.0) {
+            return Err("Bloom intensity must not be negative".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl Default for AppConfig {
+    /// Settings of a freshly created app and renderer
+    fn default() -> Self {
+        Self {
+            version: CONFIG_VERSION,
+            brush: BrushParams::default(),
+            blend_color_space: BlendColorSpace::default(),
+            srgb_canvas: false,
+            canvas_resolution: None,
+            canvas_bleed: 0,
+            view_transform: ViewTransform::IDENTITY,
+            clear_color: [0.0, 0.0, 0.0, 0.0],
+            rotation_snap_degrees: 0.0,
+            barrel_button_action: BarrelButtonAction::default(),
+            symmetry: Symmetry::NONE,
+            drop_input_while_paused: false,
+            input_resample_rate: 0.0,
+            input_prediction_ms: 0.0,
+            max_retained_strokes: 0,
+            undo_limit: crate::history::DEFAULT_UNDO_LIMIT,
+            surface_background: [0.0, 0.0, 0.0],
+            paper_color: [0.0, 0.0, 0.0, 0.0],
+            bloom_intensity: 0.0,
+            dithering: false,
+            wrap_mode: WrapMode::default(),
+            antialiasing: AaMode::default(),
+            export_includes_background: false,
+            export_includes_bleed: false,
+            export_hdr_mode: HdrExportMode::default(),
+            custom_brush_shader: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::brush::{CapStyle, FirstDabPressureMode, PressureMapping, SymmetryMode, TipShape};
+
+    #[test]
+    fn test_config_round_trips_through_json() {
+        let config = AppConfig {
+            brush: BrushParams {
+                tip_shape: TipShape::Polygon(5),
+                cap_style: CapStyle::Square,
+                first_dab_pressure_mode: FirstDabPressureMode::Threshold(0.2),
+                ..BrushParams::INK
+            },
+            blend_color_space: BlendColorSpace::Linear,
+            canvas_resolution: Some([1920, 1080]),
+            canvas_bleed: 64,
+            view_transform: ViewTransform { rotation: 0.5, scale: 2.0, translation: [30.0, -15.0], ..ViewTransform::IDENTITY },
+            clear_color: [1.0, 1.0, 1.0, 0.0],
+            paper_color: [1.0, 0.98, 0.94, 1.0],
+            barrel_button_action: BarrelButtonAction::Undo,
+            symmetry: Symmetry { mode: SymmetryMode::Radial(6), center: [256.0, 256.0] },
+            antialiasing: AaMode::Analytic,
+            export_hdr_mode: HdrExportMode::ToneMap,
+            custom_brush_shader: Some("fn brush_mask(uv: vec2<f32>) -> f32 { return uv.x; }".to_string()),
+            ..AppConfig::default()
+        };
+
+        let restored = AppConfig::from_json(&config.to_json()).unwrap();
+
+        assert_eq!(restored.brush.size, BrushParams::INK.size);
+        assert_eq!(restored.brush.pressure_mapping, PressureMapping::Size);
+        assert_eq!(restored.brush.tip_shape, TipShape::Polygon(5));
+        assert_eq!(restored.brush.cap_style, CapStyle::Square);
+        assert_eq!(restored.brush.first_dab_pressure_mode, FirstDabPressureMode::Threshold(0.2));
+        assert_eq!(restored.blend_color_space, BlendColorSpace::Linear);
+        assert_eq!(restored.canvas_resolution, Some([1920, 1080]));
+        assert_eq!(restored.canvas_bleed, 64);
+        assert_eq!(restored.view_transform, config.view_transform);
+        assert_eq!(restored.clear_color, config.clear_color);
+        assert_eq!(restored.paper_color, config.paper_color);
+        assert_eq!(restored.barrel_button_action, BarrelButtonAction::Undo);
+        assert_eq!(restored.symmetry, config.symmetry);
+        assert_eq!(restored.antialiasing, AaMode::Analytic);
+        assert_eq!(restored.export_hdr_mode, HdrExportMode::ToneMap);
+        assert_eq!(restored.custom_brush_shader, config.custom_brush_shader);
+    }
+
+    #[test]
+    fn test_missing_fields_keep_defaults() {
+        let config = AppConfig::from_json(r#"{ "brush": { "size": 12.0 }, "dithering": true }"#).unwrap();
+
+        assert_eq!(config.brush.size, 12.0);
+        assert_eq!(config.brush.spacing, BrushParams::DEFAULT.spacing);
+        assert!(config.dithering);
+        assert_eq!(config.rotation_snap_degrees, AppConfig::default().rotation_snap_degrees);
+    }
+
+    #[test]
+    fn test_invalid_configs_are_rejected() {
+        assert!(AppConfig::from_json("not json").is_err());
+        assert!(AppConfig::from_json(r#"{ "brush": { "flow": 2.0 } }"#).is_err());
+        assert!(AppConfig::from_json(r#"{ "clear_color": [0.0, 0.0, 0.0, 1.5] }"#).is_err());
+        assert!(AppConfig::from_json(r#"{ "view_transform": { "rotation": 0.0, "pixel_aspect": 0.0 } }"#).is_err());
+        assert!(AppConfig::from_json(r#"{ "blend_color_space": "Cmyk" }"#).is_err());
+        assert!(AppConfig::from_json(r#"{ "canvas_resolution": [0, 600] }"#).is_err());
+
+        let newer = format!(r#"{{ "version": {} }}"#, CONFIG_VERSION + 1);
+        assert!(AppConfig::from_json(&newer).is_err());
+    }
+}
@@ -0,0 +1,107 @@
+//! Device Pixel Ratio Helpers
+//!
+//! The canvas texture is always sized in physical pixels. With fractional scale
+//! factors (e.g. 125%/150% display scaling) the CSS size and physical size are
+//! not integer multiples of each other, so conversions must use the exact scale
+//! and round sizes the same way the browser does, instead of truncating.
+
+/// Convert a logical (CSS) size to a physical pixel size for the given scale factor
+///
+/// Rounds to the nearest pixel, matching how browsers size the device-pixel
+/// content box. Truncating would leave the canvas a pixel short and resample it.
+pub fn logical_to_physical_size(logical: [f64; 2], scale_factor: f64) -> [u32; 2] {
+    [
+        (logical[0] * scale_factor).round().max(0.0) as u32,
+        (logical[1] * scale_factor).round().max(0.0) as u32,
+    ]
+}
+
+/// Convert a logical (CSS) position to physical pixels using a per-axis scale
+///
+/// The per-axis scale should be `physical size / logical size` of the element,
+/// which stays exact after the physical size has been rounded.
+pub fn logical_to_physical_position(logical: [f64; 2], scale: [f64; 2]) -> [f32; 2] {
+    [(logical[0] * scale[0]) as f32, (logical[1] * scale[1]) as f32]
+}
+
+/// Compute the exact per-axis scale between an element's physical and logical size
+///
+/// Falls back to `fallback_scale` on an axis whose logical size is zero.
+pub fn physical_scale(physical: [u32; 2], logical: [f64; 2], fallback_scale: f64) -> [f64; 2] {
+    let axis = |physical: u32, logical: f64| {
+        if logical > 0.0 {
+            physical as f64 / logical
+        } else {
+            fallback_scale
+        }
+    };
+    [axis(physical[0], logical[0]), axis(physical[1], logical[1])]
+}
+
+/// Pixel aspects within this relative distance of 1.0 are treated as square
+/// (rounding the backing store at fractional scales skews the ratio slightly)
+pub const SQUARE_PIXEL_TOLERANCE: f64 = 0.005;
+
+/// Displayed height / width of one physical pixel, from the per-axis `physical_scale`
+///
+/// 1.0 means square pixels. Anything else means the page stretches the canvas
+/// non-uniformly (e.g. a CSS `scale(x, y)` on a container), which turns round
+/// dabs into ellipses on screen.
+pub fn pixel_aspect(scale: [f64; 2]) -> f64 {
+    if scale[1] > 0.0 {
+        scale[0] / scale[1]
+    } else {
+        1.0
+    }
+}
+
+/// Check whether a pixel aspect is square within `SQUARE_PIXEL_TOLERANCE`
+pub fn is_square_pixel_aspect(aspect: f64) -> bool {
+    (aspect - 1.0).abs() <= SQUARE_PIXEL_TOLERANCE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_logical_to_physical_size_rounds_at_fractional_scale() {
+        // 801 CSS px at 150% is 1201.5 physical px; truncation would give 1201
+        assert_eq!(logical_to_physical_size([801.0, 600.0], 1.5), [1202, 900]);
+        assert_eq!(logical_to_physical_size([333.0, 333.0], 1.25), [416, 416]);
+    }
+
+    #[test]
+    fn test_logical_position_maps_to_expected_physical_pixel_at_1_5x() {
+        let scale = physical_scale([1200, 900], [800.0, 600.0], 1.5);
+        assert_eq!(scale, [1.5, 1.5]);
+        // Centre of CSS pixel (100, 50) is the centre of physical pixel (150, 75)
+        assert_eq!(logical_to_physical_position([100.5, 50.5], scale), [150.75, 75.75]);
+        assert_eq!(logical_to_physical_position([100.0, 50.0], scale), [150.0, 75.0]);
+    }
+
+    #[test]
+    fn test_physical_scale_uses_exact_ratio_after_rounding() {
+        // 801 CSS px rounds to 1202 physical px, so the exact scale is not 1.5
+        let scale = physical_scale([1202, 900], [801.0, 600.0], 1.5);
+        let right_edge = logical_to_physical_position([801.0, 600.0], scale);
+        assert!((right_edge[0] - 1202.0).abs() < 1e-3);
+        assert!((right_edge[1] - 900.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_physical_scale_falls_back_on_zero_size() {
+        assert_eq!(physical_scale([0, 0], [0.0, 0.0], 1.25), [1.25, 1.25]);
+    }
+
+    #[test]
+    fn test_pixel_aspect() {
+        // Fractional-scale rounding stays square
+        let rounded = physical_scale([1202, 900], [801.0, 600.0], 1.5);
+        assert!(is_square_pixel_aspect(pixel_aspect(rounded)));
+        // A container scaled 2x horizontally shows pixels half as tall as wide
+        let stretched = physical_scale([800, 600], [1600.0, 600.0], 1.0);
+        assert_eq!(pixel_aspect(stretched), 0.5);
+        assert!(!is_square_pixel_aspect(pixel_aspect(stretched)));
+    }
+}
@@ -0,0 +1,91 @@
+//! Error Reporting
+//!
+//! Recoverable failures (a lost surface, a failed readback, an unsupported format) go
+//! through `report_error`, which logs them and, when enabled, forwards them to JavaScript
+//! as `onError(code, message)`. Release builds suppress most logging, so the callback is
+//! how a host app learns about these failures in production.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether errors are forwarded to the JS `onError` callback (persists across reinit)
+static ERROR_CALLBACK_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Category of a reported error, passed to JavaScript as a stable string code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    /// The surface was lost or outdated; it is reconfigured and the frame is skipped
+    SurfaceLost,
+    /// The next surface frame could not be acquired for another reason
+    SurfaceUnavailable,
+    /// The GPU device was lost (e.g. a driver reset); the renderer is rebuilt
+    DeviceLost,
+    /// Reading the canvas (or a comparison) back from the GPU failed
+    ReadbackFailed,
+    /// A texture format is not supported by the requested operation
+    UnsupportedFormat,
+    /// The canvas element could not be attached to its container
+    CanvasAttachFailed,
+}
+
+impl ErrorCode {
+    /// Stable identifier passed to the JS callback
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::SurfaceLost => "surface_lost",
+            ErrorCode::SurfaceUnavailable => "surface_unavailable",
+            ErrorCode::DeviceLost => "device_lost",
+            ErrorCode::ReadbackFailed => "readback_failed",
+            ErrorCode::UnsupportedFormat => "unsupported_format",
+            ErrorCode::CanvasAttachFailed => "canvas_attach_failed",
+        }
+    }
+}
+
+/// Set whether reported errors are forwarded to the JS `onError` callback (default: off)
+// Only the web build has a JS host to enable it for
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+pub fn set_error_callback_enabled(enabled: bool) {
+    ERROR_CALLBACK_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Check whether reported errors are forwarded to the JS `onError` callback
+pub fn error_callback_enabled() -> bool {
+    ERROR_CALLBACK_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Report a recoverable error: log it, and pass it to JS `onError(code, message)` if enabled
+pub fn report_error(code: ErrorCode, message: &str) {
+    log::error!("[{}] {}", code.as_str(), message);
+    if error_callback_enabled() {
+        call_error_callback(code, message);
+    }
+}
+
+/// Call `window.onError(code, message)` if the host has defined it
+#[cfg(target_arch = "wasm32")]
+fn call_error_callback(code: ErrorCode, message: &str) {
+    use wasm_bindgen::{JsCast, JsValue};
+
+    let Some(window) = web_sys::window() else {
+        return;
+    };
+
+    // Looked up on every call, so the host can define or replace it at any time
+    let callback = js_sys::Reflect::get(&window, &JsValue::from_str("onError"))
+        .ok()
+        .and_then(|value| value.dyn_into::<js_sys::Function>().ok());
+
+    match callback {
+        Some(callback) => {
+            // A throwing callback must not take the renderer down with it
+            if let Err(e) = callback.call2(&JsValue::NULL, &JsValue::from_str(code.as_str()), &JsValue::from_str(message)) {
+                log::warn!("onError callback threw: {:?}", e);
+            }
+        }
+        None => log::warn!("Error callback enabled but window.onError is not a function"),
+    }
+}
+
+// No JS host on native builds; the log line is the report
+#[cfg(not(target_arch = "wasm32"))]
+fn call_error_callback(_code: ErrorCode, _message: &str) {}
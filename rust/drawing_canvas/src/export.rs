@@ -0,0 +1,242 @@
+//! Image Export
+//!
+//! Encodes canvas pixel data into image files. The canvas stores whatever color
+//! space it was blended in, so the encoder tags the output accordingly:
+//! - sRGB blending: pixels are sRGB encoded, written with an `sRGB` chunk
+//!   (plus the matching `gAMA`/`cHRM` fallback for older readers)
+//! - Linear blending: pixels are linear light, written with `gAMA` = 1.0 and
+//!   sRGB primaries in `cHRM` so profile-aware apps don't treat them as sRGB
+//!
+//! PNGs can be 8 or 16 bits per channel. The 16-bit path keeps the float canvas's
+//! tonal range for editing elsewhere, with HDR values brought into range per
+//! `HdrExportMode`.
+
+use crate::renderer::BlendColorSpace;
+
+/// How canvas values above 1.0 (HDR, e.g. from additive dabs) are brought into range
+/// for 16-bit export
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum HdrExportMode {
+    /// Values above 1.0 are cut off at 1.0 (bright areas flatten out)
+    Clamp,
+    /// Bright values roll off smoothly toward 1.0, keeping their gradations
+    /// (see `color::tone_map_hdr`); values below the knee are unchanged
+    ToneMap,
+}
+
+impl Default for HdrExportMode {
+    fn default() -> Self {
+        Self::Clamp
+    }
+}
+
+impl HdrExportMode {
+    /// Bring a straight (unpremultiplied) color value into 0.0-1.0
+    pub fn apply(self, value: f32) -> f32 {
+        match self {
+            HdrExportMode::Clamp => value.clamp(0.0, 1.0),
+            HdrExportMode::ToneMap => crate::color::tone_map_hdr(value),
+        }
+    }
+}
+
+/// Encode premultiplied RGBA8 pixel data as a PNG tagged for the given color space
+///
+/// Unpremultiplied like `encode_png16`, since PNG is straight alpha.
+///
+/// # Arguments
+/// * `rgba8_data` - Tightly packed premultiplied RGBA8 pixels (width * height * 4 bytes),
+///   as returned by `Renderer::read_canvas_rgba8`
+/// * `width`, `height` - Image dimensions in pixels
+/// * `color_space` - Color space the pixel data is encoded in
+pub fn encode_png(
+    rgba8_data: &[u8],
+    width: u32,
+    height: u32,
+    color_space: BlendColorSpace,
+) -> Result<Vec<u8>, String> {
+    let expected_len = width as usize * height as usize * 4;
+    if rgba8_data.len() != expected_len {
+        return Err(format!(
+            "PNG data size mismatch: expected {} bytes for {}x{}, got {}",
+            expected_len, width, height, rgba8_data.len()
+        ));
+    }
+
+    let mut bytes = Vec::with_capacity(expected_len);
+    for pixel in rgba8_data.chunks_exact(4) {
+        let alpha = pixel[3] as u32;
+        for &value in &pixel[..3] {
+            // Rounded; fully transparent pixels have no color
+            bytes.push((value as u32 * 255 + alpha / 2).checked_div(alpha).unwrap_or(0).min(255) as u8);
+        }
+        bytes.push(pixel[3]);
+    }
+    write_png(&bytes, width, height, png::BitDepth::Eight, color_space)
+}
+
+/// Encode premultiplied RGBA16 pixel data as a 16-bit PNG tagged for the given color space
+///
+/// The canvas stores premultiplied alpha but PNG is straight alpha, so the color is
+/// divided by alpha here (fully transparent pixels become transparent black). Dividing
+/// 16-bit values keeps faint, nearly transparent color far more precisely than 8 bits would.
+///
+/// # Arguments
+/// * `rgba16_data` - Tightly packed premultiplied RGBA16 pixels (width * height * 4 values),
+///   as returned by `Renderer::read_canvas_rgba16`
+/// * `width`, `height` - Image dimensions in pixels
+/// * `color_space` - Color space the pixel data is encoded in
+pub fn encode_png16(
+    rgba16_data: &[u16],
+    width: u32,
+    height: u32,
+    color_space: BlendColorSpace,
+) -> Result<Vec<u8>, String> {
+    let expected_len = width as usize * height as usize * 4;
+    if rgba16_data.len() != expected_len {
+        return Err(format!(
+            "PNG data size mismatch: expected {} values for {}x{}, got {}",
+            expected_len, width, height, rgba16_data.len()
+        ));
+    }
+
+    // Unpremultiply, then write samples big-endian as PNG requires
+    let mut bytes = Vec::with_capacity(expected_len * 2);
+    for pixel in rgba16_data.chunks_exact(4) {
+        let alpha = pixel[3] as u32;
+        for &value in &pixel[..3] {
+            // Rounded; fully transparent pixels have no color
+            let straight = (value as u32 * 65535 + alpha / 2).checked_div(alpha).unwrap_or(0).min(65535) as u16;
+            bytes.extend_from_slice(&straight.to_be_bytes());
+        }
+        bytes.extend_from_slice(&pixel[3].to_be_bytes());
+    }
+    write_png(&bytes, width, height, png::BitDepth::Sixteen, color_space)
+}
+
+/// Write RGBA image bytes (already in PNG sample order) with the color space tags
+fn write_png(
+    image_data: &[u8],
+    width: u32,
+    height: u32,
+    depth: png::BitDepth,
+    color_space: BlendColorSpace,
+) -> Result<Vec<u8>, String> {
+    let mut png_data = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut png_data, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(depth);
+
+        match color_space {
+            BlendColorSpace::Srgb => {
+                // Also writes gAMA/cHRM values matching sRGB
+                encoder.set_srgb(png::SrgbRenderingIntent::Perceptual);
+            }
+            BlendColorSpace::Linear => {
+                encoder.set_source_gamma(png::ScaledFloat::new(1.0));
+                encoder.set_source_chromaticities(png::SourceChromaticities::new(
+                    (0.3127, 0.3290), // D65 white point
+                    (0.64, 0.33),
+                    (0.30, 0.60),
+                    (0.15, 0.06),
+                ));
+            }
+        }
+
+        let mut writer = encoder
+            .write_header()
+            .map_err(|e| format!("Failed to write PNG header: {}", e))?;
+        writer
+            .write_image_data(image_data)
+            .map_err(|e| format!("Failed to write PNG data: {}", e))?;
+    }
+
+    Ok(png_data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Check whether the encoded PNG contains a chunk with the given type
+    fn has_chunk(png_data: &[u8], chunk_type: &[u8; 4]) -> bool {
+        // Skip the 8-byte signature, then walk length/type/data/crc chunks
+        let mut offset = 8;
+        while offset + 8 <= png_data.len() {
+            let length = u32::from_be_bytes(png_data[offset..offset + 4].try_into().unwrap()) as usize;
+            if &png_data[offset + 4..offset + 8] == chunk_type {
+                return true;
+            }
+            offset += 12 + length;
+        }
+        false
+    }
+
+    #[test]
+    fn test_srgb_export_has_srgb_chunk() {
+        let pixels = vec![128u8; 2 * 2 * 4];
+        let png_data = encode_png(&pixels, 2, 2, BlendColorSpace::Srgb).unwrap();
+        assert!(has_chunk(&png_data, b"sRGB"));
+    }
+
+    #[test]
+    fn test_linear_export_has_linear_gamma() {
+        let pixels = vec![128u8; 2 * 2 * 4];
+        let png_data = encode_png(&pixels, 2, 2, BlendColorSpace::Linear).unwrap();
+        assert!(!has_chunk(&png_data, b"sRGB"));
+        assert!(has_chunk(&png_data, b"gAMA"));
+        assert!(has_chunk(&png_data, b"cHRM"));
+    }
+
+    #[test]
+    fn test_size_mismatch_is_rejected() {
+        assert!(encode_png(&[0u8; 4], 2, 2, BlendColorSpace::Srgb).is_err());
+        assert!(encode_png16(&[0u16; 4], 2, 2, BlendColorSpace::Srgb).is_err());
+    }
+
+    #[test]
+    fn test_png_round_trips_straight_alpha() {
+        // Opaque, half-transparent (premultiplied), and empty pixels
+        let pixels = [200u8, 1, 255, 255, 64, 0, 128, 128, 0, 0, 0, 0];
+        let png_data = encode_png(&pixels, 3, 1, BlendColorSpace::Srgb).unwrap();
+
+        let decoder = png::Decoder::new(png_data.as_slice());
+        let mut reader = decoder.read_info().unwrap();
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer).unwrap();
+        assert_eq!(info.bit_depth, png::BitDepth::Eight);
+        assert_eq!(&buffer[..info.buffer_size()], &[200, 1, 255, 255, 128, 0, 255, 128, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_png16_round_trips_straight_alpha() {
+        // Opaque, half-transparent (premultiplied), and empty pixels
+        let pixels = [40_000u16, 1, 65535, 65535, 10_000, 20_000, 0, 32_768, 0, 0, 0, 0];
+        let png_data = encode_png16(&pixels, 3, 1, BlendColorSpace::Linear).unwrap();
+
+        let decoder = png::Decoder::new(png_data.as_slice());
+        let mut reader = decoder.read_info().unwrap();
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer).unwrap();
+        assert_eq!(info.bit_depth, png::BitDepth::Sixteen);
+        let decoded: Vec<u16> = buffer[..info.buffer_size()]
+            .chunks_exact(2)
+            .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+            .collect();
+
+        // Full-depth values survive; the translucent pixel comes back unpremultiplied
+        assert_eq!(&decoded[..4], &pixels[..4]);
+        assert_eq!(&decoded[4..8], &[20_000, 39_999, 0, 32_768]);
+        assert_eq!(&decoded[8..], &[0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_hdr_modes_bring_values_into_range() {
+        assert_eq!(HdrExportMode::Clamp.apply(2.5), 1.0);
+        assert_eq!(HdrExportMode::Clamp.apply(0.5), 0.5);
+        let mapped = HdrExportMode::ToneMap.apply(2.5);
+        assert!(mapped > HdrExportMode::ToneMap.apply(1.5) && mapped < 1.0);
+        assert_eq!(HdrExportMode::ToneMap.apply(0.5), 0.5);
+    }
+}
@@ -0,0 +1,454 @@
+//! C API for Native Embedding (Flutter FFI)
+//!
+//! A `#[no_mangle] extern "C"` surface for hosts that embed the canvas in a native window
+//! of their own, such as a Flutter plugin on desktop or mobile through `dart:ffi`. The host
+//! creates a canvas for its window (given as raw platform handles), feeds it pointer
+//! events, and asks it to render once per frame.
+//!
+//! Errors: every function returns a `DRAWING_CANVAS_*` status code (0 = success) and never
+//! unwinds into the host. A panic inside a call is caught and returned as
+//! `DRAWING_CANVAS_ERR_PANIC`; the canvas may be left half-updated, so the host should
+//! destroy it. (Catching needs unwinding panics: don't build with `panic = "abort"`.)
+//!
+//! Threading: a canvas is not thread-safe. Create, use, and destroy it on one thread, the
+//! one that owns its window (the platform thread on macOS and iOS, where surfaces must be
+//! created on the main thread), and never call into the same canvas concurrently.
+//! Separate canvases are independent and may live on different threads.
+
+use std::ffi::c_void;
+use std::num::NonZeroIsize;
+use std::panic::AssertUnwindSafe;
+use std::ptr::NonNull;
+
+use wgpu::rwh::{
+    AndroidDisplayHandle, AndroidNdkWindowHandle, AppKitDisplayHandle, AppKitWindowHandle, RawDisplayHandle,
+    RawWindowHandle, UiKitDisplayHandle, UiKitWindowHandle, WaylandDisplayHandle, WaylandWindowHandle,
+    Win32WindowHandle, WindowsDisplayHandle, XlibDisplayHandle, XlibWindowHandle,
+};
+
+use crate::app::App;
+use crate::brush::BrushParams;
+use crate::input::{PointerEvent, PointerEventSource, PointerEventType, TimestampNormalizer};
+use crate::renderer::Renderer;
+
+/// The call succeeded
+pub const DRAWING_CANVAS_OK: i32 = 0;
+/// A required pointer argument was null
+pub const DRAWING_CANVAS_ERR_NULL_POINTER: i32 = -1;
+/// An argument was out of range (unknown event type, invalid brush value, zero size, ...)
+pub const DRAWING_CANVAS_ERR_INVALID_ARGUMENT: i32 = -2;
+/// The window handle's platform is unknown
+pub const DRAWING_CANVAS_ERR_UNSUPPORTED_PLATFORM: i32 = -3;
+/// The GPU surface, adapter, or device could not be created
+pub const DRAWING_CANVAS_ERR_GPU: i32 = -4;
+/// The call panicked; the canvas should be destroyed
+pub const DRAWING_CANVAS_ERR_PANIC: i32 = -5;
+
+/// Win32: `window` is the HWND, `display` the HINSTANCE (may be null)
+pub const DRAWING_CANVAS_PLATFORM_WIN32: u32 = 0;
+/// macOS: `window` is the NSView*, `display` is unused
+pub const DRAWING_CANVAS_PLATFORM_APPKIT: u32 = 1;
+/// iOS: `window` is the UIView*, `display` is unused
+pub const DRAWING_CANVAS_PLATFORM_UIKIT: u32 = 2;
+/// Android: `window` is the ANativeWindow*, `display` is unused
+pub const DRAWING_CANVAS_PLATFORM_ANDROID: u32 = 3;
+/// Wayland: `window` is the wl_surface*, `display` the wl_display*
+pub const DRAWING_CANVAS_PLATFORM_WAYLAND: u32 = 4;
+/// X11 (Xlib): `window` is the Window id cast to a pointer, `display` the Display*
+pub const DRAWING_CANVAS_PLATFORM_XLIB: u32 = 5;
+
+/// A native window to render into, as raw platform handles
+#[repr(C)]
+pub struct DrawingCanvasWindow {
+    /// Windowing platform, one of the `DRAWING_CANVAS_PLATFORM_*` values
+    pub platform: u32,
+    /// The window or view (see the platform constants for what it points to)
+    pub window: *mut c_void,
+    /// The display connection, on platforms that have one (see the platform constants)
+    pub display: *mut c_void,
+}
+
+impl DrawingCanvasWindow {
+    /// Convert to the raw handles wgpu creates surfaces from
+    fn raw_handles(&self) -> Result<(RawWindowHandle, RawDisplayHandle), i32> {
+        let window = NonNull::new(self.window).ok_or(DRAWING_CANVAS_ERR_NULL_POINTER);
+        let display = NonNull::new(self.display).ok_or(DRAWING_CANVAS_ERR_NULL_POINTER);
+        Ok(match self.platform {
+            DRAWING_CANVAS_PLATFORM_WIN32 => {
+                let hwnd = NonZeroIsize::new(self.window as isize).ok_or(DRAWING_CANVAS_ERR_NULL_POINTER)?;
+                let mut handle = Win32WindowHandle::new(hwnd);
+                handle.hinstance = NonZeroIsize::new(self.display as isize);
+                (handle.into(), WindowsDisplayHandle::new().into())
+            }
+            DRAWING_CANVAS_PLATFORM_APPKIT => (AppKitWindowHandle::new(window?).into(), AppKitDisplayHandle::new().into()),
+            DRAWING_CANVAS_PLATFORM_UIKIT => (UiKitWindowHandle::new(window?).into(), UiKitDisplayHandle::new().into()),
+            DRAWING_CANVAS_PLATFORM_ANDROID => {
+                (AndroidNdkWindowHandle::new(window?).into(), AndroidDisplayHandle::new().into())
+            }
+            DRAWING_CANVAS_PLATFORM_WAYLAND => {
+                (WaylandWindowHandle::new(window?).into(), WaylandDisplayHandle::new(display?).into())
+            }
+            DRAWING_CANVAS_PLATFORM_XLIB => {
+                if self.window.is_null() {
+                    return Err(DRAWING_CANVAS_ERR_NULL_POINTER);
+                }
+                let handle = XlibWindowHandle::new(self.window as std::ffi::c_ulong);
+                (handle.into(), XlibDisplayHandle::new(Some(display?), 0).into())
+            }
+            _ => return Err(DRAWING_CANVAS_ERR_UNSUPPORTED_PLATFORM),
+        })
+    }
+}
+
+/// A canvas owned by the host: the app state and the renderer drawing into its window
+pub struct DrawingCanvas {
+    app: App,
+    renderer: Renderer,
+    timestamps: TimestampNormalizer,
+}
+
+/// Run `f`, returning its status, or `DRAWING_CANVAS_ERR_PANIC` if it panics
+fn catch_panics(f: impl FnOnce() -> i32) -> i32 {
+    std::panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|_| {
+        log::error!("Panic caught at the FFI boundary");
+        DRAWING_CANVAS_ERR_PANIC
+    })
+}
+
+/// Run `f` on the canvas behind a host pointer, turning null and panics into status codes
+///
+/// # Safety
+/// `canvas` must be null or a live pointer from `drawing_canvas_create`.
+unsafe fn with_canvas(canvas: *mut DrawingCanvas, f: impl FnOnce(&mut DrawingCanvas) -> i32) -> i32 {
+    // SAFETY: non-null pointers are live canvases, per the caller's contract
+    match unsafe { canvas.as_mut() } {
+        Some(canvas) => catch_panics(|| f(canvas)),
+        None => DRAWING_CANVAS_ERR_NULL_POINTER,
+    }
+}
+
+/// Create a canvas rendering into a native window
+///
+/// Blocks while the GPU adapter and device are set up. On success the new canvas is
+/// written to `out_canvas`; it must be released with `drawing_canvas_destroy`. On failure
+/// `out_canvas` is set to null.
+///
+/// # Arguments
+/// * `window` - Raw handles of the window to render into
+/// * `width`, `height` - Surface size in physical pixels (also the canvas size)
+/// * `out_canvas` - Receives the canvas
+///
+/// # Safety
+/// `window` must point to a valid `DrawingCanvasWindow` whose handles stay valid until the
+/// canvas is destroyed, and `out_canvas` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn drawing_canvas_create(
+    window: *const DrawingCanvasWindow,
+    width: u32,
+    height: u32,
+    out_canvas: *mut *mut DrawingCanvas,
+) -> i32 {
+    if out_canvas.is_null() {
+        return DRAWING_CANVAS_ERR_NULL_POINTER;
+    }
+    // SAFETY: checked non-null above; writable per the caller's contract
+    unsafe { *out_canvas = std::ptr::null_mut() };
+    // SAFETY: null or valid per the caller's contract
+    let Some(window) = (unsafe { window.as_ref() }) else {
+        return DRAWING_CANVAS_ERR_NULL_POINTER;
+    };
+    if width == 0 || height == 0 {
+        return DRAWING_CANVAS_ERR_INVALID_ARGUMENT;
+    }
+
+    catch_panics(|| {
+        let (window_handle, display_handle) = match window.raw_handles() {
+            Ok(handles) => handles,
+            Err(code) => return code,
+        };
+        let size = winit::dpi::PhysicalSize::new(width, height);
+        // SAFETY: the handles outlive the canvas per the caller's contract
+        let renderer = match pollster::block_on(unsafe { Renderer::from_raw_handles(window_handle, display_handle, size) }) {
+            Ok(renderer) => renderer,
+            Err(e) => {
+                log::error!("Failed to create canvas renderer: {}", e);
+                return DRAWING_CANVAS_ERR_GPU;
+            }
+        };
+
+        let mut canvas = Box::new(DrawingCanvas { app: App::new(), renderer, timestamps: TimestampNormalizer::new() });
+        let DrawingCanvas { app, renderer, .. } = &mut *canvas;
+        app.clear_canvas(renderer);
+        // SAFETY: checked non-null above; writable per the caller's contract
+        unsafe { *out_canvas = Box::into_raw(canvas) };
+        log::info!("✅ FFI canvas created ({}x{})", width, height);
+        DRAWING_CANVAS_OK
+    })
+}
+
+/// Destroy a canvas and release its GPU resources (null is ignored)
+///
+/// # Safety
+/// `canvas` must be null or a live pointer from `drawing_canvas_create`; it must not be
+/// used again afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn drawing_canvas_destroy(canvas: *mut DrawingCanvas) -> i32 {
+    if canvas.is_null() {
+        return DRAWING_CANVAS_OK;
+    }
+    // SAFETY: a live pointer from `drawing_canvas_create`, released exactly once
+    let canvas = unsafe { Box::from_raw(canvas) };
+    catch_panics(move || {
+        drop(canvas);
+        DRAWING_CANVAS_OK
+    })
+}
+
+/// Queue a pointer event for the next render
+///
+/// # Arguments
+/// * `event_type` - 0 = Down, 1 = Move, 2 = Up
+/// * `source` - 0 = Mouse, 1 = Touch, 2 = Stylus, 3 = Unknown
+/// * `x`, `y` - Position in surface physical pixels (mapped through the view transform)
+/// * `pressure` - 0.0-1.0 (pass 1.0 for devices without pressure)
+/// * `timestamp_ms` - Event time in milliseconds on any clock; rebased onto the canvas's own
+///   monotonic clock, so a clock that jumps back doesn't stall strokes
+///
+/// # Safety
+/// `canvas` must be null or a live pointer from `drawing_canvas_create`.
+#[no_mangle]
+pub unsafe extern "C" fn drawing_canvas_queue_pointer(
+    canvas: *mut DrawingCanvas,
+    event_type: u32,
+    source: u32,
+    x: f32,
+    y: f32,
+    pressure: f32,
+    timestamp_ms: f64,
+) -> i32 {
+    let event_type = match event_type {
+        0 => PointerEventType::Down,
+        1 => PointerEventType::Move,
+        2 => PointerEventType::Up,
+        _ => return DRAWING_CANVAS_ERR_INVALID_ARGUMENT,
+    };
+    let source = match source {
+        0 => PointerEventSource::Mouse,
+        1 => PointerEventSource::Touch,
+        2 => PointerEventSource::TabletTool,
+        3 => PointerEventSource::Unknown,
+        _ => return DRAWING_CANVAS_ERR_INVALID_ARGUMENT,
+    };
+    if !(x.is_finite() && y.is_finite() && pressure.is_finite() && timestamp_ms.is_finite()) {
+        return DRAWING_CANVAS_ERR_INVALID_ARGUMENT;
+    }
+
+    // SAFETY: forwarded from the caller's contract
+    unsafe {
+        with_canvas(canvas, |canvas| {
+            canvas.app.queue_input_event(PointerEvent {
+                position: canvas.renderer.surface_to_canvas([x, y]),
+                pressure: pressure.clamp(0.0, 1.0),
+                tilt: None,
+                azimuth: None,
+                twist: None,
+                timestamp: canvas.timestamps.normalize(timestamp_ms, crate::time::now_ms()),
+                event_type,
+                source,
+            });
+            DRAWING_CANVAS_OK
+        })
+    }
+}
+
+/// Draw queued input into the canvas and present a frame to the window
+///
+/// Call once per frame (e.g. from the host's vsync callback).
+///
+/// # Safety
+/// `canvas` must be null or a live pointer from `drawing_canvas_create`.
+#[no_mangle]
+pub unsafe extern "C" fn drawing_canvas_render(canvas: *mut DrawingCanvas) -> i32 {
+    // SAFETY: forwarded from the caller's contract
+    unsafe {
+        with_canvas(canvas, |canvas| {
+            canvas.app.render(&mut canvas.renderer);
+            DRAWING_CANVAS_OK
+        })
+    }
+}
+
+/// Resize the surface after the host window changed size (canvas contents are kept)
+///
+/// # Safety
+/// `canvas` must be null or a live pointer from `drawing_canvas_create`.
+#[no_mangle]
+pub unsafe extern "C" fn drawing_canvas_resize(canvas: *mut DrawingCanvas, width: u32, height: u32) -> i32 {
+    if width == 0 || height == 0 {
+        return DRAWING_CANVAS_ERR_INVALID_ARGUMENT;
+    }
+    // SAFETY: forwarded from the caller's contract
+    unsafe {
+        with_canvas(canvas, |canvas| {
+            canvas.renderer.resize(winit::dpi::PhysicalSize::new(width, height));
+            DRAWING_CANVAS_OK
+        })
+    }
+}
+
+/// Set the basic brush settings; everything else about the brush is kept
+///
+/// # Arguments
+/// * `size` - Diameter in pixels (> 0)
+/// * `flow` - Per-dab opacity (0.0-1.0)
+/// * `hardness` - Edge hardness (0.0-1.0)
+/// * `spacing` - Distance between dabs as a fraction of the diameter (0.0-1.0)
+/// * `r`, `g`, `b`, `a` - Color in sRGB (0.0-1.0)
+///
+/// # Safety
+/// `canvas` must be null or a live pointer from `drawing_canvas_create`.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn drawing_canvas_set_brush(
+    canvas: *mut DrawingCanvas,
+    size: f32,
+    flow: f32,
+    hardness: f32,
+    spacing: f32,
+    r: f32,
+    g: f32,
+    b: f32,
+    a: f32,
+) -> i32 {
+    if ![size, flow, hardness, spacing, r, g, b, a].iter().all(|value| value.is_finite()) {
+        return DRAWING_CANVAS_ERR_INVALID_ARGUMENT;
+    }
+
+    // SAFETY: forwarded from the caller's contract
+    unsafe {
+        with_canvas(canvas, |canvas| {
+            let params = BrushParams {
+                size,
+                flow,
+                hardness,
+                spacing,
+                color: [r, g, b, a].map(|c| c.clamp(0.0, 1.0)),
+                ..canvas.app.brush_state().params
+            };
+            if let Err(e) = params.validate() {
+                log::warn!("Rejected brush settings: {}", e);
+                return DRAWING_CANVAS_ERR_INVALID_ARGUMENT;
+            }
+            canvas.app.set_brush_params(params);
+            DRAWING_CANVAS_OK
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(platform: u32, window: usize, display: usize) -> DrawingCanvasWindow {
+        DrawingCanvasWindow { platform, window: window as *mut c_void, display: display as *mut c_void }
+    }
+
+    #[test]
+    fn test_null_canvas_is_reported_not_dereferenced() {
+        let canvas = std::ptr::null_mut();
+        // SAFETY: null is allowed everywhere a canvas is taken
+        unsafe {
+            assert_eq!(drawing_canvas_queue_pointer(canvas, 0, 0, 1.0, 2.0, 1.0, 0.0), DRAWING_CANVAS_ERR_NULL_POINTER);
+            assert_eq!(drawing_canvas_render(canvas), DRAWING_CANVAS_ERR_NULL_POINTER);
+            assert_eq!(drawing_canvas_resize(canvas, 10, 10), DRAWING_CANVAS_ERR_NULL_POINTER);
+            assert_eq!(
+                drawing_canvas_set_brush(canvas, 10.0, 1.0, 1.0, 0.1, 0.0, 0.0, 0.0, 1.0),
+                DRAWING_CANVAS_ERR_NULL_POINTER
+            );
+            assert_eq!(drawing_canvas_destroy(canvas), DRAWING_CANVAS_OK);
+        }
+    }
+
+    #[test]
+    fn test_create_rejects_bad_arguments_before_touching_the_gpu() {
+        let mut out = NonNull::<DrawingCanvas>::dangling().as_ptr();
+        let wayland = window(DRAWING_CANVAS_PLATFORM_WAYLAND, 0x10, 0x20);
+        // SAFETY: every pointer is null, or valid for the duration of the call
+        unsafe {
+            assert_eq!(drawing_canvas_create(&wayland, 10, 10, std::ptr::null_mut()), DRAWING_CANVAS_ERR_NULL_POINTER);
+            assert_eq!(drawing_canvas_create(std::ptr::null(), 10, 10, &mut out), DRAWING_CANVAS_ERR_NULL_POINTER);
+            assert!(out.is_null(), "A failed create must null the out pointer");
+
+            for (width, height) in [(0, 10), (10, 0)] {
+                out = NonNull::dangling().as_ptr();
+                assert_eq!(drawing_canvas_create(&wayland, width, height, &mut out), DRAWING_CANVAS_ERR_INVALID_ARGUMENT);
+                assert!(out.is_null());
+            }
+
+            out = NonNull::dangling().as_ptr();
+            let unknown = window(99, 0x10, 0x20);
+            assert_eq!(drawing_canvas_create(&unknown, 10, 10, &mut out), DRAWING_CANVAS_ERR_UNSUPPORTED_PLATFORM);
+            assert!(out.is_null());
+        }
+    }
+
+    #[test]
+    fn test_raw_handles_require_the_platform_pointers() {
+        let cases = [
+            (window(DRAWING_CANVAS_PLATFORM_WIN32, 0, 0), DRAWING_CANVAS_ERR_NULL_POINTER),
+            (window(DRAWING_CANVAS_PLATFORM_APPKIT, 0, 0), DRAWING_CANVAS_ERR_NULL_POINTER),
+            (window(DRAWING_CANVAS_PLATFORM_UIKIT, 0, 0), DRAWING_CANVAS_ERR_NULL_POINTER),
+            (window(DRAWING_CANVAS_PLATFORM_ANDROID, 0, 0), DRAWING_CANVAS_ERR_NULL_POINTER),
+            (window(DRAWING_CANVAS_PLATFORM_WAYLAND, 0x10, 0), DRAWING_CANVAS_ERR_NULL_POINTER),
+            (window(DRAWING_CANVAS_PLATFORM_XLIB, 0, 0x20), DRAWING_CANVAS_ERR_NULL_POINTER),
+            (window(DRAWING_CANVAS_PLATFORM_XLIB, 0x10, 0), DRAWING_CANVAS_ERR_NULL_POINTER),
+            (window(99, 0x10, 0x20), DRAWING_CANVAS_ERR_UNSUPPORTED_PLATFORM),
+        ];
+        for (handles, code) in cases {
+            assert_eq!(handles.raw_handles().err(), Some(code), "Platform {}", handles.platform);
+        }
+
+        // The display is optional where the platform has none
+        assert!(window(DRAWING_CANVAS_PLATFORM_WIN32, 0x10, 0).raw_handles().is_ok());
+        assert!(window(DRAWING_CANVAS_PLATFORM_APPKIT, 0x10, 0).raw_handles().is_ok());
+        assert!(window(DRAWING_CANVAS_PLATFORM_WAYLAND, 0x10, 0x20).raw_handles().is_ok());
+    }
+
+    #[test]
+    fn test_invalid_arguments_are_rejected_before_the_canvas_is_used() {
+        // Argument checks come first, so even a null canvas reports the bad argument
+        let canvas = std::ptr::null_mut();
+        // SAFETY: null is allowed everywhere a canvas is taken
+        unsafe {
+            assert_eq!(drawing_canvas_queue_pointer(canvas, 3, 0, 1.0, 2.0, 1.0, 0.0), DRAWING_CANVAS_ERR_INVALID_ARGUMENT);
+            assert_eq!(drawing_canvas_queue_pointer(canvas, 0, 4, 1.0, 2.0, 1.0, 0.0), DRAWING_CANVAS_ERR_INVALID_ARGUMENT);
+            assert_eq!(
+                drawing_canvas_queue_pointer(canvas, 0, 0, f32::NAN, 2.0, 1.0, 0.0),
+                DRAWING_CANVAS_ERR_INVALID_ARGUMENT
+            );
+            assert_eq!(
+                drawing_canvas_queue_pointer(canvas, 0, 0, 1.0, 2.0, 1.0, f64::INFINITY),
+                DRAWING_CANVAS_ERR_INVALID_ARGUMENT
+            );
+            assert_eq!(drawing_canvas_resize(canvas, 0, 10), DRAWING_CANVAS_ERR_INVALID_ARGUMENT);
+            for (size, flow, spacing, a) in [
+                (f32::NAN, 1.0, 0.1, 1.0),
+                (f32::INFINITY, 1.0, 0.1, 1.0),
+                (10.0, f32::NAN, 0.1, 1.0),
+                (10.0, 1.0, f32::NAN, 1.0),
+                (10.0, 1.0, 0.1, f32::NAN),
+            ] {
+                assert_eq!(
+                    drawing_canvas_set_brush(canvas, size, flow, 1.0, spacing, 0.0, 0.0, 0.0, a),
+                    DRAWING_CANVAS_ERR_INVALID_ARGUMENT
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_panics_become_a_status_code() {
+        assert_eq!(catch_panics(|| DRAWING_CANVAS_OK), DRAWING_CANVAS_OK);
+        assert_eq!(catch_panics(|| panic!("Boom")), DRAWING_CANVAS_ERR_PANIC);
+    }
+}
@@ -0,0 +1,167 @@
+//! Bucket Fill
+//!
+//! Fills are found on the CPU: the active layer is read back, the contiguous region around
+//! the seed pixel is collected with a scanline flood fill, and the renderer draws it back
+//! as horizontal spans, blending them into the canvas like any other paint.
+//!
+//! A GPU flood fill would need one dependent compute pass per propagation step (hundreds
+//! for a winding region) and compute shaders aren't available on the WebGL2 backend at
+//! all, so the readback path is used on every backend to keep fills identical.
+
+/// A horizontal run of filled pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FillSpan {
+    /// Row in canvas pixels
+    pub y: u32,
+    /// First filled column
+    pub x_start: u32,
+    /// Column past the last filled one
+    pub x_end: u32,
+}
+
+/// A filled region with everything needed to draw it again
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fill {
+    /// Rows of the region, in the order they were found
+    pub spans: Vec<FillSpan>,
+    /// Fill color in sRGB RGBA (0.0-1.0), converted to the blend space when drawn
+    pub color: [f32; 4],
+}
+
+impl Fill {
+    /// Number of filled pixels
+    pub fn pixel_count(&self) -> u64 {
+        self.spans.iter().map(|span| (span.x_end - span.x_start) as u64).sum()
+    }
+}
+
+/// Find the 4-connected region of pixels matching the seed pixel
+///
+/// A pixel matches while every channel is within `tolerance` (0.0-1.0 of the full range)
+/// of the seed pixel's. `pixels` is RGBA8 data of `size`, as read back from the canvas.
+/// Work and memory are bounded by the pixel count: each pixel is visited at most once and
+/// the scanline stack holds at most one entry per run of open pixels.
+///
+/// # Returns
+/// The region as spans, empty if the seed is outside the image or the data doesn't match
+/// the size
+pub fn flood_fill(pixels: &[u8], size: [u32; 2], seed: [u32; 2], tolerance: f32) -> Vec<FillSpan> {
+    let [width, height] = size;
+    let [seed_x, seed_y] = seed;
+    if seed_x >= width || seed_y >= height || pixels.len() != width as usize * height as usize * 4 {
+        return Vec::new();
+    }
+
+    let index = |x: u32, y: u32| y as usize * width as usize + x as usize;
+    let limit = (tolerance.clamp(0.0, 1.0) * 255.0).round() as i16;
+    let target = &pixels[index(seed_x, seed_y) * 4..][..4];
+    let matches = |x: u32, y: u32| {
+        let pixel = &pixels[index(x, y) * 4..][..4];
+        pixel.iter().zip(target).all(|(&a, &b)| (a as i16 - b as i16).abs() <= limit)
+    };
+
+    let mut filled = vec![false; width as usize * height as usize];
+    let mut spans = Vec::new();
+    let mut stack = vec![seed];
+    while let Some([x, y]) = stack.pop() {
+        if filled[index(x, y)] {
+            continue;
+        }
+
+        // Grow the run both ways along the row
+        let mut x_start = x;
+        while x_start > 0 && !filled[index(x_start - 1, y)] && matches(x_start - 1, y) {
+            x_start -= 1;
+        }
+        let mut x_end = x + 1;
+        while x_end < width && !filled[index(x_end, y)] && matches(x_end, y) {
+            x_end += 1;
+        }
+        filled[index(x_start, y)..index(x_end, y)].fill(true);
+        spans.push(FillSpan { y, x_start, x_end });
+
+        // Seed each run of open pixels in the rows above and below
+        let neighbors = [y.checked_sub(1), (y + 1 < height).then_some(y + 1)];
+        for row in neighbors.into_iter().flatten() {
+            let mut in_run = false;
+            for column in x_start..x_end {
+                let open = !filled[index(column, row)] && matches(column, row);
+                if open && !in_run {
+                    stack.push([column, row]);
+                }
+                in_run = open;
+            }
+        }
+    }
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An image from rows of characters: '#' is black, '~' is near-white, anything else white
+    fn image(rows: &[&str]) -> (Vec<u8>, [u32; 2]) {
+        let pixels = rows
+            .iter()
+            .flat_map(|row| row.chars())
+            .flat_map(|c| match c {
+                '#' => [0, 0, 0, 255],
+                '~' => [245, 245, 245, 255],
+                _ => [255, 255, 255, 255],
+            })
+            .collect();
+        (pixels, [rows[0].len() as u32, rows.len() as u32])
+    }
+
+    fn filled(spans: &[FillSpan], size: [u32; 2]) -> Vec<String> {
+        (0..size[1])
+            .map(|y| {
+                (0..size[0])
+                    .map(|x| {
+                        let inside = spans.iter().any(|s| s.y == y && (s.x_start..s.x_end).contains(&x));
+                        if inside { 'x' } else { '.' }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_fill_stops_at_borders() {
+        let (pixels, size) = image(&[
+            "......",
+            ".####.",
+            ".#..#.",
+            ".#.##.",
+            ".#..#.",
+            ".####.",
+        ]);
+        let spans = flood_fill(&pixels, size, [2, 2], 0.0);
+        assert_eq!(
+            filled(&spans, size),
+            ["......", "......", "..xx..", "..x...", "..xx..", "......"]
+        );
+        assert_eq!(Fill { spans, color: [0.0; 4] }.pixel_count(), 5);
+    }
+
+    #[test]
+    fn test_fill_does_not_leak_through_diagonal_gaps() {
+        let (pixels, size) = image(&[
+            "..#",
+            ".#.",
+            "#..",
+        ]);
+        let spans = flood_fill(&pixels, size, [0, 0], 0.0);
+        assert_eq!(filled(&spans, size), ["xx.", "x..", "..."]);
+    }
+
+    #[test]
+    fn test_tolerance_joins_similar_colors() {
+        let (pixels, size) = image(&["..~~#."]);
+        assert_eq!(filled(&flood_fill(&pixels, size, [0, 0], 0.0), size), ["xx...."]);
+        assert_eq!(filled(&flood_fill(&pixels, size, [0, 0], 0.05), size), ["xxxx.."]);
+        assert_eq!(filled(&flood_fill(&pixels, size, [0, 0], 1.0), size), ["xxxxxx"]);
+        assert!(flood_fill(&pixels, size, [6, 0], 1.0).is_empty());
+    }
+}
@@ -0,0 +1,292 @@
+//! Background Gradients
+//!
+//! A gradient fill shown behind the strokes. It is composited beneath the canvas when
+//! displayed (and optionally when exported), never drawn into the canvas itself, so it
+//! can be changed or removed at any time without touching the drawing.
+//!
+//! Geometry is in canvas space, so the gradient pans, zooms, and rotates with the view:
+//! - Linear: top (0.0) to bottom (1.0) of the visible canvas
+//! - Radial: canvas center (0.0) out to the corners (1.0)
+//!
+//! Stop colors are sRGB. They are interpolated in sRGB by default, like CSS gradients, or
+//! optionally in linear RGB or OKLab (see `GradientInterpolation`).
+
+/// Most color stops a gradient can have (limited by the blit uniform layout)
+pub const MAX_GRADIENT_STOPS: usize = 8;
+
+/// Shape of a background gradient
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GradientKind {
+    /// Top to bottom of the canvas
+    Linear,
+    /// From the canvas center out to the corners
+    Radial,
+}
+
+/// Color space a gradient is interpolated in between stops
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GradientInterpolation {
+    /// Gamma-encoded sRGB, like CSS gradients (midpoints come out darker)
+    #[default]
+    Srgb,
+    /// Linear RGB: physically even light, but saturated pairs fade through gray
+    Linear,
+    /// OKLab: perceptually even lightness and saturation (nicest for rainbows)
+    Oklab,
+}
+
+impl GradientInterpolation {
+    /// Convert an sRGB color (straight alpha) into this space; alpha is unchanged
+    pub fn encode(self, srgb: [f32; 4]) -> [f32; 4] {
+        match self {
+            Self::Srgb => srgb,
+            Self::Linear => crate::color::srgb_to_linear_rgba(srgb),
+            Self::Oklab => {
+                let [r, g, b, a] = crate::color::srgb_to_linear_rgba(srgb);
+                let [l, ok_a, ok_b] = crate::color::linear_to_oklab([r, g, b]);
+                [l, ok_a, ok_b, a]
+            }
+        }
+    }
+
+    /// Convert a color in this space back to sRGB (straight alpha), clamped to the gamut
+    pub fn decode(self, color: [f32; 4]) -> [f32; 4] {
+        let srgb = match self {
+            Self::Srgb => color,
+            Self::Linear => crate::color::linear_to_srgb_rgba(color.map(|c| c.max(0.0))),
+            Self::Oklab => {
+                let [r, g, b] = crate::color::oklab_to_linear([color[0], color[1], color[2]]);
+                crate::color::linear_to_srgb_rgba([r.max(0.0), g.max(0.0), b.max(0.0), color[3]])
+            }
+        };
+        srgb.map(|c| c.clamp(0.0, 1.0))
+    }
+}
+
+/// A color at a position along a gradient
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GradientStop {
+    /// Position along the gradient (0.0-1.0)
+    pub position: f32,
+    /// Color in sRGB RGBA (0.0-1.0), straight alpha
+    pub color: [f32; 4],
+}
+
+/// A validated background gradient
+#[derive(Debug, Clone, PartialEq)]
+pub struct BackgroundGradient {
+    kind: GradientKind,
+    stops: Vec<GradientStop>,
+    interpolation: GradientInterpolation,
+}
+
+impl BackgroundGradient {
+    /// Create a gradient from at least two stops in ascending position order
+    pub fn new(kind: GradientKind, stops: &[GradientStop]) -> Result<Self, String> {
+        if !(2..=MAX_GRADIENT_STOPS).contains(&stops.len()) {
+            return Err(format!(
+                "Gradient needs between 2 and {} stops, got {}",
+                MAX_GRADIENT_STOPS,
+                stops.len()
+            ));
+        }
+        if stops.iter().any(|stop| !(0.0..=1.0).contains(&stop.position)) {
+            return Err("Gradient stop positions must be between 0.0 and 1.0".to_string());
+        }
+        if stops.windows(2).any(|pair| pair[1].position < pair[0].position) {
+            return Err("Gradient stop positions must be in ascending order".to_string());
+        }
+        Ok(Self {
+            kind,
+            stops: stops.to_vec(),
+            interpolation: GradientInterpolation::default(),
+        })
+    }
+
+    /// Use a different color space for interpolating between stops (default: sRGB)
+    pub fn with_interpolation(mut self, interpolation: GradientInterpolation) -> Self {
+        self.interpolation = interpolation;
+        self
+    }
+
+    /// Shape of the gradient
+    pub fn kind(&self) -> GradientKind {
+        self.kind
+    }
+
+    /// Color space the stops are interpolated in
+    pub fn interpolation(&self) -> GradientInterpolation {
+        self.interpolation
+    }
+
+    /// Color stops in ascending position order
+    pub fn stops(&self) -> &[GradientStop] {
+        &self.stops
+    }
+
+    /// Position along the gradient (unclamped) of a point in visible canvas pixels
+    pub fn position_at(&self, canvas_pos: [f32; 2], canvas_size: [f32; 2]) -> f32 {
+        match self.kind {
+            GradientKind::Linear => canvas_pos[1] / canvas_size[1].max(1.0),
+            GradientKind::Radial => {
+                let dx = canvas_pos[0] - canvas_size[0] * 0.5;
+                let dy = canvas_pos[1] - canvas_size[1] * 0.5;
+                let half_diagonal = 0.5 * (canvas_size[0].powi(2) + canvas_size[1].powi(2)).sqrt();
+                (dx * dx + dy * dy).sqrt() / half_diagonal.max(1.0)
+            }
+        }
+    }
+
+    /// sRGB color at a position along the gradient
+    /// Positions before the first stop or after the last take that stop's color.
+    pub fn color_at(&self, t: f32) -> [f32; 4] {
+        let first = self.stops[0];
+        if t <= first.position {
+            return first.color;
+        }
+        for pair in self.stops.windows(2) {
+            let (from, to) = (pair[0], pair[1]);
+            if t <= to.position {
+                let span = to.position - from.position;
+                let f = if span > 0.0 { (t - from.position) / span } else { 1.0 };
+                let space = self.interpolation;
+                let (from, to) = (space.encode(from.color), space.encode(to.color));
+                return space.decode(std::array::from_fn(|i| from[i] + (to[i] - from[i]) * f));
+            }
+        }
+        self.stops[self.stops.len() - 1].color
+    }
+
+    /// Composite the gradient beneath premultiplied RGBA8 pixels, in place
+    ///
+    /// # Arguments
+    /// * `pixels` - Tightly packed premultiplied RGBA8 rows, `width` pixels wide
+    /// * `canvas_origin` - Canvas position of the first pixel's corner (negative inside the bleed)
+    /// * `canvas_size` - Visible canvas size the gradient spans
+    /// * `linear` - Whether the pixels are linear (gradient colors are converted to match)
+    pub fn composite_under_rgba8(&self, pixels: &mut [u8], width: u32, canvas_origin: [f32; 2], canvas_size: [f32; 2], linear: bool) {
+        for (index, pixel) in pixels.chunks_exact_mut(4).enumerate() {
+            let color = self.pixel_color(index, width, canvas_origin, canvas_size, linear);
+
+            // Premultiplied canvas "over" the premultiplied gradient
+            let canvas_alpha = pixel[3] as f32 / 255.0;
+            for channel in 0..3 {
+                let under = color[channel] * color[3] * 255.0;
+                pixel[channel] = (pixel[channel] as f32 + under * (1.0 - canvas_alpha)).round().clamp(0.0, 255.0) as u8;
+            }
+            let alpha = canvas_alpha + color[3] * (1.0 - canvas_alpha);
+            pixel[3] = (alpha * 255.0).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+
+    /// Composite the gradient beneath premultiplied float RGBA pixels, in place
+    ///
+    /// Like `composite_under_rgba8`, at full precision: canvas values above 1.0 are kept.
+    pub fn composite_under(&self, pixels: &mut [[f32; 4]], width: u32, canvas_origin: [f32; 2], canvas_size: [f32; 2], linear: bool) {
+        for (index, pixel) in pixels.iter_mut().enumerate() {
+            let color = self.pixel_color(index, width, canvas_origin, canvas_size, linear);
+            let canvas_alpha = pixel[3].clamp(0.0, 1.0);
+            for channel in 0..3 {
+                pixel[channel] += color[channel] * color[3] * (1.0 - canvas_alpha);
+            }
+            pixel[3] = canvas_alpha + color[3] * (1.0 - canvas_alpha);
+        }
+    }
+
+    /// Straight gradient color under the pixel at `index` of rows `width` pixels wide,
+    /// converted to linear if the pixels are
+    fn pixel_color(&self, index: usize, width: u32, canvas_origin: [f32; 2], canvas_size: [f32; 2], linear: bool) -> [f32; 4] {
+        let width = width.max(1) as usize;
+        let x = (index % width) as f32 + 0.5 + canvas_origin[0];
+        let y = (index / width) as f32 + 0.5 + canvas_origin[1];
+        let color = self.color_at(self.position_at([x, y], canvas_size));
+        if linear {
+            crate::color::srgb_to_linear_rgba(color)
+        } else {
+            color
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stop(position: f32, value: f32) -> GradientStop {
+        GradientStop {
+            position,
+            color: [value, value, value, 1.0],
+        }
+    }
+
+    #[test]
+    fn test_color_at_interpolates_between_stops() {
+        let gradient = BackgroundGradient::new(
+            GradientKind::Linear,
+            &[stop(0.2, 0.0), stop(0.6, 1.0), stop(1.0, 0.5)],
+        )
+        .unwrap();
+        assert_eq!(gradient.color_at(0.0)[0], 0.0);
+        assert!((gradient.color_at(0.4)[0] - 0.5).abs() < 1e-5);
+        assert!((gradient.color_at(0.8)[0] - 0.75).abs() < 1e-5);
+        assert_eq!(gradient.color_at(1.5)[0], 0.5);
+    }
+
+    #[test]
+    fn test_geometry_spans_the_canvas() {
+        let linear = BackgroundGradient::new(GradientKind::Linear, &[stop(0.0, 0.0), stop(1.0, 1.0)]).unwrap();
+        assert_eq!(linear.position_at([30.0, 0.0], [100.0, 50.0]), 0.0);
+        assert_eq!(linear.position_at([30.0, 50.0], [100.0, 50.0]), 1.0);
+
+        let radial = BackgroundGradient::new(GradientKind::Radial, &[stop(0.0, 0.0), stop(1.0, 1.0)]).unwrap();
+        assert_eq!(radial.position_at([50.0, 25.0], [100.0, 50.0]), 0.0);
+        assert!((radial.position_at([100.0, 50.0], [100.0, 50.0]) - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_composite_under_fills_transparent_pixels_only() {
+        let gradient = BackgroundGradient::new(GradientKind::Linear, &[stop(0.0, 1.0), stop(1.0, 1.0)]).unwrap();
+        // One transparent pixel and one opaque black pixel
+        let mut pixels = [0, 0, 0, 0, 0, 0, 0, 255];
+        gradient.composite_under_rgba8(&mut pixels, 2, [0.0, 0.0], [2.0, 1.0], false);
+        assert_eq!(pixels, [255, 255, 255, 255, 0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_rejects_invalid_stops() {
+        assert!(BackgroundGradient::new(GradientKind::Linear, &[stop(0.0, 0.0)]).is_err());
+        assert!(BackgroundGradient::new(GradientKind::Linear, &[stop(0.5, 0.0), stop(0.2, 1.0)]).is_err());
+        assert!(BackgroundGradient::new(GradientKind::Radial, &[stop(0.0, 0.0), stop(1.5, 1.0)]).is_err());
+        let too_many = vec![stop(0.5, 0.0); MAX_GRADIENT_STOPS + 1];
+        assert!(BackgroundGradient::new(GradientKind::Linear, &too_many).is_err());
+    }
+
+    #[test]
+    fn test_interpolation_space_changes_midpoint_only() {
+        let red = GradientStop { position: 0.0, color: [1.0, 0.0, 0.0, 1.0] };
+        let blue = GradientStop { position: 1.0, color: [0.0, 0.0, 1.0, 1.0] };
+        let gradient = |space| {
+            BackgroundGradient::new(GradientKind::Linear, &[red, blue])
+                .unwrap()
+                .with_interpolation(space)
+        };
+
+        let srgb = gradient(GradientInterpolation::Srgb);
+        let linear = gradient(GradientInterpolation::Linear);
+        let oklab = gradient(GradientInterpolation::Oklab);
+        for g in [&srgb, &linear, &oklab] {
+            for (end, stop) in [(0.0, red), (1.0, blue)] {
+                for (a, b) in g.color_at(end).iter().zip(stop.color.iter()) {
+                    assert!((a - b).abs() < 1e-3);
+                }
+            }
+        }
+
+        // sRGB midpoints are darkest; linear and OKLab stay brighter
+        assert!((srgb.color_at(0.5)[0] - 0.5).abs() < 1e-5);
+        assert!(linear.color_at(0.5)[0] > 0.7);
+        let mid = oklab.color_at(0.5);
+        assert!(mid[0] > 0.5 && mid[2] > 0.5, "{:?}", mid);
+        assert_ne!(mid, linear.color_at(0.5));
+    }
+}
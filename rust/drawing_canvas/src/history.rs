@@ -0,0 +1,344 @@
+//! Undo History
+//!
+//! The canvas texture only accumulates, so strokes can't be removed from it directly.
+//! Instead the dabs of each completed stroke (or the region of each bucket fill) are kept,
+//! and undo/redo restore the canvas from a snapshot taken before the oldest kept stroke
+//! (the undo base) and replay the strokes that remain. Each layer has its own undo base,
+//! and only the layer a stroke was drawn on is rebuilt when it is undone or redone.
+//! This module holds the stroke stacks; `App` drives the renderer.
+
+use crate::brush::{BrushBlendMode, BrushDab};
+use crate::fill::Fill;
+use std::collections::VecDeque;
+
+/// Default number of strokes that can be undone
+pub const DEFAULT_UNDO_LIMIT: usize = 50;
+
+/// A completed stroke with everything needed to draw it again
+#[derive(Debug, Clone)]
+pub struct HistoryStroke {
+    /// Layer the stroke was drawn into
+    pub layer: usize,
+    /// Dabs in the order they were rendered
+    pub dabs: Vec<BrushDab>,
+    /// Whether the dabs erased instead of painted
+    pub erase: bool,
+    /// How the dabs combined with the canvas
+    pub blend_mode: BrushBlendMode,
+    /// Opacity ceiling the stroke was drawn under
+    pub max_opacity: f32,
+    /// Opacity the stroke layer composited the stroke at (unused unless per-stroke)
+    pub stroke_opacity: f32,
+    /// Whether the stroke went through the stroke layer (PerStroke or Uniform opacity)
+    pub per_stroke: bool,
+    /// Smudge strength the dabs dragged paint with, None = painted
+    pub smudge: Option<f32>,
+    /// Smudge length the dabs carried color with (unused when painted)
+    pub smudge_length: f32,
+    /// Bucket fill drawn instead of dabs
+    pub fill: Option<Fill>,
+}
+
+impl HistoryStroke {
+    /// Check whether two strokes are drawn with the same renderer settings (so they can be
+    /// replayed together)
+    /// Fills are always replayed on their own.
+    pub fn same_render_settings(&self, other: &HistoryStroke) -> bool {
+        self.fill.is_none()
+            && other.fill.is_none()
+            && self.erase == other.erase
+            && self.blend_mode == other.blend_mode
+            && self.max_opacity == other.max_opacity
+            && self.stroke_opacity == other.stroke_opacity
+            && self.per_stroke == other.per_stroke
+            && self.smudge == other.smudge
+            && self.smudge_length == other.smudge_length
+    }
+}
+
+/// Bounded stacks of undoable and redoable strokes
+#[derive(Debug)]
+pub struct StrokeHistory {
+    /// Strokes on the canvas above the undo base, oldest first
+    strokes: VecDeque<HistoryStroke>,
+    /// Undone strokes, most recently undone last
+    undone: Vec<HistoryStroke>,
+    /// Most strokes kept for undo (0 = undo off)
+    limit: usize,
+}
+
+impl StrokeHistory {
+    /// Create an empty history with the default limit
+    pub fn new() -> Self {
+        Self {
+            strokes: VecDeque::new(),
+            undone: Vec::new(),
+            limit: DEFAULT_UNDO_LIMIT,
+        }
+    }
+
+    /// Most strokes kept for undo (0 = undo off)
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Set how many strokes are kept for undo (0 = undo off)
+    ///
+    /// Returns the oldest strokes that no longer fit, oldest first. They can't be undone
+    /// anymore and must be baked into the undo base. Redo history is dropped when the
+    /// limit shrinks.
+    pub fn set_limit(&mut self, limit: usize) -> Vec<HistoryStroke> {
+        let shrinking = limit < self.limit;
+        self.limit = limit;
+        if shrinking {
+            self.undone.clear();
+        }
+        self.evict_excess()
+    }
+
+    /// Record a completed stroke, dropping the redo history
+    /// Returns the oldest strokes pushed past the limit (see `set_limit`).
+    pub fn push(&mut self, stroke: HistoryStroke) -> Vec<HistoryStroke> {
+        self.undone.clear();
+        self.strokes.push_back(stroke);
+        self.evict_excess()
+    }
+
+    /// Move the newest stroke to the redo stack and return it (None if there is nothing to undo)
+    pub fn undo(&mut self) -> Option<&HistoryStroke> {
+        let stroke = self.strokes.pop_back()?;
+        self.undone.push(stroke);
+        self.undone.last()
+    }
+
+    /// Move the most recently undone stroke back and return it (None if there is nothing to redo)
+    pub fn redo(&mut self) -> Option<&HistoryStroke> {
+        let stroke = self.undone.pop()?;
+        self.strokes.push_back(stroke);
+        self.strokes.back()
+    }
+
+    /// Check whether there is a stroke to undo
+    pub fn can_undo(&self) -> bool {
+        !self.strokes.is_empty()
+    }
+
+    /// Check whether there is a stroke to redo
+    pub fn can_redo(&self) -> bool {
+        !self.undone.is_empty()
+    }
+
+    /// Check whether there is nothing to undo or redo
+    pub fn is_empty(&self) -> bool {
+        self.strokes.is_empty() && self.undone.is_empty()
+    }
+
+    /// Strokes on the canvas above the undo base, oldest first
+    pub fn strokes(&self) -> impl ExactSizeIterator<Item = &HistoryStroke> {
+        self.strokes.iter()
+    }
+
+    /// Strokes above the undo base of one layer, oldest first
+    pub fn strokes_on(&self, layer: usize) -> impl Iterator<Item = &HistoryStroke> {
+        self.strokes.iter().filter(move |stroke| stroke.layer == layer)
+    }
+
+    /// Forget every stroke (e.g. when the canvas they were drawn on is cleared)
+    pub fn clear(&mut self) {
+        self.strokes.clear();
+        self.undone.clear();
+    }
+
+    /// Keep only the undoable and redoable strokes `keep` returns true for
+    pub fn retain(&mut self, mut keep: impl FnMut(&HistoryStroke) -> bool) {
+        self.strokes.retain(|stroke| keep(stroke));
+        self.undone.retain(|stroke| keep(stroke));
+    }
+
+    /// Forget the strokes drawn on a removed layer and renumber those on the layers above it
+    pub fn remove_layer(&mut self, layer: usize) {
+        self.retain(|stroke| stroke.layer != layer);
+        for stroke in self.strokes.iter_mut().chain(&mut self.undone) {
+            if stroke.layer > layer {
+                stroke.layer -= 1;
+            }
+        }
+    }
+
+    /// Remove the oldest strokes past the limit
+    fn evict_excess(&mut self) -> Vec<HistoryStroke> {
+        let excess = self.strokes.len().saturating_sub(self.limit);
+        self.strokes.drain(..excess).collect()
+    }
+}
+
+impl Default for StrokeHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A stroke on the bottom layer identified by its dab count
+    fn stroke(dab_count: usize) -> HistoryStroke {
+        stroke_on(0, dab_count)
+    }
+
+    /// A stroke on a layer identified by its dab count
+    fn stroke_on(layer: usize, dab_count: usize) -> HistoryStroke {
+        let dab = BrushDab {
+            position: [0.0, 0.0],
+            size: 1.0,
+            opacity: 1.0,
+            color: [0.0, 0.0, 0.0, 1.0],
+            hardness: 1.0,
+            angle: 0.0,
+            tip_shape: crate::brush::TipShape::Round,
+            clips: [None; 2],
+            square_cap: false,
+            smudge_offset: [0.0; 2],
+        };
+        HistoryStroke {
+            layer,
+            dabs: vec![dab; dab_count],
+            erase: false,
+            blend_mode: BrushBlendMode::Normal,
+            max_opacity: 1.0,
+            stroke_opacity: 1.0,
+            per_stroke: false,
+            smudge: None,
+            smudge_length: 0.0,
+            fill: None,
+        }
+    }
+
+    fn dab_counts(history: &StrokeHistory) -> Vec<usize> {
+        history.strokes().map(|s| s.dabs.len()).collect()
+    }
+
+    #[test]
+    fn test_undo_and_redo_move_strokes_between_stacks() {
+        let mut history = StrokeHistory::new();
+        history.push(stroke(1));
+        history.push(stroke(2));
+
+        assert_eq!(history.undo().map(|s| s.dabs.len()), Some(2));
+        assert_eq!(dab_counts(&history), vec![1]);
+        assert!(history.can_redo());
+
+        assert_eq!(history.redo().map(|s| s.dabs.len()), Some(2));
+        assert_eq!(dab_counts(&history), vec![1, 2]);
+        assert!(history.redo().is_none());
+
+        assert!(history.undo().is_some());
+        assert!(history.undo().is_some());
+        assert!(history.undo().is_none());
+        assert!(!history.is_empty());
+    }
+
+    #[test]
+    fn test_new_stroke_drops_redo_history() {
+        let mut history = StrokeHistory::new();
+        history.push(stroke(1));
+        history.push(stroke(2));
+        history.undo();
+
+        history.push(stroke(3));
+
+        assert!(!history.can_redo());
+        assert_eq!(dab_counts(&history), vec![1, 3]);
+    }
+
+    #[test]
+    fn test_limit_evicts_oldest_strokes() {
+        let mut history = StrokeHistory::new();
+        history.set_limit(2);
+        assert!(history.push(stroke(1)).is_empty());
+        assert!(history.push(stroke(2)).is_empty());
+
+        let evicted = history.push(stroke(3));
+        assert_eq!(evicted.len(), 1);
+        assert_eq!(evicted[0].dabs.len(), 1);
+        assert_eq!(dab_counts(&history), vec![2, 3]);
+
+        history.undo();
+        let evicted = history.set_limit(0);
+        assert_eq!(evicted.len(), 1);
+        assert!(history.is_empty());
+    }
+
+    #[test]
+    fn test_only_a_shrinking_limit_drops_redo_history() {
+        let mut history = StrokeHistory::new();
+        history.set_limit(3);
+        history.push(stroke(1));
+        history.push(stroke(2));
+        history.undo();
+
+        assert!(history.set_limit(5).is_empty());
+        assert!(history.can_redo());
+        assert!(history.set_limit(4).is_empty());
+        assert!(!history.can_redo(), "Redo past a shrunk limit could overflow it");
+        assert_eq!(dab_counts(&history), vec![1]);
+    }
+
+    #[test]
+    fn test_redo_keeps_its_order_across_several_undos() {
+        let mut history = StrokeHistory::new();
+        for dab_count in 1..=3 {
+            history.push(stroke(dab_count));
+        }
+        history.undo();
+        history.undo();
+
+        assert_eq!(history.redo().map(|s| s.dabs.len()), Some(2));
+        assert_eq!(history.redo().map(|s| s.dabs.len()), Some(3));
+        assert_eq!(dab_counts(&history), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_strokes_on_filters_by_layer() {
+        let mut history = StrokeHistory::new();
+        history.push(stroke_on(0, 1));
+        history.push(stroke_on(1, 2));
+        history.push(stroke_on(0, 3));
+
+        let counts: Vec<usize> = history.strokes_on(0).map(|s| s.dabs.len()).collect();
+        assert_eq!(counts, vec![1, 3]);
+        assert_eq!(history.undo().map(|s| s.layer), Some(0));
+        assert_eq!(history.undo().map(|s| s.layer), Some(1));
+    }
+
+    #[test]
+    fn test_removing_a_layer_keeps_other_layers_history() {
+        let mut history = StrokeHistory::new();
+        history.push(stroke_on(0, 1));
+        history.push(stroke_on(1, 2));
+        history.push(stroke_on(2, 3));
+        history.push(stroke_on(1, 4));
+        history.undo();
+
+        history.remove_layer(1);
+
+        assert_eq!(dab_counts(&history), vec![1, 3]);
+        let layers: Vec<usize> = history.strokes().map(|s| s.layer).collect();
+        assert_eq!(layers, vec![0, 1]);
+        // The undone stroke was on the removed layer
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn test_removing_a_layer_renumbers_redo_strokes() {
+        let mut history = StrokeHistory::new();
+        history.push(stroke_on(0, 1));
+        history.push(stroke_on(2, 2));
+        history.undo();
+
+        history.remove_layer(1);
+
+        assert_eq!(history.redo().map(|s| s.layer), Some(1));
+    }
+}
@@ -0,0 +1,125 @@
+//! Image Import
+//!
+//! Decodes image files supplied by the embedder into the 8-bit coverage masks
+//! the brush engine uploads as textures (255 = full coverage). Images with an
+//! alpha channel use it as the mask; opaque images use their luminance.
+
+/// Decode a PNG into an 8-bit coverage mask
+///
+/// Grayscale, grayscale+alpha, RGB, RGBA, and palette PNGs of any bit depth are
+/// accepted. The size is checked against `max_size` before the pixels are decoded.
+///
+/// # Returns
+/// `(mask, width, height)`, with the mask tightly packed (width * height bytes)
+pub fn decode_png_mask(data: &[u8], max_size: u32) -> Result<(Vec<u8>, u32, u32), String> {
+    let mut decoder = png::Decoder::new(data);
+    // Expand palette/low bit depths and drop 16-bit precision so every pixel is 8-bit
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder
+        .read_info()
+        .map_err(|e| format!("Failed to read PNG header: {}", e))?;
+
+    let (width, height) = {
+        let info = reader.info();
+        (info.width, info.height)
+    };
+    if width == 0 || height == 0 {
+        return Err("PNG dimensions must be positive".to_string());
+    }
+    if width > max_size || height > max_size {
+        return Err(format!(
+            "PNG {}x{} exceeds max brush texture size {}",
+            width, height, max_size
+        ));
+    }
+
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let frame = reader
+        .next_frame(&mut buffer)
+        .map_err(|e| format!("Failed to decode PNG: {}", e))?;
+    let pixels = &buffer[..frame.buffer_size()];
+
+    let mask = match frame.color_type {
+        png::ColorType::Grayscale => pixels.to_vec(),
+        png::ColorType::GrayscaleAlpha => pixels.chunks_exact(2).map(|p| p[1]).collect(),
+        png::ColorType::Rgb => pixels.chunks_exact(3).map(|p| luminance(p[0], p[1], p[2])).collect(),
+        png::ColorType::Rgba => pixels.chunks_exact(4).map(|p| p[3]).collect(),
+        png::ColorType::Indexed => {
+            return Err("PNG palette was not expanded".to_string());
+        }
+    };
+
+    Ok((mask, width, height))
+}
+
+/// Convert tightly packed 8-bit RGBA pixels into a coverage mask
+///
+/// Uses the alpha channel if any pixel is not fully opaque, otherwise the luminance,
+/// matching how `decode_png_mask` treats PNGs with and without alpha.
+pub fn rgba_to_mask(pixels: &[u8]) -> Vec<u8> {
+    let has_alpha = pixels.chunks_exact(4).any(|p| p[3] < 255);
+    if has_alpha {
+        pixels.chunks_exact(4).map(|p| p[3]).collect()
+    } else {
+        pixels.chunks_exact(4).map(|p| luminance(p[0], p[1], p[2])).collect()
+    }
+}
+
+/// Rec. 709 luma of 8-bit RGB, computed on the encoded values like a grayscale conversion
+fn luminance(r: u8, g: u8, b: u8) -> u8 {
+    (0.2126 * r as f32 + 0.7152 * g as f32 + 0.0722 * b as f32).round() as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(pixels: &[u8], width: u32, height: u32, color_type: png::ColorType) -> Vec<u8> {
+        let mut png_data = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut png_data, width, height);
+            encoder.set_color(color_type);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_image_data(pixels).unwrap();
+        }
+        png_data
+    }
+
+    #[test]
+    fn test_grayscale_uses_luminance() {
+        let png_data = encode(&[0, 128, 255, 64], 2, 2, png::ColorType::Grayscale);
+        let (mask, width, height) = decode_png_mask(&png_data, 16).unwrap();
+        assert_eq!((width, height), (2, 2));
+        assert_eq!(mask, vec![0, 128, 255, 64]);
+    }
+
+    #[test]
+    fn test_rgba_uses_alpha() {
+        let pixels = [255, 255, 255, 10, 0, 0, 0, 200];
+        let png_data = encode(&pixels, 2, 1, png::ColorType::Rgba);
+        let (mask, _, _) = decode_png_mask(&png_data, 16).unwrap();
+        assert_eq!(mask, vec![10, 200]);
+    }
+
+    #[test]
+    fn test_rgb_uses_luminance() {
+        let pixels = [255, 255, 255, 0, 0, 0];
+        let png_data = encode(&pixels, 2, 1, png::ColorType::Rgb);
+        let (mask, _, _) = decode_png_mask(&png_data, 16).unwrap();
+        assert_eq!(mask, vec![255, 0]);
+    }
+
+    #[test]
+    fn test_rgba_to_mask_prefers_alpha_when_present() {
+        assert_eq!(rgba_to_mask(&[0, 0, 0, 255, 255, 255, 255, 255]), vec![0, 255]);
+        assert_eq!(rgba_to_mask(&[0, 0, 0, 40, 255, 255, 255, 255]), vec![40, 255]);
+    }
+
+    #[test]
+    fn test_rejects_oversized_and_invalid() {
+        let png_data = encode(&[0; 64], 8, 8, png::ColorType::Grayscale);
+        assert!(decode_png_mask(&png_data, 4).is_err());
+        assert!(decode_png_mask(b"not a png", 16).is_err());
+    }
+}
@@ -6,6 +6,16 @@
 
 use std::collections::VecDeque;
 
+/// Interval between the samples of a synthetic stroke in milliseconds (120 Hz, like a stylus)
+pub const SYNTHETIC_SAMPLE_INTERVAL_MS: f64 = 1000.0 / 120.0;
+/// Speed synthetic strokes are drawn at when none is given, in canvas pixels per second
+pub const DEFAULT_SYNTHETIC_STROKE_SPEED: f32 = 600.0;
+/// Most events a single synthetic stroke may generate (about 14 minutes of drawing)
+const MAX_SYNTHETIC_STROKE_EVENTS: usize = 100_000;
+/// Furthest a raw timestamp may fall behind the newest one and still count as a late event;
+/// anything older means the source clock was reset (e.g. after a tab refocus)
+const MAX_LATE_EVENT_MS: f64 = 1000.0;
+
 /// A pointer input event (mouse, touch, or stylus)
 #[derive(Debug, Clone)]
 pub struct PointerEvent {
@@ -28,7 +38,7 @@ pub struct PointerEvent {
 }
 
 /// Type of pointer event
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub enum PointerEventType {
     /// Pointer button pressed (start of stroke)
     Down,
@@ -39,7 +49,7 @@ pub enum PointerEventType {
 }
 
 // Source of pointer event
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub enum PointerEventSource {
     Mouse,
     Touch,
@@ -47,6 +57,252 @@ pub enum PointerEventSource {
     Unknown,
 }
 
+/// Convert a stylus force reading into pressure (0.0-1.0)
+///
+/// `force` is the reading as a fraction of the device maximum, or None if the platform
+/// didn't report one (or reported a maximum of zero). Without a usable reading the
+/// contact draws at full pressure, like a mouse, rather than not at all. Readings
+/// slightly out of range from some drivers are clamped.
+pub fn pressure_from_force(force: Option<f64>) -> f32 {
+    match force {
+        Some(force) if force.is_finite() => force.clamp(0.0, 1.0) as f32,
+        _ => 1.0,
+    }
+}
+
+/// Rebases raw event timestamps onto the monotonic session clock (`crate::time::now_ms`)
+///
+/// Platforms stamp pointer events from different clocks, and some (e.g. a browser tab
+/// regaining focus) can jump backwards. The first timestamp, and the first after a clock
+/// reset, is pinned to the current session time; later ones keep their spacing from it.
+/// Normalized timestamps never go backwards: a late, out-of-order event gets the newest
+/// time seen (a zero delta), so de-duplication and velocity stay correct.
+#[derive(Debug, Clone, Default)]
+pub struct TimestampNormalizer {
+    /// Session time minus raw time, for the current source clock
+    offset: f64,
+    /// Newest raw timestamp seen (None before the first event)
+    newest_raw: Option<f64>,
+    /// Newest normalized timestamp handed out
+    newest: f64,
+}
+
+impl TimestampNormalizer {
+    /// Create a normalizer that pins its first event to the session time it arrives at
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Normalize the timestamp of an event arriving at session time `now_ms`
+    ///
+    /// Non-finite timestamps are treated as arriving with a zero delta.
+    pub fn normalize(&mut self, raw_ms: f64, now_ms: f64) -> f64 {
+        if !raw_ms.is_finite() {
+            return self.newest;
+        }
+        let clock_reset = match self.newest_raw {
+            Some(newest_raw) => raw_ms < newest_raw - MAX_LATE_EVENT_MS,
+            None => true,
+        };
+        if clock_reset {
+            if let Some(newest_raw) = self.newest_raw {
+                log::debug!("Input clock went back {:.0}ms, rebasing", newest_raw - raw_ms);
+            }
+            self.offset = now_ms - raw_ms;
+            self.newest_raw = Some(raw_ms);
+        } else {
+            self.newest_raw = self.newest_raw.map(|newest_raw| newest_raw.max(raw_ms));
+        }
+        self.newest = (raw_ms + self.offset).max(self.newest);
+        self.newest
+    }
+
+    /// Map a raw timestamp from the current source clock without updating the clock state
+    ///
+    /// For sub-samples of the event just normalized (e.g. coalesced pointer moves), which
+    /// are older than it and must keep their spacing; callers drop any out of order.
+    pub fn rebase(&self, raw_ms: f64) -> f64 {
+        raw_ms + self.offset
+    }
+}
+
+/// A raw input sample retained for stroke analysis
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StrokePoint {
+    /// Position in canvas space (pixels from top-left)
+    pub position: [f32; 2],
+    /// Pressure value (0.0-1.0)
+    pub pressure: f32,
+    /// Tilt angles (x and y in degrees), if available
+    pub tilt: Option<[f32; 2]>,
+    /// Azimuth angle in radians, if available
+    pub azimuth: Option<f32>,
+    /// Barrel rotation (twist) in degrees, if available
+    pub twist: Option<f32>,
+    /// Timestamp in milliseconds
+    pub timestamp: f64,
+}
+
+impl From<&PointerEvent> for StrokePoint {
+    fn from(event: &PointerEvent) -> Self {
+        Self {
+            position: event.position,
+            pressure: event.pressure,
+            tilt: event.tilt,
+            azimuth: event.azimuth,
+            twist: event.twist,
+            timestamp: event.timestamp,
+        }
+    }
+}
+
+/// Records the raw point stream of the current and last completed stroke
+///
+/// This is the temporal gesture data (for speed, smoothness, and pressure analysis),
+/// separate from the dabs rendered to the canvas. Retention is capped per stroke;
+/// points past the cap are dropped.
+///
+/// Optionally, a bounded history of completed strokes is kept as well, for vector
+/// export and replay. Every stroke is rasterized into the canvas as it is drawn, so when
+/// the history is full the oldest stroke is simply dropped: it stays baked into the
+/// canvas and only stops being available as vector data.
+pub struct StrokeRecorder {
+    /// Points of the stroke in progress
+    current: Vec<StrokePoint>,
+    /// Points of the last completed stroke
+    last: Vec<StrokePoint>,
+    /// Whether the current stroke hit the point cap
+    truncated: bool,
+    /// Completed strokes kept for vector export/replay, oldest first
+    retained: VecDeque<Vec<StrokePoint>>,
+    /// Most completed strokes kept in `retained` (0 = retention off)
+    max_retained: usize,
+}
+
+impl StrokeRecorder {
+    /// Maximum points retained per stroke (~48 bytes each, so under 1 MB)
+    pub const MAX_POINTS_PER_STROKE: usize = 20_000;
+
+    /// Create an empty recorder
+    pub fn new() -> Self {
+        Self {
+            current: Vec::new(),
+            last: Vec::new(),
+            truncated: false,
+            retained: VecDeque::new(),
+            max_retained: 0,
+        }
+    }
+
+    /// Record an input event, starting or finishing a stroke as needed
+    pub fn record(&mut self, event: &PointerEvent) {
+        if event.event_type == PointerEventType::Down {
+            self.current.clear();
+            self.truncated = false;
+        }
+
+        if self.current.len() < Self::MAX_POINTS_PER_STROKE {
+            self.current.push(StrokePoint::from(event));
+        } else if !self.truncated {
+            self.truncated = true;
+            log::warn!("Stroke exceeded {} points, dropping the rest", Self::MAX_POINTS_PER_STROKE);
+        }
+
+        if event.event_type == PointerEventType::Up {
+            let stroke = std::mem::take(&mut self.current);
+            self.complete_stroke(stroke);
+        }
+    }
+
+    /// Finish the stroke in progress as a segment and continue recording a new one
+    ///
+    /// The new segment starts at the previous segment's last point so the two join up.
+    pub fn split_stroke(&mut self) {
+        let Some(&joint) = self.current.last() else {
+            return;
+        };
+        let stroke = std::mem::replace(&mut self.current, vec![joint]);
+        self.complete_stroke(stroke);
+        self.truncated = false;
+    }
+
+    /// Hand off a completed stroke (or segment) as the last stroke, retaining it if enabled
+    fn complete_stroke(&mut self, stroke: Vec<StrokePoint>) {
+        if self.max_retained > 0 {
+            self.retained.push_back(stroke.clone());
+            self.drop_oldest_retained();
+        }
+        self.last = stroke;
+    }
+
+    /// Drop the oldest retained strokes past the cap (their pixels stay in the canvas)
+    fn drop_oldest_retained(&mut self) {
+        let excess = self.retained.len().saturating_sub(self.max_retained);
+        if excess > 0 {
+            self.retained.drain(..excess);
+            log::debug!("Dropped {} oldest retained strokes (kept baked into the canvas)", excess);
+        }
+    }
+
+    /// Set how many completed strokes are retained for vector export/replay (0 = off)
+    /// Lowering the cap drops the oldest strokes right away.
+    pub fn set_max_retained_strokes(&mut self, max: usize) {
+        self.max_retained = max;
+        self.drop_oldest_retained();
+    }
+
+    /// Get how many completed strokes are retained at most (0 = retention off)
+    pub fn max_retained_strokes(&self) -> usize {
+        self.max_retained
+    }
+
+    /// Retained completed strokes, oldest first
+    pub fn retained_strokes(&self) -> impl ExactSizeIterator<Item = &[StrokePoint]> {
+        self.retained.iter().map(Vec::as_slice)
+    }
+
+    /// Forget the retained strokes (e.g. when the canvas they were drawn on is cleared)
+    pub fn clear_retained(&mut self) {
+        self.retained.clear();
+    }
+
+    /// Points of the last completed stroke (empty if none)
+    pub fn last_stroke(&self) -> &[StrokePoint] {
+        &self.last
+    }
+
+    /// Discard all retained points
+    pub fn clear(&mut self) {
+        self.current.clear();
+        self.last.clear();
+        self.truncated = false;
+        self.retained.clear();
+    }
+}
+
+impl Default for StrokeRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Action performed while the stylus barrel (side) button is held
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum BarrelButtonAction {
+    /// Erase while the button is held, reverting on release
+    Erase,
+    /// Ignore the barrel button
+    None,
+    /// Undo the last stroke when the button is pressed
+    Undo,
+}
+
+impl Default for BarrelButtonAction {
+    fn default() -> Self {
+        Self::Erase
+    }
+}
+
 /// Queue for input events that coalesces events between frames
 pub struct InputQueue {
     /// Pending events to process
@@ -55,20 +311,114 @@ pub struct InputQueue {
     is_drawing: bool,
     /// Last known pointer position (for calculating spacing)
     last_position: Option<[f32; 2]>,
+    /// Interval between resampled events in milliseconds (0 = resampling off)
+    resample_interval_ms: f64,
+    /// Last raw sample of the stroke in progress, resampling starts from here
+    resample_last: Option<PointerEvent>,
+    /// Timestamp of the next resampled event
+    resample_next_time: f64,
 }
 
 impl InputQueue {
+    /// Longest gap between raw samples (ms) that is filled with resampled events
+    /// Beyond this the pen was resting rather than moving, so the sample passes through
+    pub const MAX_RESAMPLE_GAP_MS: f64 = 100.0;
+
     /// Create a new empty input queue
     pub fn new() -> Self {
         Self {
             events: VecDeque::new(),
             is_drawing: false,
             last_position: None,
+            resample_interval_ms: 0.0,
+            resample_last: None,
+            resample_next_time: 0.0,
+        }
+    }
+
+    /// Set the rate moves are resampled to, in events per second (0 = off)
+    ///
+    /// Raw moves are replaced by moves at a fixed interval, interpolated between the raw
+    /// samples, so dab density and velocity-based effects don't depend on the device's
+    /// report rate. Down and Up events are kept as they are.
+    pub fn set_resample_rate(&mut self, hz: f32) {
+        self.resample_interval_ms = if hz.is_finite() && hz > 0.0 { 1000.0 / hz as f64 } else { 0.0 };
+    }
+
+    /// Get the resample rate in events per second (0 = off)
+    pub fn resample_rate(&self) -> f32 {
+        if self.resample_interval_ms > 0.0 {
+            (1000.0 / self.resample_interval_ms) as f32
+        } else {
+            0.0
         }
     }
 
-    /// Add an event to the queue
+    /// Add an event to the queue, resampling moves if enabled
     pub fn push_event(&mut self, event: PointerEvent) {
+        if self.resample_interval_ms <= 0.0 {
+            self.resample_last = None;
+            self.push_raw_event(event);
+            return;
+        }
+
+        match event.event_type {
+            PointerEventType::Down => {
+                self.resample_next_time = event.timestamp + self.resample_interval_ms;
+                self.resample_last = Some(event.clone());
+                self.push_raw_event(event);
+            }
+            PointerEventType::Move => {
+                if !self.is_drawing {
+                    return;
+                }
+                self.push_resampled_moves(&event);
+            }
+            PointerEventType::Up => {
+                if self.is_drawing {
+                    self.push_resampled_moves(&event);
+                }
+                self.resample_last = None;
+                self.push_raw_event(event);
+            }
+        }
+    }
+
+    /// Queue moves at each resample time up to a new raw sample, interpolated from the last
+    fn push_resampled_moves(&mut self, sample: &PointerEvent) {
+        let Some(last) = self.resample_last.take() else {
+            // Resampling was switched on mid-stroke: start the clock at this sample
+            self.resample_next_time = sample.timestamp + self.resample_interval_ms;
+            self.resample_last = Some(sample.clone());
+            if sample.event_type == PointerEventType::Move {
+                self.push_raw_event(sample.clone());
+            }
+            return;
+        };
+
+        let elapsed = sample.timestamp - last.timestamp;
+        if elapsed > Self::MAX_RESAMPLE_GAP_MS {
+            // The pen was resting: pass the sample through and restart the clock from it
+            if sample.event_type == PointerEventType::Move {
+                self.push_raw_event(sample.clone());
+            }
+            self.resample_next_time = sample.timestamp + self.resample_interval_ms;
+        } else if elapsed > 0.0 {
+            while self.resample_next_time <= sample.timestamp {
+                let t = ((self.resample_next_time - last.timestamp) / elapsed) as f32;
+                let mut resampled = interpolate_event(&last, sample, t);
+                resampled.timestamp = self.resample_next_time;
+                resampled.event_type = PointerEventType::Move;
+                self.push_raw_event(resampled);
+                self.resample_next_time += self.resample_interval_ms;
+            }
+        }
+        // Samples with no time between them just move the interpolation start along
+        self.resample_last = Some(sample.clone());
+    }
+
+    /// Add an event to the queue as is
+    fn push_raw_event(&mut self, event: PointerEvent) {
         let event_type = event.event_type; // Copy before moving event
         
         match event.event_type {
@@ -122,3 +472,461 @@ impl Default for InputQueue {
         Self::new()
     }
 }
+
+/// Span of recent samples (ms) the prediction velocity is measured over
+const PREDICTION_WINDOW_MS: f64 = 30.0;
+/// Furthest a prediction reaches past the newest sample, in canvas pixels
+const MAX_PREDICTION_PX: f32 = 48.0;
+
+/// Extrapolates the pointer a short time past its newest sample to hide input latency
+///
+/// Velocity is measured across the samples of the last `PREDICTION_WINDOW_MS`. The
+/// predicted point is never further ahead than the pointer moved during that window (nor
+/// `MAX_PREDICTION_PX`), so a pen slowing to a stop at the end of a stroke isn't thrown
+/// past where it lands. A prediction is only offered right after new samples arrive;
+/// once the pointer goes quiet there is nothing current to extrapolate.
+pub struct InputPredictor {
+    /// How far ahead to predict in milliseconds (0 = prediction off)
+    lookahead_ms: f64,
+    /// Samples of the stroke in progress within the velocity window (empty between strokes)
+    samples: VecDeque<PointerEvent>,
+    /// Whether samples arrived since the last prediction
+    fresh: bool,
+}
+
+impl InputPredictor {
+    /// Longest lookahead in milliseconds; beyond this predictions mostly miss
+    pub const MAX_LOOKAHEAD_MS: f32 = 50.0;
+
+    /// Create a predictor with prediction off
+    pub fn new() -> Self {
+        Self {
+            lookahead_ms: 0.0,
+            samples: VecDeque::new(),
+            fresh: false,
+        }
+    }
+
+    /// Set how far ahead to predict in milliseconds (0 = off, clamped to `MAX_LOOKAHEAD_MS`)
+    pub fn set_lookahead_ms(&mut self, ms: f32) {
+        self.lookahead_ms = if ms.is_finite() { ms.clamp(0.0, Self::MAX_LOOKAHEAD_MS) as f64 } else { 0.0 };
+    }
+
+    /// Get how far ahead predictions reach in milliseconds (0 = off)
+    pub fn lookahead_ms(&self) -> f32 {
+        self.lookahead_ms as f32
+    }
+
+    /// Track an event of the processed input stream
+    pub fn record(&mut self, event: &PointerEvent) {
+        match event.event_type {
+            PointerEventType::Down => {
+                self.samples.clear();
+                self.samples.push_back(event.clone());
+                self.fresh = true;
+            }
+            PointerEventType::Move => {
+                if self.samples.is_empty() {
+                    return;
+                }
+                self.samples.push_back(event.clone());
+                // Keep the newest sample older than the window, so the window is spanned
+                let window_start = event.timestamp - PREDICTION_WINDOW_MS;
+                while self.samples.len() > 2 && self.samples[1].timestamp <= window_start {
+                    self.samples.pop_front();
+                }
+                self.fresh = true;
+            }
+            PointerEventType::Up => {
+                self.samples.clear();
+                self.fresh = false;
+            }
+        }
+    }
+
+    /// Predict where the pointer will be `lookahead_ms` after the newest sample
+    ///
+    /// Returns a Move event carrying the newest sample's pressure and angles, or None if
+    /// prediction is off, no stroke is in progress, no samples arrived since the last call,
+    /// or the pointer isn't moving.
+    pub fn predict(&mut self) -> Option<PointerEvent> {
+        if !std::mem::take(&mut self.fresh) || self.lookahead_ms <= 0.0 {
+            return None;
+        }
+        let (oldest, newest) = (self.samples.front()?, self.samples.back()?);
+        let elapsed = newest.timestamp - oldest.timestamp;
+        let delta = [newest.position[0] - oldest.position[0], newest.position[1] - oldest.position[1]];
+        let moved = delta[0].hypot(delta[1]);
+        if elapsed <= 0.0 || moved <= 0.0 {
+            return None;
+        }
+
+        let distance = ((moved as f64 * self.lookahead_ms / elapsed) as f32).min(moved).min(MAX_PREDICTION_PX);
+        let scale = distance / moved;
+        Some(PointerEvent {
+            position: [newest.position[0] + delta[0] * scale, newest.position[1] + delta[1] * scale],
+            timestamp: newest.timestamp + self.lookahead_ms,
+            event_type: PointerEventType::Move,
+            ..newest.clone()
+        })
+    }
+}
+
+impl Default for InputPredictor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Generate the timed pointer events of a stroke drawn along a polyline
+///
+/// The pointer travels the path at a constant `speed` (canvas pixels per second) and
+/// `pressure`, emitting a Down at the first point, a Move at least every
+/// `SYNTHETIC_SAMPLE_INTERVAL_MS` and at every vertex (so corners stay sharp), and an Up at
+/// the last point. Timestamps start at `start_ms`. A single point makes a tap.
+pub fn synthetic_stroke_events(path: &[[f32; 2]], pressure: f32, speed: f32, start_ms: f64) -> Result<Vec<PointerEvent>, String> {
+    let Some(&start) = path.first() else {
+        return Err("Synthetic stroke path must have at least one point".to_string());
+    };
+    if !path.iter().flatten().all(|c| c.is_finite()) {
+        return Err("Synthetic stroke path coordinates must be finite".to_string());
+    }
+    if !(0.0..=1.0).contains(&pressure) {
+        return Err("Synthetic stroke pressure must be between 0.0 and 1.0".to_string());
+    }
+    if !(speed.is_finite() && speed > 0.0) {
+        return Err("Synthetic stroke speed must be positive".to_string());
+    }
+
+    let segment_length = |pair: &[[f32; 2]]| {
+        ((pair[1][0] - pair[0][0]) as f64).hypot((pair[1][1] - pair[0][1]) as f64)
+    };
+    let step_px = speed as f64 * SYNTHETIC_SAMPLE_INTERVAL_MS / 1000.0;
+    let move_count: f64 = path.windows(2).map(|pair| (segment_length(pair) / step_px).ceil()).sum();
+    if move_count + 2.0 > MAX_SYNTHETIC_STROKE_EVENTS as f64 {
+        return Err("Synthetic stroke is too long for its speed".to_string());
+    }
+
+    let event = |position, timestamp, event_type| PointerEvent {
+        position,
+        pressure,
+        tilt: None,
+        azimuth: None,
+        twist: None,
+        timestamp,
+        event_type,
+        source: PointerEventSource::Unknown,
+    };
+    let mut events = Vec::with_capacity(move_count as usize + 2);
+    events.push(event(start, start_ms, PointerEventType::Down));
+    let mut time = start_ms;
+    for pair in path.windows(2) {
+        let length = segment_length(pair);
+        if length == 0.0 {
+            continue;
+        }
+        // Equal steps no longer than one sample interval, ending exactly on the vertex
+        let steps = (length / step_px).ceil().max(1.0);
+        let step_ms = length / steps / speed as f64 * 1000.0;
+        let [from, to] = [pair[0], pair[1]];
+        for i in 1..=steps as usize {
+            let t = (i as f64 / steps) as f32;
+            let position = [from[0] + (to[0] - from[0]) * t, from[1] + (to[1] - from[1]) * t];
+            time += step_ms;
+            events.push(event(position, time, PointerEventType::Move));
+        }
+    }
+    let end = path[path.len() - 1];
+    events.push(event(end, time, PointerEventType::Up));
+    Ok(events)
+}
+
+/// Blend two samples of a stroke, `t` of the way from `a` to `b`
+/// Angles go the short way around; optional values missing from either sample take `b`'s.
+fn interpolate_event(a: &PointerEvent, b: &PointerEvent, t: f32) -> PointerEvent {
+    let lerp = |from: f32, to: f32| from + (to - from) * t;
+    PointerEvent {
+        position: [lerp(a.position[0], b.position[0]), lerp(a.position[1], b.position[1])],
+        pressure: lerp(a.pressure, b.pressure),
+        tilt: match (a.tilt, b.tilt) {
+            (Some(from), Some(to)) => Some([lerp(from[0], to[0]), lerp(from[1], to[1])]),
+            (_, to) => to,
+        },
+        azimuth: match (a.azimuth, b.azimuth) {
+            (Some(from), Some(to)) => Some(crate::brush::smooth_angle(from, to, 1.0 - t, std::f32::consts::TAU)),
+            (_, to) => to,
+        },
+        twist: match (a.twist, b.twist) {
+            (Some(from), Some(to)) => Some(crate::brush::smooth_angle(from, to, 1.0 - t, 360.0)),
+            (_, to) => to,
+        },
+        timestamp: a.timestamp + (b.timestamp - a.timestamp) * t as f64,
+        event_type: b.event_type,
+        source: b.source,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(event_type: PointerEventType, x: f32, timestamp: f64) -> PointerEvent {
+        PointerEvent {
+            position: [x, 0.0],
+            pressure: 0.5,
+            tilt: Some([10.0, 20.0]),
+            azimuth: None,
+            twist: None,
+            timestamp,
+            event_type,
+            source: PointerEventSource::TabletTool,
+        }
+    }
+
+    #[test]
+    fn test_pressure_from_force_falls_back_to_full_pressure() {
+        assert_eq!(pressure_from_force(Some(0.25)), 0.25);
+        assert_eq!(pressure_from_force(Some(1.02)), 1.0);
+        assert_eq!(pressure_from_force(Some(-0.1)), 0.0);
+        assert_eq!(pressure_from_force(None), 1.0);
+        assert_eq!(pressure_from_force(Some(f64::NAN)), 1.0);
+    }
+
+    #[test]
+    fn test_timestamps_rebase_onto_session_clock() {
+        let mut normalizer = TimestampNormalizer::new();
+        // The first event lands at the session time; later ones keep their raw spacing
+        assert_eq!(normalizer.normalize(50_000.0, 200.0), 200.0);
+        assert_eq!(normalizer.normalize(50_008.0, 210.0), 208.0);
+        assert_eq!(normalizer.rebase(50_004.0), 204.0);
+
+        // Late and duplicate events get a zero delta rather than going backwards
+        assert_eq!(normalizer.normalize(50_004.0, 211.0), 208.0);
+        assert_eq!(normalizer.normalize(50_008.0, 212.0), 208.0);
+        assert_eq!(normalizer.normalize(f64::NAN, 212.0), 208.0);
+        assert_eq!(normalizer.normalize(50_016.0, 220.0), 216.0);
+    }
+
+    #[test]
+    fn test_timestamps_survive_backwards_clock_jump() {
+        let mut normalizer = TimestampNormalizer::new();
+        assert_eq!(normalizer.normalize(90_000.0, 1_000.0), 1_000.0);
+        assert_eq!(normalizer.normalize(90_016.0, 1_016.0), 1_016.0);
+
+        // After a refocus the source clock restarts near zero: events keep moving forward
+        // from the session time instead of stalling until the old clock is caught up
+        assert_eq!(normalizer.normalize(5.0, 4_000.0), 4_000.0);
+        assert_eq!(normalizer.normalize(13.0, 4_010.0), 4_008.0);
+        assert_eq!(normalizer.normalize(21.0, 4_020.0), 4_016.0);
+    }
+
+    #[test]
+    fn test_recorder_keeps_last_completed_stroke() {
+        let mut recorder = StrokeRecorder::new();
+        recorder.record(&event(PointerEventType::Down, 0.0, 0.0));
+        recorder.record(&event(PointerEventType::Move, 1.0, 8.0));
+        assert!(recorder.last_stroke().is_empty());
+        recorder.record(&event(PointerEventType::Up, 2.0, 16.0));
+
+        let points = recorder.last_stroke();
+        assert_eq!(points.len(), 3);
+        assert_eq!(points[1].position, [1.0, 0.0]);
+        assert_eq!(points[1].tilt, Some([10.0, 20.0]));
+        assert_eq!(points[2].timestamp, 16.0);
+
+        // A new stroke replaces the last one once it completes
+        recorder.record(&event(PointerEventType::Down, 5.0, 20.0));
+        recorder.record(&event(PointerEventType::Up, 6.0, 24.0));
+        assert_eq!(recorder.last_stroke().len(), 2);
+    }
+
+    #[test]
+    fn test_recorder_split_continues_from_joint() {
+        let mut recorder = StrokeRecorder::new();
+        recorder.record(&event(PointerEventType::Down, 0.0, 0.0));
+        recorder.record(&event(PointerEventType::Move, 1.0, 8.0));
+        recorder.split_stroke();
+        assert_eq!(recorder.last_stroke().len(), 2);
+
+        recorder.record(&event(PointerEventType::Up, 2.0, 16.0));
+        let points = recorder.last_stroke();
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[0].position, [1.0, 0.0]);
+    }
+
+    #[test]
+    fn test_recorder_caps_points_per_stroke() {
+        let mut recorder = StrokeRecorder::new();
+        recorder.record(&event(PointerEventType::Down, 0.0, 0.0));
+        for i in 0..StrokeRecorder::MAX_POINTS_PER_STROKE + 10 {
+            recorder.record(&event(PointerEventType::Move, i as f32, i as f64));
+        }
+        recorder.record(&event(PointerEventType::Up, 0.0, 1e9));
+        assert_eq!(recorder.last_stroke().len(), StrokeRecorder::MAX_POINTS_PER_STROKE);
+    }
+
+    #[test]
+    fn test_recorder_retains_up_to_max_strokes() {
+        let stroke = |recorder: &mut StrokeRecorder, x: f32| {
+            recorder.record(&event(PointerEventType::Down, x, 0.0));
+            recorder.record(&event(PointerEventType::Up, x + 1.0, 8.0));
+        };
+
+        // Off by default
+        let mut recorder = StrokeRecorder::new();
+        stroke(&mut recorder, 0.0);
+        assert_eq!(recorder.retained_strokes().len(), 0);
+
+        // Over the cap, the oldest strokes are dropped
+        recorder.set_max_retained_strokes(2);
+        for i in 0..3 {
+            stroke(&mut recorder, i as f32 * 10.0);
+        }
+        let firsts: Vec<f32> = recorder.retained_strokes().map(|points| points[0].position[0]).collect();
+        assert_eq!(firsts, [10.0, 20.0]);
+
+        // Lowering the cap trims right away
+        recorder.set_max_retained_strokes(1);
+        assert_eq!(recorder.retained_strokes().next().unwrap()[0].position[0], 20.0);
+        recorder.set_max_retained_strokes(0);
+        assert_eq!(recorder.retained_strokes().len(), 0);
+    }
+
+    fn drain_moves(queue: &mut InputQueue) -> Vec<PointerEvent> {
+        queue
+            .drain_events()
+            .filter(|event| event.event_type == PointerEventType::Move)
+            .collect()
+    }
+
+    #[test]
+    fn test_resampling_upsamples_slow_input() {
+        let mut queue = InputQueue::new();
+        queue.set_resample_rate(240.0);
+        queue.push_event(event(PointerEventType::Down, 0.0, 0.0));
+        // 60 Hz input moving 4 px per sample
+        for i in 1..=3 {
+            queue.push_event(event(PointerEventType::Move, i as f32 * 4.0, i as f64 * 1000.0 / 60.0));
+        }
+
+        let moves = drain_moves(&mut queue);
+        assert_eq!(moves.len(), 12);
+        for (i, event) in moves.iter().enumerate() {
+            // Evenly spaced in time, and position follows the straight line between samples
+            let expected_time = (i + 1) as f64 * 1000.0 / 240.0;
+            assert!((event.timestamp - expected_time).abs() < 1e-6);
+            assert!((event.position[0] - (i + 1) as f32).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_resampling_downsamples_fast_input_and_keeps_up() {
+        let mut queue = InputQueue::new();
+        queue.set_resample_rate(60.0);
+        queue.push_event(event(PointerEventType::Down, 0.0, 0.0));
+        // 240 Hz input for 50 ms
+        for i in 1..=12 {
+            queue.push_event(event(PointerEventType::Move, i as f32, i as f64 * 1000.0 / 240.0));
+        }
+        queue.push_event(event(PointerEventType::Up, 12.0, 51.0));
+
+        let events: Vec<PointerEvent> = queue.drain_events().collect();
+        let moves = events.iter().filter(|e| e.event_type == PointerEventType::Move).count();
+        assert_eq!(moves, 3);
+        assert_eq!(events.last().unwrap().event_type, PointerEventType::Up);
+        assert_eq!(events.last().unwrap().position, [12.0, 0.0]);
+    }
+
+    #[test]
+    fn test_resampling_passes_samples_through_after_a_rest() {
+        let mut queue = InputQueue::new();
+        queue.set_resample_rate(120.0);
+        queue.push_event(event(PointerEventType::Down, 0.0, 0.0));
+        queue.push_event(event(PointerEventType::Move, 10.0, 2000.0));
+        let moves = drain_moves(&mut queue);
+        assert_eq!(moves.len(), 1);
+        assert_eq!(moves[0].position, [10.0, 0.0]);
+    }
+
+    #[test]
+    fn test_prediction_extrapolates_recent_velocity() {
+        let mut predictor = InputPredictor::new();
+        predictor.set_lookahead_ms(10.0);
+        predictor.record(&event(PointerEventType::Down, 0.0, 0.0));
+        // 1 px per ms, sampled every 4 ms
+        for i in 1..=10 {
+            predictor.record(&event(PointerEventType::Move, i as f32 * 4.0, i as f64 * 4.0));
+        }
+
+        let predicted = predictor.predict().unwrap();
+        assert_eq!(predicted.event_type, PointerEventType::Move);
+        assert!((predicted.position[0] - 50.0).abs() < 1e-3);
+        assert_eq!(predicted.position[1], 0.0);
+        assert_eq!(predicted.timestamp, 50.0);
+
+        // Nothing new since the last prediction: the pointer went quiet
+        assert!(predictor.predict().is_none());
+
+        // Prediction stops with the stroke, and while off
+        predictor.record(&event(PointerEventType::Up, 40.0, 44.0));
+        predictor.record(&event(PointerEventType::Move, 44.0, 48.0));
+        assert!(predictor.predict().is_none());
+        predictor.set_lookahead_ms(0.0);
+        predictor.record(&event(PointerEventType::Down, 0.0, 100.0));
+        predictor.record(&event(PointerEventType::Move, 4.0, 104.0));
+        assert!(predictor.predict().is_none());
+    }
+
+    #[test]
+    fn test_prediction_is_bounded_by_recent_motion() {
+        let mut predictor = InputPredictor::new();
+        predictor.set_lookahead_ms(InputPredictor::MAX_LOOKAHEAD_MS);
+        // A fast flick that covers 20 px in the last sample gap
+        predictor.record(&event(PointerEventType::Down, 0.0, 0.0));
+        predictor.record(&event(PointerEventType::Move, 20.0, 8.0));
+        let predicted = predictor.predict().unwrap();
+        assert!((predicted.position[0] - 40.0).abs() < 1e-3);
+
+        // Very fast input never reaches further than the hard cap
+        predictor.record(&event(PointerEventType::Move, 500.0, 16.0));
+        let predicted = predictor.predict().unwrap();
+        assert!((predicted.position[0] - (500.0 + MAX_PREDICTION_PX)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_synthetic_stroke_follows_path_at_speed() {
+        // 120 px/s at 120 Hz is one pixel per sample
+        let path = [[0.0, 0.0], [10.0, 0.0], [10.0, 5.0]];
+        let events = synthetic_stroke_events(&path, 0.7, 120.0, 100.0).unwrap();
+
+        assert_eq!(events.first().unwrap().event_type, PointerEventType::Down);
+        assert_eq!(events.first().unwrap().position, [0.0, 0.0]);
+        assert_eq!(events.last().unwrap().event_type, PointerEventType::Up);
+        assert_eq!(events.last().unwrap().position, [10.0, 5.0]);
+        assert!(events.iter().all(|event| event.pressure == 0.7));
+
+        let moves: Vec<_> = events.iter().filter(|e| e.event_type == PointerEventType::Move).collect();
+        assert_eq!(moves.len(), 15);
+        // The corner is hit exactly
+        assert!(moves.iter().any(|event| event.position == [10.0, 0.0]));
+        // Evenly timed: 15 px at 120 px/s takes 125 ms
+        for pair in events.windows(2).take(moves.len()) {
+            let dt = pair[1].timestamp - pair[0].timestamp;
+            assert!((dt - SYNTHETIC_SAMPLE_INTERVAL_MS).abs() < 1e-6);
+        }
+        assert!((events.last().unwrap().timestamp - 225.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_synthetic_stroke_validates_input() {
+        let tap = synthetic_stroke_events(&[[3.0, 4.0]], 1.0, 100.0, 0.0).unwrap();
+        assert_eq!(tap.len(), 2);
+        assert_eq!(tap[1].event_type, PointerEventType::Up);
+
+        assert!(synthetic_stroke_events(&[], 1.0, 100.0, 0.0).is_err());
+        assert!(synthetic_stroke_events(&[[0.0, 0.0]], 1.5, 100.0, 0.0).is_err());
+        assert!(synthetic_stroke_events(&[[0.0, 0.0]], 1.0, 0.0, 0.0).is_err());
+        assert!(synthetic_stroke_events(&[[0.0, f32::NAN]], 1.0, 100.0, 0.0).is_err());
+        assert!(synthetic_stroke_events(&[[0.0, 0.0], [1.0e7, 0.0]], 1.0, 1.0, 0.0).is_err());
+    }
+}
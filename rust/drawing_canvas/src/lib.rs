@@ -2,23 +2,48 @@
 //!
 //! This crate provides a wgpu-based drawing canvas that can run:
 //! - Standalone in a browser (via WASM)
-//! - Embedded in Flutter (via FFI) - TODO: future work
+//! - Embedded in Flutter or another native host (via the C API in `ffi`)
 //!
 //! The library is structured to separate the core rendering logic from
 //! platform-specific initialization (windowing, canvas element creation).
 
 mod app;
 mod brush;
-mod color;
+mod brush_shader;
+pub mod color;
+mod config;
 pub mod debug;
+mod error;
+// Only the web build converts CSS sizes to physical pixels
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+mod dpi;
+mod export;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod ffi;
+mod fill;
+mod gradient;
+mod history;
+mod import;
 mod input;
+mod recording;
 mod renderer;
+mod session;
+mod time;
+mod view;
 mod window;
 
 pub use app::App;
-pub use brush::{BrushDab, BrushParams, BrushState, InputFilterMode, PressureMapping};
-pub use input::{InputQueue, PointerEvent, PointerEventType};
-pub use renderer::{BlendColorSpace, Renderer};
+pub use brush::{BrushBlendMode, BrushDab, BrushParams, BrushState, CapStyle, DabClip, DownDabMode, FirstDabPressureMode, InputFilterMode, OpacityMode, PressureCurve, PressureMapping, PressureResponse, SpacingMode, Symmetry, SymmetryMode, TipShape, Tool, VelocityMapping};
+pub use color::{linear_to_srgb, linear_to_srgb_rgba, srgb_to_linear, srgb_to_linear_rgba, srgb_to_linear_rgba_f64, srgb_u8_to_linear_f32};
+pub use config::{AppConfig, CONFIG_VERSION};
+pub use error::{report_error, ErrorCode};
+pub use export::HdrExportMode;
+pub use gradient::{BackgroundGradient, GradientInterpolation, GradientKind, GradientStop, MAX_GRADIENT_STOPS};
+pub use input::{BarrelButtonAction, InputPredictor, InputQueue, PointerEvent, PointerEventSource, PointerEventType, StrokePoint};
+pub use recording::{InputRecording, RecordedEvent, RECORDING_VERSION};
+pub use renderer::{AaMode, AdapterDiagnostics, BlendColorSpace, CanvasSnapshot, CanvasTextureHandle, ComparisonMode, CursorRing, Renderer, UploadedImages, WrapMode};
+pub use session::PoseSession;
+pub use view::{InputTransform, ViewTransform};
 pub use window::AppWrapper;
 
 // Re-export for WASM builds
@@ -90,14 +115,35 @@ fn run_event_loop() {
     let event_loop = EventLoop::new().expect("Failed to create event loop");
     event_loop.set_control_flow(ControlFlow::Wait);
     
-    let mut app_wrapper = AppWrapper::new();
-    
-    // Store reference for JS callbacks
-    window::set_global_app_wrapper(&mut app_wrapper);
+    // Shared with the JS entry points; the event loop borrows it for each event
+    let mut app_wrapper = window::set_global_app_wrapper(AppWrapper::new());
     
     let _ = event_loop.run_app(&mut app_wrapper);
 }
 
+/// Set the size the canvas is created at, in CSS pixels (default: 800x600 physical pixels)
+///
+/// Call before `init_drawing_canvas` when the container's final size is known, so the
+/// first frame renders at that size instead of rendering small and then resizing. The
+/// container's resize observer still takes over once the canvas is on the page.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_initial_canvas_size(width: f64, height: f64) {
+    window::set_initial_canvas_size_global(width, height);
+}
+
+/// Set whether recoverable errors are reported to JavaScript (default: false)
+///
+/// When enabled, errors call `window.onError(code, message)` if the page defines it, with
+/// `code` one of "surface_lost", "surface_unavailable", "readback_failed",
+/// "unsupported_format", or "canvas_attach_failed". Unlike log output, these reports are
+/// also delivered in release builds.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_error_callback_enabled(enabled: bool) {
+    error::set_error_callback_enabled(enabled);
+}
+
 /// Set the blend color space from JavaScript
 /// 
 /// # Arguments
@@ -108,6 +154,21 @@ pub fn set_blend_color_space(is_srgb: bool) {
     window::set_blend_color_space_global(is_srgb);
 }
 
+/// Set the blend color space, converting the existing canvas so it looks the same
+///
+/// `set_blend_color_space` keeps the canvas values, so existing art visibly shifts color
+/// (or is cleared, on an 8-bit sRGB canvas). This converts the canvas to the new space in
+/// one pass instead. Opaque paint is unchanged on screen; soft translucent edges may
+/// shift slightly, since they composite over the background in the new space.
+///
+/// # Arguments
+/// * `is_srgb` - true for sRGB gamma-space blending, false for linear blending
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_blend_color_space_preserve(is_srgb: bool) {
+    window::set_blend_color_space_preserve_global(is_srgb);
+}
+
 /// Set brush size (diameter in pixels)
 #[cfg(target_arch = "wasm32")]
 #[wasm_bindgen::prelude::wasm_bindgen]
@@ -129,6 +190,19 @@ pub fn set_brush_hardness(hardness: f32) {
     window::set_brush_hardness_global(hardness);
 }
 
+/// Set the distance between brush dabs
+///
+/// # Arguments
+/// * `spacing` - Fraction of the brush diameter (0.0-1.0) in mode 0, or canvas pixels
+///   (> 0) in mode 1
+/// * `mode` - 0 = Fraction of the diameter (default, gaps scale with brush size),
+///   1 = Absolute pixels (gaps stay fixed whatever the size)
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_brush_spacing(spacing: f32, mode: u32) -> Result<(), wasm_bindgen::JsValue> {
+    window::set_brush_spacing_global(spacing, mode).map_err(|e| wasm_bindgen::JsValue::from_str(&e))
+}
+
 /// Set brush color (sRGB values 0.0-1.0)
 #[cfg(target_arch = "wasm32")]
 #[wasm_bindgen::prelude::wasm_bindgen]
@@ -136,7 +210,538 @@ pub fn set_brush_color(r: f32, g: f32, b: f32, a: f32) {
     window::set_brush_color_global(r, g, b, a);
 }
 
+/// Get the brush color (sRGB values 0.0-1.0) as `[r, g, b, a]`
+/// Changes when the eyedropper picks a color from the canvas
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn get_brush_color() -> Vec<f32> {
+    window::get_brush_color_global().to_vec()
+}
+
+/// Switch pointer input between drawing (default) and the eyedropper
+/// With the eyedropper active, pressing or dragging on the canvas draws nothing and picks
+/// the brush color from the pixel under the pointer; read it back with `get_brush_color`.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_eyedropper_active(active: bool) {
+    window::set_eyedropper_active_global(active);
+}
+
+/// Set the maximum accumulated opacity (0.0-1.0)
+/// Unlike flow, this is a hard ceiling across all passes over a region: repeated strokes
+/// build up toward this opacity and no further, and paint that is already more opaque is
+/// left as is. 1.0 disables the cap.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_max_opacity(max_opacity: f32) {
+    window::set_max_opacity_global(max_opacity);
+}
+
+/// Set the brush grain texture for dual-mask (shape x grain) rendering
+///
+/// # Arguments
+/// * `data` - 8-bit grayscale grain values (width * height bytes)
+/// * `width`, `height` - Grain texture dimensions in pixels
+/// * `scale` - Grain scale in canvas space (1.0 = one texel per canvas pixel)
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_brush_grain(data: &[u8], width: u32, height: u32, scale: f32) -> Result<(), wasm_bindgen::JsValue> {
+    window::set_brush_grain_global(data, width, height, scale)
+}
+
+/// Remove the brush grain texture
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn clear_brush_grain() {
+    window::clear_brush_grain_global();
+}
+
+/// Set the brush stamp texture (tip shape), scaled to each dab and rotated with it
+///
+/// # Arguments
+/// * `data` - 8-bit grayscale coverage values (width * height bytes, 255 = full coverage),
+///   or RGBA pixels (width * height * 4 bytes, e.g. `ImageData.data`) whose alpha is used
+///   as the mask, or their luminance if fully opaque
+/// * `width`, `height` - Stamp dimensions in pixels (at most 2048)
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_brush_texture(data: &[u8], width: u32, height: u32) -> Result<(), wasm_bindgen::JsValue> {
+    window::set_brush_texture_global(data, width, height)
+}
+
+/// Set the brush stamp texture from an encoded PNG
+/// Uses the PNG's alpha channel as the mask if present, otherwise its luminance.
+/// Rejects images larger than 2048 pixels on either side.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_brush_texture_png(data: &[u8]) -> Result<(), wasm_bindgen::JsValue> {
+    window::set_brush_texture_png_global(data)
+}
+
+/// Remove the brush stamp texture (dabs use the procedural round/polygon shape)
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn clear_brush_texture() {
+    window::clear_brush_texture_global();
+}
+
+/// Set a custom WGSL tip mask for brush dabs
+///
+/// The snippet must define `fn brush_mask(uv: vec2<f32>) -> f32`, returning coverage (0.0-1.0)
+/// at `uv`, a point in the dab's rotated frame spanning -1 to 1 across the tip. Helper functions
+/// and constants are allowed; bindings and entry points are not. The mask multiplies the
+/// round/polygon falloff (use hardness 1.0 for the mask alone).
+/// Returns the compiler error if the snippet is invalid, keeping the current brush.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_custom_brush_shader(wgsl: &str) -> Result<(), wasm_bindgen::JsValue> {
+    window::set_custom_brush_shader_global(wgsl)
+}
+
+/// Remove the custom brush mask (dabs use the built-in shader)
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn clear_custom_brush_shader() {
+    window::clear_custom_brush_shader_global();
+}
+
+/// Set whether the brush erases instead of painting (default: false)
+/// Erasing removes paint down to transparent, rather than painting over it with the paper
+/// color, so it also works on a transparent canvas and in linear blending. The stylus
+/// barrel button's momentary eraser works the same way while held.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_eraser_mode(enabled: bool) {
+    window::set_eraser_mode_global(enabled);
+}
+
+/// Set how brush dabs combine with the canvas
+///
+/// Multiply, Screen, Darken, and Lighten blend each whole stroke with the canvas beneath,
+/// in linear color whatever the blend color space set by `set_blend_color_space`.
+///
+/// # Arguments
+/// * `mode` - 0 = Normal (paint over), 1 = AddHdr (additive, colors may exceed 1.0 for glow effects),
+///   2 = Multiply, 3 = Screen, 4 = Darken, 5 = Lighten
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_brush_blend_mode(mode: u32) {
+    window::set_brush_blend_mode_global(mode);
+}
+
+/// Set the bloom intensity applied around HDR (brighter than white) pixels
+/// 0.0 disables bloom. Bloom only affects the display, not exports.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_bloom(intensity: f32) {
+    window::set_bloom_global(intensity);
+}
+
+/// Set whether 8-bit output is ordered-dithered to break up banding (default: false)
+/// Smooths soft brush edges and gradients on display and in exports, and dabs on an 8-bit canvas.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_dithering(enabled: bool) {
+    window::set_dithering_global(enabled);
+}
+
+/// Set whether the canvas is stored as 8-bit sRGB instead of 16-bit float (default: false)
+/// Dabs then round to sRGB bytes as they accumulate, matching 8-bit painting apps such as
+/// Procreate and Clip Studio exactly in sRGB blend mode. Changing it clears the canvas, as
+/// does switching the blend color space while it is on.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_srgb_canvas(enabled: bool) {
+    window::set_srgb_canvas_global(enabled);
+}
+
+/// Set how the canvas is sampled past its edges when displayed
+/// Repeat modes tile the canvas across the whole view, for previewing seamless patterns.
+/// Display only: exports are unaffected.
+///
+/// # Arguments
+/// * `mode` - 0 = Clamp (default), 1 = Repeat, 2 = MirrorRepeat
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_wrap_mode(mode: u32) {
+    window::set_wrap_mode_global(mode);
+}
+
+/// Set how brush dab edges are antialiased
+/// MSAA is unavailable on WebGL2 (no per-sample shading); there it falls back to analytic.
+/// Affects dabs drawn from now on.
+///
+/// # Arguments
+/// * `mode` - 0 = Off (default), 1 = 4x MSAA, 2 = Analytic
+///
+/// # Returns
+/// The mode actually applied, in the same numbering
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_antialiasing(mode: u32) -> u32 {
+    window::set_antialiasing_global(mode)
+}
+
+/// Set the stroke cap style (the shape of each stroke's start and end)
+///
+/// Flat and square caps hold back the last half brush width of the stroke while drawing,
+/// since the end isn't known until the pen lifts.
+///
+/// # Arguments
+/// * `kind` - 0 = Round (default), 1 = Square (extends half the brush size), 2 = Flat
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_cap_style(kind: u32) {
+    window::set_cap_style_global(kind);
+}
+
+/// Taper each stroke in and out, for calligraphic strokes
+///
+/// The stroke's size ramps up from zero over the first `taper_in` pixels and back down
+/// over the last `taper_out`. Strokes shorter than both together taper over their whole
+/// length. Like flat caps, the last `taper_out` pixels only appear when the pen lifts.
+///
+/// # Arguments
+/// * `taper_in` - Taper length at the start in canvas pixels (0 = off)
+/// * `taper_out` - Taper length at the end in canvas pixels (0 = off)
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_taper(taper_in: f32, taper_out: f32) {
+    window::set_taper_global(taper_in, taper_out);
+}
+
+/// Set the brush tip shape
+///
+/// # Arguments
+/// * `kind` - 0 = Round (default), 1 = Polygon
+/// * `sides` - Number of polygon sides (3-32, e.g. 3 = triangle, 4 = square, 6 = hexagon);
+///   ignored for Round
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_tip_shape(kind: u32, sides: u32) {
+    window::set_tip_shape_global(kind, sides);
+}
+
+/// Set whether brush spacing adapts to the view zoom (default: false)
+/// When enabled and zoomed in, dabs are placed closer together in canvas pixels so
+/// strokes stay smooth on screen. Canvas resolution is unchanged.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_zoom_aware_spacing(enabled: bool) {
+    window::set_zoom_aware_spacing_global(enabled);
+}
+
+/// Set the minimum size in pixels of the dot left by a tap (0 = off, the default)
+/// Guarantees taps make a visible mark for dotting/stippling regardless of pressure dynamics
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_min_tap_size(px: f32) {
+    window::set_min_tap_size_global(px);
+}
+
+/// Set which brush properties respond to pen pressure
+///
+/// # Arguments
+/// * `mode` - 0 = None, 1 = Flow, 2 = Size, 3 = Both (size + flow),
+///   4 = Hardness, 5 = All (size + flow + hardness)
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_pressure_mapping(mode: u32) {
+    window::set_pressure_mapping_global(mode);
+}
+
+/// Set which brush properties respond to pen speed (faster = thinner/fainter)
+/// Applies on top of pressure, so mouse input can taper through speed alone.
+///
+/// # Arguments
+/// * `mode` - 0 = None, 1 = Flow, 2 = Size, 3 = Both (size + flow)
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_velocity_mapping(mode: u32) {
+    window::set_velocity_mapping_global(mode);
+}
+
+/// Set the speed-to-size/flow curve
+/// Takes effect when a velocity mapping is set
+///
+/// # Arguments
+/// * `max_speed` - Speed in canvas pixels per second at which the effect is strongest
+/// * `min` - Size/flow fraction at or above the max speed (0.0-1.0)
+/// * `max` - Size/flow fraction when the pen is still (clamped at 1.0 per dab)
+/// * `gamma` - Curve exponent (<1.0 = thins out early, >1.0 = only fast strokes thin out)
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_velocity_curve(max_speed: f32, min: f32, max: f32, gamma: f32) {
+    window::set_velocity_curve_global(max_speed, min, max, gamma);
+}
+
+/// Set smoothing of pen speed, which evens out spikes from uneven input timing
+///
+/// # Arguments
+/// * `factor` - 0.0 = raw speed, closer to 1.0 = smoother but slower to respond (clamped below 1.0)
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_velocity_smoothing(factor: f32) {
+    window::set_velocity_smoothing_global(factor);
+}
+
+/// Set the pressure-to-hardness curve
+/// Takes effect when the pressure mapping includes hardness (Hardness or All)
+///
+/// # Arguments
+/// * `min` - Hardness fraction at zero pressure (0.0-1.0)
+/// * `max` - Hardness fraction at full pressure (clamped at 1.0 per dab)
+/// * `gamma` - Curve exponent (<1.0 = hardens early, >1.0 = hardens late)
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_pressure_hardness(min: f32, max: f32, gamma: f32) {
+    window::set_pressure_hardness_global(min, max, gamma);
+}
+
+/// Set a pressure curve through control points, shared by size, flow, and hardness
+/// Replaces the per-property gammas; the min/max ranges still apply on top of it
+///
+/// # Arguments
+/// * `points` - Flat (input, output) pairs `[in0, out0, in1, out1, ...]`: 2 to 8 points,
+///   all 0.0-1.0, in strictly ascending input order. Empty = back to the gamma curves
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_pressure_curve(points: &[f32]) -> Result<(), wasm_bindgen::JsValue> {
+    window::set_pressure_curve_global(points).map_err(|e| wasm_bindgen::JsValue::from_str(&e))
+}
+
+/// Set the strength of the "straighten when slow" line assist
+///
+/// When the pen slows down over nearly straight input, new points are pulled toward the
+/// best-fit line through recent input. Fast strokes and curves are left alone.
+///
+/// # Arguments
+/// * `strength` - 0.0 = off, 1.0 = full pull (clamped)
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_line_assist(strength: f32) {
+    window::set_line_assist_global(strength);
+}
+
+/// Set the position stabilization strength
+///
+/// The ink follows the pen through a moving average of recent positions, evening out
+/// shaky lines (especially with a finger) at the cost of some lag. The stroke still ends
+/// where the pen lifts.
+///
+/// # Arguments
+/// * `strength` - 0.0 = off, 1.0 = heavy (clamped)
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_stabilization(strength: f32) {
+    window::set_stabilization_global(strength);
+}
+
+/// Rotate each dab to a random angle instead of following the stylus
+///
+/// Breaks up the repeating pattern of textured stamps along a stroke.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_random_rotation(enabled: bool) {
+    window::set_random_rotation_global(enabled);
+}
+
+/// Keep depositing paint while the pen is held still, like an airbrush
+///
+/// Normally dabs are only placed as the pen moves. With the airbrush on, a held pen keeps
+/// adding dabs where it rests, faster the harder it presses, so paint builds up over time.
+/// Deposition stops as soon as the pen lifts.
+///
+/// # Arguments
+/// * `enabled` - Whether the airbrush is on
+/// * `rate` - Dabs per second at full pressure (clamped to 1-1000)
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_airbrush(enabled: bool, rate: f32) {
+    window::set_airbrush_global(enabled, rate);
+}
+
+/// Vary each dab's color slightly around the brush color, like pencils and pastels
+///
+/// The variation repeats exactly when a stroke is replayed. All zero (the default) is off.
+///
+/// # Arguments
+/// * `hue` - How far the hue may stray (0.0-1.0, 1.0 = anywhere on the color wheel)
+/// * `saturation` - How far the HSV saturation may stray (0.0-1.0)
+/// * `value` - How far the HSV value may stray (0.0-1.0)
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_color_jitter(hue: f32, saturation: f32, value: f32) {
+    window::set_color_jitter_global(hue, saturation, value);
+}
+
+/// Lock each stroke's direction to the nearest multiple of an angle from its start
+///
+/// Useful for hatching and technical lines: unlike a straight-line tool, pressure and
+/// spacing still follow the pen. Call with the step while a modifier is held (or as a
+/// toggle) and with 0 to release it; the axis is picked afresh for each stroke.
+///
+/// # Arguments
+/// * `degrees` - Angle step, e.g. 45.0 for 0/45/90 degrees; 0.0 = unconstrained (clamped to 0-180)
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_angle_constraint(degrees: f32) {
+    window::set_angle_constraint_global(degrees);
+}
+
+/// Set the stroke length after which a long stroke is split into a new logical segment
+///
+/// Bounds the points retained for very long continuous strokes. The split is invisible:
+/// drawing continues without a gap or opacity change.
+///
+/// # Arguments
+/// * `px` - Length in canvas pixels; 0.0 = unlimited
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_max_stroke_length(px: f32) {
+    window::set_max_stroke_length_global(px);
+}
+
+/// Set smoothing of stylus pressure, independent of position smoothing
+///
+/// # Arguments
+/// * `factor` - 0.0 = raw pressure, closer to 1.0 = smoother but laggier (clamped below 1.0)
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_pressure_smoothing(factor: f32) {
+    window::set_pressure_smoothing_global(factor);
+}
+
+/// Set smoothing of stylus azimuth/twist before they drive dab rotation
+///
+/// # Arguments
+/// * `factor` - 0.0 = raw angles, closer to 1.0 = smoother but laggier (clamped below 1.0)
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_angle_smoothing(factor: f32) {
+    window::set_angle_smoothing_global(factor);
+}
+
+/// Set how dab coverage combines into the canvas
+///
+/// # Arguments
+/// * `per_stroke` - true for PerStroke (each stroke composited once at max opacity),
+///   false for Accumulate (dabs blend straight into the canvas)
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_opacity_mode(per_stroke: bool) {
+    window::set_opacity_mode_global(per_stroke);
+}
+
+/// Set how opacity builds up within a stroke
+///
+/// In Uniform mode dabs are drawn at full flow and the whole stroke is composited once at
+/// flow × max opacity, so overlapping dabs of a low-flow stroke don't darken it.
+///
+/// # Arguments
+/// * `mode` - 0 = Accumulate (overlaps build up, default), 1 = PerStroke (overlaps build
+///   up to the max opacity), 2 = Uniform
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_stroke_opacity_mode(mode: u32) {
+    window::set_stroke_opacity_mode_global(mode);
+}
+
+/// Set whether each new stroke starts with fresh coverage in PerStroke opacity mode
+/// When false, restarting a stroke over the previous one shares its coverage instead of darkening it
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_restart_resets_coverage(enabled: bool) {
+    window::set_restart_resets_coverage_global(enabled);
+}
+
+/// Set how the pressure of the first dab in a stroke is chosen
+///
+/// # Arguments
+/// * `mode` - 0 = FirstMove, 1 = MaxOfFirstSamples, 2 = Threshold
+/// * `value` - Sample count for MaxOfFirstSamples, pressure threshold (0.0-1.0) for Threshold; ignored for FirstMove
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_first_dab_pressure_mode(mode: u32, value: f32) {
+    window::set_first_dab_pressure_mode_global(mode, value);
+}
+
+/// Set whether the first dab of a stroke is placed on Down or waits for the pen to move
+/// Placing it on Down uses the Down pressure, so a pure tap always stamps a mark.
+///
+/// # Arguments
+/// * `mode` - 0 = Auto (default: on Down when spacing is at least half the diameter),
+///   1 = Instant (always on Down), 2 = Deferred (always wait for movement)
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_down_dab_mode(mode: u32) {
+    window::set_down_dab_mode_global(mode);
+}
+
+/// Apply a built-in brush preset by name (case-insensitive)
+/// Keeps the current brush color
+/// Returns false if the preset name is unknown
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn apply_brush_preset(name: &str) -> bool {
+    window::apply_brush_preset_global(name)
+}
+
+/// List the names of the built-in brush presets
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn list_brush_presets() -> Vec<String> {
+    BrushParams::list_presets().into_iter().map(String::from).collect()
+}
+
+/// Export every editor setting (brush, blend space, canvas size, view, clear color, display options) as JSON
+/// Canvas pixels and brush textures are not included; pair with a canvas export for a full session.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn export_config() -> Result<String, wasm_bindgen::JsValue> {
+    window::export_config_global().map_err(|e| wasm_bindgen::JsValue::from_str(&e))
+}
+
+/// Restore every editor setting from JSON produced by `export_config`
+/// Settings missing from the JSON are reset to their defaults. Returns an error, leaving
+/// the current settings unchanged, if the JSON is malformed or a value is out of range.
+/// A different canvas bleed or format clears the canvas.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn import_config(json: &str) -> Result<(), wasm_bindgen::JsValue> {
+    window::import_config_global(json).map_err(|e| wasm_bindgen::JsValue::from_str(&e))
+}
+
+/// Export the current brush settings as JSON, for saving as a user preset
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn export_brush() -> String {
+    window::export_brush_global()
+}
+
+/// Replace the brush settings with JSON produced by `export_brush`
+/// Out-of-range values are repaired rather than rejected. Returns an error, leaving the
+/// brush unchanged, only if the JSON is malformed.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn import_brush(json: &str) -> Result<(), wasm_bindgen::JsValue> {
+    window::import_brush_global(json).map_err(|e| wasm_bindgen::JsValue::from_str(&e))
+}
+
+/// Set what the stylus barrel (side) button does while held
+///
+/// # Arguments
+/// * `action` - 0 = Erase (momentary eraser), 1 = None, 2 = Undo
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_barrel_button_action(action: u32) {
+    window::set_barrel_button_action_global(action);
+}
+
 /// Set input filter mode
+///
+/// In pen-only mode touches never reach the brush, so a finger resting on the screen
+/// can't interrupt a pen stroke; two-finger pan/zoom works in either mode.
 /// 
 /// # Arguments
 /// * `pen_only` - true for pen-only mode, false for pen+touch mode
@@ -146,38 +751,712 @@ pub fn set_input_filter_mode(pen_only: bool) {
     window::set_input_filter_mode_global(pen_only);
 }
 
-/// Clear the canvas to the current clear color
+/// Draw a stroke along a path, played back in real time as if drawn with the pointer
+/// For switch/assistive input and scripted demos; the stroke goes through the normal
+/// input and brush pipeline. Strokes drawn while one is still playing queue up behind it.
+///
+/// # Arguments
+/// * `path` - Canvas-space points, flattened as [x0, y0, x1, y1, ...] (one point draws a tap)
+/// * `pressure` - Constant pressure (0.0-1.0)
+/// * `speed` - Canvas pixels per second (default 600)
 #[cfg(target_arch = "wasm32")]
 #[wasm_bindgen::prelude::wasm_bindgen]
-pub fn clear_canvas() {
-    window::clear_canvas_global();
+pub fn draw_synthetic_stroke(path: &[f32], pressure: f32, speed: Option<f32>) -> Result<(), wasm_bindgen::JsValue> {
+    window::draw_synthetic_stroke_global(path, pressure, speed)
+        .map_err(|e| wasm_bindgen::JsValue::from_str(&e))
 }
 
-/// Get canvas width in pixels
+/// Start recording every pointer event (with timing, pressure, tilt, and source)
+/// Starting again discards the recording in progress. A stroke already being drawn is skipped.
 #[cfg(target_arch = "wasm32")]
 #[wasm_bindgen::prelude::wasm_bindgen]
-pub fn get_canvas_width() -> u32 {
-    window::get_canvas_width_global()
+pub fn start_recording() {
+    window::start_recording_global();
 }
 
-/// Get canvas height in pixels
+/// Stop recording and get the recorded session as versioned JSON
+/// A stroke still being drawn is ended at the pointer's last position.
 #[cfg(target_arch = "wasm32")]
 #[wasm_bindgen::prelude::wasm_bindgen]
-pub fn get_canvas_height() -> u32 {
-    window::get_canvas_height_global()
+pub fn stop_recording() -> Result<String, wasm_bindgen::JsValue> {
+    window::stop_recording_global().map_err(|e| wasm_bindgen::JsValue::from_str(&e))
 }
 
-/// Export canvas as RGBA8 image data
-/// Returns a Uint8ClampedArray containing RGBA pixel data (width * height * 4 bytes)
+/// Play a recording from `stop_recording` back through the normal input pipeline
+/// Events are released at their recorded times, scaled by `speed` (default 1.0; 2.0 = twice
+/// as fast), and queue up behind any playback or synthetic stroke still running.
 #[cfg(target_arch = "wasm32")]
 #[wasm_bindgen::prelude::wasm_bindgen]
-pub async fn get_canvas_image_data() -> Result<js_sys::Uint8ClampedArray, wasm_bindgen::JsValue> {
+pub fn play_recording(json: &str, speed: Option<f64>) -> Result<(), wasm_bindgen::JsValue> {
+    window::play_recording_global(json, speed.unwrap_or(1.0)).map_err(|e| wasm_bindgen::JsValue::from_str(&e))
+}
+
+/// Start a timed gesture-drawing session, replacing any session in progress
+///
+/// The poses run back to back from now. When one ends, the next starts and
+/// `window.onPoseAdvance(index)` is called if the page defines it; after the last pose it is
+/// called with the pose count and the session ends. Frames keep coming at least once a
+/// second while a session runs, so a countdown built on `get_session_remaining_ms` stays current.
+///
+/// # Arguments
+/// * `durations` - Duration of each pose in seconds, e.g. [30, 60, 300]
+/// * `auto_clear` - Whether the canvas is cleared when each following pose starts
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn start_session(durations: &[f32], auto_clear: bool) -> Result<(), wasm_bindgen::JsValue> {
+    window::start_session_global(durations, auto_clear).map_err(|e| wasm_bindgen::JsValue::from_str(&e))
+}
+
+/// Stop the session in progress without finishing it (`onPoseAdvance` is not called)
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn stop_session() {
+    window::stop_session_global();
+}
+
+/// Get the index of the current pose, or -1 when no session is running
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn get_session_pose() -> i32 {
+    window::get_session_pose_global()
+}
+
+/// Get the time left in the current pose in milliseconds (0 when no session is running)
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn get_session_remaining_ms() -> f64 {
+    window::get_session_remaining_ms_global()
+}
+
+/// Draw mirrored or radially repeated copies of every dab
+///
+/// # Arguments
+/// * `mode` - 0 = None (default), 1 = Vertical mirror (left/right), 2 = Horizontal mirror
+///   (top/bottom), 3 = Radial
+/// * `folds` - Number of radial copies including the original (2-64); ignored otherwise
+/// * `x`, `y` - Canvas-space point the mirror line passes through, or the radial center;
+///   it stays fixed to the drawing as the view pans and zooms
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_symmetry(mode: u32, folds: u32, x: f32, y: f32) -> Result<(), wasm_bindgen::JsValue> {
+    window::set_symmetry_global(mode, folds, x, y).map_err(|e| wasm_bindgen::JsValue::from_str(&e))
+}
+
+/// Choose what strokes do
+///
+/// # Arguments
+/// * `kind` - 0 = Brush (default, paints), 1 = Smudge (drags existing paint along the stroke)
+/// * `strength` - Smudge only: fraction of the carried color each dab lays down (0.0-1.0);
+///   ignored for the brush. How far the color is carried is set with `set_smudge_length`
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_tool(kind: u32, strength: f32) -> Result<(), wasm_bindgen::JsValue> {
+    window::set_tool_global(kind, strength).map_err(|e| wasm_bindgen::JsValue::from_str(&e))
+}
+
+/// Set how far the smudge tool drags color along a stroke
+///
+/// Each smudge dab keeps this fraction of the color it carries and picks up the rest from
+/// the canvas under it. At 1.0 one color is dragged along the whole stroke; at 0.0 color
+/// barely moves. Defaults to 0.9.
+///
+/// # Arguments
+/// * `length` - Fraction of the carried color kept per dab (clamped to 0.0-1.0)
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_smudge_length(length: f32) {
+    window::set_smudge_length_global(length);
+}
+
+/// Get the raw input points of the last completed stroke, for gesture analysis
+/// Returns an array of `{ x, y, pressure, tilt_x, tilt_y, azimuth, twist, timestamp }` objects
+/// in canvas pixels and milliseconds; tablet fields the device doesn't report are null.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn get_last_stroke_points() -> wasm_bindgen::JsValue {
+    window::get_last_stroke_points_global()
+}
+
+/// Set how many completed strokes are retained for vector export/replay (default 0 = off)
+/// Past the cap the oldest strokes are dropped from the list but stay on the canvas exactly
+/// as drawn (strokes are always rasterized as they're drawn). Clearing the canvas drops them all.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_max_retained_strokes(max: u32) {
+    window::set_max_retained_strokes_global(max);
+}
+
+/// Get the retained strokes, oldest first
+/// Returns an array of strokes, each an array of points in the `get_last_stroke_points` format.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn get_retained_strokes() -> wasm_bindgen::JsValue {
+    window::get_retained_strokes_global()
+}
+
+/// Set whether the dabs rendered each frame are kept for `get_last_frame_dabs`
+/// Off by default; enable only for debugging or tests, as it copies every dab.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_retain_frame_dabs(retain: bool) {
+    window::set_retain_frame_dabs_global(retain);
+}
+
+/// Get the dabs rendered by the last frame that processed input, in render order
+/// Returns an array of `{ x, y, size, opacity, hardness, angle }` objects in canvas pixels
+/// and radians. Empty unless enabled with `set_retain_frame_dabs`.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn get_last_frame_dabs() -> wasm_bindgen::JsValue {
+    window::get_last_frame_dabs_global()
+}
+
+/// Pause or resume rendering (e.g. when the canvas is offscreen or the tab is hidden)
+/// While paused no GPU work is done and no redraws are requested; resuming redraws the canvas.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_rendering_paused(paused: bool) {
+    window::set_rendering_paused_global(paused);
+}
+
+/// Set whether new strokes are dropped while rendering is paused
+/// When false (default), input is queued and drawn on resume
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_drop_input_while_paused(drop: bool) {
+    window::set_drop_input_while_paused_global(drop);
+}
+
+/// Resample pointer moves to a fixed rate before dab generation
+/// Position and pressure are interpolated between the device's samples, so strokes
+/// feel the same regardless of the stylus report rate.
+///
+/// # Arguments
+/// * `hz` - Events per second (0 = disabled, use the raw events)
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_input_resample_rate(hz: f32) {
+    window::set_input_resample_rate_global(hz);
+}
+
+/// Set input prediction, which extends the stroke on screen a little past the pen to
+/// hide input latency
+///
+/// The predicted part follows the pen's recent velocity, is redrawn every frame, and is
+/// replaced by the real stroke as samples arrive; it never becomes part of the canvas.
+/// It reaches no further than the pen moved recently, so strokes don't overshoot at the end.
+///
+/// # Arguments
+/// * `enabled` - Whether to predict
+/// * `ms_ahead` - How far ahead to predict in milliseconds (clamped to 0-50)
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_prediction(enabled: bool, ms_ahead: f32) {
+    window::set_prediction_global(enabled, ms_ahead);
+}
+
+/// Show or hide a brush outline that follows the pointer while hovering
+///
+/// The outline is a ring at the brush size with a fainter inner ring where a soft edge
+/// starts fading, so the scale can be judged before drawing. It follows the view, is
+/// hidden while drawing and once the pointer leaves, and is never part of the canvas.
+///
+/// # Arguments
+/// * `enabled` - Whether to show the outline (off by default)
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_cursor_preview(enabled: bool) {
+    window::set_cursor_preview_global(enabled);
+}
+
+/// Show or hide a line from the pen to the point the ink follows while stabilizing
+///
+/// With strong stabilization (see `set_stabilization`) the ink trails the pen; the line
+/// shows the lag so the smoothing is easier to control. It follows the view, is only
+/// shown during strokes, and is never part of the canvas.
+///
+/// # Arguments
+/// * `enabled` - Whether to show the line (off by default)
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_stabilizer_preview(enabled: bool) {
+    window::set_stabilizer_preview_global(enabled);
+}
+
+/// Set a 2D affine transform applied to pointer positions before they reach the canvas
+///
+/// Uses the CSS `matrix(a, b, c, d, e, f)` convention: `x' = a*x + c*y + e`, `y' = b*x + d*y + f`,
+/// in physical pixels. Use this to correct for container transforms (CSS scale/rotate) that
+/// winit can't see. Pass (1, 0, 0, 1, 0, 0) to reset to identity.
+/// Returns an error if the matrix is not invertible.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_input_transform(a: f32, b: f32, c: f32, d: f32, e: f32, f: f32) -> Result<(), wasm_bindgen::JsValue> {
+    window::set_input_transform_global(a, b, c, d, e, f)
+        .map_err(|e| wasm_bindgen::JsValue::from_str(&e))
+}
+
+/// Set the surface background color seen behind a transparent canvas and around a letterboxed one
+/// RGB components in sRGB, 0.0-1.0 (default black). Display only: not the canvas clear color
+/// or paper, and not included in exports.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_surface_background(r: f32, g: f32, b: f32) {
+    window::set_surface_background_global(r, g, b);
+}
+
+/// Set the color the canvas is cleared to (sRGB RGBA with straight alpha, 0.0-1.0)
+///
+/// Takes effect at the next `clear_canvas`. The clear color is part of the canvas and so of
+/// `get_canvas_image_data`; clear with alpha < 1 to export transparency, and use
+/// `set_paper_color` for how the transparent canvas looks on screen.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_clear_color(r: f64, g: f64, b: f64, a: f64) {
+    window::set_clear_color_global(r, g, b, a);
+}
+
+/// Set the paper color shown beneath the canvas on screen (sRGB RGBA, 0.0-1.0)
+///
+/// Display only: exports keep the canvas's own transparency. Alpha blends the paper over
+/// the surface background (0 = no paper, the default).
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_paper_color(r: f32, g: f32, b: f32, a: f32) {
+    window::set_paper_color_global(r, g, b, a);
+}
+
+/// Load a reference image (e.g. a pose photo) shown beneath the drawing
+///
+/// The image is fit inside the canvas keeping its aspect ratio, and pans, zooms, and
+/// rotates with the view. Display only: `get_canvas_image_data` and the other exports
+/// contain just the strokes.
+///
+/// # Arguments
+/// * `data` - sRGB RGBA pixels with straight alpha (width * height * 4 bytes, e.g. `ImageData.data`)
+/// * `width`, `height` - Image dimensions in pixels
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_reference_image(data: &[u8], width: u32, height: u32) -> Result<(), wasm_bindgen::JsValue> {
+    window::set_reference_image_global(data, width, height)
+}
+
+/// Remove the reference image
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn clear_reference_image() {
+    window::clear_reference_image_global();
+}
+
+/// Set the reference image opacity (0.0-1.0, default 1.0)
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_reference_opacity(opacity: f32) {
+    window::set_reference_opacity_global(opacity);
+}
+
+/// Show or hide the reference image without unloading it (shown by default)
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_reference_visible(visible: bool) {
+    window::set_reference_visible_global(visible);
+}
+
+/// Set the canvas bleed margin in pixels
+///
+/// The canvas is allocated this much larger than the visible area on every side, so dabs
+/// that overhang the edge are painted rather than clipped. Only the visible area is shown.
+/// Changing the bleed reallocates the canvas and clears it.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_canvas_bleed(px: u32) {
+    window::set_canvas_bleed_global(px);
+}
+
+/// Get the bleed margin actually allocated, in pixels
+/// May be smaller than requested if the canvas would exceed the max texture size
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn get_canvas_bleed() -> u32 {
+    window::get_canvas_bleed_global()
+}
+
+/// Give the canvas a fixed resolution, independent of the window size
+///
+/// The canvas keeps this size while the window resizes, and the view (zoom and pan) maps it
+/// onto the window, so a large document can be drawn in a small viewport at full resolution.
+/// Existing content is kept, anchored at the top-left. The size is clamped to the GPU's max
+/// texture size; 0 for either dimension makes the canvas follow the window size again.
+///
+/// # Returns
+/// The canvas size actually applied, as `[width, height]`
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_canvas_resolution(width: u32, height: u32) -> Vec<u32> {
+    window::set_canvas_resolution_global(width, height).to_vec()
+}
+
+/// Get a platform handle to the canvas texture for zero-copy display, if supported
+///
+/// Always `undefined` in the browser: WebGL textures belong to the context that created
+/// them and cannot be imported into another WebGL or WebGPU context, so live mirroring
+/// has to go through the image data readback. Native embedders can use
+/// `Renderer::canvas_texture_handle` on backends that support it.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn get_canvas_texture_handle() -> Option<u32> {
+    window::get_canvas_texture_handle_global()
+}
+
+/// Set whether image data and PNG exports include the bleed margin (default: false)
+/// When included, exports are `width + 2 * bleed` by `height + 2 * bleed` pixels
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_export_include_bleed(include: bool) {
+    window::set_export_include_bleed_global(include);
+}
+
+/// Set whether 16-bit PNG exports tone-map values above 1.0 (default: false, clamp)
+/// HDR values come from additive dabs. Tone mapping rolls them off smoothly toward 1.0 so
+/// bright areas keep their gradations; values below 0.8 are exported unchanged either way.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_export_tone_mapping(enabled: bool) {
+    window::set_export_tone_mapping_global(enabled);
+}
+
+/// Show a gradient beneath the canvas content (display only unless exports include it)
+///
+/// The gradient is defined in canvas space, so it pans, zooms, and rotates with the view.
+///
+/// # Arguments
+/// * `colors` - sRGB RGBA per stop, 0.0-1.0 (4 values per stop)
+/// * `positions` - Stop positions 0.0-1.0 in ascending order (2-8 stops)
+/// * `kind` - 0 = none (removes the gradient), 1 = linear (top to bottom),
+///   2 = radial (center out to the corners)
+/// * `interp_space` - Color space stops are blended in: 0 = sRGB (like CSS, the default),
+///   1 = linear (fades through gray between saturated colors), 2 = OKLab (even, vivid blends)
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_background_gradient(colors: &[f32], positions: &[f32], kind: u32, interp_space: Option<u32>) -> Result<(), wasm_bindgen::JsValue> {
+    window::set_background_gradient_global(colors, positions, kind, interp_space)
+}
+
+/// Set whether image data and PNG exports include the background gradient (default: false)
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_export_include_background(include: bool) {
+    window::set_export_include_background_global(include);
+}
+
+/// Set whether the canvas compensates for non-square displayed pixels (default: true)
+///
+/// Layouts that scale the canvas container non-uniformly stretch its pixels, turning
+/// round dabs into ellipses. When enabled, the view is corrected so the canvas keeps
+/// square pixels on screen. Call again after changing a container transform to re-measure.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_keep_square_pixels(enabled: bool) {
+    window::set_keep_square_pixels_global(enabled);
+}
+
+/// Set whether pointer moves are expanded into the browser's coalesced samples (default: true)
+///
+/// Browsers deliver at most one pointermove per animation frame; high-rate styluses report
+/// the samples in between through `getCoalescedEvents()`, each with its own position,
+/// pressure, and timestamp. Using them keeps fast strokes smooth instead of faceted.
+/// Disable to draw from the per-frame events only.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_coalesced_events(enabled: bool) {
+    window::set_coalesced_events_global(enabled);
+}
+
+/// Set the canvas view rotation in degrees (clockwise)
+/// Snaps to the nearest rotation snap increment when close to it (see `set_rotation_snap`)
+/// Returns the rotation actually applied, in degrees
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_canvas_rotation(degrees: f32) -> f32 {
+    window::set_canvas_rotation_global(degrees)
+}
+
+/// Get the current canvas view rotation in degrees
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn get_canvas_rotation() -> f32 {
+    window::get_canvas_rotation_global()
+}
+
+/// Set the canvas view zoom and pan
+///
+/// Brush sizes stay in canvas pixels, so zooming in makes the brush look larger on screen.
+/// Pointer input is mapped back through the view, so strokes land under the pen.
+///
+/// # Arguments
+/// * `scale` - Surface pixels per canvas pixel (clamped to 0.1-32.0)
+/// * `tx`, `ty` - Offset of the canvas center from the surface center, in physical pixels
+///
+/// # Returns
+/// The zoom actually applied
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_view_transform(scale: f32, tx: f32, ty: f32) -> f32 {
+    window::set_view_transform_global(scale, tx, ty)
+}
+
+/// Convert a surface position (physical pixels) to canvas pixels through the current view
+/// Useful for zooming about the cursor; returns `[x, y]`
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn surface_to_canvas(x: f32, y: f32) -> Vec<f32> {
+    window::surface_to_canvas_global(x, y).to_vec()
+}
+
+/// Set the rotation snap increment in degrees (e.g. 15.0); 0.0 disables snapping
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_rotation_snap(degrees: f32) {
+    window::set_rotation_snap_global(degrees);
+}
+
+/// Clear the canvas to the current clear color
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn clear_canvas() {
+    window::clear_canvas_global();
+}
+
+/// Undo the last stroke
+/// Returns false if there is nothing to undo or a stroke is in progress.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn undo() -> bool {
+    window::undo_global()
+}
+
+/// Redo the last undone stroke
+/// Returns false if there is nothing to redo or a stroke is in progress.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn redo() -> bool {
+    window::redo_global()
+}
+
+/// Set how many strokes can be undone (default 50, 0 = undo off)
+/// Older strokes stay on the canvas but can no longer be undone. Clearing the canvas (or
+/// changing the bleed, which clears it) drops the undo history.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_undo_limit(limit: u32) {
+    window::set_undo_limit_global(limit);
+}
+
+/// Add a transparent layer on top of the others and return its index (0 = bottom)
+/// The active layer doesn't change. Each layer costs as much GPU memory as the canvas.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn add_layer() -> Result<u32, wasm_bindgen::JsValue> {
+    window::add_layer_global()
+}
+
+/// Remove a layer and its content
+/// The last remaining layer can't be removed. Removing the active layer makes the layer
+/// beneath it active. Strokes drawn on the removed layer leave the undo history.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn remove_layer(index: u32) -> Result<(), wasm_bindgen::JsValue> {
+    window::remove_layer_global(index)
+}
+
+/// Make a layer the one strokes are drawn into (0 = bottom)
+/// The undo history is kept: undo steps back through strokes on every layer.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_active_layer(index: u32) -> Result<(), wasm_bindgen::JsValue> {
+    window::set_active_layer_global(index)
+}
+
+/// Show or hide a layer (hidden layers can still be drawn into)
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_layer_visible(index: u32, visible: bool) -> Result<(), wasm_bindgen::JsValue> {
+    window::set_layer_visible_global(index, visible)
+}
+
+/// Set the opacity a layer is composited at (0.0-1.0); errors on NaN or an infinite opacity
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn set_layer_opacity(index: u32, opacity: f32) -> Result<(), wasm_bindgen::JsValue> {
+    window::set_layer_opacity_global(index, opacity)
+}
+
+/// Check whether the canvas is empty (nothing drawn since the last clear)
+/// Useful for disabling export/save on a blank canvas. Erasing strokes back to
+/// nothing does not make the canvas report empty again; only clearing does.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn is_canvas_empty() -> bool {
+    window::is_canvas_empty_global()
+}
+
+/// Get the dab overdraw ratio since the last clear (total dab area / canvas area)
+/// Useful for tuning spacing: high values mean the brush is shading pixels many times over
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn get_overdraw_ratio() -> f64 {
+    window::get_overdraw_ratio_global()
+}
+
+/// Get how many times the dab instance buffer has been reallocated
+/// Dab uploads reuse one growable buffer; this should stay flat during a continuous stroke
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn get_dab_buffer_reallocations() -> u32 {
+    window::get_dab_buffer_reallocations_global()
+}
+
+/// Get how many queue submits the last frame took (debug builds only)
+/// Brush work is batched into the frame's blit submit, so this should be 1 while drawing
+#[cfg(all(target_arch = "wasm32", debug_assertions))]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn get_frame_submits() -> u32 {
+    window::get_frame_submits_global()
+}
+
+/// Get the fraction of the canvas the last frame had to update (0.0-1.0)
+/// Bounding box of what was drawn since the frame before; 0.0 when nothing changed
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn get_dirty_area_fraction() -> f32 {
+    window::get_dirty_area_fraction_global()
+}
+
+/// Reset the overdraw statistics without clearing the canvas
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn reset_overdraw_stats() {
+    window::reset_overdraw_stats_global();
+}
+
+/// Get canvas width in pixels
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn get_canvas_width() -> u32 {
+    window::get_canvas_width_global()
+}
+
+/// Get canvas height in pixels
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn get_canvas_height() -> u32 {
+    window::get_canvas_height_global()
+}
+
+/// Get the smoothed frames-per-second (for debug HUDs)
+/// Only updates while frames are being presented; returns 0.0 before the first frames
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn get_fps() -> f32 {
+    window::get_fps_global()
+}
+
+/// Get the estimated GPU memory used by the canvas and related textures, in bytes
+/// Returned as a JS number (f64) rather than a BigInt; exact up to 2^53 bytes
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn get_vram_usage() -> f64 {
+    window::get_vram_usage_global() as f64
+}
+
+/// Get the GPU adapter and backend the renderer runs on, for diagnostics
+/// Returns a `{ name, backend, device_type, is_fallback }` object, or null before the
+/// renderer is created. `is_fallback` is true on a software rasterizer.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn get_renderer_info() -> wasm_bindgen::JsValue {
+    window::get_renderer_info_global()
+}
+
+/// Export canvas as RGBA8 image data
+/// Returns a Uint8ClampedArray containing RGBA pixel data (width * height * 4 bytes)
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub async fn get_canvas_image_data() -> Result<js_sys::Uint8ClampedArray, wasm_bindgen::JsValue> {
     window::get_canvas_image_data_global().await
 }
 
-// Future: FFI exports for Flutter integration
-// #[no_mangle]
-// pub extern "C" fn drawing_canvas_create() -> *mut App { ... }
-// #[no_mangle]
-// pub extern "C" fn drawing_canvas_render(app: *mut App) { ... }
-// etc.
+/// Bucket fill the region around a canvas position with the brush color
+///
+/// The region is every pixel of the active layer connected to the one at (`x`, `y`) whose
+/// channels are all within `tolerance` (0.0-1.0) of it. The fill blends over the existing
+/// paint in the current blend color space and can be undone like a stroke. The layer is
+/// read back to find the region, so this resolves once the GPU has caught up.
+///
+/// Resolves to whether anything was filled (false outside the canvas)
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub async fn fill_at(x: f32, y: f32, tolerance: f32) -> Result<bool, wasm_bindgen::JsValue> {
+    window::fill_at_global(x, y, tolerance).await
+}
+
+/// Pick the brush color from the canvas pixel at (`x`, `y`) in canvas pixels
+/// (see `surface_to_canvas` for pointer positions)
+///
+/// The brush takes the pixel's RGB and keeps its own alpha. Resolves to the sampled color
+/// as straight sRGB `[r, g, b, a]` (transparent black for an empty pixel), for updating a
+/// color swatch; rejects if the position is outside the canvas.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub async fn pick_color(x: f32, y: f32) -> Result<Vec<f32>, wasm_bindgen::JsValue> {
+    window::pick_color_global(x, y).await
+}
+
+/// Export canvas as a PNG file
+/// The PNG carries color space chunks matching the current blend mode:
+/// `sRGB` in sRGB blend mode, linear `gAMA`/`cHRM` in linear blend mode
+/// Returns a Uint8Array containing the encoded PNG bytes
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub async fn export_canvas_png() -> Result<js_sys::Uint8Array, wasm_bindgen::JsValue> {
+    window::export_canvas_png_global().await
+}
+
+/// Export canvas as a 16-bit-per-channel PNG file, keeping the float canvas's full depth
+/// Tagged with the same color space chunks as `export_canvas_png`, for round-tripping
+/// into an external editor. The canvas stores premultiplied alpha; the PNG gets straight
+/// alpha, unpremultiplied at 16 bits so faint translucent color keeps its precision.
+/// Values above 1.0 are clamped or tone-mapped (see `set_export_tone_mapping`).
+/// Returns a Uint8Array containing the encoded PNG bytes
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub async fn get_canvas_png16() -> Result<js_sys::Uint8Array, wasm_bindgen::JsValue> {
+    window::export_canvas_png16_global().await
+}
+
+/// Wait until all submitted GPU work has completed
+/// Await this before handing canvas contents to another API for deterministic timing
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub async fn wait_gpu_idle() -> Result<(), wasm_bindgen::JsValue> {
+    window::wait_gpu_idle_global().await
+}
+
+/// Composite the canvas against a reference image for before/after comparison
+///
+/// # Arguments
+/// * `reference` - Reference image as sRGB RGBA8 data (width * height * 4 bytes), stretched to the canvas size
+/// * `width`, `height` - Reference image dimensions
+/// * `mode` - 0 = Difference (highlights mismatches), 1 = Overlay (drawing over reference)
+///
+/// Returns a Uint8ClampedArray of RGBA pixels at the canvas size, encoded like `get_canvas_image_data`
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub async fn compare_with_reference(
+    reference: Vec<u8>,
+    width: u32,
+    height: u32,
+    mode: u32,
+) -> Result<js_sys::Uint8ClampedArray, wasm_bindgen::JsValue> {
+    window::compare_with_reference_global(reference, width, height, mode).await
+}
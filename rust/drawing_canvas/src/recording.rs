@@ -0,0 +1,331 @@
+//! Input Session Recording
+//!
+//! Captures every pointer event of a drawing session (position, pressure, tilt, azimuth,
+//! twist, and source, with timing) so it can be saved as JSON and played back later
+//! through the normal input pipeline, e.g. to replay a lesson exactly as it was drawn.
+//!
+//! Unlike `StrokeRecorder`, which keeps recent strokes' points for analysis and vector
+//! export, this records the raw event stream of a whole session, including the timing
+//! between strokes.
+
+use serde::{Deserialize, Serialize};
+
+use crate::input::{PointerEvent, PointerEventSource, PointerEventType};
+
+/// Version written into saved recordings; recordings from newer versions are rejected
+pub const RECORDING_VERSION: u32 = 1;
+
+/// Most events a recording keeps (about 70 minutes of continuous 120 Hz stylus input)
+pub const MAX_RECORDED_EVENTS: usize = 500_000;
+
+/// A pointer event as stored in a recording
+///
+/// Optional fields missing from a saved event take their defaults, so recordings written
+/// before a field existed still load.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RecordedEvent {
+    /// Milliseconds since the first recorded event
+    pub time: f64,
+    /// Position in canvas space (pixels from top-left)
+    pub position: [f32; 2],
+    /// Pressure value (0.0-1.0)
+    #[serde(default = "full_pressure")]
+    pub pressure: f32,
+    /// Tilt angles (x and y in degrees), if available
+    #[serde(default)]
+    pub tilt: Option<[f32; 2]>,
+    /// Azimuth angle in radians, if available
+    #[serde(default)]
+    pub azimuth: Option<f32>,
+    /// Barrel rotation (twist) in degrees, if available
+    #[serde(default)]
+    pub twist: Option<f32>,
+    /// Type of event (down, move, up)
+    pub event_type: PointerEventType,
+    /// Source of the event
+    #[serde(default = "unknown_source")]
+    pub source: PointerEventSource,
+}
+
+fn full_pressure() -> f32 {
+    1.0
+}
+
+fn unknown_source() -> PointerEventSource {
+    PointerEventSource::Unknown
+}
+
+impl RecordedEvent {
+    /// Convert back into a pointer event at the given timestamp
+    pub fn to_pointer_event(&self, timestamp: f64) -> PointerEvent {
+        PointerEvent {
+            position: self.position,
+            pressure: self.pressure,
+            tilt: self.tilt,
+            azimuth: self.azimuth,
+            twist: self.twist,
+            timestamp,
+            event_type: self.event_type,
+            source: self.source,
+        }
+    }
+}
+
+/// A recorded input session, serializable to JSON
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InputRecording {
+    /// Format version of the recording
+    pub version: u32,
+    /// Events in the order they were received
+    pub events: Vec<RecordedEvent>,
+}
+
+impl InputRecording {
+    /// Serialize to JSON
+    pub fn to_json(&self) -> String {
+        // Plain data with string keys: serialization cannot fail
+        serde_json::to_string(self).expect("Input recording is always serializable")
+    }
+
+    /// Parse and validate a recording from JSON
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let recording: Self = serde_json::from_str(json).map_err(|e| format!("Invalid recording JSON: {}", e))?;
+        recording.validate()?;
+        Ok(recording)
+    }
+
+    /// Validate the version and that every event is playable
+    pub fn validate(&self) -> Result<(), String> {
+        if self.version > RECORDING_VERSION {
+            return Err(format!(
+                "Recording version {} is newer than the supported version {}",
+                self.version, RECORDING_VERSION
+            ));
+        }
+        if self.events.len() > MAX_RECORDED_EVENTS {
+            return Err(format!("Recording has more than {} events", MAX_RECORDED_EVENTS));
+        }
+        let mut previous_time = 0.0;
+        for (i, event) in self.events.iter().enumerate() {
+            if !(event.time.is_finite() && event.time >= previous_time) {
+                return Err(format!("Recorded event {} has a time before the previous event", i));
+            }
+            if !event.position.iter().all(|c| c.is_finite()) {
+                return Err(format!("Recorded event {} has a non-finite position", i));
+            }
+            if !(0.0..=1.0).contains(&event.pressure) {
+                return Err(format!("Recorded event {} has pressure outside 0.0-1.0", i));
+            }
+            previous_time = event.time;
+        }
+        Ok(())
+    }
+
+    /// Length of the recording in milliseconds
+    pub fn duration_ms(&self) -> f64 {
+        self.events.last().map_or(0.0, |event| event.time)
+    }
+
+    /// Pointer events for playing the recording back from `start_ms`
+    ///
+    /// `speed` scales playback (2.0 = twice as fast) and must be positive.
+    pub fn playback_events(&self, speed: f64, start_ms: f64) -> Result<Vec<PointerEvent>, String> {
+        if !(speed.is_finite() && speed > 0.0) {
+            return Err("Playback speed must be positive".to_string());
+        }
+        Ok(self
+            .events
+            .iter()
+            .map(|event| event.to_pointer_event(start_ms + event.time / speed))
+            .collect())
+    }
+}
+
+/// Records pointer events into an `InputRecording` while recording is on
+///
+/// Only whole strokes are recorded: a stroke already in progress when recording starts is
+/// skipped, and one still in progress when it stops is ended where the pointer last was,
+/// so every recording plays back as complete strokes.
+pub struct InputRecorder {
+    /// Events recorded so far, None while not recording
+    events: Option<Vec<RecordedEvent>>,
+    /// Timestamp of the first recorded event (the recording's time zero)
+    start_timestamp: Option<f64>,
+    /// Whether a recorded stroke is in progress
+    in_stroke: bool,
+    /// Whether the event cap was hit (the rest of the session is dropped)
+    truncated: bool,
+}
+
+impl InputRecorder {
+    /// Create a recorder that is not recording
+    pub fn new() -> Self {
+        Self {
+            events: None,
+            start_timestamp: None,
+            in_stroke: false,
+            truncated: false,
+        }
+    }
+
+    /// Check whether events are being recorded
+    pub fn is_recording(&self) -> bool {
+        self.events.is_some()
+    }
+
+    /// Start a new recording, discarding any unfinished one
+    pub fn start(&mut self) {
+        self.events = Some(Vec::new());
+        self.start_timestamp = None;
+        self.in_stroke = false;
+        self.truncated = false;
+    }
+
+    /// Record an input event (ignored while not recording)
+    pub fn record(&mut self, event: &PointerEvent) {
+        let Some(events) = &mut self.events else {
+            return;
+        };
+        // The last slot is kept for the Up that closes the open stroke
+        let full = events.len() + 1 >= MAX_RECORDED_EVENTS;
+        match event.event_type {
+            PointerEventType::Down | PointerEventType::Move if full => {
+                if !self.truncated {
+                    self.truncated = true;
+                    log::warn!("Recording reached {} events, dropping the rest", MAX_RECORDED_EVENTS);
+                }
+                return;
+            }
+            PointerEventType::Down => self.in_stroke = true,
+            // The rest of a stroke that started before recording
+            _ if !self.in_stroke => return,
+            PointerEventType::Up => self.in_stroke = false,
+            PointerEventType::Move => {}
+        }
+
+        let start = *self.start_timestamp.get_or_insert(event.timestamp);
+        let previous_time = events.last().map_or(0.0, |last| last.time);
+        events.push(RecordedEvent {
+            // Clamped so the recording stays ordered even if the clock steps back
+            time: (event.timestamp - start).max(previous_time),
+            position: event.position,
+            pressure: event.pressure,
+            tilt: event.tilt,
+            azimuth: event.azimuth,
+            twist: event.twist,
+            event_type: event.event_type,
+            source: event.source,
+        });
+    }
+
+    /// Stop recording and return what was recorded (empty if recording was off)
+    pub fn stop(&mut self) -> InputRecording {
+        let mut events = self.events.take().unwrap_or_default();
+        if self.in_stroke {
+            if let Some(&last) = events.last() {
+                events.push(RecordedEvent { event_type: PointerEventType::Up, ..last });
+            }
+            self.in_stroke = false;
+        }
+        InputRecording { version: RECORDING_VERSION, events }
+    }
+}
+
+impl Default for InputRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(x: f32, timestamp: f64, event_type: PointerEventType) -> PointerEvent {
+        PointerEvent {
+            position: [x, 10.0],
+            pressure: 0.5,
+            tilt: Some([20.0, -5.0]),
+            azimuth: Some(1.0),
+            twist: None,
+            timestamp,
+            event_type,
+            source: PointerEventSource::TabletTool,
+        }
+    }
+
+    #[test]
+    fn test_recording_round_trips_through_json() {
+        let mut recorder = InputRecorder::new();
+        recorder.start();
+        recorder.record(&event(0.0, 1000.0, PointerEventType::Down));
+        recorder.record(&event(5.0, 1008.0, PointerEventType::Move));
+        recorder.record(&event(9.0, 1016.0, PointerEventType::Up));
+        let recording = recorder.stop();
+
+        assert!(!recorder.is_recording());
+        assert_eq!(recording.events.len(), 3);
+        assert_eq!(recording.events[0].time, 0.0);
+        assert_eq!(recording.duration_ms(), 16.0);
+        assert_eq!(recording.events[1].tilt, Some([20.0, -5.0]));
+
+        let restored = InputRecording::from_json(&recording.to_json()).unwrap();
+        assert_eq!(restored, recording);
+    }
+
+    #[test]
+    fn test_only_whole_strokes_are_recorded() {
+        let mut recorder = InputRecorder::new();
+        recorder.record(&event(0.0, 0.0, PointerEventType::Down));
+        recorder.start();
+        // Tail of a stroke started before recording
+        recorder.record(&event(1.0, 8.0, PointerEventType::Move));
+        recorder.record(&event(2.0, 16.0, PointerEventType::Up));
+        recorder.record(&event(3.0, 24.0, PointerEventType::Down));
+        recorder.record(&event(4.0, 32.0, PointerEventType::Move));
+        let recording = recorder.stop();
+
+        let types: Vec<_> = recording.events.iter().map(|e| e.event_type).collect();
+        assert_eq!(types, [PointerEventType::Down, PointerEventType::Move, PointerEventType::Up]);
+        assert_eq!(recording.events[2].position, [4.0, 10.0]);
+    }
+
+    #[test]
+    fn test_playback_scales_time_by_speed() {
+        let mut recorder = InputRecorder::new();
+        recorder.start();
+        recorder.record(&event(0.0, 500.0, PointerEventType::Down));
+        recorder.record(&event(1.0, 600.0, PointerEventType::Up));
+        let recording = recorder.stop();
+
+        let events = recording.playback_events(2.0, 1000.0).unwrap();
+        assert_eq!(events[0].timestamp, 1000.0);
+        assert_eq!(events[1].timestamp, 1050.0);
+        assert_eq!(events[1].source, PointerEventSource::TabletTool);
+        assert!(recording.playback_events(0.0, 0.0).is_err());
+    }
+
+    #[test]
+    fn test_missing_optional_fields_keep_defaults() {
+        let json = r#"{ "version": 1, "events": [
+            { "time": 0.0, "position": [1.0, 2.0], "event_type": "Down" },
+            { "time": 5.0, "position": [3.0, 4.0], "event_type": "Up", "future_field": 7 }
+        ] }"#;
+        let recording = InputRecording::from_json(json).unwrap();
+        assert_eq!(recording.events[0].pressure, 1.0);
+        assert_eq!(recording.events[0].tilt, None);
+        assert_eq!(recording.events[1].source, PointerEventSource::Unknown);
+    }
+
+    #[test]
+    fn test_invalid_recordings_are_rejected() {
+        let newer = format!(r#"{{ "version": {}, "events": [] }}"#, RECORDING_VERSION + 1);
+        assert!(InputRecording::from_json(&newer).is_err());
+        assert!(InputRecording::from_json("not json").is_err());
+        let backwards = r#"{ "version": 1, "events": [
+            { "time": 5.0, "position": [0.0, 0.0], "event_type": "Down" },
+            { "time": 1.0, "position": [0.0, 0.0], "event_type": "Up" }
+        ] }"#;
+        assert!(InputRecording::from_json(backwards).is_err());
+    }
+}
@@ -5,11 +5,16 @@
 
 use wgpu;
 use wgpu::util::DeviceExt;
-use crate::brush::BrushDab;
+use crate::brush::{BrushBlendMode, BrushDab, BrushParams, TipShape};
 use crate::debug;
+use crate::error::{report_error, ErrorCode};
+use crate::export::HdrExportMode;
+use crate::fill::Fill;
+use crate::gradient::{BackgroundGradient, GradientInterpolation, GradientKind, GradientStop, MAX_GRADIENT_STOPS};
+use crate::view::ViewTransform;
 
 /// Color blending mode for brush strokes
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub enum BlendColorSpace {
     /// Blend in linear color space (physically correct)
     Linear,
@@ -17,12 +22,374 @@ pub enum BlendColorSpace {
     Srgb,
 }
 
+/// Shader selector for the color space the canvas is stored in (0 = Linear, 1 = sRGB)
+fn blend_color_space_selector(space: BlendColorSpace) -> u32 {
+    match space {
+        BlendColorSpace::Linear => 0,
+        BlendColorSpace::Srgb => 1,
+    }
+}
+
+impl Default for BlendColorSpace {
+    fn default() -> Self {
+        Self::Srgb
+    }
+}
+
+/// How the canvas is sampled past its edges when displayed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum WrapMode {
+    /// Edge pixels extend outward; the area around the canvas shows the surface background
+    Clamp,
+    /// The canvas tiles across the whole surface (for previewing seamless patterns)
+    Repeat,
+    /// The canvas tiles with every other copy mirrored
+    MirrorRepeat,
+}
+
+impl WrapMode {
+    /// Sampler address mode for this wrap mode
+    fn address_mode(self) -> wgpu::AddressMode {
+        match self {
+            WrapMode::Clamp => wgpu::AddressMode::ClampToEdge,
+            WrapMode::Repeat => wgpu::AddressMode::Repeat,
+            WrapMode::MirrorRepeat => wgpu::AddressMode::MirrorRepeat,
+        }
+    }
+}
+
+/// Shader selector for how the stroke layer blends with the canvas beneath
+/// (0 = Normal, 1 = Multiply, 2 = Screen, 3 = Darken, 4 = Lighten)
+fn stroke_blend_selector(mode: BrushBlendMode) -> u32 {
+    match mode {
+        // Additive dabs never go through the stroke layer
+        BrushBlendMode::Normal | BrushBlendMode::AddHdr => 0,
+        BrushBlendMode::Multiply => 1,
+        BrushBlendMode::Screen => 2,
+        BrushBlendMode::Darken => 3,
+        BrushBlendMode::Lighten => 4,
+    }
+}
+
+impl Default for WrapMode {
+    fn default() -> Self {
+        Self::Clamp
+    }
+}
+
+/// How brush dab edges are antialiased
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum AaMode {
+    /// A one-pixel ramp just inside the tip edge (hard tips look slightly thinner)
+    Off,
+    /// Dabs drawn into a 4x multisampled target, shaded per sample, and resolved into the
+    /// canvas. Sharpest edges, at four times the fill cost and a multisampled copy of the
+    /// canvas in memory
+    Msaa4x,
+    /// Coverage computed from screen-space derivatives, centered on the tip edge
+    Analytic,
+}
+
+impl Default for AaMode {
+    fn default() -> Self {
+        Self::Off
+    }
+}
+
+/// Samples per pixel of the multisampled brush target
+const MSAA_SAMPLE_COUNT: u32 = 4;
+
+/// Formats the canvas can be stored in (see `Renderer::set_srgb_canvas`)
+const CANVAS_FORMATS: [wgpu::TextureFormat; 3] =
+    [wgpu::TextureFormat::Rgba16Float, wgpu::TextureFormat::Rgba8Unorm, wgpu::TextureFormat::Rgba8UnormSrgb];
+
+/// Pipelines for drawing dabs with 4x MSAA (see `AaMode::Msaa4x`)
+struct MsaaPipelines {
+    paint: wgpu::RenderPipeline,
+    erase: wgpu::RenderPipeline,
+    add: wgpu::RenderPipeline,
+    load: wgpu::RenderPipeline,  // Copies the target into every sample before dabs are drawn
+}
+
+/// How the canvas is composited against a reference image for comparison
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComparisonMode {
+    /// Per-channel absolute difference of the drawing (on white paper) and the reference
+    Difference,
+    /// Drawing composited over the reference
+    Overlay,
+}
+
+/// Backend-specific handle to the canvas texture, for zero-copy interop
+///
+/// The texture is the full canvas allocation (visible area plus the bleed margin on every
+/// side), in the canvas format (see `Renderer::set_srgb_canvas`), premultiplied alpha, in
+/// the blend color space. It stays owned by the renderer and is replaced whenever the
+/// canvas is resized, its bleed changes, or its format changes, so re-query the handle
+/// after any of these.
+///
+/// Supported backends:
+/// - Native OpenGL: the GL texture name, usable in wgpu's GL context and contexts that share
+///   objects with it
+///
+/// Unsupported (no handle is returned):
+/// - WebGL2: WebGL objects belong to the context that created them and cannot be imported
+///   into another context, so there is nothing useful to hand to JavaScript. Use the image
+///   data readback instead.
+/// - Vulkan, Metal, DX12 and WebGPU: not exposed yet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CanvasTextureHandle {
+    /// OpenGL texture name (bound as `GL_TEXTURE_2D`)
+    Gl(u32),
+}
+
+/// Brush outline shown around the hovering pointer (see `Renderer::set_cursor_ring`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CursorRing {
+    /// Center in canvas pixels
+    pub center: [f32; 2],
+    /// Brush radius in canvas pixels
+    pub radius: f32,
+    /// Brush edge hardness (0.0-1.0): an inner ring marks where the soft falloff starts
+    pub hardness: f32,
+}
+
+/// The GPU adapter and backend the renderer runs on, for diagnosing GPU-specific reports
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdapterDiagnostics {
+    /// Adapter name as reported by the driver (may be empty, e.g. on some browsers)
+    pub name: String,
+    /// Graphics backend, e.g. "Vulkan", "Metal", "Dx12", "Gl"
+    pub backend: String,
+    /// Kind of device, e.g. "DiscreteGpu", "IntegratedGpu", "Cpu"
+    pub device_type: String,
+    /// Whether this is a software rasterizer rather than a GPU (expect poor performance)
+    pub is_fallback: bool,
+}
+
+/// Copy of the paint layers kept on the CPU, to survive rebuilding the renderer
+///
+/// Taken with `Renderer::snapshot_canvas` and put back with `Renderer::restore_canvas`.
+/// Layers are stored as raw texels of the canvas format, so a restore is exact.
+pub struct CanvasSnapshot {
+    format: wgpu::TextureFormat,
+    size: [u32; 2],  // Canvas texture size, bleed margin included
+    active_layer: usize,
+    layers: Vec<LayerSnapshot>,
+}
+
+/// Brush and reference images given to a renderer, to upload again after rebuilding it
+///
+/// Taken with `Renderer::take_uploaded_images` and put back with
+/// `Renderer::restore_uploaded_images`. Kept on the CPU as uploaded, so they can be taken
+/// even after device loss.
+pub struct UploadedImages {
+    stamp: Option<(Vec<u8>, [u32; 2])>,  // Brush stamp mask
+    grain: Option<(Vec<u8>, [u32; 2], f32)>,  // Brush grain mask and its scale
+    reference: Option<(Vec<u8>, [u32; 2])>,  // Reference image RGBA
+    reference_opacity: f32,
+    reference_visible: bool,
+}
+
+/// Layer textures (and their undo bases) held while the canvas is reallocated, to copy back
+struct SavedContents {
+    layers: Vec<wgpu::Texture>,
+    undo_bases: Vec<Option<wgpu::Texture>>,
+    bleed: u32,  // Bleed margin the textures were allocated with
+}
+
+/// One layer of a `CanvasSnapshot`
+struct LayerSnapshot {
+    texels: Vec<u8>,
+    visible: bool,
+    opacity: f32,
+    has_strokes: bool,
+}
+
+/// Consecutive lost or outdated frames after which reconfiguring is given up on and the
+/// renderer asks to be rebuilt with a new surface (see `Renderer::needs_rebuild`)
+const MAX_SURFACE_RECONFIGURES: u32 = 3;
+
 /// Uniforms for brush shader (canvas size)
 #[repr(C, align(16))]  // Force 16-byte alignment for WebGL compatibility
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 struct BrushUniforms {
     canvas_size: [f32; 2],
-    _padding: [f32; 2],  // Align to 16 bytes
+    dither_amplitude: f32,  // Ordered dither added to dab coverage, 0 = off
+    aa_mode: u32,           // Edge antialiasing: 0 = ramp inside the edge, 1 = analytic coverage
+}
+
+/// Largest brush stamp texture dimension in pixels
+/// Dabs are rarely this large on screen, so bigger stamps only waste memory
+pub const MAX_BRUSH_TEXTURE_SIZE: u32 = 2048;
+
+/// Uniforms for brush grain texture (dual-mask rendering)
+#[repr(C, align(16))]  // Force 16-byte alignment for WebGL compatibility
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct GrainUniforms {
+    grain_size: [f32; 2],  // Grain texture size in pixels
+    grain_scale: f32,      // Scale applied to the grain texture in canvas space
+    grain_enabled: u32,    // 0 = shape only, 1 = shape x grain
+}
+
+/// Uniforms for comparison shader (composite mode)
+#[repr(C, align(16))]  // Force 16-byte alignment for WebGL compatibility
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct CompareUniforms {
+    mode: u32,           // 0 = Difference, 1 = Overlay
+    _padding: [u32; 3],  // Align to 16 bytes
+    canvas_uv_rect: [f32; 4],  // Visible canvas region in canvas UVs (offset, scale)
+}
+
+/// Uniforms for stroke composite shader (stroke opacity)
+#[repr(C, align(16))]  // Force 16-byte alignment for WebGL compatibility
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct StrokeCompositeUniforms {
+    opacity: f32,
+    _padding: [f32; 3],  // Align to 16 bytes
+}
+
+/// Uniforms for bucket fill shader (fill color)
+#[repr(C, align(16))]  // Force 16-byte alignment for WebGL compatibility
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct FillUniforms {
+    color: [f32; 4],        // Premultiplied, in the blend color space
+    canvas_size: [f32; 2],  // Canvas texture size in pixels
+    _padding: [f32; 2],     // Align to 16 bytes
+}
+
+/// Uniforms for smudge shader (carried color strength and length)
+#[repr(C, align(16))]  // Force 16-byte alignment for WebGL compatibility
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct SmudgeUniforms {
+    canvas_size: [f32; 2],  // Canvas texture size in pixels
+    strength: f32,          // Fraction of the carried color laid down at full coverage
+    length: f32,            // Fraction of the carried color kept from the previous dab
+}
+
+/// Uniforms for layer composite shader (layer and stroke opacity)
+#[repr(C, align(16))]  // Force 16-byte alignment for WebGL compatibility
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct LayerCompositeUniforms {
+    opacity: f32,
+    stroke_opacity: f32,
+    stroke_blend_mode: u32,  // See `stroke_blend_selector`
+    blend_mode: u32,         // Color space the layers are stored in: 0 = Linear, 1 = sRGB
+    stroke_ceiling: f32,     // Opacity the stroke layer may build the layer up to (1 = none)
+    _padding: [u32; 3],      // Align to 16 bytes
+}
+
+/// Index of the active layer once the layer at `removed` is taken out
+/// Removing the active layer itself makes the one beneath it (or the new bottom layer) active.
+fn active_layer_after_removal(active: usize, removed: usize) -> usize {
+    if removed <= active {
+        active.saturating_sub(1)
+    } else {
+        active
+    }
+}
+
+/// A paint layer: a canvas-sized texture composited with the other layers for display
+struct Layer {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    visible: bool,
+    opacity: f32,
+    // Whether anything was drawn into the layer since it was last cleared or recreated
+    has_strokes: bool,
+    // Snapshot from before the layer's oldest undoable stroke (same size/format as the layer)
+    // None until undo history starts on the layer, and dropped whenever the canvas is cleared
+    undo_base: Option<(wgpu::Texture, wgpu::TextureView)>,
+    undo_base_has_strokes: bool,  // Layer empty flag at the time of the snapshot
+    // What the layer is composited with, kept across frames (see `encode_layer_composite`)
+    composite_uniform_buffer: wgpu::Buffer,
+    composite_bind_group: wgpu::BindGroup,
+}
+
+impl Layer {
+    /// Wrap a canvas texture as a visible, fully opaque, empty layer
+    fn new(
+        device: &wgpu::Device,
+        composite_pipeline: &wgpu::RenderPipeline,
+        stroke_view: &wgpu::TextureView,
+        (texture, view): (wgpu::Texture, wgpu::TextureView),
+    ) -> Self {
+        let composite_uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Layer Composite Uniform Buffer"),
+            size: std::mem::size_of::<LayerCompositeUniforms>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let composite_bind_group = Renderer::create_layer_composite_bind_group(
+            device,
+            composite_pipeline,
+            &view,
+            stroke_view,
+            &composite_uniform_buffer,
+        );
+        Self {
+            texture,
+            view,
+            visible: true,
+            opacity: 1.0,
+            has_strokes: false,
+            undo_base: None,
+            undo_base_has_strokes: false,
+            composite_uniform_buffer,
+            composite_bind_group,
+        }
+    }
+
+    /// Recreate the composite bind group after the layer texture, the stroke layer, or the
+    /// composite pipeline was replaced
+    fn rebind(&mut self, device: &wgpu::Device, composite_pipeline: &wgpu::RenderPipeline, stroke_view: &wgpu::TextureView) {
+        self.composite_bind_group = Renderer::create_layer_composite_bind_group(
+            device,
+            composite_pipeline,
+            &self.view,
+            stroke_view,
+            &self.composite_uniform_buffer,
+        );
+    }
+}
+
+/// Bounding box of the canvas pixels changed since the last frame, in canvas space
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct DirtyRect {
+    min: [f32; 2],
+    max: [f32; 2],
+}
+
+impl DirtyRect {
+    /// The whole canvas, bleed margin included
+    fn full() -> Self {
+        Self { min: [f32::NEG_INFINITY; 2], max: [f32::INFINITY; 2] }
+    }
+
+    /// The quad a dab shades, with a pixel of margin for antialiasing
+    fn of_dab(dab: &BrushDab) -> Self {
+        let [x_axis, y_axis] = dab.transform();
+        let extent = [x_axis[0].abs() + y_axis[0].abs() + 1.0, x_axis[1].abs() + y_axis[1].abs() + 1.0];
+        Self {
+            min: [dab.position[0] - extent[0], dab.position[1] - extent[1]],
+            max: [dab.position[0] + extent[0], dab.position[1] + extent[1]],
+        }
+    }
+
+    /// Smallest box holding both
+    fn union(self, other: Self) -> Self {
+        Self {
+            min: [self.min[0].min(other.min[0]), self.min[1].min(other.min[1])],
+            max: [self.max[0].max(other.max[0]), self.max[1].max(other.max[1])],
+        }
+    }
+
+    /// Area in pixels of the part inside the visible canvas
+    fn visible_area(&self, canvas_size: [f32; 2]) -> f32 {
+        let width = self.max[0].min(canvas_size[0]) - self.min[0].max(0.0);
+        let height = self.max[1].min(canvas_size[1]) - self.min[1].max(0.0);
+        width.max(0.0) * height.max(0.0)
+    }
 }
 
 /// Uniforms for blit shader (blend mode)
@@ -30,7 +397,31 @@ struct BrushUniforms {
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 struct BlitUniforms {
     blend_mode: u32,  // 0 = Linear, 1 = sRGB
-    _padding: [u32; 3],  // Align to 16 bytes
+    stroke_opacity: f32,  // Opacity of the in-progress stroke layer (0 = none)
+    bloom_intensity: f32,  // Strength of the glow around HDR (> 1.0) pixels, 0 = off
+    dither_amplitude: f32, // Ordered dither added before the 8-bit surface write, 0 = off
+    view_row0: [f32; 4],  // Surface → canvas affine matrix, row 0 (a, b, tx, unused)
+    view_row1: [f32; 4],  // Surface → canvas affine matrix, row 1 (c, d, ty, unused)
+    canvas_size: [f32; 2],    // Visible canvas size in pixels
+    canvas_offset: [f32; 2],  // Bleed margin: texel offset of the visible area
+    surface_background: [f32; 4],  // sRGB color shown behind/around the canvas (alpha unused)
+    gradient_kind: u32,            // Background gradient under the canvas: 0 = none, 1 = linear, 2 = radial
+    gradient_stop_count: u32,
+    gradient_space: u32,           // Interpolation space: 0 = sRGB, 1 = linear, 2 = OKLab
+    wrap_mode: u32,                // Display tiling: 0 = clamp, 1 = repeat, 2 = mirrored repeat
+    gradient_positions: [[f32; 4]; MAX_GRADIENT_STOPS / 4],  // Stop positions, packed four per vec4
+    gradient_colors: [[f32; 4]; MAX_GRADIENT_STOPS],         // Stop colors in the interpolation space, straight alpha
+    reference_rect: [f32; 4],  // Reference image placement in canvas pixels (x, y, width, height)
+    reference_opacity: f32,    // Reference image opacity (0 = none/hidden)
+    stroke_blend_mode: u32,    // How the stroke layer blends with the canvas, see `stroke_blend_selector`
+    prediction_opacity: f32,   // Opacity of the predicted stroke overlay (0 = none)
+    cursor_visible: u32,       // Whether the cursor ring is drawn (0 = hidden)
+    paper_color: [f32; 4],     // sRGB paper beneath the canvas content (straight alpha, 0 = none)
+    cursor_ring: [f32; 4],     // Cursor ring in canvas pixels (center x, center y, radius, hardness)
+    tether_visible: u32,       // Whether the stabilizer tether is drawn (0 = hidden)
+    stroke_ceiling: f32,       // Opacity the stroke layer may build the canvas up to (1 = none)
+    _padding: [u32; 2],
+    stabilizer_tether: [f32; 4],  // Stabilizer tether in canvas pixels (pen x, pen y, ink x, ink y)
 }
 
 /// Vertex data for a single brush dab instance
@@ -38,40 +429,242 @@ struct BlitUniforms {
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 struct DabInstance {
     position: [f32; 2],
-    size: f32,
     opacity: f32,
-    color: [f32; 4],
     hardness: f32,
-    _padding: [f32; 3],  // Align to 16 bytes
+    color: [f32; 4],
+    transform: [f32; 4],  // Local unit quad → canvas pixels, 2x2 column-major (x_axis.xy, y_axis.xy)
+    sides: f32,          // Polygon side count, 0 = round
+    square_cap: f32,     // 1 = square cap piece aligned with the dab's local axes, 0 = tip shape
+    smudge_offset: [f32; 2],  // Pixels from the previous dab (only the smudge pipeline reads it)
+    clips: [[f32; 4]; 2],  // Cap trim half-planes (normal.xy, offset, enabled) in the dab's local frame
 }
 
+/// Vertex attributes of a `DabInstance`, shared by the brush and smudge pipelines
+const DAB_INSTANCE_ATTRIBUTES: [wgpu::VertexAttribute; 10] = [
+    // position
+    wgpu::VertexAttribute {
+        offset: 0,
+        shader_location: 0,
+        format: wgpu::VertexFormat::Float32x2,
+    },
+    // opacity
+    wgpu::VertexAttribute {
+        offset: 8,
+        shader_location: 1,
+        format: wgpu::VertexFormat::Float32,
+    },
+    // hardness
+    wgpu::VertexAttribute {
+        offset: 12,
+        shader_location: 2,
+        format: wgpu::VertexFormat::Float32,
+    },
+    // color
+    wgpu::VertexAttribute {
+        offset: 16,
+        shader_location: 3,
+        format: wgpu::VertexFormat::Float32x4,
+    },
+    // transform
+    wgpu::VertexAttribute {
+        offset: 32,
+        shader_location: 4,
+        format: wgpu::VertexFormat::Float32x4,
+    },
+    // sides
+    wgpu::VertexAttribute {
+        offset: 48,
+        shader_location: 5,
+        format: wgpu::VertexFormat::Float32,
+    },
+    // square cap
+    wgpu::VertexAttribute {
+        offset: 52,
+        shader_location: 6,
+        format: wgpu::VertexFormat::Float32,
+    },
+    // first cap clip
+    wgpu::VertexAttribute {
+        offset: 64,
+        shader_location: 7,
+        format: wgpu::VertexFormat::Float32x4,
+    },
+    // second cap clip
+    wgpu::VertexAttribute {
+        offset: 80,
+        shader_location: 8,
+        format: wgpu::VertexFormat::Float32x4,
+    },
+    // smudge offset
+    wgpu::VertexAttribute {
+        offset: 56,
+        shader_location: 9,
+        format: wgpu::VertexFormat::Float32x2,
+    },
+];
+
+/// Vertex buffer layout for dab instances
+const DAB_INSTANCE_LAYOUT: wgpu::VertexBufferLayout<'static> = wgpu::VertexBufferLayout {
+    array_stride: std::mem::size_of::<DabInstance>() as wgpu::BufferAddress,
+    step_mode: wgpu::VertexStepMode::Instance,
+    attributes: &DAB_INSTANCE_ATTRIBUTES,
+};
+
 /// Renderer wraps the wgpu device, queue, and surface
 pub struct Renderer {
-    surface: wgpu::Surface<'static>,
+    surface: Option<wgpu::Surface<'static>>,  // None when headless (offscreen rendering and readback only)
     device: wgpu::Device,
     queue: wgpu::Queue,
     config: wgpu::SurfaceConfiguration,
     size: winit::dpi::PhysicalSize<u32>,
     max_texture_dimension: u32,
+    adapter_info: wgpu::AdapterInfo,  // Adapter the device was created on, for diagnostics
+    device_lost: std::sync::Arc<std::sync::atomic::AtomicBool>,  // Set by the device lost callback
+    surface_reconfigures: u32,  // Consecutive frames lost to surface errors since the last present
     canvas_format: wgpu::TextureFormat, // Current canvas texture format
+    srgb_canvas: bool,                   // Whether the canvas is stored as 8-bit sRGB
     blend_color_space: BlendColorSpace,  // Current blending mode
+    max_opacity: f32,                    // Opacity ceiling painting may build the canvas up to
+    view_transform: ViewTransform,       // Canvas → surface display transform
     
     // Brush rendering pipelines (one for each target format)
     brush_pipeline: wgpu::RenderPipeline,  // For rendering to canvas
+    erase_pipeline: wgpu::RenderPipeline,  // For erasing from canvas (shares brush bind groups)
+    add_pipeline: wgpu::RenderPipeline,    // For additive HDR dabs (shares brush bind groups)
+    brush_pipeline_layout: wgpu::PipelineLayout,  // Shared by every brush pipeline, so one set of bind groups fits all
+    erase_mode: bool,  // Whether dabs erase instead of paint
+    antialiasing: AaMode,  // How dab edges are antialiased
+    msaa_pipelines: Option<MsaaPipelines>,  // Only while antialiasing is Msaa4x
+    msaa_target: Option<(wgpu::Texture, wgpu::TextureView)>,  // Multisampled canvas-sized target, made on first use
+    msaa_formats: Vec<wgpu::TextureFormat>,  // Canvas formats 4x MSAA (with per-sample shading) works for
+    blendable_formats: Vec<wgpu::TextureFormat>,  // Canvas formats the adapter can blend into
+    brush_blend_mode: BrushBlendMode,  // How painted dabs combine with the canvas
+    bloom_intensity: f32,  // Glow applied around HDR pixels when displayed
+    dithering: bool,  // Whether 8-bit targets are written with ordered dithering
+    surface_background: [f32; 3],  // sRGB color behind/around the canvas on the surface
+    paper_color: [f32; 4],  // sRGB paper shown beneath the canvas content on screen (alpha 0 = none)
+    background_gradient: Option<BackgroundGradient>,  // Gradient shown beneath the canvas content
+    export_includes_background: bool,  // Whether readback/export composites the gradient beneath
+    export_hdr_mode: HdrExportMode,  // How 16-bit exports bring values above 1.0 into range
+    // Reference image shown beneath the canvas content (display only, never exported)
+    reference_texture: wgpu::Texture,  // 1x1 placeholder while no image is loaded
+    reference_sampler: wgpu::Sampler,
+    reference_size: Option<[u32; 2]>,  // Loaded image size, None = no reference image
+    reference_data: Option<Vec<u8>>,  // Loaded image RGBA, kept to upload again after a rebuild
+    reference_opacity: f32,
+    reference_visible: bool,
     brush_uniform_buffer: wgpu::Buffer,
     brush_bind_group: wgpu::BindGroup,
+    custom_brush_mask: Option<String>,  // User WGSL tip mask compiled into the brush pipelines
+
+    // Brush grain texture (multiplied with the dab shape mask)
+    grain_texture: wgpu::Texture,
+    grain_sampler: wgpu::Sampler,
+    grain_uniform_buffer: wgpu::Buffer,
+    grain_source: Option<(Vec<u8>, [u32; 2], f32)>,  // Enabled grain mask and scale, kept to upload again
+
+    // Brush stamp texture (tip shape mask, scaled and rotated with each dab)
+    stamp_texture: wgpu::Texture,
+    stamp_sampler: wgpu::Sampler,
+    stamp_source: Option<(Vec<u8>, [u32; 2])>,  // Uploaded stamp mask, kept to upload again
+    brush_texture_bind_group: wgpu::BindGroup,
     
-    // Canvas texture for accumulating strokes
-    canvas_texture: wgpu::Texture,
-    canvas_view: wgpu::TextureView,
+    // Paint layers for accumulating strokes, bottom first (never empty)
+    // Each is allocated larger than the visible area by the bleed margin on every side
+    layers: Vec<Layer>,
+    active_layer: usize,  // Layer that dabs, undo, and conversions act on ("the canvas")
+    // Visible layers flattened for display, only while the single-layer fast path is off
+    layer_composite: Option<(wgpu::Texture, wgpu::TextureView)>,
+    layer_composite_pipeline: wgpu::RenderPipeline,
+    canvas_bleed: u32,          // Requested bleed margin in pixels
+    canvas_resolution: Option<[u32; 2]>, // Fixed visible canvas size (None: follows the surface)
+    export_includes_bleed: bool, // Whether readback/export includes the bleed margin
+
+    // Scratch layer for PerStroke opacity (same size/format as the canvas)
+    stroke_texture: wgpu::Texture,
+    stroke_view: wgpu::TextureView,
+    stroke_layer_active: bool,  // Whether dabs currently go to the stroke layer
+    stroke_layer_opacity: f32,  // Opacity the stroke layer is composited at
+    stroke_layer_blend_mode: BrushBlendMode,  // How the stroke layer blends with the canvas
+    stroke_composite_pipeline: wgpu::RenderPipeline,
+
+    // Draws bucket fill spans into the canvas
+    fill_pipeline: wgpu::RenderPipeline,
+
+    // Smudge tool: dabs drag existing paint instead of painting (see `encode_smudge_dabs`)
+    smudge_strength: Option<f32>,  // Fraction of the color carried, None = dabs paint
+    smudge_length: f32,  // Fraction of the carried color kept from dab to dab
+    smudge_pipeline: wgpu::RenderPipeline,
+    // Ping-pong partner of the canvas: each smudge dab reads the region it touches from
+    // here while writing the canvas. Canvas-sized, made on first use
+    smudge_source: Option<(wgpu::Texture, wgpu::TextureView)>,
+    // Color each smudge dab carried, written where the dab sat so the next dab can read it
+    // one smudge offset back, and its ping-pong partner. Canvas-sized, made on first use
+    smudge_carry: Option<(wgpu::Texture, wgpu::TextureView)>,
+    smudge_carry_source: Option<(wgpu::Texture, wgpu::TextureView)>,
+
+    // Predicted continuation of the stroke, redrawn every frame and shown over the canvas
+    // on display only (see `render_prediction`). Canvas-sized, made on first use
+    prediction_overlay: Option<(wgpu::Texture, wgpu::TextureView)>,
+    prediction_opacity: f32,  // Opacity the overlay is shown at (0 = hidden)
+
+    // Brush outline around the hovering pointer, drawn by the blit (display only)
+    cursor_ring: Option<CursorRing>,
+    // Line from the pen to the stabilized ink point, drawn by the blit (display only)
+    stabilizer_tether: Option<[[f32; 2]; 2]>,
     
     // Blit pipeline for copying canvas to surface
     blit_pipeline: wgpu::RenderPipeline,
     blit_uniform_buffer: wgpu::Buffer,
     blit_bind_group: wgpu::BindGroup,
     canvas_sampler: wgpu::Sampler,
+    wrap_mode: WrapMode,  // Address mode of the canvas sampler (and display tiling)
+
+    // Comparison pipeline for compositing the canvas against a reference image
+    compare_pipeline: wgpu::RenderPipeline,
+
+    // Total dab quad area rendered since the last clear, in pixels (overdraw statistics)
+    dab_area_total: f64,
+
+    // Canvas region changed since the last frame (None = nothing), and the fraction of the
+    // canvas the last frame had to update (debug statistics)
+    dirty_rect: Option<DirtyRect>,
+    last_dirty_fraction: f32,
+    // Whether the layer composite still holds the displayed layers (readback composites
+    // without the stroke layer, so the next frame must flatten again)
+    layer_composite_current: std::cell::Cell<bool>,
+
+    // Dab instance data, reused across draws and only reallocated when a batch outgrows it
+    dab_instance_buffer: wgpu::Buffer,
+    dab_instance_capacity: usize,  // In instances
+    dab_instance_reallocations: u32,  // Times the buffer has grown (allocation churn statistics)
+    // Brush work encoded but not yet submitted: it goes in with the next submit (usually the
+    // frame's blit), so a frame costs one submit however many times dabs are drawn
+    brush_batch: std::cell::Cell<Option<wgpu::CommandEncoder>>,
+    batch_instances: std::cell::Cell<u32>,  // Dab instances uploaded for the open brush batch
+    #[cfg(debug_assertions)]
+    frame_submits: std::cell::Cell<u32>,  // Queue submits since the last frame ended
+    #[cfg(debug_assertions)]
+    last_frame_submits: u32,  // Queue submits the last frame took
+
+    // Frame pacing statistics (for debug HUD)
+    last_present_time_ms: Option<f64>,
+    smoothed_fps: f32,
 }
 
+/// Weight of the newest frame in the FPS exponential moving average
+const FPS_SMOOTHING: f32 = 0.1;
+
+/// Gaps between presents longer than this are treated as idle time, not frames
+/// (the event loop runs in Wait mode, so there are no presents while idle)
+const FPS_IDLE_THRESHOLD_MS: f64 = 1000.0;
+
+/// Dab instances the pooled instance buffer starts with room for
+const INITIAL_DAB_INSTANCE_CAPACITY: usize = 256;
+
+/// Dither amplitude for 8-bit targets: one quantization step
+const DITHER_AMPLITUDE_8BIT: f32 = 1.0 / 255.0;
+
 impl Renderer {
     /// Create a new renderer
     /// 
@@ -82,14 +675,7 @@ impl Renderer {
     /// A new renderer instance
     pub async fn new(window: impl Into<wgpu::SurfaceTarget<'static>>, size: winit::dpi::PhysicalSize<u32>) -> Self {
         log::info!("🔧 Renderer::new() starting...");
-        crate::debug::update_status("Creating wgpu instance...");
-        
-        // Create wgpu instance
-        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::all() & !wgpu::Backends::BROWSER_WEBGPU,
-            ..Default::default()
-        });
-        log::info!("✅ wgpu instance created");
+        let instance = Self::create_instance();
         crate::debug::update_status("Creating surface...");
 
         // Create surface
@@ -107,6 +693,70 @@ impl Renderer {
             }
         };
         log::info!("✅ Surface created");
+
+        match Self::with_surface(instance, Some(surface), size).await {
+            Ok(renderer) => renderer,
+            Err(err_msg) => {
+                crate::debug::update_status(&format!("❌ {}", err_msg));
+                panic!("{}", err_msg);
+            }
+        }
+    }
+
+    /// Create a renderer for a native window given by raw platform handles (FFI embedding)
+    ///
+    /// Like `new`, but failures are returned rather than panicking, since the caller may be
+    /// on the other side of an FFI boundary.
+    ///
+    /// # Safety
+    /// The handles must be valid, and the window and display must outlive the renderer.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async unsafe fn from_raw_handles(
+        window: wgpu::rwh::RawWindowHandle,
+        display: wgpu::rwh::RawDisplayHandle,
+        size: winit::dpi::PhysicalSize<u32>,
+    ) -> Result<Self, String> {
+        log::info!("🔧 Renderer::from_raw_handles() starting...");
+        let instance = Self::create_instance();
+        // SAFETY: the caller guarantees the handles are valid and outlive the renderer
+        let surface = unsafe {
+            instance.create_surface_unsafe(wgpu::SurfaceTargetUnsafe::RawHandle {
+                raw_display_handle: display,
+                raw_window_handle: window,
+            })
+        }
+        .map_err(|e| format!("Failed to create surface: {:?}", e))?;
+        Self::with_surface(instance, Some(surface), size).await
+    }
+
+    /// Create a renderer without a window, for offscreen rendering and readback (e.g. tests)
+    ///
+    /// Everything but presenting works: dabs render into the canvas, and the canvas can be
+    /// read back or exported. `render` does nothing. `size` is the visible canvas size.
+    /// Returns an error if no suitable GPU adapter or device is available.
+    pub async fn new_headless(size: winit::dpi::PhysicalSize<u32>) -> Result<Self, String> {
+        log::info!("🔧 Renderer::new_headless() starting...");
+        let instance = Self::create_instance();
+        Self::with_surface(instance, None, size).await
+    }
+
+    /// Create the wgpu instance for any of the renderer's backends
+    fn create_instance() -> wgpu::Instance {
+        crate::debug::update_status("Creating wgpu instance...");
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all() & !wgpu::Backends::BROWSER_WEBGPU,
+            ..Default::default()
+        });
+        log::info!("✅ wgpu instance created");
+        instance
+    }
+
+    /// Finish creating the renderer for an optional surface (None = headless)
+    async fn with_surface(
+        instance: wgpu::Instance,
+        surface: Option<wgpu::Surface<'static>>,
+        size: winit::dpi::PhysicalSize<u32>,
+    ) -> Result<Self, String> {
         crate::debug::update_status("Requesting adapter...");
 
         // Request adapter
@@ -114,15 +764,49 @@ impl Renderer {
         let adapter = instance
             .request_adapter(&wgpu::RequestAdapterOptions {
                 power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(&surface),
+                compatible_surface: surface.as_ref(),
                 force_fallback_adapter: false,
             })
             .await
-            .expect("Failed to find suitable adapter");
+            .map_err(|e| format!("Failed to find suitable adapter: {:?}", e))?;
         
         let adapter_info = adapter.get_info();
         log::info!("✅ Adapter acquired: {:?} (backend: {:?})", adapter_info.name, adapter_info.backend);
         crate::debug::update_status(&format!("Using: {:?}", adapter_info.backend));
+
+        // Canvas formats 4x MSAA brush rendering can use: multisampling and resolve for the
+        // format, plus per-sample shading (the tip shape is computed in the fragment shader).
+        // WebGL2 lacks per-sample shading, so there the list is empty
+        let msaa_formats: Vec<_> = if adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(wgpu::DownlevelFlags::MULTISAMPLED_SHADING)
+        {
+            CANVAS_FORMATS
+                .into_iter()
+                .filter(|&format| {
+                    let flags = adapter.get_texture_format_features(format).flags;
+                    flags.sample_count_supported(MSAA_SAMPLE_COUNT)
+                        && flags.contains(wgpu::TextureFormatFeatureFlags::MULTISAMPLE_RESOLVE)
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        log::info!("4x MSAA brush formats: {:?}", msaa_formats);
+
+        // Canvas formats dabs can blend into. WebGPU guarantees all three, but some native
+        // adapters lack blending for one of them, in which case the canvas falls back
+        let blendable_formats: Vec<_> = CANVAS_FORMATS
+            .into_iter()
+            .filter(|&format| {
+                adapter
+                    .get_texture_format_features(format)
+                    .flags
+                    .contains(wgpu::TextureFormatFeatureFlags::BLENDABLE)
+            })
+            .collect();
+        log::info!("Blendable canvas formats: {:?}", blendable_formats);
         
         // Get adapter limits to check max texture size
         let adapter_limits = adapter.limits();
@@ -158,27 +842,46 @@ impl Renderer {
                 experimental_features: Default::default(),
             })
             .await
-            .expect("Failed to create device");
+            .map_err(|e| format!("Failed to create device: {:?}", e))?;
         log::info!("✅ Device and queue created");
+
+        // A lost device can't be recovered in place; the flag tells the owner to rebuild
+        let device_lost = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let lost_flag = device_lost.clone();
+        device.set_device_lost_callback(move |reason, message| {
+            // Destroyed is the renderer being dropped, not a failure
+            if matches!(reason, wgpu::DeviceLostReason::Destroyed) {
+                return;
+            }
+            report_error(ErrorCode::DeviceLost, &format!("Device lost ({:?}): {}", reason, message));
+            lost_flag.store(true, std::sync::atomic::Ordering::Relaxed);
+        });
         crate::debug::update_status("Configuring surface...");
 
         // Get surface capabilities and configure
-        let surface_caps = surface.get_capabilities(&adapter);
-        log::info!("Surface capabilities: formats={:?}, present_modes={:?}", 
-                   surface_caps.formats, surface_caps.present_modes);
-        
-        // Select an sRGB surface format
-        // Prefer sRGB formats for proper color space handling
-        let surface_format = surface_caps
-            .formats
-            .iter()
-            .copied()
-            .find(|f| f.is_srgb())
-            .unwrap_or(surface_caps.formats[0]);
+        let (surface_format, present_mode) = match &surface {
+            Some(surface) => {
+                let surface_caps = surface.get_capabilities(&adapter);
+                log::info!("Surface capabilities: formats={:?}, present_modes={:?}", 
+                           surface_caps.formats, surface_caps.present_modes);
+                
+                // Select an sRGB surface format
+                // Prefer sRGB formats for proper color space handling
+                let surface_format = surface_caps
+                    .formats
+                    .iter()
+                    .copied()
+                    .find(|f| f.is_srgb())
+                    .unwrap_or(surface_caps.formats[0]);
+                (surface_format, surface_caps.present_modes[0])
+            }
+            // Nothing is presented, but the blit pipeline still needs a target format
+            None => (wgpu::TextureFormat::Rgba8UnormSrgb, wgpu::PresentMode::Fifo),
+        };
         
         log::info!("Selected surface format: {:?}", surface_format);
 
-        let canvas_format = wgpu::TextureFormat::Rgba16Float;
+        let canvas_format = Self::canvas_format_for(false, BlendColorSpace::default(), &blendable_formats);
         log::info!("Canvas texture format: {:?}", canvas_format);
 
         // Clamp size to max texture dimension to avoid WebGL limits
@@ -196,7 +899,7 @@ impl Renderer {
             format: surface_format,
             width: clamped_width,
             height: clamped_height,
-            present_mode: surface_caps.present_modes[0],
+            present_mode,
             // Use Opaque alpha mode to prevent canvas transparency showing HTML background
             alpha_mode: wgpu::CompositeAlphaMode::Opaque,
             view_formats: vec![],
@@ -204,11 +907,11 @@ impl Renderer {
         };
 
         // Only configure if size is valid, otherwise wait for resize
-        if config.width > 0 && config.height > 0 {
+        if let (Some(surface), true) = (&surface, config.width > 0 && config.height > 0) {
             log::info!("Configuring surface with size: {}x{}", config.width, config.height);
             surface.configure(&device, &config);
             log::info!("✅ Surface configured");
-        } else {
+        } else if surface.is_some() {
             log::warn!("Skipping surface configuration (invalid size: {}x{})", config.width, config.height);
         }
 
@@ -217,14 +920,19 @@ impl Renderer {
         crate::debug::update_status("✅ Renderer complete!");
 
         // Create brush rendering pipelines for both linear canvas and sRGB surface
-        let brush_pipeline = Self::create_brush_pipeline(&device, canvas_format);
+        let brush_shader_source = crate::brush_shader::build_brush_shader(None)
+            .expect("Built-in brush shader failed to build");
+        let brush_pipeline_layout = Self::create_brush_pipeline_layout(&device);
+        let (brush_pipeline, erase_pipeline, add_pipeline) =
+            Self::create_brush_pipelines(&device, &brush_pipeline_layout, canvas_format, &brush_shader_source, 1);
         debug::update_status("Brush pipeline created...");
         log::info!("✅ Brush pipeline created for format: {:?}", canvas_format);
 
         // Create uniform buffer for canvas size
         let brush_uniforms = BrushUniforms {
             canvas_size: [clamped_width as f32, clamped_height as f32],
-            _padding: [0.0; 2],
+            dither_amplitude: 0.0,
+            aa_mode: 0,
         };
         let brush_uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Brush Uniform Buffer"),
@@ -233,15 +941,54 @@ impl Renderer {
         });
         
         // Create bind group for uniforms (both pipelines share the same layout)
-        let brush_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Brush Bind Group"),
-            layout: &brush_pipeline.get_bind_group_layout(0),
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: brush_uniform_buffer.as_entire_binding(),
-            }],
-        });
+        let brush_bind_group = Self::create_brush_bind_group(&device, &brush_pipeline, &brush_uniform_buffer);
         
+        // Create grain texture (1x1 white = no grain until one is uploaded)
+        let grain_texture = Self::create_mask_texture(&device, &queue, "Brush Grain Texture", &[255], 1, 1);
+        let grain_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Brush Grain Sampler"),
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            address_mode_w: wgpu::AddressMode::Repeat,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        let grain_uniforms = GrainUniforms {
+            grain_size: [1.0, 1.0],
+            grain_scale: 1.0,
+            grain_enabled: 0,
+        };
+        let grain_uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Brush Grain Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[grain_uniforms]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        // Create stamp texture (1x1 white = plain procedural dab until one is uploaded)
+        let stamp_texture = Self::create_mask_texture(&device, &queue, "Brush Stamp Texture", &[255], 1, 1);
+        let stamp_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Brush Stamp Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let brush_texture_bind_group = Self::create_brush_texture_bind_group(
+            &device,
+            &brush_pipeline,
+            &grain_texture,
+            &grain_sampler,
+            &grain_uniform_buffer,
+            &stamp_texture,
+            &stamp_sampler,
+        );
+
         // Create canvas texture for accumulating strokes (uses LINEAR format)
         let (canvas_texture, canvas_view) = Self::create_canvas_texture(
             &device,
@@ -251,13 +998,32 @@ impl Renderer {
         );
         log::info!("✅ Canvas texture created: {}x{}, format: {:?}", clamped_width, clamped_height, canvas_format);
 
+        // Create stroke scratch layer and the pipeline that composites it into the canvas
+        let (stroke_texture, stroke_view) = Self::create_canvas_texture(
+            &device,
+            clamped_width,
+            clamped_height,
+            canvas_format,
+        );
+        let stroke_composite_pipeline = Self::create_stroke_composite_pipeline(&device, canvas_format);
+        let layer_composite_pipeline = Self::create_layer_composite_pipeline(&device, canvas_format);
+        let fill_pipeline = Self::create_fill_pipeline(&device, canvas_format);
+        let smudge_pipeline = Self::create_smudge_pipeline(&device, canvas_format);
+
         // Create blit pipeline for copying canvas to surface (handles color space conversion)
         let (blit_pipeline, blit_bind_group_layout) = Self::create_blit_pipeline(&device, surface_format);
         log::info!("✅ Blit pipeline created");
+
+        // Create comparison pipeline (renders offscreen in the canvas format)
+        let compare_pipeline = Self::create_compare_pipeline(&device, canvas_format);
         
         // Create sampler for canvas texture
-        let canvas_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            label: Some("Canvas Sampler"),
+        let canvas_sampler = Self::create_canvas_sampler(&device, WrapMode::default());
+
+        // Create reference image texture (1x1 transparent = no reference until one is loaded)
+        let reference_texture = Self::create_reference_texture(&device, &queue, &[0; 4], 1, 1);
+        let reference_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Reference Image Sampler"),
             address_mode_u: wgpu::AddressMode::ClampToEdge,
             address_mode_v: wgpu::AddressMode::ClampToEdge,
             address_mode_w: wgpu::AddressMode::ClampToEdge,
@@ -266,17 +1032,21 @@ impl Renderer {
             mipmap_filter: wgpu::FilterMode::Nearest,
             ..Default::default()
         });
+        let reference_view = reference_texture.create_view(&wgpu::TextureViewDescriptor::default());
         
         // Create blit uniform buffer (blend mode)
         // TODO: Set blend mode on app initialization and plumb through here
-        let blend_color_space = BlendColorSpace::Srgb; // Default to sRGB blending
-        let blit_uniforms = BlitUniforms {
-            blend_mode: match blend_color_space {
-                BlendColorSpace::Linear => 0,
-                BlendColorSpace::Srgb => 1,
-            },
-            _padding: [0; 3],
-        };
+        let blend_color_space = BlendColorSpace::default(); // Default to sRGB blending
+        let canvas_size = [clamped_width as f32, clamped_height as f32];
+        let blit_uniforms = Self::build_blit_uniforms(
+            blend_color_space,
+            0.0,
+            0.0,
+            &ViewTransform::IDENTITY,
+            canvas_size,
+            [0.0, 0.0],
+            canvas_size,
+        );
         let blit_uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Blit Uniform Buffer"),
             contents: bytemuck::cast_slice(&[blit_uniforms]),
@@ -300,45 +1070,137 @@ impl Renderer {
                     binding: 2,
                     resource: blit_uniform_buffer.as_entire_binding(),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&stroke_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(&reference_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::Sampler(&reference_sampler),
+                },
+                // No prediction yet: any canvas-sized view will do while it is hidden
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: wgpu::BindingResource::TextureView(&stroke_view),
+                },
             ],
         });
 
-        Self {
+        let layers = vec![Layer::new(&device, &layer_composite_pipeline, &stroke_view, (canvas_texture, canvas_view))];
+
+        Ok(Self {
             surface,
             device,
             queue,
             config,
             size,
             max_texture_dimension,
+            adapter_info,
+            device_lost,
+            surface_reconfigures: 0,
             canvas_format,
+            srgb_canvas: false,
             blend_color_space: blend_color_space,
+            max_opacity: 1.0,
+            view_transform: ViewTransform::IDENTITY,
             brush_pipeline,
+            erase_pipeline,
+            add_pipeline,
+            brush_pipeline_layout,
+            erase_mode: false,
+            antialiasing: AaMode::default(),
+            msaa_pipelines: None,
+            msaa_target: None,
+            msaa_formats,
+            blendable_formats,
+            brush_blend_mode: BrushBlendMode::Normal,
+            bloom_intensity: 0.0,
+            dithering: false,
+            surface_background: [0.0, 0.0, 0.0],
+            paper_color: [0.0; 4],
+            background_gradient: None,
+            export_includes_background: false,
+            export_hdr_mode: HdrExportMode::default(),
+            reference_texture,
+            reference_sampler,
+            reference_size: None,
+            reference_data: None,
+            reference_opacity: 1.0,
+            reference_visible: true,
             brush_uniform_buffer,
             brush_bind_group,
-            canvas_texture,
-            canvas_view,
+            custom_brush_mask: None,
+            grain_texture,
+            grain_sampler,
+            grain_uniform_buffer,
+            grain_source: None,
+            stamp_texture,
+            stamp_sampler,
+            stamp_source: None,
+            brush_texture_bind_group,
+            layers,
+            active_layer: 0,
+            layer_composite: None,
+            layer_composite_pipeline,
+            canvas_bleed: 0,
+            canvas_resolution: None,
+            export_includes_bleed: false,
+            stroke_texture,
+            stroke_view,
+            stroke_layer_active: false,
+            stroke_layer_opacity: 1.0,
+            stroke_layer_blend_mode: BrushBlendMode::Normal,
+            stroke_composite_pipeline,
+            fill_pipeline,
+            smudge_strength: None,
+            smudge_length: BrushParams::DEFAULT.smudge_length,
+            smudge_pipeline,
+            smudge_source: None,
+            smudge_carry: None,
+            smudge_carry_source: None,
+            prediction_overlay: None,
+            prediction_opacity: 0.0,
+            cursor_ring: None,
+            stabilizer_tether: None,
             blit_pipeline,
             blit_uniform_buffer,
             blit_bind_group,
             canvas_sampler,
-        }
+            wrap_mode: WrapMode::default(),
+            compare_pipeline,
+            dab_area_total: 0.0,
+            dirty_rect: Some(DirtyRect::full()),
+            last_dirty_fraction: 0.0,
+            layer_composite_current: std::cell::Cell::new(false),
+            dab_instance_buffer: Self::create_dab_instance_buffer(&device, INITIAL_DAB_INSTANCE_CAPACITY),
+            dab_instance_capacity: INITIAL_DAB_INSTANCE_CAPACITY,
+            dab_instance_reallocations: 0,
+            brush_batch: std::cell::Cell::new(None),
+            batch_instances: std::cell::Cell::new(0),
+            #[cfg(debug_assertions)]
+            frame_submits: std::cell::Cell::new(0),
+            #[cfg(debug_assertions)]
+            last_frame_submits: 0,
+            last_present_time_ms: None,
+            smoothed_fps: 0.0,
+        })
     }
 
-    /// Create the brush rendering pipeline
-    fn create_brush_pipeline(device: &wgpu::Device, target_format: wgpu::TextureFormat) -> wgpu::RenderPipeline {
-        // Load shader
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Brush Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/brush.wgsl").into()),
-        });
-        debug::update_status("Creating brush pipeline...");
-        
+    /// Create the pipeline layout every brush pipeline shares
+    ///
+    /// Bind groups made for one brush pipeline work with all of them (single-sampled and
+    /// MSAA, paint, erase, and additive).
+    fn create_brush_pipeline_layout(device: &wgpu::Device) -> wgpu::PipelineLayout {
         // Create bind group layout for uniforms
         let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("Brush Bind Group Layout"),
             entries: &[wgpu::BindGroupLayoutEntry {
                 binding: 0,
-                visibility: wgpu::ShaderStages::VERTEX,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
                 ty: wgpu::BindingType::Buffer {
                     ty: wgpu::BufferBindingType::Uniform,
                     has_dynamic_offset: false,
@@ -348,64 +1210,213 @@ impl Renderer {
             }],
         });
         debug::update_status("Brush bind group layout created...");
-        
-        // Create pipeline layout
-        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Brush Pipeline Layout"),
-            bind_group_layouts: &[&bind_group_layout],
-            push_constant_ranges: &[],
-        });
-
-        debug::update_status("Creating vertex buffer layout...");
-        
-        // Vertex buffer layout for dab instances
-        let vertex_buffer_layout = wgpu::VertexBufferLayout {
-            array_stride: std::mem::size_of::<DabInstance>() as wgpu::BufferAddress,
-            step_mode: wgpu::VertexStepMode::Instance,
-            attributes: &[
-                // position
-                wgpu::VertexAttribute {
-                    offset: 0,
-                    shader_location: 0,
-                    format: wgpu::VertexFormat::Float32x2,
-                },
-                // size
-                wgpu::VertexAttribute {
-                    offset: 8,
-                    shader_location: 1,
-                    format: wgpu::VertexFormat::Float32,
-                },
-                // opacity
-                wgpu::VertexAttribute {
-                    offset: 12,
-                    shader_location: 2,
-                    format: wgpu::VertexFormat::Float32,
-                },
-                // color
-                wgpu::VertexAttribute {
-                    offset: 16,
-                    shader_location: 3,
-                    format: wgpu::VertexFormat::Float32x4,
-                },
-                // hardness
-                wgpu::VertexAttribute {
-                    offset: 32,
-                    shader_location: 4,
-                    format: wgpu::VertexFormat::Float32,
-                },
-            ],
-        };
 
-        debug::update_status("Creating brush render pipeline...");
-        
-        // Create the render pipeline
-        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Brush Pipeline"),
+        // Create bind group layout for brush mask textures (grain texture, sampler, uniforms,
+        // stamp texture, stamp sampler)
+        let texture_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Brush Texture Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        
+        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Brush Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout, &texture_bind_group_layout],
+            push_constant_ranges: &[],
+        })
+    }
+
+    /// Create the brush paint, erase, and additive pipelines
+    /// All use the shared brush pipeline layout so the same bind groups work with any of them
+    /// Returns the (paint, erase, additive) pipelines
+    fn create_brush_pipelines(
+        device: &wgpu::Device,
+        pipeline_layout: &wgpu::PipelineLayout,
+        target_format: wgpu::TextureFormat,
+        shader_source: &str,
+        sample_count: u32,
+    ) -> (wgpu::RenderPipeline, wgpu::RenderPipeline, wgpu::RenderPipeline) {
+        // Load shader (built from the brush.wgsl template, see brush_shader.rs)
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Brush Shader"),
+            source: wgpu::ShaderSource::Wgsl(shader_source.into()),
+        });
+        debug::update_status("Creating brush pipeline...");
+
+        debug::update_status("Creating vertex buffer layout...");
+        
+        debug::update_status("Creating brush render pipeline...");
+
+        // Premultiplied alpha "over" blend mode
+        // Source RGB is already multiplied by alpha in shader. The max opacity ceiling
+        // can't be applied here (it depends on the canvas under the dab): dabs under one
+        // are drawn into the stroke layer and blended in by `encode_stroke_layer_blend`.
+        let paint_blend = wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING;
+
+        // "Destination out": the dab's alpha removes existing paint (color is ignored)
+        // The opacity ceiling does not apply when erasing.
+        let erase_blend = wgpu::BlendState {
+            color: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::Zero,
+                dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                operation: wgpu::BlendOperation::Add,
+            },
+            alpha: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::Zero,
+                dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                operation: wgpu::BlendOperation::Add,
+            },
+        };
+
+        // Additive: color adds on top of the canvas with no clamping, so the float canvas
+        // can hold values above 1.0 (HDR). The blend constant (max opacity) scales the
+        // dab's color. Alpha uses "over" (in its dst-over form, so the constant can't
+        // lower it): coverage never drops and saturates at 1.0.
+        let add_blend = wgpu::BlendState {
+            color: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::Constant,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+            alpha: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::OneMinusDstAlpha,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+        };
+
+        let create_pipeline = |label: &str, blend: wgpu::BlendState| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[DAB_INSTANCE_LAYOUT],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: target_format,
+                        blend: Some(blend),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: None,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState {
+                    count: sample_count,
+                    ..Default::default()
+                },
+                multiview: None,
+                cache: None,
+            })
+        };
+
+        (
+            create_pipeline("Brush Pipeline", paint_blend),
+            create_pipeline("Erase Pipeline", erase_blend),
+            create_pipeline("Additive Brush Pipeline", add_blend),
+        )
+    }
+
+    /// Create the pipelines for drawing dabs with 4x MSAA into a target of the given format
+    fn create_msaa_pipelines(
+        device: &wgpu::Device,
+        brush_pipeline_layout: &wgpu::PipelineLayout,
+        target_format: wgpu::TextureFormat,
+        brush_shader_source: &str,
+    ) -> MsaaPipelines {
+        let (paint, erase, add) = Self::create_brush_pipelines(
+            device,
+            brush_pipeline_layout,
+            target_format,
+            &crate::brush_shader::with_sample_shading(brush_shader_source),
+            MSAA_SAMPLE_COUNT,
+        );
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("MSAA Load Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/msaa_load.wgsl").into()),
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("MSAA Load Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            }],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("MSAA Load Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let load = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("MSAA Load Pipeline"),
             layout: Some(&pipeline_layout),
             vertex: wgpu::VertexState {
                 module: &shader,
                 entry_point: Some("vs_main"),
-                buffers: &[vertex_buffer_layout],
+                buffers: &[],
                 compilation_options: Default::default(),
             },
             fragment: Some(wgpu::FragmentState {
@@ -413,20 +1424,8 @@ impl Renderer {
                 entry_point: Some("fs_main"),
                 targets: &[Some(wgpu::ColorTargetState {
                     format: target_format,
-                    blend: Some(wgpu::BlendState {
-                        // Premultiplied alpha blend mode
-                        // Source RGB is already multiplied by alpha in shader
-                        color: wgpu::BlendComponent {
-                            src_factor: wgpu::BlendFactor::One,
-                            dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
-                            operation: wgpu::BlendOperation::Add,
-                        },
-                        alpha: wgpu::BlendComponent {
-                            src_factor: wgpu::BlendFactor::One,
-                            dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
-                            operation: wgpu::BlendOperation::Add,
-                        },
-                    }),
+                    // Every sample is replaced with the target's pixel
+                    blend: None,
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
                 compilation_options: Default::default(),
@@ -441,77 +1440,110 @@ impl Renderer {
                 conservative: false,
             },
             depth_stencil: None,
-            multisample: wgpu::MultisampleState::default(),
+            multisample: wgpu::MultisampleState {
+                count: MSAA_SAMPLE_COUNT,
+                ..Default::default()
+            },
             multiview: None,
             cache: None,
-        })
+        });
+
+        MsaaPipelines { paint, erase, add, load }
     }
 
-    /// Create canvas texture for accumulating strokes
-    fn create_canvas_texture(
-        device: &wgpu::Device,
-        width: u32,
-        height: u32,
-        format: wgpu::TextureFormat,
-    ) -> (wgpu::Texture, wgpu::TextureView) {
-        let texture = device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("Canvas Texture"),
-            size: wgpu::Extent3d {
-                width,
-                height,
-                depth_or_array_layers: 1,
-            },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT 
-                | wgpu::TextureUsages::TEXTURE_BINDING
-                | wgpu::TextureUsages::COPY_SRC,
-            view_formats: &[],
+    /// Create the comparison pipeline for compositing the canvas against a reference
+    fn create_compare_pipeline(device: &wgpu::Device, target_format: wgpu::TextureFormat) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Compare Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/compare.wgsl").into()),
         });
-        
-        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        
-        (texture, view)
-    }
 
-    /// Recreate the blit bind group with current canvas view and uniform buffer
-    fn recreate_blit_bind_group(&mut self) {
-        self.blit_bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Blit Bind Group"),
-            layout: &self.blit_pipeline.get_bind_group_layout(0),
+        let texture_entry = |binding: u32| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            },
+            count: None,
+        };
+
+        // Canvas texture, reference texture, sampler, uniforms
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Compare Bind Group Layout"),
             entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&self.canvas_view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&self.canvas_sampler),
-                },
-                wgpu::BindGroupEntry {
+                texture_entry(0),
+                texture_entry(1),
+                wgpu::BindGroupLayoutEntry {
                     binding: 2,
-                    resource: self.blit_uniform_buffer.as_entire_binding(),
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
                 },
             ],
         });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Compare Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Compare Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: target_format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        })
     }
 
-    /// Create the blit pipeline for copying canvas to surface
-    fn create_blit_pipeline(
-        device: &wgpu::Device,
-        target_format: wgpu::TextureFormat,
-    ) -> (wgpu::RenderPipeline, wgpu::BindGroupLayout) {
-        // Load shader
+    /// Create the pipeline that composites the stroke scratch layer into the canvas
+    fn create_stroke_composite_pipeline(device: &wgpu::Device, target_format: wgpu::TextureFormat) -> wgpu::RenderPipeline {
         let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Blit Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/blit.wgsl").into()),
+            label: Some("Stroke Composite Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/stroke_composite.wgsl").into()),
         });
-        
-        // Create bind group layout for texture, sampler, and uniforms
+
         let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Blit Bind Group Layout"),
+            label: Some("Stroke Composite Bind Group Layout"),
             entries: &[
                 wgpu::BindGroupLayoutEntry {
                     binding: 0,
@@ -526,12 +1558,6 @@ impl Renderer {
                 wgpu::BindGroupLayoutEntry {
                     binding: 1,
                     visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 2,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Uniform,
                         has_dynamic_offset: false,
@@ -541,17 +1567,15 @@ impl Renderer {
                 },
             ],
         });
-        
-        // Create pipeline layout
+
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Blit Pipeline Layout"),
+            label: Some("Stroke Composite Pipeline Layout"),
             bind_group_layouts: &[&bind_group_layout],
             push_constant_ranges: &[],
         });
-        
-        // Create the render pipeline
-        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Blit Pipeline"),
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Stroke Composite Pipeline"),
             layout: Some(&pipeline_layout),
             vertex: wgpu::VertexState {
                 module: &shader,
@@ -564,7 +1588,8 @@ impl Renderer {
                 entry_point: Some("fs_main"),
                 targets: &[Some(wgpu::ColorTargetState {
                     format: target_format,
-                    blend: None,
+                    // Premultiplied "over": stroke layer on top of existing canvas content
+                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
                 compilation_options: Default::default(),
@@ -582,308 +1607,3921 @@ impl Renderer {
             multisample: wgpu::MultisampleState::default(),
             multiview: None,
             cache: None,
-        });
-        
-        (pipeline, bind_group_layout)
+        })
     }
 
-    /// Resize the surface
-    pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
-        if new_size.width > 0 && new_size.height > 0 {
-            self.size = new_size;
-            
-            // Clamp to max texture dimension
-            let clamped_width = new_size.width.min(self.max_texture_dimension);
-            let clamped_height = new_size.height.min(self.max_texture_dimension);
-            
-            if clamped_width != new_size.width || clamped_height != new_size.height {
-                log::warn!("⚠️ Resize {}x{} exceeds max texture size {}, clamping to {}x{}", 
-                           new_size.width, new_size.height, self.max_texture_dimension, 
-                           clamped_width, clamped_height);
-            }
-            
-            self.config.width = clamped_width;
-            self.config.height = clamped_height;
-            self.surface.configure(&self.device, &self.config);
+    /// Create the pipeline that draws bucket fill spans into the canvas
+    fn create_fill_pipeline(device: &wgpu::Device, target_format: wgpu::TextureFormat) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Fill Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/fill.wgsl").into()),
+        });
 
-            // Recreate canvas texture with new size
-            let (canvas_texture, canvas_view) = Self::create_canvas_texture(
-                &self.device,
-                clamped_width,
-                clamped_height,
-                self.canvas_format,
-            );
-            self.canvas_texture = canvas_texture;
-            self.canvas_view = canvas_view;
-            
-            // Recreate blit bind group with new canvas view
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Fill Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Fill Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Fill Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                // One (x_start, x_end, y) span per instance
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &[wgpu::VertexAttribute {
+                        offset: 0,
+                        shader_location: 0,
+                        format: wgpu::VertexFormat::Float32x3,
+                    }],
+                }],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: target_format,
+                    // Premultiplied "over", like paint dabs: a translucent fill lets the
+                    // region show through
+                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        })
+    }
+
+    /// Create the pipeline that draws smudge dabs into the canvas and their carried color
+    /// into the smudge carry texture
+    fn create_smudge_pipeline(device: &wgpu::Device, target_format: wgpu::TextureFormat) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Smudge Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/smudge.wgsl").into()),
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Smudge Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Smudge Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Smudge Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[DAB_INSTANCE_LAYOUT],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                // The canvas, then the carried color. The shader mixes the old and carried
+                // colors itself
+                targets: &[
+                    Some(wgpu::ColorTargetState {
+                        format: target_format,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    }),
+                    Some(wgpu::ColorTargetState {
+                        format: target_format,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    }),
+                ],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        })
+    }
+
+    /// Create the pipeline that composites a layer (and its stroke layer) into the layer composite
+    fn create_layer_composite_pipeline(device: &wgpu::Device, target_format: wgpu::TextureFormat) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Layer Composite Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/layer_composite.wgsl").into()),
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Layer Composite Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Layer Composite Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Layer Composite Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: target_format,
+                    // Premultiplied "over": each layer on top of the layers beneath it
+                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        })
+    }
+
+    /// Create the pipeline that converts canvas contents into `to` (see
+    /// `set_blend_color_space_preserving`)
+    /// Only needed when switching, so it is built on demand rather than kept around.
+    fn create_canvas_convert_pipeline(
+        device: &wgpu::Device,
+        target_format: wgpu::TextureFormat,
+        to: BlendColorSpace,
+    ) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Canvas Convert Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/canvas_convert.wgsl").into()),
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Canvas Convert Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            }],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Canvas Convert Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let fragment_entry_point = match to {
+            BlendColorSpace::Linear => "fs_to_linear",
+            BlendColorSpace::Srgb => "fs_to_srgb",
+        };
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Canvas Convert Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some(fragment_entry_point),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: target_format,
+                    // Every pixel is replaced with its converted value
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        })
+    }
+
+    /// Create canvas texture for accumulating strokes
+    fn create_canvas_texture(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+    ) -> (wgpu::Texture, wgpu::TextureView) {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Canvas Texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT 
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        
+        (texture, view)
+    }
+
+    /// Create a reference image texture (Rgba8UnormSrgb) from 8-bit sRGB RGBA data
+    fn create_reference_texture(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        data: &[u8],
+        width: u32,
+        height: u32,
+    ) -> wgpu::Texture {
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Reference Image Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            data,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(width * 4),
+                rows_per_image: Some(height),
+            },
+            size,
+        );
+
+        texture
+    }
+
+    /// Create a single-channel mask texture (R8Unorm) from 8-bit grayscale data
+    fn create_mask_texture(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        label: &str,
+        data: &[u8],
+        width: u32,
+        height: u32,
+    ) -> wgpu::Texture {
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            data,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(width),
+                rows_per_image: Some(height),
+            },
+            size,
+        );
+
+        texture
+    }
+
+    /// Create the brush uniform bind group (canvas size and dither amplitude)
+    fn create_brush_bind_group(
+        device: &wgpu::Device,
+        brush_pipeline: &wgpu::RenderPipeline,
+        brush_uniform_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Brush Bind Group"),
+            layout: &brush_pipeline.get_bind_group_layout(0),
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: brush_uniform_buffer.as_entire_binding(),
+            }],
+        })
+    }
+
+    /// Create the brush texture bind group (grain mask, sampler, grain uniforms, stamp mask, stamp sampler)
+    fn create_brush_texture_bind_group(
+        device: &wgpu::Device,
+        brush_pipeline: &wgpu::RenderPipeline,
+        grain_texture: &wgpu::Texture,
+        grain_sampler: &wgpu::Sampler,
+        grain_uniform_buffer: &wgpu::Buffer,
+        stamp_texture: &wgpu::Texture,
+        stamp_sampler: &wgpu::Sampler,
+    ) -> wgpu::BindGroup {
+        let grain_view = grain_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let stamp_view = stamp_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Brush Texture Bind Group"),
+            layout: &brush_pipeline.get_bind_group_layout(1),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&grain_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(grain_sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: grain_uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&stamp_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::Sampler(stamp_sampler),
+                },
+            ],
+        })
+    }
+
+    /// Rebuild the brush texture bind group after the grain or stamp texture changes
+    fn recreate_brush_texture_bind_group(&mut self) {
+        self.brush_texture_bind_group = Self::create_brush_texture_bind_group(
+            &self.device,
+            &self.brush_pipeline,
+            &self.grain_texture,
+            &self.grain_sampler,
+            &self.grain_uniform_buffer,
+            &self.stamp_texture,
+            &self.stamp_sampler,
+        );
+    }
+
+    /// Create the sampler the blit and comparison passes read the canvas through
+    fn create_canvas_sampler(device: &wgpu::Device, wrap_mode: WrapMode) -> wgpu::Sampler {
+        let address_mode = wrap_mode.address_mode();
+        device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Canvas Sampler"),
+            address_mode_u: address_mode,
+            address_mode_v: address_mode,
+            address_mode_w: address_mode,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        })
+    }
+
+    /// Recreate the blit bind group with current display view, reference image, prediction
+    /// overlay, and uniform buffer
+    fn recreate_blit_bind_group(&mut self) {
+        let reference_view = self.reference_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        // Without an overlay the prediction is hidden, so the stroke layer stands in
+        let prediction_view = match &self.prediction_overlay {
+            Some((_, view)) => view,
+            None => &self.stroke_view,
+        };
+        self.blit_bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Blit Bind Group"),
+            layout: &self.blit_pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(self.display_view()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.canvas_sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: self.blit_uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&self.stroke_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(&reference_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::Sampler(&self.reference_sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: wgpu::BindingResource::TextureView(prediction_view),
+                },
+            ],
+        });
+    }
+
+    /// Create the blit pipeline for copying canvas to surface
+    fn create_blit_pipeline(
+        device: &wgpu::Device,
+        target_format: wgpu::TextureFormat,
+    ) -> (wgpu::RenderPipeline, wgpu::BindGroupLayout) {
+        // Load shader
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Blit Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/blit.wgsl").into()),
+        });
+        
+        // Create bind group layout for texture, sampler, and uniforms
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Blit Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 6,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+            ],
+        });
+        
+        // Create pipeline layout
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Blit Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        
+        // Create the render pipeline
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Blit Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: target_format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+        
+        (pipeline, bind_group_layout)
+    }
+
+    /// Resize the surface
+    /// Resizing to the current size does nothing, so redundant resize events don't
+    /// reconfigure the surface or recreate the canvas.
+    ///
+    /// With a fixed canvas resolution (see `set_canvas_resolution`) only the surface changes
+    /// and the canvas is untouched. Otherwise the canvas follows the surface: layer contents
+    /// are kept, anchored at the top-left of the visible canvas, so a smaller canvas crops
+    /// the right and bottom and a larger one adds transparent area there. Any stroke layer
+    /// in progress is committed first. The undo base is kept the same way, so the undo
+    /// history survives.
+    pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
+        if new_size == self.size {
+            log::debug!("Resize to current size {}x{}, skipping", new_size.width, new_size.height);
+            return;
+        }
+        if new_size.width > 0 && new_size.height > 0 {
+            self.size = new_size;
+            
+            // Clamp to max texture dimension
+            let clamped_width = new_size.width.min(self.max_texture_dimension);
+            let clamped_height = new_size.height.min(self.max_texture_dimension);
+            
+            if clamped_width != new_size.width || clamped_height != new_size.height {
+                log::warn!("⚠️ Resize {}x{} exceeds max texture size {}, clamping to {}x{}", 
+                           new_size.width, new_size.height, self.max_texture_dimension, 
+                           clamped_width, clamped_height);
+            }
+            
+            let old_contents = self.canvas_resolution.is_none().then(|| self.take_layer_contents());
+            self.config.width = clamped_width;
+            self.config.height = clamped_height;
+            if let Some(surface) = &self.surface {
+                surface.configure(&self.device, &self.config);
+            }
+
+            match old_contents {
+                Some(old_contents) => {
+                    self.recreate_canvas_textures_keeping(old_contents);
+                    log::debug!("Surface and canvas resized to: {}x{}, format: {:?}", clamped_width, clamped_height, self.canvas_format);
+                }
+                None => {
+                    // Same canvas, shown on a surface of a different size
+                    self.write_blit_uniforms();
+                    log::debug!("Surface resized to: {}x{}", clamped_width, clamped_height);
+                }
+            }
+        }
+    }
+
+    /// Commit any stroke layer and hold on to the layer textures, undo bases, and bleed, so
+    /// their contents can be copied back after the canvas is reallocated
+    fn take_layer_contents(&mut self) -> SavedContents {
+        self.commit_stroke_layer();
+        SavedContents {
+            layers: self.layers.iter().map(|layer| layer.texture.clone()).collect(),
+            undo_bases: self.layers.iter().map(|layer| layer.undo_base.as_ref().map(|(texture, _)| texture.clone())).collect(),
+            bleed: self.bleed_px(),
+        }
+    }
+
+    /// Recreate the canvas textures, then copy back the contents saved by `take_layer_contents`
+    fn recreate_canvas_textures_keeping(&mut self, saved: SavedContents) {
+        let has_strokes: Vec<bool> = self.layers.iter().map(|layer| layer.has_strokes).collect();
+        self.recreate_canvas_textures();
+        let size = self.canvas_texture().size();
+        for (layer, undo_base) in self.layers.iter_mut().zip(&saved.undo_bases) {
+            if undo_base.is_some() {
+                layer.undo_base = Some(Self::create_canvas_texture(&self.device, size.width, size.height, self.canvas_format));
+            }
+        }
+        self.copy_layer_contents(&saved);
+        for (layer, has_strokes) in self.layers.iter_mut().zip(has_strokes) {
+            layer.has_strokes = has_strokes;
+        }
+    }
+
+    /// Recreate the layer and stroke layer textures for the current visible size and bleed
+    /// Existing layer content and any in-progress stroke layer are dropped; the layers
+    /// themselves (count, visibility, opacity) are kept.
+    fn recreate_canvas_textures(&mut self) {
+        // Fit the bleed within the max texture size
+        let [width, height] = self.visible_canvas_size();
+        let max_bleed = (self.max_texture_dimension - width.max(height)) / 2;
+        let bleed = self.canvas_bleed.min(max_bleed);
+        if bleed < self.canvas_bleed {
+            log::warn!("⚠️ Canvas bleed {}px exceeds max texture size, using {}px", self.canvas_bleed, bleed);
+        }
+        let texture_width = width + 2 * bleed;
+        let texture_height = height + 2 * bleed;
+
+        for layer in &mut self.layers {
+            let (texture, view) = Self::create_canvas_texture(
+                &self.device,
+                texture_width,
+                texture_height,
+                self.canvas_format,
+            );
+            layer.texture = texture;
+            layer.view = view;
+            layer.has_strokes = false;
+            // The undo base is a snapshot of the old canvas
+            layer.undo_base = None;
+        }
+        self.dab_area_total = 0.0;
+
+        // Recreate stroke layer to match (any in-progress stroke layer is dropped)
+        let (stroke_texture, stroke_view) = Self::create_canvas_texture(
+            &self.device,
+            texture_width,
+            texture_height,
+            self.canvas_format,
+        );
+        self.stroke_texture = stroke_texture;
+        self.stroke_view = stroke_view;
+        self.rebind_layers();
+        self.stroke_layer_active = false;
+        self.prediction_overlay = None;
+        self.prediction_opacity = 0.0;
+        
+        // Recreate the layer composite target (and blit bind group) at the new size
+        self.layer_composite = None;
+        self.update_layer_composite_target();
+        
+        // Update uniform buffer with new canvas texture size (dabs are drawn in texture space)
+        self.write_brush_uniforms();
+    }
+
+    /// Copy saved layer textures and undo bases into the current ones, keeping the top-left
+    /// of the visible canvas in place (the bleed margin may have changed size)
+    fn copy_layer_contents(&self, saved: &SavedContents) {
+        let new_bleed = self.bleed_px();
+        let shared_bleed = saved.bleed.min(new_bleed);
+        let source_origin = saved.bleed - shared_bleed;
+        let destination_origin = new_bleed - shared_bleed;
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Layer Resize Copy Encoder"),
+        });
+        let layer_pairs = saved.layers.iter().zip(self.layers.iter().map(|layer| &layer.texture));
+        let undo_base_pairs = saved.undo_bases.iter().zip(&self.layers).filter_map(|(old, layer)| {
+            Some((old.as_ref()?, &layer.undo_base.as_ref()?.0))
+        });
+        for (old_texture, new_texture) in layer_pairs.chain(undo_base_pairs) {
+            let copy_size = wgpu::Extent3d {
+                width: (old_texture.width() - source_origin).min(new_texture.width() - destination_origin),
+                height: (old_texture.height() - source_origin).min(new_texture.height() - destination_origin),
+                depth_or_array_layers: 1,
+            };
+            encoder.copy_texture_to_texture(
+                wgpu::TexelCopyTextureInfo {
+                    texture: old_texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d { x: source_origin, y: source_origin, z: 0 },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                wgpu::TexelCopyTextureInfo {
+                    texture: new_texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d { x: destination_origin, y: destination_origin, z: 0 },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                copy_size,
+            );
+        }
+        self.submit(encoder);
+    }
+
+    /// Write the canvas texture size and dither amplitude to the brush uniform buffer
+    fn write_brush_uniforms(&self) {
+        // Batched draws read the uniforms as of the submit, so they go first
+        self.flush_brush_batch();
+        // Dithering only helps when the canvas itself quantizes to 8 bits
+        let dither_amplitude = if self.dithering && self.canvas_is_8bit() { DITHER_AMPLITUDE_8BIT } else { 0.0 };
+        let brush_uniforms = BrushUniforms {
+            canvas_size: [self.canvas_texture().width() as f32, self.canvas_texture().height() as f32],
+            dither_amplitude,
+            aa_mode: if self.antialiasing == AaMode::Analytic { 1 } else { 0 },
+        };
+        self.queue.write_buffer(
+            &self.brush_uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[brush_uniforms]),
+        );
+    }
+
+    /// Canvas texture format for the given storage option and blend color space
+    ///
+    /// The default canvas is Rgba16Float. An 8-bit sRGB canvas always stores sRGB-encoded
+    /// bytes: Rgba8Unorm when blending in sRGB (dabs accumulate on the encoded values, as
+    /// 8-bit painting apps do), Rgba8UnormSrgb when blending in linear (the hardware decodes
+    /// before blending and encodes after). Either way sampling yields blend-space values.
+    ///
+    /// If the adapter can't blend into that format, the other storage option's format is
+    /// used instead (the canvas can't be painted on without blending).
+    fn canvas_format_for(
+        srgb_canvas: bool,
+        blend_color_space: BlendColorSpace,
+        blendable_formats: &[wgpu::TextureFormat],
+    ) -> wgpu::TextureFormat {
+        let format_for = |srgb_canvas| match (srgb_canvas, blend_color_space) {
+            (false, _) => wgpu::TextureFormat::Rgba16Float,
+            (true, BlendColorSpace::Srgb) => wgpu::TextureFormat::Rgba8Unorm,
+            (true, BlendColorSpace::Linear) => wgpu::TextureFormat::Rgba8UnormSrgb,
+        };
+        let preferred = format_for(srgb_canvas);
+        if blendable_formats.contains(&preferred) {
+            return preferred;
+        }
+        let fallback = format_for(!srgb_canvas);
+        if blendable_formats.contains(&fallback) {
+            log::warn!("{:?} canvas is not blendable on this adapter, using {:?}", preferred, fallback);
+            return fallback;
+        }
+        log::warn!("No blendable canvas format on this adapter, using {:?} anyway", preferred);
+        preferred
+    }
+
+    /// Switch the canvas to the format for the current storage option and blend color space
+    ///
+    /// Everything that renders into the canvas format is rebuilt. A format change recreates
+    /// the canvas, which clears it.
+    fn update_canvas_format(&mut self) {
+        let format = Self::canvas_format_for(self.srgb_canvas, self.blend_color_space, &self.blendable_formats);
+        if format == self.canvas_format {
+            return;
+        }
+        log::info!("Switching canvas format from {:?} to {:?}", self.canvas_format, format);
+        self.canvas_format = format;
+        if self.antialiasing == AaMode::Msaa4x && !self.msaa_supported() {
+            log::warn!("4x MSAA is not supported for a {:?} canvas, using analytic antialiasing", format);
+            self.antialiasing = AaMode::Analytic;
+            self.write_brush_uniforms();
+        }
+
+        let source = crate::brush_shader::build_brush_shader(self.custom_brush_mask.as_deref())
+            .expect("Current brush shader failed to rebuild");
+        self.rebuild_brush_pipelines(&source);
+        self.stroke_composite_pipeline = Self::create_stroke_composite_pipeline(&self.device, format);
+        self.layer_composite_pipeline = Self::create_layer_composite_pipeline(&self.device, format);
+        self.fill_pipeline = Self::create_fill_pipeline(&self.device, format);
+        self.smudge_pipeline = Self::create_smudge_pipeline(&self.device, format);
+        self.compare_pipeline = Self::create_compare_pipeline(&self.device, format);
+        self.recreate_canvas_textures();
+    }
+
+    /// Check whether the canvas stores 8 bits per channel (and so bands on soft gradients)
+    fn canvas_is_8bit(&self) -> bool {
+        matches!(
+            self.canvas_format,
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb
+        )
+    }
+
+    /// Render brush dabs to the canvas texture
+    ///
+    /// The draws join the open brush batch and reach the GPU with the next submit (the
+    /// frame's blit, a readback, or any other GPU work), so the calls in one frame share a
+    /// single submit.
+    pub fn render_dabs(&mut self, dabs: &[BrushDab]) {
+        if dabs.is_empty() {
+            return;
+        }
+
+        // Render dabs to the stroke layer if one is active (erasing and additive dabs always
+        // go to the canvas). The stroke layer builds coverage freely; its opacity is applied
+        // when composited
+        let to_stroke_layer = self.stroke_layer_active && self.dabs_use_stroke_layer();
+        self.ensure_msaa_target();
+        self.ensure_smudge_source();
+        let instances = self.upload_dab_instances(&[dabs]).remove(0);
+        let mut encoder = self.take_brush_batch();
+        if self.smudge_strength.is_some() {
+            self.encode_smudge_dabs(&mut encoder, dabs, instances);
+        } else {
+            self.encode_dabs(&mut encoder, instances, to_stroke_layer);
+        }
+
+        self.brush_batch.set(Some(encoder));
+        self.record_dab_stats(dabs);
+        log::debug!("Rendered {} brush dabs", dabs.len());
+    }
+
+    /// Draw recorded strokes onto the canvas again, in order, in a single encoder
+    ///
+    /// Used by undo/redo to re-composite the canvas. Every stroke is drawn with the current
+    /// brush settings (erase mode, blend mode, max opacity). If a stroke layer is active,
+    /// each stroke goes through it and is composited on its own at the layer's opacity, as
+    /// if drawn and committed one at a time; the layer is left committed.
+    pub fn replay_dabs(&mut self, strokes: &[&[BrushDab]]) {
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Replay Dabs Encoder"),
+        });
+
+        let per_stroke = self.stroke_layer_active && self.dabs_use_stroke_layer();
+        // Every stroke is uploaded up front: the draws share one instance buffer and one submit
+        let stroke_instances = self.upload_dab_instances(strokes);
+        self.ensure_msaa_target();
+        self.ensure_smudge_source();
+        let mut dab_count = 0;
+        for (i, (dabs, instances)) in strokes.iter().zip(stroke_instances).enumerate() {
+            if self.smudge_strength.is_some() {
+                self.encode_smudge_dabs(&mut encoder, dabs, instances);
+            } else if per_stroke {
+                // The layer arrives freshly cleared from `begin_stroke_layer`
+                if i > 0 {
+                    self.encode_stroke_layer_clear(&mut encoder);
+                }
+                self.encode_dabs(&mut encoder, instances, true);
+                self.encode_stroke_layer_composite(&mut encoder);
+            } else {
+                self.encode_dabs(&mut encoder, instances, false);
+            }
+            dab_count += dabs.len();
+        }
+
+        self.submit(encoder);
+        for dabs in strokes {
+            self.record_dab_stats(dabs);
+        }
+        if self.stroke_layer_active {
+            self.stroke_layer_active = false;
+            self.write_blit_uniforms();
+        }
+        log::debug!("Replayed {} strokes ({} dabs)", strokes.len(), dab_count);
+    }
+
+    /// Show predicted dabs over the canvas, replacing the previous prediction
+    ///
+    /// The dabs are drawn into a transient overlay with the current brush settings, as they
+    /// would land on the canvas, and the overlay is shown over the canvas on display only:
+    /// it is never drawn into the canvas, read back, or exported. Only plain painting is
+    /// predicted; erasing, additive, and smudge dabs would need the canvas under them. An
+    /// empty slice hides the prediction.
+    pub fn render_prediction(&mut self, dabs: &[BrushDab]) {
+        let opacity = if dabs.is_empty() || !self.dabs_use_stroke_layer() {
+            0.0
+        } else if self.stroke_layer_active {
+            self.stroke_layer_opacity
+        } else {
+            1.0
+        };
+        if opacity == 0.0 {
+            if self.prediction_opacity != 0.0 {
+                self.prediction_opacity = 0.0;
+                self.write_blit_uniforms();
+            }
+            return;
+        }
+
+        self.ensure_prediction_overlay();
+        self.ensure_msaa_target();
+        let instances = self.upload_dab_instances(&[dabs]).remove(0);
+        let Some((_, overlay_view)) = &self.prediction_overlay else {
+            return;
+        };
+
+        let mut encoder = self.take_brush_batch();
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Clear Prediction Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: overlay_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        // Only painting is predicted, which builds up freely whatever the blend constant
+        self.encode_dabs_into(&mut encoder, instances, overlay_view, 1.0);
+        self.brush_batch.set(Some(encoder));
+
+        if self.prediction_opacity != opacity {
+            self.prediction_opacity = opacity;
+            self.write_blit_uniforms();
+        }
+        log::debug!("Rendered {} predicted dabs", dabs.len());
+    }
+
+    /// Check whether a stroke prediction is currently shown
+    pub fn has_prediction(&self) -> bool {
+        self.prediction_opacity > 0.0
+    }
+
+    /// Show a brush outline at a canvas position, or hide it with None
+    ///
+    /// The ring is drawn by the blit over everything else, in canvas space so it follows
+    /// the view, with a line one surface pixel wide whatever the zoom. It is display only:
+    /// never drawn into the canvas, read back, or exported.
+    pub fn set_cursor_ring(&mut self, ring: Option<CursorRing>) {
+        let ring = ring.map(|ring| CursorRing {
+            radius: ring.radius.max(0.0),
+            hardness: ring.hardness.clamp(0.0, 1.0),
+            ..ring
+        });
+        if self.cursor_ring != ring {
+            self.cursor_ring = ring;
+            self.write_blit_uniforms();
+        }
+    }
+
+    /// Get the brush outline currently shown, if any
+    pub fn cursor_ring(&self) -> Option<CursorRing> {
+        self.cursor_ring
+    }
+
+    /// Show a line from the pen to the stabilized point the ink follows, or hide it with None
+    ///
+    /// Given as `[pen, ink]` in canvas pixels. Drawn by the blit like the cursor ring, so it
+    /// is display only as well.
+    pub fn set_stabilizer_tether(&mut self, tether: Option<[[f32; 2]; 2]>) {
+        if self.stabilizer_tether != tether {
+            self.stabilizer_tether = tether;
+            self.write_blit_uniforms();
+        }
+    }
+
+    /// Get the stabilizer tether currently shown, if any
+    pub fn stabilizer_tether(&self) -> Option<[[f32; 2]; 2]> {
+        self.stabilizer_tether
+    }
+
+    /// Make sure the prediction overlay matches the canvas
+    fn ensure_prediction_overlay(&mut self) {
+        let size = self.canvas_texture().size();
+        let matches = self
+            .prediction_overlay
+            .as_ref()
+            .is_some_and(|(texture, _)| texture.size() == size && texture.format() == self.canvas_format);
+        if !matches {
+            self.prediction_overlay = Some(Self::create_canvas_texture(&self.device, size.width, size.height, self.canvas_format));
             self.recreate_blit_bind_group();
+        }
+    }
+
+    /// Draw a bucket fill into the canvas
+    ///
+    /// The spans are blended over the canvas in the blend color space, like paint dabs.
+    /// Any stroke layer in progress is committed first, so the fill lands on top of it.
+    pub fn fill(&mut self, fill: &Fill) {
+        self.commit_stroke_layer();
+        if fill.spans.is_empty() {
+            return;
+        }
+
+        let bleed = self.bleed_px() as f32;
+        // Canvas space starts at the visible area, inside the bleed margin
+        let spans: Vec<[f32; 3]> = fill
+            .spans
+            .iter()
+            .map(|span| [span.x_start as f32 + bleed, span.x_end as f32 + bleed, span.y as f32 + bleed])
+            .collect();
+        let span_buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Fill Span Buffer"),
+            contents: bytemuck::cast_slice(&spans),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        let color = match self.blend_color_space {
+            BlendColorSpace::Linear => crate::color::srgb_to_linear_rgba(fill.color),
+            BlendColorSpace::Srgb => fill.color,
+        };
+        let alpha = color[3].clamp(0.0, 1.0);
+        let size = self.canvas_texture().size();
+        let fill_uniforms = FillUniforms {
+            color: [color[0] * alpha, color[1] * alpha, color[2] * alpha, alpha],
+            canvas_size: [size.width as f32, size.height as f32],
+            _padding: [0.0; 2],
+        };
+        let uniform_buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Fill Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[fill_uniforms]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Fill Bind Group"),
+            layout: &self.fill_pipeline.get_bind_group_layout(0),
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+        });
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Fill Encoder"),
+        });
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Fill Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: self.canvas_view(),
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,  // Keep existing canvas content
+                        store: wgpu::StoreOp::Store,
+                    },
+                    depth_slice: None,
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            render_pass.set_pipeline(&self.fill_pipeline);
+            render_pass.set_bind_group(0, &bind_group, &[]);
+            render_pass.set_vertex_buffer(0, span_buffer.slice(..));
+            render_pass.draw(0..6, 0..spans.len() as u32);
+        }
+        self.submit(encoder);
+
+        self.layers[self.active_layer].has_strokes = true;
+        let bounds = fill.spans.iter().fold(None, |bounds: Option<DirtyRect>, span| {
+            let rect = DirtyRect {
+                min: [span.x_start as f32, span.y as f32],
+                max: [span.x_end as f32, span.y as f32 + 1.0],
+            };
+            Some(bounds.map_or(rect, |bounds| bounds.union(rect)))
+        });
+        if let Some(bounds) = bounds {
+            self.mark_dirty(bounds);
+        }
+        log::debug!("Filled {} pixels in {} spans", fill.pixel_count(), spans.len());
+    }
+
+    /// Check whether painted dabs can go to the stroke layer with the current settings
+    /// (erasing, additive, and smudge dabs always draw straight into the canvas)
+    fn dabs_use_stroke_layer(&self) -> bool {
+        !self.erase_mode && self.brush_blend_mode != BrushBlendMode::AddHdr && self.smudge_strength.is_none()
+    }
+
+    /// Update the empty flag, overdraw statistics, and dirty region after drawing dabs
+    fn record_dab_stats(&mut self, dabs: &[BrushDab]) {
+        if dabs.is_empty() {
+            return;
+        }
+        self.layers[self.active_layer].has_strokes = true;
+        // Each dab shades its full square quad, so that is the area that costs fill rate
+        self.dab_area_total += dabs.iter().map(|dab| (dab.size as f64).powi(2)).sum::<f64>();
+        for dab in dabs {
+            self.mark_dirty(DirtyRect::of_dab(dab));
+        }
+    }
+
+    /// Record that a region of the canvas changed and must be shown in the next frame
+    fn mark_dirty(&mut self, rect: DirtyRect) {
+        self.dirty_rect = Some(self.dirty_rect.map_or(rect, |dirty| dirty.union(rect)));
+    }
+
+    /// Get the fraction of the visible canvas the last frame had to update (0.0-1.0)
+    ///
+    /// The bounding box of everything drawn, filled, or cleared since the frame before, so a
+    /// small stroke gives a small fraction and layer changes, clears, and undo give 1.0.
+    /// 0.0 means the frame had nothing new and the layers were not flattened again.
+    pub fn dirty_area_fraction(&self) -> f32 {
+        self.last_dirty_fraction
+    }
+
+    /// Create a dab instance buffer with room for `capacity` instances
+    fn create_dab_instance_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Dab Instance Buffer"),
+            size: (capacity * std::mem::size_of::<DabInstance>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Get how many times the dab instance buffer has been reallocated
+    ///
+    /// The buffer grows (to the next power of two) only when one upload holds more dabs than
+    /// it has room for, so this should stay flat during a continuous stroke.
+    pub fn dab_buffer_reallocations(&self) -> u32 {
+        self.dab_instance_reallocations
+    }
+
+    /// Convert dabs to instance data and write them into the pooled instance buffer
+    ///
+    /// The strokes are packed back to back, growing the buffer first if they do not fit.
+    /// The write lands before the next submit, so everything uploaded here must be drawn in
+    /// that submit.
+    ///
+    /// # Returns
+    /// The instance range of each stroke, for `encode_dabs`
+    fn upload_dab_instances(&mut self, strokes: &[&[BrushDab]]) -> Vec<std::ops::Range<u32>> {
+        let bleed = self.bleed_px() as f32;
+
+        // Convert dabs to instance data
+        // Brush colors are stored in sRGB in BrushDab, always convert to linear for shader
+        let instances: Vec<DabInstance> = strokes.iter().flat_map(|dabs| dabs.iter()).map(|&dab| {
+            // Always convert sRGB brush color to linear for shader math
+            let color = match self.blend_color_space {
+                BlendColorSpace::Linear => crate::color::srgb_to_linear_rgba(dab.color),
+                BlendColorSpace::Srgb => dab.color,  // sRGB blending uses sRGB colors directly
+            };
             
-            // Update uniform buffer with new canvas size
-            let brush_uniforms = BrushUniforms {
-                canvas_size: [clamped_width as f32, clamped_height as f32],
-                _padding: [0.0; 2],
+            let transform = dab.transform();
+            DabInstance {
+                // Canvas space starts at the visible area, inside the bleed margin
+                position: [dab.position[0] + bleed, dab.position[1] + bleed],
+                opacity: dab.opacity,
+                hardness: dab.hardness,
+                color,
+                transform: [transform[0][0], transform[0][1], transform[1][0], transform[1][1]],
+                sides: match dab.tip_shape {
+                    TipShape::Round => 0.0,
+                    TipShape::Polygon(sides) => sides as f32,
+                },
+                square_cap: if dab.square_cap { 1.0 } else { 0.0 },
+                smudge_offset: dab.smudge_offset,
+                // The shader tests clips in the dab's local frame
+                clips: dab.clips.map(|clip| match clip {
+                    Some(clip) => {
+                        let (normal, offset) = clip.in_dab_frame(transform);
+                        [normal[0], normal[1], offset, 1.0]
+                    }
+                    None => [0.0; 4],
+                }),
+            }
+        }).collect();
+        
+        // Instances of an open brush batch are still waiting to be drawn: upload after them,
+        // unless they don't fit, in which case the batch goes to the GPU first
+        let mut first = self.batch_instances.get();
+        if first as usize + instances.len() > self.dab_instance_capacity && first > 0 {
+            self.flush_brush_batch();
+            first = 0;
+        }
+        if instances.len() > self.dab_instance_capacity {
+            let capacity = instances.len().next_power_of_two();
+            self.dab_instance_buffer = Self::create_dab_instance_buffer(&self.device, capacity);
+            self.dab_instance_capacity = capacity;
+            self.dab_instance_reallocations += 1;
+            log::debug!(
+                "Grew dab instance buffer to {} instances ({} reallocations)",
+                capacity, self.dab_instance_reallocations
+            );
+        }
+        if !instances.is_empty() {
+            let offset = first as u64 * std::mem::size_of::<DabInstance>() as u64;
+            self.queue.write_buffer(&self.dab_instance_buffer, offset, bytemuck::cast_slice(&instances));
+        }
+        self.batch_instances.set(first + instances.len() as u32);
+
+        let mut start = first;
+        strokes
+            .iter()
+            .map(|dabs| {
+                let end = start + dabs.len() as u32;
+                let range = start..end;
+                start = end;
+                range
+            })
+            .collect()
+    }
+
+    /// Encode a render pass drawing uploaded dab instances into the canvas or the stroke layer
+    ///
+    /// Painting straight into the canvas under a max opacity below 1.0 needs the canvas
+    /// under each dab, which blending can't read: the dabs are drawn into the (inactive)
+    /// stroke layer instead and blended into the canvas held under the ceiling.
+    fn encode_dabs(&self, encoder: &mut wgpu::CommandEncoder, instances: std::ops::Range<u32>, to_stroke_layer: bool) {
+        let blend_constant = self.max_opacity as f64;
+        if to_stroke_layer {
+            self.encode_dabs_into(encoder, instances, &self.stroke_view, blend_constant);
+        } else if self.max_opacity < 1.0 && self.dabs_use_stroke_layer() {
+            self.encode_stroke_layer_clear(encoder);
+            self.encode_dabs_into(encoder, instances, &self.stroke_view, blend_constant);
+            self.encode_stroke_layer_blend(encoder, 1.0, BrushBlendMode::Normal);
+        } else {
+            self.encode_dabs_into(encoder, instances, self.canvas_view(), blend_constant);
+        }
+    }
+
+    /// Encode a render pass drawing uploaded dab instances into a canvas-sized target
+    ///
+    /// `blend_constant` scales additive dabs (the max opacity); painting and erasing
+    /// ignore it.
+    fn encode_dabs_into(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        instances: std::ops::Range<u32>,
+        target_view: &wgpu::TextureView,
+        blend_constant: f64,
+    ) {
+        if instances.is_empty() {
+            return;
+        }
+
+        // MSAA: the target is copied into the multisampled texture, dabs are drawn there, and
+        // the samples are resolved back over the target
+        let msaa = match (&self.msaa_pipelines, &self.msaa_target) {
+            (Some(pipelines), Some((_, msaa_view))) => {
+                self.encode_msaa_load(encoder, pipelines, target_view, msaa_view);
+                Some((pipelines, msaa_view))
+            }
+            _ => None,
+        };
+        let (view, resolve_target, store) = match msaa {
+            Some((_, msaa_view)) => (msaa_view, Some(target_view), wgpu::StoreOp::Discard),
+            None => (target_view, None, wgpu::StoreOp::Store),
+        };
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Brush Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,  // Keep existing canvas content
+                    store,
+                },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        let pipeline = match (msaa, self.erase_mode, self.brush_blend_mode) {
+            (Some((pipelines, _)), true, _) => &pipelines.erase,
+            (Some((pipelines, _)), false, BrushBlendMode::AddHdr) => &pipelines.add,
+            (Some((pipelines, _)), false, _) => &pipelines.paint,
+            (None, true, _) => &self.erase_pipeline,
+            (None, false, BrushBlendMode::AddHdr) => &self.add_pipeline,
+            (None, false, _) => &self.brush_pipeline,
+        };
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &self.brush_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.brush_texture_bind_group, &[]);
+        render_pass.set_blend_constant(wgpu::Color {
+            r: blend_constant,
+            g: blend_constant,
+            b: blend_constant,
+            a: blend_constant,
+        });
+        render_pass.set_vertex_buffer(0, self.dab_instance_buffer.slice(..));
+
+        // Draw 6 vertices per instance (2 triangles = 1 quad per dab)
+        render_pass.draw(0..6, instances);
+    }
+
+    /// Make sure the smudge source and carry textures match the canvas, while smudging
+    fn ensure_smudge_source(&mut self) {
+        if self.smudge_strength.is_none() {
+            return;
+        }
+        let size = self.canvas_texture().size();
+        let format = self.canvas_format;
+        let device = &self.device;
+        for slot in [&mut self.smudge_source, &mut self.smudge_carry, &mut self.smudge_carry_source] {
+            let matches = slot
+                .as_ref()
+                .is_some_and(|(texture, _)| texture.size() == size && texture.format() == format);
+            if !matches {
+                *slot = Some(Self::create_canvas_texture(device, size.width, size.height, format));
+            }
+        }
+    }
+
+    /// Encode smudge dabs dragging the canvas paint, one pass per dab
+    ///
+    /// Each dab carries a color: the color the previous dab carried (read from the carry
+    /// texture one smudge offset back) mixed with the canvas under it by the smudge length.
+    /// It lays that color down and writes it to the carry texture for the next dab. The
+    /// first dab of a stroke (no offset) picks up the canvas under it.
+    ///
+    /// A dab reads the canvas and the carry texture and writes both, which a single pass
+    /// can't do. So before each dab the regions it touches are copied into the smudge
+    /// source textures and the dab samples those copies. Each dab sees the paint the
+    /// previous one moved, as if dragged continuously.
+    fn encode_smudge_dabs(&self, encoder: &mut wgpu::CommandEncoder, dabs: &[BrushDab], instances: std::ops::Range<u32>) {
+        let (
+            Some(strength),
+            Some((source_texture, source_view)),
+            Some((carry_texture, carry_view)),
+            Some((carry_source_texture, carry_source_view)),
+        ) = (self.smudge_strength, &self.smudge_source, &self.smudge_carry, &self.smudge_carry_source)
+        else {
+            return;
+        };
+        if dabs.is_empty() {
+            return;
+        }
+
+        let size = self.canvas_texture().size();
+        let smudge_uniforms = SmudgeUniforms {
+            canvas_size: [size.width as f32, size.height as f32],
+            strength,
+            length: self.smudge_length,
+        };
+        let uniform_buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Smudge Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[smudge_uniforms]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let sampler = self.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Smudge Source Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Smudge Bind Group"),
+            layout: &self.smudge_pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(source_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(carry_source_view),
+                },
+            ],
+        });
+
+        let bleed = self.bleed_px() as f32;
+        for (dab, instance) in dabs.iter().zip(instances) {
+            // Bounds of the dab quad and of where it pulls from, plus a pixel for filtering
+            let [x_axis, y_axis] = dab.transform();
+            let extent = [x_axis[0].abs() + y_axis[0].abs(), x_axis[1].abs() + y_axis[1].abs()];
+            let center = [dab.position[0] + bleed, dab.position[1] + bleed];
+            let offset = dab.smudge_offset;
+            let low = |axis: usize| (center[axis] - extent[axis] - offset[axis].max(0.0) - 1.0).floor().max(0.0) as u32;
+            let high = |axis: usize, limit: u32| {
+                (center[axis] + extent[axis] - offset[axis].min(0.0) + 1.0).ceil().min(limit as f32).max(0.0) as u32
+            };
+            let (x0, y0, x1, y1) = (low(0), low(1), high(0, size.width), high(1, size.height));
+            if x0 >= x1 || y0 >= y1 {
+                continue;
+            }
+
+            let origin = wgpu::Origin3d { x: x0, y: y0, z: 0 };
+            for (from, to) in [(self.canvas_texture(), source_texture), (carry_texture, carry_source_texture)] {
+                encoder.copy_texture_to_texture(
+                    wgpu::TexelCopyTextureInfo {
+                        texture: from,
+                        mip_level: 0,
+                        origin,
+                        aspect: wgpu::TextureAspect::All,
+                    },
+                    wgpu::TexelCopyTextureInfo {
+                        texture: to,
+                        mip_level: 0,
+                        origin,
+                        aspect: wgpu::TextureAspect::All,
+                    },
+                    wgpu::Extent3d { width: x1 - x0, height: y1 - y0, depth_or_array_layers: 1 },
+                );
+            }
+
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Smudge Pass"),
+                color_attachments: &[
+                    Some(wgpu::RenderPassColorAttachment {
+                        view: self.canvas_view(),
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Load,  // Keep existing canvas content
+                            store: wgpu::StoreOp::Store,
+                        },
+                        depth_slice: None,
+                    }),
+                    Some(wgpu::RenderPassColorAttachment {
+                        view: carry_view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Load,
+                            store: wgpu::StoreOp::Store,
+                        },
+                        depth_slice: None,
+                    }),
+                ],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            render_pass.set_pipeline(&self.smudge_pipeline);
+            render_pass.set_bind_group(0, &bind_group, &[]);
+            render_pass.set_vertex_buffer(0, self.dab_instance_buffer.slice(..));
+            // Only pixels inside the copied region are written
+            render_pass.set_scissor_rect(x0, y0, x1 - x0, y1 - y0);
+            render_pass.draw(0..6, instance..instance + 1);
+        }
+    }
+
+    pub fn is_valid_surface(&self) -> bool {
+        self.config.width > 0 
+        && self.config.height > 0 
+        && self.surface.as_ref().is_some_and(|surface| surface.get_current_texture().is_ok())
+    }
+
+    /// Render a frame (blit canvas to surface)
+    ///
+    /// The surface is redrawn in full every frame (its previous contents are not kept), but
+    /// the layers are only flattened again when something changed since the last frame.
+    /// The frame's batched brush work goes in the same submit as the blit. A headless
+    /// renderer draws nothing; the call only submits the brush work and ends the frame for
+    /// the statistics.
+    pub fn render(&mut self) {
+        let Some(surface) = &self.surface else {
+            self.flush_brush_batch();
+            self.end_dirty_frame();
+            self.end_submit_frame();
+            return;
+        };
+        if self.config.width == 0 || self.config.height == 0 {
+            log::warn!("Invalid surface state, skipping render");
+            self.flush_brush_batch();
+            self.end_submit_frame();
+            return;
+        }
+
+        // Get the next frame
+        let output = match surface.get_current_texture() {
+            Ok(output) => output,
+            Err(e) => {
+                // The frame's dabs still belong on the canvas
+                self.flush_brush_batch();
+                self.end_submit_frame();
+                self.handle_surface_error(e);
+                return;
+            }
+        };
+        if self.surface_reconfigures > 0 {
+            log::info!("Surface recovered after {} lost frames", self.surface_reconfigures);
+            self.surface_reconfigures = 0;
+        }
+
+        let view = output
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        // Create command encoder
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Render Encoder"),
+            });
+
+        // Flatten the layers first unless the blit can show the canvas directly, or nothing
+        // changed since the last flatten
+        let composite_stale = self.dirty_rect.is_some() || !self.layer_composite_current.get();
+        if composite_stale {
+            self.encode_layer_composite(&mut encoder, true);
+        }
+
+        // Blit canvas texture to surface using full-screen quad
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Blit Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(self.surface_clear_color()),
+                        store: wgpu::StoreOp::Store,
+                    },
+                    depth_slice: None,
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            render_pass.set_pipeline(&self.blit_pipeline);
+            render_pass.set_bind_group(0, &self.blit_bind_group, &[]);
+            render_pass.draw(0..6, 0..1);
+        }
+
+        // Submit commands
+        self.submit(encoder);
+        output.present();
+        self.end_dirty_frame();
+        self.end_submit_frame();
+        self.update_fps();
+    }
+
+    /// Take the open brush batch's encoder to encode more brush work into, or start one
+    ///
+    /// Put it back in `brush_batch` afterwards; it is submitted ahead of the next submit.
+    fn take_brush_batch(&self) -> wgpu::CommandEncoder {
+        self.brush_batch.take().unwrap_or_else(|| {
+            self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Brush Batch Encoder"),
+            })
+        })
+    }
+
+    /// Submit the open brush batch, if any
+    ///
+    /// Needed before writing a buffer or texture the batched draws read: queue writes land
+    /// at the next submit, ahead of everything in it.
+    fn flush_brush_batch(&self) {
+        if let Some(batch) = self.brush_batch.take() {
+            self.queue.submit(std::iter::once(batch.finish()));
+            self.count_submit();
+        }
+        self.batch_instances.set(0);
+    }
+
+    /// Submit an encoder's work, after the open brush batch so the GPU sees both in order
+    fn submit(&self, encoder: wgpu::CommandEncoder) {
+        let mut command_buffers = Vec::with_capacity(2);
+        if let Some(batch) = self.brush_batch.take() {
+            command_buffers.push(batch.finish());
+        }
+        self.batch_instances.set(0);
+        command_buffers.push(encoder.finish());
+        self.queue.submit(command_buffers);
+        self.count_submit();
+    }
+
+    /// Count a queue submit towards the current frame (debug builds only)
+    fn count_submit(&self) {
+        #[cfg(debug_assertions)]
+        self.frame_submits.set(self.frame_submits.get() + 1);
+    }
+
+    /// Record how many submits the frame just rendered took and start counting the next
+    ///
+    /// A frame normally takes one: the brush batch goes in with the blit. More means
+    /// something submitted on its own mid-frame (debug builds only).
+    fn end_submit_frame(&mut self) {
+        #[cfg(debug_assertions)]
+        {
+            self.last_frame_submits = self.frame_submits.replace(0);
+            if self.last_frame_submits > 1 {
+                log::debug!("Frame took {} queue submits", self.last_frame_submits);
+            }
+        }
+    }
+
+    /// Get how many queue submits the last rendered frame took (debug builds only)
+    #[cfg(debug_assertions)]
+    pub fn last_frame_submits(&self) -> u32 {
+        self.last_frame_submits
+    }
+
+    /// Recover from a failure to get the next surface frame
+    ///
+    /// A lost or outdated surface (e.g. after the tab was backgrounded) is reconfigured in
+    /// place, and the next frame renders normally. If that keeps failing, or the device
+    /// itself is gone, `needs_rebuild` reports that the renderer has to be recreated.
+    /// Timeouts just skip the frame.
+    pub fn handle_surface_error(&mut self, error: wgpu::SurfaceError) {
+        match error {
+            wgpu::SurfaceError::Timeout => {
+                log::warn!("Timed out waiting for surface texture, skipping frame");
+            }
+            wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated => {
+                self.surface_reconfigures += 1;
+                report_error(
+                    ErrorCode::SurfaceLost,
+                    &format!("Surface {:?}, reconfiguring (attempt {})", error, self.surface_reconfigures),
+                );
+                if let Some(surface) = &self.surface {
+                    surface.configure(&self.device, &self.config);
+                }
+            }
+            error => {
+                report_error(ErrorCode::SurfaceUnavailable, &format!("Failed to get surface texture: {:?}", error));
+            }
+        }
+    }
+
+    /// Check whether the GPU device was lost (nothing renders until the renderer is rebuilt)
+    pub fn is_device_lost(&self) -> bool {
+        self.device_lost.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Check whether the renderer can't recover in place and has to be recreated
+    ///
+    /// True once the device is lost, or the surface stayed lost through several
+    /// reconfigures. Keep the canvas with `snapshot_canvas` (while the device still works)
+    /// and `restore_canvas` on the new renderer.
+    pub fn needs_rebuild(&self) -> bool {
+        self.is_device_lost() || self.surface_reconfigures >= MAX_SURFACE_RECONFIGURES
+    }
+
+    /// Record the dirty-area statistics of the frame just rendered and start a clean one
+    fn end_dirty_frame(&mut self) {
+        let [width, height] = self.canvas_size();
+        let canvas_area = width * height;
+        self.last_dirty_fraction = match self.dirty_rect.take() {
+            Some(dirty) if canvas_area > 0.0 => (dirty.visible_area([width, height]) / canvas_area).min(1.0),
+            _ => 0.0,
+        };
+    }
+
+    /// Update the smoothed FPS estimate from the time since the previous present
+    fn update_fps(&mut self) {
+        let now = crate::time::now_ms();
+        if let Some(last) = self.last_present_time_ms {
+            let frame_ms = now - last;
+            if frame_ms > 0.0 && frame_ms < FPS_IDLE_THRESHOLD_MS {
+                let instant_fps = (1000.0 / frame_ms) as f32;
+                self.smoothed_fps = if self.smoothed_fps > 0.0 {
+                    self.smoothed_fps + (instant_fps - self.smoothed_fps) * FPS_SMOOTHING
+                } else {
+                    instant_fps
+                };
+            }
+        }
+        self.last_present_time_ms = Some(now);
+    }
+
+    /// Get the adapter and backend the renderer runs on
+    pub fn adapter_info(&self) -> AdapterDiagnostics {
+        let info = &self.adapter_info;
+        AdapterDiagnostics {
+            name: info.name.clone(),
+            backend: format!("{:?}", info.backend),
+            device_type: format!("{:?}", info.device_type),
+            is_fallback: info.device_type == wgpu::DeviceType::Cpu,
+        }
+    }
+
+    /// Get the smoothed frames-per-second based on recent presents
+    /// Returns 0.0 until at least two frames have been presented
+    pub fn fps(&self) -> f32 {
+        self.smoothed_fps
+    }
+
+    /// Clear the canvas to a color (sRGB RGBA with straight alpha, 0.0-1.0)
+    /// The bottom layer is filled with the color and every layer above it is cleared to
+    /// transparent, so the color shows through as the paper. The clear color is part of
+    /// the canvas and so of exports; with alpha < 1 the canvas stays (partly) transparent
+    /// and the display shows the paper color (see `set_paper_color`) beneath it.
+    pub fn clear_canvas(&mut self, clear_color: &[f64; 4]) {
+        let clear_color = match self.blend_color_space {
+            BlendColorSpace::Linear => crate::color::srgb_to_linear_rgba_f64(clear_color),
+            BlendColorSpace::Srgb => *clear_color,
+        };
+        // The canvas holds premultiplied color
+        let alpha = clear_color[3];
+        let clear_color = [clear_color[0] * alpha, clear_color[1] * alpha, clear_color[2] * alpha, alpha];
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Clear Canvas Encoder"),
+        });
+
+        for (index, layer) in self.layers.iter().enumerate() {
+            let color = if index == 0 {
+                wgpu::Color {
+                    r: clear_color[0],
+                    g: clear_color[1],
+                    b: clear_color[2],
+                    a: clear_color[3],
+                }
+            } else {
+                wgpu::Color::TRANSPARENT
+            };
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Clear Canvas Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &layer.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(color),
+                        store: wgpu::StoreOp::Store,
+                    },
+                    depth_slice: None,
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+        }
+
+        self.submit(encoder);
+        for layer in &mut self.layers {
+            layer.has_strokes = false;
+            layer.undo_base = None;
+        }
+        self.dab_area_total = 0.0;
+        self.mark_dirty(DirtyRect::full());
+        if self.stroke_layer_active {
+            self.stroke_layer_active = false;
+            self.write_blit_uniforms();
+        }
+        log::debug!("Canvas cleared to color: {:?}", clear_color);
+    }
+
+    /// Check whether the canvas is empty (nothing drawn on any layer since the last clear)
+    ///
+    /// This is a dirty flag, not a pixel scan: it is set whenever dabs are rendered and reset
+    /// when the canvas is cleared or recreated. The clear color itself (e.g. an opaque paper
+    /// fill) does not count as content. Strokes that are later erased back to nothing still
+    /// leave the canvas reported as non-empty, so "empty" is reliable but "non-empty" may be
+    /// a false alarm.
+    pub fn is_canvas_empty(&self) -> bool {
+        !self.layers.iter().any(|layer| layer.has_strokes)
+    }
+
+    /// Check whether nothing was drawn into a layer since the last clear, like
+    /// `is_canvas_empty` for a single layer (None if the index is out of range)
+    pub fn is_layer_empty(&self, index: usize) -> Option<bool> {
+        self.layers.get(index).map(|layer| !layer.has_strokes)
+    }
+
+    /// Get the dab overdraw ratio since the canvas was last cleared
+    ///
+    /// Total dab quad area divided by the visible canvas area: 3.0 means every canvas pixel
+    /// has been shaded three times on average. Tight spacing on a large brush drives this up
+    /// fastest, and it is the main cost of painting.
+    pub fn overdraw_ratio(&self) -> f64 {
+        let [width, height] = self.canvas_size();
+        let canvas_area = width as f64 * height as f64;
+        if canvas_area > 0.0 {
+            self.dab_area_total / canvas_area
+        } else {
+            0.0
+        }
+    }
+
+    /// Reset the overdraw statistics without clearing the canvas
+    pub fn reset_overdraw_stats(&mut self) {
+        self.dab_area_total = 0.0;
+    }
+
+    /// Get the current surface size
+    pub fn size(&self) -> winit::dpi::PhysicalSize<u32> {
+        self.size
+    }
+
+    /// Get the current blend color space
+    pub fn blend_color_space(&self) -> BlendColorSpace {
+        self.blend_color_space
+    }
+
+    /// Set the blend color space
+    pub fn set_blend_color_space(&mut self, color_space: BlendColorSpace) {
+        if self.blend_color_space == color_space {
+            return;
+        }
+
+        // The stroke layer holds values in the old color space, so finish it first
+        self.commit_stroke_layer();
+
+        log::info!("Switching blend color space from {:?} to {:?}", self.blend_color_space, color_space);
+        self.blend_color_space = color_space;
+        self.mark_dirty(DirtyRect::full());
+
+        // An 8-bit sRGB canvas changes format with the blend space
+        self.update_canvas_format();
+
+        // Update uniform buffer with new blend mode value
+        self.write_blit_uniforms();
+    }
+
+    /// Switch the blend color space, converting the canvas so existing art looks the same
+    ///
+    /// `set_blend_color_space` leaves the canvas values as they are, so art drawn in the old
+    /// space shifts color when it is displayed in the new one (or is cleared, if the canvas
+    /// changes format). This rewrites every layer, and their undo bases, into the new space
+    /// with one full-canvas pass each instead. Opaque pixels keep their displayed color (up to
+    /// rounding on an 8-bit canvas); translucent pixels are composited over the background
+    /// in the new space, so soft edges over a background can shift slightly.
+    pub fn set_blend_color_space_preserving(&mut self, color_space: BlendColorSpace) {
+        if self.blend_color_space == color_space {
+            return;
+        }
+
+        // Finish the stroke layer in the old space, then keep the old contents alive
+        // through the switch (it may recreate the canvas)
+        self.commit_stroke_layer();
+        let layer_views: Vec<_> = self.layers.iter().map(|layer| layer.view.clone()).collect();
+        let undo_base_views: Vec<_> = self.layers.iter().map(|layer| layer.undo_base.as_ref().map(|(_, view)| view.clone())).collect();
+        let has_strokes: Vec<bool> = self.layers.iter().map(|layer| layer.has_strokes).collect();
+        let dab_area_total = self.dab_area_total;
+
+        self.set_blend_color_space(color_space);
+
+        let pipeline = Self::create_canvas_convert_pipeline(&self.device, self.canvas_format, color_space);
+        let size = self.canvas_texture().size();
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Canvas Convert Encoder"),
+        });
+        let converted_layers: Vec<_> = layer_views
+            .iter()
+            .map(|view| self.encode_canvas_conversion(&mut encoder, &pipeline, view, size))
+            .collect();
+        let converted_undo_bases: Vec<_> = undo_base_views
+            .iter()
+            .map(|view| view.as_ref().map(|view| self.encode_canvas_conversion(&mut encoder, &pipeline, view, size)))
+            .collect();
+        self.submit(encoder);
+
+        let converted = converted_layers.into_iter().zip(converted_undo_bases).zip(has_strokes);
+        for (layer, (((texture, view), undo_base), has_strokes)) in self.layers.iter_mut().zip(converted) {
+            layer.texture = texture;
+            layer.view = view;
+            layer.has_strokes = has_strokes;
+            layer.undo_base = undo_base;
+        }
+        self.rebind_layers();
+        self.dab_area_total = dab_area_total;
+        self.recreate_blit_bind_group();
+        log::info!("Converted canvas contents to {:?}", color_space);
+    }
+
+    /// Encode a pass converting a canvas-sized texture into a new texture in the current
+    /// canvas format and blend color space
+    fn encode_canvas_conversion(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        pipeline: &wgpu::RenderPipeline,
+        source_view: &wgpu::TextureView,
+        size: wgpu::Extent3d,
+    ) -> (wgpu::Texture, wgpu::TextureView) {
+        let (texture, view) = Self::create_canvas_texture(&self.device, size.width, size.height, self.canvas_format);
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Canvas Convert Bind Group"),
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(source_view),
+            }],
+        });
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Canvas Convert Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                    depth_slice: None,
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, &bind_group, &[]);
+            render_pass.draw(0..6, 0..1);
+        }
+
+        (texture, view)
+    }
+
+    /// Check whether the canvas is stored as 8-bit sRGB
+    pub fn srgb_canvas(&self) -> bool {
+        self.srgb_canvas
+    }
+
+    /// Get the canvas texture format
+    ///
+    /// Follows `set_srgb_canvas` and the blend color space, unless the adapter can't blend
+    /// into that format and the canvas fell back to the other one.
+    pub fn canvas_format(&self) -> wgpu::TextureFormat {
+        self.canvas_format
+    }
+
+    /// Set whether the canvas is stored as 8-bit sRGB instead of Rgba16Float (default: off)
+    ///
+    /// Painting apps such as Procreate and Clip Studio accumulate dabs in 8-bit sRGB, so
+    /// every dab rounds to the byte before the next blends over it. The float canvas keeps
+    /// that precision and builds up soft gradients slightly differently. With this on (and
+    /// sRGB blending) dabs accumulate on sRGB bytes the same way, so output matches those
+    /// apps. Additive HDR dabs clip at 1.0 on an 8-bit canvas.
+    ///
+    /// Changing the canvas format clears the canvas, as does switching the blend color
+    /// space while this is on, so set it before drawing.
+    pub fn set_srgb_canvas(&mut self, enabled: bool) {
+        if self.srgb_canvas == enabled {
+            return;
+        }
+        self.commit_stroke_layer();
+        self.srgb_canvas = enabled;
+        self.update_canvas_format();
+        log::info!("sRGB canvas {}", if enabled { "enabled" } else { "disabled" });
+    }
+
+    /// Build blit uniforms from the blend mode and view transform
+    fn build_blit_uniforms(
+        blend_color_space: BlendColorSpace,
+        stroke_opacity: f32,
+        bloom_intensity: f32,
+        view_transform: &ViewTransform,
+        canvas_size: [f32; 2],
+        canvas_offset: [f32; 2],
+        surface_size: [f32; 2],
+    ) -> BlitUniforms {
+        let m = view_transform.inverse_matrix(canvas_size, surface_size);
+        BlitUniforms {
+            blend_mode: blend_color_space_selector(blend_color_space),
+            stroke_opacity,
+            bloom_intensity,
+            dither_amplitude: 0.0,
+            view_row0: [m[0][0], m[0][1], m[0][2], 0.0],
+            view_row1: [m[1][0], m[1][1], m[1][2], 0.0],
+            canvas_size,
+            canvas_offset,
+            surface_background: [0.0, 0.0, 0.0, 1.0],
+            gradient_kind: 0,
+            gradient_stop_count: 0,
+            gradient_space: 0,
+            wrap_mode: 0,
+            gradient_positions: [[0.0; 4]; MAX_GRADIENT_STOPS / 4],
+            gradient_colors: [[0.0; 4]; MAX_GRADIENT_STOPS],
+            reference_rect: [0.0; 4],
+            reference_opacity: 0.0,
+            stroke_blend_mode: 0,
+            prediction_opacity: 0.0,
+            cursor_visible: 0,
+            paper_color: [0.0; 4],
+            cursor_ring: [0.0; 4],
+            tether_visible: 0,
+            stroke_ceiling: 1.0,
+            _padding: [0; 2],
+            stabilizer_tether: [0.0; 4],
+        }
+    }
+
+    /// Write the current blend mode, stroke layer opacity, bloom, view transform, and surface
+    /// background to the blit uniform buffer
+    fn write_blit_uniforms(&self) {
+        // With several layers the stroke layer is shown by the layer composite instead
+        let stroke_opacity = if self.stroke_layer_active && self.layer_composite.is_none() {
+            self.stroke_layer_opacity
+        } else {
+            0.0
+        };
+        let mut blit_uniforms = Self::build_blit_uniforms(
+            self.blend_color_space,
+            stroke_opacity,
+            self.bloom_intensity,
+            &self.view_transform,
+            self.canvas_size(),
+            [self.bleed_px() as f32; 2],
+            self.surface_size(),
+        );
+        if stroke_opacity > 0.0 {
+            blit_uniforms.stroke_blend_mode = stroke_blend_selector(self.stroke_layer_blend_mode);
+            blit_uniforms.stroke_ceiling = self.max_opacity;
+        }
+        if self.prediction_overlay.is_some() {
+            blit_uniforms.prediction_opacity = self.prediction_opacity;
+        }
+        let [r, g, b] = self.surface_background;
+        blit_uniforms.surface_background = [r, g, b, 1.0];
+        blit_uniforms.paper_color = self.paper_color;
+        if let Some(ring) = self.cursor_ring {
+            blit_uniforms.cursor_visible = 1;
+            blit_uniforms.cursor_ring = [ring.center[0], ring.center[1], ring.radius, ring.hardness];
+        }
+        if let Some([pen, ink]) = self.stabilizer_tether {
+            blit_uniforms.tether_visible = 1;
+            blit_uniforms.stabilizer_tether = [pen[0], pen[1], ink[0], ink[1]];
+        }
+        if self.dithering {
+            blit_uniforms.dither_amplitude = DITHER_AMPLITUDE_8BIT;
+        }
+        blit_uniforms.wrap_mode = match self.wrap_mode {
+            WrapMode::Clamp => 0,
+            WrapMode::Repeat => 1,
+            WrapMode::MirrorRepeat => 2,
+        };
+        if let Some(gradient) = &self.background_gradient {
+            blit_uniforms.gradient_kind = match gradient.kind() {
+                GradientKind::Linear => 1,
+                GradientKind::Radial => 2,
+            };
+            blit_uniforms.gradient_stop_count = gradient.stops().len() as u32;
+            let space = gradient.interpolation();
+            blit_uniforms.gradient_space = match space {
+                GradientInterpolation::Srgb => 0,
+                GradientInterpolation::Linear => 1,
+                GradientInterpolation::Oklab => 2,
+            };
+            // Stops are converted up front so the shader only interpolates and decodes
+            for (i, stop) in gradient.stops().iter().enumerate() {
+                blit_uniforms.gradient_positions[i / 4][i % 4] = stop.position;
+                blit_uniforms.gradient_colors[i] = space.encode(stop.color);
+            }
+        }
+        if let (Some([width, height]), true) = (self.reference_size, self.reference_visible) {
+            blit_uniforms.reference_rect = crate::view::fit_rect([width as f32, height as f32], self.canvas_size());
+            blit_uniforms.reference_opacity = self.reference_opacity;
+        }
+        self.queue.write_buffer(
+            &self.blit_uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[blit_uniforms]),
+        );
+    }
+
+    /// Texture of the active layer (what dabs, undo, and conversions act on)
+    fn canvas_texture(&self) -> &wgpu::Texture {
+        &self.layers[self.active_layer].texture
+    }
+
+    /// View of the active layer
+    fn canvas_view(&self) -> &wgpu::TextureView {
+        &self.layers[self.active_layer].view
+    }
+
+    /// Texture shown on the surface and read back: the layer composite, or the only layer
+    fn display_texture(&self) -> &wgpu::Texture {
+        match &self.layer_composite {
+            Some((texture, _)) => texture,
+            None => self.canvas_texture(),
+        }
+    }
+
+    /// View of `display_texture`
+    fn display_view(&self) -> &wgpu::TextureView {
+        match &self.layer_composite {
+            Some((_, view)) => view,
+            None => self.canvas_view(),
+        }
+    }
+
+    /// Visible canvas size in pixels (the canvas texture minus the bleed margin)
+    fn canvas_size(&self) -> [f32; 2] {
+        let bleed = self.bleed_px();
+        [
+            (self.canvas_texture().width() - 2 * bleed) as f32,
+            (self.canvas_texture().height() - 2 * bleed) as f32,
+        ]
+    }
+
+    /// Bleed margin actually allocated around the visible canvas, in pixels
+    /// May be less than requested if the canvas would exceed the max texture size
+    pub fn bleed_px(&self) -> u32 {
+        (self.canvas_texture().width() - self.visible_canvas_size()[0]) / 2
+    }
+
+    /// Visible canvas size in whole pixels: the fixed resolution if one is set, otherwise
+    /// the surface size
+    fn visible_canvas_size(&self) -> [u32; 2] {
+        self.canvas_resolution.unwrap_or([self.config.width, self.config.height])
+    }
+
+    /// Give the canvas a fixed resolution, independent of the surface size
+    ///
+    /// The surface keeps following the window and the view transform maps between the two,
+    /// so e.g. a 4000x4000 document can be drawn in an 800x600 window and zoomed into at full
+    /// resolution. Layer contents and the undo base are kept, anchored at the top-left like a
+    /// surface resize; any stroke layer in progress is committed first.
+    ///
+    /// # Arguments
+    /// * `width`, `height` - Visible canvas size in pixels (clamped to the max texture size);
+    ///   0 for either makes the canvas follow the surface size again
+    ///
+    /// # Returns
+    /// The visible canvas size actually applied
+    pub fn set_canvas_resolution(&mut self, width: u32, height: u32) -> [u32; 2] {
+        let resolution = if width == 0 || height == 0 {
+            None
+        } else {
+            let clamped = [width.min(self.max_texture_dimension), height.min(self.max_texture_dimension)];
+            if clamped != [width, height] {
+                log::warn!("⚠️ Canvas resolution {}x{} exceeds max texture size {}, clamping to {}x{}",
+                           width, height, self.max_texture_dimension, clamped[0], clamped[1]);
+            }
+            Some(clamped)
+        };
+
+        let size = resolution.unwrap_or([self.config.width, self.config.height]);
+        if size == self.visible_canvas_size() {
+            self.canvas_resolution = resolution;
+        } else {
+            let old_contents = self.take_layer_contents();
+            self.canvas_resolution = resolution;
+            self.recreate_canvas_textures_keeping(old_contents);
+        }
+        log::info!("Canvas resolution set to {}x{} ({})", size[0], size[1],
+                   if resolution.is_some() { "fixed" } else { "follows the surface" });
+        size
+    }
+
+    /// Get the fixed canvas resolution, or None if the canvas follows the surface size
+    pub fn canvas_resolution(&self) -> Option<[u32; 2]> {
+        self.canvas_resolution
+    }
+
+    /// Set the bleed margin: extra canvas allocated past each visible edge so large
+    /// dabs near the border fade out naturally instead of being cut off
+    ///
+    /// Reallocates the canvas, so existing content is cleared.
+    pub fn set_canvas_bleed(&mut self, bleed_px: u32) {
+        if bleed_px == self.canvas_bleed {
+            return;
+        }
+        self.canvas_bleed = bleed_px;
+        self.recreate_canvas_textures();
+        log::info!("Canvas bleed set to {}px (allocated {}px)", bleed_px, self.bleed_px());
+    }
+
+    /// Get the requested bleed margin in pixels
+    pub fn canvas_bleed(&self) -> u32 {
+        self.canvas_bleed
+    }
+
+    /// Set whether readback/export includes the bleed margin (default: visible area only)
+    pub fn set_export_includes_bleed(&mut self, include: bool) {
+        self.export_includes_bleed = include;
+    }
+
+    /// Check whether readback/export includes the bleed margin
+    pub fn export_includes_bleed(&self) -> bool {
+        self.export_includes_bleed
+    }
+
+    /// Set how 16-bit readback/export brings values above 1.0 into range (default: clamp)
+    pub fn set_export_hdr_mode(&mut self, mode: HdrExportMode) {
+        self.export_hdr_mode = mode;
+    }
+
+    /// Get how 16-bit readback/export brings values above 1.0 into range
+    pub fn export_hdr_mode(&self) -> HdrExportMode {
+        self.export_hdr_mode
+    }
+
+    /// Get a backend handle to the canvas texture, if the backend supports it
+    ///
+    /// Dabs of a PerStroke stroke in progress live in the stroke layer until the stroke is
+    /// committed, so they are not in this texture yet. With several layers this is the
+    /// active layer only. See `CanvasTextureHandle` for the supported backends.
+    pub fn canvas_texture_handle(&self) -> Option<CanvasTextureHandle> {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            // Safety: the raw handle is only read; the renderer keeps ownership of the texture
+            let hal_texture = unsafe { self.canvas_texture().as_hal::<wgpu::hal::api::Gles>() }?;
+            match &hal_texture.inner {
+                wgpu::hal::gles::TextureInner::Texture { raw, .. } => Some(CanvasTextureHandle::Gl(raw.0.get())),
+                _ => None,
+            }
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            // WebGL textures can't leave their context (see CanvasTextureHandle)
+            None
+        }
+    }
+
+    /// Region of the canvas texture covered by readback/export, as (origin, size)
+    fn export_region(&self) -> ([u32; 2], [u32; 2]) {
+        if self.export_includes_bleed {
+            ([0, 0], [self.canvas_texture().width(), self.canvas_texture().height()])
+        } else {
+            let bleed = self.bleed_px();
+            ([bleed, bleed], self.visible_canvas_size())
+        }
+    }
+
+    /// Configured surface size in pixels
+    pub fn surface_size(&self) -> [f32; 2] {
+        [self.config.width as f32, self.config.height as f32]
+    }
+
+    /// Get the current view transform
+    pub fn view_transform(&self) -> ViewTransform {
+        self.view_transform
+    }
+
+    /// Set the view transform used to display the canvas on the surface
+    /// The zoom is clamped to `ViewTransform::MIN_SCALE..=ViewTransform::MAX_SCALE`
+    pub fn set_view_transform(&mut self, mut view_transform: ViewTransform) {
+        view_transform.scale = ViewTransform::clamp_scale(view_transform.scale);
+        self.view_transform = view_transform;
+        self.write_blit_uniforms();
+    }
+
+    /// Convert a surface-space position (e.g. pointer input) to canvas space
+    pub fn surface_to_canvas(&self, position: [f32; 2]) -> [f32; 2] {
+        self.view_transform.surface_to_canvas(position, self.canvas_size(), self.surface_size())
+    }
+
+    /// Convert a canvas-space position to surface space (e.g. to place UI over the canvas)
+    pub fn canvas_to_surface(&self, position: [f32; 2]) -> [f32; 2] {
+        self.view_transform.canvas_to_surface(position, self.canvas_size(), self.surface_size())
+    }
+
+    /// Get the surface background color (sRGB, 0.0-1.0)
+    pub fn surface_background(&self) -> [f32; 3] {
+        self.surface_background
+    }
+
+    /// Set the color shown behind a transparent canvas and around a letterboxed one (sRGB, 0.0-1.0)
+    /// This is a display color only; it is separate from the canvas clear color and never exported.
+    pub fn set_surface_background(&mut self, color: [f32; 3]) {
+        self.surface_background = color.map(|c| c.clamp(0.0, 1.0));
+        self.write_blit_uniforms();
+    }
+
+    /// Get the size of the reference image, if one is loaded
+    pub fn reference_image_size(&self) -> Option<[u32; 2]> {
+        self.reference_size
+    }
+
+    /// Load a reference image shown beneath the canvas content (e.g. a pose photo to draw over)
+    ///
+    /// The image is fit inside the canvas keeping its aspect ratio (letterboxed), defined in
+    /// canvas space so it pans, zooms, and rotates with the drawing. It is display only:
+    /// it is never drawn into the canvas, so exports and undo only ever see the strokes.
+    ///
+    /// # Arguments
+    /// * `data` - 8-bit sRGB RGBA pixels with straight alpha (width * height * 4 bytes,
+    ///   e.g. `ImageData.data`)
+    /// * `width`, `height` - Image dimensions in pixels (at most the max texture dimension)
+    pub fn set_reference_image(&mut self, data: &[u8], width: u32, height: u32) -> Result<(), String> {
+        if width == 0 || height == 0 {
+            return Err("Reference image dimensions must be positive".to_string());
+        }
+        if width > self.max_texture_dimension || height > self.max_texture_dimension {
+            return Err(format!(
+                "Reference image {}x{} exceeds max texture dimension {}",
+                width, height, self.max_texture_dimension
+            ));
+        }
+        if data.len() != (width as usize) * (height as usize) * 4 {
+            return Err(format!(
+                "Reference image data length {} does not match {}x{} RGBA image",
+                data.len(), width, height
+            ));
+        }
+
+        self.reference_texture = Self::create_reference_texture(&self.device, &self.queue, data, width, height);
+        self.reference_size = Some([width, height]);
+        self.reference_data = Some(data.to_vec());
+        self.recreate_blit_bind_group();
+        self.write_blit_uniforms();
+
+        log::info!("Reference image set: {}x{}", width, height);
+        Ok(())
+    }
+
+    /// Remove the reference image
+    pub fn clear_reference_image(&mut self) {
+        self.reference_texture = Self::create_reference_texture(&self.device, &self.queue, &[0; 4], 1, 1);
+        self.reference_size = None;
+        self.reference_data = None;
+        self.recreate_blit_bind_group();
+        self.write_blit_uniforms();
+        log::info!("Reference image cleared");
+    }
+
+    /// Get the reference image opacity (0.0-1.0)
+    pub fn reference_opacity(&self) -> f32 {
+        self.reference_opacity
+    }
+
+    /// Set the reference image opacity (clamped to 0.0-1.0)
+    pub fn set_reference_opacity(&mut self, opacity: f32) {
+        self.reference_opacity = opacity.clamp(0.0, 1.0);
+        self.write_blit_uniforms();
+    }
+
+    /// Check whether the reference image is shown
+    pub fn reference_visible(&self) -> bool {
+        self.reference_visible
+    }
+
+    /// Show or hide the reference image without unloading it
+    pub fn set_reference_visible(&mut self, visible: bool) {
+        self.reference_visible = visible;
+        self.write_blit_uniforms();
+    }
+
+    /// Get the paper color shown beneath the canvas content (sRGB RGBA, 0.0-1.0)
+    pub fn paper_color(&self) -> [f32; 4] {
+        self.paper_color
+    }
+
+    /// Set the paper color shown beneath the canvas content on screen (sRGB RGBA, 0.0-1.0)
+    ///
+    /// Unlike the canvas clear color this is display only, so a canvas cleared to
+    /// transparent can look like white paper while drawing and still export with
+    /// transparency. Alpha blends the paper over the surface background (0 = no paper,
+    /// the default). It only covers the canvas area, beneath any background gradient.
+    pub fn set_paper_color(&mut self, color: [f32; 4]) {
+        self.paper_color = color.map(|c| c.clamp(0.0, 1.0));
+        self.write_blit_uniforms();
+    }
+
+    /// Get the background gradient shown beneath the canvas, if any
+    pub fn background_gradient(&self) -> Option<&BackgroundGradient> {
+        self.background_gradient.as_ref()
+    }
+
+    /// Show a gradient beneath the canvas content (in place of the surface background)
+    ///
+    /// Compositing only: the gradient is drawn under the canvas when displayed and never
+    /// into the canvas, so it follows the view and can be changed without touching the
+    /// drawing. Exports leave it out unless `set_export_includes_background` is on.
+    pub fn set_background_gradient(
+        &mut self,
+        stops: &[GradientStop],
+        kind: GradientKind,
+        interpolation: GradientInterpolation,
+    ) -> Result<(), String> {
+        self.background_gradient = Some(BackgroundGradient::new(kind, stops)?.with_interpolation(interpolation));
+        self.write_blit_uniforms();
+        Ok(())
+    }
+
+    /// Remove the background gradient
+    pub fn clear_background_gradient(&mut self) {
+        self.background_gradient = None;
+        self.write_blit_uniforms();
+    }
+
+    /// Set whether readback/export composites the background gradient beneath the content
+    /// (default: content only)
+    pub fn set_export_includes_background(&mut self, include: bool) {
+        self.export_includes_background = include;
+    }
+
+    /// Check whether readback/export composites the background gradient beneath the content
+    pub fn export_includes_background(&self) -> bool {
+        self.export_includes_background
+    }
+
+    /// Surface background as a linear clear color (the surface is sRGB, so clears are linear)
+    fn surface_clear_color(&self) -> wgpu::Color {
+        let [r, g, b] = self.surface_background;
+        let linear = crate::color::srgb_to_linear_rgba_f64(&[r as f64, g as f64, b as f64, 1.0]);
+        wgpu::Color { r: linear[0], g: linear[1], b: linear[2], a: 1.0 }
+    }
+
+    /// Get the opacity ceiling applied to brush dabs
+    pub fn max_opacity(&self) -> f32 {
+        self.max_opacity
+    }
+
+    /// Set the opacity ceiling applied to painted dabs (0.0-1.0)
+    ///
+    /// A hard ceiling across passes: painting never raises a pixel's opacity past it, and
+    /// never lowers a pixel that is already more opaque. Additive dabs are scaled by it
+    /// instead, and erasing ignores it.
+    pub fn set_max_opacity(&mut self, max_opacity: f32) {
+        let max_opacity = max_opacity.clamp(0.0, 1.0);
+        if max_opacity == self.max_opacity {
+            return;
+        }
+        self.max_opacity = max_opacity;
+        if self.stroke_layer_active {
+            // The stroke layer is shown held under the ceiling
+            self.mark_dirty(DirtyRect::full());
+            self.write_blit_uniforms();
+        }
+    }
+
+    /// Start routing dabs into a fresh stroke layer, composited at `opacity` with `blend_mode`
+    ///
+    /// Any stroke layer already in progress is committed first. Until committed, the
+    /// stroke layer is shown over the canvas but is not part of the canvas texture.
+    /// Additive blending doesn't apply to the stroke layer and composites as Normal.
+    pub fn begin_stroke_layer(&mut self, opacity: f32, blend_mode: BrushBlendMode) {
+        self.commit_stroke_layer();
+
+        let mut encoder = self.take_brush_batch();
+        self.encode_stroke_layer_clear(&mut encoder);
+        self.brush_batch.set(Some(encoder));
+
+        self.stroke_layer_active = true;
+        self.stroke_layer_opacity = opacity.clamp(0.0, 1.0);
+        self.stroke_layer_blend_mode = blend_mode;
+        self.write_blit_uniforms();
+    }
+
+    /// Composite the in-progress stroke layer into the canvas (no-op if none is active)
+    pub fn commit_stroke_layer(&mut self) {
+        if !self.stroke_layer_active {
+            return;
+        }
+
+        let mut encoder = self.take_brush_batch();
+        self.encode_stroke_layer_composite(&mut encoder);
+        self.brush_batch.set(Some(encoder));
+
+        self.stroke_layer_active = false;
+        self.mark_dirty(DirtyRect::full());
+        self.write_blit_uniforms();
+        log::debug!("Committed stroke layer at opacity {}", self.stroke_layer_opacity);
+    }
+
+    /// Encode a pass clearing the stroke layer to transparent
+    fn encode_stroke_layer_clear(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Clear Stroke Layer Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &self.stroke_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+    }
+
+    /// Encode a pass compositing the stroke layer into the canvas at the layer's opacity
+    fn encode_stroke_layer_composite(&self, encoder: &mut wgpu::CommandEncoder) {
+        if self.stroke_layer_blend_mode.blends_per_stroke() || self.max_opacity < 1.0 {
+            self.encode_stroke_layer_blend(encoder, self.stroke_layer_opacity, self.stroke_layer_blend_mode);
+            return;
+        }
+
+        let composite_uniforms = StrokeCompositeUniforms {
+            opacity: self.stroke_layer_opacity,
+            _padding: [0.0; 3],
+        };
+        let uniform_buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Stroke Composite Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[composite_uniforms]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Stroke Composite Bind Group"),
+            layout: &self.stroke_composite_pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&self.stroke_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Stroke Composite Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: self.canvas_view(),
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,  // Keep existing canvas content
+                    store: wgpu::StoreOp::Store,
+                },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        render_pass.set_pipeline(&self.stroke_composite_pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.draw(0..6, 0..1);
+    }
+
+    /// Encode passes blending the stroke layer into the canvas at `opacity` with `blend_mode`,
+    /// held under the max opacity ceiling
+    ///
+    /// Blending and the ceiling read the canvas, so the result is drawn into a scratch
+    /// texture with the layer composite shader and copied back over the canvas.
+    fn encode_stroke_layer_blend(&self, encoder: &mut wgpu::CommandEncoder, opacity: f32, blend_mode: BrushBlendMode) {
+        let size = self.canvas_texture().size();
+        let (scratch_texture, scratch_view) =
+            Self::create_canvas_texture(&self.device, size.width, size.height, self.canvas_format);
+        let composite_uniforms = LayerCompositeUniforms {
+            opacity: 1.0,
+            stroke_opacity: opacity,
+            stroke_blend_mode: stroke_blend_selector(blend_mode),
+            blend_mode: blend_color_space_selector(self.blend_color_space),
+            stroke_ceiling: self.max_opacity,
+            _padding: [0; 3],
+        };
+        let uniform_buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Stroke Blend Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[composite_uniforms]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let bind_group = Self::create_layer_composite_bind_group(
+            &self.device,
+            &self.layer_composite_pipeline,
+            self.canvas_view(),
+            &self.stroke_view,
+            &uniform_buffer,
+        );
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Stroke Blend Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &scratch_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                    depth_slice: None,
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            render_pass.set_pipeline(&self.layer_composite_pipeline);
+            render_pass.set_bind_group(0, &bind_group, &[]);
+            render_pass.draw(0..6, 0..1);
+        }
+
+        encoder.copy_texture_to_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &scratch_texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::TexelCopyTextureInfo {
+                texture: self.canvas_texture(),
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            size,
+        );
+    }
+
+    /// Check whether a stroke layer is in progress (not yet composited into the canvas)
+    pub fn is_stroke_layer_active(&self) -> bool {
+        self.stroke_layer_active
+    }
+
+    /// Opacity the current stroke layer will be composited at
+    pub fn stroke_layer_opacity(&self) -> f32 {
+        self.stroke_layer_opacity
+    }
+
+    /// Blend mode the current stroke layer will be composited with
+    pub fn stroke_layer_blend_mode(&self) -> BrushBlendMode {
+        self.stroke_layer_blend_mode
+    }
+
+    /// Number of paint layers (always at least one)
+    pub fn layer_count(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Index of the layer dabs are drawn into (0 = bottom)
+    pub fn active_layer(&self) -> usize {
+        self.active_layer
+    }
+
+    /// Check whether a layer is shown (None if the index is out of range)
+    pub fn is_layer_visible(&self, index: usize) -> Option<bool> {
+        self.layers.get(index).map(|layer| layer.visible)
+    }
+
+    /// Opacity a layer is composited at (None if the index is out of range)
+    pub fn layer_opacity(&self, index: usize) -> Option<f32> {
+        self.layers.get(index).map(|layer| layer.opacity)
+    }
+
+    /// Add a transparent layer on top of the others and return its index
+    /// The active layer doesn't change. Each layer costs as much memory as the canvas.
+    pub fn add_layer(&mut self) -> usize {
+        let size = self.canvas_texture().size();
+        let layer = Layer::new(
+            &self.device,
+            &self.layer_composite_pipeline,
+            &self.stroke_view,
+            Self::create_canvas_texture(&self.device, size.width, size.height, self.canvas_format),
+        );
+        self.layers.push(layer);
+        self.update_layer_composite_target();
+        log::info!("Added layer {} ({} layers)", self.layers.len() - 1, self.layers.len());
+        self.layers.len() - 1
+    }
+
+    /// Remove a layer and its content
+    ///
+    /// The last remaining layer can't be removed. Removing the active layer drops its
+    /// in-progress stroke layer and makes the layer beneath it active.
+    pub fn remove_layer(&mut self, index: usize) -> Result<(), String> {
+        self.check_layer_index(index)?;
+        if self.layers.len() == 1 {
+            return Err("Can't remove the only layer".to_string());
+        }
+
+        if index == self.active_layer {
+            self.stroke_layer_active = false;
+        }
+        self.active_layer = active_layer_after_removal(self.active_layer, index);
+        self.layers.remove(index);
+        self.update_layer_composite_target();
+        log::info!("Removed layer {} ({} layers)", index, self.layers.len());
+        Ok(())
+    }
+
+    /// Make a layer the one dabs are drawn into
+    ///
+    /// Any stroke layer in progress is committed to the old layer first. Each layer keeps
+    /// its own undo base.
+    pub fn set_active_layer(&mut self, index: usize) -> Result<(), String> {
+        self.check_layer_index(index)?;
+        if index == self.active_layer {
+            return Ok(());
+        }
+
+        self.commit_stroke_layer();
+        self.active_layer = index;
+        // The blit shows the active layer directly on the single-layer path
+        self.recreate_blit_bind_group();
+        log::debug!("Active layer is now {}", index);
+        Ok(())
+    }
+
+    /// Show or hide a layer (hidden layers can still be drawn into)
+    pub fn set_layer_visible(&mut self, index: usize, visible: bool) -> Result<(), String> {
+        self.check_layer_index(index)?;
+        self.layers[index].visible = visible;
+        self.update_layer_composite_target();
+        Ok(())
+    }
+
+    /// Set the opacity a layer is composited at (clamped to 0-1; NaN and infinities are rejected)
+    pub fn set_layer_opacity(&mut self, index: usize, opacity: f32) -> Result<(), String> {
+        self.check_layer_index(index)?;
+        if !opacity.is_finite() {
+            return Err(format!("Layer opacity must be a finite number, got {}", opacity));
+        }
+        self.layers[index].opacity = opacity.clamp(0.0, 1.0);
+        self.update_layer_composite_target();
+        Ok(())
+    }
+
+    /// Fail with a message if `index` is not a layer
+    fn check_layer_index(&self, index: usize) -> Result<(), String> {
+        if index < self.layers.len() {
+            Ok(())
+        } else {
+            Err(format!("Layer index {} out of range ({} layers)", index, self.layers.len()))
+        }
+    }
+
+    /// Check whether the layers must be flattened for display
+    ///
+    /// A single visible, fully opaque layer is the fast path: the blit samples it directly
+    /// and shows the stroke layer over it, with no composite texture at all.
+    fn uses_layer_composite(&self) -> bool {
+        match self.layers.as_slice() {
+            [layer] => !layer.visible || layer.opacity < 1.0,
+            _ => true,
+        }
+    }
+
+    /// Allocate or free the layer composite texture to match the layers, and point the
+    /// blit at whichever texture is now displayed
+    fn update_layer_composite_target(&mut self) {
+        // Layer contents, visibility, or opacity changed: the whole composite is stale
+        self.mark_dirty(DirtyRect::full());
+        if !self.uses_layer_composite() {
+            self.layer_composite = None;
+        } else if self.layer_composite.is_none() {
+            let size = self.canvas_texture().size();
+            self.layer_composite = Some(Self::create_canvas_texture(&self.device, size.width, size.height, self.canvas_format));
+        }
+        self.recreate_blit_bind_group();
+        self.write_blit_uniforms();
+    }
+
+    /// Point every layer's composite bind group at the current layer textures, stroke layer,
+    /// and composite pipeline
+    fn rebind_layers(&mut self) {
+        for layer in &mut self.layers {
+            layer.rebind(&self.device, &self.layer_composite_pipeline, &self.stroke_view);
+        }
+    }
+
+    /// Create a layer composite bind group reading `layer_view` and the stroke layer
+    fn create_layer_composite_bind_group(
+        device: &wgpu::Device,
+        composite_pipeline: &wgpu::RenderPipeline,
+        layer_view: &wgpu::TextureView,
+        stroke_view: &wgpu::TextureView,
+        uniform_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Layer Composite Bind Group"),
+            layout: &composite_pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(layer_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(stroke_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    /// Encode a pass compositing the visible layers bottom-to-top into the layer composite
+    ///
+    /// Does nothing on the single-layer fast path. The in-progress stroke layer is shown
+    /// over the active layer if `include_stroke_layer` is set (for display, not readback).
+    /// Each layer's uniforms are written through the queue, so the encoder must be submitted
+    /// before the layers are composited again.
+    fn encode_layer_composite(&self, encoder: &mut wgpu::CommandEncoder, include_stroke_layer: bool) {
+        let Some((_, composite_view)) = &self.layer_composite else {
+            return;
+        };
+        // Without the stroke layer this is not what the next frame shows
+        self.layer_composite_current.set(include_stroke_layer);
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Layer Composite Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: composite_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        render_pass.set_pipeline(&self.layer_composite_pipeline);
+        for (index, layer) in self.layers.iter().enumerate() {
+            if !layer.visible || layer.opacity <= 0.0 {
+                continue;
+            }
+            let stroke_opacity = if include_stroke_layer && self.stroke_layer_active && index == self.active_layer {
+                self.stroke_layer_opacity
+            } else {
+                0.0
             };
-            self.queue.write_buffer(
-                &self.brush_uniform_buffer,
-                0,
-                bytemuck::cast_slice(&[brush_uniforms]),
-            );
+            let composite_uniforms = LayerCompositeUniforms {
+                opacity: layer.opacity,
+                stroke_opacity,
+                stroke_blend_mode: if stroke_opacity > 0.0 {
+                    stroke_blend_selector(self.stroke_layer_blend_mode)
+                } else {
+                    0
+                },
+                blend_mode: blend_color_space_selector(self.blend_color_space),
+                stroke_ceiling: if stroke_opacity > 0.0 { self.max_opacity } else { 1.0 },
+                _padding: [0; 3],
+            };
+            self.queue.write_buffer(&layer.composite_uniform_buffer, 0, bytemuck::cast_slice(&[composite_uniforms]));
+            render_pass.set_bind_group(0, &layer.composite_bind_group, &[]);
+            render_pass.draw(0..6, 0..1);
+        }
+    }
 
-            log::debug!("Surface and canvas resized to: {}x{}, format: {:?}", clamped_width, clamped_height, self.canvas_format);
+    /// Snapshot the active layer as its undo base (the state undo rewinds it to)
+    ///
+    /// Any stroke layer in progress is committed first so the snapshot matches what is shown.
+    /// The snapshot texture is allocated on first use and costs as much memory as the canvas.
+    pub fn snapshot_undo_base(&mut self) {
+        self.commit_stroke_layer();
+
+        let size = self.canvas_texture().size();
+        let layer = &mut self.layers[self.active_layer];
+        let reusable = layer
+            .undo_base
+            .as_ref()
+            .is_some_and(|(texture, _)| texture.size() == size && texture.format() == self.canvas_format);
+        if !reusable {
+            layer.undo_base = Some(Self::create_canvas_texture(&self.device, size.width, size.height, self.canvas_format));
+        }
+        layer.undo_base_has_strokes = layer.has_strokes;
+        let layer = &self.layers[self.active_layer];
+        if let Some((undo_texture, _)) = &layer.undo_base {
+            self.copy_canvas_texture(&layer.texture, undo_texture);
         }
+        log::debug!("Snapshotted layer {} as its undo base", self.active_layer);
     }
 
-    /// Render brush dabs to the canvas texture
-    pub fn render_dabs(&mut self, dabs: &[BrushDab]) {
-        if dabs.is_empty() {
+    /// Restore the active layer to its undo base, dropping any stroke layer in progress
+    /// Returns false (and changes nothing) if the layer has no undo base.
+    pub fn restore_undo_base(&mut self) -> bool {
+        let layer = &self.layers[self.active_layer];
+        let Some((undo_texture, _)) = &layer.undo_base else {
+            return false;
+        };
+        self.copy_canvas_texture(undo_texture, &layer.texture);
+        let layer = &mut self.layers[self.active_layer];
+        layer.has_strokes = layer.undo_base_has_strokes;
+        self.mark_dirty(DirtyRect::full());
+        if self.stroke_layer_active {
+            self.stroke_layer_active = false;
+            self.write_blit_uniforms();
+        }
+        true
+    }
+
+    /// Check whether a layer holds an undo base (false if the index is out of range)
+    /// Undo bases are dropped when the canvas is cleared.
+    pub fn has_undo_base(&self, index: usize) -> bool {
+        self.layers.get(index).is_some_and(|layer| layer.undo_base.is_some())
+    }
+
+    /// Free the undo base of every layer
+    pub fn discard_undo_bases(&mut self) {
+        for layer in &mut self.layers {
+            layer.undo_base = None;
+        }
+    }
+
+    /// Run drawing commands against the active layer's undo base instead of the layer
+    ///
+    /// Used to bake strokes that drop out of the undo history into the base. Any stroke layer
+    /// in progress is committed to the canvas first, since the drawing may reuse the layer.
+    /// Does nothing if the layer has no undo base.
+    pub fn draw_into_undo_base(&mut self, draw: impl FnOnce(&mut Self)) {
+        self.commit_stroke_layer();
+        let layer = &mut self.layers[self.active_layer];
+        let Some((mut undo_texture, mut undo_view)) = layer.undo_base.take() else {
             return;
+        };
+
+        // Swap the base in as the active layer; the blit bind group keeps the real layer view
+        std::mem::swap(&mut layer.texture, &mut undo_texture);
+        std::mem::swap(&mut layer.view, &mut undo_view);
+        let has_strokes = std::mem::replace(&mut layer.has_strokes, layer.undo_base_has_strokes);
+        let dab_area_total = self.dab_area_total;
+        let dirty_rect = self.dirty_rect;
+
+        draw(self);
+        self.commit_stroke_layer();
+
+        let layer = &mut self.layers[self.active_layer];
+        std::mem::swap(&mut layer.texture, &mut undo_texture);
+        std::mem::swap(&mut layer.view, &mut undo_view);
+        layer.undo_base_has_strokes = std::mem::replace(&mut layer.has_strokes, has_strokes);
+        layer.undo_base = Some((undo_texture, undo_view));
+        self.dab_area_total = dab_area_total;
+        self.dirty_rect = dirty_rect;
+    }
+
+    /// Run drawing commands against another layer as if it were the active one
+    ///
+    /// Used to replay undo history into the layer it was drawn on. Any stroke layer in
+    /// progress is committed to the active layer first, and whatever the drawing leaves in
+    /// the stroke layer is committed to the other layer before the active layer returns.
+    pub fn draw_into_layer(&mut self, index: usize, draw: impl FnOnce(&mut Self)) -> Result<(), String> {
+        self.check_layer_index(index)?;
+        self.commit_stroke_layer();
+        let active_layer = std::mem::replace(&mut self.active_layer, index);
+        draw(self);
+        self.commit_stroke_layer();
+        self.active_layer = active_layer;
+        Ok(())
+    }
+
+    /// Copy one canvas-sized texture into another
+    fn copy_canvas_texture(&self, source: &wgpu::Texture, destination: &wgpu::Texture) {
+        // Part of drawing a stroke (and of undo), so it joins the brush batch
+        let mut encoder = self.take_brush_batch();
+        encoder.copy_texture_to_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: source,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::TexelCopyTextureInfo {
+                texture: destination,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            source.size(),
+        );
+        self.brush_batch.set(Some(encoder));
+    }
+
+    /// Check whether dabs currently erase instead of paint
+    pub fn erase_mode(&self) -> bool {
+        self.erase_mode
+    }
+
+    /// Set whether subsequent dabs erase existing paint instead of adding color
+    pub fn set_erase_mode(&mut self, erase: bool) {
+        self.erase_mode = erase;
+    }
+
+    /// Get the smudge strength, or None while dabs paint
+    pub fn smudge_strength(&self) -> Option<f32> {
+        self.smudge_strength
+    }
+
+    /// Set whether subsequent dabs smudge instead of paint, laying down `strength` (0.0-1.0)
+    /// of the color they carry (see `set_smudge_length`)
+    ///
+    /// Smudging overrides erase mode and the blend mode, and draws straight into the canvas.
+    pub fn set_smudge_strength(&mut self, strength: Option<f32>) {
+        self.smudge_strength = strength.map(|strength| strength.clamp(0.0, 1.0));
+        if self.smudge_strength.is_none() {
+            self.smudge_source = None;
+            self.smudge_carry = None;
+            self.smudge_carry_source = None;
         }
-        
-        // Convert dabs to instance data
-        // Brush colors are stored in sRGB in BrushDab, always convert to linear for shader
-        let instances: Vec<DabInstance> = dabs.iter().map(|&dab| {
-            // Always convert sRGB brush color to linear for shader math
-            let color = match self.blend_color_space {
-                BlendColorSpace::Linear => crate::color::srgb_to_linear_rgba(dab.color),
-                BlendColorSpace::Srgb => dab.color,  // sRGB blending uses sRGB colors directly
-            };
-            
-            DabInstance {
-                position: dab.position,
-                size: dab.size,
-                opacity: dab.opacity,
-                color,
-                hardness: dab.hardness,
-                _padding: [0.0; 3],
-            }
-        }).collect();
-        
-        // Create instance buffer
-        let instance_buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Dab Instance Buffer"),
-            contents: bytemuck::cast_slice(&instances),
-            usage: wgpu::BufferUsages::VERTEX,
+    }
+
+    /// Get the fraction of the carried color smudge dabs keep from dab to dab
+    pub fn smudge_length(&self) -> f32 {
+        self.smudge_length
+    }
+
+    /// Set the fraction (0.0-1.0) of the carried color each smudge dab keeps from the
+    /// previous one; the rest is picked up from the canvas under it
+    pub fn set_smudge_length(&mut self, length: f32) {
+        self.smudge_length = length.clamp(0.0, 1.0);
+    }
+
+    /// Get how painted dabs combine with the canvas
+    pub fn brush_blend_mode(&self) -> BrushBlendMode {
+        self.brush_blend_mode
+    }
+
+    /// Set how subsequent painted dabs combine with the canvas
+    /// Modes that blend whole strokes (Multiply etc.) are applied when the stroke layer is
+    /// composited, so pass the same mode to `begin_stroke_layer`; dabs drawn straight into
+    /// the canvas with them paint as Normal.
+    pub fn set_brush_blend_mode(&mut self, blend_mode: BrushBlendMode) {
+        self.brush_blend_mode = blend_mode;
+    }
+
+    /// Get the bloom intensity applied when displaying the canvas
+    pub fn bloom_intensity(&self) -> f32 {
+        self.bloom_intensity
+    }
+
+    /// Set the bloom intensity (0.0 = off)
+    /// Canvas values above 1.0 (from additive HDR dabs) glow into their surroundings.
+    /// Bloom is a display effect only; it is not part of the canvas or exports.
+    pub fn set_bloom_intensity(&mut self, intensity: f32) {
+        self.bloom_intensity = intensity.max(0.0);
+        self.write_blit_uniforms();
+    }
+
+    /// Check whether 8-bit output is dithered
+    pub fn dithering(&self) -> bool {
+        self.dithering
+    }
+
+    /// Set whether 8-bit output is written with ordered dithering (default: off)
+    ///
+    /// Soft brush edges and low-flow build-up are smooth opacity ramps that quantize into
+    /// visible bands at 8 bits per channel. Dithering trades the bands for a fine, even
+    /// pattern. It applies to dabs when the canvas is 8-bit, to the display (the surface is
+    /// always 8-bit), and to image data and PNG exports.
+    pub fn set_dithering(&mut self, enabled: bool) {
+        self.dithering = enabled;
+        self.write_brush_uniforms();
+        self.write_blit_uniforms();
+    }
+
+    /// Get the canvas sampler's address mode
+    pub fn sampler_address_mode(&self) -> WrapMode {
+        self.wrap_mode
+    }
+
+    /// Set how the canvas is sampled past its edges when displayed (default: Clamp)
+    ///
+    /// Repeat and MirrorRepeat tile the canvas across the whole surface through the view
+    /// transform, with filtering that wraps across the seams, for previewing seamless
+    /// patterns. With a bleed margin the seams filter against the margin instead, so
+    /// tiling previews are cleanest without one. Display only: exports are unaffected.
+    pub fn set_sampler_address_mode(&mut self, mode: WrapMode) {
+        if mode == self.wrap_mode {
+            return;
+        }
+        self.wrap_mode = mode;
+        // Samplers are immutable, so the bind group has to be rebuilt around a new one
+        self.canvas_sampler = Self::create_canvas_sampler(&self.device, mode);
+        self.recreate_blit_bind_group();
+        self.write_blit_uniforms();
+    }
+
+    /// Estimate the GPU memory used by the renderer's textures, in bytes
+    ///
+    /// This is width x height x bytes-per-pixel (x samples) summed over the layers (and their composite), stroke layer, undo base, smudge source and carry,
+    /// MSAA brush target, brush mask textures, reference image, and the surface's swapchain images. Driver overhead, alignment padding,
+    /// and transient per-frame buffers are not included, so treat it as a lower bound.
+    /// It is computed on demand, so it always reflects the current sizes.
+    pub fn estimated_vram_bytes(&self) -> u64 {
+        let canvas_bytes: u64 = self.layers.iter().map(|layer| Self::texture_bytes(&layer.texture)).sum();
+        let composite_bytes = self.layer_composite.as_ref().map_or(0, |(texture, _)| Self::texture_bytes(texture));
+        let stroke_bytes = Self::texture_bytes(&self.stroke_texture);
+        let grain_bytes = Self::texture_bytes(&self.grain_texture);
+        let stamp_bytes = Self::texture_bytes(&self.stamp_texture);
+        let reference_bytes = Self::texture_bytes(&self.reference_texture);
+        let undo_base_bytes: u64 = self
+            .layers
+            .iter()
+            .map(|layer| layer.undo_base.as_ref().map_or(0, |(texture, _)| Self::texture_bytes(texture)))
+            .sum();
+        let smudge_bytes: u64 = [&self.smudge_source, &self.smudge_carry, &self.smudge_carry_source]
+            .into_iter()
+            .map(|slot| slot.as_ref().map_or(0, |(texture, _)| Self::texture_bytes(texture)))
+            .sum();
+        let prediction_bytes = self.prediction_overlay.as_ref().map_or(0, |(texture, _)| Self::texture_bytes(texture));
+        let msaa_bytes = self.msaa_target.as_ref().map_or(0, |(texture, _)| Self::texture_bytes(texture));
+
+        // Swapchain: one image in flight per frame of latency plus the one being presented
+        let surface_bytes_per_pixel = self.config.format.block_copy_size(None).unwrap_or(4) as u64;
+        let surface_image_count = self.config.desired_maximum_frame_latency as u64 + 1;
+        let surface_bytes = self.config.width as u64
+            * self.config.height as u64
+            * surface_bytes_per_pixel
+            * surface_image_count;
+
+        canvas_bytes
+            + composite_bytes
+            + stroke_bytes
+            + undo_base_bytes
+            + smudge_bytes
+            + prediction_bytes
+            + msaa_bytes
+            + grain_bytes
+            + stamp_bytes
+            + reference_bytes
+            + surface_bytes
+    }
+
+    /// Size of a single-mip 2D texture in bytes, every sample counted
+    fn texture_bytes(texture: &wgpu::Texture) -> u64 {
+        let bytes_per_pixel = texture.format().block_copy_size(None).unwrap_or(4) as u64;
+        texture.width() as u64 * texture.height() as u64 * bytes_per_pixel * texture.sample_count() as u64
+    }
+
+    /// Set the brush grain texture (dual-mask rendering)
+    ///
+    /// Each dab's shape mask is multiplied by this grain, producing pencil/charcoal texture.
+    /// The grain is anchored to the canvas (like paper tooth), so it stays put as the brush
+    /// moves and overlapping dabs reinforce the same pattern.
+    ///
+    /// # Arguments
+    /// * `data` - 8-bit grayscale grain values (width * height bytes, 255 = full coverage)
+    /// * `width`, `height` - Grain texture dimensions in pixels
+    /// * `scale` - Grain scale in canvas space (1.0 = one grain texel per canvas pixel)
+    pub fn set_brush_grain(&mut self, data: &[u8], width: u32, height: u32, scale: f32) -> Result<(), String> {
+        if width == 0 || height == 0 {
+            return Err("Grain texture dimensions must be positive".to_string());
+        }
+        if width > self.max_texture_dimension || height > self.max_texture_dimension {
+            return Err(format!(
+                "Grain texture {}x{} exceeds max texture size {}",
+                width, height, self.max_texture_dimension
+            ));
+        }
+        if data.len() != (width as usize) * (height as usize) {
+            return Err(format!(
+                "Grain data length {} does not match {}x{} grayscale texture",
+                data.len(), width, height
+            ));
+        }
+        if !(scale > 0.0) {
+            return Err("Grain scale must be positive".to_string());
+        }
+
+        self.grain_texture = Self::create_mask_texture(
+            &self.device,
+            &self.queue,
+            "Brush Grain Texture",
+            data,
+            width,
+            height,
+        );
+        self.write_grain_uniforms(width, height, scale, true);
+        self.grain_source = Some((data.to_vec(), [width, height], scale));
+        self.recreate_brush_texture_bind_group();
+
+        log::info!("Brush grain set: {}x{}, scale={}", width, height, scale);
+        Ok(())
+    }
+
+    /// Set the brush stamp texture (tip shape mask)
+    ///
+    /// The stamp is stretched over each dab's square, rotated with the dab, and multiplied
+    /// with the procedural round/polygon falloff.
+    ///
+    /// # Arguments
+    /// * `data` - 8-bit grayscale coverage values (width * height bytes, 255 = full coverage),
+    ///   or 8-bit RGBA pixels (width * height * 4 bytes) converted with `import::rgba_to_mask`
+    /// * `width`, `height` - Stamp dimensions in pixels (at most `MAX_BRUSH_TEXTURE_SIZE`)
+    pub fn set_brush_texture(&mut self, data: &[u8], width: u32, height: u32) -> Result<(), String> {
+        let max_size = self.max_brush_texture_size();
+        if width == 0 || height == 0 {
+            return Err("Brush texture dimensions must be positive".to_string());
+        }
+        if width > max_size || height > max_size {
+            return Err(format!(
+                "Brush texture {}x{} exceeds max brush texture size {}",
+                width, height, max_size
+            ));
+        }
+        let pixel_count = (width as usize) * (height as usize);
+        let rgba_mask;
+        let mask = if data.len() == pixel_count {
+            data
+        } else if data.len() == pixel_count * 4 {
+            rgba_mask = crate::import::rgba_to_mask(data);
+            &rgba_mask
+        } else {
+            return Err(format!(
+                "Brush texture data length {} does not match {}x{} grayscale or RGBA texture",
+                data.len(), width, height
+            ));
+        };
+
+        self.stamp_texture = Self::create_mask_texture(
+            &self.device,
+            &self.queue,
+            "Brush Stamp Texture",
+            mask,
+            width,
+            height,
+        );
+        self.stamp_source = Some((mask.to_vec(), [width, height]));
+        self.recreate_brush_texture_bind_group();
+
+        log::info!("Brush texture set: {}x{}", width, height);
+        Ok(())
+    }
+
+    /// Set the brush stamp texture from an encoded PNG
+    /// Uses the PNG's alpha channel as the mask if it has one, otherwise its luminance
+    pub fn set_brush_texture_png(&mut self, png_data: &[u8]) -> Result<(), String> {
+        let (mask, width, height) = crate::import::decode_png_mask(png_data, self.max_brush_texture_size())?;
+        self.set_brush_texture(&mask, width, height)
+    }
+
+    /// Remove the brush stamp texture (dabs use the procedural shape only)
+    pub fn clear_brush_texture(&mut self) {
+        self.stamp_texture = Self::create_mask_texture(&self.device, &self.queue, "Brush Stamp Texture", &[255], 1, 1);
+        self.stamp_source = None;
+        self.recreate_brush_texture_bind_group();
+        log::info!("Brush texture cleared");
+    }
+
+    /// Get how brush dab edges are antialiased
+    pub fn antialiasing(&self) -> AaMode {
+        self.antialiasing
+    }
+
+    /// Set how brush dab edges are antialiased, returning the mode actually used
+    ///
+    /// `Msaa4x` needs multisampling with resolve for the canvas format and per-sample
+    /// shading. WebGL2 has no per-sample shading (and often no multisampled float targets),
+    /// so there, and anywhere else it is unsupported, this falls back to `Analytic`. A later
+    /// canvas format change that MSAA does not support falls back the same way. Only dabs
+    /// drawn from now on are affected.
+    pub fn set_antialiasing(&mut self, mode: AaMode) -> AaMode {
+        let mode = if mode == AaMode::Msaa4x && !self.msaa_supported() {
+            log::warn!("4x MSAA is not supported for a {:?} canvas, using analytic antialiasing", self.canvas_format);
+            AaMode::Analytic
+        } else {
+            mode
+        };
+        if mode == self.antialiasing {
+            return mode;
+        }
+
+        log::info!("Brush antialiasing set to {:?}", mode);
+        self.antialiasing = mode;
+        let source = crate::brush_shader::build_brush_shader(self.custom_brush_mask.as_deref())
+            .expect("Current brush shader failed to rebuild");
+        self.rebuild_brush_pipelines(&source);
+        self.write_brush_uniforms();
+        mode
+    }
+
+    /// Check whether 4x MSAA brush rendering works for the current canvas format
+    fn msaa_supported(&self) -> bool {
+        self.msaa_formats.contains(&self.canvas_format)
+    }
+
+    /// Make sure the multisampled brush target matches the canvas, while MSAA is on
+    fn ensure_msaa_target(&mut self) {
+        if self.msaa_pipelines.is_none() {
+            return;
+        }
+        let size = self.canvas_texture().size();
+        let matches = self
+            .msaa_target
+            .as_ref()
+            .is_some_and(|(texture, _)| texture.size() == size && texture.format() == self.canvas_format);
+        if matches {
+            return;
+        }
+
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("MSAA Brush Target"),
+            size,
+            mip_level_count: 1,
+            sample_count: MSAA_SAMPLE_COUNT,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.canvas_format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
         });
-        
-        // Create command encoder
-        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-            label: Some("Brush Render Encoder"),
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        self.msaa_target = Some((texture, view));
+    }
+
+    /// Encode a pass copying `source` into every sample of the multisampled brush target
+    fn encode_msaa_load(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        pipelines: &MsaaPipelines,
+        source: &wgpu::TextureView,
+        msaa_view: &wgpu::TextureView,
+    ) {
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("MSAA Load Bind Group"),
+            layout: &pipelines.load.get_bind_group_layout(0),
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(source),
+            }],
         });
-        
-        // Render dabs to canvas texture
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Brush Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &self.canvas_view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Load,  // Keep existing canvas content
-                        store: wgpu::StoreOp::Store,
-                    },
-                    depth_slice: None,
-                })],
-                depth_stencil_attachment: None,
-                timestamp_writes: None,
-                occlusion_query_set: None,
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("MSAA Load Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: msaa_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    // Every sample is overwritten
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(&pipelines.load);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.draw(0..6, 0..1);
+    }
+
+    /// Get the custom brush mask source, if one is set
+    pub fn custom_brush_shader(&self) -> Option<&str> {
+        self.custom_brush_mask.as_deref()
+    }
+
+    /// Set a custom WGSL brush tip mask, recompiling the brush pipelines with it
+    ///
+    /// The snippet must define `fn brush_mask(uv: vec2<f32>) -> f32`, returning coverage
+    /// (0-1) at `uv` in the dab's rotated frame (-1 to 1 across the tip). It may add helper
+    /// functions and constants, but no bindings or entry points. The mask multiplies the
+    /// procedural falloff like a stamp texture; use full hardness to get the mask alone.
+    /// On error (with the compiler's message) the current brush shader is kept.
+    pub fn set_custom_brush_shader(&mut self, wgsl: &str) -> Result<(), String> {
+        let source = crate::brush_shader::build_brush_shader(Some(wgsl))?;
+        self.rebuild_brush_pipelines(&source);
+        self.custom_brush_mask = Some(wgsl.to_string());
+        log::info!("Custom brush shader set ({} bytes)", wgsl.len());
+        Ok(())
+    }
+
+    /// Remove the custom brush mask (dabs use the built-in shader)
+    pub fn clear_custom_brush_shader(&mut self) {
+        if self.custom_brush_mask.take().is_none() {
+            return;
+        }
+        let source = crate::brush_shader::build_brush_shader(None)
+            .expect("Built-in brush shader failed to build");
+        self.rebuild_brush_pipelines(&source);
+        log::info!("Custom brush shader cleared");
+    }
+
+    /// Recreate the brush pipelines from shader source, along with the bind groups made from them
+    fn rebuild_brush_pipelines(&mut self, shader_source: &str) {
+        let (brush_pipeline, erase_pipeline, add_pipeline) = Self::create_brush_pipelines(
+            &self.device,
+            &self.brush_pipeline_layout,
+            self.canvas_format,
+            shader_source,
+            1,
+        );
+        self.brush_pipeline = brush_pipeline;
+        self.erase_pipeline = erase_pipeline;
+        self.add_pipeline = add_pipeline;
+        self.msaa_pipelines = (self.antialiasing == AaMode::Msaa4x).then(|| {
+            Self::create_msaa_pipelines(&self.device, &self.brush_pipeline_layout, self.canvas_format, shader_source)
+        });
+        if self.msaa_pipelines.is_none() {
+            self.msaa_target = None;
+        }
+        self.brush_bind_group = Self::create_brush_bind_group(&self.device, &self.brush_pipeline, &self.brush_uniform_buffer);
+        self.recreate_brush_texture_bind_group();
+    }
+
+    /// Largest accepted brush stamp dimension (limited by the device's max texture size)
+    fn max_brush_texture_size(&self) -> u32 {
+        MAX_BRUSH_TEXTURE_SIZE.min(self.max_texture_dimension)
+    }
+
+    /// Remove the brush grain (dabs use the shape mask only)
+    pub fn clear_brush_grain(&mut self) {
+        self.write_grain_uniforms(1, 1, 1.0, false);
+        self.grain_source = None;
+        log::info!("Brush grain cleared");
+    }
+
+    /// Write grain uniforms to the GPU
+    fn write_grain_uniforms(&self, width: u32, height: u32, scale: f32, enabled: bool) {
+        self.flush_brush_batch();
+        let grain_uniforms = GrainUniforms {
+            grain_size: [width as f32, height as f32],
+            grain_scale: scale,
+            grain_enabled: if enabled { 1 } else { 0 },
+        };
+        self.queue.write_buffer(
+            &self.grain_uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[grain_uniforms]),
+        );
+    }
+
+    /// Wait until all GPU work submitted so far has completed
+    ///
+    /// Use before handing canvas contents to another API so nothing is still in flight.
+    /// Resolves immediately (after one queue round-trip) when nothing is pending.
+    /// On native the device is polled to completion; on the web the browser drives
+    /// the queue and the returned future resolves when it reports the work done.
+    pub async fn wait_idle(&self) {
+        self.flush_brush_batch();
+        let (tx, rx) = futures::channel::oneshot::channel();
+        self.queue.on_submitted_work_done(move || {
+            let _ = tx.send(());
+        });
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Err(e) = self.device.poll(wgpu::PollType::wait_indefinitely()) {
+            log::warn!("Failed to wait for GPU idle: {:?}", e);
+        }
+
+        if rx.await.is_err() {
+            log::warn!("GPU work-done callback was dropped");
+        }
+    }
+
+    /// Copy every paint layer back to the CPU, to restore after rebuilding the renderer
+    ///
+    /// Commit any stroke layer first: in-progress coverage isn't part of the layers. Fails
+    /// once the device is lost, so take snapshots ahead of time (e.g. when hidden).
+    pub async fn snapshot_canvas(&self) -> Result<CanvasSnapshot, String> {
+        let size = self.canvas_texture().size();
+        let size = [size.width, size.height];
+        let mut layers = Vec::with_capacity(self.layers.len());
+        for layer in &self.layers {
+            layers.push(LayerSnapshot {
+                texels: self.read_texture_region(&layer.texture, [0, 0], size).await?,
+                visible: layer.visible,
+                opacity: layer.opacity,
+                has_strokes: layer.has_strokes,
             });
-            
-            render_pass.set_pipeline(&self.brush_pipeline);
-            render_pass.set_bind_group(0, &self.brush_bind_group, &[]);
-            render_pass.set_vertex_buffer(0, instance_buffer.slice(..));
-            
-            // Draw 6 vertices per instance (2 triangles = 1 quad per dab)
-            render_pass.draw(0..6, 0..instances.len() as u32);
         }
-        
-        self.queue.submit(std::iter::once(encoder.finish()));
-        log::debug!("Rendered {} brush dabs", dabs.len());
+        log::info!("Snapshotted {} layers of {}x{} canvas", layers.len(), size[0], size[1]);
+        Ok(CanvasSnapshot { format: self.canvas_format, size, active_layer: self.active_layer, layers })
+    }
+
+    /// Put back the layers of a snapshot taken with `snapshot_canvas`
+    ///
+    /// The canvas must have the same format and texture size as when the snapshot was
+    /// taken (apply the same config first). Layers are added to match the snapshot; the
+    /// undo history is not restored.
+    pub fn restore_canvas(&mut self, snapshot: &CanvasSnapshot) -> Result<(), String> {
+        let size = self.canvas_texture().size();
+        if snapshot.format != self.canvas_format || snapshot.size != [size.width, size.height] {
+            return Err(format!(
+                "Snapshot of a {:?} {}x{} canvas doesn't fit the {:?} {}x{} canvas",
+                snapshot.format, snapshot.size[0], snapshot.size[1], self.canvas_format, size.width, size.height
+            ));
+        }
+
+        self.flush_brush_batch();
+        self.stroke_layer_active = false;
+        self.discard_undo_bases();
+        while self.layers.len() < snapshot.layers.len() {
+            self.add_layer();
+        }
+        while self.layers.len() > snapshot.layers.len() {
+            self.remove_layer(self.layers.len() - 1)?;
+        }
+        let bytes_per_row = size.width * Self::canvas_texel_size(snapshot.format) as u32;
+        for (layer, saved) in self.layers.iter_mut().zip(&snapshot.layers) {
+            self.queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    texture: &layer.texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &saved.texels,
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: Some(size.height),
+                },
+                size,
+            );
+            layer.visible = saved.visible;
+            layer.opacity = saved.opacity;
+            layer.has_strokes = saved.has_strokes;
+        }
+        self.active_layer = snapshot.active_layer.min(self.layers.len() - 1);
+        self.update_layer_composite_target();
+        log::info!("Restored {} layers from snapshot", snapshot.layers.len());
+        Ok(())
     }
 
-    pub fn is_valid_surface(&self) -> bool {
-        self.config.width > 0 
-        && self.config.height > 0 
-        && self.surface.get_current_texture().is_ok()
+    /// Take the brush and reference images uploaded so far, to restore on a rebuilt renderer
+    ///
+    /// This renderer keeps showing them but can't hand them out again.
+    pub fn take_uploaded_images(&mut self) -> UploadedImages {
+        UploadedImages {
+            stamp: self.stamp_source.take(),
+            grain: self.grain_source.take(),
+            reference: self.reference_data.take().zip(self.reference_size),
+            reference_opacity: self.reference_opacity,
+            reference_visible: self.reference_visible,
+        }
     }
 
-    /// Render a frame (blit canvas to surface)
-    pub fn render(&mut self) {
-        if !self.is_valid_surface() {
-            log::warn!("Invalid surface state, skipping render");
-            return;
+    /// Upload the images taken from another renderer with `take_uploaded_images`
+    ///
+    /// Fails if an image exceeds this device's limits; the images before it are kept.
+    pub fn restore_uploaded_images(&mut self, images: &UploadedImages) -> Result<(), String> {
+        if let Some((mask, [width, height])) = &images.stamp {
+            self.set_brush_texture(mask, *width, *height)?;
+        }
+        if let Some((mask, [width, height], scale)) = &images.grain {
+            self.set_brush_grain(mask, *width, *height, *scale)?;
         }
+        if let Some((data, [width, height])) = &images.reference {
+            self.set_reference_image(data, *width, *height)?;
+        }
+        self.set_reference_opacity(images.reference_opacity);
+        self.set_reference_visible(images.reference_visible);
+        Ok(())
+    }
 
-        // Get the next frame
-        let output = match self.surface.get_current_texture() {
-            Ok(output) => output,
-            Err(e) => {
-                log::error!("Failed to get surface texture: {:?}", e);
-                return;
-            }
+    /// Read canvas texture back to CPU as RGBA8 data
+    /// This is an expensive operation requiring GPU->CPU transfer
+    /// Covers the visible area, plus the bleed margin if `set_export_includes_bleed` is on,
+    /// with the background gradient beneath if `set_export_includes_background` is on
+    pub async fn read_canvas_rgba8(&self) -> Result<Vec<u8>, String> {
+        let (origin, size) = self.export_region();
+        let mut rgba8_data = if self.layer_composite.is_some() {
+            // Flatten without the stroke layer, like the single-layer path reads the canvas
+            let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Layer Composite Readback Encoder"),
+            });
+            self.encode_layer_composite(&mut encoder, false);
+            self.submit(encoder);
+            self.read_texture_rgba8(self.display_texture(), origin, size, self.dithering).await?
+        } else {
+            self.read_texture_rgba8(self.canvas_texture(), origin, size, self.dithering).await?
         };
+        if let (true, Some(gradient)) = (self.export_includes_background, &self.background_gradient) {
+            let bleed = self.bleed_px();
+            let canvas_origin = [origin[0] as f32 - bleed as f32, origin[1] as f32 - bleed as f32];
+            let linear = self.blend_color_space == BlendColorSpace::Linear;
+            gradient.composite_under_rgba8(&mut rgba8_data, size[0], canvas_origin, self.canvas_size(), linear);
+        }
+        Ok(rgba8_data)
+    }
 
-        let view = output
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
-
-        // Create command encoder
-        let mut encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("Render Encoder"),
+    /// Read canvas texture back to CPU as RGBA16 data (16-bit unsigned normalized)
+    ///
+    /// Covers the same region as `read_canvas_rgba8`, with the background gradient beneath
+    /// if that is on, but keeps the canvas's full depth and is never dithered. Values are
+    /// in the blend color space and premultiplied by alpha, as the canvas stores them
+    /// (`export::encode_png16` unpremultiplies for PNG). Values above 1.0 are brought into
+    /// range per `set_export_hdr_mode`, on the straight color so alpha is unaffected.
+    pub async fn read_canvas_rgba16(&self) -> Result<Vec<u16>, String> {
+        let (origin, size) = self.export_region();
+        let texture = if self.layer_composite.is_some() {
+            // Flatten without the stroke layer, like the single-layer path reads the canvas
+            let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Layer Composite Readback Encoder"),
             });
+            self.encode_layer_composite(&mut encoder, false);
+            self.submit(encoder);
+            self.display_texture()
+        } else {
+            self.canvas_texture()
+        };
+        let format = texture.format();
+        let raw_data = self.read_texture_region(texture, origin, size).await?;
+        let bytes_per_pixel = Self::canvas_texel_size(format);
+        let mut pixels: Vec<[f32; 4]> = raw_data
+            .chunks_exact(bytes_per_pixel)
+            .map(|texel| Self::decode_texel(format, texel))
+            .collect();
 
-        // Blit canvas texture to surface using full-screen quad
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Blit Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
-                        store: wgpu::StoreOp::Store,
-                    },
-                    depth_slice: None,
-                })],
-                depth_stencil_attachment: None,
-                timestamp_writes: None,
-                occlusion_query_set: None,
-            });
+        if let (true, Some(gradient)) = (self.export_includes_background, &self.background_gradient) {
+            let bleed = self.bleed_px();
+            let canvas_origin = [origin[0] as f32 - bleed as f32, origin[1] as f32 - bleed as f32];
+            let linear = self.blend_color_space == BlendColorSpace::Linear;
+            gradient.composite_under(&mut pixels, size[0], canvas_origin, self.canvas_size(), linear);
+        }
 
-            render_pass.set_pipeline(&self.blit_pipeline);
-            render_pass.set_bind_group(0, &self.blit_bind_group, &[]);
-            render_pass.draw(0..6, 0..1);
+        let mut rgba16_data = Vec::with_capacity(pixels.len() * 4);
+        for pixel in pixels {
+            let alpha = pixel[3].clamp(0.0, 1.0);
+            for value in &pixel[..3] {
+                let premultiplied = if alpha > 0.0 {
+                    self.export_hdr_mode.apply(value / alpha) * alpha
+                } else {
+                    0.0
+                };
+                rgba16_data.push(crate::color::quantize_unorm16(premultiplied));
+            }
+            rgba16_data.push(crate::color::quantize_unorm16(alpha));
         }
 
-        // Submit commands
-        self.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
+        log::info!("Canvas read back at 16 bits: {}x{} pixels", size[0], size[1]);
+        Ok(rgba16_data)
     }
 
-    /// Clear the canvas to a color
-    pub fn clear_canvas(&self, clear_color: &[f64; 4]) {
-        let clear_color = match self.blend_color_space {
-            BlendColorSpace::Linear => crate::color::srgb_to_linear_rgba_f64(clear_color),
-            BlendColorSpace::Srgb => *clear_color,
-        };
+    /// Read the visible area of the active layer back to CPU as RGBA8 data, for bucket fills
+    ///
+    /// Unlike `read_canvas_rgba8` this reads the active layer alone, without the stroke layer
+    /// (commit it first), the bleed margin, the background, or dithering, so a flat region
+    /// reads back as one flat color.
+    ///
+    /// # Returns
+    /// The pixels and their size
+    pub async fn read_active_layer_rgba8(&self) -> Result<(Vec<u8>, [u32; 2]), String> {
+        let bleed = self.bleed_px();
+        let size = self.visible_canvas_size();
+        let rgba8_data = self.read_texture_rgba8(self.canvas_texture(), [bleed, bleed], size, false).await?;
+        Ok((rgba8_data, size))
+    }
 
-        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-            label: Some("Clear Canvas Encoder"),
-        });
+    /// Read back the color of one canvas pixel, for the eyedropper
+    ///
+    /// Samples the active layer at canvas position (`x`, `y`) with a 1x1 copy, without the
+    /// stroke layer (commit it first). The stored premultiplied blend-space value is
+    /// converted to straight sRGB at full precision.
+    ///
+    /// # Returns
+    /// The color as straight sRGB RGBA (0.0-1.0), transparent black for an empty pixel; an
+    /// error if the position is outside the canvas or the readback fails
+    pub async fn sample_pixel(&self, x: f32, y: f32) -> Result<[f32; 4], String> {
+        let [width, height] = self.canvas_size();
+        if !(x >= 0.0 && y >= 0.0 && x < width && y < height) {
+            return Err(format!("Position ({}, {}) is outside the canvas", x, y));
+        }
+        let bleed = self.bleed_px();
+        let texture = self.canvas_texture();
+        let texel = self.read_texture_region(texture, [x as u32 + bleed, y as u32 + bleed], [1, 1]).await?;
+        let premultiplied = Self::decode_texel(texture.format(), &texel);
+        let linear = self.blend_color_space == BlendColorSpace::Linear;
+        Ok(crate::color::premultiplied_to_straight_srgb(premultiplied, linear))
+    }
 
-        {
-            let _render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Clear Canvas Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &self.canvas_view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: clear_color[0],
-                            g: clear_color[1],
-                            b: clear_color[2],
-                            a: clear_color[3],
-                        }),
-                        store: wgpu::StoreOp::Store,
-                    },
-                    depth_slice: None,
-                })],
-                depth_stencil_attachment: None,
-                timestamp_writes: None,
-                occlusion_query_set: None,
-            });
+    /// Read a region of a texture in a canvas format back to CPU as RGBA8 data
+    /// Values come back in the blend color space, like the canvas stores them; `dither`
+    /// dithers the quantization of higher precision formats
+    async fn read_texture_rgba8(
+        &self,
+        texture: &wgpu::Texture,
+        origin: [u32; 2],
+        size: [u32; 2],
+        dither: bool,
+    ) -> Result<Vec<u8>, String> {
+        let format = texture.format();
+        let [width, height] = size;
+        let raw_data = self.read_texture_region(texture, origin, size).await?;
+
+        // Rgba16Float is converted to RGBA8; the data in the buffer is f16 values (2 bytes
+        // per channel). Rgba8Unorm bytes are already blend-space values.
+        if format == wgpu::TextureFormat::Rgba8Unorm {
+            return Ok(raw_data);
+        }
+        let mut rgba8_data = Vec::with_capacity((width * height) as usize * 4);
+        let bytes_per_pixel = Self::canvas_texel_size(format) as u32;
+        for y in 0..height {
+            for x in 0..width {
+                // Truncate as before unless dithering, which thresholds against the
+                // pattern at the pixel's canvas-texture position
+                let threshold = if dither {
+                    crate::color::ordered_dither_threshold(origin[0] + x, origin[1] + y)
+                } else {
+                    0.0
+                };
+                let pixel_offset = ((y * width + x) * bytes_per_pixel) as usize;
+                let texel = Self::decode_texel(format, &raw_data[pixel_offset..pixel_offset + bytes_per_pixel as usize]);
+                for value in &texel[..3] {
+                    rgba8_data.push(crate::color::quantize_unorm8(*value, threshold));
+                }
+                // 8-bit alpha is stored linearly, so it is copied as is
+                rgba8_data.push(match format {
+                    wgpu::TextureFormat::Rgba8UnormSrgb => raw_data[pixel_offset + 3],
+                    _ => crate::color::quantize_unorm8(texel[3], threshold),
+                });
+            }
         }
 
-        self.queue.submit(std::iter::once(encoder.finish()));
-        log::debug!("Canvas cleared to color: {:?}", clear_color);
+        log::info!("Texture read back: {}x{} pixels ({} bytes)", width, height, rgba8_data.len());
+        Ok(rgba8_data)
     }
 
-    /// Get the current surface size
-    pub fn size(&self) -> winit::dpi::PhysicalSize<u32> {
-        self.size
+    /// Bytes per texel of a canvas format, as copied back by `read_texture_region`
+    fn canvas_texel_size(format: wgpu::TextureFormat) -> usize {
+        format.block_copy_size(None).expect("Canvas formats have a fixed texel size") as usize
     }
 
-    /// Get the current blend color space
-    pub fn blend_color_space(&self) -> BlendColorSpace {
-        self.blend_color_space
+    /// Decode one texel of a canvas format into blend-space values (premultiplied, as stored)
+    fn decode_texel(format: wgpu::TextureFormat, bytes: &[u8]) -> [f32; 4] {
+        match format {
+            // Stored sRGB-encoded but sampled (and blended) as linear: decode the color
+            wgpu::TextureFormat::Rgba8UnormSrgb => [
+                crate::color::srgb_to_linear(bytes[0] as f32 / 255.0),
+                crate::color::srgb_to_linear(bytes[1] as f32 / 255.0),
+                crate::color::srgb_to_linear(bytes[2] as f32 / 255.0),
+                bytes[3] as f32 / 255.0,
+            ],
+            wgpu::TextureFormat::Rgba16Float => {
+                std::array::from_fn(|channel| half::f16::from_le_bytes([bytes[channel * 2], bytes[channel * 2 + 1]]).to_f32())
+            }
+            _ => std::array::from_fn(|channel| bytes[channel] as f32 / 255.0),
+        }
     }
 
-    /// Set the blend color space
-    pub fn set_blend_color_space(&mut self, color_space: BlendColorSpace) {
-        if self.blend_color_space == color_space {
-            return;
-        }
+    /// Copy a region of a texture in a canvas format back to CPU, as tightly packed rows
+    ///
+    /// Rows are copied into a buffer padded to the 256-byte row alignment WebGPU requires
+    /// (even a single pixel takes a full 256-byte row), then the padding is stripped.
+    async fn read_texture_region(
+        &self,
+        texture: &wgpu::Texture,
+        origin: [u32; 2],
+        size: [u32; 2],
+    ) -> Result<Vec<u8>, String> {
+        let format = texture.format();
+        // Rgba16Float is 8 bytes per pixel (4 channels * 2 bytes per f16), the 8-bit formats 4
+        let bytes_per_pixel = match format {
+            wgpu::TextureFormat::Rgba16Float => 8,
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => 4,
+            _ => {
+                let message = format!("Unsupported readback format: {:?}", format);
+                report_error(ErrorCode::UnsupportedFormat, &message);
+                return Err(message);
+            }
+        };
 
-        log::info!("Switching blend color space from {:?} to {:?}", self.blend_color_space, color_space);
-        self.blend_color_space = color_space;
+        let [width, height] = size;
 
-        // Update uniform buffer with new blend mode value
-        let blit_uniforms = BlitUniforms {
-            blend_mode: match self.blend_color_space {
-                BlendColorSpace::Linear => 0,
-                BlendColorSpace::Srgb => 1,
-            },
-            _padding: [0; 3],
-        };
-        self.queue.write_buffer(
-            &self.blit_uniform_buffer,
-            0,
-            bytemuck::cast_slice(&[blit_uniforms]),
-        );
-    }
+        log::info!("Reading texture: {}x{} pixels", width, height);
 
-    /// Read canvas texture back to CPU as RGBA8 data
-    /// This is an expensive operation requiring GPU->CPU transfer
-    #[cfg(target_arch = "wasm32")]
-    pub async fn read_canvas_rgba8(&self) -> Result<Vec<u8>, String> {
-        // Use canvas texture dimensions, not surface config dimensions
-        let width = self.canvas_texture.width();
-        let height = self.canvas_texture.height();
-        let pixel_count = (width * height) as usize;
-        
-        log::info!("Reading canvas texture: {}x{} pixels", width, height);
-        
         // Create a buffer to copy texture data into
-        // Canvas is Rgba16Float (8 bytes per pixel: 4 channels * 2 bytes per f16)
-        let bytes_per_pixel = 8;
         let bytes_per_row_unpadded = width * bytes_per_pixel;
         // Align to 256 bytes per row as required by WebGPU
-        let bytes_per_row_padded = ((bytes_per_row_unpadded + 255) / 256) * 256;
+        let bytes_per_row_padded = bytes_per_row_unpadded.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
         let buffer_size = (bytes_per_row_padded * height) as u64;
-        
+
         log::debug!(
             "Buffer layout: unpadded={}, padded={}, buffer_size={}",
             bytes_per_row_unpadded, bytes_per_row_padded, buffer_size
         );
-        
-        // Validate that padded row is sufficient
-        if bytes_per_row_padded < bytes_per_row_unpadded {
-            return Err(format!(
-                "Invalid padding: padded ({}) < unpadded ({})",
-                bytes_per_row_padded, bytes_per_row_unpadded
-            ));
-        }
-        
+
         let output_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
             label: Some("Canvas Readback Buffer"),
             size: buffer_size,
             usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
             mapped_at_creation: false,
         });
-        
+
         // Create command encoder for copy operation
         let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: Some("Canvas Readback Encoder"),
         });
-        
+
         // Copy canvas texture to buffer
         encoder.copy_texture_to_buffer(
             wgpu::TexelCopyTextureInfo {
-                texture: &self.canvas_texture,
+                texture,
                 mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
+                origin: wgpu::Origin3d { x: origin[0], y: origin[1], z: 0 },
                 aspect: wgpu::TextureAspect::All,
             },
             wgpu::TexelCopyBufferInfo {
@@ -900,56 +5538,241 @@ impl Renderer {
                 depth_or_array_layers: 1,
             },
         );
-        
-        self.queue.submit(std::iter::once(encoder.finish()));
-        
+
+        self.submit(encoder);
+
         // Map the buffer to read data back
         let buffer_slice = output_buffer.slice(..);
         let (tx, rx) = futures::channel::oneshot::channel();
-        
+
         buffer_slice.map_async(wgpu::MapMode::Read, move |result| {
             let _ = tx.send(result);
         });
-        
+
+        // Native backends only run the map callback while the device is polled
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Err(e) = self.device.poll(wgpu::PollType::wait_indefinitely()) {
+            log::warn!("Failed to wait for readback: {:?}", e);
+        }
+
         // Wait for mapping to complete (device.poll happens internally in WASM)
-        rx.await
-            .map_err(|_| "Failed to receive buffer map result".to_string())?
-            .map_err(|e| format!("Failed to map buffer: {:?}", e))?;
-        
-        // Read the data
+        let mapped = rx.await
+            .map_err(|_| "Failed to receive buffer map result".to_string())
+            .and_then(|result| result.map_err(|e| format!("Failed to map buffer: {:?}", e)));
+        if let Err(message) = mapped {
+            report_error(ErrorCode::ReadbackFailed, &message);
+            return Err(message);
+        }
+
+        // Strip the row padding
         let mapped_data = buffer_slice.get_mapped_range();
-        
-        // Canvas texture is Rgba16Float, so we need to convert to RGBA8
-        // The data in the buffer is f16 values (2 bytes per channel)
-        let mut rgba8_data = Vec::with_capacity(pixel_count * 4);
-        
-        for y in 0..height {
-            let row_offset = (y * bytes_per_row_padded) as usize;
-            for x in 0..width {
-                let pixel_offset = row_offset + (x * 8) as usize; // 8 bytes per pixel (4 * f16)
-                
-                // Read f16 values and convert to u8
-                for channel in 0..4 {
-                    let offset = pixel_offset + channel * 2;
-                    if offset + 1 < mapped_data.len() {
-                        let f16_bytes = [mapped_data[offset], mapped_data[offset + 1]];
-                        let f16_val = half::f16::from_le_bytes(f16_bytes);
-                        let f32_val = f16_val.to_f32();
-                        // Convert 0.0-1.0 float to 0-255 u8, clamping for safety
-                        let u8_val = (f32_val * 255.0).clamp(0.0, 255.0) as u8;
-                        rgba8_data.push(u8_val);
-                    } else {
-                        rgba8_data.push(0); // Fallback for out-of-bounds
-                    }
-                }
-            }
+        let mut data = Vec::with_capacity((bytes_per_row_unpadded * height) as usize);
+        for row in mapped_data.chunks_exact(bytes_per_row_padded as usize) {
+            data.extend_from_slice(&row[..bytes_per_row_unpadded as usize]);
         }
-        
         drop(mapped_data);
         output_buffer.unmap();
-        
-        log::info!("Canvas texture read back: {}x{} pixels ({} bytes)", width, height, rgba8_data.len());
-        Ok(rgba8_data)
+        Ok(data)
+    }
+
+    /// Upload a reference image (sRGB RGBA8) for use with `render_comparison`
+    ///
+    /// The texture format is chosen so sampling yields values in the current blend
+    /// color space, matching what the canvas stores.
+    pub fn create_reference_texture(&self, data: &[u8], width: u32, height: u32) -> Result<wgpu::Texture, String> {
+        if width == 0 || height == 0 {
+            return Err("Reference image must not be empty".to_string());
+        }
+        if width > self.max_texture_dimension || height > self.max_texture_dimension {
+            return Err(format!(
+                "Reference image {}x{} exceeds max texture size {}",
+                width, height, self.max_texture_dimension
+            ));
+        }
+        if data.len() != (width * height * 4) as usize {
+            return Err(format!(
+                "Reference data size mismatch: expected {} bytes for {}x{}, got {}",
+                width * height * 4, width, height, data.len()
+            ));
+        }
+
+        let format = match self.blend_color_space {
+            BlendColorSpace::Linear => wgpu::TextureFormat::Rgba8UnormSrgb,  // Decoded to linear on sample
+            BlendColorSpace::Srgb => wgpu::TextureFormat::Rgba8Unorm,        // Sampled as raw sRGB values
+        };
+        let size = wgpu::Extent3d { width, height, depth_or_array_layers: 1 };
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Reference Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        self.queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            data,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(width * 4),
+                rows_per_image: Some(height),
+            },
+            size,
+        );
+        Ok(texture)
+    }
+
+    /// Composite the canvas against a reference texture for before/after comparison
+    ///
+    /// The reference is stretched to the canvas size. The result is a new texture in
+    /// the canvas format and color space, suitable for display or readback/export.
+    pub fn render_comparison(&self, reference: &wgpu::Texture, mode: ComparisonMode) -> wgpu::Texture {
+        // Compare the visible canvas only; the bleed margin is left out
+        let [width, height] = self.visible_canvas_size();
+        let (output_texture, output_view) = Self::create_canvas_texture(
+            &self.device,
+            width,
+            height,
+            self.canvas_format,
+        );
+        let bleed = self.bleed_px() as f32;
+        let texture_width = self.canvas_texture().width() as f32;
+        let texture_height = self.canvas_texture().height() as f32;
+
+        let compare_uniforms = CompareUniforms {
+            mode: match mode {
+                ComparisonMode::Difference => 0,
+                ComparisonMode::Overlay => 1,
+            },
+            _padding: [0; 3],
+            canvas_uv_rect: [
+                bleed / texture_width,
+                bleed / texture_height,
+                width as f32 / texture_width,
+                height as f32 / texture_height,
+            ],
+        };
+        let uniform_buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Compare Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[compare_uniforms]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let reference_view = reference.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Compare Bind Group"),
+            layout: &self.compare_pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(self.display_view()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&reference_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&self.canvas_sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Compare Encoder"),
+        });
+        self.encode_layer_composite(&mut encoder, false);
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Compare Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &output_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                    depth_slice: None,
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            render_pass.set_pipeline(&self.compare_pipeline);
+            render_pass.set_bind_group(0, &bind_group, &[]);
+            render_pass.draw(0..6, 0..1);
+        }
+        self.submit(encoder);
+
+        log::debug!("Rendered {:?} comparison", mode);
+        output_texture
+    }
+
+    /// Composite the canvas against a reference image and read the result back as RGBA8
+    /// The reference is sRGB RGBA8 data; the output uses the same encoding as `read_canvas_rgba8`
+    pub async fn read_comparison_rgba8(
+        &self,
+        reference_data: &[u8],
+        width: u32,
+        height: u32,
+        mode: ComparisonMode,
+    ) -> Result<Vec<u8>, String> {
+        let reference = self.create_reference_texture(reference_data, width, height)?;
+        let output = self.render_comparison(&reference, mode);
+        self.read_texture_rgba8(&output, [0, 0], [output.width(), output.height()], self.dithering).await
+    }
+
+    /// Read the canvas back and encode it as a PNG
+    /// The PNG is tagged with the color space the canvas is currently blended in
+    pub async fn export_png(&self) -> Result<Vec<u8>, String> {
+        let rgba8_data = self.read_canvas_rgba8().await?;
+        let (_, [width, height]) = self.export_region();
+        crate::export::encode_png(
+            &rgba8_data,
+            width,
+            height,
+            self.blend_color_space,
+        )
+    }
+
+    /// Read the canvas back at full depth and encode it as a 16-bit PNG
+    /// Tagged like `export_png`; see `read_canvas_rgba16` for how HDR values are handled
+    pub async fn export_png16(&self) -> Result<Vec<u8>, String> {
+        let rgba16_data = self.read_canvas_rgba16().await?;
+        let (_, [width, height]) = self.export_region();
+        crate::export::encode_png16(
+            &rgba16_data,
+            width,
+            height,
+            self.blend_color_space,
+        )
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_removing_a_layer_keeps_the_active_layer_in_place() {
+        // Layers above the active one don't move it
+        assert_eq!(active_layer_after_removal(1, 2), 1);
+        // Removing one beneath shifts it down with the rest of the stack
+        assert_eq!(active_layer_after_removal(2, 0), 1);
+        // Removing the active layer hands over to the one beneath, or the new bottom
+        assert_eq!(active_layer_after_removal(2, 2), 1);
+        assert_eq!(active_layer_after_removal(0, 0), 0);
+    }
+}
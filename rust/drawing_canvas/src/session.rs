@@ -0,0 +1,175 @@
+//! Timed Gesture-Drawing Sessions
+//!
+//! A session steps through a list of pose durations (e.g. 30 s, 60 s, 5 min), as in timed
+//! gesture drawing practice. The app advances it at the start of every frame, and the
+//! window schedules a frame at least once a second while it runs (the event loop otherwise
+//! waits for input), so pose changes land on time and a countdown display stays current.
+//! Each pose change is reported to JavaScript as `onPoseAdvance(index)`.
+
+/// Longest gap between frames while a session runs, so a countdown display ticks every second
+const SESSION_TICK_MS: f64 = 1000.0;
+
+/// A running gesture-drawing session: pose durations and where the clock is in them
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoseSession {
+    /// Duration of each pose in milliseconds
+    durations_ms: Vec<f64>,
+    /// Whether the canvas is cleared when the next pose starts
+    auto_clear: bool,
+    /// Index of the current pose (equal to the pose count once the session is over)
+    pose: usize,
+    /// Time the current pose started, on the `crate::time::now_ms` clock
+    pose_start_ms: f64,
+}
+
+impl PoseSession {
+    /// Start a session at `now_ms` with the first pose
+    ///
+    /// # Arguments
+    /// * `durations_secs` - Duration of each pose in seconds (at least one, all positive)
+    /// * `auto_clear` - Whether the canvas is cleared when each following pose starts
+    /// * `now_ms` - Current time on the `crate::time::now_ms` clock
+    pub fn new(durations_secs: &[f32], auto_clear: bool, now_ms: f64) -> Result<Self, String> {
+        if durations_secs.is_empty() {
+            return Err("A session needs at least one pose".to_string());
+        }
+        if !durations_secs.iter().all(|secs| secs.is_finite() && *secs > 0.0) {
+            return Err("Pose durations must be positive numbers of seconds".to_string());
+        }
+        Ok(Self {
+            durations_ms: durations_secs.iter().map(|&secs| secs as f64 * 1000.0).collect(),
+            auto_clear,
+            pose: 0,
+            pose_start_ms: now_ms,
+        })
+    }
+
+    /// Number of poses in the session
+    pub fn pose_count(&self) -> usize {
+        self.durations_ms.len()
+    }
+
+    /// Index of the current pose, or None once the last pose is over
+    pub fn current_pose(&self) -> Option<usize> {
+        (self.pose < self.pose_count()).then_some(self.pose)
+    }
+
+    /// Check whether the last pose is over
+    pub fn is_finished(&self) -> bool {
+        self.current_pose().is_none()
+    }
+
+    /// Check whether the canvas is cleared between poses
+    pub fn auto_clear(&self) -> bool {
+        self.auto_clear
+    }
+
+    /// Time left in the current pose in milliseconds (0.0 once the session is over)
+    pub fn remaining_ms(&self, now_ms: f64) -> f64 {
+        match self.current_pose() {
+            Some(pose) => (self.pose_start_ms + self.durations_ms[pose] - now_ms).max(0.0),
+            None => 0.0,
+        }
+    }
+
+    /// Move past every pose whose time is up
+    ///
+    /// Poses are timed back to back from the session start, so a late frame doesn't push the
+    /// later poses back; if several are over (e.g. the tab was hidden), all are skipped.
+    ///
+    /// # Returns
+    /// The index of each pose entered, in order; the pose count stands for the session end
+    pub fn advance(&mut self, now_ms: f64) -> Vec<usize> {
+        let mut entered = Vec::new();
+        while let Some(pose) = self.current_pose() {
+            let end_ms = self.pose_start_ms + self.durations_ms[pose];
+            if now_ms < end_ms {
+                break;
+            }
+            self.pose_start_ms = end_ms;
+            self.pose += 1;
+            entered.push(self.pose);
+        }
+        entered
+    }
+
+    /// Time in milliseconds until the session next needs a frame: the end of the current
+    /// pose or the countdown's next whole second, whichever is sooner (None once over)
+    pub fn next_tick_ms(&self, now_ms: f64) -> Option<f64> {
+        self.current_pose()?;
+        let remaining = self.remaining_ms(now_ms);
+        let partial = remaining % SESSION_TICK_MS;
+        let to_next_second = if partial > 0.0 { partial } else { SESSION_TICK_MS };
+        Some(remaining.min(to_next_second))
+    }
+}
+
+/// Tell JavaScript a new pose started: `window.onPoseAdvance(index)`, if defined
+///
+/// `index` is the pose count when the session ends. The call is made once the current
+/// frame is done, so the callback can use the rest of the canvas API.
+#[cfg(target_arch = "wasm32")]
+pub fn notify_pose_advance(index: usize) {
+    use wasm_bindgen::{JsCast, JsValue};
+
+    wasm_bindgen_futures::spawn_local(async move {
+        let Some(window) = web_sys::window() else {
+            return;
+        };
+        // Looked up on every call, so the host can define or replace it at any time
+        let callback = js_sys::Reflect::get(&window, &JsValue::from_str("onPoseAdvance"))
+            .ok()
+            .and_then(|value| value.dyn_into::<js_sys::Function>().ok());
+        match callback {
+            Some(callback) => {
+                if let Err(e) = callback.call1(&JsValue::NULL, &JsValue::from_f64(index as f64)) {
+                    log::warn!("onPoseAdvance callback threw: {:?}", e);
+                }
+            }
+            None => log::debug!("Pose {} started (window.onPoseAdvance is not defined)", index),
+        }
+    });
+}
+
+// No JS host on native builds; the log line is the report
+#[cfg(not(target_arch = "wasm32"))]
+pub fn notify_pose_advance(index: usize) {
+    log::info!("Pose {} started", index);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_advances_through_poses_on_time() {
+        let mut session = PoseSession::new(&[30.0, 60.0], false, 1000.0).unwrap();
+        assert_eq!(session.current_pose(), Some(0));
+        assert_eq!(session.remaining_ms(11_000.0), 20_000.0);
+        assert!(session.advance(30_999.0).is_empty());
+
+        // A late frame still times the next pose from the end of the previous one
+        assert_eq!(session.advance(31_500.0), vec![1]);
+        assert_eq!(session.remaining_ms(31_500.0), 59_500.0);
+
+        // Frames tick on whole seconds of the countdown, and at the end of the pose
+        assert_eq!(session.next_tick_ms(31_500.0), Some(500.0));
+        assert_eq!(session.next_tick_ms(91_000.0 - 300.0), Some(300.0));
+
+        assert_eq!(session.advance(91_000.0), vec![2]);
+        assert!(session.is_finished());
+        assert_eq!(session.next_tick_ms(91_000.0), None);
+    }
+
+    #[test]
+    fn test_session_skips_every_pose_that_is_over() {
+        let mut session = PoseSession::new(&[1.0, 1.0, 5.0], true, 0.0).unwrap();
+        assert_eq!(session.advance(2_500.0), vec![1, 2]);
+        assert_eq!(session.current_pose(), Some(2));
+        assert_eq!(session.remaining_ms(2_500.0), 4_500.0);
+
+        assert!(PoseSession::new(&[], false, 0.0).is_err());
+        assert!(PoseSession::new(&[30.0, 0.0], false, 0.0).is_err());
+        assert!(PoseSession::new(&[f32::NAN], false, 0.0).is_err());
+    }
+}
@@ -13,6 +13,47 @@
 //   - Blit converts: sRGB → linear
 //   - Surface: Rgba8UnormSrgb (auto-converts linear → sRGB on write)
 //   - Result: sRGB → linear → sRGB preserves original colors
+//
+// Stroke layer:
+//   - While a PerStroke stroke is in progress its scratch layer is shown composited
+//     over the canvas at the stroke opacity (0 = no stroke layer), with the stroke's
+//     blend mode (Multiply etc. blend in linear color whatever the blend mode)
+//
+// Stroke prediction:
+//   - While input prediction is on, the predicted continuation of the stroke is shown
+//     over the canvas (normal blending, at the prediction opacity, 0 = none). It is
+//     redrawn every frame and display only, never part of the canvas
+//
+// Paper:
+//   - Optionally shown beneath the canvas content (inside the canvas only), over the
+//     surface background; display only, so a transparent canvas exports transparent
+//
+// Background gradient:
+//   - Optionally shown beneath the canvas content (inside the canvas only), over the
+//     paper and surface background; defined in canvas space so it follows the view
+//
+// Reference image:
+//   - Optionally shown over the background (inside the canvas only) and beneath the
+//     canvas content, fit to the canvas aspect ratio; defined in canvas space so it
+//     follows the view. Display only, never part of the canvas
+//
+// Cursor ring:
+//   - While hovering, a brush outline is drawn over everything at the pointer: a ring at
+//     the brush radius and a fainter one where the soft edge starts (hardness x radius).
+//     Defined in canvas space so it follows the view, one surface pixel wide at any zoom
+//
+// Stabilizer tether:
+//   - While a stabilized stroke is drawn, a line from the pen to the lagging point the ink
+//     follows is drawn over everything, styled like the cursor ring
+//
+// Dithering:
+//   - When enabled, the output is ordered-dithered by one 8-bit step (in sRGB, the
+//     surface's encoding) so smooth gradients don't band on display
+//
+// View transform:
+//   - Each surface pixel is mapped back to canvas space with the inverse view matrix
+//     (rotation, zoom, and pan)
+//   - Pixels that land outside the canvas show the surface background
 
 struct VertexOutput {
     @builtin(position) position: vec4<f32>,
@@ -20,10 +61,34 @@ struct VertexOutput {
 }
 
 struct BlitUniforms {
+    // Canvas samples are already in this space (the sampler decodes an 8-bit sRGB canvas in linear mode)
     blend_mode: u32,  // 0 = Linear, 1 = sRGB
+    stroke_opacity: f32,  // Opacity of the in-progress stroke layer (0 = none)
+    bloom_intensity: f32,  // Glow around HDR (> 1.0) canvas pixels (0 = off)
+    dither_amplitude: f32,  // Ordered dither added before the 8-bit surface write (0 = off)
+    view_row0: vec4<f32>,    // Surface → canvas affine matrix, row 0 (a, b, tx, unused)
+    view_row1: vec4<f32>,    // Surface → canvas affine matrix, row 1 (c, d, ty, unused)
+    canvas_size: vec2<f32>,  // Visible canvas dimensions in pixels
+    canvas_offset: vec2<f32>,  // Bleed margin: texel offset of the visible area in the canvas texture
+    surface_background: vec4<f32>,  // sRGB color behind/around the canvas (alpha unused)
+    gradient_kind: u32,  // Background gradient under the canvas: 0 = none, 1 = linear, 2 = radial
+    gradient_stop_count: u32,
+    gradient_space: u32,  // Interpolation space: 0 = sRGB, 1 = linear, 2 = OKLab
+    wrap_mode: u32,  // Display tiling: 0 = clamp, 1 = repeat, 2 = mirrored repeat
+    gradient_positions: array<vec4<f32>, 2>,  // Stop positions, packed four per vec4
+    gradient_colors: array<vec4<f32>, 8>,     // Stop colors in the interpolation space, straight alpha
+    reference_rect: vec4<f32>,  // Reference image placement in canvas pixels (x, y, width, height)
+    reference_opacity: f32,     // Reference image opacity (0 = none/hidden)
+    stroke_blend_mode: u32,     // 0 = Normal, 1 = Multiply, 2 = Screen, 3 = Darken, 4 = Lighten
+    prediction_opacity: f32,    // Opacity of the predicted stroke overlay (0 = none)
+    cursor_visible: u32,        // Whether the cursor ring is drawn (0 = hidden)
+    paper_color: vec4<f32>,     // sRGB paper beneath the canvas content (straight alpha, 0 = none)
+    cursor_ring: vec4<f32>,     // Cursor ring in canvas pixels (center x, center y, radius, hardness)
+    tether_visible: u32,        // Whether the stabilizer tether is drawn (0 = hidden)
+    stroke_ceiling: f32,        // Opacity the stroke layer may build the canvas up to (1 = none)
     _padding0: u32,
     _padding1: u32,
-    _padding2: u32,
+    stabilizer_tether: vec4<f32>,  // Stabilizer tether in canvas pixels (pen x, pen y, ink x, ink y)
 }
 
 @group(0) @binding(0)
@@ -35,6 +100,18 @@ var canvas_sampler: sampler;
 @group(0) @binding(2)
 var<uniform> blit_uniforms: BlitUniforms;
 
+@group(0) @binding(3)
+var stroke_texture: texture_2d<f32>;
+
+@group(0) @binding(4)
+var reference_texture: texture_2d<f32>;  // sRGB texture: samples decode to linear, straight alpha
+
+@group(0) @binding(5)
+var reference_sampler: sampler;
+
+@group(0) @binding(6)
+var prediction_texture: texture_2d<f32>;  // Predicted dabs, premultiplied like the canvas
+
 // Vertex shader: Generate full-screen quad
 @vertex
 fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
@@ -87,27 +164,347 @@ fn srgb_to_linear(c: f32) -> f32 {
     }
 }
 
+// linear → sRGB conversion per component (inverse of srgb_to_linear)
+fn linear_to_srgb(c: f32) -> f32 {
+    if (c <= 0.0031308) {
+        return max(c, 0.0) * 12.92;
+    } else {
+        return 1.055 * pow(c, 1.0 / 2.4) - 0.055;
+    }
+}
+
+// 4x4 Bayer ordered dither threshold (0-1) for a pixel, matching color::ordered_dither_threshold
+fn bayer4(pixel: vec2<f32>) -> f32 {
+    let matrix = array<f32, 16>(0.0, 8.0, 2.0, 10.0, 12.0, 4.0, 14.0, 6.0, 3.0, 11.0, 1.0, 9.0, 15.0, 7.0, 13.0, 5.0);
+    let p = vec2<u32>(pixel) % vec2<u32>(4u);
+    return (matrix[p.y * 4u + p.x] + 0.5) / 16.0;
+}
+
+// Dither a linear color in sRGB space, where the surface quantizes it
+fn dither_linear(color: vec3<f32>, pixel: vec2<f32>) -> vec3<f32> {
+    let offset = (bayer4(pixel) - 0.5) * blit_uniforms.dither_amplitude;
+    return vec3<f32>(
+        srgb_to_linear(clamp(linear_to_srgb(color.r) + offset, 0.0, 1.0)),
+        srgb_to_linear(clamp(linear_to_srgb(color.g) + offset, 0.0, 1.0)),
+        srgb_to_linear(clamp(linear_to_srgb(color.b) + offset, 0.0, 1.0))
+    );
+}
+
+// OKLab → linear RGB (https://bottosson.github.io/posts/oklab/)
+fn oklab_to_linear(lab: vec3<f32>) -> vec3<f32> {
+    let l = lab.x + 0.3963377774 * lab.y + 0.2158037573 * lab.z;
+    let m = lab.x - 0.1055613458 * lab.y - 0.0638541728 * lab.z;
+    let s = lab.x - 0.0894841775 * lab.y - 1.2914855480 * lab.z;
+    let lms = vec3<f32>(l * l * l, m * m * m, s * s * s);
+    return vec3<f32>(
+        dot(vec3<f32>(4.0767416621, -3.3077115913, 0.2309699292), lms),
+        dot(vec3<f32>(-1.2684380046, 2.6097574011, -0.3413193965), lms),
+        dot(vec3<f32>(-0.0041960863, -0.7034186147, 1.7076147010), lms)
+    );
+}
+
+// Convert an interpolated gradient color back to sRGB (clamped to the gamut)
+fn gradient_decode(color: vec4<f32>) -> vec4<f32> {
+    var rgb = color.rgb;
+    if (blit_uniforms.gradient_space == 2u) {
+        rgb = oklab_to_linear(rgb);
+    }
+    if (blit_uniforms.gradient_space != 0u) {
+        rgb = vec3<f32>(linear_to_srgb(rgb.r), linear_to_srgb(rgb.g), linear_to_srgb(rgb.b));
+    }
+    return clamp(vec4<f32>(rgb, color.a), vec4<f32>(0.0), vec4<f32>(1.0));
+}
+
+// Background gradient color (sRGB, straight alpha) at a canvas position
+// Linear runs top to bottom of the canvas; radial runs from the center out to the corners
+fn gradient_color(canvas_pos: vec2<f32>) -> vec4<f32> {
+    let size = blit_uniforms.canvas_size;
+    var t = canvas_pos.y / max(size.y, 1.0);
+    if (blit_uniforms.gradient_kind == 2u) {
+        t = length(canvas_pos - 0.5 * size) / max(0.5 * length(size), 1.0);
+    }
+
+    let count = i32(blit_uniforms.gradient_stop_count);
+    var color = blit_uniforms.gradient_colors[0];
+    var prev_position = blit_uniforms.gradient_positions[0].x;
+    for (var i = 1; i < count; i++) {
+        let position = blit_uniforms.gradient_positions[i / 4][i % 4];
+        let next_color = blit_uniforms.gradient_colors[i];
+        if (t >= position) {
+            color = next_color;
+        } else if (t > prev_position) {
+            color = mix(color, next_color, (t - prev_position) / (position - prev_position));
+            break;
+        } else {
+            break;
+        }
+        prev_position = position;
+    }
+    return gradient_decode(color);
+}
+
+// sRGB ↔ linear conversion per channel (correct piecewise functions)
+fn srgb_to_linear3(c: vec3<f32>) -> vec3<f32> {
+    return select(pow((c + 0.055) / 1.055, vec3<f32>(2.4)), c / 12.92, c <= vec3<f32>(0.04045));
+}
+
+fn linear_to_srgb3(c: vec3<f32>) -> vec3<f32> {
+    return select(1.055 * pow(c, vec3<f32>(1.0 / 2.4)) - 0.055, max(c, vec3<f32>(0.0)) * 12.92, c <= vec3<f32>(0.0031308));
+}
+
+// Hold premultiplied `color` under the opacity ceiling: its alpha may not rise past
+// `ceiling`, nor fall below the backdrop's, so paint already more opaque is kept as is.
+// Keep in sync with the copy in layer_composite.wgsl.
+fn apply_ceiling(color: vec4<f32>, backdrop_alpha: f32, ceiling: f32) -> vec4<f32> {
+    let limit = max(backdrop_alpha, ceiling);
+    if (color.a <= limit) {
+        return color;
+    }
+    return color * (limit / color.a);
+}
+
+// Composite premultiplied `source` (the stroke layer) over premultiplied `backdrop`
+// Normal is plain "over" in the stored color space. The other modes use the W3C
+// separable blend formula on linear color, decoding first if the colors are sRGB-encoded.
+// Keep in sync with the copy in layer_composite.wgsl.
+fn blend_stroke(backdrop: vec4<f32>, source: vec4<f32>, mode: u32, srgb_encoded: bool) -> vec4<f32> {
+    if (mode == 0u) {
+        return source + backdrop * (1.0 - source.a);
+    }
+    let alpha = source.a + backdrop.a * (1.0 - source.a);
+    if (alpha <= 0.0) {
+        return vec4<f32>(0.0);
+    }
+
+    var cb = backdrop.rgb / max(backdrop.a, 1e-6);
+    var cs = source.rgb / max(source.a, 1e-6);
+    if (srgb_encoded) {
+        cb = srgb_to_linear3(cb);
+        cs = srgb_to_linear3(cs);
+    }
+    var mixed: vec3<f32>;
+    switch mode {
+        case 1u: {
+            mixed = cb * cs;  // Multiply
+        }
+        case 2u: {
+            mixed = cb + cs - cb * cs;  // Screen
+        }
+        case 3u: {
+            mixed = min(cb, cs);  // Darken
+        }
+        default: {
+            mixed = max(cb, cs);  // Lighten
+        }
+    }
+    // Where only one of the two has coverage, its own color shows through
+    var color = (cs * source.a * (1.0 - backdrop.a)
+        + cb * backdrop.a * (1.0 - source.a)
+        + mixed * source.a * backdrop.a) / alpha;
+    if (srgb_encoded) {
+        color = linear_to_srgb3(color);
+    }
+    return vec4<f32>(color * alpha, alpha);
+}
+
+// Wrap a canvas position back into the visible canvas, for the tiling preview
+fn wrap_canvas_pos(pos: vec2<f32>) -> vec2<f32> {
+    let size = blit_uniforms.canvas_size;
+    if (blit_uniforms.wrap_mode == 2u) {
+        // Mirrored: fold every other period back on itself
+        let period = 2.0 * size;
+        let folded = pos - period * floor(pos / period);
+        return size - abs(folded - size);
+    }
+    return pos - size * floor(pos / size);
+}
+
+// Bloom: gather the over-1.0 (HDR) part of the canvas from rings of taps around `uv`
+// Uses explicit-LOD sampling so it can run in non-uniform control flow
+fn bloom(uv: vec2<f32>, texel: vec2<f32>) -> vec3<f32> {
+    let radii = array<f32, 3>(3.0, 8.0, 16.0);
+    let weights = array<f32, 3>(0.5, 0.3, 0.2);
+    var glow = vec3<f32>(0.0);
+    for (var ring = 0; ring < 3; ring++) {
+        for (var i = 0; i < 8; i++) {
+            let angle = f32(i) * 0.785398 + f32(ring) * 0.392699;  // 45° steps, staggered per ring
+            let offset = vec2<f32>(cos(angle), sin(angle)) * radii[ring] * texel;
+            let color = textureSampleLevel(canvas_texture, canvas_sampler, uv + offset, 0.0).rgb;
+            glow += max(color - vec3<f32>(1.0), vec3<f32>(0.0)) * (weights[ring] / 8.0);
+        }
+    }
+    return glow;
+}
+
+// Coverage of a line `width` pixels wide centered `distance` pixels away (antialiased)
+fn line_coverage(distance: f32, width: f32) -> f32 {
+    return clamp(0.5 * width + 0.5 - abs(distance), 0.0, 1.0);
+}
+
+// Canvas pixels per surface pixel, from the surface → canvas matrix
+fn surface_pixel_size() -> f32 {
+    return max(length(vec2<f32>(blit_uniforms.view_row0.x, blit_uniforms.view_row1.x)), 1e-6);
+}
+
+// Draw the cursor ring over a linear color: a dark line with a light halo, so it shows
+// on any background. `canvas_pos` is unwrapped so the ring isn't repeated by tiling
+fn draw_cursor_ring(color: vec3<f32>, canvas_pos: vec2<f32>) -> vec3<f32> {
+    let ring = blit_uniforms.cursor_ring;
+    let pixel_size = surface_pixel_size();
+    let distance = length(canvas_pos - ring.xy) / pixel_size;
+    let outer = distance - ring.z / pixel_size;
+    var halo = line_coverage(outer, 3.0);
+    var line = line_coverage(outer, 1.0);
+    // Soft brushes: a fainter inner ring where the falloff starts
+    // (skipped while it would crowd the outer ring)
+    if (ring.z * (1.0 - ring.w) > 2.0 * pixel_size) {
+        let inner = distance - ring.z * ring.w / pixel_size;
+        halo = max(halo, 0.5 * line_coverage(inner, 3.0));
+        line = max(line, 0.5 * line_coverage(inner, 1.0));
+    }
+    let haloed = mix(color, vec3<f32>(0.8), halo);
+    return mix(haloed, vec3<f32>(0.0), line);
+}
+
+// Draw the stabilizer tether over a linear color, like the cursor ring: the segment from
+// the pen to the stabilized point, one surface pixel wide. `canvas_pos` is unwrapped
+fn draw_stabilizer_tether(color: vec3<f32>, canvas_pos: vec2<f32>) -> vec3<f32> {
+    let pen = blit_uniforms.stabilizer_tether.xy;
+    let along = blit_uniforms.stabilizer_tether.zw - pen;
+    let t = clamp(dot(canvas_pos - pen, along) / max(dot(along, along), 1e-6), 0.0, 1.0);
+    let distance = length(canvas_pos - (pen + along * t)) / surface_pixel_size();
+    let haloed = mix(color, vec3<f32>(0.8), line_coverage(distance, 3.0));
+    return mix(haloed, vec3<f32>(0.0), line_coverage(distance, 1.0));
+}
+
 // Fragment shader: Sample canvas and convert based on blend mode
 // Shader handles different color space conversions for each mode
 @fragment
 fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
-    // Sample color from canvas
-    let canvas_color = textureSample(canvas_texture, canvas_sampler, input.uv);
+    // Map this surface pixel back into canvas space through the view transform
+    let surface_pos = vec3<f32>(input.position.xy, 1.0);
+    let view_pos = vec2<f32>(
+        dot(blit_uniforms.view_row0.xyz, surface_pos),
+        dot(blit_uniforms.view_row1.xyz, surface_pos)
+    );
+    var canvas_pos = view_pos;
+    // Tiling preview: every surface pixel lands on some copy of the canvas
+    if (blit_uniforms.wrap_mode != 0u) {
+        canvas_pos = wrap_canvas_pos(canvas_pos);
+    }
+    let texture_size = blit_uniforms.canvas_size + 2.0 * blit_uniforms.canvas_offset;
+    let canvas_uv = (canvas_pos + blit_uniforms.canvas_offset) / texture_size;
+
+    // Sample color from canvas and stroke layer (unconditionally, to keep control flow uniform)
+    let base_color = textureSample(canvas_texture, canvas_sampler, canvas_uv);
+    let stroke_color = textureSample(stroke_texture, canvas_sampler, canvas_uv) * blit_uniforms.stroke_opacity;
+    let prediction_color = textureSample(prediction_texture, canvas_sampler, canvas_uv) * blit_uniforms.prediction_opacity;
+    let reference_rect = blit_uniforms.reference_rect;
+    let reference_uv = (canvas_pos - reference_rect.xy) / max(reference_rect.zw, vec2<f32>(1e-6));
+    let reference_color = textureSample(reference_texture, reference_sampler, reference_uv);
+
+    // Premultiplied stroke layer over the canvas with the stroke's blend mode
+    var canvas_color = blend_stroke(
+        base_color,
+        stroke_color,
+        blit_uniforms.stroke_blend_mode,
+        blit_uniforms.blend_mode == 1u
+    );
+    canvas_color = apply_ceiling(canvas_color, base_color.a, blit_uniforms.stroke_ceiling);
+    // Premultiplied prediction over everything drawn so far
+    canvas_color = prediction_color + canvas_color * (1.0 - prediction_color.a);
+
+    // Surface background in the canvas's color space (the uniform is sRGB)
+    var background = blit_uniforms.surface_background.rgb;
+    if (blit_uniforms.blend_mode == 0u) {
+        background = vec3<f32>(
+            srgb_to_linear(background.r),
+            srgb_to_linear(background.g),
+            srgb_to_linear(background.b)
+        );
+    }
+
+    // Outside the canvas: show the surface background
+    // (the bleed margin is never shown)
+    let inside = all(canvas_pos >= vec2<f32>(0.0)) && all(canvas_pos <= blit_uniforms.canvas_size);
+
+    // Inside: the paper (over the surface background, by its alpha)
+    if (inside && blit_uniforms.paper_color.a > 0.0) {
+        var paper = blit_uniforms.paper_color.rgb;
+        if (blit_uniforms.blend_mode == 0u) {
+            paper = vec3<f32>(
+                srgb_to_linear(paper.r),
+                srgb_to_linear(paper.g),
+                srgb_to_linear(paper.b)
+            );
+        }
+        background = mix(background, paper, blit_uniforms.paper_color.a);
+    }
+
+    if (!inside) {
+        canvas_color = vec4<f32>(background, 1.0);
+    } else if (blit_uniforms.gradient_kind != 0u) {
+        // Inside: the gradient (over the paper and surface background, by its alpha) shows through
+        var fill = gradient_color(canvas_pos);
+        if (blit_uniforms.blend_mode == 0u) {
+            fill = vec4<f32>(
+                srgb_to_linear(fill.r),
+                srgb_to_linear(fill.g),
+                srgb_to_linear(fill.b),
+                fill.a
+            );
+        }
+        background = mix(background, fill.rgb, fill.a);
+    }
+
+    // Reference image over the background, letterboxed inside the canvas
+    let in_reference = all(reference_uv >= vec2<f32>(0.0)) && all(reference_uv <= vec2<f32>(1.0));
+    if (inside && in_reference && blit_uniforms.reference_opacity > 0.0) {
+        var reference_rgb = reference_color.rgb;
+        if (blit_uniforms.blend_mode == 1u) {
+            reference_rgb = vec3<f32>(
+                linear_to_srgb(reference_rgb.r),
+                linear_to_srgb(reference_rgb.g),
+                linear_to_srgb(reference_rgb.b)
+            );
+        }
+        background = mix(background, reference_rgb, reference_color.a * blit_uniforms.reference_opacity);
+    }
+
+    // HDR glow from additive dabs bleeds into neighbouring pixels
+    if (blit_uniforms.bloom_intensity > 0.0) {
+        let glow = bloom(canvas_uv, 1.0 / texture_size) * blit_uniforms.bloom_intensity;
+        canvas_color = vec4<f32>(canvas_color.rgb + glow, canvas_color.a);
+    }
+
+    // Transparent canvas areas show the surface background through
+    canvas_color = vec4<f32>(canvas_color.rgb + background * (1.0 - canvas_color.a), 1.0);
     
     // Check blend mode
+    var output = canvas_color;
     if (blit_uniforms.blend_mode == 1u) {
         // sRGB mode: Canvas stores sRGB-encoded values in Rgba16Float
         // Need to convert sRGB → linear so surface's linear → sRGB is a no-op
         // Using correct sRGB piecewise function
-        return vec4<f32>(
+        output = vec4<f32>(
             srgb_to_linear(canvas_color.r),
             srgb_to_linear(canvas_color.g),
             srgb_to_linear(canvas_color.b),
             canvas_color.a
         );
-    } else {
-        // Linear mode: Canvas already has linear values, pass through
-        // Surface will auto-convert linear → sRGB
-        return canvas_color;
     }
+    // Linear mode: Canvas already has linear values, pass through
+    // Surface will auto-convert linear → sRGB
+
+    if (blit_uniforms.cursor_visible != 0u) {
+        output = vec4<f32>(draw_cursor_ring(output.rgb, view_pos), output.a);
+    }
+    if (blit_uniforms.tether_visible != 0u) {
+        output = vec4<f32>(draw_stabilizer_tether(output.rgb, view_pos), output.a);
+    }
+
+    if (blit_uniforms.dither_amplitude > 0.0) {
+        output = vec4<f32>(dither_linear(output.rgb, input.position.xy), output.a);
+    }
+    return output;
 }
@@ -1,31 +1,70 @@
 // Brush Dab Shader
-// Renders circular brush stamps with variable size, opacity, and hardness
+// Renders circular or regular-polygon brush stamps with variable size, opacity, and hardness
+// Each dab is a unit quad mapped onto the canvas by a per-dab 2x2 transform (rotation + scale),
+// so all shapes, stamps, and masks are evaluated in the dab's local frame
+// Dabs at flat/square stroke caps are trimmed against up to two half-planes
+// Optionally multiplies the shape mask by a canvas-anchored grain texture (dual-mask)
+// Coverage is ordered-dithered on 8-bit canvases when dithering is enabled
+// Edges are antialiased by a one-pixel ramp inside the tip, or analytically from screen-space
+// derivatives (coverage centered on the edge); 4x MSAA shades uv per sample (see renderer.rs)
+// The brush_mask function is a template slot: a custom WGSL mask replaces it (see brush_shader.rs)
 
 struct VertexInput {
     @builtin(vertex_index) vertex_index: u32,
     @location(0) dab_position: vec2<f32>,  // Center position of dab in pixels
-    @location(1) dab_size: f32,            // Diameter in pixels
-    @location(2) dab_opacity: f32,         // Opacity (0.0-1.0)
+    @location(1) dab_opacity: f32,         // Opacity (0.0-1.0)
+    @location(2) dab_hardness: f32,        // Edge hardness (0.0-1.0)
     @location(3) dab_color: vec4<f32>,     // RGBA color
-    @location(4) dab_hardness: f32,        // Edge hardness (0.0-1.0)
+    @location(4) dab_transform: vec4<f32>, // Local → canvas pixels, 2x2 columns (x_axis.xy, y_axis.xy)
+    @location(5) dab_sides: f32,           // Polygon side count, 0 = round
+    @location(6) dab_square_cap: f32,      // 1 = square cap piece aligned with the local axes
+    @location(7) dab_clip0: vec4<f32>,     // Trim half-plane: normal.xy, offset (local units), enabled
+    @location(8) dab_clip1: vec4<f32>,
 }
 
 struct VertexOutput {
     @builtin(position) position: vec4<f32>,
-    @location(0) uv: vec2<f32>,           // UV coordinates relative to dab center (-1 to 1)
+    @location(0) uv: vec2<f32>,           // Coordinates in the dab's local frame (-1 to 1)
     @location(1) color: vec4<f32>,
     @location(2) opacity: f32,
     @location(3) hardness: f32,
+    @location(4) @interpolate(flat) sides: f32,
+    @location(5) @interpolate(flat) square_cap: f32,
+    @location(6) @interpolate(flat) clip0: vec4<f32>,
+    @location(7) @interpolate(flat) clip1: vec4<f32>,
 }
 
 struct Uniforms {
     canvas_size: vec2<f32>,  // Canvas dimensions in pixels
-    _padding: vec2<f32>,
+    dither_amplitude: f32,   // Ordered dither added to coverage (one 8-bit step), 0 = off
+    aa_mode: u32,            // Edge antialiasing: 0 = ramp inside the edge, 1 = analytic coverage
+}
+
+struct GrainUniforms {
+    grain_size: vec2<f32>,  // Grain texture size in pixels
+    grain_scale: f32,       // Grain scale in canvas space
+    grain_enabled: u32,     // 0 = shape only, 1 = shape x grain
 }
 
 @group(0) @binding(0)
 var<uniform> uniforms: Uniforms;
 
+@group(1) @binding(0)
+var grain_texture: texture_2d<f32>;
+
+@group(1) @binding(1)
+var grain_sampler: sampler;
+
+@group(1) @binding(2)
+var<uniform> grain: GrainUniforms;
+
+// Stamp (tip shape) mask, 1x1 white when no stamp is set
+@group(1) @binding(3)
+var stamp_texture: texture_2d<f32>;
+
+@group(1) @binding(4)
+var stamp_sampler: sampler;
+
 // Vertex shader: Generate a quad for each brush dab instance
 @vertex
 fn vs_main(input: VertexInput) -> VertexOutput {
@@ -45,41 +84,133 @@ fn vs_main(input: VertexInput) -> VertexOutput {
         default: { quad_pos = vec2<f32>(1.0, 1.0); }    // Top-right
     }
     
-    // Scale quad by dab size (radius = size / 2)
-    let radius = input.dab_size * 0.5;
-    let world_pos = input.dab_position + quad_pos * radius;
+    // Analytic coverage ramps across the tip edge, so grow the quad by a pixel to fit the
+    // outer half of the ramp
+    let radius_px = min(length(input.dab_transform.xy), length(input.dab_transform.zw));
+    let grow = select(1.0, 1.0 + 1.0 / max(radius_px, 1e-3), uniforms.aa_mode == 1u);
+    let local_pos = quad_pos * grow;
+
+    // Map the unit quad onto the canvas (rotation and radius live in the transform)
+    let transform = mat2x2<f32>(input.dab_transform.xy, input.dab_transform.zw);
+    let world_pos = input.dab_position + transform * local_pos;
     
     // Convert to NDC (normalized device coordinates)
+    // The quad is built in canvas pixels, and canvas_size is the canvas texture size,
+    // so dab shapes are independent of the surface/display aspect.
     // Canvas space: (0,0) top-left, (width,height) bottom-right
     // NDC space: (-1,-1) bottom-left, (1,1) top-right
     let ndc_x = (world_pos.x / uniforms.canvas_size.x) * 2.0 - 1.0;
     let ndc_y = 1.0 - (world_pos.y / uniforms.canvas_size.y) * 2.0;
     
     output.position = vec4<f32>(ndc_x, ndc_y, 0.0, 1.0);
-    output.uv = quad_pos;
+    output.uv = local_pos;
     output.color = input.dab_color;
     output.opacity = input.dab_opacity;
     output.hardness = input.dab_hardness;
+    output.sides = input.dab_sides;
+    output.square_cap = input.dab_square_cap;
+    output.clip0 = input.dab_clip0;
+    output.clip1 = input.dab_clip1;
     
     return output;
 }
 
-// Fragment shader: Draw circular brush stamp with soft/hard edges
+// Normalized distance to the edge of a regular n-gon inscribed in the unit circle
+// 1.0 on the edge, < 1.0 inside. Edge normals sit at 90° + k * 360°/n, so the
+// polygon has a flat edge at the bottom (+y points down in canvas space).
+fn polygon_distance(p: vec2<f32>, sides: f32) -> f32 {
+    let sector = 6.2831853 / sides;
+    let a = atan2(p.y, p.x) - 1.5707963;
+    // Angle from the nearest edge normal
+    let local = a - sector * round(a / sector);
+    return length(p) * cos(local) / cos(0.5 * sector);
+}
+
+// 4x4 Bayer ordered dither threshold (0-1) for a pixel, matching color::ordered_dither_threshold
+fn bayer4(pixel: vec2<f32>) -> f32 {
+    let matrix = array<f32, 16>(0.0, 8.0, 2.0, 10.0, 12.0, 4.0, 14.0, 6.0, 3.0, 11.0, 1.0, 9.0, 15.0, 7.0, 13.0, 5.0);
+    let p = vec2<u32>(pixel) % vec2<u32>(4u);
+    return (matrix[p.y * 4u + p.x] + 0.5) / 16.0;
+}
+
+// BEGIN BRUSH MASK
+// Custom tip mask: coverage (0-1) at a point in the dab's local frame (-1 to 1 on each axis)
+fn brush_mask(uv: vec2<f32>) -> f32 {
+    return 1.0;
+}
+// END BRUSH MASK
+
+// Coverage of a trim half-plane (normal points at the trimmed side), antialiased over `aa`
+fn clip_coverage(uv: vec2<f32>, clip: vec4<f32>, aa: f32) -> f32 {
+    if clip.w < 0.5 {
+        return 1.0;
+    }
+    return clamp((clip.z - dot(uv, clip.xy)) / aa + 0.5, 0.0, 1.0);
+}
+
+// Fragment shader: Draw round or polygon brush stamp with soft/hard edges
 @fragment
 fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
-    // Calculate distance from center of dab (UV space is -1 to 1)
-    let dist = length(input.uv);
-    
-    // Discard pixels outside the circle
-    if dist > 1.0 {
+    // Already in the dab's local frame: the vertex transform rotated and scaled the quad
+    let local_uv = input.uv;
+
+    // Distance from center of dab, normalized so the tip edge is at 1.0 (UV space is -1 to 1)
+    var dist = length(local_uv);
+    if input.square_cap > 0.5 {
+        dist = max(abs(local_uv.x), abs(local_uv.y));
+    } else if input.sides >= 3.0 {
+        dist = polygon_distance(local_uv, input.sides);
+    }
+
+    // Width of one pixel in UV space, for antialiasing hard edges
+    // (computed before discard so derivatives stay in uniform control flow)
+    let aa = fwidth(dist);
+    // One pixel measured along the distance gradient (exact on diagonals, unlike fwidth)
+    let edge_pixel = max(length(vec2<f32>(dpdx(dist), dpdy(dist))), 1e-4);
+    let analytic = uniforms.aa_mode == 1u;
+    // (gradient length rather than fwidth, so it doesn't grow with the dab's rotation)
+    let pixel_uv = max(length(vec2<f32>(dpdx(local_uv.x), dpdy(local_uv.x))), 1e-4);
+
+    // Flat/square caps: complementary ramps on each side of a cap line sum to full coverage
+    let cap_coverage = clip_coverage(local_uv, input.clip0, pixel_uv) * clip_coverage(local_uv, input.clip1, pixel_uv);
+
+    // Discard pixels outside the tip (analytic coverage reaches half a pixel past the edge)
+    let outer_edge = select(1.0, 1.0 + 0.5 * edge_pixel, analytic);
+    if dist > outer_edge || cap_coverage <= 0.0 {
         discard;
     }
     
     // Apply hardness to create soft or hard edges
     // hardness = 0.0: very soft (linear falloff)
-    // hardness = 1.0: very hard (sharp edge)
-    let falloff = smoothstep(input.hardness, 1.0, dist);
-    let alpha = (1.0 - falloff) * input.opacity;
+    // hardness = 1.0: very hard (sharp edge, antialiased over one pixel)
+    var coverage = 1.0 - smoothstep(min(input.hardness, 1.0 - aa), 1.0, dist);
+    if analytic {
+        // Pixel coverage of the tip edge, half inside and half outside it; a falloff
+        // narrower than a pixel is left to the edge coverage alone
+        let edge_coverage = clamp((1.0 - dist) / edge_pixel + 0.5, 0.0, 1.0);
+        let soft = select(1.0, 1.0 - smoothstep(input.hardness, 1.0, dist), input.hardness < 1.0 - edge_pixel);
+        coverage = edge_coverage * soft;
+    }
+    var alpha = coverage * input.opacity * cap_coverage;
+
+    // Stamp mask stretched over the dab's local frame
+    let stamp_uv = local_uv * 0.5 + 0.5;
+    alpha *= textureSampleLevel(stamp_texture, stamp_sampler, stamp_uv, 0.0).r;
+
+    // Custom procedural mask (1.0 everywhere unless one is set)
+    alpha *= clamp(brush_mask(local_uv), 0.0, 1.0);
+
+    // Dual-mask: multiply shape by grain sampled in canvas space
+    // (textureSampleLevel avoids the uniform control flow requirement after discard)
+    if grain.grain_enabled == 1u {
+        let grain_uv = input.position.xy / (grain.grain_size * grain.grain_scale);
+        alpha *= textureSampleLevel(grain_texture, grain_sampler, grain_uv, 0.0).r;
+    }
+
+    // 8-bit canvas: jitter coverage by up to one step so soft ramps don't band
+    if uniforms.dither_amplitude > 0.0 && alpha > 0.0 {
+        alpha = clamp(alpha + (bayer4(input.position.xy) - 0.5) * uniforms.dither_amplitude, 0.0, 1.0);
+    }
     
     // Return premultiplied alpha for correct blending
     // Premultiply: RGB = RGB * A
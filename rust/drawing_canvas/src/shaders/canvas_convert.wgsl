@@ -0,0 +1,79 @@
+// Canvas Color Space Conversion Shader
+// Rewrites the canvas from one blend color space to the other, so existing art keeps
+// its displayed colors when the blend space changes
+//
+// The canvas holds premultiplied color, so each pixel is unpremultiplied, converted,
+// and premultiplied again. Pixels are read directly (no sampling) into a canvas-sized
+// target of the new format, with blending off.
+
+@group(0) @binding(0)
+var source_texture: texture_2d<f32>;
+
+// Vertex shader: Generate full-screen quad
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    // Generate full-screen quad vertices (6 vertices = 2 triangles)
+    let vertex_id = vertex_index % 6u;
+    var pos: vec2<f32>;
+
+    switch vertex_id {
+        case 0u: {
+            pos = vec2<f32>(-1.0, -1.0);  // Bottom-left
+        }
+        case 1u, 4u: {
+            pos = vec2<f32>(1.0, -1.0);   // Bottom-right
+        }
+        case 2u, 3u: {
+            pos = vec2<f32>(-1.0, 1.0);   // Top-left
+        }
+        default: {
+            pos = vec2<f32>(1.0, 1.0);    // Top-right
+        }
+    }
+
+    return vec4<f32>(pos, 0.0, 1.0);
+}
+
+// sRGB → linear conversion per component (same function the blit displays sRGB canvases with)
+fn srgb_to_linear(c: f32) -> f32 {
+    if (c <= 0.04045) {
+        return c / 12.92;
+    } else {
+        return pow((c + 0.055) / 1.055, 2.4);
+    }
+}
+
+// linear → sRGB conversion per component (inverse of srgb_to_linear)
+fn linear_to_srgb(c: f32) -> f32 {
+    if (c <= 0.0031308) {
+        return max(c, 0.0) * 12.92;
+    } else {
+        return 1.055 * pow(c, 1.0 / 2.4) - 0.055;
+    }
+}
+
+// Unpremultiply (transparent pixels have no color to convert)
+fn straight_color(premultiplied: vec4<f32>) -> vec3<f32> {
+    if (premultiplied.a <= 0.0) {
+        return vec3<f32>(0.0);
+    }
+    return premultiplied.rgb / premultiplied.a;
+}
+
+// sRGB canvas → linear canvas
+@fragment
+fn fs_to_linear(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let source = textureLoad(source_texture, vec2<i32>(position.xy), 0);
+    let color = straight_color(source);
+    let linear = vec3<f32>(srgb_to_linear(color.r), srgb_to_linear(color.g), srgb_to_linear(color.b));
+    return vec4<f32>(linear * source.a, source.a);
+}
+
+// Linear canvas → sRGB canvas
+@fragment
+fn fs_to_srgb(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let source = textureLoad(source_texture, vec2<i32>(position.xy), 0);
+    let color = straight_color(source);
+    let srgb = vec3<f32>(linear_to_srgb(color.r), linear_to_srgb(color.g), linear_to_srgb(color.b));
+    return vec4<f32>(srgb * source.a, source.a);
+}
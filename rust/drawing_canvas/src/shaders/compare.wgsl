@@ -0,0 +1,88 @@
+// Comparison Shader
+// Composites the canvas against a reference image for before/after feedback
+//
+// Both inputs are sampled in the canvas's blend color space:
+//   - Canvas: Rgba16Float, premultiplied alpha over a transparent background
+//   - Reference: uploaded so that sampling yields the same encoding as the canvas
+//     (raw sRGB values in sRGB mode, linear values in linear mode)
+//
+// Modes:
+//   0 = Difference: |drawing on white paper - reference|, mistakes light up
+//   1 = Overlay: drawing composited over the reference
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+struct CompareUniforms {
+    mode: u32,  // 0 = Difference, 1 = Overlay
+    _padding0: u32,
+    _padding1: u32,
+    _padding2: u32,
+    canvas_uv_rect: vec4<f32>,  // Visible canvas region in canvas UVs (offset.xy, scale.zw), excludes bleed
+}
+
+@group(0) @binding(0)
+var canvas_texture: texture_2d<f32>;
+
+@group(0) @binding(1)
+var reference_texture: texture_2d<f32>;
+
+@group(0) @binding(2)
+var compare_sampler: sampler;
+
+@group(0) @binding(3)
+var<uniform> compare_uniforms: CompareUniforms;
+
+// Vertex shader: Generate full-screen quad
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    var output: VertexOutput;
+
+    // Generate full-screen quad vertices (6 vertices = 2 triangles)
+    let vertex_id = vertex_index % 6u;
+    var pos: vec2<f32>;
+    var uv: vec2<f32>;
+
+    switch vertex_id {
+        case 0u: {
+            pos = vec2<f32>(-1.0, -1.0);  // Bottom-left
+            uv = vec2<f32>(0.0, 1.0);
+        }
+        case 1u, 4u: {
+            pos = vec2<f32>(1.0, -1.0);   // Bottom-right
+            uv = vec2<f32>(1.0, 1.0);
+        }
+        case 2u, 3u: {
+            pos = vec2<f32>(-1.0, 1.0);   // Top-left
+            uv = vec2<f32>(0.0, 0.0);
+        }
+        default: {
+            pos = vec2<f32>(1.0, 1.0);    // Top-right
+            uv = vec2<f32>(1.0, 0.0);
+        }
+    }
+
+    output.position = vec4<f32>(pos, 0.0, 1.0);
+    output.uv = uv;
+
+    return output;
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    let canvas_uv = compare_uniforms.canvas_uv_rect.xy + input.uv * compare_uniforms.canvas_uv_rect.zw;
+    let canvas_color = textureSample(canvas_texture, compare_sampler, canvas_uv);
+    let reference_color = textureSample(reference_texture, compare_sampler, input.uv);
+
+    if (compare_uniforms.mode == 0u) {
+        // Difference: flatten the drawing onto white paper, then compare per channel
+        let drawing = canvas_color.rgb + vec3<f32>(1.0 - canvas_color.a);
+        return vec4<f32>(abs(drawing - reference_color.rgb), 1.0);
+    } else {
+        // Overlay: premultiplied canvas "over" the reference
+        let composite = canvas_color.rgb + reference_color.rgb * (1.0 - canvas_color.a);
+        return vec4<f32>(composite, 1.0);
+    }
+}
@@ -0,0 +1,37 @@
+// Bucket Fill Shader
+// Draws a filled region as one-pixel-high quads, one per horizontal span (see fill.rs)
+// Spans cover whole pixels, so the fill has hard, exact edges and needs no antialiasing
+
+struct Uniforms {
+    color: vec4<f32>,        // Premultiplied fill color in the blend color space
+    canvas_size: vec2<f32>,  // Canvas texture dimensions in pixels
+    _padding: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> uniforms: Uniforms;
+
+// Vertex shader: Generate a quad covering each span instance
+// span = (first column, column past the end, row) in canvas texture pixels
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32, @location(0) span: vec3<f32>) -> @builtin(position) vec4<f32> {
+    let vertex_id = vertex_index % 6u;
+    var corner: vec2<f32>;
+
+    switch vertex_id {
+        case 0u: { corner = vec2<f32>(0.0, 1.0); }  // Bottom-left
+        case 1u, 4u: { corner = vec2<f32>(1.0, 1.0); }  // Bottom-right
+        case 2u, 3u: { corner = vec2<f32>(0.0, 0.0); }  // Top-left
+        default: { corner = vec2<f32>(1.0, 0.0); }  // Top-right
+    }
+
+    let pixel = vec2<f32>(mix(span.x, span.y, corner.x), span.z + corner.y);
+    let ndc_x = (pixel.x / uniforms.canvas_size.x) * 2.0 - 1.0;
+    let ndc_y = 1.0 - (pixel.y / uniforms.canvas_size.y) * 2.0;
+    return vec4<f32>(ndc_x, ndc_y, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return uniforms.color;
+}
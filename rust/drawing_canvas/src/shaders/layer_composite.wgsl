@@ -0,0 +1,135 @@
+// Layer Composite Shader
+// Composites one paint layer over the layers beneath it for display
+//
+// Layers have the same size and color space as the canvas and hold premultiplied
+// color, so each pixel is read directly (no sampling). The active layer also shows
+// its in-progress stroke layer on top, like the blit does on the single-layer path.
+// The result is scaled by the layer opacity and blended "over" the layers already
+// composited by the pipeline (One, OneMinusSrcAlpha).
+//
+// Also used to commit a stroke layer with a non-Normal blend mode or under an opacity
+// ceiling: the active layer and its stroke layer are blended into a cleared scratch
+// texture at opacity 1.
+
+struct LayerCompositeUniforms {
+    opacity: f32,
+    stroke_opacity: f32,  // 0 unless this is the active layer with a stroke in progress
+    stroke_blend_mode: u32,  // 0 = Normal, 1 = Multiply, 2 = Screen, 3 = Darken, 4 = Lighten
+    blend_mode: u32,  // Color space the layers are stored in: 0 = Linear, 1 = sRGB
+    stroke_ceiling: f32,  // Opacity the stroke layer may build the layer up to (1 = none)
+    _padding0: u32,
+    _padding1: u32,
+    _padding2: u32,
+}
+
+@group(0) @binding(0)
+var layer_texture: texture_2d<f32>;
+
+@group(0) @binding(1)
+var stroke_texture: texture_2d<f32>;
+
+@group(0) @binding(2)
+var<uniform> composite_uniforms: LayerCompositeUniforms;
+
+// Vertex shader: Generate full-screen quad
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    // Generate full-screen quad vertices (6 vertices = 2 triangles)
+    let vertex_id = vertex_index % 6u;
+    var pos: vec2<f32>;
+
+    switch vertex_id {
+        case 0u: {
+            pos = vec2<f32>(-1.0, -1.0);  // Bottom-left
+        }
+        case 1u, 4u: {
+            pos = vec2<f32>(1.0, -1.0);   // Bottom-right
+        }
+        case 2u, 3u: {
+            pos = vec2<f32>(-1.0, 1.0);   // Top-left
+        }
+        default: {
+            pos = vec2<f32>(1.0, 1.0);    // Top-right
+        }
+    }
+
+    return vec4<f32>(pos, 0.0, 1.0);
+}
+
+// sRGB ↔ linear conversion per channel (correct piecewise functions)
+fn srgb_to_linear3(c: vec3<f32>) -> vec3<f32> {
+    return select(pow((c + 0.055) / 1.055, vec3<f32>(2.4)), c / 12.92, c <= vec3<f32>(0.04045));
+}
+
+fn linear_to_srgb3(c: vec3<f32>) -> vec3<f32> {
+    return select(1.055 * pow(c, vec3<f32>(1.0 / 2.4)) - 0.055, max(c, vec3<f32>(0.0)) * 12.92, c <= vec3<f32>(0.0031308));
+}
+
+// Hold premultiplied `color` under the opacity ceiling: its alpha may not rise past
+// `ceiling`, nor fall below the backdrop's, so paint already more opaque is kept as is.
+// Keep in sync with the copy in blit.wgsl.
+fn apply_ceiling(color: vec4<f32>, backdrop_alpha: f32, ceiling: f32) -> vec4<f32> {
+    let limit = max(backdrop_alpha, ceiling);
+    if (color.a <= limit) {
+        return color;
+    }
+    return color * (limit / color.a);
+}
+
+// Composite premultiplied `source` (the stroke layer) over premultiplied `backdrop`
+// Normal is plain "over" in the stored color space. The other modes use the W3C
+// separable blend formula on linear color, decoding first if the colors are sRGB-encoded.
+// Keep in sync with the copy in blit.wgsl.
+fn blend_stroke(backdrop: vec4<f32>, source: vec4<f32>, mode: u32, srgb_encoded: bool) -> vec4<f32> {
+    if (mode == 0u) {
+        return source + backdrop * (1.0 - source.a);
+    }
+    let alpha = source.a + backdrop.a * (1.0 - source.a);
+    if (alpha <= 0.0) {
+        return vec4<f32>(0.0);
+    }
+
+    var cb = backdrop.rgb / max(backdrop.a, 1e-6);
+    var cs = source.rgb / max(source.a, 1e-6);
+    if (srgb_encoded) {
+        cb = srgb_to_linear3(cb);
+        cs = srgb_to_linear3(cs);
+    }
+    var mixed: vec3<f32>;
+    switch mode {
+        case 1u: {
+            mixed = cb * cs;  // Multiply
+        }
+        case 2u: {
+            mixed = cb + cs - cb * cs;  // Screen
+        }
+        case 3u: {
+            mixed = min(cb, cs);  // Darken
+        }
+        default: {
+            mixed = max(cb, cs);  // Lighten
+        }
+    }
+    // Where only one of the two has coverage, its own color shows through
+    var color = (cs * source.a * (1.0 - backdrop.a)
+        + cb * backdrop.a * (1.0 - source.a)
+        + mixed * source.a * backdrop.a) / alpha;
+    if (srgb_encoded) {
+        color = linear_to_srgb3(color);
+    }
+    return vec4<f32>(color * alpha, alpha);
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(position.xy);
+    let layer_color = textureLoad(layer_texture, pixel, 0);
+    let stroke_color = textureLoad(stroke_texture, pixel, 0) * composite_uniforms.stroke_opacity;
+    let color = blend_stroke(
+        layer_color,
+        stroke_color,
+        composite_uniforms.stroke_blend_mode,
+        composite_uniforms.blend_mode == 1u
+    );
+    return apply_ceiling(color, layer_color.a, composite_uniforms.stroke_ceiling) * composite_uniforms.opacity;
+}
@@ -0,0 +1,39 @@
+// Multisample Load Shader
+// Copies the canvas (or stroke layer) into every sample of the 4x multisampled brush
+// target, so MSAA dabs blend onto the existing paint before being resolved back
+//
+// Pixels are read directly (no sampling) and written with blending off. Untouched pixels
+// resolve to exactly their old value, since all their samples are equal.
+
+@group(0) @binding(0)
+var source_texture: texture_2d<f32>;
+
+// Vertex shader: Generate full-screen quad
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    // Generate full-screen quad vertices (6 vertices = 2 triangles)
+    let vertex_id = vertex_index % 6u;
+    var pos: vec2<f32>;
+
+    switch vertex_id {
+        case 0u: {
+            pos = vec2<f32>(-1.0, -1.0);  // Bottom-left
+        }
+        case 1u, 4u: {
+            pos = vec2<f32>(1.0, -1.0);   // Bottom-right
+        }
+        case 2u, 3u: {
+            pos = vec2<f32>(-1.0, 1.0);   // Top-left
+        }
+        default: {
+            pos = vec2<f32>(1.0, 1.0);    // Top-right
+        }
+    }
+
+    return vec4<f32>(pos, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    return textureLoad(source_texture, vec2<i32>(position.xy), 0);
+}
@@ -0,0 +1,104 @@
+// Smudge Shader
+// Drags existing paint along a stroke: each dab carries a color and mixes the canvas under it
+// toward that color, weighted by the dab's coverage. The carried color is the one the previous
+// dab carried (read from the carry texture one smudge offset back) mixed with the canvas under
+// the dab by the smudge length; the first dab of a stroke picks up the canvas under it.
+//
+// The canvas and carry texture can't be sampled while they are being drawn into, so each dab
+// reads copies of the region it touches (see encode_smudge_dabs in renderer.rs) and writes both
+// with blending off. All reads are premultiplied values in the blend color space, so the mix
+// happens in that space, like paint dabs blend.
+// Dabs are round with the brush hardness falloff; tip shapes and masks don't apply.
+
+struct VertexInput {
+    @builtin(vertex_index) vertex_index: u32,
+    @location(0) dab_position: vec2<f32>,   // Center position of dab in pixels
+    @location(1) dab_opacity: f32,          // Opacity (0.0-1.0), scales how much is carried
+    @location(2) dab_hardness: f32,         // Edge hardness (0.0-1.0)
+    @location(4) dab_transform: vec4<f32>,  // Local → canvas pixels, 2x2 columns (x_axis.xy, y_axis.xy)
+    @location(9) dab_smudge_offset: vec2<f32>,  // Pixels from the previous dab to this one
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,  // Coordinates in the dab's local frame (-1 to 1)
+    @location(1) opacity: f32,
+    @location(2) hardness: f32,
+    @location(3) @interpolate(flat) smudge_offset: vec2<f32>,
+}
+
+struct Uniforms {
+    canvas_size: vec2<f32>,  // Canvas texture dimensions in pixels
+    strength: f32,           // Fraction of the carried color laid down at full coverage
+    length: f32,             // Fraction of the carried color kept from the previous dab
+}
+
+@group(0) @binding(0)
+var<uniform> uniforms: Uniforms;
+
+// Copy of the canvas around the current dab and where it pulls from
+@group(0) @binding(1)
+var source_texture: texture_2d<f32>;
+
+@group(0) @binding(2)
+var source_sampler: sampler;
+
+// Copy of the carry texture over the same region
+@group(0) @binding(3)
+var carry_texture: texture_2d<f32>;
+
+struct FragmentOutput {
+    @location(0) canvas: vec4<f32>,
+    @location(1) carry: vec4<f32>,
+}
+
+// Vertex shader: Generate a quad for each dab instance
+@vertex
+fn vs_main(input: VertexInput) -> VertexOutput {
+    var output: VertexOutput;
+
+    let vertex_id = input.vertex_index % 6u;
+    var quad_pos: vec2<f32>;
+
+    switch vertex_id {
+        case 0u: { quad_pos = vec2<f32>(-1.0, -1.0); }  // Bottom-left
+        case 1u, 4u: { quad_pos = vec2<f32>(1.0, -1.0); }  // Bottom-right
+        case 2u, 3u: { quad_pos = vec2<f32>(-1.0, 1.0); }  // Top-left
+        default: { quad_pos = vec2<f32>(1.0, 1.0); }  // Top-right
+    }
+
+    let transform = mat2x2<f32>(input.dab_transform.xy, input.dab_transform.zw);
+    let world_pos = input.dab_position + transform * quad_pos;
+    let ndc_x = (world_pos.x / uniforms.canvas_size.x) * 2.0 - 1.0;
+    let ndc_y = 1.0 - (world_pos.y / uniforms.canvas_size.y) * 2.0;
+
+    output.position = vec4<f32>(ndc_x, ndc_y, 0.0, 1.0);
+    output.uv = quad_pos;
+    output.opacity = input.dab_opacity;
+    output.hardness = input.dab_hardness;
+    output.smudge_offset = input.dab_smudge_offset;
+    return output;
+}
+
+// The whole quad is drawn (coverage is zero outside the circle) so the carry texture holds
+// the carried color wherever the next dab may look for it
+@fragment
+fn fs_main(input: VertexOutput) -> FragmentOutput {
+    let dist = length(input.uv);
+    let aa = fwidth(dist);
+    let coverage = 1.0 - smoothstep(min(input.hardness, 1.0 - aa), 1.0, dist);
+    let amount = clamp(coverage * input.opacity * uniforms.strength, 0.0, 1.0);
+
+    // Texel centers land exactly on texels, so the current color is read unfiltered; the
+    // carried color is filtered, since the offset is rarely a whole number of pixels
+    let current = textureSampleLevel(source_texture, source_sampler, input.position.xy / uniforms.canvas_size, 0.0);
+    let previous_position = input.position.xy - input.smudge_offset;
+    let previous = textureSampleLevel(carry_texture, source_sampler, previous_position / uniforms.canvas_size, 0.0);
+    let starts_stroke = all(input.smudge_offset == vec2<f32>(0.0));
+    let carried = select(mix(current, previous, uniforms.length), current, starts_stroke);
+
+    var output: FragmentOutput;
+    output.canvas = mix(current, carried, amount);
+    output.carry = carried;
+    return output;
+}
@@ -0,0 +1,50 @@
+// Stroke Composite Shader
+// Composites the per-stroke scratch layer into the canvas at the stroke opacity
+//
+// The scratch layer has the same size and color space as the canvas and holds
+// premultiplied coverage, so each pixel is read directly (no sampling) and
+// blended "over" the canvas by the pipeline (One, OneMinusSrcAlpha).
+
+struct StrokeCompositeUniforms {
+    opacity: f32,
+    _padding0: f32,
+    _padding1: f32,
+    _padding2: f32,
+}
+
+@group(0) @binding(0)
+var stroke_texture: texture_2d<f32>;
+
+@group(0) @binding(1)
+var<uniform> composite_uniforms: StrokeCompositeUniforms;
+
+// Vertex shader: Generate full-screen quad
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    // Generate full-screen quad vertices (6 vertices = 2 triangles)
+    let vertex_id = vertex_index % 6u;
+    var pos: vec2<f32>;
+
+    switch vertex_id {
+        case 0u: {
+            pos = vec2<f32>(-1.0, -1.0);  // Bottom-left
+        }
+        case 1u, 4u: {
+            pos = vec2<f32>(1.0, -1.0);   // Bottom-right
+        }
+        case 2u, 3u: {
+            pos = vec2<f32>(-1.0, 1.0);   // Top-left
+        }
+        default: {
+            pos = vec2<f32>(1.0, 1.0);    // Top-right
+        }
+    }
+
+    return vec4<f32>(pos, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let stroke_color = textureLoad(stroke_texture, vec2<i32>(position.xy), 0);
+    return stroke_color * composite_uniforms.opacity;
+}
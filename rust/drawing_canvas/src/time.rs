@@ -0,0 +1,24 @@
+//! Platform Timebase
+//!
+//! Provides a monotonic millisecond clock that works on both native and web.
+//! - Web: `performance.now()` (milliseconds since page load)
+//! - Native: `std::time::Instant` relative to the first call
+
+/// Get the current time in milliseconds from the platform's monotonic clock
+#[cfg(target_arch = "wasm32")]
+pub fn now_ms() -> f64 {
+    web_sys::window()
+        .and_then(|w| w.performance())
+        .map(|p| p.now())
+        .unwrap_or(0.0)
+}
+
+/// Get the current time in milliseconds from the platform's monotonic clock
+#[cfg(not(target_arch = "wasm32"))]
+pub fn now_ms() -> f64 {
+    use std::sync::OnceLock;
+    use std::time::Instant;
+
+    static START: OnceLock<Instant> = OnceLock::new();
+    START.get_or_init(Instant::now).elapsed().as_secs_f64() * 1000.0
+}
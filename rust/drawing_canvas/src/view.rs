@@ -0,0 +1,360 @@
+//! View Transform
+//!
+//! This module maps between canvas space (pixels in the canvas texture) and
+//! surface space (pixels in the window/swapchain). The canvas is rotated and
+//! scaled about its center, placed at the center of the surface, then panned,
+//! so the identity transform reproduces the original 1:1 mapping when both are
+//! the same size.
+//!
+//! Canvas pixels are always square. If the page displays surface pixels
+//! non-square (a non-uniform container scale), the pixel aspect compensates so
+//! round dabs still look round on screen.
+
+/// Default distance (in degrees) from a snap angle within which rotation snaps
+pub const DEFAULT_ROTATION_SNAP_THRESHOLD_DEGREES: f32 = 4.0;
+
+/// View transform applied when displaying the canvas on the surface
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ViewTransform {
+    /// Canvas rotation in radians (clockwise on screen, since y points down)
+    pub rotation: f32,
+    /// Displayed height / width of one surface pixel (1.0 = square)
+    pub pixel_aspect: f32,
+    /// Zoom: surface pixels per canvas pixel (1.0 = 1:1)
+    pub scale: f32,
+    /// Pan: offset of the canvas center from the surface center, in surface pixels
+    pub translation: [f32; 2],
+}
+
+impl ViewTransform {
+    /// The identity transform (canvas pixels map 1:1 onto surface pixels)
+    pub const IDENTITY: Self = Self { rotation: 0.0, pixel_aspect: 1.0, scale: 1.0, translation: [0.0, 0.0] };
+
+    /// Smallest zoom (the canvas shown at a tenth of its size)
+    pub const MIN_SCALE: f32 = 0.1;
+    /// Largest zoom (one canvas pixel spans 32 surface pixels)
+    pub const MAX_SCALE: f32 = 32.0;
+
+    /// Clamp a zoom to `MIN_SCALE..=MAX_SCALE` (non-finite zooms become 1.0)
+    pub fn clamp_scale(scale: f32) -> f32 {
+        if scale.is_finite() {
+            scale.clamp(Self::MIN_SCALE, Self::MAX_SCALE)
+        } else {
+            1.0
+        }
+    }
+
+    /// Largest canvas → surface scale: surface pixels spanned by one canvas pixel along
+    /// its most stretched axis (1.0 for a 1:1 view)
+    pub fn max_scale(&self) -> f32 {
+        self.scale * (1.0 / self.pixel_aspect).max(1.0)
+    }
+
+    /// Map a canvas-space point to surface space
+    pub fn canvas_to_surface(&self, point: [f32; 2], canvas_size: [f32; 2], surface_size: [f32; 2]) -> [f32; 2] {
+        let (sin, cos) = self.rotation.sin_cos();
+        let x = point[0] - canvas_size[0] * 0.5;
+        let y = point[1] - canvas_size[1] * 0.5;
+        // Rotate and zoom in square display units, then squash y into surface pixels
+        [
+            (cos * x - sin * y) * self.scale + surface_size[0] * 0.5 + self.translation[0],
+            (sin * x + cos * y) * self.scale / self.pixel_aspect + surface_size[1] * 0.5 + self.translation[1],
+        ]
+    }
+
+    /// Map a surface-space point (e.g. pointer input) back to canvas space
+    pub fn surface_to_canvas(&self, point: [f32; 2], canvas_size: [f32; 2], surface_size: [f32; 2]) -> [f32; 2] {
+        let m = self.inverse_matrix(canvas_size, surface_size);
+        [
+            m[0][0] * point[0] + m[0][1] * point[1] + m[0][2],
+            m[1][0] * point[0] + m[1][1] * point[1] + m[1][2],
+        ]
+    }
+
+    /// Get the surface-to-canvas affine matrix as two rows of (a, b, translation)
+    /// Used by the blit shader to find the canvas pixel under each surface pixel
+    pub fn inverse_matrix(&self, canvas_size: [f32; 2], surface_size: [f32; 2]) -> [[f32; 3]; 2] {
+        // Inverse rotation is the transpose, applied after removing the pan, stretching
+        // surface y back into square display units, and undoing the zoom
+        let (sin, cos) = self.rotation.sin_cos();
+        let aspect = self.pixel_aspect;
+        let inv_scale = 1.0 / self.scale;
+        let sx = surface_size[0] * 0.5 + self.translation[0];
+        let sy = surface_size[1] * 0.5 + self.translation[1];
+        let cx = canvas_size[0] * 0.5;
+        let cy = canvas_size[1] * 0.5;
+        [
+            [cos * inv_scale, sin * aspect * inv_scale, -(cos * sx + sin * aspect * sy) * inv_scale + cx],
+            [-sin * inv_scale, cos * aspect * inv_scale, -(-sin * sx + cos * aspect * sy) * inv_scale + cy],
+        ]
+    }
+
+    /// Follow a two-finger pinch from one pair of surface points to the next
+    ///
+    /// The zoom changes by the ratio of the finger spacings (measured in square display
+    /// units) and the canvas point under the fingers' midpoint moves with the midpoint, so
+    /// the content stays under the fingers. The rotation is kept.
+    ///
+    /// # Arguments
+    /// * `from` - Both touch positions before the move, in surface pixels
+    /// * `to` - The same touches after the move, in the same order
+    /// * `surface_size` - Surface size in pixels
+    pub fn pinched(&self, from: [[f32; 2]; 2], to: [[f32; 2]; 2], surface_size: [f32; 2]) -> Self {
+        // Fingers this close together give no usable spacing ratio, so only pan
+        const MIN_SPACING: f32 = 1.0;
+
+        let spacing = |points: [[f32; 2]; 2]| {
+            let dx = points[1][0] - points[0][0];
+            let dy = (points[1][1] - points[0][1]) * self.pixel_aspect;
+            (dx * dx + dy * dy).sqrt()
+        };
+        let midpoint = |points: [[f32; 2]; 2]| [(points[0][0] + points[1][0]) * 0.5, (points[0][1] + points[1][1]) * 0.5];
+
+        let (from_spacing, to_spacing) = (spacing(from), spacing(to));
+        let scale = if from_spacing >= MIN_SPACING && to_spacing >= MIN_SPACING {
+            Self::clamp_scale(self.scale * to_spacing / from_spacing)
+        } else {
+            self.scale
+        };
+        let ratio = scale / self.scale;
+
+        // Scale the canvas center's offset from the old midpoint, then hang it off the new one
+        let (from_mid, to_mid) = (midpoint(from), midpoint(to));
+        let center = [surface_size[0] * 0.5 + self.translation[0], surface_size[1] * 0.5 + self.translation[1]];
+        Self {
+            scale,
+            translation: [
+                to_mid[0] + (center[0] - from_mid[0]) * ratio - surface_size[0] * 0.5,
+                to_mid[1] + (center[1] - from_mid[1]) * ratio - surface_size[1] * 0.5,
+            ],
+            ..*self
+        }
+    }
+}
+
+impl Default for ViewTransform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+/// Fit content of one size inside a frame, keeping its aspect ratio (letterboxing)
+///
+/// # Returns
+/// `[x, y, width, height]` of the largest centered rectangle with the content's aspect
+/// ratio that fits in the frame, in frame units
+pub fn fit_rect(content_size: [f32; 2], frame_size: [f32; 2]) -> [f32; 4] {
+    let scale = (frame_size[0] / content_size[0]).min(frame_size[1] / content_size[1]);
+    let width = content_size[0] * scale;
+    let height = content_size[1] * scale;
+    [(frame_size[0] - width) * 0.5, (frame_size[1] - height) * 0.5, width, height]
+}
+
+/// 2D affine transform applied to raw input positions before the view transform
+///
+/// Uses the CSS `matrix(a, b, c, d, e, f)` convention:
+/// `x' = a * x + c * y + e`, `y' = b * x + d * y + f`.
+/// Embedders use this to undo container transforms (e.g. CSS scale/rotate)
+/// that the windowing layer can't see.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InputTransform {
+    pub a: f32,
+    pub b: f32,
+    pub c: f32,
+    pub d: f32,
+    pub e: f32,
+    pub f: f32,
+}
+
+impl InputTransform {
+    /// The identity transform (positions pass through unchanged)
+    pub const IDENTITY: Self = Self { a: 1.0, b: 0.0, c: 0.0, d: 1.0, e: 0.0, f: 0.0 };
+
+    /// Smallest determinant magnitude accepted as invertible
+    const MIN_DETERMINANT: f32 = 1e-6;
+
+    /// Create an input transform, rejecting non-finite or non-invertible matrices
+    pub fn new(a: f32, b: f32, c: f32, d: f32, e: f32, f: f32) -> Result<Self, String> {
+        if ![a, b, c, d, e, f].iter().all(|v| v.is_finite()) {
+            return Err("Input transform values must be finite".to_string());
+        }
+        let transform = Self { a, b, c, d, e, f };
+        let det = transform.determinant();
+        if det.abs() < Self::MIN_DETERMINANT {
+            return Err(format!("Input transform is not invertible (determinant {})", det));
+        }
+        Ok(transform)
+    }
+
+    /// Determinant of the linear part
+    pub fn determinant(&self) -> f32 {
+        self.a * self.d - self.b * self.c
+    }
+
+    /// Apply the transform to a point
+    pub fn apply(&self, point: [f32; 2]) -> [f32; 2] {
+        [
+            self.a * point[0] + self.c * point[1] + self.e,
+            self.b * point[0] + self.d * point[1] + self.f,
+        ]
+    }
+}
+
+impl Default for InputTransform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+/// Snap an angle to the nearest multiple of `increment` if within `threshold`
+///
+/// # Arguments
+/// * `angle_degrees` - Requested angle in degrees
+/// * `increment` - Snap increment in degrees (e.g. 15.0); <= 0.0 disables snapping
+/// * `threshold` - Dead zone in degrees around each snap point (clamped to half the increment)
+///
+/// # Returns
+/// The snapped angle in degrees, or the original angle if not near a snap point
+pub fn snap_rotation_degrees(angle_degrees: f32, increment: f32, threshold: f32) -> f32 {
+    if increment <= 0.0 {
+        return angle_degrees;
+    }
+    let threshold = threshold.clamp(0.0, increment * 0.5);
+    let nearest = (angle_degrees / increment).round() * increment;
+    if (angle_degrees - nearest).abs() <= threshold {
+        nearest
+    } else {
+        angle_degrees
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: [f32; 2], b: [f32; 2]) {
+        assert!((a[0] - b[0]).abs() < 1e-3 && (a[1] - b[1]).abs() < 1e-3, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn test_identity_is_one_to_one() {
+        let view = ViewTransform::IDENTITY;
+        let size = [800.0, 600.0];
+        assert_close(view.canvas_to_surface([10.0, 20.0], size, size), [10.0, 20.0]);
+        assert_close(view.surface_to_canvas([10.0, 20.0], size, size), [10.0, 20.0]);
+    }
+
+    #[test]
+    fn test_rotation_round_trip() {
+        let view = ViewTransform { rotation: 0.7, ..ViewTransform::IDENTITY };
+        let canvas = [800.0, 600.0];
+        let surface = [1024.0, 768.0];
+        let p = [123.0, 456.0];
+        let s = view.canvas_to_surface(p, canvas, surface);
+        assert_close(view.surface_to_canvas(s, canvas, surface), p);
+    }
+
+    #[test]
+    fn test_pixel_aspect_keeps_dabs_round() {
+        // Surface pixels displayed twice as tall as wide: a canvas circle must cover
+        // half as many surface pixels vertically as horizontally
+        let view = ViewTransform { rotation: 0.3, pixel_aspect: 2.0, ..ViewTransform::IDENTITY };
+        let size = [800.0, 600.0];
+        let center = view.canvas_to_surface([400.0, 300.0], size, size);
+        let right = view.canvas_to_surface([410.0, 300.0], size, size);
+        let down = view.canvas_to_surface([400.0, 310.0], size, size);
+        let display_len = |p: [f32; 2]| {
+            ((p[0] - center[0]).powi(2) + ((p[1] - center[1]) * 2.0).powi(2)).sqrt()
+        };
+        assert!((display_len(right) - 10.0).abs() < 1e-3);
+        assert!((display_len(down) - 10.0).abs() < 1e-3);
+        assert_close(view.surface_to_canvas(right, size, size), [410.0, 300.0]);
+    }
+
+    #[test]
+    fn test_pan_zoom_round_trip() {
+        let view = ViewTransform { rotation: 0.4, scale: 3.0, translation: [-120.0, 45.0], ..ViewTransform::IDENTITY };
+        let canvas = [800.0, 600.0];
+        let surface = [1024.0, 768.0];
+        let p = [250.0, 80.0];
+        let s = view.canvas_to_surface(p, canvas, surface);
+        assert_close(view.surface_to_canvas(s, canvas, surface), p);
+
+        // The canvas center lands on the panned surface center, and canvas distances grow by the zoom
+        let zoomed = ViewTransform { scale: 4.0, translation: [10.0, -20.0], ..ViewTransform::IDENTITY };
+        assert_close(zoomed.canvas_to_surface([400.0, 300.0], canvas, surface), [522.0, 364.0]);
+        assert_close(zoomed.canvas_to_surface([401.0, 300.0], canvas, surface), [526.0, 364.0]);
+        assert_eq!(zoomed.max_scale(), 4.0);
+    }
+
+    #[test]
+    fn test_pinch_keeps_content_under_fingers() {
+        let view = ViewTransform { rotation: 0.3, scale: 2.0, translation: [15.0, -40.0], ..ViewTransform::IDENTITY };
+        let canvas = [800.0, 600.0];
+        let surface = [1024.0, 768.0];
+        let from = [[400.0, 300.0], [500.0, 300.0]];
+        let to = [[350.0, 320.0], [550.0, 320.0]];
+        let grabbed = view.surface_to_canvas([450.0, 300.0], canvas, surface);
+
+        // Spreading the fingers to twice the spacing doubles the zoom about the midpoint
+        let pinched = view.pinched(from, to, surface);
+        assert!((pinched.scale - 4.0).abs() < 1e-4);
+        assert_eq!(pinched.rotation, view.rotation);
+        assert_close(pinched.canvas_to_surface(grabbed, canvas, surface), [450.0, 320.0]);
+
+        // Moving both fingers together only pans
+        let panned = view.pinched(from, [[410.0, 290.0], [510.0, 290.0]], surface);
+        assert_eq!(panned.scale, view.scale);
+        assert_close(panned.translation, [25.0, -50.0]);
+
+        // Past the zoom limit the content under the midpoint still stays put
+        let clamped = view.pinched(from, [[-1000.0, 300.0], [2000.0, 300.0]], surface);
+        assert_eq!(clamped.scale, ViewTransform::MAX_SCALE);
+        assert_close(clamped.canvas_to_surface(grabbed, canvas, surface), [500.0, 300.0]);
+    }
+
+    #[test]
+    fn test_clamp_scale() {
+        assert_eq!(ViewTransform::clamp_scale(2.5), 2.5);
+        assert_eq!(ViewTransform::clamp_scale(0.0), ViewTransform::MIN_SCALE);
+        assert_eq!(ViewTransform::clamp_scale(1000.0), ViewTransform::MAX_SCALE);
+        assert_eq!(ViewTransform::clamp_scale(f32::NAN), 1.0);
+    }
+
+    #[test]
+    fn test_fit_rect_letterboxes() {
+        // Wide content in a square frame: bars above and below
+        assert_eq!(fit_rect([400.0, 200.0], [100.0, 100.0]), [0.0, 25.0, 100.0, 50.0]);
+        // Tall content in a wide frame: bars left and right
+        assert_eq!(fit_rect([100.0, 300.0], [800.0, 600.0]), [300.0, 0.0, 200.0, 600.0]);
+        // Same aspect fills the frame
+        assert_eq!(fit_rect([40.0, 30.0], [800.0, 600.0]), [0.0, 0.0, 800.0, 600.0]);
+    }
+
+    #[test]
+    fn test_input_transform_apply() {
+        assert_close(InputTransform::IDENTITY.apply([3.0, 4.0]), [3.0, 4.0]);
+        // Undo a 2x container scale plus offset
+        let t = InputTransform::new(0.5, 0.0, 0.0, 0.5, -10.0, 5.0).unwrap();
+        assert_close(t.apply([100.0, 40.0]), [40.0, 25.0]);
+    }
+
+    #[test]
+    fn test_input_transform_rejects_non_invertible() {
+        assert!(InputTransform::new(1.0, 2.0, 2.0, 4.0, 0.0, 0.0).is_err());
+        assert!(InputTransform::new(0.0, 0.0, 0.0, 0.0, 0.0, 0.0).is_err());
+        assert!(InputTransform::new(f32::NAN, 0.0, 0.0, 1.0, 0.0, 0.0).is_err());
+    }
+
+    #[test]
+    fn test_snap_rotation() {
+        // Within the dead zone snaps to the nearest increment
+        assert_eq!(snap_rotation_degrees(14.0, 15.0, 4.0), 15.0);
+        assert_eq!(snap_rotation_degrees(-46.5, 15.0, 4.0), -45.0);
+        // Outside the dead zone is left alone
+        assert_eq!(snap_rotation_degrees(22.0, 15.0, 4.0), 22.0);
+        // Zero increment disables snapping
+        assert_eq!(snap_rotation_degrees(14.0, 0.0, 4.0), 14.0);
+    }
+}
@@ -5,19 +5,116 @@
 
 use crate::{App, Renderer};
 use crate::debug;
-use crate::input::{PointerEvent, PointerEventSource, PointerEventType};
+#[cfg(target_arch = "wasm32")]
+use crate::error::{report_error, ErrorCode};
+use crate::input::{PointerEvent, PointerEventSource, PointerEventType, TimestampNormalizer};
+use crate::view::InputTransform;
 use winit::application::ApplicationHandler;
-use winit::event::{WindowEvent, ElementState, Force};
+use winit::event::{WindowEvent, ElementState, FingerId, Force};
 use winit::event_loop::ActiveEventLoop;
 use winit::window::{Window, WindowAttributes, WindowId};
 
 #[cfg(target_arch = "wasm32")]
-use std::cell::RefCell;
+use std::cell::{Cell, OnceCell, RefCell, RefMut};
+#[cfg(target_arch = "wasm32")]
+use std::rc::Rc;
 use std::sync::{Mutex, OnceLock};
 
 #[cfg(target_arch = "wasm32")]
 thread_local! {
-    static GLOBAL_APP_WRAPPER: RefCell<Option<*mut AppWrapper>> = RefCell::new(None);
+    // The app wrapper, shared by the event loop and the JS entry points (set once)
+    static GLOBAL_APP_WRAPPER: OnceCell<Rc<RefCell<AppWrapper>>> = const { OnceCell::new() };
+    // Bumped whenever the global wrapper is set or its app/renderer are (re)created
+    static GLOBAL_APP_GENERATION: Cell<u64> = const { Cell::new(0) };
+    // Sub-frame pointer samples captured from PointerEvent.getCoalescedEvents()
+    static COALESCED_SAMPLES: RefCell<Vec<CoalescedSample>> = RefCell::new(Vec::new());
+    // Whether pointer moves are expanded into their coalesced sub-frame samples
+    static COALESCED_EVENTS_ENABLED: Cell<bool> = const { Cell::new(true) };
+    // Pointer whose coalesced samples are buffered: the one held down, else the primary one
+    static COALESCED_POINTER_ID: Cell<Option<i32>> = const { Cell::new(None) };
+    // Canvas CSS-to-physical scale for coalesced samples (measured lazily, reset on resize/scroll)
+    static COALESCED_SCALE: Cell<Option<[f64; 2]>> = const { Cell::new(None) };
+    // Pending setTimeout for the session's next frame
+    static SESSION_TIMER: Cell<Option<i32>> = const { Cell::new(None) };
+    // Timeout callback requesting the session's next frame (created once, reused)
+    static SESSION_TICK_CALLBACK: js_sys::Function = {
+        use wasm_bindgen::JsCast;
+        wasm_bindgen::closure::Closure::<dyn Fn()>::new(|| {
+            SESSION_TIMER.with(|timer| timer.set(None));
+            GLOBAL_APP_WRAPPER.with(|global| {
+                if let Some(wrapper) = borrow_global_wrapper(global) {
+                    wrapper.request_redraw();
+                }
+            });
+        })
+        .into_js_value()
+        .unchecked_into()
+    };
+}
+
+/// DOM listeners that feed `COALESCED_SAMPLES`, removed from the page when dropped (WASM only)
+///
+/// Held by the `AppWrapper`, so the callbacks live exactly as long as the canvas they
+/// listen to instead of being leaked for the lifetime of the page.
+#[cfg(target_arch = "wasm32")]
+struct CoalescedListeners {
+    // Listeners added in the capture phase: target, event name, and callback
+    registered: Vec<(web_sys::EventTarget, &'static str, js_sys::Function)>,
+    _pointer_callbacks: [wasm_bindgen::closure::Closure<dyn Fn(web_sys::PointerEvent)>; 3],
+    _scroll_callback: wasm_bindgen::closure::Closure<dyn Fn()>,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Drop for CoalescedListeners {
+    fn drop(&mut self) {
+        for (target, name, callback) in &self.registered {
+            if let Err(e) = target.remove_event_listener_with_callback_and_bool(name, callback, true) {
+                log::warn!("Failed to remove {} listener: {:?}", name, e);
+            }
+        }
+    }
+}
+
+/// A single sub-frame pointer sample from `PointerEvent.getCoalescedEvents()` (WASM only)
+#[cfg(target_arch = "wasm32")]
+#[derive(Debug, Clone, Copy)]
+struct CoalescedSample {
+    /// Position in physical surface pixels (or canvas pixels once transformed)
+    position: [f32; 2],
+    /// Pressure reported by the browser (0.0-1.0)
+    pressure: f32,
+    /// DOM event timestamp in milliseconds
+    timestamp: f64,
+}
+
+/// Quiet period (ms) a container must hold its size before a resize is applied
+/// Resizing recreates the surface and canvas textures, so while a drag-resize fires the
+/// observer every frame, only the first and the final size are applied.
+#[cfg(target_arch = "wasm32")]
+const RESIZE_DEBOUNCE_MS: i32 = 100;
+
+/// Upper bound on buffered coalesced samples if winit stops consuming them
+#[cfg(target_arch = "wasm32")]
+const MAX_COALESCED_SAMPLES: usize = 256;
+
+/// Surface size the window is created with when no initial size was set from JS
+const DEFAULT_INITIAL_CANVAS_SIZE: [u32; 2] = [800, 600];
+
+// Initial canvas size in CSS pixels, set from JS before init and read when the window is created
+static INITIAL_CANVAS_SIZE: Mutex<Option<[f64; 2]>> = Mutex::new(None);
+
+/// Physical surface size to create the window with
+fn initial_canvas_size() -> winit::dpi::PhysicalSize<u32> {
+    #[cfg(target_arch = "wasm32")]
+    let scale_factor = web_sys::window().map(|w| w.device_pixel_ratio()).unwrap_or(1.0);
+    #[cfg(not(target_arch = "wasm32"))]
+    let scale_factor = 1.0;
+
+    let [width, height] = match *INITIAL_CANVAS_SIZE.lock().unwrap() {
+        Some(logical) => crate::dpi::logical_to_physical_size(logical, scale_factor),
+        None => DEFAULT_INITIAL_CANVAS_SIZE,
+    };
+    winit::dpi::PhysicalSize::new(width.max(1), height.max(1))
 }
 
 // Global brush parameters that persist across app reinitialization
@@ -48,12 +145,182 @@ where
                params.size, params.flow, params.hardness);
 }
 
-/// Set the global app wrapper reference (WASM only)
+/// Share the app wrapper with the JS entry points (WASM only)
+///
+/// # Returns
+/// The handler to run the event loop with; it borrows the shared wrapper for each event
 #[cfg(target_arch = "wasm32")]
-pub fn set_global_app_wrapper(wrapper: &mut AppWrapper) {
+pub fn set_global_app_wrapper(wrapper: AppWrapper) -> SharedAppWrapper {
+    let wrapper = Rc::new(RefCell::new(wrapper));
     GLOBAL_APP_WRAPPER.with(|global| {
-        *global.borrow_mut() = Some(wrapper as *mut AppWrapper);
+        if global.set(wrapper.clone()).is_err() {
+            log::error!("Global app wrapper already set; JS calls still reach the first one");
+        }
     });
+    bump_global_app_generation();
+    SharedAppWrapper { wrapper, deferred: Vec::new() }
+}
+
+/// Borrow the global app wrapper for the duration of one JS call (WASM only)
+///
+/// The event loop and the JS entry points share the wrapper through an `Rc<RefCell<_>>`,
+/// so a call can neither outlive it nor alias it. Returns None if the wrapper isn't set,
+/// or if it is already borrowed: a JS entry point called back from inside another one, or
+/// from inside the event handler. Nothing should re-enter the wrapper, so debug builds
+/// assert; release builds refuse the call with a warning.
+#[cfg(target_arch = "wasm32")]
+fn borrow_global_wrapper(global: &OnceCell<Rc<RefCell<AppWrapper>>>) -> Option<RefMut<'_, AppWrapper>> {
+    let borrowed = global.get()?.try_borrow_mut();
+    debug_assert!(borrowed.is_ok(), "Re-entrant access to the global app wrapper");
+    match borrowed {
+        Ok(wrapper) => Some(wrapper),
+        Err(_) => {
+            log::warn!("Ignoring re-entrant access to the global app wrapper");
+            None
+        }
+    }
+}
+
+/// Current generation of the global app wrapper (WASM only)
+/// Work that awaits can compare it before and after to detect that the app or renderer
+/// it started with has been replaced in the meantime.
+#[cfg(target_arch = "wasm32")]
+fn global_app_generation() -> u64 {
+    GLOBAL_APP_GENERATION.with(Cell::get)
+}
+
+/// Mark the global app wrapper's contents as replaced, returning the new generation (WASM only)
+#[cfg(target_arch = "wasm32")]
+fn bump_global_app_generation() -> u64 {
+    GLOBAL_APP_GENERATION.with(|generation| {
+        generation.set(generation.get().wrapping_add(1));
+        generation.get()
+    })
+}
+
+/// The renderer, moved out of the global wrapper while a JS call awaits the GPU (WASM only)
+///
+/// Keeping the wrapper borrowed across an await would lock the event loop out, and a
+/// reference kept after the borrow ends could dangle if the canvas is recreated. The
+/// renderer is taken out instead, so frames are skipped until the loan is dropped and
+/// JS setters that need the renderer are queued meanwhile. Dropping it puts the renderer
+/// back (catching up on any resize missed meanwhile), unless the canvas was recreated
+/// during the loan, then runs the queued setters.
+#[cfg(target_arch = "wasm32")]
+struct RendererLoan {
+    renderer: Option<Renderer>,
+    generation: u64,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl RendererLoan {
+    /// Take the renderer, or None if it isn't initialized (or is already on loan)
+    fn take() -> Option<Self> {
+        let generation = global_app_generation();
+        let renderer = GLOBAL_APP_WRAPPER.with(|global| {
+            let mut wrapper = borrow_global_wrapper(global)?;
+            let renderer = wrapper.renderer.take()?;
+            wrapper.renderer_on_loan = true;
+            Some(renderer)
+        })?;
+        Some(Self { renderer: Some(renderer), generation })
+    }
+
+    /// Check whether the canvas was recreated since the renderer was taken
+    fn is_stale(&self) -> bool {
+        global_app_generation() != self.generation
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl std::ops::Deref for RendererLoan {
+    type Target = Renderer;
+
+    fn deref(&self) -> &Renderer {
+        self.renderer.as_ref().expect("Renderer is on loan until dropped")
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl std::ops::DerefMut for RendererLoan {
+    fn deref_mut(&mut self) -> &mut Renderer {
+        self.renderer.as_mut().expect("Renderer is on loan until dropped")
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Drop for RendererLoan {
+    fn drop(&mut self) {
+        let Some(mut renderer) = self.renderer.take() else {
+            return;
+        };
+        let stale = self.is_stale();
+        if stale {
+            log::info!("Dropping a renderer replaced while it was on loan");
+        }
+        let deferred = GLOBAL_APP_WRAPPER.with(|global| match borrow_global_wrapper(global) {
+            Some(mut wrapper) => {
+                if !stale {
+                    if let Some(window) = &wrapper.window {
+                        renderer.resize(window.surface_size());
+                    }
+                    wrapper.renderer = Some(renderer);
+                    wrapper.request_redraw();
+                }
+                wrapper.renderer_on_loan = false;
+                std::mem::take(&mut wrapper.deferred_setters)
+            }
+            None => {
+                log::error!("Could not return the renderer to the global app wrapper");
+                Vec::new()
+            }
+        });
+
+        // Apply settings made during the loan now the wrapper is released, since each
+        // setter borrows it again
+        for setter in deferred {
+            setter();
+        }
+    }
+}
+
+/// Event loop handler for the shared global app wrapper (WASM only)
+///
+/// Borrows the wrapper for each event. An event that arrives while a JS entry point holds
+/// the wrapper (e.g. dispatched synchronously by a winit call it made) is deferred to the
+/// next event rather than handled re-entrantly.
+#[cfg(target_arch = "wasm32")]
+pub struct SharedAppWrapper {
+    wrapper: Rc<RefCell<AppWrapper>>,
+    deferred: Vec<(WindowId, WindowEvent)>,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl ApplicationHandler for SharedAppWrapper {
+    fn can_create_surfaces(&mut self, event_loop: &dyn ActiveEventLoop) {
+        match self.wrapper.try_borrow_mut() {
+            Ok(mut wrapper) => wrapper.can_create_surfaces(event_loop),
+            Err(_) => log::error!("App wrapper busy, can't create surfaces"),
+        }
+    }
+
+    fn resumed(&mut self, event_loop: &dyn ActiveEventLoop) {
+        if let Ok(mut wrapper) = self.wrapper.try_borrow_mut() {
+            wrapper.resumed(event_loop);
+        }
+    }
+
+    fn window_event(&mut self, event_loop: &dyn ActiveEventLoop, id: WindowId, event: WindowEvent) {
+        let Ok(mut wrapper) = self.wrapper.try_borrow_mut() else {
+            log::debug!("App wrapper busy, deferring {:?}", event);
+            self.deferred.push((id, event));
+            return;
+        };
+        for (id, event) in self.deferred.drain(..) {
+            wrapper.window_event(event_loop, id, event);
+        }
+        wrapper.window_event(event_loop, id, event);
+    }
 }
 
 /// Set blend color space from JavaScript (WASM only)
@@ -62,27 +329,49 @@ pub fn set_blend_color_space_global(is_srgb: bool) {
     use crate::renderer::BlendColorSpace;
     
     GLOBAL_APP_WRAPPER.with(|global| {
-        if let Some(wrapper_ptr) = *global.borrow() {
-            unsafe {
-                let wrapper = &mut *wrapper_ptr;
-                if let (Some(app), Some(renderer)) = (&mut wrapper.app, &mut wrapper.renderer) {
-                    let color_space = if is_srgb {
-                        BlendColorSpace::Srgb
-                    } else {
-                        BlendColorSpace::Linear
-                    };
-                    
-                    app.set_blend_color_space(color_space, renderer);
-                    
-                    // Request a redraw
-                    if let Some(window) = &wrapper.window {
-                        window.request_redraw();
-                    }
-                    
-                    log::info!("✅ Blend color space changed to: {:?}", color_space);
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let (Some(app), Some(renderer)) = (&mut wrapper.app, &mut wrapper.renderer) {
+                let color_space = if is_srgb {
+                    BlendColorSpace::Srgb
                 } else {
-                    log::warn!("App or renderer not yet initialized");
-                }
+                    BlendColorSpace::Linear
+                };
+                
+                app.set_blend_color_space(color_space, renderer);
+                
+                // Request a redraw
+                wrapper.request_redraw();
+                
+                log::info!("✅ Blend color space changed to: {:?}", color_space);
+            } else if !wrapper.defer_until_renderer_returns(move || set_blend_color_space_global(is_srgb)) {
+                log::warn!("App or renderer not yet initialized");
+            }
+        } else {
+            log::warn!("Global app wrapper not set");
+        }
+    });
+}
+
+/// Set the blend color space from JavaScript, converting the canvas to match (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_blend_color_space_preserve_global(is_srgb: bool) {
+    use crate::renderer::BlendColorSpace;
+
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let (Some(app), Some(renderer)) = (&mut wrapper.app, &mut wrapper.renderer) {
+                let color_space = if is_srgb {
+                    BlendColorSpace::Srgb
+                } else {
+                    BlendColorSpace::Linear
+                };
+
+                app.set_blend_color_space_preserving(color_space, renderer);
+                wrapper.request_redraw();
+            } else if !wrapper.defer_until_renderer_returns(move || set_blend_color_space_preserve_global(is_srgb)) {
+                log::warn!("App or renderer not yet initialized");
             }
         } else {
             log::warn!("Global app wrapper not set");
@@ -102,13 +391,11 @@ pub fn set_brush_size_global(size: f32) {
     
     // Also update current app if it exists
     GLOBAL_APP_WRAPPER.with(|global| {
-        if let Some(wrapper_ptr) = *global.borrow() {
-            unsafe {
-                let wrapper = &mut *wrapper_ptr;
-                if let Some(app) = &mut wrapper.app {
-                    app.brush_state_mut().params.size = size.max(0.1);
-                    log::info!("Updated app brush size to: {}", size);
-                }
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.brush_state_mut().params.size = size.max(0.1);
+                log::info!("Updated app brush size to: {}", size);
             }
         }
     });
@@ -126,13 +413,11 @@ pub fn set_brush_flow_global(flow: f32) {
     
     // Also update current app if it exists
     GLOBAL_APP_WRAPPER.with(|global| {
-        if let Some(wrapper_ptr) = *global.borrow() {
-            unsafe {
-                let wrapper = &mut *wrapper_ptr;
-                if let Some(app) = &mut wrapper.app {
-                    app.brush_state_mut().params.flow = flow.clamp(0.0, 1.0);
-                    log::info!("Updated app brush flow to: {}", flow);
-                }
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.brush_state_mut().params.flow = flow.clamp(0.0, 1.0);
+                log::info!("Updated app brush flow to: {}", flow);
             }
         }
     });
@@ -150,16 +435,51 @@ pub fn set_brush_hardness_global(hardness: f32) {
     
     // Also update current app if it exists
     GLOBAL_APP_WRAPPER.with(|global| {
-        if let Some(wrapper_ptr) = *global.borrow() {
-            unsafe {
-                let wrapper = &mut *wrapper_ptr;
-                if let Some(app) = &mut wrapper.app {
-                    app.brush_state_mut().params.hardness = hardness.clamp(0.0, 1.0);
-                    log::info!("Updated app brush hardness to: {}", hardness);
-                }
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.brush_state_mut().params.hardness = hardness.clamp(0.0, 1.0);
+                log::info!("Updated app brush hardness to: {}", hardness);
+            }
+        }
+    });
+}
+
+/// Set brush spacing and how it is measured from JavaScript (WASM only)
+/// mode: 0 = fraction of the diameter, 1 = absolute canvas pixels
+#[cfg(target_arch = "wasm32")]
+pub fn set_brush_spacing_global(spacing: f32, mode: u32) -> Result<(), String> {
+    use crate::brush::SpacingMode;
+
+    log::info!("set_brush_spacing_global called: spacing={}, mode={}", spacing, mode);
+
+    let spacing_mode = match mode {
+        0 => SpacingMode::DiameterFraction,
+        1 => SpacingMode::AbsolutePixels,
+        _ => return Err(format!("Unknown spacing mode: {}", mode)),
+    };
+    // Value and mode are checked together, since the valid range depends on the mode
+    crate::brush::BrushParams { spacing, spacing_mode, ..crate::brush::BrushParams::DEFAULT }.validate()?;
+
+    // Update global brush params (persists across app reinit)
+    update_global_brush_params(|params| {
+        params.spacing = spacing;
+        params.spacing_mode = spacing_mode;
+    });
+
+    // Also update current app if it exists
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                let params = &mut app.brush_state_mut().params;
+                params.spacing = spacing;
+                params.spacing_mode = spacing_mode;
+                log::info!("Updated app brush spacing to: {} ({:?})", spacing, spacing_mode);
             }
         }
     });
+    Ok(())
 }
 
 /// Set brush color from JavaScript (WASM only)
@@ -179,74 +499,75 @@ pub fn set_brush_color_global(r: f32, g: f32, b: f32, a: f32) {
     
     // Also update current app if it exists
     GLOBAL_APP_WRAPPER.with(|global| {
-        if let Some(wrapper_ptr) = *global.borrow() {
-            unsafe {
-                let wrapper = &mut *wrapper_ptr;
-                if let Some(app) = &mut wrapper.app {
-                    app.brush_state_mut().params.color = [
-                        r.clamp(0.0, 1.0),
-                        g.clamp(0.0, 1.0),
-                        b.clamp(0.0, 1.0),
-                        a.clamp(0.0, 1.0),
-                    ];
-                    log::info!("Updated app brush color to: [{}, {}, {}, {}]", r, g, b, a);
-                }
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.brush_state_mut().params.color = [
+                    r.clamp(0.0, 1.0),
+                    g.clamp(0.0, 1.0),
+                    b.clamp(0.0, 1.0),
+                    a.clamp(0.0, 1.0),
+                ];
+                log::info!("Updated app brush color to: [{}, {}, {}, {}]", r, g, b, a);
             }
         }
     });
 }
 
-/// Set input filter mode from JavaScript (WASM only)
+/// Set max opacity cap from JavaScript (WASM only)
 #[cfg(target_arch = "wasm32")]
-pub fn set_input_filter_mode_global(pen_only: bool) {
-    use crate::brush::InputFilterMode;
-    
-    log::info!("set_input_filter_mode_global called: pen_only={}", pen_only);
-    
-    let mode = if pen_only {
-        InputFilterMode::PenOnly
-    } else {
-        InputFilterMode::PenAndTouch
-    };
+pub fn set_max_opacity_global(max_opacity: f32) {
+    log::info!("set_max_opacity_global called: {}", max_opacity);
     
     // Update global brush params (persists across app reinit)
     update_global_brush_params(|params| {
-        params.input_filter_mode = mode;
+        params.max_opacity = max_opacity.clamp(0.0, 1.0);
     });
     
     // Also update current app if it exists
     GLOBAL_APP_WRAPPER.with(|global| {
-        if let Some(wrapper_ptr) = *global.borrow() {
-            unsafe {
-                let wrapper = &mut *wrapper_ptr;
-                if let Some(app) = &mut wrapper.app {
-                    app.brush_state_mut().params.input_filter_mode = mode;
-                    log::info!("Input filter mode updated to: {:?}", mode);
-                }
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.brush_state_mut().params.max_opacity = max_opacity.clamp(0.0, 1.0);
+                log::info!("Updated app max opacity to: {}", max_opacity);
             }
         }
     });
 }
 
-/// Clear canvas from JavaScript (WASM only)
+/// Set brush grain texture from JavaScript (WASM only)
 #[cfg(target_arch = "wasm32")]
-pub fn clear_canvas_global() {
+pub fn set_brush_grain_global(data: &[u8], width: u32, height: u32, scale: f32) -> Result<(), wasm_bindgen::JsValue> {
+    use wasm_bindgen::JsValue;
+    
+    log::info!("set_brush_grain_global called: {}x{}, scale={}", width, height, scale);
+    
     GLOBAL_APP_WRAPPER.with(|global| {
-        if let Some(wrapper_ptr) = *global.borrow() {
-            unsafe {
-                let wrapper = &mut *wrapper_ptr;
-                if let (Some(app), Some(renderer)) = (&mut wrapper.app, &mut wrapper.renderer) {
-                    app.clear_canvas(renderer);
-                    
-                    // Request a redraw
-                    if let Some(window) = &wrapper.window {
-                        window.request_redraw();
-                    }
-                    
-                    log::info!("Canvas cleared");
-                } else {
-                    log::warn!("App or renderer not yet initialized");
-                }
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let (Some(app), Some(renderer)) = (&mut wrapper.app, &mut wrapper.renderer) {
+                app.set_brush_grain(renderer, data, width, height, scale)
+                    .map_err(|e| JsValue::from_str(&e))
+            } else {
+                Err(JsValue::from_str("Renderer not yet initialized"))
+            }
+        } else {
+            Err(JsValue::from_str("Global app wrapper not set"))
+        }
+    })
+}
+
+/// Clear brush grain texture from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn clear_brush_grain_global() {
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let (Some(app), Some(renderer)) = (&mut wrapper.app, &mut wrapper.renderer) {
+                app.clear_brush_grain(renderer);
+            } else if !wrapper.defer_until_renderer_returns(move || clear_brush_grain_global()) {
+                log::warn!("App or renderer not yet initialized");
             }
         } else {
             log::warn!("Global app wrapper not set");
@@ -254,164 +575,2739 @@ pub fn clear_canvas_global() {
     });
 }
 
-/// Get canvas width from JavaScript (WASM only)
+/// Set brush stamp texture from JavaScript (WASM only)
 #[cfg(target_arch = "wasm32")]
-pub fn get_canvas_width_global() -> u32 {
+pub fn set_brush_texture_global(data: &[u8], width: u32, height: u32) -> Result<(), wasm_bindgen::JsValue> {
+    use wasm_bindgen::JsValue;
+
+    log::info!("set_brush_texture_global called: {}x{}", width, height);
+
     GLOBAL_APP_WRAPPER.with(|global| {
-        if let Some(wrapper_ptr) = *global.borrow() {
-            unsafe {
-                let wrapper = &*wrapper_ptr;
-                if let Some(renderer) = &wrapper.renderer {
-                    renderer.size().width
-                } else {
-                    0
-                }
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let (Some(app), Some(renderer)) = (&mut wrapper.app, &mut wrapper.renderer) {
+                app.set_brush_texture(renderer, data, width, height)
+                    .map_err(|e| JsValue::from_str(&e))
+            } else {
+                Err(JsValue::from_str("Renderer not yet initialized"))
             }
         } else {
-            0
+            Err(JsValue::from_str("Global app wrapper not set"))
         }
     })
 }
 
-/// Get canvas height from JavaScript (WASM only)
+/// Set brush stamp texture from an encoded PNG from JavaScript (WASM only)
 #[cfg(target_arch = "wasm32")]
-pub fn get_canvas_height_global() -> u32 {
+pub fn set_brush_texture_png_global(png_data: &[u8]) -> Result<(), wasm_bindgen::JsValue> {
+    use wasm_bindgen::JsValue;
+
+    log::info!("set_brush_texture_png_global called: {} bytes", png_data.len());
+
     GLOBAL_APP_WRAPPER.with(|global| {
-        if let Some(wrapper_ptr) = *global.borrow() {
-            unsafe {
-                let wrapper = &*wrapper_ptr;
-                if let Some(renderer) = &wrapper.renderer {
-                    renderer.size().height
-                } else {
-                    0
-                }
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let (Some(app), Some(renderer)) = (&mut wrapper.app, &mut wrapper.renderer) {
+                app.set_brush_texture_png(renderer, png_data)
+                    .map_err(|e| JsValue::from_str(&e))
+            } else {
+                Err(JsValue::from_str("Renderer not yet initialized"))
             }
         } else {
-            0
+            Err(JsValue::from_str("Global app wrapper not set"))
         }
     })
 }
 
-/// Export canvas as RGBA8 image data from JavaScript (WASM only)
+/// Clear brush stamp texture from JavaScript (WASM only)
 #[cfg(target_arch = "wasm32")]
-pub async fn get_canvas_image_data_global() -> Result<js_sys::Uint8ClampedArray, wasm_bindgen::JsValue> {
+pub fn clear_brush_texture_global() {
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let (Some(app), Some(renderer)) = (&mut wrapper.app, &mut wrapper.renderer) {
+                app.clear_brush_texture(renderer);
+            } else if !wrapper.defer_until_renderer_returns(move || clear_brush_texture_global()) {
+                log::warn!("App or renderer not yet initialized");
+            }
+        } else {
+            log::warn!("Global app wrapper not set");
+        }
+    });
+}
+
+/// Set a custom WGSL brush mask from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_custom_brush_shader_global(wgsl: &str) -> Result<(), wasm_bindgen::JsValue> {
     use wasm_bindgen::JsValue;
-    
-    // Read back GPU texture data - this is async and requires waiting for GPU->CPU transfer
-    let result = GLOBAL_APP_WRAPPER.with(|global| -> Option<*mut Renderer> {
-        if let Some(wrapper_ptr) = *global.borrow() {
-            unsafe {
-                let wrapper = &mut *wrapper_ptr;
-                wrapper.renderer.as_mut().map(|r| r as *mut Renderer)
+
+    log::info!("set_custom_brush_shader_global called: {} bytes", wgsl.len());
+
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(renderer) = &mut wrapper.renderer {
+                renderer.set_custom_brush_shader(wgsl).map_err(|e| JsValue::from_str(&e))
+            } else {
+                Err(JsValue::from_str("Renderer not yet initialized"))
             }
         } else {
-            None
+            Err(JsValue::from_str("Global app wrapper not set"))
+        }
+    })
+}
+
+/// Clear the custom brush mask from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn clear_custom_brush_shader_global() {
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(renderer) = &mut wrapper.renderer {
+                renderer.clear_custom_brush_shader();
+            } else if !wrapper.defer_until_renderer_returns(move || clear_custom_brush_shader_global()) {
+                log::warn!("Renderer not yet initialized");
+            }
+        } else {
+            log::warn!("Global app wrapper not set");
         }
     });
-    
-    match result {
-        Some(renderer_ptr) => {
-            // Call async method outside the closure to avoid borrow issues
-            let renderer = unsafe { &*renderer_ptr };
-            let rgba8_data = renderer.read_canvas_rgba8()
-                .await
-                .map_err(|e| JsValue::from_str(&e))?;
-            
-            // Convert Vec<u8> to Uint8ClampedArray for JavaScript
-            let js_array = js_sys::Uint8ClampedArray::new_with_length(rgba8_data.len() as u32);
-            js_array.copy_from(&rgba8_data);
-            
-            log::info!("Exported canvas image data: {} bytes", rgba8_data.len());
-            Ok(js_array)
+}
+
+/// Set the brush eraser mode from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_eraser_mode_global(enabled: bool) {
+    log::info!("set_eraser_mode_global called: {}", enabled);
+
+    // Update global brush params (persists across app reinit)
+    update_global_brush_params(|params| {
+        params.eraser = enabled;
+    });
+
+    // Also update current app if it exists
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.brush_state_mut().params.eraser = enabled;
+                log::info!("Brush eraser mode {}", if enabled { "on" } else { "off" });
+            }
         }
-        None => Err(JsValue::from_str("Renderer not yet initialized"))
-    }
+    });
 }
 
-/// Check if canvas needs to be relocated to a new container (WASM only)
-/// This is called on every init_drawing_canvas() to handle Flutter rebuilds
+/// Set brush blend mode from JavaScript (WASM only)
 #[cfg(target_arch = "wasm32")]
-pub fn check_and_relocate_canvas_global() {
-    use wasm_bindgen::JsCast;
-    use winit::platform::web::WindowExtWeb;
-    
+pub fn set_brush_blend_mode_global(mode: u32) {
+    use crate::brush::BrushBlendMode;
+
+    log::info!("set_brush_blend_mode_global called: {}", mode);
+
+    let blend_mode = match mode {
+        0 => BrushBlendMode::Normal,
+        1 => BrushBlendMode::AddHdr,
+        2 => BrushBlendMode::Multiply,
+        3 => BrushBlendMode::Screen,
+        4 => BrushBlendMode::Darken,
+        5 => BrushBlendMode::Lighten,
+        _ => {
+            log::warn!("Unknown brush blend mode: {}", mode);
+            return;
+        }
+    };
+
+    // Update global brush params (persists across app reinit)
+    update_global_brush_params(|params| {
+        params.blend_mode = blend_mode;
+    });
+
+    // Also update current app if it exists
     GLOBAL_APP_WRAPPER.with(|global| {
-        if let Some(wrapper_ptr) = *global.borrow() {
-            unsafe {
-                let wrapper = &*wrapper_ptr;
-                
-                // Only proceed if we have a window
-                if let Some(window_arc) = &wrapper.window {
-                    let canvas = match window_arc.canvas() {
-                        Some(c) => c,
-                        None => {
-                            log::warn!("Failed to get canvas from window");
-                            return;
-                        }
-                    };
-                    
-                    let document = web_sys::window()
-                        .and_then(|win| win.document())
-                        .expect("Failed to get document");
-                    
-                    // Find the canvas-container that doesn't have a canvas child yet
-                    let containers = match document.query_selector_all("[data-canvas-container]") {
-                        Ok(c) => c,
-                        Err(e) => {
-                            log::warn!("Failed to query canvas containers: {:?}", e);
-                            return;
-                        }
-                    };
-                    
-                    log::info!("🔍 Checking {} container(s) for canvas relocation", containers.length());
-                    
-                    let mut empty_container: Option<web_sys::Element> = None;
-                    for i in 0..containers.length() {
-                        if let Some(elem) = containers.get(i) {
-                            if let Ok(html_elem) = elem.dyn_into::<web_sys::HtmlElement>() {
-                                let container_id = html_elem.id();
-                                let has_canvas = html_elem.query_selector("canvas").ok().flatten().is_some();
-                                log::info!("  Container '{}': has_canvas={}", container_id, has_canvas);
-                                
-                                // Check if this container already has a canvas child
-                                if !has_canvas {
-                                    empty_container = Some(html_elem.into());
-                                    break;
-                                }
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.brush_state_mut().params.blend_mode = blend_mode;
+                log::info!("Brush blend mode updated to: {:?}", blend_mode);
+            }
+        }
+    });
+}
+
+/// Set bloom intensity from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_bloom_global(intensity: f32) {
+    log::info!("set_bloom_global called: {}", intensity);
+
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(renderer) = &mut wrapper.renderer {
+                renderer.set_bloom_intensity(intensity);
+                wrapper.request_redraw();
+            } else if !wrapper.defer_until_renderer_returns(move || set_bloom_global(intensity)) {
+                log::warn!("Renderer not yet initialized");
+            }
+        }
+    });
+}
+
+/// Set whether 8-bit output is dithered from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_dithering_global(enabled: bool) {
+    log::info!("set_dithering_global called: {}", enabled);
+
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(renderer) = &mut wrapper.renderer {
+                renderer.set_dithering(enabled);
+                wrapper.request_redraw();
+            } else if !wrapper.defer_until_renderer_returns(move || set_dithering_global(enabled)) {
+                log::warn!("Renderer not yet initialized");
+            }
+        }
+    });
+}
+
+/// Set the size the canvas is created at from JavaScript (WASM only)
+/// Only read when the window is created, so it must be called before `init_drawing_canvas`.
+#[cfg(target_arch = "wasm32")]
+pub fn set_initial_canvas_size_global(width: f64, height: f64) {
+    if !(width.is_finite() && height.is_finite() && width >= 1.0 && height >= 1.0) {
+        log::warn!("Invalid initial canvas size: {}x{}", width, height);
+        return;
+    }
+    *INITIAL_CANVAS_SIZE.lock().unwrap() = Some([width, height]);
+    log::info!("Initial canvas size set to {}x{} CSS pixels", width, height);
+}
+
+/// Set whether the canvas is stored as 8-bit sRGB from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_srgb_canvas_global(enabled: bool) {
+    log::info!("set_srgb_canvas_global called: {}", enabled);
+
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let (Some(app), Some(renderer)) = (&mut wrapper.app, &mut wrapper.renderer) {
+                app.set_srgb_canvas(enabled, renderer);
+                wrapper.request_redraw();
+            } else if !wrapper.defer_until_renderer_returns(move || set_srgb_canvas_global(enabled)) {
+                log::warn!("App or renderer not yet initialized");
+            }
+        }
+    });
+}
+
+/// Set the canvas sampler wrap mode from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_wrap_mode_global(mode: u32) {
+    use crate::renderer::WrapMode;
+
+    log::info!("set_wrap_mode_global called: {}", mode);
+
+    let wrap_mode = match mode {
+        0 => WrapMode::Clamp,
+        1 => WrapMode::Repeat,
+        2 => WrapMode::MirrorRepeat,
+        _ => {
+            log::warn!("Unknown wrap mode: {}", mode);
+            return;
+        }
+    };
+
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(renderer) = &mut wrapper.renderer {
+                renderer.set_sampler_address_mode(wrap_mode);
+                wrapper.request_redraw();
+            } else if !wrapper.defer_until_renderer_returns(move || set_wrap_mode_global(mode)) {
+                log::warn!("Renderer not yet initialized");
+            }
+        }
+    });
+}
+
+/// Set the brush antialiasing mode from JavaScript (WASM only)
+/// Returns the mode actually applied (MSAA falls back to analytic where unsupported)
+#[cfg(target_arch = "wasm32")]
+pub fn set_antialiasing_global(mode: u32) -> u32 {
+    use crate::renderer::AaMode;
+
+    log::info!("set_antialiasing_global called: {}", mode);
+
+    let aa_mode = match mode {
+        0 => Some(AaMode::Off),
+        1 => Some(AaMode::Msaa4x),
+        2 => Some(AaMode::Analytic),
+        _ => {
+            log::warn!("Unknown antialiasing mode: {}", mode);
+            None
+        }
+    };
+
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(renderer) = &mut wrapper.renderer {
+                let applied = match aa_mode {
+                    Some(aa_mode) => renderer.set_antialiasing(aa_mode),
+                    None => renderer.antialiasing(),
+                };
+                match applied {
+                    AaMode::Off => 0,
+                    AaMode::Msaa4x => 1,
+                    AaMode::Analytic => 2,
+                }
+            } else {
+                log::warn!("Renderer not yet initialized");
+                mode
+            }
+        } else {
+            mode
+        }
+    })
+}
+
+/// Set brush tip shape from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_tip_shape_global(kind: u32, sides: u32) {
+    use crate::brush::TipShape;
+
+    log::info!("set_tip_shape_global called: kind={}, sides={}", kind, sides);
+
+    let tip_shape = match kind {
+        0 => TipShape::Round,
+        1 => TipShape::Polygon(sides.clamp(TipShape::MIN_SIDES, TipShape::MAX_SIDES)),
+        _ => {
+            log::warn!("Unknown tip shape kind: {}", kind);
+            return;
+        }
+    };
+
+    // Update global brush params (persists across app reinit)
+    update_global_brush_params(|params| {
+        params.tip_shape = tip_shape;
+    });
+
+    // Also update current app if it exists
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.brush_state_mut().params.tip_shape = tip_shape;
+                log::info!("Tip shape updated to: {:?}", tip_shape);
+            }
+        }
+    });
+}
+
+/// Set stroke cap style from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_cap_style_global(kind: u32) {
+    use crate::brush::CapStyle;
+
+    log::info!("set_cap_style_global called: kind={}", kind);
+
+    let cap_style = match kind {
+        0 => CapStyle::Round,
+        1 => CapStyle::Square,
+        2 => CapStyle::Flat,
+        _ => {
+            log::warn!("Unknown cap style: {}", kind);
+            return;
+        }
+    };
+
+    // Update global brush params (persists across app reinit)
+    update_global_brush_params(|params| {
+        params.cap_style = cap_style;
+    });
+
+    // Also update current app if it exists
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.brush_state_mut().params.cap_style = cap_style;
+                log::info!("Cap style updated to: {:?}", cap_style);
+            }
+        }
+    });
+}
+
+/// Set stroke taper lengths from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_taper_global(taper_in: f32, taper_out: f32) {
+    log::info!("set_taper_global called: taper_in={}, taper_out={}", taper_in, taper_out);
+
+    let length = |px: f32| if px.is_finite() { px.max(0.0) } else { 0.0 };
+    let (taper_in, taper_out) = (length(taper_in), length(taper_out));
+
+    // Update global brush params (persists across app reinit)
+    update_global_brush_params(|params| {
+        params.taper_in = taper_in;
+        params.taper_out = taper_out;
+    });
+
+    // Also update current app if it exists
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                let params = &mut app.brush_state_mut().params;
+                params.taper_in = taper_in;
+                params.taper_out = taper_out;
+                log::info!("Taper updated to: in {} px, out {} px", taper_in, taper_out);
+            }
+        }
+    });
+}
+
+/// Set pressure mapping from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_pressure_mapping_global(mode: u32) {
+    use crate::brush::PressureMapping;
+    
+    log::info!("set_pressure_mapping_global called: {}", mode);
+    
+    let mapping = match mode {
+        0 => PressureMapping::None,
+        1 => PressureMapping::Flow,
+        2 => PressureMapping::Size,
+        3 => PressureMapping::Both,
+        4 => PressureMapping::Hardness,
+        5 => PressureMapping::All,
+        _ => {
+            log::warn!("Unknown pressure mapping mode: {}", mode);
+            return;
+        }
+    };
+    
+    // Update global brush params (persists across app reinit)
+    update_global_brush_params(|params| {
+        params.pressure_mapping = mapping;
+    });
+    
+    // Also update current app if it exists
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.brush_state_mut().params.pressure_mapping = mapping;
+                log::info!("Pressure mapping updated to: {:?}", mapping);
+            }
+        }
+    });
+}
+
+/// Set zoom-aware brush spacing from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_zoom_aware_spacing_global(enabled: bool) {
+    log::info!("set_zoom_aware_spacing_global called: {}", enabled);
+
+    // Update global brush params (persists across app reinit)
+    update_global_brush_params(|params| {
+        params.zoom_aware_spacing = enabled;
+    });
+
+    // Also update current app if it exists
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.brush_state_mut().params.zoom_aware_spacing = enabled;
+                log::info!("Zoom-aware spacing: {}", enabled);
+            }
+        }
+    });
+}
+
+/// Set minimum tap dab size from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_min_tap_size_global(px: f32) {
+    log::info!("set_min_tap_size_global called: {}", px);
+
+    let px = px.max(0.0);
+
+    // Update global brush params (persists across app reinit)
+    update_global_brush_params(|params| {
+        params.min_tap_dab_size = px;
+    });
+
+    // Also update current app if it exists
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.brush_state_mut().params.min_tap_dab_size = px;
+                log::info!("Min tap dab size updated to: {}", px);
+            }
+        }
+    });
+}
+
+/// Set pressure-to-hardness curve from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_pressure_hardness_global(min: f32, max: f32, gamma: f32) {
+    log::info!("set_pressure_hardness_global called: min={}, max={}, gamma={}", min, max, gamma);
+    
+    let min = min.clamp(0.0, 1.0);
+    let max = max.max(0.0);
+    let gamma = gamma.max(0.01);
+    
+    // Update global brush params (persists across app reinit)
+    update_global_brush_params(|params| {
+        params.min_hardness_percent = min;
+        params.max_hardness_percent = max;
+        params.hardness_gamma = gamma;
+    });
+    
+    // Also update current app if it exists
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                let params = &mut app.brush_state_mut().params;
+                params.min_hardness_percent = min;
+                params.max_hardness_percent = max;
+                params.hardness_gamma = gamma;
+                log::info!("Updated app pressure hardness curve");
+            }
+        }
+    });
+}
+
+/// Set the control point pressure curve from JavaScript (WASM only)
+/// An empty list goes back to the per-property gamma curves.
+#[cfg(target_arch = "wasm32")]
+pub fn set_pressure_curve_global(points: &[f32]) -> Result<(), String> {
+    use crate::brush::{PressureCurve, PressureResponse};
+
+    log::info!("set_pressure_curve_global called: points={:?}", points);
+
+    let response = if points.is_empty() {
+        PressureResponse::Gamma
+    } else {
+        PressureResponse::Curve(PressureCurve::from_flat(points)?)
+    };
+
+    // Update global brush params (persists across app reinit)
+    update_global_brush_params(|params| {
+        params.pressure_response = response;
+    });
+
+    // Also update current app if it exists
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.brush_state_mut().params.pressure_response = response;
+                log::info!("Updated app pressure response to: {:?}", response);
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Set line assist strength from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_line_assist_global(strength: f32) {
+    log::info!("set_line_assist_global called: strength={}", strength);
+
+    let strength = strength.clamp(0.0, 1.0);
+
+    // Update global brush params (persists across app reinit)
+    update_global_brush_params(|params| {
+        params.line_assist = strength;
+    });
+
+    // Also update current app if it exists
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.brush_state_mut().params.line_assist = strength;
+                log::info!("Line assist updated to: {}", strength);
+            }
+        }
+    });
+}
+
+/// Set position stabilization from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_stabilization_global(strength: f32) {
+    log::info!("set_stabilization_global called: strength={}", strength);
+
+    let strength = strength.clamp(0.0, 1.0);
+
+    // Update global brush params (persists across app reinit)
+    update_global_brush_params(|params| {
+        params.stabilization = strength;
+    });
+
+    // Also update current app if it exists
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.brush_state_mut().params.stabilization = strength;
+                log::info!("Stabilization updated to: {}", strength);
+            }
+        }
+    });
+}
+
+/// Set per-dab random rotation from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_random_rotation_global(enabled: bool) {
+    log::info!("set_random_rotation_global called: enabled={}", enabled);
+
+    // Update global brush params (persists across app reinit)
+    update_global_brush_params(|params| {
+        params.random_rotation = enabled;
+    });
+
+    // Also update current app if it exists
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.brush_state_mut().params.random_rotation = enabled;
+                log::info!("Random rotation updated to: {}", enabled);
+            }
+        }
+    });
+}
+
+/// Set airbrush mode from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_airbrush_global(enabled: bool, rate: f32) {
+    log::info!("set_airbrush_global called: enabled={}, rate={}", enabled, rate);
+
+    let rate = if rate.is_finite() {
+        rate.clamp(1.0, crate::brush::MAX_AIRBRUSH_RATE)
+    } else {
+        crate::brush::BrushParams::DEFAULT.airbrush_rate
+    };
+
+    // Update global brush params (persists across app reinit)
+    update_global_brush_params(|params| {
+        params.airbrush = enabled;
+        params.airbrush_rate = rate;
+    });
+
+    // Also update current app if it exists
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                let params = &mut app.brush_state_mut().params;
+                params.airbrush = enabled;
+                params.airbrush_rate = rate;
+                log::info!("Airbrush updated to: {} ({} dabs/s)", enabled, rate);
+            }
+        }
+    });
+}
+
+/// Set per-dab color jitter from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_color_jitter_global(hue: f32, saturation: f32, value: f32) {
+    log::info!("set_color_jitter_global called: hue={}, saturation={}, value={}", hue, saturation, value);
+
+    let apply = |params: &mut crate::brush::BrushParams| {
+        params.hue_jitter = hue.clamp(0.0, 1.0);
+        params.saturation_jitter = saturation.clamp(0.0, 1.0);
+        params.value_jitter = value.clamp(0.0, 1.0);
+    };
+
+    // Update global brush params (persists across app reinit)
+    update_global_brush_params(apply);
+
+    // Also update current app if it exists
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                apply(&mut app.brush_state_mut().params);
+                log::info!("Color jitter updated to: hue={}, saturation={}, value={}", hue, saturation, value);
+            }
+        }
+    });
+}
+
+/// Set velocity mapping mode from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_velocity_mapping_global(mode: u32) {
+    use crate::brush::VelocityMapping;
+
+    log::info!("set_velocity_mapping_global called: {}", mode);
+
+    let mapping = match mode {
+        0 => VelocityMapping::None,
+        1 => VelocityMapping::Flow,
+        2 => VelocityMapping::Size,
+        3 => VelocityMapping::Both,
+        _ => {
+            log::warn!("Unknown velocity mapping mode: {}", mode);
+            return;
+        }
+    };
+
+    // Update global brush params (persists across app reinit)
+    update_global_brush_params(|params| {
+        params.velocity_mapping = mapping;
+    });
+
+    // Also update current app if it exists
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.brush_state_mut().params.velocity_mapping = mapping;
+                log::info!("Velocity mapping updated to: {:?}", mapping);
+            }
+        }
+    });
+}
+
+/// Set the speed-to-size/flow curve from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_velocity_curve_global(max_speed: f32, min: f32, max: f32, gamma: f32) {
+    log::info!(
+        "set_velocity_curve_global called: max_speed={}, min={}, max={}, gamma={}",
+        max_speed, min, max, gamma
+    );
+
+    let max_speed = max_speed.max(1.0);
+    let min = min.clamp(0.0, 1.0);
+    let max = max.max(0.0);
+    let gamma = gamma.max(0.01);
+
+    // Update global brush params (persists across app reinit)
+    update_global_brush_params(|params| {
+        params.velocity_max_speed = max_speed;
+        params.min_velocity_percent = min;
+        params.max_velocity_percent = max;
+        params.velocity_gamma = gamma;
+    });
+
+    // Also update current app if it exists
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                let params = &mut app.brush_state_mut().params;
+                params.velocity_max_speed = max_speed;
+                params.min_velocity_percent = min;
+                params.max_velocity_percent = max;
+                params.velocity_gamma = gamma;
+                log::info!("Updated app velocity curve");
+            }
+        }
+    });
+}
+
+/// Set velocity smoothing from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_velocity_smoothing_global(factor: f32) {
+    log::info!("set_velocity_smoothing_global called: factor={}", factor);
+
+    let factor = factor.clamp(0.0, 0.99);
+
+    // Update global brush params (persists across app reinit)
+    update_global_brush_params(|params| {
+        params.velocity_smoothing = factor;
+    });
+
+    // Also update current app if it exists
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.brush_state_mut().params.velocity_smoothing = factor;
+                log::info!("Velocity smoothing updated to: {}", factor);
+            }
+        }
+    });
+}
+
+/// Set the stroke angle constraint from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_angle_constraint_global(degrees: f32) {
+    log::info!("set_angle_constraint_global called: degrees={}", degrees);
+
+    let degrees = degrees.clamp(0.0, 180.0);
+
+    // Update global brush params (persists across app reinit)
+    update_global_brush_params(|params| {
+        params.angle_constraint = degrees;
+    });
+
+    // Also update current app if it exists
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.brush_state_mut().params.angle_constraint = degrees;
+                log::info!("Angle constraint updated to: {}", degrees);
+            }
+        }
+    });
+}
+
+/// Set max stroke length from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_max_stroke_length_global(px: f32) {
+    log::info!("set_max_stroke_length_global called: px={}", px);
+
+    let px = if px.is_finite() { px.max(0.0) } else { 0.0 };
+
+    // Update global brush params (persists across app reinit)
+    update_global_brush_params(|params| {
+        params.max_stroke_length = px;
+    });
+
+    // Also update current app if it exists
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.brush_state_mut().params.max_stroke_length = px;
+                log::info!("Max stroke length updated to: {}", px);
+            }
+        }
+    });
+}
+
+/// Set pressure smoothing from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_pressure_smoothing_global(factor: f32) {
+    log::info!("set_pressure_smoothing_global called: factor={}", factor);
+
+    let factor = factor.clamp(0.0, 0.99);
+
+    // Update global brush params (persists across app reinit)
+    update_global_brush_params(|params| {
+        params.pressure_smoothing = factor;
+    });
+
+    // Also update current app if it exists
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.brush_state_mut().params.pressure_smoothing = factor;
+                log::info!("Pressure smoothing updated to: {}", factor);
+            }
+        }
+    });
+}
+
+/// Set angle smoothing from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_angle_smoothing_global(factor: f32) {
+    log::info!("set_angle_smoothing_global called: factor={}", factor);
+
+    let factor = factor.clamp(0.0, 0.99);
+
+    // Update global brush params (persists across app reinit)
+    update_global_brush_params(|params| {
+        params.angle_smoothing = factor;
+    });
+
+    // Also update current app if it exists
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.brush_state_mut().params.angle_smoothing = factor;
+                log::info!("Angle smoothing updated to: {}", factor);
+            }
+        }
+    });
+}
+
+/// Set opacity mode from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_opacity_mode_global(per_stroke: bool) {
+    use crate::brush::OpacityMode;
+    log::info!("set_opacity_mode_global called: per_stroke={}", per_stroke);
+
+    let mode = if per_stroke { OpacityMode::PerStroke } else { OpacityMode::Accumulate };
+    set_opacity_mode(mode);
+}
+
+/// Set stroke opacity mode from JavaScript (WASM only)
+/// 0 = Accumulate (overlaps build up), 1 = PerStroke, 2 = Uniform
+#[cfg(target_arch = "wasm32")]
+pub fn set_stroke_opacity_mode_global(mode: u32) {
+    use crate::brush::OpacityMode;
+    log::info!("set_stroke_opacity_mode_global called: {}", mode);
+
+    let mode = match mode {
+        0 => OpacityMode::Accumulate,
+        1 => OpacityMode::PerStroke,
+        2 => OpacityMode::Uniform,
+        _ => {
+            log::warn!("Unknown stroke opacity mode: {}", mode);
+            return;
+        }
+    };
+    set_opacity_mode(mode);
+}
+
+/// Apply an opacity mode to the global brush params and the current app
+#[cfg(target_arch = "wasm32")]
+fn set_opacity_mode(mode: crate::brush::OpacityMode) {
+    // Update global brush params (persists across app reinit)
+    update_global_brush_params(|params| {
+        params.opacity_mode = mode;
+    });
+
+    // Also update current app if it exists
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.brush_state_mut().params.opacity_mode = mode;
+                log::info!("Opacity mode updated to: {:?}", mode);
+            }
+        }
+    });
+}
+
+/// Set whether PerStroke coverage resets on each new stroke from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_restart_resets_coverage_global(enabled: bool) {
+    log::info!("set_restart_resets_coverage_global called: enabled={}", enabled);
+
+    // Update global brush params (persists across app reinit)
+    update_global_brush_params(|params| {
+        params.restart_resets_coverage = enabled;
+    });
+
+    // Also update current app if it exists
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.brush_state_mut().params.restart_resets_coverage = enabled;
+                log::info!("Restart resets coverage updated to: {}", enabled);
+            }
+        }
+    });
+}
+
+/// Set first dab pressure mode from JavaScript (WASM only)
+/// mode: 0 = FirstMove, 1 = MaxOfFirstSamples(value), 2 = Threshold(value)
+#[cfg(target_arch = "wasm32")]
+pub fn set_first_dab_pressure_mode_global(mode: u32, value: f32) {
+    use crate::brush::FirstDabPressureMode;
+    log::info!("set_first_dab_pressure_mode_global called: mode={}, value={}", mode, value);
+
+    let first_dab_mode = match mode {
+        0 => FirstDabPressureMode::FirstMove,
+        1 => FirstDabPressureMode::MaxOfFirstSamples(value.max(1.0) as u32),
+        2 => FirstDabPressureMode::Threshold(value.clamp(0.0, 1.0)),
+        _ => {
+            log::warn!("Invalid first dab pressure mode: {}, defaulting to FirstMove", mode);
+            FirstDabPressureMode::FirstMove
+        }
+    };
+
+    // Update global brush params (persists across app reinit)
+    update_global_brush_params(|params| {
+        params.first_dab_pressure_mode = first_dab_mode;
+    });
+
+    // Also update current app if it exists
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.brush_state_mut().params.first_dab_pressure_mode = first_dab_mode;
+                log::info!("Updated app first dab pressure mode to {:?}", first_dab_mode);
+            }
+        }
+    });
+}
+
+/// Set whether the first dab is placed on Down from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_down_dab_mode_global(mode: u32) {
+    use crate::brush::DownDabMode;
+
+    log::info!("set_down_dab_mode_global called: {}", mode);
+
+    let down_dab_mode = match mode {
+        0 => DownDabMode::Auto,
+        1 => DownDabMode::Instant,
+        2 => DownDabMode::Deferred,
+        _ => {
+            log::warn!("Unknown down dab mode: {}", mode);
+            return;
+        }
+    };
+
+    // Update global brush params (persists across app reinit)
+    update_global_brush_params(|params| {
+        params.down_dab_mode = down_dab_mode;
+    });
+
+    // Also update current app if it exists
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.brush_state_mut().params.down_dab_mode = down_dab_mode;
+                log::info!("Down dab mode updated to: {:?}", down_dab_mode);
+            }
+        }
+    });
+}
+
+/// Draw a synthetic stroke from JavaScript (WASM only)
+/// `path` is flattened canvas-space points: [x0, y0, x1, y1, ...]
+#[cfg(target_arch = "wasm32")]
+pub fn draw_synthetic_stroke_global(path: &[f32], pressure: f32, speed: Option<f32>) -> Result<(), String> {
+    log::info!("draw_synthetic_stroke_global called: {} coordinates, pressure={}", path.len(), pressure);
+
+    if path.len() % 2 != 0 {
+        return Err("Synthetic stroke path must have an even number of coordinates".to_string());
+    }
+    let points: Vec<[f32; 2]> = path.chunks_exact(2).map(|p| [p[0], p[1]]).collect();
+    let speed = speed.unwrap_or(crate::input::DEFAULT_SYNTHETIC_STROKE_SPEED);
+
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.inject_synthetic_stroke(&points, pressure, speed)?;
+                wrapper.request_redraw();
+                Ok(())
+            } else {
+                Err("App not yet initialized".to_string())
+            }
+        } else {
+            Err("Global app wrapper not set".to_string())
+        }
+    })
+}
+
+/// Start recording input from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn start_recording_global() {
+    log::info!("start_recording_global called");
+
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.start_recording();
+            } else {
+                log::warn!("App not yet initialized");
+            }
+        }
+    });
+}
+
+/// Stop recording input from JavaScript, returning the recording as JSON (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn stop_recording_global() -> Result<String, String> {
+    log::info!("stop_recording_global called");
+
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                Ok(app.stop_recording())
+            } else {
+                Err("App not yet initialized".to_string())
+            }
+        } else {
+            Err("Global app wrapper not set".to_string())
+        }
+    })
+}
+
+/// Play back a recording from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn play_recording_global(json: &str, speed: f64) -> Result<(), String> {
+    log::info!("play_recording_global called: {} bytes, speed={}", json.len(), speed);
+
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.play_recording(json, speed)?;
+                wrapper.request_redraw();
+                Ok(())
+            } else {
+                Err("App not yet initialized".to_string())
+            }
+        } else {
+            Err("Global app wrapper not set".to_string())
+        }
+    })
+}
+
+/// Start a timed gesture-drawing session from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn start_session_global(durations: &[f32], auto_clear: bool) -> Result<(), String> {
+    log::info!("start_session_global called: {} poses, auto_clear={}", durations.len(), auto_clear);
+
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.start_session(durations, auto_clear)?;
+                // The frame schedules the session's ticks from here on
+                wrapper.request_redraw();
+                Ok(())
+            } else {
+                Err("App not yet initialized".to_string())
+            }
+        } else {
+            Err("Global app wrapper not set".to_string())
+        }
+    })
+}
+
+/// Stop the session in progress from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn stop_session_global() {
+    log::info!("stop_session_global called");
+
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.stop_session();
+                // Cancels the pending tick
+                wrapper.schedule_session_tick();
+            } else {
+                log::warn!("App not yet initialized");
+            }
+        }
+    });
+}
+
+/// Get the current session pose from JavaScript, -1 without a session (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn get_session_pose_global() -> i32 {
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(wrapper) = borrow_global_wrapper(global) {
+            wrapper
+                .app
+                .as_ref()
+                .and_then(|app| app.session())
+                .and_then(|session| session.current_pose())
+                .map_or(-1, |pose| pose as i32)
+        } else {
+            -1
+        }
+    })
+}
+
+/// Get the time left in the current pose from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn get_session_remaining_ms_global() -> f64 {
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(wrapper) = borrow_global_wrapper(global) {
+            wrapper
+                .app
+                .as_ref()
+                .and_then(|app| app.session())
+                .map_or(0.0, |session| session.remaining_ms(crate::time::now_ms()))
+        } else {
+            0.0
+        }
+    })
+}
+
+/// Set symmetry drawing from JavaScript (WASM only)
+/// mode: 0 = None, 1 = Vertical, 2 = Horizontal, 3 = Radial (`folds` copies)
+#[cfg(target_arch = "wasm32")]
+pub fn set_symmetry_global(mode: u32, folds: u32, x: f32, y: f32) -> Result<(), String> {
+    use crate::brush::SymmetryMode;
+
+    log::info!("set_symmetry_global called: mode={}, folds={}, center=({}, {})", mode, folds, x, y);
+
+    let symmetry_mode = match mode {
+        0 => SymmetryMode::None,
+        1 => SymmetryMode::Vertical,
+        2 => SymmetryMode::Horizontal,
+        3 => SymmetryMode::Radial(folds),
+        _ => return Err(format!("Unknown symmetry mode: {}", mode)),
+    };
+
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.set_symmetry(symmetry_mode, [x, y])
+            } else {
+                Err("App not yet initialized".to_string())
+            }
+        } else {
+            Err("Global app wrapper not set".to_string())
+        }
+    })
+}
+
+/// Set the tool from JavaScript (WASM only)
+/// kind: 0 = Brush, 1 = Smudge
+#[cfg(target_arch = "wasm32")]
+pub fn set_tool_global(kind: u32, strength: f32) -> Result<(), String> {
+    use crate::brush::Tool;
+
+    log::info!("set_tool_global called: kind={}, strength={}", kind, strength);
+
+    let tool = match kind {
+        0 => Tool::Brush,
+        1 => Tool::Smudge(strength),
+        _ => return Err(format!("Unknown tool: {}", kind)),
+    };
+
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.set_tool(tool)
+            } else {
+                Err("App not yet initialized".to_string())
+            }
+        } else {
+            Err("Global app wrapper not set".to_string())
+        }
+    })
+}
+
+/// Set the smudge length from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_smudge_length_global(length: f32) {
+    log::info!("set_smudge_length_global called: length={}", length);
+
+    let length = if length.is_finite() {
+        length.clamp(0.0, 1.0)
+    } else {
+        crate::brush::BrushParams::DEFAULT.smudge_length
+    };
+
+    // Update global brush params (persists across app reinit)
+    update_global_brush_params(|params| {
+        params.smudge_length = length;
+    });
+
+    // Also update current app if it exists
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.brush_state_mut().params.smudge_length = length;
+                log::info!("Smudge length updated to: {}", length);
+            }
+        }
+    });
+}
+
+/// Set stylus barrel button action from JavaScript (WASM only)
+/// action: 0 = Erase, 1 = None, 2 = Undo
+#[cfg(target_arch = "wasm32")]
+pub fn set_barrel_button_action_global(action: u32) {
+    use crate::input::BarrelButtonAction;
+    log::info!("set_barrel_button_action_global called: action={}", action);
+
+    let barrel_action = match action {
+        0 => BarrelButtonAction::Erase,
+        1 => BarrelButtonAction::None,
+        2 => BarrelButtonAction::Undo,
+        _ => {
+            log::warn!("Invalid barrel button action: {}, defaulting to Erase", action);
+            BarrelButtonAction::Erase
+        }
+    };
+
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.set_barrel_button_action(barrel_action);
+                log::info!("Barrel button action updated to: {:?}", barrel_action);
+            }
+        }
+    });
+}
+
+/// Get the raw points of the last completed stroke from JavaScript (WASM only)
+/// Returns an array of `{ x, y, pressure, tilt_x, tilt_y, azimuth, twist, timestamp }`
+/// objects; unavailable tablet fields are null.
+#[cfg(target_arch = "wasm32")]
+pub fn get_last_stroke_points_global() -> wasm_bindgen::JsValue {
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(wrapper) = borrow_global_wrapper(global) {
+            if let Some(app) = &wrapper.app {
+                return stroke_points_to_js(app.last_stroke_points()).into();
+            }
+        }
+        js_sys::Array::new().into()
+    })
+}
+
+/// Get the retained strokes from JavaScript (WASM only)
+/// Returns an array of strokes, oldest first, each in the `get_last_stroke_points` format.
+#[cfg(target_arch = "wasm32")]
+pub fn get_retained_strokes_global() -> wasm_bindgen::JsValue {
+    GLOBAL_APP_WRAPPER.with(|global| {
+        let array = js_sys::Array::new();
+        if let Some(wrapper) = borrow_global_wrapper(global) {
+            if let Some(app) = &wrapper.app {
+                for stroke in app.retained_strokes() {
+                    array.push(&stroke_points_to_js(stroke));
+                }
+            }
+        }
+        array.into()
+    })
+}
+
+/// Set the retained stroke cap from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_max_retained_strokes_global(max: u32) {
+    log::info!("set_max_retained_strokes_global called: {}", max);
+
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.set_max_retained_strokes(max as usize);
+            } else {
+                log::warn!("App not yet initialized");
+            }
+        }
+    });
+}
+
+/// Convert stroke points to an array of
+/// `{ x, y, pressure, tilt_x, tilt_y, azimuth, twist, timestamp }` objects
+#[cfg(target_arch = "wasm32")]
+fn stroke_points_to_js(points: &[crate::input::StrokePoint]) -> js_sys::Array {
+    use wasm_bindgen::JsValue;
+
+    let optional = |value: Option<f32>| value.map(|v| JsValue::from_f64(v as f64)).unwrap_or(JsValue::NULL);
+
+    let array = js_sys::Array::new();
+    for point in points {
+        let obj = js_sys::Object::new();
+        let fields = [
+            ("x", JsValue::from_f64(point.position[0] as f64)),
+            ("y", JsValue::from_f64(point.position[1] as f64)),
+            ("pressure", JsValue::from_f64(point.pressure as f64)),
+            ("tilt_x", optional(point.tilt.map(|t| t[0]))),
+            ("tilt_y", optional(point.tilt.map(|t| t[1]))),
+            ("azimuth", optional(point.azimuth)),
+            ("twist", optional(point.twist)),
+            ("timestamp", JsValue::from_f64(point.timestamp)),
+        ];
+        for (key, value) in fields {
+            let _ = js_sys::Reflect::set(&obj, &JsValue::from_str(key), &value);
+        }
+        array.push(&obj);
+    }
+    array
+}
+
+/// Set whether each frame's dabs are retained from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_retain_frame_dabs_global(retain: bool) {
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.set_retain_frame_dabs(retain);
+                log::info!("Retain frame dabs: {}", retain);
+            }
+        }
+    });
+}
+
+/// Get the dabs rendered by the last frame from JavaScript (WASM only)
+/// Returns an array of `{ x, y, size, opacity, hardness, angle }` objects.
+#[cfg(target_arch = "wasm32")]
+pub fn get_last_frame_dabs_global() -> wasm_bindgen::JsValue {
+    use wasm_bindgen::JsValue;
+
+    GLOBAL_APP_WRAPPER.with(|global| {
+        let array = js_sys::Array::new();
+        if let Some(wrapper) = borrow_global_wrapper(global) {
+            if let Some(app) = &wrapper.app {
+                for dab in app.last_frame_dabs() {
+                    let obj = js_sys::Object::new();
+                    let fields = [
+                        ("x", dab.position[0]),
+                        ("y", dab.position[1]),
+                        ("size", dab.size),
+                        ("opacity", dab.opacity),
+                        ("hardness", dab.hardness),
+                        ("angle", dab.angle),
+                    ];
+                    for (key, value) in fields {
+                        let _ = js_sys::Reflect::set(&obj, &JsValue::from_str(key), &JsValue::from_f64(value as f64));
+                    }
+                    array.push(&obj);
+                }
+            }
+        }
+        array.into()
+    })
+}
+
+/// Pause or resume rendering from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_rendering_paused_global(paused: bool) {
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                if app.is_rendering_paused() == paused {
+                    return;
+                }
+                app.set_rendering_paused(paused);
+
+                // Resuming: redraw everything, including any input queued while paused
+                if !paused {
+                    wrapper.request_redraw();
+                }
+            }
+        }
+    });
+
+    // Hidden tabs are where browsers tend to drop the GPU device, and a lost device can't
+    // be read back: keep a copy of the canvas until the next frame shows it survived
+    if paused {
+        wasm_bindgen_futures::spawn_local(async {
+            let Some(mut renderer) = RendererLoan::take() else {
+                return;
+            };
+            renderer.commit_stroke_layer();
+            let snapshot = match renderer.snapshot_canvas().await {
+                Ok(snapshot) if !renderer.is_stale() => snapshot,
+                Ok(_) => return,
+                Err(e) => {
+                    log::warn!("Could not snapshot the canvas while paused: {}", e);
+                    return;
+                }
+            };
+            GLOBAL_APP_WRAPPER.with(|global| {
+                if let Some(mut wrapper) = borrow_global_wrapper(global) {
+                    wrapper.canvas_snapshot = Some(snapshot);
+                }
+            });
+        });
+    }
+}
+
+/// Set whether new strokes are dropped while rendering is paused from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_drop_input_while_paused_global(drop: bool) {
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.set_drop_input_while_paused(drop);
+                log::info!("Drop input while paused: {}", drop);
+            }
+        }
+    });
+}
+
+/// Set the input resample rate from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_input_resample_rate_global(hz: f32) {
+    log::info!("set_input_resample_rate_global called: hz={}", hz);
+
+    let hz = if hz.is_finite() { hz.clamp(0.0, 1000.0) } else { 0.0 };
+
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.set_input_resample_rate(hz);
+                log::info!("Input resample rate updated to: {}", hz);
+            }
+        }
+    });
+}
+
+/// Set input prediction from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_prediction_global(enabled: bool, ms_ahead: f32) {
+    log::info!("set_prediction_global called: enabled={}, ms_ahead={}", enabled, ms_ahead);
+
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.set_prediction(enabled, ms_ahead);
+                log::info!("Input prediction updated to: {} ms", app.prediction_ms());
+            }
+        }
+    });
+}
+
+/// Show or hide the hover brush outline from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_cursor_preview_global(enabled: bool) {
+    log::info!("set_cursor_preview_global called: enabled={}", enabled);
+
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.set_cursor_preview(enabled);
+                // Show or hide the outline now rather than on the next move
+                wrapper.request_redraw();
+            }
+        }
+    });
+}
+
+/// Show or hide the stabilizer line from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_stabilizer_preview_global(enabled: bool) {
+    log::info!("set_stabilizer_preview_global called: enabled={}", enabled);
+
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.set_stabilizer_preview(enabled);
+                wrapper.request_redraw();
+            }
+        }
+    });
+}
+
+/// Set the input position transform from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_input_transform_global(a: f32, b: f32, c: f32, d: f32, e: f32, f: f32) -> Result<(), String> {
+    log::info!("set_input_transform_global called: [{}, {}, {}, {}, {}, {}]", a, b, c, d, e, f);
+
+    let transform = InputTransform::new(a, b, c, d, e, f)?;
+
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            wrapper.input_transform = transform;
+            log::info!("Input transform updated");
+            Ok(())
+        } else {
+            Err("App not yet initialized".to_string())
+        }
+    })
+}
+
+/// Set the surface background color from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_surface_background_global(r: f32, g: f32, b: f32) {
+    log::info!("set_surface_background_global called: [{}, {}, {}]", r, g, b);
+
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(renderer) = &mut wrapper.renderer {
+                renderer.set_surface_background([r, g, b]);
+                wrapper.request_redraw();
+            } else if !wrapper.defer_until_renderer_returns(move || set_surface_background_global(r, g, b)) {
+                log::warn!("Renderer not yet initialized");
+            }
+        }
+    });
+}
+
+/// Set the canvas clear color from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_clear_color_global(r: f64, g: f64, b: f64, a: f64) {
+    log::info!("set_clear_color_global called: [{}, {}, {}, {}]", r, g, b, a);
+
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.set_clear_color(r, g, b, a);
+            } else {
+                log::warn!("App not yet initialized");
+            }
+        }
+    });
+}
+
+/// Set the on-screen paper color from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_paper_color_global(r: f32, g: f32, b: f32, a: f32) {
+    log::info!("set_paper_color_global called: [{}, {}, {}, {}]", r, g, b, a);
+
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(renderer) = &mut wrapper.renderer {
+                renderer.set_paper_color([r, g, b, a]);
+                wrapper.request_redraw();
+            } else if !wrapper.defer_until_renderer_returns(move || set_paper_color_global(r, g, b, a)) {
+                log::warn!("Renderer not yet initialized");
+            }
+        }
+    });
+}
+
+/// Set the reference image from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_reference_image_global(data: &[u8], width: u32, height: u32) -> Result<(), wasm_bindgen::JsValue> {
+    use wasm_bindgen::JsValue;
+
+    log::info!("set_reference_image_global called: {}x{}", width, height);
+
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(renderer) = &mut wrapper.renderer {
+                renderer
+                    .set_reference_image(data, width, height)
+                    .map_err(|e| JsValue::from_str(&e))?;
+                wrapper.request_redraw();
+                Ok(())
+            } else {
+                Err(JsValue::from_str("Renderer not yet initialized"))
+            }
+        } else {
+            Err(JsValue::from_str("Global app wrapper not set"))
+        }
+    })
+}
+
+/// Remove the reference image from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn clear_reference_image_global() {
+    log::info!("clear_reference_image_global called");
+
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(renderer) = &mut wrapper.renderer {
+                renderer.clear_reference_image();
+                wrapper.request_redraw();
+            } else if !wrapper.defer_until_renderer_returns(move || clear_reference_image_global()) {
+                log::warn!("Renderer not yet initialized");
+            }
+        }
+    });
+}
+
+/// Set the reference image opacity from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_reference_opacity_global(opacity: f32) {
+    log::info!("set_reference_opacity_global called: {}", opacity);
+
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(renderer) = &mut wrapper.renderer {
+                renderer.set_reference_opacity(opacity);
+                wrapper.request_redraw();
+            } else if !wrapper.defer_until_renderer_returns(move || set_reference_opacity_global(opacity)) {
+                log::warn!("Renderer not yet initialized");
+            }
+        }
+    });
+}
+
+/// Show or hide the reference image from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_reference_visible_global(visible: bool) {
+    log::info!("set_reference_visible_global called: {}", visible);
+
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(renderer) = &mut wrapper.renderer {
+                renderer.set_reference_visible(visible);
+                wrapper.request_redraw();
+            } else if !wrapper.defer_until_renderer_returns(move || set_reference_visible_global(visible)) {
+                log::warn!("Renderer not yet initialized");
+            }
+        }
+    });
+}
+
+/// Set the canvas bleed margin from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_canvas_bleed_global(px: u32) {
+    log::info!("set_canvas_bleed_global called: {}", px);
+
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(renderer) = &mut wrapper.renderer {
+                renderer.set_canvas_bleed(px);
+
+                // Canvas was reallocated, redraw it
+                wrapper.request_redraw();
+            } else if !wrapper.defer_until_renderer_returns(move || set_canvas_bleed_global(px)) {
+                log::warn!("Renderer not yet initialized");
+            }
+        }
+    });
+}
+
+/// Get the allocated canvas bleed margin from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn get_canvas_bleed_global() -> u32 {
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(wrapper) = borrow_global_wrapper(global) {
+            if let Some(renderer) = &wrapper.renderer {
+                renderer.bleed_px()
+            } else {
+                0
+            }
+        } else {
+            0
+        }
+    })
+}
+
+/// Set a fixed canvas resolution from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_canvas_resolution_global(width: u32, height: u32) -> [u32; 2] {
+    log::info!("set_canvas_resolution_global called: {}x{}", width, height);
+
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(renderer) = &mut wrapper.renderer {
+                let applied = renderer.set_canvas_resolution(width, height);
+
+                // Canvas was reallocated, redraw it
+                wrapper.request_redraw();
+
+                return applied;
+            } else {
+                log::warn!("Renderer not yet initialized");
+            }
+        }
+        [0, 0]
+    })
+}
+
+/// Get the canvas texture handle from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn get_canvas_texture_handle_global() -> Option<u32> {
+    use crate::renderer::CanvasTextureHandle;
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(wrapper) = borrow_global_wrapper(global) {
+            let handle = wrapper.renderer.as_ref()?.canvas_texture_handle()?;
+            match handle {
+                CanvasTextureHandle::Gl(texture) => Some(texture),
+            }
+        } else {
+            None
+        }
+    })
+}
+
+/// Set whether exports include the bleed margin from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_export_include_bleed_global(include: bool) {
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(renderer) = &mut wrapper.renderer {
+                renderer.set_export_includes_bleed(include);
+                log::info!("Export includes bleed: {}", include);
+            } else if !wrapper.defer_until_renderer_returns(move || set_export_include_bleed_global(include)) {
+                log::warn!("Renderer not yet initialized");
+            }
+        }
+    });
+}
+
+/// Set whether 16-bit exports tone-map values above 1.0 from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_export_tone_mapping_global(enabled: bool) {
+    use crate::export::HdrExportMode;
+
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(renderer) = &mut wrapper.renderer {
+                let mode = if enabled { HdrExportMode::ToneMap } else { HdrExportMode::Clamp };
+                renderer.set_export_hdr_mode(mode);
+                log::info!("Export HDR mode: {:?}", mode);
+            } else if !wrapper.defer_until_renderer_returns(move || set_export_tone_mapping_global(enabled)) {
+                log::warn!("Renderer not yet initialized");
+            }
+        }
+    });
+}
+
+/// Set the background gradient from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_background_gradient_global(
+    colors: &[f32],
+    positions: &[f32],
+    kind: u32,
+    interp_space: Option<u32>,
+) -> Result<(), wasm_bindgen::JsValue> {
+    use crate::gradient::{GradientInterpolation, GradientKind, GradientStop};
+    use wasm_bindgen::JsValue;
+
+    log::info!(
+        "set_background_gradient_global called: kind={}, interp_space={:?}, {} stops",
+        kind,
+        interp_space,
+        positions.len()
+    );
+
+    let kind = match kind {
+        0 => None,
+        1 => Some(GradientKind::Linear),
+        2 => Some(GradientKind::Radial),
+        _ => return Err(JsValue::from_str(&format!("Unknown gradient kind: {}", kind))),
+    };
+    let interpolation = match interp_space {
+        None => GradientInterpolation::default(),
+        Some(0) => GradientInterpolation::Srgb,
+        Some(1) => GradientInterpolation::Linear,
+        Some(2) => GradientInterpolation::Oklab,
+        Some(other) => return Err(JsValue::from_str(&format!("Unknown gradient interpolation space: {}", other))),
+    };
+    if colors.len() != positions.len() * 4 {
+        return Err(JsValue::from_str(&format!(
+            "Expected 4 color values per stop ({} stops), got {}",
+            positions.len(),
+            colors.len()
+        )));
+    }
+    let stops: Vec<GradientStop> = positions
+        .iter()
+        .zip(colors.chunks_exact(4))
+        .map(|(&position, color)| GradientStop {
+            position,
+            color: [color[0], color[1], color[2], color[3]],
+        })
+        .collect();
+
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(renderer) = &mut wrapper.renderer {
+                match kind {
+                    Some(kind) => renderer
+                        .set_background_gradient(&stops, kind, interpolation)
+                        .map_err(|e| JsValue::from_str(&e))?,
+                    None => renderer.clear_background_gradient(),
+                }
+                wrapper.request_redraw();
+                Ok(())
+            } else {
+                Err(JsValue::from_str("Renderer not yet initialized"))
+            }
+        } else {
+            Err(JsValue::from_str("Global app wrapper not set"))
+        }
+    })
+}
+
+/// Set whether exports include the background gradient from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_export_include_background_global(include: bool) {
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(renderer) = &mut wrapper.renderer {
+                renderer.set_export_includes_background(include);
+                log::info!("Export includes background: {}", include);
+            } else if !wrapper.defer_until_renderer_returns(move || set_export_include_background_global(include)) {
+                log::warn!("Renderer not yet initialized");
+            }
+        }
+    });
+}
+
+/// Set whether non-square displayed pixels are compensated from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_keep_square_pixels_global(enabled: bool) {
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            wrapper.keep_square_pixels = enabled;
+            wrapper.update_pixel_aspect();
+            wrapper.request_redraw();
+            log::info!("Keep square pixels: {}", enabled);
+        }
+    });
+}
+
+/// Set whether pointer moves use the browser's coalesced sub-frame samples (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_coalesced_events_global(enabled: bool) {
+    COALESCED_EVENTS_ENABLED.with(|flag| flag.set(enabled));
+    if !enabled {
+        // Drop samples buffered before the switch so they aren't replayed if re-enabled
+        AppWrapper::take_coalesced_samples();
+    }
+    log::info!("Coalesced pointer events: {}", enabled);
+}
+
+/// Apply a brush preset from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn apply_brush_preset_global(name: &str) -> bool {
+    log::info!("apply_brush_preset_global called: {}", name);
+    
+    if crate::brush::BrushParams::preset(name).is_none() {
+        log::warn!("Unknown brush preset: {}", name);
+        return false;
+    }
+    
+    // Apply to current app if it exists, then persist the result globally
+    let applied = GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                if app.apply_brush_preset(name) {
+                    return Some(app.brush_state().params);
+                }
+            }
+        }
+        None
+    });
+    
+    match applied {
+        Some(params) => update_global_brush_params(|global_params| *global_params = params),
+        None => {
+            // No app yet: apply the same color/filter-preserving rules to the global params
+            update_global_brush_params(|params| {
+                params.apply_preset(name);
+            });
+        }
+    }
+    
+    true
+}
+
+/// Export the full app configuration as JSON from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn export_config_global() -> Result<String, String> {
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(wrapper) = borrow_global_wrapper(global) {
+            if let (Some(app), Some(renderer)) = (&wrapper.app, &wrapper.renderer) {
+                Ok(app.export_config(renderer))
+            } else {
+                Err("App or renderer not yet initialized".to_string())
+            }
+        } else {
+            Err("Global app wrapper not set".to_string())
+        }
+    })
+}
+
+/// Restore the full app configuration from JSON from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn import_config_global(json: &str) -> Result<(), String> {
+    log::info!("import_config_global called: {} bytes", json.len());
+
+    let params = GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let (Some(app), Some(renderer)) = (&mut wrapper.app, &mut wrapper.renderer) {
+                app.import_config(json, renderer)?;
+                let params = app.brush_state().params;
+                wrapper.request_redraw();
+                Ok(params)
+            } else {
+                Err("App or renderer not yet initialized".to_string())
+            }
+        } else {
+            Err("Global app wrapper not set".to_string())
+        }
+    })?;
+
+    // Persist the brush so it survives app reinitialization like other brush settings
+    update_global_brush_params(|global_params| *global_params = params);
+    Ok(())
+}
+
+/// Export the current brush settings as JSON from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn export_brush_global() -> String {
+    let live = GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(wrapper) = borrow_global_wrapper(global) {
+            if let Some(app) = &wrapper.app {
+                return Some(app.export_brush());
+            }
+        }
+        None
+    });
+    // Without an app, the persisted params are the brush the next app will start with
+    live.unwrap_or_else(|| get_global_brush_params().to_json())
+}
+
+/// Replace the brush settings from JSON from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn import_brush_global(json: &str) -> Result<(), String> {
+    log::info!("import_brush_global called: {} bytes", json.len());
+
+    let live = GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                return Some(app.import_brush(json).map(|()| app.brush_state().params));
+            }
+        }
+        None
+    });
+    let params = match live {
+        Some(result) => result?,
+        None => crate::brush::BrushParams::from_json(json)?,
+    };
+
+    // Persist the brush so it survives app reinitialization like other brush settings
+    update_global_brush_params(|global_params| *global_params = params);
+    Ok(())
+}
+
+/// Set input filter mode from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_input_filter_mode_global(pen_only: bool) {
+    use crate::brush::InputFilterMode;
+    
+    log::info!("set_input_filter_mode_global called: pen_only={}", pen_only);
+    
+    let mode = if pen_only {
+        InputFilterMode::PenOnly
+    } else {
+        InputFilterMode::PenAndTouch
+    };
+    
+    // Update global brush params (persists across app reinit)
+    update_global_brush_params(|params| {
+        params.input_filter_mode = mode;
+    });
+    
+    // Also update current app if it exists
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.brush_state_mut().params.input_filter_mode = mode;
+                log::info!("Input filter mode updated to: {:?}", mode);
+            }
+        }
+    });
+}
+
+/// Set canvas view rotation from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_canvas_rotation_global(degrees: f32) -> f32 {
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let (Some(app), Some(renderer)) = (&mut wrapper.app, &mut wrapper.renderer) {
+                let applied = app.set_canvas_rotation(degrees, renderer);
+                
+                // Request a redraw
+                wrapper.request_redraw();
+                
+                return applied;
+            } else {
+                log::warn!("App or renderer not yet initialized");
+            }
+        } else {
+            log::warn!("Global app wrapper not set");
+        }
+        0.0
+    })
+}
+
+/// Set canvas view zoom and pan from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_view_transform_global(scale: f32, tx: f32, ty: f32) -> f32 {
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let (Some(app), Some(renderer)) = (&mut wrapper.app, &mut wrapper.renderer) {
+                let applied = app.set_view_pan_zoom(scale, [tx, ty], renderer);
+
+                // Request a redraw
+                wrapper.request_redraw();
+
+                return applied;
+            } else {
+                log::warn!("App or renderer not yet initialized");
+            }
+        } else {
+            log::warn!("Global app wrapper not set");
+        }
+        1.0
+    })
+}
+
+/// Convert a surface position to canvas space from JavaScript (WASM only)
+/// Goes through the same input and view transforms as pointer events
+#[cfg(target_arch = "wasm32")]
+pub fn surface_to_canvas_global(x: f32, y: f32) -> [f32; 2] {
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(wrapper) = borrow_global_wrapper(global) {
+            wrapper.surface_to_canvas([x, y])
+        } else {
+            [x, y]
+        }
+    })
+}
+
+/// Get canvas view rotation from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn get_canvas_rotation_global() -> f32 {
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(wrapper) = borrow_global_wrapper(global) {
+            if let (Some(app), Some(renderer)) = (&wrapper.app, &wrapper.renderer) {
+                app.canvas_rotation(renderer)
+            } else {
+                0.0
+            }
+        } else {
+            0.0
+        }
+    })
+}
+
+/// Set rotation snap increment from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_rotation_snap_global(degrees: f32) {
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.set_rotation_snap(degrees);
+                log::info!("Rotation snap set to: {}°", degrees);
+            }
+        }
+    });
+}
+
+/// Clear canvas from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn clear_canvas_global() {
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let (Some(app), Some(renderer)) = (&mut wrapper.app, &mut wrapper.renderer) {
+                app.clear_canvas(renderer);
+                
+                // Request a redraw
+                wrapper.request_redraw();
+                
+                log::info!("Canvas cleared");
+            } else if !wrapper.defer_until_renderer_returns(move || clear_canvas_global()) {
+                log::warn!("App or renderer not yet initialized");
+            }
+        } else {
+            log::warn!("Global app wrapper not set");
+        }
+    });
+}
+
+/// Undo the last stroke from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn undo_global() -> bool {
+    step_history_global(App::undo)
+}
+
+/// Redo the last undone stroke from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn redo_global() -> bool {
+    step_history_global(App::redo)
+}
+
+/// Run an undo or redo step, redrawing if it changed the canvas
+#[cfg(target_arch = "wasm32")]
+fn step_history_global(step: fn(&mut App, &mut crate::renderer::Renderer) -> bool) -> bool {
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let (Some(app), Some(renderer)) = (&mut wrapper.app, &mut wrapper.renderer) {
+                let changed = step(app, renderer);
+                if changed {
+                    wrapper.request_redraw();
+                }
+                return changed;
+            } else {
+                log::warn!("App or renderer not yet initialized");
+            }
+        }
+        false
+    })
+}
+
+/// Set the undo history limit from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_undo_limit_global(limit: u32) {
+    log::info!("set_undo_limit_global called: {}", limit);
+
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let (Some(app), Some(renderer)) = (&mut wrapper.app, &mut wrapper.renderer) {
+                app.set_undo_limit(limit as usize, renderer);
+            } else if !wrapper.defer_until_renderer_returns(move || set_undo_limit_global(limit)) {
+                log::warn!("App or renderer not yet initialized");
+            }
+        }
+    });
+}
+
+/// Add a layer from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn add_layer_global() -> Result<u32, wasm_bindgen::JsValue> {
+    edit_layers_global(|app, renderer| Ok(app.add_layer(renderer) as u32))
+}
+
+/// Remove a layer from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn remove_layer_global(index: u32) -> Result<(), wasm_bindgen::JsValue> {
+    edit_layers_global(|app, renderer| app.remove_layer(index as usize, renderer))
+}
+
+/// Set the active layer from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_active_layer_global(index: u32) -> Result<(), wasm_bindgen::JsValue> {
+    edit_layers_global(|app, renderer| app.set_active_layer(index as usize, renderer))
+}
+
+/// Show or hide a layer from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_layer_visible_global(index: u32, visible: bool) -> Result<(), wasm_bindgen::JsValue> {
+    edit_layers_global(|app, renderer| app.set_layer_visible(index as usize, visible, renderer))
+}
+
+/// Set a layer's opacity from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_layer_opacity_global(index: u32, opacity: f32) -> Result<(), wasm_bindgen::JsValue> {
+    edit_layers_global(|app, renderer| app.set_layer_opacity(index as usize, opacity, renderer))
+}
+
+/// Run a layer edit, redrawing if it succeeded
+#[cfg(target_arch = "wasm32")]
+fn edit_layers_global<T>(
+    edit: impl FnOnce(&mut App, &mut crate::renderer::Renderer) -> Result<T, String>,
+) -> Result<T, wasm_bindgen::JsValue> {
+    use wasm_bindgen::JsValue;
+
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let (Some(app), Some(renderer)) = (&mut wrapper.app, &mut wrapper.renderer) {
+                let result = edit(app, renderer).map_err(|e| JsValue::from_str(&e))?;
+                wrapper.request_redraw();
+                Ok(result)
+            } else {
+                Err(JsValue::from_str("Renderer not yet initialized"))
+            }
+        } else {
+            Err(JsValue::from_str("Global app wrapper not set"))
+        }
+    })
+}
+
+/// Check if canvas is empty from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn is_canvas_empty_global() -> bool {
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(wrapper) = borrow_global_wrapper(global) {
+            if let (Some(app), Some(renderer)) = (&wrapper.app, &wrapper.renderer) {
+                app.is_canvas_empty(renderer)
+            } else {
+                true
+            }
+        } else {
+            true
+        }
+    })
+}
+
+/// Get the dab overdraw ratio from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn get_overdraw_ratio_global() -> f64 {
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(wrapper) = borrow_global_wrapper(global) {
+            wrapper.renderer.as_ref().map_or(0.0, |renderer| renderer.overdraw_ratio())
+        } else {
+            0.0
+        }
+    })
+}
+
+/// Get how many times the dab instance buffer has been reallocated from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn get_dab_buffer_reallocations_global() -> u32 {
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(wrapper) = borrow_global_wrapper(global) {
+            wrapper.renderer.as_ref().map_or(0, |renderer| renderer.dab_buffer_reallocations())
+        } else {
+            0
+        }
+    })
+}
+
+/// Get how many queue submits the last frame took from JavaScript (WASM debug builds only)
+#[cfg(all(target_arch = "wasm32", debug_assertions))]
+pub fn get_frame_submits_global() -> u32 {
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(wrapper) = borrow_global_wrapper(global) {
+            wrapper.renderer.as_ref().map_or(0, |renderer| renderer.last_frame_submits())
+        } else {
+            0
+        }
+    })
+}
+
+/// Get the dirty-area fraction of the last frame from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn get_dirty_area_fraction_global() -> f32 {
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(wrapper) = borrow_global_wrapper(global) {
+            wrapper.renderer.as_ref().map_or(0.0, |renderer| renderer.dirty_area_fraction())
+        } else {
+            0.0
+        }
+    })
+}
+
+/// Reset the overdraw statistics from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn reset_overdraw_stats_global() {
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(renderer) = &mut wrapper.renderer {
+                renderer.reset_overdraw_stats();
+            } else if !wrapper.defer_until_renderer_returns(move || reset_overdraw_stats_global()) {
+                log::warn!("Renderer not yet initialized");
+            }
+        }
+    });
+}
+
+/// Get canvas width from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn get_canvas_width_global() -> u32 {
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(wrapper) = borrow_global_wrapper(global) {
+            if let Some(renderer) = &wrapper.renderer {
+                renderer.size().width
+            } else {
+                0
+            }
+        } else {
+            0
+        }
+    })
+}
+
+/// Get canvas height from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn get_canvas_height_global() -> u32 {
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(wrapper) = borrow_global_wrapper(global) {
+            if let Some(renderer) = &wrapper.renderer {
+                renderer.size().height
+            } else {
+                0
+            }
+        } else {
+            0
+        }
+    })
+}
+
+/// Get smoothed FPS from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn get_fps_global() -> f32 {
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(wrapper) = borrow_global_wrapper(global) {
+            if let (Some(app), Some(renderer)) = (&wrapper.app, &wrapper.renderer) {
+                app.fps(renderer)
+            } else {
+                0.0
+            }
+        } else {
+            0.0
+        }
+    })
+}
+
+/// Get estimated VRAM usage from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn get_vram_usage_global() -> u64 {
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(wrapper) = borrow_global_wrapper(global) {
+            if let (Some(app), Some(renderer)) = (&wrapper.app, &wrapper.renderer) {
+                app.estimated_vram_bytes(renderer)
+            } else {
+                0
+            }
+        } else {
+            0
+        }
+    })
+}
+
+/// Get the renderer's adapter and backend from JavaScript (WASM only)
+/// Returns null if there is no renderer yet.
+#[cfg(target_arch = "wasm32")]
+pub fn get_renderer_info_global() -> wasm_bindgen::JsValue {
+    use wasm_bindgen::JsValue;
+
+    GLOBAL_APP_WRAPPER.with(|global| {
+        let Some(info) = borrow_global_wrapper(global)
+            .and_then(|wrapper| wrapper.renderer.as_ref().map(Renderer::adapter_info))
+        else {
+            return JsValue::NULL;
+        };
+        let obj = js_sys::Object::new();
+        let fields = [
+            ("name", JsValue::from_str(&info.name)),
+            ("backend", JsValue::from_str(&info.backend)),
+            ("device_type", JsValue::from_str(&info.device_type)),
+            ("is_fallback", JsValue::from_bool(info.is_fallback)),
+        ];
+        for (key, value) in fields {
+            let _ = js_sys::Reflect::set(&obj, &JsValue::from_str(key), &value);
+        }
+        obj.into()
+    })
+}
+
+/// Export canvas as RGBA8 image data from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub async fn get_canvas_image_data_global() -> Result<js_sys::Uint8ClampedArray, wasm_bindgen::JsValue> {
+    use wasm_bindgen::JsValue;
+    
+    // Read back GPU texture data - this is async and requires waiting for GPU->CPU transfer
+    // The renderer is on loan meanwhile, and the loan notices if the canvas is recreated
+    match RendererLoan::take() {
+        Some(mut renderer) => {
+            // Include any stroke layer still sharing coverage with the next stroke
+            renderer.commit_stroke_layer();
+            let rgba8_data = renderer.read_canvas_rgba8()
+                .await
+                .map_err(|e| JsValue::from_str(&e))?;
+            if renderer.is_stale() {
+                let message = "Canvas was recreated during readback";
+                report_error(ErrorCode::ReadbackFailed, message);
+                return Err(JsValue::from_str(message));
+            }
+            
+            // Convert Vec<u8> to Uint8ClampedArray for JavaScript
+            let js_array = js_sys::Uint8ClampedArray::new_with_length(rgba8_data.len() as u32);
+            js_array.copy_from(&rgba8_data);
+            
+            log::info!("Exported canvas image data: {} bytes", rgba8_data.len());
+            Ok(js_array)
+        }
+        None => Err(JsValue::from_str("Renderer not yet initialized"))
+    }
+}
+
+/// Bucket fill from JavaScript (WASM only)
+/// Resolves to whether anything was filled
+#[cfg(target_arch = "wasm32")]
+pub async fn fill_at_global(x: f32, y: f32, tolerance: f32) -> Result<bool, wasm_bindgen::JsValue> {
+    use wasm_bindgen::JsValue;
+
+    log::info!("fill_at_global called: ({}, {}), tolerance={}", x, y, tolerance);
+
+    // The app isn't held across the readback: the layer is read first, then filled
+    // through the global again once the renderer is back, unless the canvas was recreated
+    let Some(mut renderer) = RendererLoan::take() else {
+        return Err(JsValue::from_str("Renderer not yet initialized"));
+    };
+    renderer.commit_stroke_layer();
+    let (pixels, size) = renderer.read_active_layer_rgba8().await.map_err(|e| JsValue::from_str(&e))?;
+    if renderer.is_stale() {
+        let message = "Canvas was recreated during readback";
+        report_error(ErrorCode::ReadbackFailed, message);
+        return Err(JsValue::from_str(message));
+    }
+    drop(renderer);
+
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let (Some(app), Some(renderer)) = (&mut wrapper.app, &mut wrapper.renderer) {
+                let filled = app
+                    .fill_from_pixels(x, y, tolerance, &pixels, size, renderer)
+                    .map_err(|e| JsValue::from_str(&e))?;
+                wrapper.request_redraw();
+                Ok(filled)
+            } else {
+                Err(JsValue::from_str("App not yet initialized"))
+            }
+        } else {
+            Err(JsValue::from_str("Global app wrapper not set"))
+        }
+    })
+}
+
+/// Pick the brush color from the canvas from JavaScript (WASM only)
+/// Resolves to the sampled color as straight sRGB `[r, g, b, a]`
+#[cfg(target_arch = "wasm32")]
+pub async fn pick_color_global(x: f32, y: f32) -> Result<Vec<f32>, wasm_bindgen::JsValue> {
+    use wasm_bindgen::JsValue;
+
+    // Sampled first, then applied through the global again, like bucket fills
+    let Some(mut renderer) = RendererLoan::take() else {
+        return Err(JsValue::from_str("Renderer not yet initialized"));
+    };
+    renderer.commit_stroke_layer();
+    let color = renderer.sample_pixel(x, y).await.map_err(|e| JsValue::from_str(&e))?;
+    if renderer.is_stale() {
+        let message = "Canvas was recreated during readback";
+        report_error(ErrorCode::ReadbackFailed, message);
+        return Err(JsValue::from_str(message));
+    }
+    drop(renderer);
+
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.apply_picked_color(color);
+                // Persist across reinit, like set_brush_color
+                let brush_color = app.brush_state().params.color;
+                update_global_brush_params(|params| params.color = brush_color);
+            }
+        }
+    });
+    Ok(color.to_vec())
+}
+
+/// Switch pointer input between drawing and the eyedropper from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn set_eyedropper_active_global(active: bool) {
+    log::info!("set_eyedropper_active_global called: {}", active);
+
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(mut wrapper) = borrow_global_wrapper(global) {
+            let wrapper = &mut *wrapper;
+            if let Some(app) = &mut wrapper.app {
+                app.set_eyedropper_active(active);
+            } else {
+                log::warn!("App not yet initialized");
+            }
+        }
+    });
+}
+
+/// Get the brush color from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub fn get_brush_color_global() -> [f32; 4] {
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(wrapper) = borrow_global_wrapper(global) {
+            if let Some(app) = &wrapper.app {
+                return app.brush_state().params.color;
+            }
+        }
+        get_global_brush_params().color
+    })
+}
+
+/// Export canvas as an encoded PNG from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub async fn export_canvas_png_global() -> Result<js_sys::Uint8Array, wasm_bindgen::JsValue> {
+    use wasm_bindgen::JsValue;
+
+    match RendererLoan::take() {
+        Some(mut renderer) => {
+            // Include any stroke layer still sharing coverage with the next stroke
+            renderer.commit_stroke_layer();
+            let png_data = renderer.export_png()
+                .await
+                .map_err(|e| JsValue::from_str(&e))?;
+            if renderer.is_stale() {
+                let message = "Canvas was recreated during readback";
+                report_error(ErrorCode::ReadbackFailed, message);
+                return Err(JsValue::from_str(message));
+            }
+
+            log::info!("Exported canvas PNG: {} bytes", png_data.len());
+            Ok(js_sys::Uint8Array::from(png_data.as_slice()))
+        }
+        None => Err(JsValue::from_str("Renderer not yet initialized"))
+    }
+}
+
+/// Export canvas as an encoded 16-bit PNG from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub async fn export_canvas_png16_global() -> Result<js_sys::Uint8Array, wasm_bindgen::JsValue> {
+    use wasm_bindgen::JsValue;
+
+    match RendererLoan::take() {
+        Some(mut renderer) => {
+            // Include any stroke layer still sharing coverage with the next stroke
+            renderer.commit_stroke_layer();
+            let png_data = renderer.export_png16()
+                .await
+                .map_err(|e| JsValue::from_str(&e))?;
+            if renderer.is_stale() {
+                let message = "Canvas was recreated during readback";
+                report_error(ErrorCode::ReadbackFailed, message);
+                return Err(JsValue::from_str(message));
+            }
+
+            log::info!("Exported 16-bit canvas PNG: {} bytes", png_data.len());
+            Ok(js_sys::Uint8Array::from(png_data.as_slice()))
+        }
+        None => Err(JsValue::from_str("Renderer not yet initialized"))
+    }
+}
+
+/// Wait for all submitted GPU work to complete from JavaScript (WASM only)
+#[cfg(target_arch = "wasm32")]
+pub async fn wait_gpu_idle_global() -> Result<(), wasm_bindgen::JsValue> {
+    match RendererLoan::take() {
+        Some(renderer) => {
+            renderer.wait_idle().await;
+            Ok(())
+        }
+        None => Err(wasm_bindgen::JsValue::from_str("Renderer not yet initialized"))
+    }
+}
+
+/// Composite the canvas against a reference image from JavaScript (WASM only)
+/// mode: 0 = Difference, 1 = Overlay
+#[cfg(target_arch = "wasm32")]
+pub async fn compare_with_reference_global(
+    reference: Vec<u8>,
+    width: u32,
+    height: u32,
+    mode: u32,
+) -> Result<js_sys::Uint8ClampedArray, wasm_bindgen::JsValue> {
+    use crate::renderer::ComparisonMode;
+    use wasm_bindgen::JsValue;
+
+    let comparison_mode = match mode {
+        0 => ComparisonMode::Difference,
+        1 => ComparisonMode::Overlay,
+        _ => return Err(JsValue::from_str(&format!("Invalid comparison mode: {}", mode))),
+    };
+
+    match RendererLoan::take() {
+        Some(mut renderer) => {
+            // Include any stroke layer still sharing coverage with the next stroke
+            renderer.commit_stroke_layer();
+            let rgba8_data = renderer.read_comparison_rgba8(&reference, width, height, comparison_mode)
+                .await
+                .map_err(|e| JsValue::from_str(&e))?;
+            if renderer.is_stale() {
+                let message = "Canvas was recreated during readback";
+                report_error(ErrorCode::ReadbackFailed, message);
+                return Err(JsValue::from_str(message));
+            }
+
+            let js_array = js_sys::Uint8ClampedArray::new_with_length(rgba8_data.len() as u32);
+            js_array.copy_from(&rgba8_data);
+
+            log::info!("Rendered {:?} comparison: {} bytes", comparison_mode, rgba8_data.len());
+            Ok(js_array)
+        }
+        None => Err(JsValue::from_str("Renderer not yet initialized"))
+    }
+}
+
+/// Check if canvas needs to be relocated to a new container (WASM only)
+/// This is called on every init_drawing_canvas() to handle Flutter rebuilds
+#[cfg(target_arch = "wasm32")]
+pub fn check_and_relocate_canvas_global() {
+    use wasm_bindgen::JsCast;
+    use winit::platform::web::WindowExtWeb;
+    
+    GLOBAL_APP_WRAPPER.with(|global| {
+        if let Some(wrapper) = borrow_global_wrapper(global) {
+            
+            // Only proceed if we have a window
+            if let Some(window_arc) = &wrapper.window {
+                let canvas = match window_arc.canvas() {
+                    Some(c) => c,
+                    None => {
+                        log::warn!("Failed to get canvas from window");
+                        return;
+                    }
+                };
+                
+                let document = web_sys::window()
+                    .and_then(|win| win.document())
+                    .expect("Failed to get document");
+                
+                // Find the canvas-container that doesn't have a canvas child yet
+                let containers = match document.query_selector_all("[data-canvas-container]") {
+                    Ok(c) => c,
+                    Err(e) => {
+                        log::warn!("Failed to query canvas containers: {:?}", e);
+                        return;
+                    }
+                };
+                
+                log::info!("🔍 Checking {} container(s) for canvas relocation", containers.length());
+                
+                let mut empty_container: Option<web_sys::Element> = None;
+                for i in 0..containers.length() {
+                    if let Some(elem) = containers.get(i) {
+                        if let Ok(html_elem) = elem.dyn_into::<web_sys::HtmlElement>() {
+                            let container_id = html_elem.id();
+                            let has_canvas = html_elem.query_selector("canvas").ok().flatten().is_some();
+                            log::info!("  Container '{}': has_canvas={}", container_id, has_canvas);
+                            
+                            // Check if this container already has a canvas child
+                            if !has_canvas {
+                                empty_container = Some(html_elem.into());
+                                break;
                             }
                         }
                     }
-                    
-                    // If we found a new empty container, move the canvas there
-                    if let Some(new_container) = empty_container {
-                        // Check if canvas is in a different container
-                        if let Some(current_parent) = canvas.parent_element() {
-                            if current_parent.id() != new_container.id() {
-                                log::info!("🔄 Moving canvas from container '{}' to '{}'", 
-                                    current_parent.id(), new_container.id());
-                                
-                                // Move canvas to new container
-                                if let Err(e) = new_container.append_child(&canvas) {
-                                    log::error!("Failed to move canvas to new container: {:?}", e);
-                                    return;
-                                }
-                                
-                                log::info!("✅ Canvas moved to new container");
-                            } else {
-                                log::info!("Canvas already in correct container: {}", new_container.id());
-                            }
-                        } else {
-                            // Canvas has no parent (orphaned), attach to new container
-                            log::info!("🔄 Attaching orphaned canvas to container '{}'", new_container.id());
+                }
+                
+                // If we found a new empty container, move the canvas there
+                if let Some(new_container) = empty_container {
+                    // Check if canvas is in a different container
+                    if let Some(current_parent) = canvas.parent_element() {
+                        if current_parent.id() != new_container.id() {
+                            log::info!("🔄 Moving canvas from container '{}' to '{}'", 
+                                current_parent.id(), new_container.id());
+                            
+                            // Move canvas to new container
                             if let Err(e) = new_container.append_child(&canvas) {
-                                log::error!("Failed to attach canvas to container: {:?}", e);
+                                report_error(ErrorCode::CanvasAttachFailed, &format!("Failed to move canvas to new container: {:?}", e));
                                 return;
                             }
-                            log::info!("✅ Canvas attached to container");
+                            
+                            log::info!("✅ Canvas moved to new container");
+                        } else {
+                            log::info!("Canvas already in correct container: {}", new_container.id());
                         }
                     } else {
-                        log::info!("No empty container found (canvas already placed or no containers available)");
+                        // Canvas has no parent (orphaned), attach to new container
+                        log::info!("🔄 Attaching orphaned canvas to container '{}'", new_container.id());
+                        if let Err(e) = new_container.append_child(&canvas) {
+                            report_error(ErrorCode::CanvasAttachFailed, &format!("Failed to attach canvas to container: {:?}", e));
+                            return;
+                        }
+                        log::info!("✅ Canvas attached to container");
                     }
+                } else {
+                    log::info!("No empty container found (canvas already placed or no containers available)");
                 }
             }
         } else {
@@ -427,8 +3323,26 @@ pub struct AppWrapper {
     pub app: Option<App>,
     cursor_position: Option<winit::dpi::PhysicalPosition<f64>>,
     last_pointer_move_time: f64, // Used for de-duplicating erroneous pointer move events on iOS webkit
+    timestamps: TimestampNormalizer, // Rebases pointer event times onto the session clock
+    input_transform: InputTransform, // Embedder-supplied correction applied to raw input positions
+    touches: Vec<(FingerId, [f32; 2])>, // Fingers currently down, in press order, at their raw positions
+    drawing_touch: Option<FingerId>, // The finger whose stroke the app is drawing
+    touch_gesture: bool, // Two fingers went down: touches pan/zoom until all of them lift
+    canvas_snapshot: Option<crate::CanvasSnapshot>, // Canvas kept while hidden, in case the device is lost
+    #[cfg(target_arch = "wasm32")]
+    last_coalesced_time: f64, // Timestamp of the newest coalesced sample already queued
+    #[cfg(target_arch = "wasm32")]
+    coalesced_listeners: Option<CoalescedListeners>, // Removed from the page with the wrapper
+    #[cfg(target_arch = "wasm32")]
+    keep_square_pixels: bool, // Compensate the view for non-square displayed pixels
+    #[cfg(target_arch = "wasm32")]
+    renderer_on_loan: bool, // An async readback holds the renderer (see RendererLoan)
+    #[cfg(target_arch = "wasm32")]
+    deferred_setters: Vec<Box<dyn FnOnce()>>, // JS setters called during the loan, rerun when it ends
     #[cfg(not(target_arch = "wasm32"))]
     start_time: Option<std::time::Instant>,
+    #[cfg(not(target_arch = "wasm32"))]
+    session_wake: Option<std::time::Instant>, // When a running session needs its next frame
 }
 
 impl AppWrapper {
@@ -440,23 +3354,451 @@ impl AppWrapper {
             app: None,
             cursor_position: None,
             last_pointer_move_time: 0.0,
+            timestamps: TimestampNormalizer::new(),
+            input_transform: InputTransform::IDENTITY,
+            touches: Vec::new(),
+            drawing_touch: None,
+            touch_gesture: false,
+            canvas_snapshot: None,
+            #[cfg(target_arch = "wasm32")]
+            last_coalesced_time: 0.0,
+            #[cfg(target_arch = "wasm32")]
+            coalesced_listeners: None,
+            #[cfg(target_arch = "wasm32")]
+            keep_square_pixels: true,
+            #[cfg(target_arch = "wasm32")]
+            renderer_on_loan: false,
+            #[cfg(target_arch = "wasm32")]
+            deferred_setters: Vec::new(),
             #[cfg(not(target_arch = "wasm32"))]
             start_time: Some(std::time::Instant::now()),
+            #[cfg(not(target_arch = "wasm32"))]
+            session_wake: None,
+        }
+    }
+
+    /// Queue a JS setter that needs the renderer until an async readback returns it (WASM only)
+    ///
+    /// Returns false if the renderer isn't on loan, i.e. it simply isn't initialized yet.
+    #[cfg(target_arch = "wasm32")]
+    fn defer_until_renderer_returns(&mut self, setter: impl FnOnce() + 'static) -> bool {
+        if !self.renderer_on_loan {
+            return false;
+        }
+        log::info!("Renderer is busy with a readback, applying the setting when it returns");
+        self.deferred_setters.push(Box::new(setter));
+        true
+    }
+
+    /// Request a redraw unless rendering is paused
+    fn request_redraw(&self) {
+        let paused = self.app.as_ref().is_some_and(|app| app.is_rendering_paused());
+        if paused {
+            return;
+        }
+        if let Some(window) = &self.window {
+            window.request_redraw();
+        }
+    }
+
+    /// Schedule a redraw for when the running session needs its next frame
+    ///
+    /// The event loop waits for input, so without this a pose would only change (and a
+    /// countdown only tick) when the user happens to draw. Native wakes the loop with
+    /// `ControlFlow::WaitUntil` (see `about_to_wait`); the web uses a `setTimeout`.
+    fn schedule_session_tick(&mut self) {
+        let delay_ms = self.app.as_ref().and_then(|app| app.next_session_tick_ms());
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.session_wake =
+                delay_ms.map(|ms| std::time::Instant::now() + std::time::Duration::from_secs_f64(ms / 1000.0));
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        {
+            let Some(browser) = web_sys::window() else {
+                return;
+            };
+            // Only the soonest tick matters; a frame drawn meanwhile reschedules it
+            if let Some(handle) = SESSION_TIMER.with(|timer| timer.take()) {
+                browser.clear_timeout_with_handle(handle);
+            }
+            let Some(delay_ms) = delay_ms else {
+                return;
+            };
+            let scheduled = SESSION_TICK_CALLBACK.with(|callback| {
+                browser.set_timeout_with_callback_and_timeout_and_arguments_0(callback, delay_ms.ceil() as i32)
+            });
+            match scheduled {
+                Ok(handle) => SESSION_TIMER.with(|timer| timer.set(Some(handle))),
+                Err(e) => log::warn!("Failed to schedule the session's next frame: {:?}", e),
+            }
+        }
+    }
+
+    /// Measure how the page displays the canvas's pixels and keep them square (WASM only)
+    ///
+    /// A non-uniform container scale stretches the canvas element, so round dabs would
+    /// show up as ellipses. The mismatch is always logged; when `keep_square_pixels` is on
+    /// the view transform compensates for it.
+    #[cfg(target_arch = "wasm32")]
+    fn update_pixel_aspect(&mut self) {
+        use winit::platform::web::WindowExtWeb;
+
+        let Some(canvas) = self.window.as_ref().and_then(|window| window.canvas()) else {
+            return;
+        };
+        let dpr = web_sys::window().map(|w| w.device_pixel_ratio()).unwrap_or(1.0);
+        let rect = canvas.get_bounding_client_rect();
+        let scale = crate::dpi::physical_scale([canvas.width(), canvas.height()], [rect.width(), rect.height()], dpr);
+        // Coalesced samples share this measurement until the next resize or scroll
+        COALESCED_SCALE.with(|cached| cached.set(Some(scale)));
+        let measured = crate::dpi::pixel_aspect(scale);
+        let is_square = crate::dpi::is_square_pixel_aspect(measured);
+
+        if !is_square {
+            log::warn!(
+                "⚠️ Canvas pixels are displayed non-square (aspect {:.3}, {}x{} px shown at {:.1}x{:.1} CSS px); {}",
+                measured, canvas.width(), canvas.height(), rect.width(), rect.height(),
+                if self.keep_square_pixels { "compensating" } else { "dabs will look stretched" }
+            );
+        }
+
+        let aspect = if self.keep_square_pixels && !is_square { measured as f32 } else { 1.0 };
+        if let Some(renderer) = &mut self.renderer {
+            let mut view = renderer.view_transform();
+            if view.pixel_aspect != aspect {
+                view.pixel_aspect = aspect;
+                renderer.set_view_transform(view);
+            }
+        }
+    }
+
+    /// Convert a surface position from winit to canvas space through the view transform
+    fn to_canvas_position(&self, position: winit::dpi::PhysicalPosition<f64>) -> [f32; 2] {
+        self.surface_to_canvas([position.x as f32, position.y as f32])
+    }
+
+    /// Convert a surface-space point to canvas space through the input and view transforms
+    fn surface_to_canvas(&self, surface_pos: [f32; 2]) -> [f32; 2] {
+        let surface_pos = self.input_transform.apply(surface_pos);
+        match &self.renderer {
+            Some(renderer) => renderer.surface_to_canvas(surface_pos),
+            None => surface_pos,
+        }
+    }
+
+    /// Check whether a lone finger draws (in pen-only mode touches only pan and zoom)
+    fn touch_draws(&self) -> bool {
+        use crate::brush::InputFilterMode;
+
+        self.app.as_ref().is_none_or(|app| app.brush_state().params.input_filter_mode != InputFilterMode::PenOnly)
+    }
+
+    /// Surface positions of the two fingers driving a pan/zoom gesture (the first two down)
+    fn touch_pair(&self) -> Option<[[f32; 2]; 2]> {
+        match self.touches.as_slice() {
+            [first, second, ..] => Some([self.input_transform.apply(first.1), self.input_transform.apply(second.1)]),
+            _ => None,
+        }
+    }
+
+    /// Track a finger going down or lifting
+    ///
+    /// A lone finger draws. When a second finger goes down the touches become a pan/zoom
+    /// gesture: the first finger's stroke ends where it is, and no finger draws again until
+    /// all of them have lifted, so lifting one finger mid-gesture leaves the view in place.
+    ///
+    /// # Returns
+    /// Whether the event belongs to the finger that is drawing and goes on to the app
+    fn track_touch_button(&mut self, finger_id: FingerId, position: [f32; 2], pressed: bool, time_stamp: f64) -> bool {
+        if !pressed {
+            self.touches.retain(|(id, _)| *id != finger_id);
+            if self.touches.is_empty() {
+                self.touch_gesture = false;
+            }
+            let was_drawing = self.drawing_touch == Some(finger_id);
+            if was_drawing {
+                self.drawing_touch = None;
+            }
+            return was_drawing;
+        }
+
+        self.touches.retain(|(id, _)| *id != finger_id);
+        self.touches.push((finger_id, position));
+        if self.touches.len() == 1 {
+            if self.touch_draws() {
+                self.drawing_touch = Some(finger_id);
+            }
+            return self.drawing_touch.is_some();
+        }
+
+        if !self.touch_gesture {
+            log::debug!("Two-finger gesture started");
+            self.touch_gesture = true;
+            self.end_touch_stroke(time_stamp);
+        }
+        false
+    }
+
+    /// Track a finger moving, panning and zooming the view while a gesture is in progress
+    ///
+    /// Each move applies only the change since the previous one, so the view never jumps
+    /// when the pair of fingers driving the gesture changes.
+    ///
+    /// # Returns
+    /// Whether the event belongs to the finger that is drawing and goes on to the app
+    fn track_touch_move(&mut self, finger_id: FingerId, position: [f32; 2]) -> bool {
+        let from = self.touch_pair();
+        let Some(touch) = self.touches.iter_mut().find(|(id, _)| *id == finger_id) else {
+            return false;
+        };
+        touch.1 = position;
+
+        if self.touch_gesture {
+            if let (Some(from), Some(to)) = (from, self.touch_pair()) {
+                if from != to {
+                    if let (Some(app), Some(renderer)) = (&mut self.app, &mut self.renderer) {
+                        app.pinch_view(from, to, renderer);
+                    }
+                    self.request_redraw();
+                }
+            }
+            return false;
+        }
+        self.drawing_touch == Some(finger_id)
+    }
+
+    /// End the drawing finger's stroke at its last position (when it turns into a gesture
+    /// or the touch is cancelled). The stroke so far is kept; undo removes it if unwanted.
+    fn end_touch_stroke(&mut self, time_stamp: f64) {
+        let Some(finger_id) = self.drawing_touch.take() else {
+            return;
+        };
+        let Some(&(_, position)) = self.touches.iter().find(|(id, _)| *id == finger_id) else {
+            return;
+        };
+        let event = PointerEvent {
+            position: self.surface_to_canvas(position),
+            pressure: 1.0,
+            tilt: None,
+            azimuth: None,
+            twist: None,
+            timestamp: time_stamp,
+            event_type: PointerEventType::Up,
+            source: PointerEventSource::Touch,
+        };
+        if let Some(app) = &mut self.app {
+            app.queue_input_event(event);
+        }
+        self.request_redraw();
+    }
+
+    /// Listen for raw DOM pointer moves and buffer their coalesced sub-frame samples (WASM only)
+    ///
+    /// Browsers deliver at most one pointermove per animation frame and fold the rest into
+    /// `getCoalescedEvents()`. The listener is registered on the canvas in the capture phase so
+    /// it runs before winit's own handler, which means the samples for a DOM event are buffered
+    /// by the time winit emits the matching `PointerMoved`.
+    ///
+    /// Only the pointer that is drawing is buffered (the one pressed last, or the primary
+    /// pointer while none is held), so a second finger or a hovering mouse can't slip its
+    /// samples into the stroke.
+    ///
+    /// Returns the listeners, which stay on the page until they are dropped (None if the
+    /// pointer move listener couldn't be added).
+    #[cfg(target_arch = "wasm32")]
+    fn setup_coalesced_pointer_listener(canvas: &web_sys::HtmlCanvasElement) -> Option<CoalescedListeners> {
+        use wasm_bindgen::prelude::*;
+        use wasm_bindgen::JsCast;
+
+        let options = web_sys::AddEventListenerOptions::new();
+        options.set_capture(true);
+        options.set_passive(true);
+
+        let listener_canvas = canvas.clone();
+        let callback = Closure::<dyn Fn(web_sys::PointerEvent)>::new(move |event: web_sys::PointerEvent| {
+            if !COALESCED_EVENTS_ENABLED.with(Cell::get) {
+                return;
+            }
+            let pointer_id = event.pointer_id();
+            let is_active = match COALESCED_POINTER_ID.with(Cell::get) {
+                Some(active) => pointer_id == active,
+                None => event.is_primary(),
+            };
+            if !is_active {
+                return;
+            }
+
+            // Use the exact backing-store/CSS ratio rather than devicePixelRatio: at fractional
+            // scales the backing store is rounded to whole pixels, so the two differ slightly.
+            // Measuring forces a layout, so it's cached until the canvas is resized or scrolled.
+            let scale = COALESCED_SCALE.with(Cell::get).unwrap_or_else(|| {
+                let dpr = web_sys::window().map(|w| w.device_pixel_ratio()).unwrap_or(1.0);
+                let rect = listener_canvas.get_bounding_client_rect();
+                let scale = crate::dpi::physical_scale(
+                    [listener_canvas.width(), listener_canvas.height()],
+                    [rect.width(), rect.height()],
+                    dpr,
+                );
+                COALESCED_SCALE.with(|cached| cached.set(Some(scale)));
+                scale
+            });
+
+            // offsetX/offsetY are doubles in the DOM spec, but web-sys exposes them as i32.
+            // Read them directly to keep sub-pixel precision.
+            let to_sample = |e: &web_sys::PointerEvent| {
+                let offset = |name: &str| {
+                    js_sys::Reflect::get(e, &JsValue::from_str(name))
+                        .ok()
+                        .and_then(|v| v.as_f64())
+                        .unwrap_or(0.0)
+                };
+                CoalescedSample {
+                    position: crate::dpi::logical_to_physical_position([offset("offsetX"), offset("offsetY")], scale),
+                    pressure: e.pressure(),
+                    timestamp: e.time_stamp(),
+                }
+            };
+
+            let has_coalesced = js_sys::Reflect::has(&event, &JsValue::from_str("getCoalescedEvents"))
+                .unwrap_or(false);
+
+            COALESCED_SAMPLES.with(|samples| {
+                let mut samples = samples.borrow_mut();
+                let coalesced = if has_coalesced {
+                    event.get_coalesced_events()
+                } else {
+                    js_sys::Array::new()
+                };
+
+                if coalesced.length() == 0 {
+                    // Unsupported (or empty): fall back to the event itself
+                    samples.push(to_sample(&event));
+                } else {
+                    for e in coalesced.iter() {
+                        if let Ok(e) = e.dyn_into::<web_sys::PointerEvent>() {
+                            if e.pointer_id() == pointer_id {
+                                samples.push(to_sample(&e));
+                            }
+                        }
+                    }
+                }
+
+                if samples.len() > MAX_COALESCED_SAMPLES {
+                    let excess = samples.len() - MAX_COALESCED_SAMPLES;
+                    samples.drain(..excess);
+                }
+            });
+        });
+
+        if let Err(e) = canvas.add_event_listener_with_callback_and_add_event_listener_options(
+            "pointermove",
+            callback.as_ref().unchecked_ref(),
+            &options,
+        ) {
+            log::warn!("Failed to add coalesced pointer listener: {:?}", e);
+            return None;
+        }
+        let canvas_target: &web_sys::EventTarget = canvas.as_ref();
+        let mut registered = vec![(canvas_target.clone(), "pointermove", callback.as_ref().unchecked_ref::<js_sys::Function>().clone())];
+
+        // Follow the pointer that is pressed, dropping samples buffered from any other one
+        let down_callback = Closure::<dyn Fn(web_sys::PointerEvent)>::new(|event: web_sys::PointerEvent| {
+            let pointer_id = event.pointer_id();
+            if COALESCED_POINTER_ID.with(|active| active.replace(Some(pointer_id))) != Some(pointer_id) {
+                Self::take_coalesced_samples();
+            }
+        });
+        let up_callback = Closure::<dyn Fn(web_sys::PointerEvent)>::new(|event: web_sys::PointerEvent| {
+            COALESCED_POINTER_ID.with(|active| {
+                if active.get() == Some(event.pointer_id()) {
+                    active.set(None);
+                }
+            });
+        });
+        let scroll_callback = Closure::<dyn Fn()>::new(|| COALESCED_SCALE.with(|scale| scale.set(None)));
+        let listeners = [
+            ("pointerdown", down_callback.as_ref()),
+            ("pointerup", up_callback.as_ref()),
+            ("pointercancel", up_callback.as_ref()),
+        ];
+        for (name, listener) in listeners {
+            match canvas.add_event_listener_with_callback_and_add_event_listener_options(
+                name,
+                listener.unchecked_ref(),
+                &options,
+            ) {
+                Ok(()) => registered.push((canvas_target.clone(), name, listener.unchecked_ref::<js_sys::Function>().clone())),
+                Err(e) => log::warn!("Failed to add {} listener: {:?}", name, e),
+            }
         }
+        // Scrolling any ancestor can move the canvas; capture sees scrolls that don't bubble
+        if let Some(window) = web_sys::window() {
+            let scroll_listener: &js_sys::Function = scroll_callback.as_ref().unchecked_ref();
+            match window.add_event_listener_with_callback_and_add_event_listener_options("scroll", scroll_listener, &options) {
+                Ok(()) => registered.push((window.into(), "scroll", scroll_listener.clone())),
+                Err(e) => log::warn!("Failed to add scroll listener: {:?}", e),
+            }
+        }
+
+        log::info!("✅ Coalesced pointer listener set up on canvas");
+
+        Some(CoalescedListeners {
+            registered,
+            _pointer_callbacks: [callback, down_callback, up_callback],
+            _scroll_callback: scroll_callback,
+        })
+    }
+
+    /// Take all buffered coalesced samples (WASM only)
+    #[cfg(target_arch = "wasm32")]
+    fn take_coalesced_samples() -> Vec<CoalescedSample> {
+        COALESCED_SAMPLES.with(|samples| std::mem::take(&mut *samples.borrow_mut()))
+    }
+
+    /// Expand a pointer move into one event per coalesced sample (WASM only)
+    /// Position, pressure (for styluses), and timestamp come from each sample;
+    /// everything else is shared with the winit event.
+    #[cfg(target_arch = "wasm32")]
+    fn expand_coalesced(event: PointerEvent, samples: &[CoalescedSample]) -> Vec<PointerEvent> {
+        if samples.is_empty() {
+            return vec![event];
+        }
+        samples
+            .iter()
+            .map(|sample| PointerEvent {
+                position: sample.position,
+                pressure: if event.source == PointerEventSource::TabletTool {
+                    sample.pressure
+                } else {
+                    event.pressure
+                },
+                timestamp: sample.timestamp,
+                ..event.clone()
+            })
+            .collect()
     }
 
     /// Extract pressure from Force enum
+    /// Tablets that report no force (or a calibrated maximum of zero) draw at full pressure
     fn extract_pressure(force: &Option<Force>) -> f32 {
-        match force {
-            Some(Force::Normalized(p)) => *p as f32,
-            Some(Force::Calibrated { force, max_possible_force, .. }) => {
-                (force / max_possible_force) as f32
+        let fraction = match force {
+            Some(Force::Normalized(p)) => Some(*p),
+            Some(Force::Calibrated { force, max_possible_force, .. }) if *max_possible_force > 0.0 => {
+                Some(force / max_possible_force)
             }
-            None => 1.0,
-        }
+            _ => None,
+        };
+        crate::input::pressure_from_force(fraction)
     }
 
     /// Extract tablet tool data (pressure, tilt, azimuth, twist) from TabletToolData
+    ///
+    /// Used on every target: winit reports Wacom and other tablets as `TabletTool` on
+    /// desktop too. Readings a platform doesn't report are left out (pressure falls back
+    /// to full), and tablets a platform only exposes as a mouse draw like one.
     fn extract_tablet_data(data: &winit::event::TabletToolData) -> (f32, Option<[f32; 2]>, Option<f32>, Option<f32>) {
         let pressure = Self::extract_pressure(&data.force);
         
@@ -521,27 +3863,105 @@ impl AppWrapper {
         }
     }
 
+    /// Read the exact device-pixel content size from a ResizeObserver entry (WASM only)
+    /// Returns None where `devicePixelContentBoxSize` is unsupported (e.g. Safari)
+    #[cfg(target_arch = "wasm32")]
+    fn device_pixel_content_size(entry: &web_sys::ResizeObserverEntry) -> Option<[u32; 2]> {
+        use wasm_bindgen::JsCast;
+        use wasm_bindgen::JsValue;
+
+        let sizes = js_sys::Reflect::get(entry, &JsValue::from_str("devicePixelContentBoxSize")).ok()?;
+        let sizes = sizes.dyn_into::<js_sys::Array>().ok()?;
+        let size = sizes.get(0).dyn_into::<web_sys::ResizeObserverSize>().ok()?;
+        Some([size.inline_size().round() as u32, size.block_size().round() as u32])
+    }
+
     /// Set up a ResizeObserver to watch the container and resize the canvas accordingly
+    ///
+    /// Resizes are debounced: one arriving after a quiet period applies immediately, and
+    /// any that follow within `RESIZE_DEBOUNCE_MS` of each other are coalesced into the
+    /// last, applied once the container stops changing. Sizes equal to the last one
+    /// applied are skipped.
     #[cfg(target_arch = "wasm32")]
     fn setup_resize_observer(container: &web_sys::Element, window: std::sync::Arc<Box<dyn Window>>) {
+        use std::cell::Cell;
+        use std::rc::Rc;
         use wasm_bindgen::prelude::*;
         use wasm_bindgen::JsCast;
 
-        let window_clone = window.clone();
+        // Last size requested from the window, the latest size waiting out the quiet
+        // period, and the running quiet period timer
+        let applied_size = Rc::new(Cell::new(None::<[u32; 2]>));
+        let pending_size = Rc::new(Cell::new(None::<[u32; 2]>));
+        let debounce_timer = Rc::new(Cell::new(None::<i32>));
+
+        let apply_size = Rc::new(move |[width, height]: [u32; 2]| {
+            if applied_size.get() == Some([width, height]) {
+                log::debug!("Container size unchanged at {}x{}, skipping resize", width, height);
+                return;
+            }
+            applied_size.set(Some([width, height]));
+            let _ = window.request_surface_size(winit::dpi::PhysicalSize::new(width, height).into());
+        });
+
+        // Fires once the container has held its size for the quiet period
+        let on_quiet = {
+            let apply_size = apply_size.clone();
+            let pending_size = pending_size.clone();
+            let debounce_timer = debounce_timer.clone();
+            Closure::<dyn Fn()>::new(move || {
+                debounce_timer.set(None);
+                if let Some(size) = pending_size.take() {
+                    apply_size(size);
+                }
+            })
+        };
+        let on_quiet_fn: js_sys::Function = on_quiet.as_ref().unchecked_ref::<js_sys::Function>().clone();
+        // Lives as long as the observer (i.e. the page), like the observer callback below
+        on_quiet.forget();
 
         let callback = Closure::<dyn Fn(js_sys::Array)>::new(move |entries: js_sys::Array| {
             // Get the first entry (our container)
             if let Some(entry) = entries.get(0).dyn_into::<web_sys::ResizeObserverEntry>().ok() {
                 let content_rect = entry.content_rect();
-                let width = content_rect.width() as u32;
-                let height = content_rect.height() as u32;
-                
-                log::info!("📐 Container resized to: {}x{}", width, height);
-                
+                let logical = [content_rect.width(), content_rect.height()];
+                let scale_factor = web_sys::window().map(|w| w.device_pixel_ratio()).unwrap_or(1.0);
+
+                // Prefer the browser's exact device-pixel size; otherwise round the fractional
+                // CSS size (truncating to whole CSS pixels first loses up to a pixel at 150%)
+                let [width, height] = Self::device_pixel_content_size(&entry)
+                    .unwrap_or_else(|| crate::dpi::logical_to_physical_size(logical, scale_factor));
+
+                log::info!(
+                    "📐 Container resized to: {:.2}x{:.2} CSS px @ {}x = {}x{} physical px",
+                    logical[0], logical[1], scale_factor, width, height
+                );
+
                 // Request the window to resize to match the container
                 if width > 0 && height > 0 {
-                    let new_size = winit::dpi::LogicalSize::new(width, height);
-                    let _ = window_clone.request_surface_size(new_size.into());
+                    let Some(browser) = web_sys::window() else {
+                        apply_size([width, height]);
+                        return;
+                    };
+                    match debounce_timer.take() {
+                        // First resize after a quiet period: apply right away
+                        None => apply_size([width, height]),
+                        // Mid-burst: hold this size until the burst ends
+                        Some(handle) => {
+                            browser.clear_timeout_with_handle(handle);
+                            pending_size.set(Some([width, height]));
+                        }
+                    }
+                    // (Re)start the quiet period
+                    match browser.set_timeout_with_callback_and_timeout_and_arguments_0(&on_quiet_fn, RESIZE_DEBOUNCE_MS) {
+                        Ok(handle) => debounce_timer.set(Some(handle)),
+                        Err(_) => {
+                            // No timer: apply anything held back now rather than lose it
+                            if let Some(size) = pending_size.take() {
+                                apply_size(size);
+                            }
+                        }
+                    }
                 }
             }
         });
@@ -564,13 +3984,19 @@ impl AppWrapper {
         {
             // WASM: Initialize asynchronously
             let window_for_renderer = window.clone();
-            let app_ptr = &mut self.app as *mut Option<App>;
-            let renderer_ptr = &mut self.renderer as *mut Option<Renderer>;
             let window_for_redraw = window.clone();
+            let generation = bump_global_app_generation();
 
             wasm_bindgen_futures::spawn_local(async move {
                 debug::update_status("Creating renderer...");
                 let mut renderer = Renderer::new(window_for_renderer, initial_size).await;
+
+                // A newer init started while this one was waiting (e.g. Flutter tore down and
+                // recreated the canvas quickly): let it win instead of overwriting it
+                if global_app_generation() != generation {
+                    log::warn!("Discarding renderer from a superseded initialization");
+                    return;
+                }
                 
                 // Create app with global brush params (persists across reinit)
                 let brush_params = get_global_brush_params();
@@ -581,9 +4007,18 @@ impl AppWrapper {
                 // Clear canvas to initial color
                 app.clear_canvas(&mut renderer);
 
-                unsafe {
-                    *renderer_ptr = Some(renderer);
-                    *app_ptr = Some(app);
+                // Stored through the global: the event loop only lends the wrapper per event
+                let stored = GLOBAL_APP_WRAPPER.with(|global| match borrow_global_wrapper(global) {
+                    Some(mut wrapper) => {
+                        wrapper.renderer = Some(renderer);
+                        wrapper.app = Some(app);
+                        true
+                    }
+                    None => false,
+                });
+                if !stored {
+                    log::error!("Global app wrapper unavailable, renderer not stored");
+                    return;
                 }
 
                 log::info!("✅ Renderer initialized successfully with persisted brush settings");
@@ -617,10 +4052,112 @@ impl AppWrapper {
     }
 }
 
+impl AppWrapper {
+    /// Replace a renderer that can't recover in place (see `Renderer::needs_rebuild`)
+    ///
+    /// The app and its settings, brush textures, and reference image carry over to the new
+    /// renderer. The canvas is read back from the old one if its device still works; after
+    /// device loss it comes from the snapshot taken when rendering was last paused (on the
+    /// web), or starts blank.
+    fn rebuild_renderer(&mut self) {
+        let (Some(window), Some(mut old), Some(app)) = (self.window.clone(), self.renderer.take(), &self.app) else {
+            return;
+        };
+        let device_lost = old.is_device_lost();
+        log::warn!("Rebuilding renderer ({})", if device_lost { "device lost" } else { "surface lost" });
+        let config = app.config(&old);
+        let images = old.take_uploaded_images();
+        let saved_snapshot = self.canvas_snapshot.take();
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let snapshot = if device_lost {
+                saved_snapshot
+            } else {
+                old.commit_stroke_layer();
+                pollster::block_on(old.snapshot_canvas())
+                    .map_err(|e| log::warn!("Could not snapshot the canvas before rebuilding: {}", e))
+                    .ok()
+            };
+            // The old surface has to go before a new one is made for the window
+            drop(old);
+            let mut renderer = pollster::block_on(Renderer::new(window.clone(), window.surface_size()));
+            if let Some(app) = &mut self.app {
+                restore_rebuilt_renderer(app, &mut renderer, &config, &images, snapshot.as_ref());
+            }
+            self.renderer = Some(renderer);
+            self.request_redraw();
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        {
+            // Outstanding renderer loans belong to the old renderer
+            let generation = bump_global_app_generation();
+            wasm_bindgen_futures::spawn_local(async move {
+                let snapshot = if device_lost {
+                    saved_snapshot
+                } else {
+                    old.commit_stroke_layer();
+                    old.snapshot_canvas()
+                        .await
+                        .map_err(|e| log::warn!("Could not snapshot the canvas before rebuilding: {}", e))
+                        .ok()
+                };
+                // The old surface has to go before a new one is made for the canvas
+                drop(old);
+                let mut renderer = Renderer::new(window.clone(), window.surface_size()).await;
+                if global_app_generation() != generation {
+                    log::warn!("Discarding rebuilt renderer from a superseded initialization");
+                    return;
+                }
+                GLOBAL_APP_WRAPPER.with(|global| match borrow_global_wrapper(global) {
+                    Some(mut wrapper) => {
+                        let wrapper = &mut *wrapper;
+                        if let Some(app) = &mut wrapper.app {
+                            restore_rebuilt_renderer(app, &mut renderer, &config, &images, snapshot.as_ref());
+                        }
+                        wrapper.renderer = Some(renderer);
+                        wrapper.request_redraw();
+                    }
+                    None => log::error!("Global app wrapper unavailable, rebuilt renderer not stored"),
+                });
+            });
+        }
+    }
+}
+
+/// Bring a rebuilt renderer back to the old one's state: the app's config (which sizes
+/// the canvas like the old one), the uploaded images, then the canvas
+fn restore_rebuilt_renderer(
+    app: &mut App,
+    renderer: &mut Renderer,
+    config: &crate::AppConfig,
+    images: &crate::UploadedImages,
+    snapshot: Option<&crate::CanvasSnapshot>,
+) {
+    if let Err(e) = app.apply_config(config, renderer) {
+        log::warn!("Could not reapply settings to the rebuilt renderer: {}", e);
+    }
+    if let Err(e) = renderer.restore_uploaded_images(images) {
+        log::warn!("Could not upload the brush and reference images to the rebuilt renderer: {}", e);
+    }
+    match snapshot.map(|snapshot| renderer.restore_canvas(snapshot)) {
+        Some(Ok(())) => log::info!("✅ Renderer rebuilt, canvas restored"),
+        result => {
+            if let Some(Err(e)) = result {
+                log::warn!("Could not restore the canvas snapshot: {}", e);
+            }
+            // The pixels are gone, and with them what the history and retained strokes describe
+            app.clear_canvas(renderer);
+            log::warn!("Renderer rebuilt, canvas could not be restored and was cleared");
+        }
+    }
+}
+
 impl ApplicationHandler for AppWrapper {
     fn can_create_surfaces(&mut self, event_loop: &dyn ActiveEventLoop) {
         debug::update_stage("Creating window...");
-        let initial_size = winit::dpi::PhysicalSize::new(800, 600);
+        let initial_size = initial_canvas_size();
         
         // On WASM, we need to check if we should move the canvas to a new container
         // This handles layout changes where Flutter destroys the old container
@@ -759,6 +4296,12 @@ impl ApplicationHandler for AppWrapper {
                 let window_for_resize = window_arc.clone();
                 Self::setup_resize_observer(&container, window_for_resize);
 
+                // Capture sub-frame pointer samples for smoother high-speed strokes
+                // (replacing the listeners of any earlier canvas removes them)
+                let canvas = window_arc.canvas().expect("Failed to get canvas from window");
+                self.coalesced_listeners = Self::setup_coalesced_pointer_listener(&canvas);
+                drop(canvas);
+
                 // Initialize renderer async
                 log::info!("🔧 Initializing renderer with size: {:?}", initial_size);
             }
@@ -771,10 +4314,31 @@ impl ApplicationHandler for AppWrapper {
         log::info!("Application resumed");
     }
 
+    // Wake for a running session's next frame, otherwise wait for input (native only; the
+    // web schedules session frames with setTimeout)
+    #[cfg(not(target_arch = "wasm32"))]
+    fn about_to_wait(&mut self, event_loop: &dyn ActiveEventLoop) {
+        use winit::event_loop::ControlFlow;
+
+        match self.session_wake {
+            Some(wake) if std::time::Instant::now() >= wake => {
+                self.session_wake = None;
+                self.request_redraw();
+                event_loop.set_control_flow(ControlFlow::Wait);
+            }
+            Some(wake) => event_loop.set_control_flow(ControlFlow::WaitUntil(wake)),
+            None => event_loop.set_control_flow(ControlFlow::Wait),
+        }
+    }
+
     fn window_event(&mut self, event_loop: &dyn ActiveEventLoop, _id: WindowId, event: WindowEvent) {
         match event {
             WindowEvent::CloseRequested => {
                 log::info!("Close requested, exiting");
+                #[cfg(target_arch = "wasm32")]
+                {
+                    self.coalesced_listeners = None;
+                }
                 event_loop.exit();
             }
             WindowEvent::SurfaceResized(physical_size) => {
@@ -801,24 +4365,88 @@ impl ApplicationHandler for AppWrapper {
                     return;
                 }
 
+                if let Some(window) = &self.window {
+                    let scale_factor = window.scale_factor();
+                    let logical = physical_size.to_logical::<f64>(scale_factor);
+                    log::info!(
+                        "  Scale factor {}: {:.2}x{:.2} logical = {}x{} physical",
+                        scale_factor, logical.width, logical.height, physical_size.width, physical_size.height
+                    );
+                }
+
                 if let Some(renderer) = &mut self.renderer {
                     renderer.resize(physical_size);
                     log::info!("✅ Surface configured with size: {:?}", physical_size);
                     debug::update_status(&format!("Surface: {}x{}", physical_size.width, physical_size.height));
                 }
+
+                #[cfg(target_arch = "wasm32")]
+                self.update_pixel_aspect();
             }
             WindowEvent::RedrawRequested => {
+                if self.renderer.as_ref().is_some_and(Renderer::needs_rebuild) {
+                    self.rebuild_renderer();
+                    return;
+                }
                 // Render if we have valid components (renderer will check surface validity)
                 if let (Some(renderer), Some(app)) = (&mut self.renderer, &mut self.app) {
+                    if app.is_rendering_paused() {
+                        return;
+                    }
+                    // Back from hidden with the device intact: the snapshot isn't needed
+                    if self.canvas_snapshot.take().is_some() {
+                        log::info!("Device survived while hidden, dropping canvas snapshot");
+                    }
                     app.render(renderer);
                     debug::increment_frame_count();
+                    // Sampling waits on the GPU: blocking on native, in the background on the web
+                    if let Some([x, y]) = app.take_eyedropper_request() {
+                        #[cfg(not(target_arch = "wasm32"))]
+                        if let Err(e) = pollster::block_on(app.pick_color(x, y, renderer)) {
+                            log::warn!("Eyedropper pick failed: {}", e);
+                        }
+                        #[cfg(target_arch = "wasm32")]
+                        wasm_bindgen_futures::spawn_local(async move {
+                            if let Err(e) = pick_color_global(x, y).await {
+                                log::warn!("Eyedropper pick failed: {:?}", e);
+                            }
+                        });
+                    }
                     // Don't request another redraw - we're in Wait mode, only redraw on events
+                    // (synthetic strokes play back over time, so keep frames coming until done)
+                    // A shown prediction needs another frame to be hidden once the pen goes quiet,
+                    // and a held airbrush deposits every frame until the pen lifts
+                    if app.is_playing_synthetic_input() || renderer.has_prediction() || app.is_airbrushing() {
+                        self.request_redraw();
+                    }
+                    self.schedule_session_tick();
                 }
             }
             WindowEvent::PointerButton { button, state, primary, position, time_stamp, .. } => {
+                let time_stamp = self.timestamps.normalize(time_stamp, crate::time::now_ms());
+
+                // Stylus barrel button: handled by the app's barrel action, never starts a stroke
+                if let winit::event::ButtonSource::TabletTool { button: winit::event::TabletToolButton::Barrel, .. } = button {
+                    if let Some(app) = &mut self.app {
+                        app.handle_barrel_button(state == ElementState::Pressed);
+                    }
+                    // A barrel undo runs in the next frame
+                    self.request_redraw();
+                    return;
+                }
+
                 // Handle pointer button press/release (mouse, stylus, touch)
                 // Respond to primary button (left click, stylus tip) or any touch input
                 let is_touch = matches!(button, winit::event::ButtonSource::Touch { .. });
+
+                // Touches go to the brush only from the finger that is drawing
+                if let winit::event::ButtonSource::Touch { finger_id, .. } = &button {
+                    let raw_position = [position.x as f32, position.y as f32];
+                    if !self.track_touch_button(*finger_id, raw_position, state == ElementState::Pressed, time_stamp) {
+                        return;
+                    }
+                }
+
                 let should_handle = primary || is_touch;
                 
                 if should_handle {
@@ -839,7 +4467,7 @@ impl ApplicationHandler for AppWrapper {
                     ) = Self::extract_button_data(&button);
                     
                     let event = PointerEvent {
-                        position: [event_pos.x as f32, event_pos.y as f32],
+                        position: self.to_canvas_position(event_pos),
                         pressure,
                         tilt,
                         azimuth,
@@ -852,20 +4480,31 @@ impl ApplicationHandler for AppWrapper {
                         source: event_src,
                     };
 
+                    // A lifted finger isn't hovering; a mouse or pen still is, right here
+                    let hover = (!is_touch).then_some(event.position);
+
                     if let Some(app) = &mut self.app {
                         app.queue_input_event(event);
+                        app.set_hover_position(hover);
                         let input_type = if is_touch { "touch" } else { "pointer" };
                         log::debug!("{} button {:?} at ({}, {}), pressure={}", 
                             input_type, state, event_pos.x, event_pos.y, pressure);
                     }
 
                     // Request redraw to process the input
-                    if let Some(window) = &self.window {
-                        window.request_redraw();
-                    }
+                    self.request_redraw();
                 }
             }
             WindowEvent::PointerMoved { source, position, time_stamp, .. } => {
+                let time_stamp = self.timestamps.normalize(time_stamp, crate::time::now_ms());
+
+                // Checked before de-duplication: fingers moving together share timestamps
+                if let winit::event::PointerSource::Touch { finger_id, .. } = &source {
+                    if !self.track_touch_move(*finger_id, [position.x as f32, position.y as f32]) {
+                        return;
+                    }
+                }
+
                 if time_stamp <= self.last_pointer_move_time {
                     // Duplicate or out-of-order event, ignore
                     return;
@@ -901,9 +4540,47 @@ impl ApplicationHandler for AppWrapper {
                 );
                 
                 // Handle pointer movement
+                let canvas_position = self.to_canvas_position(position);
+
+                // On web, pick up the sub-frame samples the browser coalesced into this move.
+                // Samples older than ones already queued belong to an earlier PointerMoved, and
+                // out-of-order ones are dropped so timestamps stay monotonic for the brush.
+                #[cfg(target_arch = "wasm32")]
+                let coalesced: Vec<CoalescedSample> = if COALESCED_EVENTS_ENABLED.with(Cell::get) {
+                    let last_time = self.last_coalesced_time;
+                    let mut newest = last_time;
+                    let samples: Vec<CoalescedSample> = Self::take_coalesced_samples()
+                        .into_iter()
+                        .map(|sample| CoalescedSample {
+                            timestamp: self.timestamps.rebase(sample.timestamp),
+                            ..sample
+                        })
+                        .filter(|sample| {
+                            let in_order = sample.timestamp > last_time && sample.timestamp >= newest;
+                            if in_order {
+                                newest = sample.timestamp;
+                            }
+                            in_order
+                        })
+                        .map(|sample| CoalescedSample {
+                            position: self.surface_to_canvas(sample.position),
+                            ..sample
+                        })
+                        .collect();
+                    if let Some(last) = samples.last() {
+                        self.last_coalesced_time = last.timestamp;
+                    } else if time_stamp <= self.last_coalesced_time {
+                        // Already covered by coalesced samples from a previous move
+                        return;
+                    }
+                    samples
+                } else {
+                    Vec::new()
+                };
+
                 if let Some(app) = &mut self.app {
                     let event = PointerEvent {
-                        position: [position.x as f32, position.y as f32],
+                        position: canvas_position,
                         pressure,
                         tilt,
                         azimuth,
@@ -913,13 +4590,43 @@ impl ApplicationHandler for AppWrapper {
                         source: event_src,
                     };
 
-                    app.queue_input_event(event);
+                    #[cfg(target_arch = "wasm32")]
+                    let events = Self::expand_coalesced(event, &coalesced);
+                    #[cfg(not(target_arch = "wasm32"))]
+                    let events = vec![event];
 
-                    // Only request redraw if we have pending input (drawing)
-                    if app.has_pending_input() {
-                        if let Some(window) = &self.window {
-                            window.request_redraw();
-                        }
+                    for event in events {
+                        app.queue_input_event(event);
+                    }
+
+                    // Hovering (no button down) moves the brush outline instead
+                    let hover = (event_src != PointerEventSource::Touch).then_some(canvas_position);
+                    let outline_moved = app.set_hover_position(hover);
+
+                    // Only request redraw if we have pending input (drawing) or the outline moved
+                    if app.has_pending_input() || outline_moved {
+                        self.request_redraw();
+                    }
+                }
+            }
+            WindowEvent::PointerLeft { kind: winit::event::PointerKind::Touch(finger_id), .. } => {
+                // A lifted finger was already dropped; one still tracked here was cancelled
+                // (e.g. the system took over the touch) and never lifts
+                if self.touches.iter().any(|(id, _)| *id == finger_id) {
+                    if self.drawing_touch == Some(finger_id) {
+                        self.end_touch_stroke(self.last_pointer_move_time);
+                    }
+                    self.touches.retain(|(id, _)| *id != finger_id);
+                    if self.touches.is_empty() {
+                        self.touch_gesture = false;
+                    }
+                }
+            }
+            WindowEvent::PointerLeft { .. } => {
+                // The brush outline goes with the pointer
+                if let Some(app) = &mut self.app {
+                    if app.set_hover_position(None) {
+                        self.request_redraw();
                     }
                 }
             }
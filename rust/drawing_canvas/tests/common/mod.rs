@@ -0,0 +1,84 @@
+//! Helpers Shared by the GPU Tests
+//!
+//! Used by both the golden image tests and the GPU behavior tests; each test crate only
+//! uses some of them.
+
+#![allow(dead_code)]
+
+use drawing_canvas::{App, BrushDab, BrushParams, BrushState, PointerEvent, PointerEventSource, PointerEventType, Renderer};
+
+/// Size of the rendered canvas in pixels
+pub const CANVAS_SIZE: [u32; 2] = [128, 96];
+
+/// Largest per-channel difference (0-255) still counted as a match
+pub const CHANNEL_TOLERANCE: u8 = 3;
+
+/// Paper the strokes are drawn on (opaque white)
+pub const PAPER: [f64; 4] = [1.0, 1.0, 1.0, 1.0];
+
+/// Create a headless renderer
+pub fn headless_renderer() -> Renderer {
+    let size = winit::dpi::PhysicalSize::new(CANVAS_SIZE[0], CANVAS_SIZE[1]);
+    pollster::block_on(Renderer::new_headless(size)).expect("No headless renderer (these tests need a GPU adapter)")
+}
+
+/// Run a scripted stroke of (position, pressure) samples through the brush engine
+pub fn stroke_dabs(params: BrushParams, samples: &[([f32; 2], f32)]) -> Vec<BrushDab> {
+    let mut state = BrushState::with_params(params);
+    state.begin_stroke();
+
+    let mut dabs = Vec::new();
+    let last = samples.len() - 1;
+    for (i, &(position, pressure)) in samples.iter().enumerate() {
+        let event_type = match i {
+            0 => PointerEventType::Down,
+            i if i == last => PointerEventType::Up,
+            _ => PointerEventType::Move,
+        };
+        dabs.extend(state.calculate_dabs(position, pressure, event_type));
+    }
+    state.end_stroke();
+    dabs
+}
+
+/// An S-curve across the canvas with pressure rising then falling
+pub fn s_curve() -> Vec<([f32; 2], f32)> {
+    let steps = 48;
+    (0..=steps)
+        .map(|i| {
+            let t = i as f32 / steps as f32;
+            let x = 16.0 + t * 96.0;
+            let y = 48.0 + (t * std::f32::consts::TAU).sin() * 24.0;
+            let pressure = 0.2 + 0.8 * (t * std::f32::consts::PI).sin();
+            ([x, y], pressure)
+        })
+        .collect()
+}
+
+/// Queue a scripted stroke of (position, pressure) samples as pen input
+pub fn queue_stroke(app: &mut App, samples: &[([f32; 2], f32)]) {
+    let last = samples.len() - 1;
+    for (i, &(position, pressure)) in samples.iter().enumerate() {
+        app.queue_input_event(PointerEvent {
+            position,
+            pressure,
+            tilt: None,
+            azimuth: None,
+            twist: None,
+            timestamp: i as f64 * 8.0,
+            event_type: match i {
+                0 => PointerEventType::Down,
+                i if i == last => PointerEventType::Up,
+                _ => PointerEventType::Move,
+            },
+            source: PointerEventSource::TabletTool,
+        });
+    }
+}
+
+/// Render dabs onto fresh paper and read the canvas back as RGBA8
+pub fn render(renderer: &mut Renderer, dabs: &[BrushDab]) -> Vec<u8> {
+    renderer.clear_canvas(&PAPER);
+    renderer.render_dabs(dabs);
+    pollster::block_on(renderer.read_canvas_rgba8()).expect("Canvas readback failed")
+}
@@ -0,0 +1,149 @@
+//! Golden Image Tests
+//!
+//! Scripted strokes are rendered with a headless renderer, read back, and compared against
+//! PNGs in `tests/golden/` within a per-channel tolerance (absorbing small differences
+//! between GPUs and drivers). They cover what the `calculate_dabs` unit tests can't: dab
+//! placement on the canvas, blending, and color space handling. Other GPU behavior is
+//! tested in `tests/gpu.rs`.
+//!
+//! After an intended rendering change, regenerate the goldens and commit them:
+//!
+//! ```text
+//! UPDATE_GOLDENS=1 cargo test --test golden -- --include-ignored
+//! ```
+//!
+//! A missing golden fails the test unless `UPDATE_GOLDENS` is set. On a mismatch the
+//! rendered image is written to `CARGO_TARGET_TMPDIR` for inspection.
+//!
+//! Every test here needs a GPU adapter, so they are ignored by default and show up as
+//! ignored in the test summary. Run them on a machine with a GPU:
+//!
+//! ```text
+//! cargo test --test golden -- --include-ignored
+//! ```
+
+mod common;
+
+use std::path::PathBuf;
+
+use common::{headless_renderer, render, s_curve, stroke_dabs, CANVAS_SIZE, CHANNEL_TOLERANCE};
+use drawing_canvas::{BlendColorSpace, BrushParams, TipShape};
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden").join(format!("{}.png", name))
+}
+
+fn write_png(path: &PathBuf, rgba: &[u8], [width, height]: [u32; 2]) {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).expect("Failed to create golden directory");
+    }
+    let file = std::fs::File::create(path).expect("Failed to create PNG");
+    let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().expect("Failed to write PNG header");
+    writer.write_image_data(rgba).expect("Failed to write PNG data");
+}
+
+fn read_png(path: &PathBuf) -> (Vec<u8>, [u32; 2]) {
+    let file = std::fs::File::open(path).expect("Failed to open golden");
+    let mut reader = png::Decoder::new(file).read_info().expect("Failed to read golden header");
+    let mut rgba = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut rgba).expect("Failed to decode golden");
+    assert_eq!(
+        (info.color_type, info.bit_depth),
+        (png::ColorType::Rgba, png::BitDepth::Eight),
+        "Golden {} must be 8-bit RGBA",
+        path.display()
+    );
+    rgba.truncate(info.buffer_size());
+    (rgba, [info.width, info.height])
+}
+
+/// Compare rendered RGBA8 against the named golden, recording it instead if `UPDATE_GOLDENS` is set
+fn assert_matches_golden(name: &str, rgba: &[u8], size: [u32; 2]) {
+    let path = golden_path(name);
+    if std::env::var_os("UPDATE_GOLDENS").is_some() {
+        write_png(&path, rgba, size);
+        return;
+    }
+    assert!(
+        path.exists(),
+        "Golden {} is missing; record it with UPDATE_GOLDENS=1 and commit it",
+        path.display()
+    );
+
+    let (golden, golden_size) = read_png(&path);
+    assert_eq!(golden_size, size, "Golden {} has a different size", name);
+
+    let mut mismatched = 0;
+    let mut worst: Option<(usize, u8)> = None;
+    for (pixel, (actual, expected)) in rgba.chunks_exact(4).zip(golden.chunks_exact(4)).enumerate() {
+        let diff = actual.iter().zip(expected).map(|(a, e)| a.abs_diff(*e)).max().unwrap_or(0);
+        if diff > CHANNEL_TOLERANCE {
+            mismatched += 1;
+            if worst.is_none_or(|(_, worst_diff)| diff > worst_diff) {
+                worst = Some((pixel, diff));
+            }
+        }
+    }
+
+    if let Some((pixel, diff)) = worst {
+        let actual_path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("{}.actual.png", name));
+        write_png(&actual_path, rgba, size);
+        panic!(
+            "{} pixels differ from golden {} by more than {} (worst: {} at ({}, {})); rendered image written to {}",
+            mismatched,
+            name,
+            CHANNEL_TOLERANCE,
+            diff,
+            pixel as u32 % size[0],
+            pixel as u32 / size[0],
+            actual_path.display()
+        );
+    }
+}
+
+#[test]
+#[ignore = "needs a GPU adapter"]
+fn test_round_brush_stroke_matches_golden() {
+    let mut renderer = headless_renderer();
+    renderer.set_blend_color_space(BlendColorSpace::Srgb);
+
+    let dabs = stroke_dabs(BrushParams { size: 12.0, color: [0.1, 0.2, 0.6, 1.0], ..BrushParams::DEFAULT }, &s_curve());
+    let rgba = render(&mut renderer, &dabs);
+
+    assert_matches_golden("round_brush_srgb", &rgba, CANVAS_SIZE);
+}
+
+#[test]
+#[ignore = "needs a GPU adapter"]
+fn test_soft_brush_linear_blending_matches_golden() {
+    let mut renderer = headless_renderer();
+    renderer.set_blend_color_space(BlendColorSpace::Linear);
+
+    let params = BrushParams { size: 20.0, hardness: 0.2, color: [0.9, 0.3, 0.1, 1.0], ..BrushParams::DEFAULT };
+    let dabs = stroke_dabs(params, &s_curve());
+    let rgba = render(&mut renderer, &dabs);
+
+    assert_matches_golden("soft_brush_linear", &rgba, CANVAS_SIZE);
+}
+
+#[test]
+#[ignore = "needs a GPU adapter"]
+fn test_polygon_tip_stroke_matches_golden() {
+    let mut renderer = headless_renderer();
+    renderer.set_blend_color_space(BlendColorSpace::Srgb);
+
+    let params = BrushParams {
+        size: 16.0,
+        spacing: 0.6,
+        tip_shape: TipShape::Polygon(5),
+        color: [0.0, 0.0, 0.0, 1.0],
+        ..BrushParams::DEFAULT
+    };
+    let dabs = stroke_dabs(params, &s_curve());
+    let rgba = render(&mut renderer, &dabs);
+
+    assert_matches_golden("polygon_tip_srgb", &rgba, CANVAS_SIZE);
+}
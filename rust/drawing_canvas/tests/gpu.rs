@@ -0,0 +1,926 @@
+//! GPU Behavior Tests
+//!
+//! End-to-end checks that need a real renderer: undo and layers recompositing the canvas,
+//! exports, resizing, smudging, and the other paths where the result only shows up in the
+//! pixels read back. Exact images are compared in `tests/golden.rs`; logic that doesn't
+//! need the GPU is unit tested in its module.
+//!
+//! Every test here needs a GPU adapter, so they are ignored by default and show up as
+//! ignored in the test summary. Run them on a machine with a GPU:
+//!
+//! ```text
+//! cargo test --test gpu -- --include-ignored
+//! ```
+
+mod common;
+
+use common::{headless_renderer, queue_stroke, render, s_curve, stroke_dabs, CANVAS_SIZE, CHANNEL_TOLERANCE, PAPER};
+use drawing_canvas::{
+    AaMode, App, BlendColorSpace, BrushBlendMode, BrushDab, BrushParams, HdrExportMode, OpacityMode, PointerEvent,
+    PointerEventSource, PointerEventType, PressureMapping, Renderer, SymmetryMode, Tool,
+};
+
+#[test]
+#[ignore = "needs a GPU adapter"]
+fn test_undo_and_redo_recomposite_the_canvas() {
+    let mut renderer = headless_renderer();
+    let mut app = App::new();
+    app.set_clear_color(PAPER[0], PAPER[1], PAPER[2], PAPER[3]);
+    app.clear_canvas(&mut renderer);
+    let read = |renderer: &mut Renderer| pollster::block_on(renderer.read_canvas_rgba8()).expect("Canvas readback failed");
+
+    queue_stroke(&mut app, &s_curve());
+    app.render(&mut renderer);
+    let one_stroke = read(&mut renderer);
+
+    let mirrored: Vec<_> = s_curve().into_iter().map(|([x, y], pressure)| ([x, 96.0 - y], pressure)).collect();
+    queue_stroke(&mut app, &mirrored);
+    app.render(&mut renderer);
+    let two_strokes = read(&mut renderer);
+    assert!(one_stroke != two_strokes, "Second stroke should change the canvas");
+
+    // Replay draws the same dabs with the same pipelines, so the pixels match exactly
+    assert!(app.undo(&mut renderer));
+    assert!(read(&mut renderer) == one_stroke, "Undo should leave only the first stroke");
+    assert!(app.redo(&mut renderer));
+    assert!(read(&mut renderer) == two_strokes, "Redo should bring the second stroke back");
+
+    // The first undo snapshot is the blank paper
+    assert!(app.undo(&mut renderer));
+    assert!(app.undo(&mut renderer));
+    assert!(!app.undo(&mut renderer));
+    assert!(read(&mut renderer).chunks_exact(4).all(|pixel| pixel == [255, 255, 255, 255]));
+}
+
+#[test]
+#[ignore = "needs a GPU adapter"]
+fn test_eraser_clears_paint_to_transparent() {
+    let mut renderer = headless_renderer();
+    let params = BrushParams { size: 12.0, color: [0.1, 0.2, 0.6, 1.0], ..BrushParams::DEFAULT };
+    let dabs = stroke_dabs(params, &s_curve());
+    renderer.clear_canvas(&[0.0; 4]);
+    renderer.render_dabs(&dabs);
+
+    // A larger hard eraser along the same path removes the stroke entirely
+    let eraser = BrushParams { size: 40.0, ..BrushParams::ERASER };
+    renderer.set_erase_mode(true);
+    renderer.render_dabs(&stroke_dabs(eraser, &s_curve()));
+
+    let rgba = pollster::block_on(renderer.read_canvas_rgba8()).expect("Canvas readback failed");
+    assert!(rgba.chunks_exact(4).all(|pixel| pixel[3] == 0), "Erased canvas should be fully transparent");
+}
+
+#[test]
+#[ignore = "needs a GPU adapter"]
+fn test_preserving_blend_space_switch_round_trips_the_canvas() {
+    let mut renderer = headless_renderer();
+    renderer.set_blend_color_space(BlendColorSpace::Srgb);
+    let params = BrushParams { size: 20.0, hardness: 0.2, color: [0.9, 0.3, 0.1, 1.0], ..BrushParams::DEFAULT };
+    let before = render(&mut renderer, &stroke_dabs(params, &s_curve()));
+
+    // Without conversion the art would shift color after each switch; converted, it comes
+    // back (within 8-bit readback rounding) after a switch there and back
+    renderer.set_blend_color_space_preserving(BlendColorSpace::Linear);
+    renderer.set_blend_color_space_preserving(BlendColorSpace::Srgb);
+    let after = pollster::block_on(renderer.read_canvas_rgba8()).expect("Canvas readback failed");
+
+    let worst = before.iter().zip(&after).map(|(a, b)| a.abs_diff(*b)).max().unwrap_or(0);
+    assert!(worst <= 1, "Round trip changed the canvas by up to {}", worst);
+}
+
+#[test]
+#[ignore = "needs a GPU adapter"]
+fn test_layers_composite_bottom_to_top() {
+    let mut renderer = headless_renderer();
+    renderer.set_blend_color_space(BlendColorSpace::Srgb);
+    let red = BrushParams { size: 20.0, color: [1.0, 0.0, 0.0, 1.0], ..BrushParams::DEFAULT };
+    let blue = BrushParams { color: [0.0, 0.0, 1.0, 1.0], ..red };
+    let single_layer = render(&mut renderer, &stroke_dabs(red, &s_curve()));
+
+    // Blue on a layer above covers the red stroke along the same path
+    let top = renderer.add_layer();
+    assert_eq!((renderer.is_layer_empty(0), renderer.is_layer_empty(top)), (Some(false), Some(true)));
+    renderer.set_active_layer(top).expect("New layer should exist");
+    renderer.render_dabs(&stroke_dabs(blue, &s_curve()));
+    assert_eq!(renderer.is_layer_empty(top), Some(false));
+    let layered = pollster::block_on(renderer.read_canvas_rgba8()).expect("Canvas readback failed");
+    let red_pixels: Vec<_> = single_layer
+        .chunks_exact(4)
+        .zip(layered.chunks_exact(4))
+        .filter(|(before, _)| before[1] < 10 && before[2] < 10)
+        .collect();
+    assert!(!red_pixels.is_empty());
+    assert!(
+        red_pixels.iter().all(|(_, after)| after[2] > after[0]),
+        "Top layer should cover the bottom one"
+    );
+
+    // Hiding the top layer shows the bottom layer exactly as it was drawn
+    renderer.set_layer_visible(top, false).expect("Layer should exist");
+    let hidden = pollster::block_on(renderer.read_canvas_rgba8()).expect("Canvas readback failed");
+    assert_eq!(hidden, single_layer);
+
+    // Each composite rewrites the layers' uniforms, so going back to the first settings
+    // gives the first result
+    renderer.set_layer_visible(top, true).expect("Layer should exist");
+    renderer.set_layer_opacity(top, 0.5).expect("Layer should exist");
+    let faded = pollster::block_on(renderer.read_canvas_rgba8()).expect("Canvas readback failed");
+    assert_ne!(faded, layered);
+    renderer.set_layer_opacity(top, 1.0).expect("Layer should exist");
+    let restored = pollster::block_on(renderer.read_canvas_rgba8()).expect("Canvas readback failed");
+    assert_eq!(restored, layered);
+    assert!(renderer.set_layer_opacity(top, f32::NAN).is_err());
+    assert_eq!(renderer.layer_opacity(top), Some(1.0));
+    renderer.set_layer_visible(top, false).expect("Layer should exist");
+
+    assert!(renderer.remove_layer(top).is_ok());
+    assert!(renderer.remove_layer(0).is_err(), "The only layer can't be removed");
+}
+
+#[test]
+#[ignore = "needs a GPU adapter"]
+fn test_resize_keeps_canvas_contents() {
+    let mut renderer = headless_renderer();
+    let params = BrushParams { size: 16.0, color: [0.2, 0.5, 0.9, 1.0], ..BrushParams::DEFAULT };
+    let before = render(&mut renderer, &stroke_dabs(params, &s_curve()));
+
+    let [width, height] = CANVAS_SIZE;
+    renderer.resize(winit::dpi::PhysicalSize::new(width + 40, height + 30));
+    let after = pollster::block_on(renderer.read_canvas_rgba8()).expect("Canvas readback failed");
+
+    // The old canvas sits unchanged in the top-left; the added area is transparent
+    let row_bytes = width as usize * 4;
+    let new_row_bytes = (width + 40) as usize * 4;
+    for (y, row) in before.chunks_exact(row_bytes).enumerate() {
+        assert_eq!(&after[y * new_row_bytes..y * new_row_bytes + row_bytes], row, "Row {} changed", y);
+        assert!(after[y * new_row_bytes + row_bytes..(y + 1) * new_row_bytes].iter().all(|&v| v == 0));
+    }
+    assert!(after[height as usize * new_row_bytes..].iter().all(|&v| v == 0));
+}
+
+#[test]
+#[ignore = "needs a GPU adapter"]
+fn test_undo_history_survives_a_resize() {
+    let mut renderer = headless_renderer();
+    let mut app = App::new();
+    app.set_clear_color(PAPER[0], PAPER[1], PAPER[2], PAPER[3]);
+    app.clear_canvas(&mut renderer);
+    let read = |renderer: &mut Renderer| pollster::block_on(renderer.read_canvas_rgba8()).expect("Canvas readback failed");
+
+    queue_stroke(&mut app, &s_curve());
+    app.render(&mut renderer);
+    let one_stroke = read(&mut renderer);
+    let mirrored: Vec<_> = s_curve().into_iter().map(|([x, y], pressure)| ([x, 96.0 - y], pressure)).collect();
+    queue_stroke(&mut app, &mirrored);
+    app.render(&mut renderer);
+
+    let [width, height] = CANVAS_SIZE;
+    renderer.resize(winit::dpi::PhysicalSize::new(width + 40, height + 30));
+    assert!(app.undo(&mut renderer), "Resizing dropped the undo history");
+
+    // The undo base was resized with the canvas, so the first stroke is back in the top-left
+    let after = read(&mut renderer);
+    let row_bytes = width as usize * 4;
+    let new_row_bytes = (width + 40) as usize * 4;
+    for (y, row) in one_stroke.chunks_exact(row_bytes).enumerate() {
+        assert!(&after[y * new_row_bytes..y * new_row_bytes + row_bytes] == row, "Row {} differs after undo", y);
+    }
+    assert!(app.redo(&mut renderer));
+}
+
+#[test]
+#[ignore = "needs a GPU adapter"]
+fn test_undo_steps_through_strokes_on_every_layer() {
+    let mut renderer = headless_renderer();
+    let mut app = App::new();
+    app.set_clear_color(PAPER[0], PAPER[1], PAPER[2], PAPER[3]);
+    app.clear_canvas(&mut renderer);
+
+    queue_stroke(&mut app, &s_curve());
+    app.render(&mut renderer);
+    let top = app.add_layer(&mut renderer);
+    app.set_active_layer(top, &mut renderer).expect("New layer should exist");
+    let mirrored: Vec<_> = s_curve().into_iter().map(|([x, y], pressure)| ([x, 96.0 - y], pressure)).collect();
+    queue_stroke(&mut app, &mirrored);
+    app.render(&mut renderer);
+
+    // Switching back keeps the history; undo rewinds each stroke on its own layer
+    app.set_active_layer(0, &mut renderer).expect("Layer 0 exists");
+    assert!(app.undo(&mut renderer), "Switching layers dropped the undo history");
+    assert_eq!((renderer.is_layer_empty(0), renderer.is_layer_empty(top)), (Some(false), Some(true)));
+    assert!(app.undo(&mut renderer));
+    assert_eq!(renderer.is_layer_empty(0), Some(true));
+    assert!(app.redo(&mut renderer) && app.redo(&mut renderer));
+    assert_eq!((renderer.is_layer_empty(0), renderer.is_layer_empty(top)), (Some(false), Some(false)));
+
+    // Removing the top layer only takes its own stroke out of the history
+    app.remove_layer(top, &mut renderer).expect("Top layer exists");
+    assert!(app.undo(&mut renderer));
+    assert_eq!(renderer.is_layer_empty(0), Some(true));
+    assert!(!app.can_undo());
+}
+
+#[test]
+#[ignore = "needs a GPU adapter"]
+fn test_fixed_canvas_resolution_ignores_surface_size() {
+    let mut renderer = headless_renderer();
+    let params = BrushParams { size: 16.0, color: [0.2, 0.5, 0.9, 1.0], ..BrushParams::DEFAULT };
+    let before = render(&mut renderer, &stroke_dabs(params, &s_curve()));
+
+    // A canvas larger than the surface keeps the drawing in its top-left
+    let [width, height] = CANVAS_SIZE;
+    assert_eq!(renderer.set_canvas_resolution(400, 300), [400, 300]);
+    assert_eq!(renderer.canvas_resolution(), Some([400, 300]));
+    let large = pollster::block_on(renderer.read_canvas_rgba8()).expect("Canvas readback failed");
+    assert_eq!(large.len(), 400 * 300 * 4);
+    let row_bytes = width as usize * 4;
+    for (y, row) in before.chunks_exact(row_bytes).enumerate() {
+        assert_eq!(&large[y * 1600..y * 1600 + row_bytes], row, "Row {} changed", y);
+    }
+
+    // Resizing the surface leaves a fixed canvas alone
+    renderer.resize(winit::dpi::PhysicalSize::new(width / 2, height / 2));
+    let resized = pollster::block_on(renderer.read_canvas_rgba8()).expect("Canvas readback failed");
+    assert_eq!(resized, large);
+
+    // Oversized requests are clamped, and 0 follows the surface again
+    let [max_width, _] = renderer.set_canvas_resolution(u32::MAX, 300);
+    assert!(max_width < u32::MAX);
+    assert_eq!(renderer.set_canvas_resolution(0, 0), [width / 2, height / 2]);
+    assert_eq!(renderer.canvas_resolution(), None);
+}
+
+#[test]
+#[ignore = "needs a GPU adapter"]
+fn test_reference_image_is_left_out_of_exports() {
+    let mut renderer = headless_renderer();
+    let params = BrushParams { size: 16.0, color: [0.2, 0.5, 0.9, 1.0], ..BrushParams::DEFAULT };
+    let strokes_only = render(&mut renderer, &stroke_dabs(params, &s_curve()));
+
+    // An opaque green photo under the drawing is display only
+    let [width, height] = [40, 30];
+    let photo: Vec<u8> = [0, 200, 0, 255].repeat(width * height);
+    renderer
+        .set_reference_image(&photo, width as u32, height as u32)
+        .expect("Reference image should load");
+    renderer.set_reference_opacity(0.5);
+    assert_eq!(renderer.reference_image_size(), Some([width as u32, height as u32]));
+    let exported = pollster::block_on(renderer.read_canvas_rgba8()).expect("Canvas readback failed");
+    assert_eq!(exported, strokes_only);
+
+    assert!(renderer.set_reference_image(&photo[4..], width as u32, height as u32).is_err());
+    renderer.clear_reference_image();
+    assert_eq!(renderer.reference_image_size(), None);
+}
+
+#[test]
+#[ignore = "needs a GPU adapter"]
+fn test_antialiasing_modes_keep_stroke_coverage() {
+    let mut renderer = headless_renderer();
+    let params = BrushParams { size: 10.0, hardness: 1.0, color: [0.0, 0.0, 0.0, 1.0], ..BrushParams::DEFAULT };
+    let dabs = stroke_dabs(params, &s_curve());
+    let ink = |rgba: &[u8]| rgba.chunks_exact(4).map(|p| 255 - p[0] as u64).sum::<u64>();
+    let off = ink(&render(&mut renderer, &dabs));
+
+    // Smoother edges, same stroke: analytic edges sit half a pixel further out than the
+    // ramp inside the edge. MSAA falls back to analytic where it is unsupported
+    for mode in [AaMode::Analytic, AaMode::Msaa4x] {
+        let applied = renderer.set_antialiasing(mode);
+        assert!(applied == mode || applied == AaMode::Analytic);
+        assert_eq!(renderer.antialiasing(), applied);
+        let vram_before = renderer.estimated_vram_bytes();
+        let coverage = ink(&render(&mut renderer, &dabs)) as f64 / off as f64;
+        assert!((0.9..1.25).contains(&coverage), "{:?} changed stroke coverage by {}", applied, coverage);
+
+        // The multisampled target is made by the first MSAA draw and counted per sample
+        if applied == AaMode::Msaa4x {
+            let bleed = 2 * renderer.bleed_px() as u64;
+            let texel_bytes = renderer.canvas_format().block_copy_size(None).expect("Canvas texel size") as u64;
+            let msaa_bytes = (CANVAS_SIZE[0] as u64 + bleed) * (CANVAS_SIZE[1] as u64 + bleed) * texel_bytes * 4;
+            assert_eq!(renderer.estimated_vram_bytes(), vram_before + msaa_bytes);
+        }
+    }
+}
+
+#[test]
+#[ignore = "needs a GPU adapter"]
+fn test_dab_batches_reuse_the_instance_buffer() {
+    let mut renderer = headless_renderer();
+    let params = BrushParams { size: 12.0, color: [0.8, 0.2, 0.1, 1.0], ..BrushParams::DEFAULT };
+    let dabs = stroke_dabs(params, &s_curve());
+    let whole = render(&mut renderer, &dabs);
+    let reallocations = renderer.dab_buffer_reallocations();
+
+    // A continuous stroke arrives a few dabs per frame: same pixels, no reallocations
+    renderer.clear_canvas(&PAPER);
+    for frame in dabs.chunks(3) {
+        renderer.render_dabs(frame);
+    }
+    let batched = pollster::block_on(renderer.read_canvas_rgba8()).expect("Canvas readback failed");
+    assert_eq!(batched, whole);
+    assert_eq!(renderer.dab_buffer_reallocations(), reallocations);
+
+    // An oversized batch grows the buffer once, then it is reused
+    let many: Vec<BrushDab> = dabs.iter().cycle().take(4096).copied().collect();
+    renderer.render_dabs(&many);
+    assert_eq!(renderer.dab_buffer_reallocations(), reallocations + 1);
+    renderer.render_dabs(&many);
+    assert_eq!(renderer.dab_buffer_reallocations(), reallocations + 1);
+}
+
+#[test]
+#[ignore = "needs a GPU adapter"]
+fn test_transparent_clear_exports_transparent_over_paper() {
+    let mut renderer = headless_renderer();
+
+    // The paper color is display only; the cleared canvas keeps its alpha
+    renderer.set_paper_color([1.0, 1.0, 1.0, 1.0]);
+    renderer.clear_canvas(&[1.0, 1.0, 1.0, 0.0]);
+    let exported = pollster::block_on(renderer.read_canvas_rgba8()).expect("Canvas readback failed");
+    assert!(exported.chunks_exact(4).all(|p| p[3] == 0));
+
+    renderer.clear_canvas(&[1.0, 1.0, 1.0, 1.0]);
+    let exported = pollster::block_on(renderer.read_canvas_rgba8()).expect("Canvas readback failed");
+    assert!(exported.chunks_exact(4).all(|p| p == [255, 255, 255, 255]));
+}
+
+#[test]
+#[ignore = "needs a GPU adapter"]
+fn test_16_bit_export_keeps_hdr_values_in_range() {
+    let mut renderer = headless_renderer();
+    let gray = [0.5, 0.5, 0.5, 1.0];
+    renderer.clear_canvas(&gray);
+
+    // Additive white over gray pushes the stroke well past 1.0
+    let white = BrushParams { size: 20.0, color: [1.0, 1.0, 1.0, 1.0], ..BrushParams::DEFAULT };
+    renderer.set_brush_blend_mode(BrushBlendMode::AddHdr);
+    for _ in 0..3 {
+        renderer.render_dabs(&stroke_dabs(white, &s_curve()));
+    }
+    let read = |renderer: &mut Renderer, mode| {
+        renderer.set_export_hdr_mode(mode);
+        pollster::block_on(renderer.read_canvas_rgba16()).expect("Canvas readback failed")
+    };
+    let clamped = read(&mut renderer, HdrExportMode::Clamp);
+    let tone_mapped = read(&mut renderer, HdrExportMode::ToneMap);
+
+    // Gray paper away from the stroke is the same either way; the stroke clips or rolls off
+    assert_eq!(&clamped[..4], &tone_mapped[..4]);
+    let bright: Vec<_> = clamped.chunks_exact(4).zip(tone_mapped.chunks_exact(4)).filter(|(c, _)| c[0] == u16::MAX).collect();
+    assert!(!bright.is_empty());
+    assert!(bright.iter().all(|(_, t)| t[0] < u16::MAX && t[0] > u16::MAX / 2 && t[3] == u16::MAX));
+
+    let png_data = pollster::block_on(renderer.export_png16()).expect("PNG export failed");
+    let reader = png::Decoder::new(png_data.as_slice()).read_info().expect("Invalid PNG");
+    assert_eq!(reader.info().bit_depth, png::BitDepth::Sixteen);
+}
+
+#[test]
+#[ignore = "needs a GPU adapter"]
+fn test_multiply_stroke_darkens_the_canvas_beneath() {
+    let mut renderer = headless_renderer();
+    let red = BrushParams { size: 20.0, color: [1.0, 0.0, 0.0, 1.0], ..BrushParams::DEFAULT };
+    let blue = BrushParams { color: [0.0, 0.0, 1.0, 1.0], ..red };
+    let before = render(&mut renderer, &stroke_dabs(red, &s_curve()));
+
+    // Blue multiplied over red leaves black; over white paper it stays blue
+    renderer.set_brush_blend_mode(BrushBlendMode::Multiply);
+    renderer.begin_stroke_layer(1.0, BrushBlendMode::Multiply);
+    renderer.render_dabs(&stroke_dabs(blue, &s_curve()));
+    renderer.commit_stroke_layer();
+    let after = pollster::block_on(renderer.read_canvas_rgba8()).expect("Canvas readback failed");
+
+    let red_pixels: Vec<_> = before
+        .chunks_exact(4)
+        .zip(after.chunks_exact(4))
+        .filter(|(before, _)| before[1] < 10 && before[2] < 10)
+        .collect();
+    assert!(!red_pixels.is_empty());
+    assert!(
+        red_pixels.iter().all(|(_, after)| after[..3].iter().all(|&v| v < 10)),
+        "Multiplying blue over red should give black"
+    );
+    assert_eq!(&after[..4], &before[..4], "Paper away from the stroke should be unchanged");
+}
+
+#[test]
+#[ignore = "needs a GPU adapter"]
+fn test_recorded_session_plays_back_the_same_dabs() {
+    let mut renderer = headless_renderer();
+    let mut app = App::new();
+    app.set_retain_frame_dabs(true);
+    app.brush_state_mut().params = BrushParams { size: 14.0, pressure_mapping: PressureMapping::Size, ..BrushParams::DEFAULT };
+
+    app.start_recording();
+    queue_stroke(&mut app, &s_curve());
+    app.render(&mut renderer);
+    let drawn: Vec<([f32; 2], f32)> = app.last_frame_dabs().iter().map(|dab| (dab.position, dab.size)).collect();
+    let json = app.stop_recording();
+    assert!(!app.is_recording());
+
+    // Played back fast enough to be due by the next frame
+    app.play_recording(&json, 1000.0).expect("Recording should play");
+    std::thread::sleep(std::time::Duration::from_millis(5));
+    app.render(&mut renderer);
+    let replayed: Vec<([f32; 2], f32)> = app.last_frame_dabs().iter().map(|dab| (dab.position, dab.size)).collect();
+
+    assert!(!drawn.is_empty());
+    assert_eq!(replayed, drawn);
+    assert!(!app.is_playing_synthetic_input());
+    assert!(app.play_recording("{}", 1.0).is_err());
+}
+
+#[test]
+#[ignore = "needs a GPU adapter"]
+fn test_tablet_pressure_varies_dab_size() {
+    let mut renderer = headless_renderer();
+    let mut app = App::new();
+    app.set_retain_frame_dabs(true);
+    app.brush_state_mut().params =
+        BrushParams { size: 20.0, pressure_mapping: PressureMapping::Size, min_size_percent: 0.2, ..BrushParams::DEFAULT };
+
+    // Pen input with pressure rising then falling, as the desktop build queues it
+    queue_stroke(&mut app, &s_curve());
+    app.render(&mut renderer);
+
+    let sizes: Vec<f32> = app.last_frame_dabs().iter().map(|dab| dab.size).collect();
+    assert!(sizes.len() > 10);
+    let smallest = sizes.iter().copied().fold(f32::MAX, f32::min);
+    let largest = sizes.iter().copied().fold(0.0, f32::max);
+    assert!(largest > 18.0, "Full pressure should reach the full size, got {}", largest);
+    assert!(smallest < largest * 0.5, "Light pressure should thin the stroke ({} vs {})", smallest, largest);
+}
+
+#[test]
+#[ignore = "needs a GPU adapter"]
+fn test_symmetry_mirrors_dabs_under_a_panned_zoomed_view() {
+    let mut renderer = headless_renderer();
+    let mut app = App::new();
+    app.set_retain_frame_dabs(true);
+    app.set_view_pan_zoom(2.0, [40.0, -25.0], &mut renderer);
+    let center_x = CANVAS_SIZE[0] as f32 / 2.0;
+    app.set_symmetry(SymmetryMode::Vertical, [center_x, 0.0]).expect("Vertical symmetry is valid");
+
+    // A short stroke on the left half; input is already in canvas space
+    queue_stroke(&mut app, &[([20.0, 30.0], 1.0), ([30.0, 40.0], 1.0), ([40.0, 30.0], 1.0)]);
+    app.render(&mut renderer);
+
+    let dabs = app.last_frame_dabs();
+    assert!(!dabs.is_empty());
+    let (left, right): (Vec<_>, Vec<_>) = dabs.iter().partition(|dab| dab.position[0] < center_x);
+    assert_eq!(left.len(), right.len(), "Every dab should have one mirrored copy");
+    for dab in &left {
+        let mirrored = [2.0 * center_x - dab.position[0], dab.position[1]];
+        assert!(
+            right.iter().any(|copy| copy.position == mirrored && copy.size == dab.size),
+            "Missing mirrored copy of the dab at {:?}",
+            dab.position
+        );
+    }
+    assert!(app.set_symmetry(SymmetryMode::Radial(0), [0.0, 0.0]).is_err());
+}
+
+#[test]
+#[ignore = "needs a GPU adapter"]
+fn test_bucket_fill_stops_at_a_stroke_and_undoes() {
+    let mut renderer = headless_renderer();
+    let mut app = App::new();
+    app.set_clear_color(PAPER[0], PAPER[1], PAPER[2], PAPER[3]);
+    app.clear_canvas(&mut renderer);
+    let read = |renderer: &mut Renderer| pollster::block_on(renderer.read_canvas_rgba8()).expect("Canvas readback failed");
+    let pixel = |rgba: &[u8], x: usize, y: usize| {
+        let i = (y * CANVAS_SIZE[0] as usize + x) * 4;
+        [rgba[i], rgba[i + 1], rgba[i + 2], rgba[i + 3]]
+    };
+
+    // A hard black line from top to bottom splits the paper in two
+    app.brush_state_mut().params =
+        BrushParams { size: 6.0, hardness: 1.0, color: [0.0, 0.0, 0.0, 1.0], ..BrushParams::DEFAULT };
+    let line: Vec<_> = (0..=24).map(|i| ([64.0, -8.0 + i as f32 * 4.5], 1.0)).collect();
+    queue_stroke(&mut app, &line);
+    app.render(&mut renderer);
+    let before = read(&mut renderer);
+
+    app.brush_state_mut().params.color = [1.0, 0.0, 0.0, 1.0];
+    let filled = pollster::block_on(app.fill_at(20.0, 48.0, 0.1, &mut renderer)).expect("Fill should succeed");
+    assert!(filled);
+    let after = read(&mut renderer);
+    assert_eq!(pixel(&after, 20, 48), [255, 0, 0, 255]);
+    assert_eq!(pixel(&after, 0, 0), [255, 0, 0, 255]);
+    assert_eq!(pixel(&after, 100, 48), [255, 255, 255, 255], "The fill should stop at the line");
+    assert_eq!(pixel(&after, 64, 48), pixel(&before, 64, 48));
+
+    assert!(app.undo(&mut renderer));
+    assert!(read(&mut renderer) == before, "Undo should remove the fill");
+    assert!(app.redo(&mut renderer));
+    assert!(read(&mut renderer) == after, "Redo should replay the fill");
+
+    let outside = pollster::block_on(app.fill_at(-5.0, 10.0, 0.1, &mut renderer)).expect("Fill should succeed");
+    assert!(!outside);
+}
+
+#[test]
+#[ignore = "needs a GPU adapter"]
+fn test_eyedropper_picks_the_canvas_color() {
+    let mut renderer = headless_renderer();
+    let mut app = App::new();
+    app.set_retain_frame_dabs(true);
+    app.set_clear_color(0.2, 0.4, 0.6, 1.0);
+    app.clear_canvas(&mut renderer);
+    app.brush_state_mut().params.color = [0.0, 0.0, 0.0, 0.5];
+
+    // Taps with the eyedropper pick instead of drawing
+    app.set_eyedropper_active(true);
+    queue_stroke(&mut app, &[([10.0, 20.0], 1.0), ([12.0, 20.0], 1.0)]);
+    app.render(&mut renderer);
+    assert!(app.last_frame_dabs().is_empty());
+    let [x, y] = app.take_eyedropper_request().expect("The tap should request a pick");
+    assert_eq!([x, y], [12.0, 20.0]);
+
+    let picked = pollster::block_on(app.pick_color(x, y, &mut renderer)).expect("Pick should succeed");
+    for (picked, expected) in picked.iter().zip([0.2, 0.4, 0.6, 1.0]) {
+        assert!((picked - expected).abs() < 1.0 / 255.0, "Picked {:?}", picked);
+    }
+    let brush_color = app.brush_state().params.color;
+    assert_eq!(brush_color[..3], picked[..3]);
+    assert_eq!(brush_color[3], 0.5, "The brush keeps its own alpha");
+
+    assert!(pollster::block_on(renderer.sample_pixel(CANVAS_SIZE[0] as f32, 0.0)).is_err());
+    renderer.clear_canvas(&[0.0; 4]);
+    assert_eq!(pollster::block_on(renderer.sample_pixel(5.0, 5.0)), Ok([0.0; 4]));
+}
+
+#[test]
+#[ignore = "needs a GPU adapter"]
+fn test_smudge_drags_paint_along_the_stroke() {
+    for color_space in [BlendColorSpace::Srgb, BlendColorSpace::Linear] {
+        let mut renderer = headless_renderer();
+        let mut app = App::new();
+        app.set_blend_color_space(color_space, &mut renderer);
+        app.set_clear_color(PAPER[0], PAPER[1], PAPER[2], PAPER[3]);
+        app.clear_canvas(&mut renderer);
+        let read = |renderer: &mut Renderer| pollster::block_on(renderer.read_canvas_rgba8()).expect("Canvas readback failed");
+        let pixel = |rgba: &[u8], x: usize, y: usize| {
+            let i = (y * CANVAS_SIZE[0] as usize + x) * 4;
+            [rgba[i], rgba[i + 1], rgba[i + 2], rgba[i + 3]]
+        };
+
+        // A hard red band down the left of the paper
+        app.brush_state_mut().params =
+            BrushParams { size: 16.0, hardness: 1.0, color: [1.0, 0.0, 0.0, 1.0], ..BrushParams::DEFAULT };
+        let band: Vec<_> = (0..=24).map(|i| ([32.0, -8.0 + i as f32 * 4.5], 1.0)).collect();
+        queue_stroke(&mut app, &band);
+        app.render(&mut renderer);
+        let before = read(&mut renderer);
+        assert_eq!(pixel(&before, 52, 48), [255, 255, 255, 255]);
+
+        // Dragging across it carries red out onto the paper, fading with distance
+        app.set_tool(Tool::Smudge(0.9)).expect("Valid smudge strength");
+        app.brush_state_mut().params.hardness = 0.5;
+        let drag: Vec<_> = (0..=20).map(|i| ([28.0 + i as f32 * 4.0, 48.0], 1.0)).collect();
+        queue_stroke(&mut app, &drag);
+        app.render(&mut renderer);
+        let after = read(&mut renderer);
+        let near = pixel(&after, 48, 48);
+        let far = pixel(&after, 64, 48);
+        assert!(near[0] > 200 && near[1] < 200, "{:?}: red should be dragged onto the paper, got {:?}", color_space, near);
+        assert!(far[1] > near[1], "{:?}: the smear should fade, got {:?} then {:?}", color_space, near, far);
+        assert_eq!(near[3], 255, "Smudging opaque paint keeps it opaque");
+        assert_eq!(pixel(&after, 52, 20), [255, 255, 255, 255], "Paper away from the stroke is untouched");
+
+        assert!(app.undo(&mut renderer));
+        assert!(read(&mut renderer) == before, "{:?}: undo should remove the smear", color_space);
+        assert!(app.redo(&mut renderer));
+        assert!(read(&mut renderer) == after, "{:?}: redo should replay the smear", color_space);
+        assert!(app.set_tool(Tool::Smudge(-0.1)).is_err());
+    }
+}
+
+#[test]
+#[ignore = "needs a GPU adapter"]
+fn test_smudge_length_sets_how_far_color_travels() {
+    let pixel = |rgba: &[u8], x: usize| {
+        let i = (48 * CANVAS_SIZE[0] as usize + x) * 4;
+        [rgba[i], rgba[i + 1], rgba[i + 2], rgba[i + 3]]
+    };
+    let mut smears = Vec::new();
+    for length in [0.0, 0.5, 1.0] {
+        let mut renderer = headless_renderer();
+        let mut app = App::new();
+        app.set_clear_color(PAPER[0], PAPER[1], PAPER[2], PAPER[3]);
+        app.clear_canvas(&mut renderer);
+
+        // The red band and drag from test_smudge_drags_paint_along_the_stroke
+        app.brush_state_mut().params =
+            BrushParams { size: 16.0, hardness: 1.0, color: [1.0, 0.0, 0.0, 1.0], ..BrushParams::DEFAULT };
+        let band: Vec<_> = (0..=24).map(|i| ([32.0, -8.0 + i as f32 * 4.5], 1.0)).collect();
+        queue_stroke(&mut app, &band);
+        app.render(&mut renderer);
+
+        app.set_tool(Tool::Smudge(0.9)).expect("Valid smudge strength");
+        app.brush_state_mut().params.hardness = 0.5;
+        app.brush_state_mut().params.smudge_length = length;
+        let drag: Vec<_> = (0..=20).map(|i| ([28.0 + i as f32 * 4.0, 48.0], 1.0)).collect();
+        queue_stroke(&mut app, &drag);
+        app.render(&mut renderer);
+        smears.push(pollster::block_on(renderer.read_canvas_rgba8()).expect("Canvas readback failed"));
+    }
+
+    // With no length nothing is carried off the band
+    assert_eq!(pixel(&smears[0], 48), [255, 255, 255, 255]);
+    assert_eq!(pixel(&smears[0], 64), [255, 255, 255, 255]);
+    // Longer lengths carry the red farther out onto the paper
+    let far = |smear: &[u8]| pixel(smear, 64)[1];
+    assert!(far(&smears[1]) < 255, "Length 0.5 should carry some red, got {:?}", pixel(&smears[1], 64));
+    assert!(
+        far(&smears[2]) < far(&smears[1]),
+        "Length 1.0 should carry more red than 0.5, got {:?} and {:?}",
+        pixel(&smears[2], 64),
+        pixel(&smears[1], 64)
+    );
+}
+
+#[test]
+#[ignore = "needs a GPU adapter"]
+fn test_prediction_is_shown_but_never_drawn_into_the_canvas() {
+    let mut renderer = headless_renderer();
+    let mut app = App::new();
+    app.set_clear_color(PAPER[0], PAPER[1], PAPER[2], PAPER[3]);
+    app.clear_canvas(&mut renderer);
+    app.brush_state_mut().params =
+        BrushParams { size: 8.0, hardness: 1.0, color: [0.0, 0.0, 1.0, 1.0], ..BrushParams::DEFAULT };
+    app.set_prediction(true, 40.0);
+    let read = |renderer: &mut Renderer| pollster::block_on(renderer.read_canvas_rgba8()).expect("Canvas readback failed");
+    let pixel = |rgba: &[u8], x: usize| {
+        let i = (48 * CANVAS_SIZE[0] as usize + x) * 4;
+        [rgba[i], rgba[i + 1], rgba[i + 2], rgba[i + 3]]
+    };
+    let event = |x: f32, timestamp: f64, event_type| PointerEvent {
+        position: [x, 48.0],
+        pressure: 1.0,
+        tilt: None,
+        azimuth: None,
+        twist: None,
+        timestamp,
+        event_type,
+        source: PointerEventSource::TabletTool,
+    };
+
+    // Half a stroke heading right, with the pen still down at x = 64
+    app.queue_input_event(event(16.0, 0.0, PointerEventType::Down));
+    for i in 1..=12 {
+        app.queue_input_event(event(16.0 + i as f32 * 4.0, i as f64 * 8.0, PointerEventType::Move));
+    }
+    app.render(&mut renderer);
+    assert!(renderer.has_prediction(), "A moving pen should be predicted ahead");
+    let partial = read(&mut renderer);
+    assert_ne!(pixel(&partial, 52), [255, 255, 255, 255], "The real stroke is on the canvas");
+    assert_eq!(pixel(&partial, 74), [255, 255, 255, 255], "The prediction ahead of the pen is not");
+
+    // Once the pen goes quiet the prediction is hidden; the stroke itself is unchanged
+    app.render(&mut renderer);
+    assert!(!renderer.has_prediction());
+    assert!(read(&mut renderer) == partial);
+
+    // The real samples take over where the prediction was
+    for i in 13..=18 {
+        app.queue_input_event(event(16.0 + i as f32 * 4.0, i as f64 * 8.0, PointerEventType::Move));
+    }
+    app.queue_input_event(event(88.0, 152.0, PointerEventType::Up));
+    app.render(&mut renderer);
+    assert!(!renderer.has_prediction(), "Nothing is predicted once the stroke ends");
+    assert_ne!(pixel(&read(&mut renderer), 74), [255, 255, 255, 255]);
+}
+
+#[test]
+#[ignore = "needs a GPU adapter"]
+fn test_dirty_area_tracks_what_changed_each_frame() {
+    let mut renderer = headless_renderer();
+    renderer.clear_canvas(&PAPER);
+    renderer.render();
+    assert_eq!(renderer.dirty_area_fraction(), 1.0, "A clear changes the whole canvas");
+    renderer.render();
+    assert_eq!(renderer.dirty_area_fraction(), 0.0, "Nothing changed since the last frame");
+
+    // A short stroke dirties only its own neighborhood
+    let params = BrushParams { size: 8.0, color: [0.0, 0.0, 0.0, 1.0], ..BrushParams::DEFAULT };
+    renderer.render_dabs(&stroke_dabs(params, &[([20.0, 20.0], 1.0), ([28.0, 20.0], 1.0)]));
+    renderer.render();
+    let fraction = renderer.dirty_area_fraction();
+    assert!(fraction > 0.0 && fraction < 0.05, "Dirty fraction {} for a short stroke", fraction);
+
+    // Layer changes restack the whole canvas
+    renderer.add_layer();
+    renderer.render();
+    assert_eq!(renderer.dirty_area_fraction(), 1.0);
+}
+
+#[test]
+#[ignore = "needs a GPU adapter"]
+fn test_8_bit_canvas_reads_back_like_the_float_canvas() {
+    let mut renderer = headless_renderer();
+    let params = BrushParams { size: 16.0, color: [0.2, 0.5, 0.8, 1.0], ..BrushParams::DEFAULT };
+    let dabs = stroke_dabs(params, &s_curve());
+    for color_space in [BlendColorSpace::Srgb, BlendColorSpace::Linear] {
+        renderer.set_srgb_canvas(false);
+        renderer.set_blend_color_space(color_space);
+        let float = render(&mut renderer, &dabs);
+
+        // Either 8-bit format reads back with 4-byte texels into the same layout. Only the
+        // antialiased edges, where dabs overlap, round differently at the lower precision
+        renderer.set_srgb_canvas(true);
+        let format = renderer.canvas_format();
+        let eight_bit = render(&mut renderer, &dabs);
+        assert_eq!(eight_bit.len(), float.len());
+        let worst = float.iter().zip(&eight_bit).map(|(a, b)| a.abs_diff(*b)).max().unwrap_or(0);
+        assert!(worst <= 4, "{:?} canvas differs from the float canvas by up to {}", format, worst);
+    }
+}
+
+#[test]
+#[ignore = "needs a GPU adapter"]
+fn test_uniform_opacity_keeps_overlaps_from_darkening() {
+    let mut renderer = headless_renderer();
+    let mut app = App::new();
+    app.set_clear_color(PAPER[0], PAPER[1], PAPER[2], PAPER[3]);
+    app.clear_canvas(&mut renderer);
+    app.brush_state_mut().params = BrushParams {
+        size: 20.0,
+        flow: 0.3,
+        color: [0.0, 0.0, 0.0, 1.0],
+        pressure_mapping: PressureMapping::None,
+        opacity_mode: OpacityMode::Uniform,
+        ..BrushParams::DEFAULT
+    };
+
+    // Out along y = 40 and back along y = 56: the legs overlap between y = 46 and 50
+    let mut samples: Vec<([f32; 2], f32)> = (0..=20).map(|i| ([20.0 + i as f32 * 4.0, 40.0], 1.0)).collect();
+    samples.extend((0..=20).map(|i| ([100.0 - i as f32 * 4.0, 56.0], 1.0)));
+    queue_stroke(&mut app, &samples);
+    app.render(&mut renderer);
+    let rgba = pollster::block_on(renderer.read_canvas_rgba8()).expect("Canvas readback failed");
+    let pixel = |x: usize, y: usize| {
+        let i = (y * CANVAS_SIZE[0] as usize + x) * 4;
+        [rgba[i], rgba[i + 1], rgba[i + 2], rgba[i + 3]]
+    };
+
+    let out_leg = pixel(60, 35);
+    let overlap = pixel(60, 48);
+    let back_leg = pixel(60, 61);
+    assert!(out_leg[0] < 255 && out_leg[0] > 128, "A 30% flow stroke should be light gray, got {:?}", out_leg);
+    for (name, other) in [("overlap", overlap), ("return leg", back_leg)] {
+        let worst = out_leg.iter().zip(&other).map(|(a, b)| a.abs_diff(*b)).max().unwrap_or(0);
+        assert!(worst <= 1, "The {} differs from the first leg by {}: {:?} vs {:?}", name, worst, other, out_leg);
+    }
+}
+
+#[test]
+#[ignore = "needs a GPU adapter"]
+fn test_max_opacity_caps_build_up_without_lightening_opaque_paint() {
+    let mut renderer = headless_renderer();
+    renderer.clear_canvas(&[0.0; 4]);
+    let params = BrushParams {
+        size: 16.0,
+        hardness: 1.0,
+        color: [0.1, 0.2, 0.8, 1.0],
+        pressure_mapping: PressureMapping::None,
+        ..BrushParams::DEFAULT
+    };
+    let line = |from: [f32; 2], to: [f32; 2]| -> Vec<([f32; 2], f32)> {
+        (0..=24)
+            .map(|i| {
+                let t = i as f32 / 24.0;
+                ([from[0] + (to[0] - from[0]) * t, from[1] + (to[1] - from[1]) * t], 1.0)
+            })
+            .collect()
+    };
+    let alpha = |renderer: &Renderer, x: usize, y: usize| {
+        let rgba = pollster::block_on(renderer.read_active_layer_rgba8()).expect("Canvas readback failed").0;
+        rgba[(y * CANVAS_SIZE[0] as usize + x) * 4 + 3]
+    };
+
+    // Opaque paint across, then repeated capped passes down through it
+    let across = stroke_dabs(params, &line([8.0, 30.0], [120.0, 30.0]));
+    for _ in 0..4 {
+        renderer.render_dabs(&across);
+    }
+    let opaque = alpha(&renderer, 64, 30);
+    assert!(opaque > 250, "Uncapped passes should build up to opaque, got alpha {}", opaque);
+    renderer.set_max_opacity(0.5);
+    let down = stroke_dabs(params, &line([64.0, 8.0], [64.0, 88.0]));
+    for _ in 0..4 {
+        renderer.render_dabs(&down);
+    }
+
+    let crossing = alpha(&renderer, 64, 30);
+    assert!(crossing >= opaque, "Capped paint lowered opaque paint from alpha {} to {}", opaque, crossing);
+    let capped = alpha(&renderer, 64, 70);
+    assert!(
+        capped > 100 && capped <= 128 + CHANNEL_TOLERANCE,
+        "Repeated passes at a 0.5 cap should stop at half opacity, got alpha {}",
+        capped
+    );
+}
+
+#[test]
+#[ignore = "needs a GPU adapter"]
+fn test_adapter_info_names_the_backend() {
+    let renderer = headless_renderer();
+    let info = renderer.adapter_info();
+    // Native drivers always name the adapter; only browsers may hide it
+    assert!(!info.name.is_empty(), "Adapter has no name: {:?}", info);
+    assert!(["Vulkan", "Metal", "Dx12", "Gl"].contains(&info.backend.as_str()), "Unexpected backend {:?}", info.backend);
+    assert!(
+        ["Other", "IntegratedGpu", "DiscreteGpu", "VirtualGpu", "Cpu"].contains(&info.device_type.as_str()),
+        "Unexpected device type {:?}",
+        info.device_type
+    );
+    assert_eq!(info.is_fallback, info.device_type == "Cpu");
+}
+
+#[test]
+#[ignore = "needs a GPU adapter"]
+fn test_canvas_snapshot_restores_layers_exactly() {
+    let mut renderer = headless_renderer();
+    let params = BrushParams { size: 18.0, hardness: 0.3, color: [0.7, 0.2, 0.4, 1.0], ..BrushParams::DEFAULT };
+    render(&mut renderer, &stroke_dabs(params, &s_curve()));
+    let read_layer = |renderer: &Renderer| pollster::block_on(renderer.read_active_layer_rgba8()).expect("Canvas readback failed").0;
+    let painted = read_layer(&renderer);
+    renderer.add_layer();
+    renderer.set_layer_opacity(1, 0.5).expect("Layer 1 exists");
+    let snapshot = pollster::block_on(renderer.snapshot_canvas()).expect("Snapshot failed");
+
+    // A rebuilt renderer starts with one blank layer
+    let mut rebuilt = headless_renderer();
+    rebuilt.restore_canvas(&snapshot).expect("Snapshot fits a canvas of the same size");
+    assert_eq!(rebuilt.layer_count(), 2);
+    assert_eq!(rebuilt.layer_opacity(1), Some(0.5));
+    assert_eq!(rebuilt.is_layer_empty(0), Some(false));
+    assert_eq!(rebuilt.is_layer_empty(1), Some(true));
+    assert!(!rebuilt.is_canvas_empty());
+    rebuilt.set_active_layer(0).expect("Layer 0 exists");
+    assert_eq!(read_layer(&rebuilt), painted);
+}
+
+#[test]
+#[ignore = "needs a GPU adapter"]
+fn test_rebuilt_renderer_gets_canvas_size_and_images_back() {
+    let mut renderer = headless_renderer();
+    let app = App::new();
+    renderer.set_canvas_bleed(8);
+    let checker: Vec<u8> = (0..64).map(|i| if (i / 8 + i % 8) % 2 == 0 { 255 } else { 0 }).collect();
+    renderer.set_brush_texture(&checker, 8, 8).expect("Stamp is valid");
+    renderer.set_reference_image(&[200; 4 * 4 * 4], 4, 4).expect("Reference image is valid");
+    renderer.set_reference_opacity(0.25);
+    let dabs = stroke_dabs(BrushParams { size: 24.0, ..BrushParams::DEFAULT }, &s_curve());
+    render(&mut renderer, &dabs);
+
+    let config = app.config(&renderer);
+    let images = renderer.take_uploaded_images();
+    let snapshot = pollster::block_on(renderer.snapshot_canvas()).expect("Snapshot failed");
+
+    let mut rebuilt = headless_renderer();
+    let mut rebuilt_app = App::new();
+    rebuilt_app.apply_config(&config, &mut rebuilt).expect("Config applies");
+    rebuilt.restore_uploaded_images(&images).expect("Images fit the rebuilt device");
+    assert_eq!(rebuilt.canvas_bleed(), 8);
+    assert_eq!(rebuilt.reference_opacity(), 0.25);
+    rebuilt.restore_canvas(&snapshot).expect("Config gave the canvas its old size");
+
+    // The same stroke again must use the same stamp on both
+    assert_eq!(render(&mut rebuilt, &dabs), render(&mut renderer, &dabs));
+}
+
+#[test]
+#[ignore = "needs a GPU adapter"]
+fn test_surface_that_stays_lost_asks_for_a_rebuild() {
+    let mut renderer = headless_renderer();
+    assert!(!renderer.needs_rebuild());
+    renderer.handle_surface_error(wgpu::SurfaceError::Timeout);
+    assert!(!renderer.needs_rebuild(), "A timeout only skips the frame");
+    for _ in 0..3 {
+        renderer.handle_surface_error(wgpu::SurfaceError::Lost);
+    }
+    assert!(renderer.needs_rebuild(), "Reconfiguring didn't bring the surface back");
+}
+
+#[cfg(debug_assertions)]
+#[test]
+#[ignore = "needs a GPU adapter"]
+fn test_frame_batches_brush_work_into_one_submit() {
+    let mut renderer = headless_renderer();
+    let mut app = App::new();
+    app.set_clear_color(PAPER[0], PAPER[1], PAPER[2], PAPER[3]);
+    app.clear_canvas(&mut renderer);
+    app.render(&mut renderer);
+
+    // Two whole strokes in one frame, through the stroke layer: each begins and commits it
+    app.brush_state_mut().params.opacity_mode = OpacityMode::PerStroke;
+    queue_stroke(&mut app, &s_curve());
+    queue_stroke(&mut app, &[([20.0, 80.0], 1.0), ([60.0, 80.0], 1.0), ([100.0, 80.0], 1.0)]);
+    app.render(&mut renderer);
+    assert_eq!(renderer.last_frame_submits(), 1, "Brush work should share the frame's submit");
+
+    let rgba = pollster::block_on(renderer.read_canvas_rgba8()).expect("Canvas readback failed");
+    assert!(rgba.chunks_exact(4).any(|pixel| pixel[..3] != [255, 255, 255]), "The strokes reached the canvas");
+}